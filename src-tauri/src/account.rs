@@ -0,0 +1,171 @@
+//! Xtream account status as the provider reports it in `player_api.php`'s `user_info`:
+//! subscription state, expiry and connection use. A background job checks every Xtream server
+//! a few times a day and emits `account-expiring` once per expiry date when it is less than a
+//! week away.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, Manager, State};
+
+use crate::servers::{ServerConfig, ServerKind, ServerStore};
+use crate::store::JsonStore;
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(6 * 3600);
+/// Lets startup settle before the first check.
+const FIRST_CHECK_DELAY: Duration = Duration::from_secs(60);
+const NOTICE_BEFORE: u64 = 7 * 86_400;
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountInfo {
+    /// False when the provider rejected the credentials.
+    pub authorized: bool,
+    /// `Active`, `Expired`, `Banned`, `Disabled`… as the provider words it.
+    pub status: Option<String>,
+    /// Unix seconds; `None` for accounts that never expire.
+    pub expires_at: Option<u64>,
+    pub created_at: Option<u64>,
+    pub trial: bool,
+    pub max_connections: Option<u32>,
+    pub active_connections: Option<u32>,
+}
+
+impl AccountInfo {
+    pub fn is_expired(&self) -> bool {
+        self.expires_at.is_some_and(|e| e < crate::now_secs())
+    }
+}
+
+#[derive(Deserialize)]
+struct AccountResponse {
+    user_info: Option<UserInfo>,
+}
+
+#[derive(Deserialize)]
+struct UserInfo {
+    #[serde(default)]
+    auth: Option<serde_json::Value>,
+    #[serde(default)]
+    status: Option<String>,
+    #[serde(default)]
+    exp_date: Option<serde_json::Value>,
+    #[serde(default)]
+    created_at: Option<serde_json::Value>,
+    #[serde(default)]
+    is_trial: Option<serde_json::Value>,
+    #[serde(default)]
+    active_cons: Option<serde_json::Value>,
+    #[serde(default)]
+    max_connections: Option<serde_json::Value>,
+}
+
+fn number(value: &Option<serde_json::Value>) -> Option<u64> {
+    crate::catalog::text(value.as_ref()?)?.parse().ok()
+}
+
+/// Expiry dates already notified, by server id.
+pub type AccountNoticeStore = JsonStore<BTreeMap<String, u64>>;
+
+pub fn open(app: &tauri::AppHandle) -> AccountNoticeStore {
+    JsonStore::open(app, "account-notices.json")
+}
+
+/// Asks the Xtream server for its account status.
+pub(crate) async fn fetch(server: &ServerConfig) -> Result<AccountInfo, String> {
+    if server.kind != ServerKind::Xtream {
+        return Err(format!("{} is not an Xtream server", server.name));
+    }
+    let client = crate::http::client(Some(server))?;
+    let req = client
+        .get(format!("{}/player_api.php", server.base_url()))
+        .query(&[
+            ("username", server.username.as_str()),
+            ("password", server.password.as_str()),
+        ]);
+    let resp = crate::http::send(req)
+        .await
+        .map_err(|e| format!("Failed to reach {}: {}", server.name, e))?;
+    if matches!(resp.status().as_u16(), 401 | 403) {
+        return Ok(AccountInfo::default());
+    }
+    let account: AccountResponse = resp
+        .json()
+        .await
+        .map_err(|e| format!("Unexpected account response: {}", e))?;
+    let info = account
+        .user_info
+        .ok_or_else(|| format!("{} didn't report the account", server.name))?;
+    Ok(AccountInfo {
+        authorized: number(&info.auth) != Some(0),
+        status: info.status.filter(|s| !s.is_empty()),
+        expires_at: number(&info.exp_date).filter(|e| *e > 0),
+        created_at: number(&info.created_at).filter(|c| *c > 0),
+        trial: number(&info.is_trial) == Some(1),
+        max_connections: number(&info.max_connections).map(|n| n as u32),
+        active_connections: number(&info.active_cons).map(|n| n as u32),
+    })
+}
+
+/// Emits `account-expiring` for the server if its expiry is within a week and hasn't been
+/// notified yet.
+async fn check(app: &tauri::AppHandle, server: &ServerConfig) {
+    let info = match fetch(server).await {
+        Ok(info) => info,
+        Err(e) => {
+            tracing::debug!("Account check of {} failed: {}", server.name, e);
+            return;
+        }
+    };
+    let now = crate::now_secs();
+    let Some(expires) = info
+        .expires_at
+        .filter(|e| *e > now && *e - now <= NOTICE_BEFORE)
+    else {
+        return;
+    };
+    let notices = app.state::<AccountNoticeStore>();
+    if notices.read(|n| n.get(&server.id) == Some(&expires)) {
+        return;
+    }
+    let _ = notices.update(|n| n.insert(server.id.clone(), expires));
+    tracing::info!(server = %server.name, expires, "Subscription expires soon");
+    let _ = app.emit(
+        "account-expiring",
+        serde_json::json!({
+            "serverId": server.id,
+            "serverName": server.name,
+            "expiresAt": expires,
+            "daysLeft": (expires - now) / 86_400,
+        }),
+    );
+}
+
+/// Checks every Xtream server's expiry for the life of the app.
+pub fn start(app: &tauri::AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(FIRST_CHECK_DELAY).await;
+        loop {
+            let servers = app.state::<ServerStore>().read(|servers| servers.clone());
+            let ids: Vec<&str> = servers.iter().map(|s| s.id.as_str()).collect();
+            let _ = app
+                .state::<AccountNoticeStore>()
+                .update(|n| n.retain(|id, _| ids.contains(&id.as_str())));
+            for server in servers.iter().filter(|s| s.kind == ServerKind::Xtream) {
+                check(&app, server).await;
+            }
+            tokio::time::sleep(CHECK_INTERVAL).await;
+        }
+    });
+}
+
+#[tauri::command]
+pub async fn get_account_info(
+    store: State<'_, ServerStore>,
+    server_id: String,
+) -> Result<AccountInfo, String> {
+    let server = crate::servers::get(&store, &server_id)?;
+    fetch(&server).await
+}
@@ -0,0 +1,146 @@
+//! Audio output device selection, so TV audio can go to e.g. HDMI while system sounds stay on
+//! the speakers. Devices are named the way mpv names them (`wasapi/{guid}`, `pulse/<sink>`,
+//! `alsa/<pcm>`, `coreaudio/<uid>`); a profile's device is applied when mpv or VLC starts and
+//! a single mpv window can be switched while playing. The webview engine always plays to the
+//! system default.
+
+use std::process::Command;
+
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::mpv::MpvState;
+use crate::settings::SettingsStore;
+
+/// mpv's name for the system default output.
+const AUTO: &str = "auto";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AudioDevice {
+    /// Device name as passed to `--audio-device`.
+    #[serde(alias = "name")]
+    pub id: String,
+    #[serde(default)]
+    pub description: String,
+}
+
+/// Devices from embedded libmpv, else from the `mpv` binary when it is installed.
+fn devices() -> Result<Vec<AudioDevice>, String> {
+    match crate::mpv::audio_device_list() {
+        Ok(json) => serde_json::from_str(&json)
+            .map_err(|e| format!("Unexpected audio device list from mpv: {}", e)),
+        Err(embedded) => {
+            let Ok(mpv) = which::which("mpv") else {
+                return Err(embedded);
+            };
+            let output = Command::new(mpv)
+                .arg("--audio-device=help")
+                .output()
+                .map_err(|e| format!("Failed to run mpv: {}", e))?;
+            Ok(parse_help(&String::from_utf8_lossy(&output.stdout)))
+        }
+    }
+}
+
+/// Parses `mpv --audio-device=help`, one `  'id' (description)` line per device.
+fn parse_help(text: &str) -> Vec<AudioDevice> {
+    text.lines()
+        .filter_map(|line| {
+            let rest = line.trim().strip_prefix('\'')?;
+            let (id, rest) = rest.split_once('\'')?;
+            let description = rest.trim().trim_start_matches('(').trim_end_matches(')');
+            Some(AudioDevice {
+                id: id.to_string(),
+                description: description.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// The profile's output device; `None` for the system default.
+pub fn device_for(settings: &SettingsStore, profile_id: &str) -> Option<String> {
+    settings.read(|s| s.audio_devices.get(profile_id).cloned())
+}
+
+/// Points a VLC launch at `device`; false when VLC has no way to address that output.
+pub fn select_for_vlc(command: &mut Command, device: &str) -> bool {
+    let Some((output, name)) = device.split_once('/') else {
+        return false;
+    };
+    match output {
+        "wasapi" => {
+            command.arg("--aout=mmdevice");
+            command.arg(format!("--mmdevice-audio-device={}", name));
+        }
+        "alsa" => {
+            command.arg("--aout=alsa");
+            command.arg(format!("--alsa-audio-device={}", name));
+        }
+        // VLC's PulseAudio output has no device option but honours the sink variable
+        "pulse" | "pipewire" => {
+            command.arg("--aout=pulse");
+            command.env("PULSE_SINK", name);
+        }
+        _ => return false,
+    }
+    true
+}
+
+/// Output devices available to the mpv and VLC engines, the system default first.
+#[tauri::command]
+pub async fn list_audio_devices() -> Result<Vec<AudioDevice>, String> {
+    let mut devices = tauri::async_runtime::spawn_blocking(devices)
+        .await
+        .map_err(|e| e.to_string())??;
+    if !devices.iter().any(|d| d.id == AUTO) {
+        devices.insert(
+            0,
+            AudioDevice {
+                id: AUTO.to_string(),
+                description: "System default".to_string(),
+            },
+        );
+    }
+    Ok(devices)
+}
+
+#[tauri::command]
+pub fn get_audio_device(
+    settings: State<'_, SettingsStore>,
+    profile_id: Option<String>,
+) -> Option<String> {
+    device_for(&settings, &crate::settings::profile_id(profile_id))
+}
+
+/// Sets the profile's output device for players opened from now on; `None` or `auto` goes
+/// back to the system default.
+#[tauri::command]
+pub fn set_audio_device(
+    settings: State<'_, SettingsStore>,
+    profile_id: Option<String>,
+    device: Option<String>,
+) -> Result<(), String> {
+    let profile = crate::settings::profile_id(profile_id);
+    settings.update(|s| match device.filter(|d| !d.is_empty() && d != AUTO) {
+        Some(device) => {
+            s.audio_devices.insert(profile, device);
+        }
+        None => {
+            s.audio_devices.remove(&profile);
+        }
+    })
+}
+
+/// Moves one playing mpv window to another output device without changing the profile's.
+#[tauri::command]
+pub fn set_window_audio_device(
+    mpv: State<'_, MpvState>,
+    window_label: String,
+    device: Option<String>,
+) -> Result<(), String> {
+    if !mpv.has(&window_label) {
+        return Err("Only windows played by mpv can switch audio output".to_string());
+    }
+    mpv.set_audio_device(&window_label, device.as_deref().unwrap_or(AUTO))
+}
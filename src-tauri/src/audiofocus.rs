@@ -0,0 +1,119 @@
+//! Which video window is audible. Open video windows are tracked in the order they opened;
+//! with the `MuteOthers` policy a newly opened window takes the audio focus and the others are
+//! muted, and closing the focused window hands it to the most recent remaining one.
+//! `set_audio_focus` switches it by hand under either policy.
+
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, Manager};
+
+use crate::remote::PlayerCommand;
+use crate::settings::SettingsStore;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AudioFocusPolicy {
+    /// Every window plays its own audio.
+    #[default]
+    Mix,
+    /// Only the newest window (or the one picked with `set_audio_focus`) is audible.
+    MuteOthers,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AudioFocus {
+    /// Open video windows, oldest first.
+    pub windows: Vec<String>,
+    /// `None` until a window takes the focus.
+    pub focused: Option<String>,
+}
+
+static FOCUS: Mutex<AudioFocus> = Mutex::new(AudioFocus {
+    windows: Vec::new(),
+    focused: None,
+});
+
+fn focus() -> std::sync::MutexGuard<'static, AudioFocus> {
+    FOCUS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn policy(app: &tauri::AppHandle) -> AudioFocusPolicy {
+    app.state::<SettingsStore>().read(|s| s.audio_focus)
+}
+
+fn set_muted(app: &tauri::AppHandle, label: &str, muted: bool) {
+    if let Err(e) = crate::remote::send_player_command(app, label, &PlayerCommand::Mute { muted }) {
+        tracing::debug!(
+            "Could not {} {}: {}",
+            if muted { "mute" } else { "unmute" },
+            label,
+            e
+        );
+    }
+}
+
+/// Gives `label` the focus: every other window is muted and `label` unmuted, unless it is new
+/// and has nothing to unmute yet.
+fn give(app: &tauri::AppHandle, label: &str, unmute: bool) {
+    let others: Vec<String> = {
+        let mut focus = focus();
+        focus.focused = Some(label.to_string());
+        focus
+            .windows
+            .iter()
+            .filter(|w| *w != label)
+            .cloned()
+            .collect()
+    };
+    for other in &others {
+        set_muted(app, other, true);
+    }
+    if unmute {
+        set_muted(app, label, false);
+    }
+    let _ = app.emit("audio-focus-changed", serde_json::json!({ "label": label }));
+}
+
+/// Tracks a newly built video window, muting the others under `MuteOthers`.
+pub fn opened(app: &tauri::AppHandle, label: &str) {
+    focus().windows.push(label.to_string());
+    if policy(app) == AudioFocusPolicy::MuteOthers {
+        give(app, label, false);
+    }
+}
+
+/// Forgets a closed window; under `MuteOthers` the focus moves to the newest remaining one.
+pub fn release(app: &tauri::AppHandle, label: &str) {
+    let next = {
+        let mut focus = focus();
+        let Some(at) = focus.windows.iter().position(|w| w == label) else {
+            return;
+        };
+        focus.windows.remove(at);
+        if focus.focused.as_deref() != Some(label) {
+            return;
+        }
+        focus.focused = None;
+        focus.windows.last().cloned()
+    };
+    if let Some(next) = next.filter(|_| policy(app) == AudioFocusPolicy::MuteOthers) {
+        give(app, &next, true);
+    }
+}
+
+#[tauri::command]
+pub fn get_audio_focus() -> AudioFocus {
+    focus().clone()
+}
+
+/// Makes video window `label` the only audible one.
+#[tauri::command]
+pub fn set_audio_focus(app: tauri::AppHandle, label: String) -> Result<(), String> {
+    if !focus().windows.contains(&label) {
+        return Err(format!("No video window {}", label));
+    }
+    give(&app, &label, true);
+    Ok(())
+}
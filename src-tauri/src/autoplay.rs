@@ -0,0 +1,315 @@
+//! Next-episode autoplay. A video window playing a series episode is registered with
+//! `track_series_playback`, which resolves the following episode's stream URL straight away.
+//! When the current episode's progress comes within the countdown of its end (or reaches the
+//! credits) the window gets `next-episode-ready` with the prefetched stream and counts down;
+//! `play_next_episode` then swaps it into the same window.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use serde::Serialize;
+use serde_json::Value;
+use tauri::{Emitter, Manager, State};
+
+use crate::catalog::text;
+use crate::history::{WatchKind, WatchStart};
+use crate::proxy::ProxyState;
+use crate::remote::PlayerCommand;
+use crate::servers::{ServerConfig, ServerKind, ServerStore};
+
+/// How long before the end the next episode is announced, without known credits.
+const COUNTDOWN_SECS: f64 = 20.0;
+
+#[derive(Debug, Clone)]
+struct Episode {
+    id: String,
+    season: Option<u32>,
+    number: Option<u32>,
+    title: String,
+    /// Xtream container extension.
+    extension: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NextEpisode {
+    pub episode_id: String,
+    pub title: String,
+    pub season: Option<u32>,
+    pub episode: Option<u32>,
+    pub stream_url: String,
+    pub content_key: String,
+    /// Seconds until the current episode ends when announced.
+    pub countdown_secs: f64,
+}
+
+struct SeriesPlayback {
+    server_id: String,
+    series_name: String,
+    episodes: Vec<Episode>,
+    current: usize,
+    next: Option<NextEpisode>,
+    announced: bool,
+}
+
+impl SeriesPlayback {
+    fn key(&self, index: usize) -> String {
+        format!("{}:series:{}", self.server_id, self.episodes[index].id)
+    }
+}
+
+static PLAYBACK: Mutex<BTreeMap<String, SeriesPlayback>> = Mutex::new(BTreeMap::new());
+
+fn playback() -> std::sync::MutexGuard<'static, BTreeMap<String, SeriesPlayback>> {
+    PLAYBACK.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn number(value: &Value) -> Option<u32> {
+    text(value)?.parse().ok()
+}
+
+/// Series name and episodes in airing order.
+async fn fetch_episodes(
+    app: &tauri::AppHandle,
+    server: &ServerConfig,
+    series_id: &str,
+) -> Result<(String, Vec<Episode>), String> {
+    let (name, mut episodes): (String, Vec<Episode>) = match server.kind {
+        ServerKind::Xtream => {
+            let info: Value =
+                crate::catalog::xtream_call(server, "get_series_info", &[("series_id", series_id)])
+                    .await?;
+            let episodes = info["episodes"]
+                .as_object()
+                .into_iter()
+                .flat_map(|seasons| seasons.iter())
+                .flat_map(|(season, list)| {
+                    let season = season.parse().ok();
+                    list.as_array().into_iter().flatten().filter_map(move |e| {
+                        Some(Episode {
+                            id: text(&e["id"])?,
+                            season: number(&e["season"]).or(season),
+                            number: number(&e["episode_num"]),
+                            title: text(&e["title"]).unwrap_or_default(),
+                            extension: text(&e["container_extension"]),
+                        })
+                    })
+                })
+                .collect();
+            (text(&info["info"]["name"]).unwrap_or_default(), episodes)
+        }
+        ServerKind::Emby => {
+            let items =
+                crate::emby::episodes(&app.state::<ServerStore>(), &server.id, series_id).await?;
+            let name = items
+                .iter()
+                .find_map(|i| i.series_name.clone())
+                .unwrap_or_default();
+            let episodes = items
+                .into_iter()
+                .map(|i| Episode {
+                    id: i.id,
+                    season: i.season_number,
+                    number: i.episode_number,
+                    title: i.name,
+                    extension: None,
+                })
+                .collect();
+            (name, episodes)
+        }
+        _ => return Err(format!("{} has no series", server.name)),
+    };
+    // Specials (season 0) go last rather than interrupting the run
+    episodes.sort_by_key(|e| (e.season.filter(|s| *s > 0).unwrap_or(u32::MAX), e.number));
+    Ok((name, episodes))
+}
+
+async fn stream_url(
+    app: &tauri::AppHandle,
+    server: &ServerConfig,
+    episode: &Episode,
+) -> Result<String, String> {
+    match server.kind {
+        ServerKind::Emby => {
+            crate::emby::stream_url(&app.state::<ServerStore>(), &server.id, &episode.id).await
+        }
+        _ => Ok(format!(
+            "{}/series/{}/{}/{}.{}",
+            server.base_url(),
+            urlencoding::encode(&server.username),
+            urlencoding::encode(&server.password),
+            episode.id,
+            episode.extension.as_deref().unwrap_or("mp4")
+        )),
+    }
+}
+
+/// Resolves the episode after the window's current one, keeping it unless the window moved on
+/// meanwhile. Returns it, `None` at the end of the series.
+async fn prefetch(app: &tauri::AppHandle, label: &str) -> Result<Option<NextEpisode>, String> {
+    let target = {
+        let playback = playback();
+        let Some(p) = playback.get(label) else {
+            return Ok(None);
+        };
+        if p.next.is_some() {
+            return Ok(p.next.clone());
+        }
+        let index = p.current + 1;
+        let Some(episode) = p.episodes.get(index).cloned() else {
+            return Ok(None);
+        };
+        (p.server_id.clone(), p.current, p.key(index), episode)
+    };
+    let (server_id, from, content_key, episode) = target;
+    let server = crate::servers::get(&app.state::<ServerStore>(), &server_id)?;
+    let next = NextEpisode {
+        stream_url: stream_url(app, &server, &episode).await?,
+        episode_id: episode.id,
+        title: episode.title,
+        season: episode.season,
+        episode: episode.number,
+        content_key,
+        countdown_secs: COUNTDOWN_SECS,
+    };
+    if let Some(p) = playback().get_mut(label).filter(|p| p.current == from) {
+        p.next = Some(next.clone());
+    }
+    Ok(Some(next))
+}
+
+fn spawn_prefetch(app: &tauri::AppHandle, label: String) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = prefetch(&app, &label).await {
+            tracing::warn!("Could not resolve the next episode: {}", e);
+        }
+    });
+}
+
+/// Called on every progress update; announces the next episode once the current one is about
+/// to end.
+pub fn on_progress(
+    app: &tauri::AppHandle,
+    content_key: &str,
+    position_secs: f64,
+    duration_secs: Option<f64>,
+    credits_start_secs: Option<f64>,
+) {
+    let Some(duration) = duration_secs else {
+        return;
+    };
+    let due: Vec<(String, NextEpisode)> = playback()
+        .iter_mut()
+        .filter(|(_, p)| !p.announced && p.key(p.current) == content_key)
+        .filter_map(|(label, p)| {
+            let at = credits_start_secs.unwrap_or(duration - COUNTDOWN_SECS);
+            if position_secs < at {
+                return None;
+            }
+            let mut next = p.next.clone()?;
+            next.countdown_secs = (duration - position_secs).max(0.0);
+            p.announced = true;
+            Some((label.clone(), next))
+        })
+        .collect();
+    for (label, next) in due {
+        let _ = app.emit_to(label.as_str(), "next-episode-ready", next);
+    }
+}
+
+pub fn release(label: &str) {
+    playback().remove(label);
+}
+
+/// Registers a window as playing `episode_id` of a series so the next episode is prefetched
+/// and announced.
+#[tauri::command]
+pub async fn track_series_playback(
+    app: tauri::AppHandle,
+    store: State<'_, ServerStore>,
+    window_label: String,
+    server_id: String,
+    series_id: String,
+    episode_id: String,
+) -> Result<(), String> {
+    let server = crate::servers::get(&store, &server_id)?;
+    let (series_name, episodes) = fetch_episodes(&app, &server, &series_id).await?;
+    let current = episodes
+        .iter()
+        .position(|e| e.id == episode_id)
+        .ok_or_else(|| "Episode not found in series".to_string())?;
+    playback().insert(
+        window_label.clone(),
+        SeriesPlayback {
+            server_id,
+            series_name,
+            episodes,
+            current,
+            next: None,
+            announced: false,
+        },
+    );
+    spawn_prefetch(&app, window_label);
+    Ok(())
+}
+
+/// Swaps the window's stream for the next episode without opening a new window.
+#[tauri::command]
+pub async fn play_next_episode(
+    app: tauri::AppHandle,
+    proxy: State<'_, ProxyState>,
+    window_label: String,
+) -> Result<NextEpisode, String> {
+    let next = prefetch(&app, &window_label)
+        .await?
+        .ok_or_else(|| "This is the last episode".to_string())?;
+    let (server_id, title) = {
+        let mut playback = playback();
+        let p = playback
+            .get_mut(&window_label)
+            .ok_or_else(|| "Window is not playing a series".to_string())?;
+        p.current += 1;
+        p.next = None;
+        p.announced = false;
+        let title = if p.series_name.is_empty() {
+            next.title.clone()
+        } else {
+            format!("{} – {}", p.series_name, next.title)
+        };
+        (p.server_id.clone(), title)
+    };
+    let server = crate::servers::get(&app.state::<ServerStore>(), &server_id)?;
+
+    let resolved = crate::resolve::resolve(Some(&server), &next.stream_url).await;
+    let mut url = resolved.url.clone();
+    if resolved.needs_relay(Some(&server)) {
+        proxy.stop_owned_by(&window_label);
+        let relay = proxy.relay(&resolved, Some(&server))?;
+        proxy.set_owner(&relay.id, &window_label);
+        url = relay.url;
+    }
+    let command = PlayerCommand::Load {
+        url,
+        content_key: Some(next.content_key.clone()),
+    };
+    crate::remote::send_player_command(&app, &window_label, &command)?;
+    if let Some(window) = app.get_webview_window(&window_label) {
+        let _ = window.set_title(&title);
+    }
+    crate::history::start_watching(
+        app.clone(),
+        window_label.clone(),
+        WatchStart {
+            server_id,
+            kind: WatchKind::Episode,
+            title,
+            content_key: Some(next.content_key.clone()),
+            channel_id: None,
+            channel_name: None,
+            genre: None,
+        },
+    );
+    spawn_prefetch(&app, window_label);
+    Ok(next)
+}
@@ -0,0 +1,271 @@
+//! Backups of the app's configuration: servers with their credentials, settings, and the
+//! per-channel favorites, edits and volumes, the watch-later list and webhooks, as one JSON
+//! document. Given a passphrase, the document is sealed with AES-256-GCM under a PBKDF2-SHA256
+//! key instead, since it carries provider passwords.
+//!
+//! Encrypted layout: `TVXB`, version byte, salt (16 bytes), PBKDF2 rounds (u32 LE), nonce
+//! (12 bytes), then the sealed JSON and its tag. The header is authenticated with the data, so
+//! a changed round count fails like a wrong passphrase.
+
+use std::collections::BTreeMap;
+use std::num::NonZeroU32;
+
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::pbkdf2;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, Manager};
+
+use crate::edits::{ChannelEditStore, ServerEdits};
+use crate::favorites::FavoriteStore;
+use crate::servers::{self, ServerConfig, ServerStore};
+use crate::settings::{AppSettings, SettingsStore};
+use crate::volume::VolumeStore;
+use crate::watchlater::{WatchLaterItem, WatchLaterStore};
+use crate::webhooks::{Webhook, WebhookStore};
+
+const MAGIC: &[u8; 4] = b"TVXB";
+const VERSION: u8 = 1;
+const SALT_LEN: usize = 16;
+const HEADER_LEN: usize = MAGIC.len() + 1 + SALT_LEN + 4 + NONCE_LEN;
+const ROUNDS: u32 = 600_000;
+/// Highest round count accepted on import, so a damaged header can't stall it for hours.
+const MAX_ROUNDS: u32 = 10_000_000;
+const MIN_PASSPHRASE_LEN: usize = 8;
+
+/// Everything a backup restores. Sections that are `None` were not in the backup and are
+/// left alone on import.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Backup {
+    /// Unix seconds when the backup was written.
+    pub created_at: u64,
+    pub servers: Vec<ServerConfig>,
+    pub settings: Option<AppSettings>,
+    pub favorites: Option<BTreeMap<String, Vec<String>>>,
+    pub channel_edits: Option<BTreeMap<String, ServerEdits>>,
+    pub channel_volume: Option<BTreeMap<String, f64>>,
+    pub watch_later: Option<Vec<WatchLaterItem>>,
+    pub webhooks: Option<Vec<Webhook>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupInfo {
+    /// True when importing needs the passphrase the backup was exported with.
+    pub encrypted: bool,
+}
+
+fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+fn key(passphrase: &str, salt: &[u8], rounds: NonZeroU32) -> LessSafeKey {
+    let mut key = [0u8; 32];
+    pbkdf2::derive(
+        pbkdf2::PBKDF2_HMAC_SHA256,
+        rounds,
+        salt,
+        passphrase.as_bytes(),
+        &mut key,
+    );
+    LessSafeKey::new(UnboundKey::new(&AES_256_GCM, &key).expect("AES-256 keys are 32 bytes"))
+}
+
+fn seal(plain: &[u8], passphrase: &str, rounds: u32) -> Result<Vec<u8>, String> {
+    let rounds = NonZeroU32::new(rounds).ok_or("PBKDF2 needs at least one round")?;
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    let rng = SystemRandom::new();
+    rng.fill(&mut salt)
+        .and_then(|_| rng.fill(&mut nonce))
+        .map_err(|_| "No secure random source for the backup key".to_string())?;
+    let mut sealed = Vec::with_capacity(HEADER_LEN + plain.len() + AES_256_GCM.tag_len());
+    sealed.extend_from_slice(MAGIC);
+    sealed.push(VERSION);
+    sealed.extend_from_slice(&salt);
+    sealed.extend_from_slice(&rounds.get().to_le_bytes());
+    sealed.extend_from_slice(&nonce);
+    let mut data = plain.to_vec();
+    key(passphrase, &salt, rounds)
+        .seal_in_place_append_tag(
+            Nonce::assume_unique_for_key(nonce),
+            Aad::from(&sealed[..HEADER_LEN]),
+            &mut data,
+        )
+        .map_err(|_| "Failed to encrypt the backup".to_string())?;
+    sealed.extend_from_slice(&data);
+    Ok(sealed)
+}
+
+fn unseal(sealed: &[u8], passphrase: &str) -> Result<Vec<u8>, String> {
+    if sealed.len() < HEADER_LEN + AES_256_GCM.tag_len() {
+        return Err("The backup is truncated".to_string());
+    }
+    let (header, data) = sealed.split_at(HEADER_LEN);
+    if header[MAGIC.len()] != VERSION {
+        return Err(format!(
+            "Unsupported backup version {}",
+            header[MAGIC.len()]
+        ));
+    }
+    let salt = &header[MAGIC.len() + 1..][..SALT_LEN];
+    let rounds = u32::from_le_bytes(
+        header[MAGIC.len() + 1 + SALT_LEN..][..4]
+            .try_into()
+            .unwrap(),
+    );
+    let rounds = NonZeroU32::new(rounds)
+        .filter(|r| r.get() <= MAX_ROUNDS)
+        .ok_or("The backup is damaged")?;
+    let nonce = Nonce::try_assume_unique_for_key(&header[HEADER_LEN - NONCE_LEN..])
+        .map_err(|_| "The backup is damaged".to_string())?;
+    let mut data = data.to_vec();
+    let plain = key(passphrase, salt, rounds)
+        .open_in_place(nonce, Aad::from(header), &mut data)
+        .map_err(|_| "Wrong passphrase, or the backup is damaged".to_string())?;
+    Ok(plain.to_vec())
+}
+
+/// Turns a backup file's contents back into a `Backup`, decrypting with `passphrase` when the
+/// file is encrypted.
+fn decode(data: &[u8], passphrase: Option<&str>) -> Result<Backup, String> {
+    let plain = if is_encrypted(data) {
+        let passphrase = passphrase
+            .filter(|p| !p.is_empty())
+            .ok_or("This backup is encrypted; enter its passphrase")?;
+        unseal(data, passphrase)?
+    } else {
+        data.to_vec()
+    };
+    serde_json::from_slice(&plain).map_err(|e| format!("Not a TvX backup: {}", e))
+}
+
+fn collect(app: &tauri::AppHandle) -> Backup {
+    Backup {
+        created_at: crate::now_secs(),
+        servers: app.state::<ServerStore>().read(|s| s.clone()),
+        settings: Some(app.state::<SettingsStore>().read(|s| s.clone())),
+        favorites: Some(app.state::<FavoriteStore>().read(|f| f.clone())),
+        channel_edits: Some(app.state::<ChannelEditStore>().read(|e| e.clone())),
+        channel_volume: Some(app.state::<VolumeStore>().read(|v| v.clone())),
+        watch_later: Some(app.state::<WatchLaterStore>().read(|w| w.clone())),
+        webhooks: Some(app.state::<WebhookStore>().read(|w| w.clone())),
+    }
+}
+
+/// Writes a backup to `path`, encrypted when a passphrase is given. Returns the path written.
+#[tauri::command]
+pub fn export_backup(
+    app: tauri::AppHandle,
+    path: String,
+    passphrase: Option<String>,
+) -> Result<String, String> {
+    let passphrase = passphrase.filter(|p| !p.is_empty());
+    if passphrase
+        .as_ref()
+        .is_some_and(|p| p.chars().count() < MIN_PASSPHRASE_LEN)
+    {
+        return Err(format!(
+            "The passphrase needs at least {} characters",
+            MIN_PASSPHRASE_LEN
+        ));
+    }
+    let json = serde_json::to_vec_pretty(&collect(&app)).map_err(|e| e.to_string())?;
+    let contents = match passphrase.as_deref() {
+        Some(passphrase) => seal(&json, passphrase, ROUNDS)?,
+        None => json,
+    };
+    std::fs::write(&path, contents).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    tracing::info!(
+        encrypted = passphrase.is_some(),
+        "Exported a backup to {}",
+        path
+    );
+    Ok(path)
+}
+
+/// Tells whether the backup at `path` needs a passphrase, so it can be asked for up front.
+#[tauri::command]
+pub fn inspect_backup(path: String) -> Result<BackupInfo, String> {
+    let data = std::fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    Ok(BackupInfo {
+        encrypted: is_encrypted(&data),
+    })
+}
+
+/// Restores the backup at `path`. Servers are merged by id; every other section in the backup
+/// replaces what is saved.
+#[tauri::command]
+pub fn import_backup(
+    app: tauri::AppHandle,
+    path: String,
+    passphrase: Option<String>,
+) -> Result<(), String> {
+    let data = std::fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let backup = decode(&data, passphrase.as_deref())?;
+    if let Some(settings) = backup.settings {
+        crate::settings::save_settings(app.clone(), app.state(), settings)?;
+    }
+    let store = app.state::<ServerStore>();
+    for server in backup.servers {
+        servers::upsert(&store, server)?;
+    }
+    if let Some(favorites) = backup.favorites {
+        app.state::<FavoriteStore>().update(|f| *f = favorites)?;
+    }
+    if let Some(edits) = backup.channel_edits {
+        app.state::<ChannelEditStore>().update(|e| *e = edits)?;
+    }
+    if let Some(volume) = backup.channel_volume {
+        app.state::<VolumeStore>().update(|v| *v = volume)?;
+    }
+    if let Some(items) = backup.watch_later {
+        app.state::<WatchLaterStore>().update(|w| *w = items)?;
+    }
+    if let Some(hooks) = backup.webhooks {
+        app.state::<WebhookStore>().update(|w| *w = hooks)?;
+    }
+    tracing::info!("Imported a backup from {}", path);
+    let _ = app.emit("backup-imported", ());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAIN: &[u8] = br#"{"createdAt":1,"servers":[]}"#;
+
+    #[test]
+    fn sealed_backups_open_with_their_passphrase() {
+        let sealed = seal(PLAIN, "correct horse", 10).unwrap();
+        assert!(is_encrypted(&sealed));
+        assert_eq!(unseal(&sealed, "correct horse").unwrap(), PLAIN);
+        assert!(decode(&sealed, Some("correct horse")).is_ok());
+    }
+
+    #[test]
+    fn wrong_or_missing_passphrases_are_refused() {
+        let sealed = seal(PLAIN, "correct horse", 10).unwrap();
+        assert!(unseal(&sealed, "battery staple").is_err());
+        let missing = decode(&sealed, None).unwrap_err();
+        assert!(missing.contains("passphrase"), "{}", missing);
+    }
+
+    #[test]
+    fn a_changed_header_fails_to_open() {
+        let mut sealed = seal(PLAIN, "correct horse", 10).unwrap();
+        sealed[MAGIC.len() + 1 + SALT_LEN] ^= 1;
+        assert!(unseal(&sealed, "correct horse").is_err());
+        assert!(unseal(&sealed[..HEADER_LEN], "correct horse").is_err());
+    }
+
+    #[test]
+    fn plain_backups_need_no_passphrase() {
+        assert!(!is_encrypted(PLAIN));
+        let backup = decode(PLAIN, None).unwrap();
+        assert_eq!(backup.created_at, 1);
+        assert!(backup.settings.is_none());
+    }
+}
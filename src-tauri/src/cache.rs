@@ -0,0 +1,224 @@
+//! On-disk caches under the app cache dir, one subdirectory per kind, each with a size limit
+//! from Settings. Files are evicted least recently used first: readers `touch` a file when
+//! they use it, and eviction removes the oldest modification times until the cache fits.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+use tauri::{Manager, State};
+
+use crate::settings::SettingsStore;
+
+const ENFORCE_INTERVAL: Duration = Duration::from_secs(600);
+const MB: u64 = 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheKind {
+    /// Offline copies of channel lists and VOD libraries, and channel index snapshots.
+    Catalogs,
+    /// Offline copies of guide data.
+    Epg,
+    Artwork,
+    /// Preview thumbnails for channels and recordings.
+    Thumbnails,
+    /// Downloaded and extracted subtitles.
+    Subtitles,
+}
+
+impl CacheKind {
+    pub const ALL: [CacheKind; 5] = [
+        CacheKind::Catalogs,
+        CacheKind::Epg,
+        CacheKind::Artwork,
+        CacheKind::Thumbnails,
+        CacheKind::Subtitles,
+    ];
+
+    fn dir_name(self) -> &'static str {
+        match self {
+            CacheKind::Catalogs => "catalogs",
+            CacheKind::Epg => "epg",
+            CacheKind::Artwork => "artwork",
+            CacheKind::Thumbnails => "thumbnails",
+            CacheKind::Subtitles => "subtitles",
+        }
+    }
+}
+
+/// Per-cache size limits in megabytes; 0 means unlimited.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CacheLimits {
+    pub catalogs_mb: u64,
+    pub epg_mb: u64,
+    pub artwork_mb: u64,
+    pub thumbnails_mb: u64,
+    pub subtitles_mb: u64,
+}
+
+impl Default for CacheLimits {
+    fn default() -> Self {
+        Self {
+            catalogs_mb: 500,
+            epg_mb: 200,
+            artwork_mb: 500,
+            thumbnails_mb: 200,
+            subtitles_mb: 100,
+        }
+    }
+}
+
+impl CacheLimits {
+    /// Limit in bytes, `None` when unlimited.
+    fn bytes(&self, kind: CacheKind) -> Option<u64> {
+        let mb = match kind {
+            CacheKind::Catalogs => self.catalogs_mb,
+            CacheKind::Epg => self.epg_mb,
+            CacheKind::Artwork => self.artwork_mb,
+            CacheKind::Thumbnails => self.thumbnails_mb,
+            CacheKind::Subtitles => self.subtitles_mb,
+        };
+        (mb > 0).then(|| mb * MB)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheStats {
+    pub kind: CacheKind,
+    pub bytes: u64,
+    pub files: usize,
+    pub limit_bytes: Option<u64>,
+}
+
+/// The cache directory for `kind` (not created).
+pub fn dir(app: &tauri::AppHandle, kind: CacheKind) -> Result<PathBuf, String> {
+    Ok(app
+        .path()
+        .app_cache_dir()
+        .map_err(|e| e.to_string())?
+        .join(kind.dir_name()))
+}
+
+/// Marks a cached file as used now, for eviction order.
+pub fn touch(path: &Path) {
+    if let Ok(file) = fs::File::options().append(true).open(path) {
+        let _ = file.set_modified(SystemTime::now());
+    }
+}
+
+struct CachedFile {
+    path: PathBuf,
+    size: u64,
+    used: SystemTime,
+}
+
+fn walk(dir: &Path, files: &mut Vec<CachedFile>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let Ok(meta) = entry.metadata() else {
+            continue;
+        };
+        if meta.is_dir() {
+            walk(&entry.path(), files);
+        } else {
+            files.push(CachedFile {
+                path: entry.path(),
+                size: meta.len(),
+                used: meta.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+            });
+        }
+    }
+}
+
+fn files(app: &tauri::AppHandle, kind: CacheKind) -> Vec<CachedFile> {
+    let mut files = Vec::new();
+    if let Ok(dir) = dir(app, kind) {
+        walk(&dir, &mut files);
+    }
+    files
+}
+
+/// Evicts least recently used files until the cache fits its limit. Returns bytes freed.
+fn evict(app: &tauri::AppHandle, kind: CacheKind, limit: u64) -> u64 {
+    let mut files = files(app, kind);
+    let mut total: u64 = files.iter().map(|f| f.size).sum();
+    if total <= limit {
+        return 0;
+    }
+    files.sort_by_key(|f| f.used);
+    let mut freed = 0;
+    for file in files {
+        if total <= limit {
+            break;
+        }
+        if fs::remove_file(&file.path).is_ok() {
+            total -= file.size;
+            freed += file.size;
+        }
+    }
+    tracing::info!(
+        "Evicted {} KB from the {} cache",
+        freed / 1024,
+        kind.dir_name()
+    );
+    freed
+}
+
+/// Applies every cache's size limit.
+pub fn enforce(app: &tauri::AppHandle, limits: &CacheLimits) {
+    for kind in CacheKind::ALL {
+        if let Some(limit) = limits.bytes(kind) {
+            evict(app, kind, limit);
+        }
+    }
+}
+
+/// Enforces the limits at startup and every ten minutes after.
+pub fn start(app: &tauri::AppHandle) {
+    let app = app.clone();
+    std::thread::spawn(move || loop {
+        let limits = app
+            .state::<SettingsStore>()
+            .read(|s| s.cache_limits.clone());
+        enforce(&app, &limits);
+        std::thread::sleep(ENFORCE_INTERVAL);
+    });
+}
+
+#[tauri::command]
+pub async fn get_cache_stats(
+    app: tauri::AppHandle,
+    settings: State<'_, SettingsStore>,
+) -> Result<Vec<CacheStats>, String> {
+    let limits = settings.read(|s| s.cache_limits.clone());
+    tauri::async_runtime::spawn_blocking(move || {
+        CacheKind::ALL
+            .into_iter()
+            .map(|kind| {
+                let files = files(&app, kind);
+                CacheStats {
+                    kind,
+                    bytes: files.iter().map(|f| f.size).sum(),
+                    files: files.len(),
+                    limit_bytes: limits.bytes(kind),
+                }
+            })
+            .collect()
+    })
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Deletes everything in one cache. Returns the bytes freed.
+#[tauri::command]
+pub async fn clear_cache(app: tauri::AppHandle, kind: CacheKind) -> Result<u64, String> {
+    tauri::async_runtime::spawn_blocking(move || evict(&app, kind, 0))
+        .await
+        .map_err(|e| e.to_string())
+}
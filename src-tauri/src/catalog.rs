@@ -0,0 +1,670 @@
+//! Server-side channel index. Channel lists are loaded once per server into memory and handed
+//! to the frontend a page at a time (`query_channels`) or in chunks via events
+//! (`stream_channels`), so a 100k-channel provider doesn't go over IPC as one JSON blob.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::{Emitter, Manager, State};
+
+use crate::cache::CacheKind;
+use crate::m3u::M3uEntry;
+use crate::offline;
+use crate::servers::{self, ServerConfig, ServerKind, ServerStore};
+use crate::snapshot;
+
+const DEFAULT_PAGE: usize = 200;
+const MAX_PAGE: usize = 2000;
+
+/// A channel from any backend, reduced to what lists and search need.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CatalogChannel {
+    /// Backend id: Xtream stream id, Emby item id, TVHeadend uuid, portal channel id,
+    /// HDHomeRun/SAT>IP URL.
+    pub id: String,
+    pub name: String,
+    pub number: Option<String>,
+    pub group: Option<String>,
+    pub logo: Option<String>,
+    /// XMLTV id for guide matching, where the backend provides one.
+    pub epg_id: Option<String>,
+    /// Audio-only station, played in a compact window. Only set when the provider says so;
+    /// group names aren't a reliable sign ("Radio Bremen TV"), and other streams without
+    /// video get the compact window once probing finds no video.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub radio: bool,
+    /// ClearKey `license_key` from the playlist's KODIPROP lines (see `drm`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clearkey: Option<String>,
+    /// Hidden by the user (see `edits`); left out of queries unless they ask for hidden ones.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub hidden: bool,
+}
+
+impl From<M3uEntry> for CatalogChannel {
+    fn from(entry: M3uEntry) -> Self {
+        Self {
+            number: entry.attributes.get("tvg-chno").cloned(),
+            radio: entry
+                .attributes
+                .get("radio")
+                .is_some_and(|r| r.eq_ignore_ascii_case("true")),
+            clearkey: crate::drm::clearkey_license(&entry.properties),
+            id: entry.url,
+            name: entry.name,
+            group: entry.group,
+            logo: entry.logo,
+            epg_id: entry.tvg_id,
+            hidden: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ChannelFilter {
+    /// Case-insensitive substring of the channel name.
+    pub search: Option<String>,
+    /// Exact group/category name.
+    pub group: Option<String>,
+    /// Also match channels the user hid, for managing them.
+    pub include_hidden: bool,
+    /// Only radio stations (`true`) or only TV channels (`false`).
+    pub radio: Option<bool>,
+}
+
+impl ChannelFilter {
+    fn matcher(&self) -> impl Fn(&CatalogChannel) -> bool + '_ {
+        let search = self
+            .search
+            .as_deref()
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(str::to_lowercase);
+        move |channel| {
+            (self.include_hidden || !channel.hidden)
+                && self.radio.is_none_or(|radio| channel.radio == radio)
+                && self
+                    .group
+                    .as_deref()
+                    .is_none_or(|g| channel.group.as_deref() == Some(g))
+                && search
+                    .as_deref()
+                    .is_none_or(|s| channel.name.to_lowercase().contains(s))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChannelPage {
+    pub items: Vec<CatalogChannel>,
+    /// Pass back to get the next page; `None` on the last one.
+    pub next_cursor: Option<String>,
+    /// Matches for the filter across all pages.
+    pub total: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CatalogInfo {
+    pub total: usize,
+    pub groups: Vec<String>,
+    pub stale: bool,
+    pub fetched_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ChannelChunk {
+    server_id: String,
+    offset: usize,
+    items: Vec<CatalogChannel>,
+    done: bool,
+}
+
+struct Index {
+    /// Bumped on every reload so cursors from an older list are rejected.
+    revision: u64,
+    /// The provider's list; `channels` is it with the user's edits applied (see `edits`).
+    raw: Arc<Vec<CatalogChannel>>,
+    channels: Arc<Vec<CatalogChannel>>,
+    stale: bool,
+    fetched_at: u64,
+    /// Loaded from the startup snapshot; the first query refreshes it from the server.
+    from_snapshot: bool,
+}
+
+static INDEX: Mutex<BTreeMap<String, Index>> = Mutex::new(BTreeMap::new());
+
+fn index() -> std::sync::MutexGuard<'static, BTreeMap<String, Index>> {
+    INDEX.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Xtream panels send ids and numbers as either strings or numbers.
+pub(crate) fn text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) if !s.is_empty() => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+pub(crate) async fn xtream_action(
+    server: &ServerConfig,
+    action: &str,
+) -> Result<Vec<Value>, String> {
+    xtream_call(server, action, &[]).await
+}
+
+/// A `player_api.php` action with extra query parameters, e.g. `get_series_info`.
+pub(crate) async fn xtream_call<T: serde::de::DeserializeOwned>(
+    server: &ServerConfig,
+    action: &str,
+    params: &[(&str, &str)],
+) -> Result<T, String> {
+    let _permit = crate::http::queue(Some(server)).await;
+    let req = crate::http::client(Some(server))?
+        .get(format!("{}/player_api.php", server.base_url()))
+        .query(&[
+            ("username", server.username.as_str()),
+            ("password", server.password.as_str()),
+            ("action", action),
+        ])
+        .query(params);
+    let resp = crate::wol::send_waking(server, req)
+        .await
+        .map_err(|e| format!("Xtream request failed: {}", e))?;
+    if !resp.status().is_success() {
+        return Err(format!("Xtream server returned HTTP {}", resp.status()));
+    }
+    resp.json()
+        .await
+        .map_err(|e| format!("Invalid Xtream response: {}", e))
+}
+
+async fn fetch_xtream(server: &ServerConfig) -> Result<Vec<CatalogChannel>, String> {
+    let categories: BTreeMap<String, String> = xtream_action(server, "get_live_categories")
+        .await?
+        .iter()
+        .filter_map(|c| Some((text(&c["category_id"])?, text(&c["category_name"])?)))
+        .collect();
+    Ok(xtream_action(server, "get_live_streams")
+        .await?
+        .iter()
+        .filter_map(|s| {
+            let group = text(&s["category_id"]).and_then(|id| categories.get(&id).cloned());
+            Some(CatalogChannel {
+                id: text(&s["stream_id"])?,
+                name: text(&s["name"]).unwrap_or_default(),
+                number: text(&s["num"]),
+                radio: text(&s["stream_type"]).as_deref() == Some("radio_streams"),
+                group,
+                logo: text(&s["stream_icon"]),
+                epg_id: text(&s["epg_channel_id"]),
+                clearkey: None,
+                hidden: false,
+            })
+        })
+        .collect())
+}
+
+fn map_cached<T>(
+    cached: offline::Cached<Vec<T>>,
+    f: impl FnMut(T) -> CatalogChannel,
+) -> offline::Cached<Vec<CatalogChannel>> {
+    offline::Cached {
+        data: cached.data.into_iter().map(f).collect(),
+        stale: cached.stale,
+        fetched_at: cached.fetched_at,
+        error: cached.error,
+    }
+}
+
+/// Loads the channel list from the server (or its offline copy). The Emby, TVHeadend and
+/// portal lists share the cache entries of their own channel commands.
+async fn fetch(
+    app: &tauri::AppHandle,
+    server: &ServerConfig,
+) -> Result<offline::Cached<Vec<CatalogChannel>>, String> {
+    let id = server.id.clone();
+    match server.kind {
+        ServerKind::Xtream => {
+            offline::cached(
+                app,
+                CacheKind::Catalogs,
+                &id,
+                "catalog",
+                fetch_xtream(server),
+            )
+            .await
+        }
+        ServerKind::Emby => {
+            let cached = crate::emby::emby_live_channels(app.clone(), app.state(), id).await?;
+            Ok(map_cached(cached, |c| CatalogChannel {
+                id: c.id,
+                name: c.name,
+                number: c.number,
+                group: None,
+                logo: c.image_url,
+                epg_id: None,
+                radio: false,
+                clearkey: None,
+                hidden: false,
+            }))
+        }
+        ServerKind::Tvheadend => {
+            let tags = crate::tvheadend::tvh_channel_tags(app.clone(), app.state(), id.clone())
+                .await
+                .map(|tags| tags.data)
+                .unwrap_or_default();
+            let cached = crate::tvheadend::tvh_channels(app.clone(), app.state(), id).await?;
+            Ok(map_cached(cached, |c| CatalogChannel {
+                group: c.tag_uuids.iter().find_map(|uuid| {
+                    tags.iter()
+                        .find(|t| &t.uuid == uuid)
+                        .map(|t| t.name.clone())
+                }),
+                id: c.uuid,
+                name: c.name,
+                number: c.number.map(|n| n.to_string()),
+                logo: c.icon_url,
+                epg_id: None,
+                radio: false,
+                clearkey: None,
+                hidden: false,
+            }))
+        }
+        ServerKind::Stalker => {
+            let cached = crate::stalker::stalker_channels(app.clone(), app.state(), id).await?;
+            Ok(map_cached(cached, |c| CatalogChannel {
+                id: c.id,
+                name: c.name,
+                number: c.number,
+                group: c.genre,
+                logo: c.logo,
+                epg_id: c.xmltv_id,
+                radio: false,
+                clearkey: None,
+                hidden: false,
+            }))
+        }
+        ServerKind::Hdhomerun => {
+            let lineup = crate::hdhomerun::hdhomerun_lineup(app.state(), id.clone());
+            offline::cached(app, CacheKind::Catalogs, &id, "catalog", async {
+                Ok(lineup
+                    .await?
+                    .into_iter()
+                    .map(|c| CatalogChannel {
+                        id: c.url,
+                        name: c.guide_name,
+                        number: Some(c.guide_number),
+                        group: None,
+                        logo: None,
+                        epg_id: None,
+                        radio: false,
+                        clearkey: None,
+                        hidden: false,
+                    })
+                    .collect())
+            })
+            .await
+        }
+        ServerKind::Satip => {
+            let list = crate::satip::satip_channels(app.state(), id.clone());
+            offline::cached(app, CacheKind::Catalogs, &id, "catalog", async {
+                Ok(list.await?.into_iter().map(CatalogChannel::from).collect())
+            })
+            .await
+        }
+        ServerKind::M3u => {
+            let import_id = uuid::Uuid::new_v4().to_string();
+            offline::cached(app, CacheKind::Catalogs, &id, "catalog", async {
+                Ok(crate::playlist::import(app, server, &import_id)
+                    .await?
+                    .into_iter()
+                    .map(CatalogChannel::from)
+                    .collect())
+            })
+            .await
+        }
+    }
+}
+
+/// Replaces the server's index with a freshly fetched list.
+async fn reload(app: &tauri::AppHandle, server: &ServerConfig) -> Result<CatalogInfo, String> {
+    Ok(replace(app, &server.id, fetch(app, server).await?))
+}
+
+/// Swaps in a complete list in one step, so queries never see a half-loaded index, and
+/// snapshots fresh lists for the next launch.
+pub fn replace(
+    app: &tauri::AppHandle,
+    server_id: &str,
+    cached: offline::Cached<Vec<CatalogChannel>>,
+) -> CatalogInfo {
+    if !cached.stale {
+        if let Err(e) = snapshot::write(app, server_id, cached.fetched_at, &cached.data) {
+            tracing::warn!("{}", e);
+        }
+    }
+    let channels = crate::edits::apply(app, server_id, &cached.data);
+    let visible: Vec<CatalogChannel> = channels.iter().filter(|c| !c.hidden).cloned().collect();
+    let info = CatalogInfo {
+        total: visible.len(),
+        groups: groups(&visible),
+        stale: cached.stale,
+        fetched_at: cached.fetched_at,
+    };
+    let mut index = index();
+    let revision = index.get(server_id).map_or(0, |i| i.revision + 1);
+    index.insert(
+        server_id.to_string(),
+        Index {
+            revision,
+            raw: Arc::new(cached.data),
+            channels: Arc::new(channels),
+            stale: cached.stale,
+            fetched_at: cached.fetched_at,
+            from_snapshot: false,
+        },
+    );
+    info
+}
+
+/// Loads the snapshot of every saved server's index in the background, so the first
+/// `query_channels` after launch doesn't wait for the network.
+pub fn preload(app: &tauri::AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let servers = app.state::<ServerStore>().read(|servers| servers.clone());
+        for server in servers {
+            let Some((fetched_at, channels)) = snapshot::read(&app, &server.id) else {
+                continue;
+            };
+            let edited = crate::edits::apply(&app, &server.id, &channels);
+            index().entry(server.id).or_insert(Index {
+                revision: 0,
+                raw: Arc::new(channels),
+                channels: Arc::new(edited),
+                stale: true,
+                fetched_at,
+                from_snapshot: true,
+            });
+        }
+    });
+}
+
+/// Refreshes a snapshot-loaded index from the server, then emits `channels-updated` so the
+/// list can be queried again.
+fn refresh_in_background(app: &tauri::AppHandle, server: &ServerConfig) {
+    let app = app.clone();
+    let server = server.clone();
+    tauri::async_runtime::spawn(async move {
+        match reload(&app, &server).await {
+            Ok(info) => {
+                let _ = app.emit(
+                    "channels-updated",
+                    serde_json::json!({ "serverId": server.id, "total": info.total }),
+                );
+            }
+            Err(e) => tracing::warn!("Failed to refresh channels for {}: {}", server.name, e),
+        }
+    });
+}
+
+/// Reloads every index that was served from the offline cache, e.g. once the network is back.
+pub(crate) fn refresh_stale(app: &tauri::AppHandle) {
+    let stale: Vec<String> = index()
+        .iter()
+        .filter(|(_, i)| i.stale)
+        .map(|(id, _)| id.clone())
+        .collect();
+    let store = app.state::<ServerStore>();
+    for server_id in stale {
+        if let Ok(server) = crate::servers::get(&store, &server_id) {
+            refresh_in_background(app, &server);
+        }
+    }
+}
+
+/// The server's index, loading it on first use.
+pub(crate) async fn channels(
+    app: &tauri::AppHandle,
+    server: &ServerConfig,
+) -> Result<(u64, Arc<Vec<CatalogChannel>>), String> {
+    let cached = index().get_mut(&server.id).map(|i| {
+        let refresh = std::mem::take(&mut i.from_snapshot);
+        (i.revision, i.channels.clone(), refresh)
+    });
+    if let Some((revision, channels, refresh)) = cached {
+        if refresh {
+            refresh_in_background(app, server);
+        }
+        return Ok((revision, channels));
+    }
+    reload(app, server).await?;
+    let index = index();
+    let i = index
+        .get(&server.id)
+        .ok_or_else(|| "Channel list was cleared while loading".to_string())?;
+    Ok((i.revision, i.channels.clone()))
+}
+
+/// A channel from an already loaded index, without fetching. Hidden channels are found too.
+pub fn lookup(server_id: &str, channel_id: &str) -> Option<CatalogChannel> {
+    index()
+        .get(server_id)?
+        .channels
+        .iter()
+        .find(|c| c.id == channel_id)
+        .cloned()
+}
+
+/// Ids of the hidden channels of each loaded index by server id, for filtering many entries
+/// at once.
+pub fn hidden_channels() -> HashMap<String, HashSet<String>> {
+    index()
+        .iter()
+        .map(|(server_id, i)| {
+            let hidden = i.channels.iter().filter(|c| c.hidden).map(|c| c.id.clone());
+            (server_id.clone(), hidden.collect())
+        })
+        .collect()
+}
+
+/// The provider's list of an already loaded index, before the user's edits.
+pub(crate) fn provider_channels(server_id: &str) -> Option<Arc<Vec<CatalogChannel>>> {
+    index().get(server_id).map(|i| i.raw.clone())
+}
+
+/// Rebuilds a loaded index after its edits changed and emits `channels-updated`.
+pub(crate) fn reapply_edits(app: &tauri::AppHandle, server_id: &str) {
+    let Some(raw) = provider_channels(server_id) else {
+        return;
+    };
+    let channels = crate::edits::apply(app, server_id, &raw);
+    let total = channels.iter().filter(|c| !c.hidden).count();
+    if let Some(i) = index().get_mut(server_id) {
+        i.revision += 1;
+        i.channels = Arc::new(channels);
+    }
+    let _ = app.emit(
+        "channels-updated",
+        serde_json::json!({ "serverId": server_id, "total": total }),
+    );
+}
+
+/// A playable URL for a channel of the server's index.
+pub(crate) async fn stream_url(
+    app: &tauri::AppHandle,
+    server: &ServerConfig,
+    channel_id: &str,
+) -> Result<String, String> {
+    match server.kind {
+        ServerKind::Xtream => Ok(format!(
+            "{}/live/{}/{}/{}.m3u8",
+            server.base_url(),
+            urlencoding::encode(&server.username),
+            urlencoding::encode(&server.password),
+            channel_id
+        )),
+        ServerKind::Emby => {
+            crate::emby::stream_url(&app.state::<ServerStore>(), &server.id, channel_id).await
+        }
+        ServerKind::Tvheadend => crate::tvheadend::tvh_stream_url(
+            app.state(),
+            server.id.clone(),
+            channel_id.to_string(),
+            None,
+        ),
+        ServerKind::Stalker => crate::stalker::stream_url(app, server, channel_id).await,
+        // Tuner and playlist channels are identified by their stream URL
+        ServerKind::Hdhomerun | ServerKind::Satip | ServerKind::M3u => {
+            Ok(crate::ingest::via_udpxy(Some(server), channel_id)
+                .unwrap_or_else(|| channel_id.to_string()))
+        }
+    }
+}
+
+fn groups(channels: &[CatalogChannel]) -> Vec<String> {
+    let mut seen = BTreeSet::new();
+    channels
+        .iter()
+        .filter_map(|c| c.group.clone())
+        .filter(|g| seen.insert(g.clone()))
+        .collect()
+}
+
+/// Cursors are `<revision>:<offset>` into the filtered list.
+fn parse_cursor(cursor: &str, revision: u64) -> Result<usize, String> {
+    let (rev, offset) = cursor
+        .split_once(':')
+        .and_then(|(r, o)| Some((r.parse::<u64>().ok()?, o.parse::<usize>().ok()?)))
+        .ok_or_else(|| "Invalid cursor".to_string())?;
+    if rev != revision {
+        return Err("The channel list changed; start the query again".to_string());
+    }
+    Ok(offset)
+}
+
+/// Fetches the server's channel list again and rebuilds its index.
+#[tauri::command]
+pub async fn refresh_channels(
+    app: tauri::AppHandle,
+    store: State<'_, ServerStore>,
+    server_id: String,
+) -> Result<CatalogInfo, String> {
+    let server = servers::get(&store, &server_id)?;
+    reload(&app, &server).await
+}
+
+/// Size, groups and freshness of the server's index, loading it if needed. Hidden channels
+/// and groups only count with `include_hidden`.
+#[tauri::command]
+pub async fn get_channel_index(
+    app: tauri::AppHandle,
+    store: State<'_, ServerStore>,
+    server_id: String,
+    include_hidden: Option<bool>,
+) -> Result<CatalogInfo, String> {
+    let server = servers::get(&store, &server_id)?;
+    let (_, channels) = channels(&app, &server).await?;
+    let (stale, fetched_at) = index()
+        .get(&server_id)
+        .map_or((false, 0), |i| (i.stale, i.fetched_at));
+    let include_hidden = include_hidden.unwrap_or(false);
+    let channels: Vec<CatalogChannel> = channels
+        .iter()
+        .filter(|c| include_hidden || !c.hidden)
+        .cloned()
+        .collect();
+    Ok(CatalogInfo {
+        total: channels.len(),
+        groups: groups(&channels),
+        stale,
+        fetched_at,
+    })
+}
+
+/// One page of the server's channels matching `filter`. Start with no cursor and pass back
+/// `nextCursor` for the following pages.
+#[tauri::command]
+pub async fn query_channels(
+    app: tauri::AppHandle,
+    store: State<'_, ServerStore>,
+    server_id: String,
+    filter: Option<ChannelFilter>,
+    cursor: Option<String>,
+    limit: Option<usize>,
+) -> Result<ChannelPage, String> {
+    let server = servers::get(&store, &server_id)?;
+    let (revision, channels) = channels(&app, &server).await?;
+    let offset = match &cursor {
+        Some(cursor) => parse_cursor(cursor, revision)?,
+        None => 0,
+    };
+    let limit = limit.unwrap_or(DEFAULT_PAGE).clamp(1, MAX_PAGE);
+    let filter = filter.unwrap_or_default();
+    let matches = filter.matcher();
+    let mut total = 0;
+    let mut items = Vec::new();
+    for channel in channels.iter().filter(|c| matches(c)) {
+        if total >= offset && items.len() < limit {
+            items.push(channel.clone());
+        }
+        total += 1;
+    }
+    let end = offset + items.len();
+    Ok(ChannelPage {
+        items,
+        next_cursor: (end < total).then(|| format!("{}:{}", revision, end)),
+        total,
+    })
+}
+
+/// Emits the server's channels matching `filter` as `channel-chunk` events of `chunkSize`
+/// items, so the list can render as it arrives. Returns the number of matches.
+#[tauri::command]
+pub async fn stream_channels(
+    app: tauri::AppHandle,
+    store: State<'_, ServerStore>,
+    server_id: String,
+    filter: Option<ChannelFilter>,
+    chunk_size: Option<usize>,
+) -> Result<usize, String> {
+    let server = servers::get(&store, &server_id)?;
+    let (_, channels) = channels(&app, &server).await?;
+    let filter = filter.unwrap_or_default();
+    let matches = filter.matcher();
+    let chunk_size = chunk_size.unwrap_or(DEFAULT_PAGE).clamp(1, MAX_PAGE);
+    let matching: Vec<&CatalogChannel> = channels.iter().filter(|c| matches(c)).collect();
+    let mut offset = 0;
+    for chunk in matching.chunks(chunk_size) {
+        let _ = app.emit(
+            "channel-chunk",
+            ChannelChunk {
+                server_id: server_id.clone(),
+                offset,
+                items: chunk.iter().map(|&c| c.clone()).collect(),
+                done: offset + chunk.len() == matching.len(),
+            },
+        );
+        offset += chunk.len();
+    }
+    if matching.is_empty() {
+        let _ = app.emit(
+            "channel-chunk",
+            ChannelChunk {
+                server_id,
+                offset: 0,
+                items: Vec::new(),
+                done: true,
+            },
+        );
+    }
+    Ok(matching.len())
+}
@@ -0,0 +1,146 @@
+//! Catch-up (archive) playback URLs. Xtream timeshift URLs take the start time in the
+//! provider's local time, so the UTC programme start is converted with the provider's declared
+//! zone at that instant; a fixed offset would be an hour off for programmes across a DST change.
+//! Stalker portals play archived programmes by their programme id instead (see `stalker`).
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+use serde_json::Value;
+use tauri::State;
+
+use crate::servers::{self, ServerConfig, ServerKind, ServerStore};
+use crate::tz::{self, Zone};
+
+#[derive(Clone)]
+enum ProviderZone {
+    Named(Arc<Zone>),
+    /// Seconds east of UTC, when the panel only reveals its current offset.
+    Fixed(i32),
+}
+
+impl ProviderZone {
+    fn offset_at(&self, utc: i64) -> i32 {
+        match self {
+            ProviderZone::Named(zone) => zone.offset_at(utc),
+            ProviderZone::Fixed(offset) => *offset,
+        }
+    }
+}
+
+static ZONES: Mutex<BTreeMap<String, ProviderZone>> = Mutex::new(BTreeMap::new());
+
+fn zones() -> std::sync::MutexGuard<'static, BTreeMap<String, ProviderZone>> {
+    ZONES.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Parses the panel's `time_now` (`2024-05-01 14:00:00`, provider local time).
+fn parse_local(s: &str) -> Option<i64> {
+    let (date, time) = s.trim().split_once(' ')?;
+    let mut date = date.split('-').map(|p| p.parse::<i64>().ok());
+    let days = crate::days_from_civil(date.next()??, date.next()?? as u32, date.next()?? as u32);
+    let mut time = time.split(':').map(|p| p.parse::<i64>().ok());
+    let secs = time.next()?? * 3600 + time.next()?? * 60 + time.next().flatten().unwrap_or(0);
+    Some(days * 86_400 + secs)
+}
+
+/// The zone from `server_info`: `timezone` if it names a known zone, else the offset between
+/// `time_now` and `timestamp_now`, rounded to the quarter hour.
+fn zone_from_server_info(info: &Value) -> Option<ProviderZone> {
+    if let Some(zone) = info["timezone"]
+        .as_str()
+        .and_then(|name| tz::zone(name).ok())
+    {
+        return Some(ProviderZone::Named(zone));
+    }
+    let utc = crate::catalog::text(&info["timestamp_now"])?
+        .parse::<i64>()
+        .ok()?;
+    let local = parse_local(info["time_now"].as_str()?)?;
+    let offset = ((local - utc) as f64 / 900.0).round() as i32 * 900;
+    Some(ProviderZone::Fixed(offset))
+}
+
+async fn fetch_zone(server: &ServerConfig) -> Result<ProviderZone, String> {
+    let _permit = crate::http::queue(Some(server)).await;
+    let req = crate::http::client(Some(server))?
+        .get(format!("{}/player_api.php", server.base_url()))
+        .query(&[
+            ("username", server.username.as_str()),
+            ("password", server.password.as_str()),
+        ]);
+    let resp = crate::wol::send_waking(server, req)
+        .await
+        .map_err(|e| format!("Xtream request failed: {}", e))?;
+    if !resp.status().is_success() {
+        return Err(format!("Xtream server returned HTTP {}", resp.status()));
+    }
+    let body: Value = resp
+        .json()
+        .await
+        .map_err(|e| format!("Invalid Xtream response: {}", e))?;
+    Ok(
+        zone_from_server_info(&body["server_info"]).unwrap_or_else(|| {
+            tracing::warn!("{} does not declare a time zone; assuming UTC", server.name);
+            ProviderZone::Fixed(0)
+        }),
+    )
+}
+
+/// The server's zone override, else what the panel declares (fetched once per session).
+async fn provider_zone(server: &ServerConfig) -> Result<ProviderZone, String> {
+    if let Some(name) = server.timezone.as_deref().filter(|n| !n.trim().is_empty()) {
+        return Ok(ProviderZone::Named(tz::zone(name)?));
+    }
+    if let Some(zone) = zones().get(&server.id) {
+        return Ok(zone.clone());
+    }
+    let zone = fetch_zone(server).await?;
+    zones().insert(server.id.clone(), zone.clone());
+    Ok(zone)
+}
+
+/// Archive URL for `stream_id` from `start` to `stop` (unix seconds). Portals need the
+/// guide's `programme_id`, which is looked up among loaded programmes when not given.
+#[tauri::command]
+pub async fn get_catchup_url(
+    store: State<'_, ServerStore>,
+    server_id: String,
+    stream_id: String,
+    start: i64,
+    stop: i64,
+    programme_id: Option<String>,
+) -> Result<String, String> {
+    let server = servers::get(&store, &server_id)?;
+    if !matches!(server.kind, ServerKind::Xtream | ServerKind::Stalker) {
+        return Err(format!("{} does not support catch-up", server.name));
+    }
+    if stop <= start {
+        return Err("Catch-up window is empty".to_string());
+    }
+    if start > crate::now_secs() as i64 {
+        return Err("Programme has not started yet".to_string());
+    }
+    if server.kind == ServerKind::Stalker {
+        let programme_id = programme_id
+            .or_else(|| crate::epg::airing_at(&server_id, &stream_id, start).and_then(|p| p.id))
+            .ok_or("Load the guide for this channel to play the programme from its archive")?;
+        return crate::stalker::archive_url(&server, &programme_id).await;
+    }
+    let zone = provider_zone(&server).await?;
+    let (year, month, day, hour, minute) = tz::local_parts(start, zone.offset_at(start));
+    let duration_minutes = (stop - start + 59) / 60;
+    Ok(format!(
+        "{}/timeshift/{}/{}/{}/{:04}-{:02}-{:02}:{:02}-{:02}/{}.ts",
+        server.base_url(),
+        urlencoding::encode(&server.username),
+        urlencoding::encode(&server.password),
+        duration_minutes,
+        year,
+        month,
+        day,
+        hour,
+        minute,
+        stream_id
+    ))
+}
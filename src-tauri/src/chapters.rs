@@ -0,0 +1,397 @@
+//! Chapter markers for recordings. Chapters are always written to a JSON sidecar next to the
+//! file (`{file}.chapters.json`), which the player reads; containers that support chapters
+//! (Matroska, MP4) also get them embedded by an ffmpeg stream-copy remux. MPEG-TS can't hold
+//! chapters, so HDHomeRun `.ts` recordings rely on the sidecar.
+//!
+//! Ad breaks are found from ffmpeg's `blackdetect` and `silencedetect`: a moment that is both
+//! black and silent is a likely cut between programme and ad, and a cluster of such cuts a
+//! few seconds to two minutes apart is a run of ad spots.
+//!
+//! Recordings spanning several guide programmes (a whole evening) get a chapter per programme
+//! when they join the library. Ad break detection keeps those: stretches between breaks are
+//! split where the programme changes and named after it.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, Manager, State};
+
+use crate::epg::Programme;
+use crate::recordings::RecordingStore;
+
+/// Longest gap between cuts within one ad break.
+const MAX_SPOT_SECS: f64 = 120.0;
+/// Shortest cluster of cuts treated as a break rather than a scene change.
+const MIN_BREAK_SECS: f64 = 60.0;
+const EMBED_EXTENSIONS: [&str; 4] = ["mkv", "mp4", "m4v", "mov"];
+
+/// `(start, end)` in seconds.
+type Interval = (f64, f64);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChapterKind {
+    Content,
+    /// A likely ad break the player can offer to skip.
+    Break,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Chapter {
+    /// Seconds from the start of the file.
+    pub start: f64,
+    pub end: f64,
+    pub title: String,
+    pub kind: ChapterKind,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DetectionEvent {
+    recording_id: String,
+    status: &'static str,
+    breaks: usize,
+    error: Option<String>,
+}
+
+pub fn sidecar_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".chapters.json");
+    path.with_file_name(name)
+}
+
+pub fn read_sidecar(path: &Path) -> Option<Vec<Chapter>> {
+    serde_json::from_str(&fs::read_to_string(sidecar_path(path)).ok()?).ok()
+}
+
+fn escape_metadata(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '=' | ';' | '#' | '\\' | '\n') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+fn ffmetadata(chapters: &[Chapter]) -> String {
+    let mut meta = String::from(";FFMETADATA1\n");
+    for chapter in chapters {
+        meta.push_str(&format!(
+            "[CHAPTER]\nTIMEBASE=1/1000\nSTART={}\nEND={}\ntitle={}\n",
+            (chapter.start * 1000.0).round() as i64,
+            (chapter.end * 1000.0).round() as i64,
+            escape_metadata(&chapter.title)
+        ));
+    }
+    meta
+}
+
+/// Remuxes `path` in place with `chapters` replacing any it had.
+fn embed(path: &Path, chapters: &[Chapter]) -> Result<(), String> {
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default();
+    let meta = std::env::temp_dir().join(format!("tvx-chapters-{}.txt", uuid::Uuid::new_v4()));
+    fs::write(&meta, ffmetadata(chapters))
+        .map_err(|e| format!("Failed to write chapter metadata: {}", e))?;
+    let tmp = path.with_extension(format!("chapters-tmp.{}", ext));
+    let output = Command::new(crate::ffmpeg::ffmpeg_path()?)
+        .args(["-hide_banner", "-loglevel", "error", "-nostdin", "-y"])
+        .arg("-i")
+        .arg(path)
+        .args(["-f", "ffmetadata", "-i"])
+        .arg(&meta)
+        .args([
+            "-map",
+            "0",
+            "-map_metadata",
+            "0",
+            "-map_chapters",
+            "1",
+            "-c",
+            "copy",
+        ])
+        .arg(&tmp)
+        .output();
+    let _ = fs::remove_file(&meta);
+    let output = output.map_err(|e| format!("Failed to run ffmpeg: {}", e))?;
+    if !output.status.success() {
+        let _ = fs::remove_file(&tmp);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("Writing chapters failed: {}", stderr.trim()));
+    }
+    fs::rename(&tmp, path).map_err(|e| format!("Failed to replace {}: {}", path.display(), e))
+}
+
+/// Writes the sidecar and, where the container allows, embeds the chapters. Returns whether
+/// they were embedded.
+pub fn write(path: &Path, chapters: &[Chapter]) -> Result<bool, String> {
+    let json = serde_json::to_string_pretty(chapters).map_err(|e| e.to_string())?;
+    let sidecar = sidecar_path(path);
+    fs::write(&sidecar, json)
+        .map_err(|e| format!("Failed to write {}: {}", sidecar.display(), e))?;
+    let embeddable = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| EMBED_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()));
+    if embeddable {
+        embed(path, chapters)?;
+    }
+    Ok(embeddable)
+}
+
+/// The number after `key` in an ffmpeg filter log line.
+fn field(line: &str, key: &str) -> Option<f64> {
+    let rest = &line[line.find(key)? + key.len()..];
+    rest.trim_start()
+        .split(|c: char| c.is_whitespace() || c == '|')
+        .next()?
+        .parse()
+        .ok()
+}
+
+/// Black and silent intervals from one decoding pass.
+fn detect_intervals(path: &Path) -> Result<(Vec<Interval>, Vec<Interval>), String> {
+    let output = Command::new(crate::ffmpeg::ffmpeg_path()?)
+        .args(["-hide_banner", "-nostats", "-nostdin", "-i"])
+        .arg(path)
+        .args([
+            "-vf",
+            "blackdetect=d=0.1:pix_th=0.10",
+            "-af",
+            "silencedetect=noise=-50dB:d=0.1",
+            "-sn",
+            "-dn",
+            "-f",
+            "null",
+            "-",
+        ])
+        .output()
+        .map_err(|e| format!("Failed to run ffmpeg: {}", e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let last = stderr.lines().last().unwrap_or_default();
+        return Err(format!("Break detection failed: {}", last.trim()));
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let mut black = Vec::new();
+    let mut silence = Vec::new();
+    let mut silence_start = None;
+    for line in stderr.lines() {
+        if line.contains("[blackdetect") {
+            if let (Some(start), Some(end)) =
+                (field(line, "black_start:"), field(line, "black_end:"))
+            {
+                black.push((start, end));
+            }
+        } else if line.contains("[silencedetect") {
+            if let Some(start) = field(line, "silence_start:") {
+                silence_start = Some(start);
+            } else if let (Some(start), Some(end)) = (silence_start, field(line, "silence_end:")) {
+                silence.push((start, end));
+                silence_start = None;
+            }
+        }
+    }
+    Ok((black, silence))
+}
+
+/// Ad breaks from black and silent intervals.
+fn breaks(black: &[Interval], silence: &[Interval]) -> Vec<Interval> {
+    let cuts: Vec<f64> = black
+        .iter()
+        .filter(|(bs, be)| silence.iter().any(|(ss, se)| ss < be && bs < se))
+        .map(|(bs, be)| (bs + be) / 2.0)
+        .collect();
+    let mut breaks = Vec::new();
+    let mut cluster: Option<Interval> = None;
+    for cut in cuts {
+        cluster = match cluster {
+            Some((start, last)) if cut - last <= MAX_SPOT_SECS => Some((start, cut)),
+            Some(done) => {
+                breaks.push(done);
+                Some((cut, cut))
+            }
+            None => Some((cut, cut)),
+        };
+    }
+    breaks.extend(cluster);
+    breaks.retain(|(start, end)| end - start >= MIN_BREAK_SECS);
+    breaks
+}
+
+/// A chapter per guide programme of a recording that started at `started_at` (unix seconds)
+/// and runs `duration` seconds; empty unless it spans more than one.
+pub fn programme_chapters(
+    programmes: &[Programme],
+    started_at: i64,
+    duration: f64,
+) -> Vec<Chapter> {
+    let mut programmes: Vec<&Programme> = programmes.iter().collect();
+    programmes.sort_by_key(|p| p.start);
+    let mut chapters: Vec<Chapter> = programmes
+        .into_iter()
+        .map(|p| Chapter {
+            start: ((p.start - started_at) as f64).clamp(0.0, duration),
+            end: ((p.stop - started_at) as f64).clamp(0.0, duration),
+            title: match &p.subtitle {
+                Some(subtitle) => format!("{}: {}", p.title, subtitle),
+                None => p.title.clone(),
+            },
+            kind: ChapterKind::Content,
+        })
+        .filter(|c| c.end - c.start >= 1.0)
+        .collect();
+    if chapters.len() < 2 {
+        return Vec::new();
+    }
+    // The recording's padding before the first and after the last programme belongs to them
+    chapters[0].start = 0.0;
+    if let Some(last) = chapters.last_mut() {
+        last.end = duration;
+    }
+    chapters
+}
+
+/// Content and break chapters covering `0..duration`. Content is split where `programmes`
+/// (chapters from `programme_chapters`) change and named after them.
+fn chapters_from_breaks(
+    breaks: &[Interval],
+    duration: f64,
+    programmes: &[Chapter],
+) -> Vec<Chapter> {
+    let mut chapters = Vec::new();
+    let mut at = 0.0;
+    let mut part = 1;
+    let mut content = |chapters: &mut Vec<Chapter>, start: f64, end: f64| {
+        let mut cuts: Vec<f64> = programmes
+            .iter()
+            .map(|p| p.start)
+            .filter(|&t| t > start && t < end)
+            .collect();
+        cuts.push(end);
+        let mut from = start;
+        for to in cuts {
+            // Slivers of programme between back-to-back breaks aren't worth a chapter
+            if to - from >= 1.0 {
+                let title = programmes
+                    .iter()
+                    .find(|p| p.start <= from && from < p.end)
+                    .map(|p| p.title.clone())
+                    .unwrap_or_else(|| format!("Part {}", part));
+                chapters.push(Chapter {
+                    start: from,
+                    end: to,
+                    title,
+                    kind: ChapterKind::Content,
+                });
+                part += 1;
+            }
+            from = to;
+        }
+    };
+    for &(start, end) in breaks {
+        content(&mut chapters, at, start);
+        chapters.push(Chapter {
+            start,
+            end,
+            title: "Break".to_string(),
+            kind: ChapterKind::Break,
+        });
+        at = end;
+    }
+    content(&mut chapters, at, duration.max(at));
+    chapters
+}
+
+/// Finds ad breaks in a file and writes its chapters. Blocking; decodes the whole file.
+pub fn mark_commercials(path: &Path) -> Result<Vec<Chapter>, String> {
+    let (black, silence) = detect_intervals(path)?;
+    let breaks = breaks(&black, &silence);
+    let duration = black
+        .iter()
+        .chain(&silence)
+        .map(|&(_, end)| end)
+        .fold(0.0, f64::max);
+    let duration = crate::probe::run_ffprobe(&path.to_string_lossy())
+        .ok()
+        .and_then(|p| p.duration_secs)
+        .unwrap_or(duration);
+    let programmes: Vec<Chapter> = read_sidecar(path)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|c| c.kind == ChapterKind::Content)
+        .collect();
+    let chapters = chapters_from_breaks(&breaks, duration, &programmes);
+    write(path, &chapters)?;
+    Ok(chapters)
+}
+
+/// Runs break detection on a library recording in the background, emitting
+/// `commercial-detection` when it starts and finishes.
+pub fn spawn_detection(app: &tauri::AppHandle, recording_id: String) {
+    let app = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let emit = |status: &'static str, breaks: usize, error: Option<String>| {
+            let _ = app.emit(
+                "commercial-detection",
+                DetectionEvent {
+                    recording_id: recording_id.clone(),
+                    status,
+                    breaks,
+                    error,
+                },
+            );
+        };
+        let path = app.state::<RecordingStore>().read(|recordings| {
+            recordings
+                .iter()
+                .find(|r| r.id == recording_id)
+                .map(|r| PathBuf::from(&r.path))
+        });
+        let Some(path) = path else {
+            return;
+        };
+        emit("started", 0, None);
+        match mark_commercials(&path) {
+            Ok(chapters) => {
+                let breaks = chapters
+                    .iter()
+                    .filter(|c| c.kind == ChapterKind::Break)
+                    .count();
+                tracing::info!("Found {} ad breaks in {}", breaks, path.display());
+                emit("finished", breaks, None)
+            }
+            Err(e) => {
+                tracing::warn!("Break detection for {} failed: {}", path.display(), e);
+                emit("failed", 0, Some(e))
+            }
+        }
+    });
+}
+
+/// Starts ad break detection for a recording; progress arrives as `commercial-detection`.
+#[tauri::command]
+pub fn detect_commercials(
+    app: tauri::AppHandle,
+    store: State<'_, RecordingStore>,
+    recording_id: String,
+) -> Result<(), String> {
+    if !store.read(|recordings| recordings.iter().any(|r| r.id == recording_id)) {
+        return Err("Recording not found".to_string());
+    }
+    spawn_detection(&app, recording_id);
+    Ok(())
+}
+
+/// Chapters from a file's sidecar, empty when it has none.
+#[tauri::command]
+pub fn get_chapters(path: String) -> Vec<Chapter> {
+    read_sidecar(Path::new(&path)).unwrap_or_default()
+}
@@ -0,0 +1,356 @@
+//! Charset detection and decoding for text files that predate UTF-8, mainly subtitles.
+//! Covers UTF-8/UTF-16 and the Windows code pages subtitle files are commonly saved in.
+
+/// Sentinel for bytes a code page leaves undefined.
+const UNDEF: u16 = 0xFFFD;
+
+/// Windows-1252 0x80..=0x9F; 0xA0..=0xFF match Latin-1.
+const CP1252_HIGH: [u16; 32] = [
+    0x20AC, UNDEF, 0x201A, 0x0192, 0x201E, 0x2026, 0x2020, 0x2021, 0x02C6, 0x2030, 0x0160, 0x2039,
+    0x0152, UNDEF, 0x017D, UNDEF, UNDEF, 0x2018, 0x2019, 0x201C, 0x201D, 0x2022, 0x2013, 0x2014,
+    0x02DC, 0x2122, 0x0161, 0x203A, 0x0153, UNDEF, 0x017E, 0x0178,
+];
+
+/// Windows-1250 (Central European) 0x80..=0xFF.
+const CP1250: [u16; 128] = [
+    0x20AC, UNDEF, 0x201A, UNDEF, 0x201E, 0x2026, 0x2020, 0x2021, UNDEF, 0x2030, 0x0160, 0x2039,
+    0x015A, 0x0164, 0x017D, 0x0179, UNDEF, 0x2018, 0x2019, 0x201C, 0x201D, 0x2022, 0x2013, 0x2014,
+    UNDEF, 0x2122, 0x0161, 0x203A, 0x015B, 0x0165, 0x017E, 0x017A, 0x00A0, 0x02C7, 0x02D8, 0x0141,
+    0x00A4, 0x0104, 0x00A6, 0x00A7, 0x00A8, 0x00A9, 0x015E, 0x00AB, 0x00AC, 0x00AD, 0x00AE, 0x017B,
+    0x00B0, 0x00B1, 0x02DB, 0x0142, 0x00B4, 0x00B5, 0x00B6, 0x00B7, 0x00B8, 0x0105, 0x015F, 0x00BB,
+    0x013D, 0x02DD, 0x013E, 0x017C, 0x0154, 0x00C1, 0x00C2, 0x0102, 0x00C4, 0x0139, 0x0106, 0x00C7,
+    0x010C, 0x00C9, 0x0118, 0x00CB, 0x011A, 0x00CD, 0x00CE, 0x010E, 0x0110, 0x0143, 0x0147, 0x00D3,
+    0x00D4, 0x0150, 0x00D6, 0x00D7, 0x0158, 0x016E, 0x00DA, 0x0170, 0x00DC, 0x00DD, 0x0162, 0x00DF,
+    0x0155, 0x00E1, 0x00E2, 0x0103, 0x00E4, 0x013A, 0x0107, 0x00E7, 0x010D, 0x00E9, 0x0119, 0x00EB,
+    0x011B, 0x00ED, 0x00EE, 0x010F, 0x0111, 0x0144, 0x0148, 0x00F3, 0x00F4, 0x0151, 0x00F6, 0x00F7,
+    0x0159, 0x016F, 0x00FA, 0x0171, 0x00FC, 0x00FD, 0x0163, 0x02D9,
+];
+
+/// Windows-1251 (Cyrillic) 0x80..=0xBF; 0xC0..=0xFF are А..я in order.
+const CP1251_HIGH: [u16; 64] = [
+    0x0402, 0x0403, 0x201A, 0x0453, 0x201E, 0x2026, 0x2020, 0x2021, 0x20AC, 0x2030, 0x0409, 0x2039,
+    0x040A, 0x040C, 0x040B, 0x040F, 0x0452, 0x2018, 0x2019, 0x201C, 0x201D, 0x2022, 0x2013, 0x2014,
+    UNDEF, 0x2122, 0x0459, 0x203A, 0x045A, 0x045C, 0x045B, 0x045F, 0x00A0, 0x040E, 0x045E, 0x0408,
+    0x00A4, 0x0490, 0x00A6, 0x00A7, 0x0401, 0x00A9, 0x0404, 0x00AB, 0x00AC, 0x00AD, 0x00AE, 0x0407,
+    0x00B0, 0x00B1, 0x0406, 0x0456, 0x0491, 0x00B5, 0x00B6, 0x00B7, 0x0451, 0x2116, 0x0454, 0x00BB,
+    0x0458, 0x0405, 0x0455, 0x0457,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Charset {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    /// Western European.
+    Windows1252,
+    /// Central European (Polish, Czech, Hungarian, ...).
+    Windows1250,
+    /// Cyrillic.
+    Windows1251,
+    /// Greek.
+    Windows1253,
+    /// Turkish.
+    Windows1254,
+}
+
+impl Charset {
+    pub fn name(self) -> &'static str {
+        match self {
+            Charset::Utf8 => "utf-8",
+            Charset::Utf16Le => "utf-16le",
+            Charset::Utf16Be => "utf-16be",
+            Charset::Windows1252 => "windows-1252",
+            Charset::Windows1250 => "windows-1250",
+            Charset::Windows1251 => "windows-1251",
+            Charset::Windows1253 => "windows-1253",
+            Charset::Windows1254 => "windows-1254",
+        }
+    }
+
+    /// Parses a charset name as accepted by `name`, plus common aliases.
+    pub fn from_name(name: &str) -> Option<Charset> {
+        let name = name.trim().to_lowercase().replace('_', "-");
+        Some(match name.as_str() {
+            "utf-8" | "utf8" => Charset::Utf8,
+            "utf-16le" | "utf-16" => Charset::Utf16Le,
+            "utf-16be" => Charset::Utf16Be,
+            "windows-1252" | "cp1252" | "latin1" | "iso-8859-1" => Charset::Windows1252,
+            "windows-1250" | "cp1250" => Charset::Windows1250,
+            "windows-1251" | "cp1251" => Charset::Windows1251,
+            "windows-1253" | "cp1253" => Charset::Windows1253,
+            "windows-1254" | "cp1254" | "iso-8859-9" => Charset::Windows1254,
+            _ => return None,
+        })
+    }
+
+    /// Legacy code page subtitle files in `language` are usually saved in.
+    fn for_language(language: &str) -> Option<Charset> {
+        let primary = language.to_lowercase();
+        let primary = primary.split(['-', '_']).next().unwrap_or_default();
+        Some(match primary {
+            "cs" | "cze" | "ces" | "sk" | "slo" | "slk" | "pl" | "pol" | "hu" | "hun" | "sl"
+            | "slv" | "hr" | "hrv" | "bs" | "bos" | "ro" | "rum" | "ron" => Charset::Windows1250,
+            "ru" | "rus" | "uk" | "ukr" | "bg" | "bul" | "be" | "bel" | "mk" | "mac" | "mkd"
+            | "sr" | "srp" => Charset::Windows1251,
+            "el" | "gre" | "ell" => Charset::Windows1253,
+            "tr" | "tur" => Charset::Windows1254,
+            _ => return None,
+        })
+    }
+}
+
+fn decode_byte(charset: Charset, b: u8) -> char {
+    let code = match (charset, b) {
+        (_, 0x00..=0x7F) => b as u16,
+        (Charset::Windows1250, _) => CP1250[b as usize - 0x80],
+        (Charset::Windows1251, 0xC0..=0xFF) => 0x0410 + (b as u16 - 0xC0),
+        (Charset::Windows1251, _) => CP1251_HIGH[b as usize - 0x80],
+        (Charset::Windows1253, 0xA1) => 0x0385,
+        (Charset::Windows1253, 0xA2) => 0x0386,
+        (Charset::Windows1253, 0xAA | 0xD2 | 0xFF) => UNDEF,
+        (Charset::Windows1253, 0xAF) => 0x2015,
+        (Charset::Windows1253, 0xB4) => 0x0384,
+        (Charset::Windows1253, 0xB8..=0xBA | 0xBC | 0xBE..=0xFE) => 0x0388 + (b as u16 - 0xB8),
+        (Charset::Windows1254, 0xD0) => 0x011E,
+        (Charset::Windows1254, 0xDD) => 0x0130,
+        (Charset::Windows1254, 0xDE) => 0x015E,
+        (Charset::Windows1254, 0xF0) => 0x011F,
+        (Charset::Windows1254, 0xFD) => 0x0131,
+        (Charset::Windows1254, 0xFE) => 0x015F,
+        (_, 0x80..=0x9F) => CP1252_HIGH[b as usize - 0x80],
+        _ => b as u16,
+    };
+    char::from_u32(code as u32).unwrap_or('\u{FFFD}')
+}
+
+fn decode_utf16(bytes: &[u8], little_endian: bool) -> String {
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|p| {
+            if little_endian {
+                u16::from_le_bytes([p[0], p[1]])
+            } else {
+                u16::from_be_bytes([p[0], p[1]])
+            }
+        })
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+pub fn decode_as(bytes: &[u8], charset: Charset) -> String {
+    match charset {
+        Charset::Utf8 => {
+            String::from_utf8_lossy(bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes))
+                .into_owned()
+        }
+        Charset::Utf16Le => decode_utf16(bytes.strip_prefix(b"\xFF\xFE").unwrap_or(bytes), true),
+        Charset::Utf16Be => decode_utf16(bytes.strip_prefix(b"\xFE\xFF").unwrap_or(bytes), false),
+        _ => bytes.iter().map(|b| decode_byte(charset, *b)).collect(),
+    }
+}
+
+/// Share of high bytes directly next to another high byte. Latin-script languages sprinkle
+/// accented letters between ASCII ones; Cyrillic and Greek text is almost all high bytes.
+fn high_byte_clustering(bytes: &[u8]) -> f64 {
+    let (mut high, mut clustered) = (0usize, 0usize);
+    for (i, b) in bytes.iter().enumerate() {
+        if *b < 0x80 {
+            continue;
+        }
+        high += 1;
+        let prev = i > 0 && bytes[i - 1] >= 0x80;
+        let next = bytes.get(i + 1).is_some_and(|n| *n >= 0x80);
+        if prev || next {
+            clustered += 1;
+        }
+    }
+    if high == 0 {
+        0.0
+    } else {
+        clustered as f64 / high as f64
+    }
+}
+
+/// How plausible `text` looks: letters score, symbols and undefined bytes inside words don't.
+fn plausibility(text: &str) -> i64 {
+    let chars: Vec<char> = text.chars().collect();
+    let mut score = 0i64;
+    for (i, c) in chars.iter().enumerate() {
+        if c.is_ascii() {
+            continue;
+        }
+        let in_word = chars
+            .get(i.wrapping_sub(1))
+            .is_some_and(|p| p.is_alphabetic())
+            || chars.get(i + 1).is_some_and(|n| n.is_alphabetic());
+        score += match (c.is_alphabetic(), in_word) {
+            (true, _) => 2,
+            (false, _) if *c == '\u{FFFD}' => -10,
+            (false, true) => -5,
+            (false, false) => 0,
+        };
+    }
+    score
+}
+
+/// Detects the charset of `bytes`. `language_hint` (e.g. from `Movie.pl.srt`) picks between
+/// code pages that can't be told apart reliably from the bytes alone.
+pub fn detect(bytes: &[u8], language_hint: Option<&str>) -> Charset {
+    if bytes.starts_with(b"\xEF\xBB\xBF") {
+        return Charset::Utf8;
+    }
+    if bytes.starts_with(b"\xFF\xFE") {
+        return Charset::Utf16Le;
+    }
+    if bytes.starts_with(b"\xFE\xFF") {
+        return Charset::Utf16Be;
+    }
+    // BOM-less UTF-16: mostly-ASCII text leaves every other byte zero
+    let sample = &bytes[..bytes.len().min(4096)];
+    let zeros = |offset: usize| {
+        sample
+            .iter()
+            .skip(offset)
+            .step_by(2)
+            .filter(|b| **b == 0)
+            .count()
+    };
+    if sample.len() >= 16 {
+        let half = sample.len() / 2;
+        if zeros(1) > half * 3 / 4 {
+            return Charset::Utf16Le;
+        }
+        if zeros(0) > half * 3 / 4 {
+            return Charset::Utf16Be;
+        }
+    }
+    if std::str::from_utf8(bytes).is_ok() {
+        return Charset::Utf8;
+    }
+    if let Some(charset) = language_hint.and_then(Charset::for_language) {
+        return charset;
+    }
+    if high_byte_clustering(bytes) > 0.6 {
+        // The commonest letters: о/а in Cyrillic, α/ο in Greek, are rare in the other page
+        let count = |b: u8| bytes.iter().filter(|x| **x == b).count();
+        return if count(0xE1) + count(0xEF) > count(0xEE) + count(0xE0) {
+            Charset::Windows1253
+        } else {
+            Charset::Windows1251
+        };
+    }
+    // Ties go to the first candidate, the most widespread code page
+    let mut best = (Charset::Windows1252, i64::MIN);
+    for charset in [
+        Charset::Windows1252,
+        Charset::Windows1250,
+        Charset::Windows1254,
+    ] {
+        let score = plausibility(&decode_as(bytes, charset));
+        if score > best.1 {
+            best = (charset, score);
+        }
+    }
+    best.0
+}
+
+/// Decodes `bytes` to a string, detecting the charset. Returns the charset used.
+pub fn decode(bytes: &[u8], language_hint: Option<&str>) -> (String, Charset) {
+    let charset = detect(bytes, language_hint);
+    (decode_as(bytes, charset), charset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn boms_pick_unicode() {
+        assert_eq!(detect(b"\xEF\xBB\xBFabc", None), Charset::Utf8);
+        assert_eq!(detect(b"\xFF\xFEa\0", None), Charset::Utf16Le);
+        assert_eq!(detect(b"\xFE\xFF\0a", None), Charset::Utf16Be);
+        assert_eq!(decode(b"\xEF\xBB\xBFd\xC3\xA9j", None).0, "déj");
+        assert_eq!(decode(b"\xFF\xFEa\0\xE9\0", None).0, "aé");
+    }
+
+    #[test]
+    fn bomless_utf16_is_detected_from_zero_bytes() {
+        let text = "1\n00:00:01,000 --> 00:00:02,000\nHello\n";
+        let le: Vec<u8> = text.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        let be: Vec<u8> = text.encode_utf16().flat_map(u16::to_be_bytes).collect();
+        assert_eq!(decode(&le, None), (text.to_string(), Charset::Utf16Le));
+        assert_eq!(decode(&be, None), (text.to_string(), Charset::Utf16Be));
+    }
+
+    #[test]
+    fn valid_utf8_wins_over_hints() {
+        assert_eq!(detect("Zażółć".as_bytes(), Some("pl")), Charset::Utf8);
+    }
+
+    #[test]
+    fn language_hints_pick_the_code_page() {
+        // "Łódź" in Windows-1250
+        let bytes = b"\xA3\xF3d\x9F";
+        assert_eq!(
+            decode(bytes, Some("pl")),
+            ("Łódź".to_string(), Charset::Windows1250)
+        );
+        // Languages without a code page of their own fall back to detection
+        assert_eq!(
+            detect(b"N\xE3o \xE9 poss\xEDvel", Some("pt-BR")),
+            Charset::Windows1252
+        );
+    }
+
+    #[test]
+    fn cyrillic_and_greek_are_told_apart() {
+        // "проверка" in Windows-1251, "καλοκαίρι" in Windows-1253
+        let russian = b"\xEF\xF0\xEE\xE2\xE5\xF0\xEA\xE0";
+        let greek = b"\xEA\xE1\xEB\xEF\xEA\xE1\xDF\xF1\xE9";
+        assert_eq!(
+            decode(russian, None),
+            ("проверка".to_string(), Charset::Windows1251)
+        );
+        assert_eq!(
+            decode(greek, None),
+            ("καλοκαίρι".to_string(), Charset::Windows1253)
+        );
+    }
+
+    #[test]
+    fn latin_code_pages_decode_their_own_letters() {
+        assert_eq!(decode_as(b"caf\xE9 \x80", Charset::Windows1252), "café €");
+        assert_eq!(
+            decode_as(b"\xD0\xDD\xDE\xF0\xFD\xFE", Charset::Windows1254),
+            "ĞİŞğış"
+        );
+        assert_eq!(decode(b"na\xEFve caf\xE9", None).1, Charset::Windows1252);
+    }
+
+    #[test]
+    fn undefined_bytes_become_replacement_characters() {
+        assert_eq!(decode_as(b"a\x81b", Charset::Windows1252), "a\u{FFFD}b");
+        assert_eq!(decode_as(b"\xAA", Charset::Windows1253), "\u{FFFD}");
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for charset in [
+            Charset::Utf8,
+            Charset::Utf16Le,
+            Charset::Utf16Be,
+            Charset::Windows1252,
+            Charset::Windows1250,
+            Charset::Windows1251,
+            Charset::Windows1253,
+            Charset::Windows1254,
+        ] {
+            assert_eq!(Charset::from_name(charset.name()), Some(charset));
+        }
+        assert_eq!(Charset::from_name(" CP_1251 "), None);
+        assert_eq!(
+            Charset::from_name(" ISO_8859-1 "),
+            Some(Charset::Windows1252)
+        );
+    }
+}
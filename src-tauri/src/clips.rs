@@ -0,0 +1,143 @@
+//! Clip export: cuts `start..end` out of a recording or a VOD stream into a file of its own.
+//! The cut is a stream copy, which is instant but starts at the keyframe before `start`; when
+//! the streams can't be copied into the chosen container it is re-encoded instead. ffmpeg's
+//! `-progress` output drives `clip-export` events for the progress bar.
+
+use std::io::{BufRead, BufReader};
+use std::process::{Command, Stdio};
+
+use serde::Serialize;
+use tauri::Emitter;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ClipExportEvent<'a> {
+    path: &'a str,
+    status: &'static str,
+    /// From 0 to 1.
+    progress: f64,
+    error: Option<String>,
+}
+
+fn emit(
+    app: &tauri::AppHandle,
+    path: &str,
+    status: &'static str,
+    progress: f64,
+    error: Option<String>,
+) {
+    let _ = app.emit(
+        "clip-export",
+        ClipExportEvent {
+            path,
+            status,
+            progress,
+            error,
+        },
+    );
+}
+
+/// Runs one ffmpeg cut, reporting progress. Returns ffmpeg's error output when it fails.
+fn cut(
+    app: &tauri::AppHandle,
+    source: &str,
+    start: f64,
+    duration: f64,
+    path: &str,
+    copy: bool,
+) -> Result<(), String> {
+    let mut command = Command::new(crate::ffmpeg::ffmpeg_path()?);
+    command
+        .args(["-hide_banner", "-loglevel", "error", "-nostdin", "-y"])
+        .args(["-ss", &format!("{:.3}", start)])
+        .args(crate::transcode::input_args(source))
+        .args([
+            "-t",
+            &format!("{:.3}", duration),
+            "-map",
+            "0:v?",
+            "-map",
+            "0:a?",
+        ]);
+    if copy {
+        command.args(["-c", "copy", "-avoid_negative_ts", "make_zero"]);
+    } else {
+        command.args(["-c:v", "libx264", "-preset", "veryfast", "-c:a", "aac"]);
+    }
+    let mut child = command
+        .args(["-progress", "pipe:1"])
+        .arg(path)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to start ffmpeg: {}", e))?;
+    if let Some(stdout) = child.stdout.take() {
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            // `out_time_ms` is in microseconds too, despite the name
+            let micros = line
+                .strip_prefix("out_time_us=")
+                .or_else(|| line.strip_prefix("out_time_ms="))
+                .and_then(|v| v.trim().parse::<f64>().ok());
+            if let Some(micros) = micros {
+                let progress = (micros / 1_000_000.0 / duration).clamp(0.0, 1.0);
+                emit(app, path, "progress", progress, None);
+            }
+        }
+    }
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("ffmpeg failed: {}", e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(stderr.trim().to_string());
+    }
+    Ok(())
+}
+
+/// Saves `start..end` (seconds) of `source`, a recording's path or a VOD URL, to `path`.
+/// Resolves with the path once the clip is written; `clip-export` reports progress meanwhile.
+#[tauri::command]
+pub async fn export_clip(
+    app: tauri::AppHandle,
+    source: String,
+    start: f64,
+    end: f64,
+    path: String,
+) -> Result<String, String> {
+    if !start.is_finite() || !end.is_finite() || start < 0.0 || end <= start {
+        return Err("The clip must end after it starts".to_string());
+    }
+    if path.trim().is_empty() {
+        return Err("Choose where to save the clip".to_string());
+    }
+    if let Some(dir) = std::path::Path::new(&path).parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    let duration = end - start;
+    emit(&app, &path, "started", 0.0, None);
+    let task_app = app.clone();
+    let task_path = path.clone();
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        let (app, path) = (task_app, task_path);
+        cut(&app, &source, start, duration, &path, true).or_else(|e| {
+            tracing::info!("Stream copy of clip failed ({}), re-encoding", e);
+            cut(&app, &source, start, duration, &path, false)
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())?;
+    match result {
+        Ok(()) => {
+            tracing::info!(path = %path, start, end, "Exported clip");
+            emit(&app, &path, "finished", 1.0, None);
+            Ok(path)
+        }
+        Err(e) => {
+            let _ = std::fs::remove_file(&path);
+            let error = format!("Could not export the clip: {}", e);
+            emit(&app, &path, "failed", 0.0, Some(error.clone()));
+            Err(error)
+        }
+    }
+}
@@ -0,0 +1,259 @@
+//! Recording conflict detection. Before a recording is scheduled, the server's existing jobs
+//! are checked against how many it can record at once; a collision is reported with the jobs
+//! involved and can be resolved by priority, by skipping, or by recording another airing.
+
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::epg::Programme;
+use crate::servers::{self, ServerConfig, ServerKind, ServerStore};
+use crate::tvheadend::{self, PRIORITY_NORMAL};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictStrategy {
+    /// Cancel less important jobs to make room, if that is enough.
+    Priority,
+    /// Leave the schedule as it is and don't record.
+    Skip,
+    /// Record the soonest other airing of the programme that doesn't collide.
+    Alternate,
+}
+
+/// A scheduled or running recording on a server.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordingJob {
+    pub id: String,
+    pub title: String,
+    pub channel_id: String,
+    pub channel_name: String,
+    pub start: i64,
+    pub stop: i64,
+    /// 0 (important) to 4 (unimportant), as on TVHeadend.
+    pub priority: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordingConflict {
+    /// Recordings the server can run at once.
+    pub limit: u32,
+    /// Jobs overlapping the new recording at a moment when the limit would be exceeded.
+    pub jobs: Vec<RecordingJob>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduleOutcome {
+    /// The server's id for the new recording; `None` when nothing was scheduled.
+    pub recording_id: Option<String>,
+    /// What was scheduled: the requested programme or, with `alternate`, another airing.
+    pub scheduled: Option<Programme>,
+    /// The collision found for the requested programme, even when it was resolved.
+    pub conflict: Option<RecordingConflict>,
+    /// Jobs cancelled to make room.
+    pub cancelled: Vec<RecordingJob>,
+}
+
+fn limit(server: &ServerConfig) -> Option<u32> {
+    server
+        .max_recordings
+        .or(server.max_connections)
+        .filter(|&n| n > 0)
+}
+
+async fn jobs(server: &ServerConfig) -> Result<Vec<RecordingJob>, String> {
+    match server.kind {
+        ServerKind::Tvheadend => Ok(tvheadend::upcoming_recordings(server)
+            .await?
+            .into_iter()
+            .map(|r| RecordingJob {
+                priority: r.effective_priority(),
+                id: r.uuid,
+                title: r.title,
+                channel_id: r.channel_uuid,
+                channel_name: r.channel_name,
+                start: r.start,
+                stop: r.stop,
+            })
+            .collect()),
+        _ => Ok(Vec::new()),
+    }
+}
+
+/// Indexes of the jobs running at each moment of `start..stop` where adding one more
+/// recording would exceed `limit`. The running set only grows when a job starts, so checking
+/// the window's start and every job start inside it covers every moment.
+fn overloaded(jobs: &[RecordingJob], start: i64, stop: i64, limit: u32) -> Vec<Vec<usize>> {
+    let points = std::iter::once(start).chain(
+        jobs.iter()
+            .map(|j| j.start)
+            .filter(|&t| t > start && t < stop),
+    );
+    let mut sets: Vec<Vec<usize>> = points
+        .map(|t| {
+            (0..jobs.len())
+                .filter(|&i| jobs[i].start <= t && t < jobs[i].stop)
+                .collect::<Vec<_>>()
+        })
+        .filter(|running| running.len() as u32 >= limit)
+        .collect();
+    sets.sort();
+    sets.dedup();
+    sets
+}
+
+fn conflict(jobs: &[RecordingJob], sets: &[Vec<usize>], limit: u32) -> Option<RecordingConflict> {
+    let mut involved: Vec<usize> = sets.iter().flatten().copied().collect();
+    if involved.is_empty() {
+        return None;
+    }
+    involved.sort_unstable();
+    involved.dedup();
+    Some(RecordingConflict {
+        limit,
+        jobs: involved.into_iter().map(|i| jobs[i].clone()).collect(),
+    })
+}
+
+/// Jobs to cancel so a recording of `priority` fits: at each overloaded moment, the least
+/// important jobs strictly below `priority`. `None` if that isn't enough somewhere.
+fn make_room(
+    jobs: &[RecordingJob],
+    sets: &[Vec<usize>],
+    limit: u32,
+    priority: u32,
+) -> Option<Vec<usize>> {
+    let mut cancel: Vec<usize> = Vec::new();
+    for set in sets {
+        let mut running: Vec<usize> = set
+            .iter()
+            .copied()
+            .filter(|i| !cancel.contains(i))
+            .collect();
+        let excess = (running.len() + 1).saturating_sub(limit as usize);
+        running.retain(|&i| jobs[i].priority > priority);
+        if running.len() < excess {
+            return None;
+        }
+        running.sort_by_key(|&i| std::cmp::Reverse(jobs[i].priority));
+        cancel.extend(&running[..excess]);
+    }
+    Some(cancel)
+}
+
+async fn schedule(
+    store: State<'_, ServerStore>,
+    server: &ServerConfig,
+    programme: &Programme,
+    priority: Option<u32>,
+) -> Result<String, String> {
+    match server.kind {
+        ServerKind::Tvheadend => {
+            let id = match programme.id.as_deref().and_then(|id| id.parse().ok()) {
+                Some(event_id) => {
+                    tvheadend::tvh_schedule_recording(store, server.id.clone(), event_id).await?
+                }
+                None => {
+                    tvheadend::tvh_schedule_manual_recording(
+                        store,
+                        server.id.clone(),
+                        programme.channel_id.clone(),
+                        programme.start,
+                        programme.stop,
+                        programme.title.clone(),
+                    )
+                    .await?
+                }
+            };
+            if let Some(priority) = priority {
+                tvheadend::set_priority(server, &id, priority).await?;
+            }
+            Ok(id)
+        }
+        _ => Err(format!("{} can't schedule recordings", server.name)),
+    }
+}
+
+async fn cancel(server: &ServerConfig, job: &RecordingJob) -> Result<(), String> {
+    match server.kind {
+        ServerKind::Tvheadend => tvheadend::cancel_recording(server, &job.id).await,
+        _ => Ok(()),
+    }
+}
+
+/// Schedules `programme` on its server unless it collides with existing jobs. On a collision
+/// with no `strategy` nothing is scheduled and the outcome carries the conflict, so the UI can
+/// ask the user how to resolve it.
+pub async fn record(
+    store: State<'_, ServerStore>,
+    programme: Programme,
+    strategy: Option<ConflictStrategy>,
+    priority: Option<u32>,
+) -> Result<ScheduleOutcome, String> {
+    let server = servers::get(&store, &programme.server_id)?;
+    if server.kind != ServerKind::Tvheadend {
+        return Err(format!("{} can't schedule recordings", server.name));
+    }
+    let collision = match limit(&server) {
+        Some(limit) => {
+            let jobs = jobs(&server).await?;
+            let sets = overloaded(&jobs, programme.start, programme.stop, limit);
+            conflict(&jobs, &sets, limit).map(|found| (limit, jobs, sets, found))
+        }
+        None => None,
+    };
+    let Some((limit, jobs, sets, found)) = collision else {
+        let id = schedule(store, &server, &programme, priority).await?;
+        return Ok(ScheduleOutcome {
+            recording_id: Some(id),
+            scheduled: Some(programme),
+            ..Default::default()
+        });
+    };
+    let mut outcome = ScheduleOutcome {
+        conflict: Some(found),
+        ..Default::default()
+    };
+    match strategy {
+        None | Some(ConflictStrategy::Skip) => {}
+        Some(ConflictStrategy::Priority) => {
+            let priority = priority.unwrap_or(PRIORITY_NORMAL);
+            if let Some(cancel_ids) = make_room(&jobs, &sets, limit, priority) {
+                for &i in &cancel_ids {
+                    cancel(&server, &jobs[i]).await?;
+                    outcome.cancelled.push(jobs[i].clone());
+                }
+                outcome.recording_id =
+                    Some(schedule(store, &server, &programme, Some(priority)).await?);
+                outcome.scheduled = Some(programme);
+            }
+        }
+        Some(ConflictStrategy::Alternate) => {
+            let alternate = crate::epg::airings(&programme)
+                .into_iter()
+                .find(|p| overloaded(&jobs, p.start, p.stop, limit).is_empty());
+            if let Some(alternate) = alternate {
+                outcome.recording_id = Some(schedule(store, &server, &alternate, priority).await?);
+                outcome.scheduled = Some(alternate);
+            }
+        }
+    }
+    Ok(outcome)
+}
+
+/// The collision scheduling `programme` would cause, without scheduling anything.
+#[tauri::command]
+pub async fn check_recording_conflicts(
+    store: State<'_, ServerStore>,
+    programme: Programme,
+) -> Result<Option<RecordingConflict>, String> {
+    let server = servers::get(&store, &programme.server_id)?;
+    let Some(limit) = limit(&server) else {
+        return Ok(None);
+    };
+    let jobs = jobs(&server).await?;
+    let sets = overloaded(&jobs, programme.start, programme.stop, limit);
+    Ok(conflict(&jobs, &sets, limit))
+}
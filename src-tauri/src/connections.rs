@@ -0,0 +1,315 @@
+//! Provider connection limits across windows and recordings. Every stream the app holds open
+//! is tracked by its holder (a video window label or a recording's) and server. Before a new
+//! one starts it reserves a connection within the server's limit: the `max_connections`
+//! Xtream reports for the account, else the server's `max_recordings`. Going over it makes
+//! the provider drop one of the streams without warning, often a recording, so under
+//! `Refuse` the new stream isn't started and under `Warn` it starts with
+//! `connection-limit-warning`; either way the streams in use are listed so one can be stopped
+//! with `stop_active_stream` first.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, Manager, State};
+
+use crate::servers::{ServerConfig, ServerKind, ServerStore};
+use crate::settings::SettingsStore;
+
+/// How long an account's reported limit is reused before asking again.
+const LIMIT_TTL: Duration = Duration::from_secs(600);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ConnectionLimitPolicy {
+    /// Start the stream anyway and emit `connection-limit-warning`.
+    Warn,
+    /// Don't start the stream.
+    #[default]
+    Refuse,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActiveStream {
+    /// Video window label, or the recording's label.
+    pub holder: String,
+    pub server_id: String,
+    pub title: String,
+    pub recording: bool,
+    pub started_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LimitReached {
+    pub server_id: String,
+    pub limit: u32,
+    pub active: Vec<ActiveStream>,
+}
+
+/// The limit an account reported, and when.
+struct Reported {
+    limit: Option<u32>,
+    at: Instant,
+}
+
+static STREAMS: Mutex<Vec<ActiveStream>> = Mutex::new(Vec::new());
+static REPORTED: Mutex<BTreeMap<String, Reported>> = Mutex::new(BTreeMap::new());
+
+fn streams() -> std::sync::MutexGuard<'static, Vec<ActiveStream>> {
+    STREAMS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn reported() -> std::sync::MutexGuard<'static, BTreeMap<String, Reported>> {
+    REPORTED.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Connections the server allows at once, if it has a known limit.
+async fn limit(server: &ServerConfig) -> Option<u32> {
+    if server.kind != ServerKind::Xtream {
+        return server.max_recordings.filter(|&n| n > 0);
+    }
+    let cached = reported()
+        .get(&server.id)
+        .filter(|r| r.at.elapsed() < LIMIT_TTL)
+        .map(|r| r.limit);
+    let reported = match cached {
+        Some(limit) => limit,
+        None => {
+            let limit = crate::account::fetch(server)
+                .await
+                .ok()
+                .and_then(|info| info.max_connections);
+            reported().insert(
+                server.id.clone(),
+                Reported {
+                    limit,
+                    at: Instant::now(),
+                },
+            );
+            limit
+        }
+    };
+    reported.or(server.max_recordings).filter(|&n| n > 0)
+}
+
+/// The limit a new stream on `server` would exceed among `streams`, not counting the stream
+/// of `replacing`, whose holder switches to the new one.
+fn over_limit(
+    streams: &[ActiveStream],
+    server_id: &str,
+    limit: u32,
+    replacing: Option<&str>,
+) -> Option<LimitReached> {
+    let active: Vec<ActiveStream> = streams
+        .iter()
+        .filter(|s| s.server_id == server_id && Some(s.holder.as_str()) != replacing)
+        .cloned()
+        .collect();
+    (active.len() as u32 >= limit).then(|| LimitReached {
+        server_id: server_id.to_string(),
+        limit,
+        active,
+    })
+}
+
+/// The limit a new stream on `server` would exceed, with the streams using it.
+async fn reached(server: &ServerConfig) -> Option<LimitReached> {
+    let limit = limit(server).await?;
+    over_limit(&streams(), &server.id, limit, None)
+}
+
+/// A connection taken for a stream being started. It counts against the server's limit from
+/// `reserve` on, so two streams started at once can't both fit in the last one, and is freed
+/// when dropped unless `keep` hands it to the stream's holder.
+pub(crate) struct Reservation {
+    id: String,
+}
+
+impl Reservation {
+    /// Gives the connection to `holder`, replacing the stream it held before.
+    pub fn keep(self, holder: &str) {
+        let mut streams = streams();
+        streams.retain(|s| s.holder != holder);
+        if let Some(stream) = streams.iter_mut().find(|s| s.holder == self.id) {
+            stream.holder = holder.to_string();
+            stream.started_at = crate::now_secs();
+        }
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        release(&self.id);
+    }
+}
+
+/// Takes a connection of `server` for a stream of `title`, per the limit policy: `Err` under
+/// `Refuse` when none is left, a `connection-limit-warning` under `Warn`. `replacing` is the
+/// holder about to switch streams; its current one doesn't count.
+pub(crate) async fn reserve(
+    app: &tauri::AppHandle,
+    server: &ServerConfig,
+    title: &str,
+    recording: bool,
+    replacing: Option<&str>,
+) -> Result<Reservation, String> {
+    let limit = limit(server).await;
+    let mut streams = streams();
+    let reached = limit.and_then(|limit| over_limit(&streams, &server.id, limit, replacing));
+    if let Some(reached) = reached {
+        let policy = app.state::<SettingsStore>().read(|s| s.connection_limit);
+        tracing::info!(server = %server.name, limit = reached.limit, ?policy, "Connection limit reached");
+        if policy == ConnectionLimitPolicy::Refuse {
+            return Err(refusal(server, &reached));
+        }
+        let _ = app.emit("connection-limit-warning", &reached);
+    }
+    let id = format!("pending-{}", uuid::Uuid::new_v4().simple());
+    streams.push(ActiveStream {
+        holder: id.clone(),
+        server_id: server.id.clone(),
+        title: title.to_string(),
+        recording,
+        started_at: crate::now_secs(),
+    });
+    Ok(Reservation { id })
+}
+
+fn refusal(server: &ServerConfig, reached: &LimitReached) -> String {
+    let recording = reached.active.iter().filter(|s| s.recording).count();
+    format!(
+        "{} allows {} connection{} and {} in use{}. Stop one of them first.",
+        server.name,
+        reached.limit,
+        if reached.limit == 1 { "" } else { "s" },
+        if reached.active.len() == 1 {
+            "it's"
+        } else {
+            "they're all"
+        },
+        match recording {
+            0 => String::new(),
+            1 => " (1 by a recording)".to_string(),
+            n => format!(" ({} by recordings)", n),
+        }
+    )
+}
+
+/// Forgets `holder`'s stream, when its window closes or its recording ends.
+pub fn release(holder: &str) {
+    streams().retain(|s| s.holder != holder);
+}
+
+/// Streams held open, optionally only those of one server.
+#[tauri::command]
+pub fn list_active_streams(server_id: Option<String>) -> Vec<ActiveStream> {
+    streams()
+        .iter()
+        .filter(|s| server_id.as_ref().is_none_or(|id| s.server_id == *id))
+        .cloned()
+        .collect()
+}
+
+/// Whether a new stream on the server would go over its limit, without starting anything.
+#[tauri::command]
+pub async fn check_connection_limit(
+    store: State<'_, ServerStore>,
+    server_id: String,
+) -> Result<Option<LimitReached>, String> {
+    let server = crate::servers::get(&store, &server_id)?;
+    Ok(reached(&server).await)
+}
+
+/// Stops a stream to free its connection: closes its video window. Recordings are stopped
+/// where they were started.
+#[tauri::command]
+pub fn stop_active_stream(app: tauri::AppHandle, holder: String) -> Result<(), String> {
+    let stream = streams()
+        .iter()
+        .find(|s| s.holder == holder)
+        .cloned()
+        .ok_or_else(|| format!("No stream held by {}", holder))?;
+    if stream.recording {
+        return Err(format!(
+            "{} is a recording; cancel it to free its connection",
+            stream.title
+        ));
+    }
+    crate::video_window(&app, &holder)?
+        .close()
+        .map_err(|e| e.to_string())?;
+    release(&holder);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stream(holder: &str, server_id: &str) -> ActiveStream {
+        ActiveStream {
+            holder: holder.to_string(),
+            server_id: server_id.to_string(),
+            title: holder.to_string(),
+            recording: false,
+            started_at: 0,
+        }
+    }
+
+    #[test]
+    fn only_the_servers_streams_count() {
+        let streams = [
+            stream("video-1", "a"),
+            stream("video-2", "b"),
+            stream("record-1", "a"),
+        ];
+        let reached = over_limit(&streams, "a", 2, None).unwrap();
+        assert_eq!(reached.active.len(), 2);
+        assert!(over_limit(&streams, "a", 3, None).is_none());
+        assert!(over_limit(&streams, "b", 2, None).is_none());
+    }
+
+    #[test]
+    fn the_replaced_stream_does_not_count() {
+        let streams = [stream("video-1", "a")];
+        assert!(over_limit(&streams, "a", 1, None).is_some());
+        assert!(over_limit(&streams, "a", 1, Some("video-1")).is_none());
+        assert!(over_limit(&streams, "a", 1, Some("video-2")).is_some());
+    }
+
+    fn reservation(server_id: &str) -> Reservation {
+        let id = format!("pending-{}", uuid::Uuid::new_v4().simple());
+        streams().push(stream(&id, server_id));
+        Reservation { id }
+    }
+
+    fn holders(server_id: &str) -> Vec<String> {
+        list_active_streams(Some(server_id.to_string()))
+            .into_iter()
+            .map(|s| s.holder)
+            .collect()
+    }
+
+    #[test]
+    fn kept_reservations_replace_the_holders_stream() {
+        let first = reservation("kept");
+        assert_eq!(holders("kept").len(), 1);
+        first.keep("video-kept");
+        let second = reservation("kept");
+        assert_eq!(holders("kept").len(), 2);
+        second.keep("video-kept");
+        assert_eq!(holders("kept"), ["video-kept"]);
+        release("video-kept");
+    }
+
+    #[test]
+    fn dropped_reservations_free_the_connection() {
+        let pending = reservation("dropped");
+        assert_eq!(holders("dropped").len(), 1);
+        drop(pending);
+        assert!(holders("dropped").is_empty());
+    }
+}
@@ -0,0 +1,377 @@
+//! Per-server cookie jars behind `http::send`. Some panels only answer API calls and serve
+//! streams within a session that a cookie identifies. Cookies set by a saved server's host are
+//! kept in that server's jar, saved across restarts, and sent back with later requests to the
+//! host. Clearing a server's cookies starts a fresh session when its login gets stuck.
+
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Serialize};
+use tauri::{Manager, State};
+
+use crate::servers::ServerStore;
+use crate::store::JsonStore;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Cookie {
+    pub name: String,
+    pub value: String,
+    /// Lowercase, without a leading dot.
+    pub domain: String,
+    /// Sent to `domain` only, not its subdomains (no `Domain` attribute).
+    pub host_only: bool,
+    pub path: String,
+    pub secure: bool,
+    /// Unix seconds; `None` for session cookies, which are kept until cleared.
+    pub expires: Option<u64>,
+}
+
+impl Cookie {
+    pub(crate) fn matches(&self, url: &reqwest::Url, now: u64) -> bool {
+        let host = url.host_str().unwrap_or_default().to_ascii_lowercase();
+        let domain = host == self.domain
+            || (!self.host_only && host.ends_with(&format!(".{}", self.domain)));
+        let path = url.path();
+        let path = path == self.path
+            || (path.starts_with(&self.path)
+                && (self.path.ends_with('/') || path[self.path.len()..].starts_with('/')));
+        domain
+            && path
+            && (!self.secure || url.scheme() == "https")
+            && self.expires.is_none_or(|e| e > now)
+    }
+}
+
+/// Cookies by server id.
+pub type CookieStore = JsonStore<BTreeMap<String, Vec<Cookie>>>;
+
+/// For `http::send`, which runs without an app handle.
+static APP: OnceLock<tauri::AppHandle> = OnceLock::new();
+
+pub fn open(app: &tauri::AppHandle) -> CookieStore {
+    let _ = APP.set(app.clone());
+    JsonStore::open(app, "cookies.json")
+}
+
+const MONTHS: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+
+/// Unix seconds of an `Expires` date such as `Wed, 21 Oct 2015 07:28:00 GMT` (also with
+/// dashes, `21-Oct-2015`, as older servers send it).
+fn parse_expires(value: &str) -> Option<u64> {
+    let mut day = None;
+    let mut month = None;
+    let mut year = None;
+    let mut time = None;
+    for token in value.split([' ', '-', ',']).filter(|t| !t.is_empty()) {
+        if let Some((h, rest)) = token.split_once(':') {
+            let (m, s) = rest.split_once(':').unwrap_or((rest, "0"));
+            time = Some((
+                h.parse::<i64>().ok()?,
+                m.parse::<i64>().ok()?,
+                s.parse::<i64>().ok()?,
+            ));
+        } else if let Some(index) = MONTHS
+            .iter()
+            .position(|m| token.to_ascii_lowercase().starts_with(m))
+        {
+            month = Some(index as u32 + 1);
+        } else if let Ok(number) = token.parse::<i64>() {
+            match (day, number) {
+                (None, 1..=31) => day = Some(number as u32),
+                (_, 0..=69) => year = Some(2000 + number),
+                (_, 70..=99) => year = Some(1900 + number),
+                _ => year = Some(number),
+            }
+        }
+    }
+    let (h, m, s) = time.unwrap_or((0, 0, 0));
+    let days = crate::days_from_civil(year?, month?, day?);
+    u64::try_from(days * 86_400 + h * 3600 + m * 60 + s).ok()
+}
+
+/// The cookie a `Set-Cookie` header received from `url` sets, if it may set it.
+pub(crate) fn parse(header: &str, url: &reqwest::Url, now: u64) -> Option<Cookie> {
+    let host = url.host_str()?.to_ascii_lowercase();
+    let mut parts = header.split(';');
+    let (name, value) = parts.next()?.split_once('=')?;
+    let name = name.trim();
+    if name.is_empty() {
+        return None;
+    }
+    // The directory of the request path, per RFC 6265
+    let default_path = match url.path().rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(end) => url.path()[..end].to_string(),
+    };
+    let mut cookie = Cookie {
+        name: name.to_string(),
+        value: value.trim().to_string(),
+        domain: host.clone(),
+        host_only: true,
+        path: default_path,
+        secure: false,
+        expires: None,
+    };
+    let mut max_age = None;
+    for attribute in parts {
+        let (key, value) = attribute.split_once('=').unwrap_or((attribute, ""));
+        let value = value.trim();
+        match key.trim().to_ascii_lowercase().as_str() {
+            "domain" if !value.is_empty() => {
+                let domain = value.trim_start_matches('.').to_ascii_lowercase();
+                // A host can only set cookies for itself or a parent domain
+                if host != domain && !host.ends_with(&format!(".{}", domain)) {
+                    return None;
+                }
+                cookie.domain = domain;
+                cookie.host_only = false;
+            }
+            "path" if value.starts_with('/') => cookie.path = value.to_string(),
+            "secure" => cookie.secure = true,
+            "max-age" => max_age = value.parse::<i64>().ok(),
+            "expires" => cookie.expires = cookie.expires.or_else(|| parse_expires(value)),
+            _ => {}
+        }
+    }
+    // Max-Age wins over Expires; zero or less removes the cookie
+    if let Some(max_age) = max_age {
+        cookie.expires = Some(if max_age > 0 { now + max_age as u64 } else { 0 });
+    }
+    Some(cookie)
+}
+
+/// Adds `received` to `jar`, replacing cookies of the same name, domain and path and dropping
+/// expired ones.
+pub(crate) fn merge(jar: &mut Vec<Cookie>, received: Vec<Cookie>, now: u64) {
+    for cookie in received {
+        jar.retain(|c| {
+            (&c.name, &c.domain, &c.path) != (&cookie.name, &cookie.domain, &cookie.path)
+        });
+        if cookie.expires.is_none_or(|e| e > now) {
+            jar.push(cookie);
+        }
+    }
+    jar.retain(|c| c.expires.is_none_or(|e| e > now));
+}
+
+/// `Cookie` header with the cookies of `jar` that go with a request to `url`.
+pub(crate) fn header(jar: &[Cookie], url: &reqwest::Url, now: u64) -> Option<String> {
+    let mut cookies: Vec<&Cookie> = jar.iter().filter(|c| c.matches(url, now)).collect();
+    if cookies.is_empty() {
+        return None;
+    }
+    // More specific paths first, as browsers send them
+    cookies.sort_by_key(|c| std::cmp::Reverse(c.path.len()));
+    Some(
+        cookies
+            .iter()
+            .map(|c| format!("{}={}", c.name, c.value))
+            .collect::<Vec<_>>()
+            .join("; "),
+    )
+}
+
+fn host_matches(server: &crate::servers::ServerConfig, host: &str) -> bool {
+    crate::health::host_of(server).is_some_and(|h| h.eq_ignore_ascii_case(host))
+}
+
+/// The saved server on `host`, whose jar requests to it use.
+fn server_for(app: &tauri::AppHandle, host: &str) -> Option<String> {
+    app.state::<ServerStore>().read(|servers| {
+        servers
+            .iter()
+            .find(|s| host_matches(s, host))
+            .map(|s| s.id.clone())
+    })
+}
+
+/// `Cookie` header for a request to `url`, from the jar of the server on its host.
+pub fn header_for(url: &reqwest::Url) -> Option<String> {
+    let app = APP.get()?;
+    let server_id = server_for(app, url.host_str()?)?;
+    app.state::<CookieStore>().read(|jars| {
+        header(
+            jars.get(&server_id).map_or(&[], Vec::as_slice),
+            url,
+            crate::now_secs(),
+        )
+    })
+}
+
+/// Keeps the cookies `resp` sets in the jar of the server on its host.
+pub fn store(resp: &reqwest::Response) {
+    let headers: Vec<&str> = resp
+        .headers()
+        .get_all(reqwest::header::SET_COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .collect();
+    if headers.is_empty() {
+        return;
+    }
+    let Some(app) = APP.get() else {
+        return;
+    };
+    let url = resp.url();
+    let Some(server_id) = url.host_str().and_then(|host| server_for(app, host)) else {
+        return;
+    };
+    let now = crate::now_secs();
+    let received: Vec<Cookie> = headers.iter().filter_map(|h| parse(h, url, now)).collect();
+    let store = app.state::<CookieStore>();
+    // Most responses repeat the session cookie as it is, so only write when something changed
+    let changed = store.read(|jars| {
+        let saved = jars.get(&server_id).map_or(&[][..], Vec::as_slice);
+        let mut jar = saved.to_vec();
+        merge(&mut jar, received.clone(), now);
+        jar != saved
+    });
+    if !changed {
+        return;
+    }
+    let saved = store.update(|jars| merge(jars.entry(server_id).or_default(), received, now));
+    if let Err(e) = saved {
+        tracing::warn!("Could not save cookies: {}", e);
+    }
+}
+
+/// Forgets the cookies of `server_id`, so its next request starts a new session.
+pub fn clear(store: &CookieStore, server_id: &str) -> Result<(), String> {
+    store.update(|jars| {
+        jars.remove(server_id);
+    })
+}
+
+#[tauri::command]
+pub fn clear_server_cookies(
+    store: State<'_, CookieStore>,
+    server_id: String,
+) -> Result<(), String> {
+    clear(&store, &server_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn url(s: &str) -> reqwest::Url {
+        reqwest::Url::parse(s).unwrap()
+    }
+
+    #[test]
+    fn expires_dates_in_common_formats() {
+        assert_eq!(
+            parse_expires("Wed, 21 Oct 2015 07:28:00 GMT"),
+            Some(1_445_412_480)
+        );
+        assert_eq!(
+            parse_expires("Wednesday, 21-Oct-15 07:28:00 GMT"),
+            Some(1_445_412_480)
+        );
+        assert_eq!(parse_expires("Thu, 01-Jan-1970 00:00:10 GMT"), Some(10));
+        assert_eq!(parse_expires("Sun Nov  6 08:49:37 1994"), Some(784_111_777));
+        assert_eq!(parse_expires("21 October 2015"), Some(1_445_385_600));
+        assert_eq!(parse_expires("tomorrow"), None);
+        assert_eq!(parse_expires("Wed, 21 Oct 2015 07:xx:00 GMT"), None);
+    }
+
+    #[test]
+    fn parse_reads_attributes() {
+        let cookie = parse(
+            "session=abc123; Path=/api; Domain=.Example.com; Secure; HttpOnly; Max-Age=60",
+            &url("https://tv.example.com/login"),
+            NOW,
+        )
+        .unwrap();
+        assert_eq!(
+            cookie,
+            Cookie {
+                name: "session".to_string(),
+                value: "abc123".to_string(),
+                domain: "example.com".to_string(),
+                host_only: false,
+                path: "/api".to_string(),
+                secure: true,
+                expires: Some(NOW + 60),
+            }
+        );
+    }
+
+    #[test]
+    fn parse_defaults_to_the_request_host_and_directory() {
+        let cookie = parse("id = 7 ", &url("http://TV.example.com/a/b/c.php"), NOW).unwrap();
+        assert_eq!(cookie.domain, "tv.example.com");
+        assert!(cookie.host_only);
+        assert_eq!(cookie.path, "/a/b");
+        assert_eq!(cookie.value, "7");
+        assert_eq!(cookie.expires, None);
+        let root = parse("id=7; Path=relative", &url("http://example.com/login"), NOW).unwrap();
+        assert_eq!(root.path, "/");
+    }
+
+    #[test]
+    fn parse_rejects_foreign_domains_and_nameless_cookies() {
+        let from = url("http://tv.example.com/");
+        assert!(parse("a=1; Domain=other.com", &from, NOW).is_none());
+        assert!(parse("a=1; Domain=xample.com", &from, NOW).is_none());
+        assert!(parse("=1", &from, NOW).is_none());
+        assert!(parse("novalue", &from, NOW).is_none());
+    }
+
+    #[test]
+    fn max_age_wins_over_expires() {
+        let from = url("http://example.com/");
+        let header = "a=1; Max-Age=0; Expires=Wed, 21 Oct 2099 07:28:00 GMT";
+        assert_eq!(parse(header, &from, NOW).unwrap().expires, Some(0));
+        let header = "a=1; Expires=Wed, 21 Oct 2015 07:28:00 GMT";
+        assert_eq!(
+            parse(header, &from, NOW).unwrap().expires,
+            Some(1_445_412_480)
+        );
+    }
+
+    #[test]
+    fn jars_replace_and_expire_cookies() {
+        let from = url("http://example.com/");
+        let mut jar = Vec::new();
+        merge(&mut jar, vec![parse("a=1", &from, NOW).unwrap()], NOW);
+        merge(&mut jar, vec![parse("a=2", &from, NOW).unwrap()], NOW);
+        assert_eq!(jar.len(), 1);
+        assert_eq!(jar[0].value, "2");
+        merge(
+            &mut jar,
+            vec![parse("a=; Max-Age=0", &from, NOW).unwrap()],
+            NOW,
+        );
+        assert!(jar.is_empty());
+    }
+
+    #[test]
+    fn headers_send_matching_cookies_longest_path_first() {
+        let from = url("https://www.example.com/app/login");
+        let jar: Vec<Cookie> = [
+            "root=1; Path=/",
+            "app=2; Path=/app",
+            "other=3; Path=/application",
+            "secure=4; Secure; Path=/",
+            "shared=5; Domain=example.com; Path=/",
+        ]
+        .iter()
+        .map(|h| parse(h, &from, NOW).unwrap())
+        .collect();
+        assert_eq!(
+            header(&jar, &url("https://www.example.com/app/list"), NOW).as_deref(),
+            Some("app=2; root=1; secure=4; shared=5")
+        );
+        assert_eq!(
+            header(&jar, &url("http://cdn.example.com/"), NOW).as_deref(),
+            Some("shared=5")
+        );
+        assert_eq!(header(&jar, &url("http://example.org/"), NOW), None);
+    }
+}
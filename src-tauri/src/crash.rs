@@ -0,0 +1,254 @@
+//! Crash reports. A panic hook writes a JSON report (message, location, backtrace, the last
+//! commands invoked, app version) to `crashes/` in the app data dir. Reports are uploaded only
+//! when the user opted in and the build has a report endpoint (`TVX_CRASH_REPORT_URL` at
+//! compile time); otherwise they stay local for the diagnostics bundle.
+//!
+//! A marker file present while the app runs tells the next start that the previous session
+//! ended without a clean exit, which also catches crashes that never reach the panic hook.
+
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use serde::{Deserialize, Serialize};
+use tauri::{Manager, State};
+
+use crate::settings::SettingsStore;
+
+const UPLOAD_URL: Option<&str> = option_env!("TVX_CRASH_REPORT_URL");
+const RUNNING_MARKER: &str = "running";
+const RECENT_COMMANDS: usize = 20;
+
+static COMMANDS: Mutex<VecDeque<(u64, String)>> = Mutex::new(VecDeque::new());
+static CONTEXT: OnceLock<CrashContext> = OnceLock::new();
+
+struct CrashContext {
+    dir: PathBuf,
+    version: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CrashReport {
+    pub id: String,
+    pub version: String,
+    pub os: String,
+    pub arch: String,
+    /// Unix seconds.
+    pub time: u64,
+    pub thread: String,
+    pub message: String,
+    pub location: Option<String>,
+    pub backtrace: String,
+    /// Most recent last, as `(unix secs, command)`.
+    pub recent_commands: Vec<(u64, String)>,
+}
+
+/// Whether the previous run crashed, decided once at startup.
+pub struct CrashState {
+    crashed_last_time: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CrashStatus {
+    /// The previous session did not exit cleanly; offer to restore it.
+    pub crashed_last_time: bool,
+    /// Reports still on disk, newest first.
+    pub reports: Vec<CrashReport>,
+    pub upload_enabled: bool,
+    /// Whether this build can upload reports at all.
+    pub upload_available: bool,
+}
+
+/// Remembers a command for the next crash report. Called for every invocation.
+pub fn note_command(command: &str) {
+    let mut commands = COMMANDS.lock().unwrap_or_else(|e| e.into_inner());
+    if commands.len() == RECENT_COMMANDS {
+        commands.pop_front();
+    }
+    commands.push_back((crate::now_secs(), command.to_string()));
+}
+
+fn crash_dir(app: &tauri::AppHandle) -> PathBuf {
+    app.path()
+        .app_data_dir()
+        .map(|dir| dir.join("crashes"))
+        .unwrap_or_else(|_| PathBuf::from("crashes"))
+}
+
+fn write_report(info: &std::panic::PanicHookInfo<'_>) {
+    let Some(context) = CONTEXT.get() else {
+        return;
+    };
+    let message = info
+        .payload()
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| info.payload().downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "Unknown panic".to_string());
+    // try_lock: the panic may have happened while the list was locked
+    let recent_commands = COMMANDS
+        .try_lock()
+        .map(|c| c.iter().cloned().collect())
+        .unwrap_or_default();
+    let time = crate::now_secs();
+    let report = CrashReport {
+        id: format!(
+            "{}-{}",
+            time,
+            &uuid::Uuid::new_v4().simple().to_string()[..8]
+        ),
+        version: context.version.clone(),
+        os: std::env::consts::OS.to_string(),
+        arch: std::env::consts::ARCH.to_string(),
+        time,
+        thread: std::thread::current()
+            .name()
+            .unwrap_or("unnamed")
+            .to_string(),
+        message,
+        location: info
+            .location()
+            .map(|l| format!("{}:{}:{}", l.file(), l.line(), l.column())),
+        backtrace: std::backtrace::Backtrace::force_capture().to_string(),
+        recent_commands,
+    };
+    let _ = fs::create_dir_all(&context.dir);
+    if let Ok(json) = serde_json::to_string_pretty(&report) {
+        let _ = fs::write(context.dir.join(format!("crash-{}.json", report.id)), json);
+    }
+}
+
+fn read_reports(dir: &Path) -> Vec<(PathBuf, CrashReport)> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut reports: Vec<(PathBuf, CrashReport)> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|path| {
+            let report = serde_json::from_str(&fs::read_to_string(&path).ok()?).ok()?;
+            Some((path, report))
+        })
+        .collect();
+    reports.sort_by_key(|(_, report)| std::cmp::Reverse(report.time));
+    reports
+}
+
+/// Local report files, newest first.
+pub fn report_files(app: &tauri::AppHandle) -> Vec<PathBuf> {
+    read_reports(&crash_dir(app))
+        .into_iter()
+        .map(|(path, _)| path)
+        .collect()
+}
+
+/// Installs the panic hook and checks how the previous run ended. Returns the state to manage.
+pub fn install(app: &tauri::AppHandle) -> CrashState {
+    let dir = crash_dir(app);
+    let _ = fs::create_dir_all(&dir);
+    let marker = dir.join(RUNNING_MARKER);
+    let crashed_last_time = marker.exists();
+    if crashed_last_time {
+        tracing::warn!("The previous session did not exit cleanly");
+    }
+    let _ = fs::write(&marker, crate::now_secs().to_string());
+    let _ = CONTEXT.set(CrashContext {
+        dir,
+        version: app.package_info().version.to_string(),
+    });
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        write_report(info);
+        previous(info);
+    }));
+    if upload_enabled(app) {
+        let app = app.clone();
+        tauri::async_runtime::spawn(async move {
+            if let Err(e) = upload_pending(&app).await {
+                tracing::warn!("Crash report upload failed: {}", e);
+            }
+        });
+    }
+    CrashState { crashed_last_time }
+}
+
+/// Removes the running marker; called when the app exits normally.
+pub fn clean_exit(app: &tauri::AppHandle) {
+    let _ = fs::remove_file(crash_dir(app).join(RUNNING_MARKER));
+}
+
+fn upload_enabled(app: &tauri::AppHandle) -> bool {
+    UPLOAD_URL.is_some()
+        && app
+            .state::<SettingsStore>()
+            .read(|s| s.upload_crash_reports)
+}
+
+/// Uploads every local report, deleting the ones the endpoint accepted. Returns how many.
+async fn upload_pending(app: &tauri::AppHandle) -> Result<usize, String> {
+    let url = UPLOAD_URL.ok_or_else(|| "This build cannot upload crash reports".to_string())?;
+    let client = crate::http::client(None)?;
+    let mut uploaded = 0;
+    for (path, report) in read_reports(&crash_dir(app)) {
+        let resp = crate::http::send(client.post(url).json(&report))
+            .await
+            .map_err(|e| format!("Crash report upload failed: {}", e))?;
+        if !resp.status().is_success() {
+            return Err(format!(
+                "Crash report endpoint returned HTTP {}",
+                resp.status()
+            ));
+        }
+        let _ = fs::remove_file(path);
+        uploaded += 1;
+    }
+    Ok(uploaded)
+}
+
+#[tauri::command]
+pub fn get_crash_status(
+    app: tauri::AppHandle,
+    state: State<'_, CrashState>,
+    settings: State<'_, SettingsStore>,
+) -> CrashStatus {
+    CrashStatus {
+        crashed_last_time: state.crashed_last_time,
+        reports: read_reports(&crash_dir(&app))
+            .into_iter()
+            .map(|(_, report)| report)
+            .collect(),
+        upload_enabled: settings.read(|s| s.upload_crash_reports),
+        upload_available: UPLOAD_URL.is_some(),
+    }
+}
+
+/// Records the user's consent to uploading crash reports.
+#[tauri::command]
+pub fn set_crash_reporting(settings: State<'_, SettingsStore>, upload: bool) -> Result<(), String> {
+    settings.update(|s| s.upload_crash_reports = upload)
+}
+
+/// Uploads pending reports now. Requires consent. Returns how many were sent.
+#[tauri::command]
+pub async fn upload_crash_reports(app: tauri::AppHandle) -> Result<usize, String> {
+    if !app
+        .state::<SettingsStore>()
+        .read(|s| s.upload_crash_reports)
+    {
+        return Err("Crash report uploads are turned off".to_string());
+    }
+    upload_pending(&app).await
+}
+
+/// Deletes all local crash reports.
+#[tauri::command]
+pub fn clear_crash_reports(app: tauri::AppHandle) -> Result<(), String> {
+    for (path, _) in read_reports(&crash_dir(&app)) {
+        fs::remove_file(&path)
+            .map_err(|e| format!("Failed to delete {}: {}", path.display(), e))?;
+    }
+    Ok(())
+}
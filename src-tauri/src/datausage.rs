@@ -0,0 +1,310 @@
+//! Data usage and metered connections. Bytes the backend moves for playback (proxy relays and
+//! ffmpeg sessions; streams the webview plays directly aren't seen) are counted per calendar
+//! month in `data-usage.json`, and `data-cap-warning` is emitted once the configured monthly
+//! cap is nearly or fully used. The connection counts as metered when the OS says so
+//! (Windows connection cost, NetworkManager on Linux) or the user says so; then data saving
+//! caps adaptive stream quality and skips background guide prefetching.
+
+use std::collections::BTreeMap;
+use std::process::Command;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, Manager, State};
+
+use crate::settings::SettingsStore;
+use crate::store::JsonStore;
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+/// Metered detection runs a system tool, so it is refreshed less often than usage is saved.
+const DETECT_EVERY: u32 = 5;
+const MB: u64 = 1024 * 1024;
+/// Height adaptive streams are capped to while saving data.
+pub const SAVER_MAX_HEIGHT: u32 = 720;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MeteredOverride {
+    /// Follow the operating system.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DataSaverSettings {
+    pub metered: MeteredOverride,
+    /// Cap adaptive streams to 720p on metered connections.
+    pub reduce_quality: bool,
+    /// Skip background guide prefetching on metered connections.
+    pub pause_prefetch: bool,
+    /// Monthly cap in megabytes; 0 for none.
+    pub monthly_cap_mb: u64,
+    /// Share of the cap at which to warn, 1–100.
+    pub warn_percent: u8,
+}
+
+impl Default for DataSaverSettings {
+    fn default() -> Self {
+        Self {
+            metered: MeteredOverride::Auto,
+            reduce_quality: true,
+            pause_prefetch: true,
+            monthly_cap_mb: 0,
+            warn_percent: 80,
+        }
+    }
+}
+
+impl DataSaverSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(1..=100).contains(&self.warn_percent) {
+            return Err("The data cap warning must be between 1 and 100 percent".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DataUsage {
+    /// Bytes per `YYYY-MM` (UTC).
+    #[serde(default)]
+    pub months: BTreeMap<String, u64>,
+    /// Highest warning sent, as `YYYY-MM:percent`, so it isn't repeated.
+    #[serde(default)]
+    pub warned: Option<String>,
+}
+
+pub type DataUsageStore = JsonStore<DataUsage>;
+
+pub fn open(app: &tauri::AppHandle) -> DataUsageStore {
+    JsonStore::open(app, "data-usage.json")
+}
+
+/// Bytes counted since the last save.
+static PENDING: AtomicU64 = AtomicU64::new(0);
+/// Last detected state: 0 unknown, 1 metered, 2 not metered.
+static DETECTED: AtomicU8 = AtomicU8::new(0);
+
+/// Counts bytes moved for playback.
+pub fn add(bytes: usize) {
+    PENDING.fetch_add(bytes as u64, Ordering::Relaxed);
+}
+
+fn month(unix_secs: u64) -> String {
+    let (year, month, _) = crate::civil_date(unix_secs);
+    format!("{:04}-{:02}", year, month)
+}
+
+/// Whether the OS reports the connection as metered; `None` when it can't tell.
+fn detect() -> Option<bool> {
+    if cfg!(target_os = "windows") {
+        let script = "[void][Windows.Networking.Connectivity.NetworkInformation,Windows,ContentType=WindowsRuntime];\
+            [Windows.Networking.Connectivity.NetworkInformation]::GetInternetConnectionProfile().GetConnectionCost().NetworkCostType";
+        let mut command = Command::new("powershell");
+        command.args(["-NoProfile", "-NonInteractive", "-Command", script]);
+        #[cfg(windows)]
+        {
+            use std::os::windows::process::CommandExt;
+            // CREATE_NO_WINDOW, so no console flashes up every few minutes
+            command.creation_flags(0x0800_0000);
+        }
+        let output = command.output().ok()?;
+        match String::from_utf8_lossy(&output.stdout).trim() {
+            "Unrestricted" => Some(false),
+            "Fixed" | "Variable" => Some(true),
+            _ => None,
+        }
+    } else if cfg!(target_os = "linux") {
+        let output = Command::new("busctl")
+            .args([
+                "get-property",
+                "org.freedesktop.NetworkManager",
+                "/org/freedesktop/NetworkManager",
+                "org.freedesktop.NetworkManager",
+                "Metered",
+            ])
+            .output()
+            .ok()?;
+        // NMMetered: 1 yes, 2 no, 3 guessed yes, 4 guessed no
+        let value: u32 = String::from_utf8_lossy(&output.stdout)
+            .trim()
+            .strip_prefix("u ")?
+            .parse()
+            .ok()?;
+        match value {
+            1 | 3 => Some(true),
+            2 | 4 => Some(false),
+            _ => None,
+        }
+    } else {
+        None
+    }
+}
+
+fn detected() -> Option<bool> {
+    match DETECTED.load(Ordering::Relaxed) {
+        1 => Some(true),
+        2 => Some(false),
+        _ => None,
+    }
+}
+
+pub fn is_metered(settings: &SettingsStore) -> bool {
+    match settings.read(|s| s.data_saver.metered) {
+        MeteredOverride::Always => true,
+        MeteredOverride::Never => false,
+        MeteredOverride::Auto => detected() == Some(true),
+    }
+}
+
+/// Whether adaptive streams should be capped to `SAVER_MAX_HEIGHT`.
+pub fn reduce_quality(settings: &SettingsStore) -> bool {
+    settings.read(|s| s.data_saver.reduce_quality) && is_metered(settings)
+}
+
+/// Whether background prefetching should be skipped.
+pub fn pause_prefetch(app: &tauri::AppHandle) -> bool {
+    let settings = app.state::<SettingsStore>();
+    settings.read(|s| s.data_saver.pause_prefetch) && is_metered(&settings)
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CapWarning {
+    pub bytes: u64,
+    pub cap_bytes: u64,
+    pub percent: u8,
+}
+
+/// Adds pending bytes to this month and warns when the cap is approached or reached.
+fn save(app: &tauri::AppHandle) {
+    let bytes = PENDING.swap(0, Ordering::Relaxed);
+    let month = month(crate::now_secs());
+    let (cap_mb, warn_percent) = app
+        .state::<SettingsStore>()
+        .read(|s| (s.data_saver.monthly_cap_mb, s.data_saver.warn_percent));
+    let warning = app.state::<DataUsageStore>().update(|usage| {
+        let used = usage.months.entry(month.clone()).or_default();
+        *used += bytes;
+        let used = *used;
+        if cap_mb == 0 {
+            return None;
+        }
+        let cap_bytes = cap_mb * MB;
+        let percent = (used.saturating_mul(100) / cap_bytes).min(100) as u8;
+        let level = if percent >= 100 {
+            100
+        } else if percent >= warn_percent {
+            warn_percent
+        } else {
+            return None;
+        };
+        let warned_level = usage
+            .warned
+            .as_deref()
+            .and_then(|w| w.strip_prefix(&format!("{}:", month)))
+            .and_then(|p| p.parse::<u8>().ok());
+        if warned_level.is_some_and(|w| w >= level) {
+            return None;
+        }
+        usage.warned = Some(format!("{}:{}", month, level));
+        Some(CapWarning {
+            bytes: used,
+            cap_bytes,
+            percent,
+        })
+    });
+    match warning {
+        Ok(Some(warning)) => {
+            let _ = app.emit("data-cap-warning", warning);
+        }
+        Ok(None) => {}
+        Err(e) => tracing::warn!("Could not save data usage: {}", e),
+    }
+}
+
+fn refresh_detection(app: &tauri::AppHandle) {
+    let value = match detect() {
+        Some(true) => 1,
+        Some(false) => 2,
+        None => 0,
+    };
+    if DETECTED.swap(value, Ordering::Relaxed) != value {
+        let metered = is_metered(&app.state::<SettingsStore>());
+        let _ = app.emit("metered-changed", metered);
+    }
+}
+
+/// Saves usage every minute and re-checks whether the connection is metered every five.
+pub fn start(app: &tauri::AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let mut ticks = 0;
+        loop {
+            if ticks % DETECT_EVERY == 0 {
+                let detecting = app.clone();
+                let _ = tauri::async_runtime::spawn_blocking(move || refresh_detection(&detecting))
+                    .await;
+            }
+            ticks += 1;
+            tokio::time::sleep(CHECK_INTERVAL).await;
+            if PENDING.load(Ordering::Relaxed) > 0 {
+                save(&app);
+            }
+        }
+    });
+}
+
+/// Saves usage counted since the last save, on exit.
+pub fn shutdown(app: &tauri::AppHandle) {
+    save(app);
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DataUsageReport {
+    /// `YYYY-MM` of the current month.
+    pub month: String,
+    pub bytes: u64,
+    pub cap_bytes: Option<u64>,
+    /// Effective state, after the user's override.
+    pub metered: bool,
+    /// What the OS reports; `None` when it can't tell.
+    pub metered_detected: Option<bool>,
+    /// Earlier months, newest first.
+    pub history: Vec<(String, u64)>,
+}
+
+#[tauri::command]
+pub fn get_data_usage(
+    store: State<'_, DataUsageStore>,
+    settings: State<'_, SettingsStore>,
+) -> DataUsageReport {
+    let month = month(crate::now_secs());
+    let pending = PENDING.load(Ordering::Relaxed);
+    let (bytes, history) = store.read(|usage| {
+        let bytes = usage.months.get(&month).copied().unwrap_or(0) + pending;
+        let history = usage
+            .months
+            .iter()
+            .rev()
+            .filter(|(m, _)| **m != month)
+            .map(|(m, b)| (m.clone(), *b))
+            .collect();
+        (bytes, history)
+    });
+    let cap_mb = settings.read(|s| s.data_saver.monthly_cap_mb);
+    DataUsageReport {
+        month,
+        bytes,
+        cap_bytes: (cap_mb > 0).then(|| cap_mb * MB),
+        metered: is_metered(&settings),
+        metered_detected: detected(),
+        history,
+    }
+}
@@ -0,0 +1,240 @@
+//! Diagnostics bundle for bug reports: recent logs and crash reports, configuration with
+//! credentials redacted, system information and which external tools were found, as one ZIP.
+
+use std::process::Command;
+
+use serde::Serialize;
+use tauri::{Manager, State};
+
+use crate::logging::Logger;
+use crate::servers::ServerStore;
+use crate::settings::SettingsStore;
+use crate::webhooks::WebhookStore;
+use crate::zip::ZipWriter;
+
+const REDACTED: &str = "[redacted]";
+
+/// Object keys whose string values are replaced, matched case-insensitively after removing
+/// `_` so both `api_key` and `apiKey` are caught.
+const SECRET_KEYS: [&str; 7] = [
+    "password",
+    "token",
+    "apikey",
+    "secret",
+    "username",
+    "userid",
+    // The MAC and serial a portal account is tied to work like its credentials
+    "portaldevice",
+];
+
+fn is_secret(key: &str) -> bool {
+    let key = key.replace('_', "").to_ascii_lowercase();
+    SECRET_KEYS.iter().any(|secret| key.contains(secret))
+}
+
+/// Path prefixes of Xtream stream URLs, which are followed by the username and password.
+const XTREAM_PATHS: [&str; 4] = ["live", "movie", "series", "timeshift"];
+
+/// Removes `user:password@`, Xtream path credentials and the query from URLs, where stream
+/// and server URLs often carry credentials. Also used for URLs written to the log.
+pub(crate) fn sanitize_url(value: &str) -> String {
+    match reqwest::Url::parse(value) {
+        Ok(mut url) => {
+            let _ = url.set_username("");
+            let _ = url.set_password(None);
+            url.set_query(None);
+            let segments: Option<Vec<String>> =
+                url.path_segments().map(|s| s.map(str::to_string).collect());
+            if let Some(mut segments) = segments {
+                if segments.len() > 3 && XTREAM_PATHS.contains(&segments[0].as_str()) {
+                    segments[1] = "redacted".to_string();
+                    segments[2] = "redacted".to_string();
+                    url.set_path(&segments.join("/"));
+                }
+            }
+            url.to_string()
+        }
+        Err(_) => value.to_string(),
+    }
+}
+
+/// `text` with every URL in it sanitized, for log lines and crash reports.
+fn redact_text(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(at) = rest.find("://") {
+        let start = rest[..at]
+            .char_indices()
+            .rev()
+            .find(|(_, c)| !(c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.')))
+            .map_or(0, |(i, c)| i + c.len_utf8());
+        let end = rest[at..]
+            .find(|c: char| c.is_whitespace() || matches!(c, '"' | '\'' | '<' | '>'))
+            .map_or(rest.len(), |i| at + i);
+        out.push_str(&rest[..start]);
+        out.push_str(&sanitize_url(&rest[start..end]));
+        rest = &rest[end..];
+    }
+    out.push_str(rest);
+    out
+}
+
+fn redact(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, field) in map.iter_mut() {
+                if is_secret(key) && !matches!(field, serde_json::Value::Null) {
+                    *field = serde_json::Value::String(REDACTED.to_string());
+                } else {
+                    redact(field);
+                }
+            }
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(redact),
+        serde_json::Value::String(s) if s.contains("://") => *s = sanitize_url(s),
+        _ => {}
+    }
+}
+
+fn redacted_json<T: Serialize>(value: &T) -> Result<Vec<u8>, String> {
+    let mut json = serde_json::to_value(value).map_err(|e| e.to_string())?;
+    redact(&mut json);
+    serde_json::to_vec_pretty(&json).map_err(|e| e.to_string())
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ToolInfo {
+    path: Option<String>,
+    version: Option<String>,
+    error: Option<String>,
+}
+
+/// First line of `<tool> -version` (ffmpeg prints its build there).
+fn tool_info(found: Result<std::path::PathBuf, String>) -> ToolInfo {
+    match found {
+        Ok(path) => ToolInfo {
+            version: Command::new(&path)
+                .arg("-version")
+                .output()
+                .ok()
+                .and_then(|out| {
+                    String::from_utf8_lossy(&out.stdout)
+                        .lines()
+                        .next()
+                        .map(str::to_string)
+                }),
+            path: Some(path.to_string_lossy().into_owned()),
+            error: None,
+        },
+        Err(e) => ToolInfo {
+            path: None,
+            version: None,
+            error: Some(e),
+        },
+    }
+}
+
+async fn system_info(app: &tauri::AppHandle) -> serde_json::Value {
+    let (ffmpeg, ffprobe) = tauri::async_runtime::spawn_blocking(|| {
+        (
+            tool_info(crate::ffmpeg::ffmpeg_path()),
+            tool_info(crate::ffmpeg::ffprobe_path()),
+        )
+    })
+    .await
+    .unwrap_or_else(|e| {
+        let failed = || ToolInfo {
+            path: None,
+            version: None,
+            error: Some(e.to_string()),
+        };
+        (failed(), failed())
+    });
+    // VLC has no quiet version flag on Windows (it opens a window), so only report the path
+    let vlc = match crate::vlc::find_vlc() {
+        Ok(path) => ToolInfo {
+            path: Some(path),
+            version: None,
+            error: None,
+        },
+        Err(e) => ToolInfo {
+            path: None,
+            version: None,
+            error: Some(e),
+        },
+    };
+    serde_json::json!({
+        "app": "TvX",
+        "version": app.package_info().version.to_string(),
+        "os": std::env::consts::OS,
+        "family": std::env::consts::FAMILY,
+        "arch": std::env::consts::ARCH,
+        "generatedAt": crate::now_secs(),
+        "mpvEngine": cfg!(feature = "mpv"),
+        "ffmpeg": ffmpeg,
+        "ffprobe": ffprobe,
+        "vlc": vlc,
+        "hwCaps": match crate::hwaccel::caps().await {
+            Ok(caps) => serde_json::to_value(caps).unwrap_or_default(),
+            Err(e) => serde_json::json!({ "error": e }),
+        },
+    })
+}
+
+/// Writes a ZIP with logs, redacted settings, servers and webhooks, and system and tool
+/// detection results to `path`. Returns the path written.
+#[tauri::command]
+pub async fn export_diagnostics(
+    app: tauri::AppHandle,
+    logger: State<'_, Logger>,
+    settings: State<'_, SettingsStore>,
+    servers: State<'_, ServerStore>,
+    path: String,
+) -> Result<String, String> {
+    tracing::info!("Exporting diagnostics to {}", path);
+    let mut zip = ZipWriter::new();
+    for file in logger.files() {
+        let Ok(contents) = std::fs::read(&file) else {
+            continue;
+        };
+        let contents = redact_text(&String::from_utf8_lossy(&contents));
+        let name = file.file_name().unwrap_or_default().to_string_lossy();
+        zip.add(&format!("logs/{}", name), contents.as_bytes())?;
+    }
+    for file in crate::crash::report_files(&app) {
+        let Ok(contents) = std::fs::read(&file) else {
+            continue;
+        };
+        let contents = redact_text(&String::from_utf8_lossy(&contents));
+        let name = file.file_name().unwrap_or_default().to_string_lossy();
+        zip.add(&format!("crashes/{}", name), contents.as_bytes())?;
+    }
+    zip.add(
+        "config/settings.json",
+        &redacted_json(&settings.read(|s| s.clone()))?,
+    )?;
+    zip.add(
+        "config/servers.json",
+        &redacted_json(&servers.read(|s| s.clone()))?,
+    )?;
+    // Webhook paths are often secret themselves (Home Assistant webhook ids), so keep the host
+    let webhooks = app.state::<WebhookStore>().read(|hooks| {
+        hooks
+            .iter()
+            .cloned()
+            .map(|mut hook| {
+                hook.url = reqwest::Url::parse(&hook.url)
+                    .map(|u| u.origin().ascii_serialization())
+                    .unwrap_or_default();
+                hook
+            })
+            .collect::<Vec<_>>()
+    });
+    zip.add("config/webhooks.json", &redacted_json(&webhooks)?)?;
+    let system = system_info(&app).await;
+    let system = serde_json::to_vec_pretty(&system).map_err(|e| e.to_string())?;
+    zip.add("system.json", &system)?;
+    std::fs::write(&path, zip.finish()).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    Ok(path)
+}
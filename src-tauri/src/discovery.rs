@@ -0,0 +1,182 @@
+//! LAN discovery of media servers to prefill the "add server" dialog. Each product is probed
+//! with its own protocol in parallel: Jellyfin/Emby UDP 7359, Plex GDM, HDHomeRun UDP 65001,
+//! Kodi via mDNS and SAT>IP via SSDP.
+
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::servers::ServerKind;
+
+const WAIT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Product {
+    Jellyfin,
+    Emby,
+    Plex,
+    Hdhomerun,
+    Kodi,
+    Satip,
+}
+
+/// A server found on the LAN. `server_kind` is set when TvX can add it as a server directly.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiscoveredServer {
+    pub product: Product,
+    pub name: String,
+    pub url: String,
+    pub id: Option<String>,
+    pub server_kind: Option<ServerKind>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct JellyfinReply {
+    address: String,
+    #[serde(default)]
+    id: Option<String>,
+    #[serde(default)]
+    name: Option<String>,
+}
+
+/// Sends `payload` to `dest` and collects replies for `wait`. Blocking.
+fn udp_probe(dest: SocketAddr, payload: &[u8], wait: Duration) -> Vec<(Vec<u8>, SocketAddr)> {
+    let Ok(socket) = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)) else {
+        return Vec::new();
+    };
+    let _ = socket.set_broadcast(true);
+    let _ = socket.set_read_timeout(Some(Duration::from_millis(200)));
+    if socket.send_to(payload, dest).is_err() {
+        return Vec::new();
+    }
+    let deadline = Instant::now() + wait;
+    let mut replies = Vec::new();
+    let mut buf = [0u8; 4096];
+    while Instant::now() < deadline {
+        if let Ok((n, from)) = socket.recv_from(&mut buf) {
+            replies.push((buf[..n].to_vec(), from));
+        }
+    }
+    replies
+}
+
+/// Jellyfin and Emby share a discovery protocol but answer different probe strings.
+fn discover_jellyfin_like(product: Product) -> Vec<DiscoveredServer> {
+    let (probe, kind) = match product {
+        Product::Emby => (&b"who is EmbyServer?"[..], Some(ServerKind::Emby)),
+        _ => (&b"who is JellyfinServer?"[..], None),
+    };
+    let dest = SocketAddr::from((Ipv4Addr::BROADCAST, 7359));
+    udp_probe(dest, probe, WAIT)
+        .into_iter()
+        .filter_map(|(reply, _)| serde_json::from_slice::<JellyfinReply>(&reply).ok())
+        .map(|reply| DiscoveredServer {
+            product,
+            name: reply.name.unwrap_or_else(|| reply.address.clone()),
+            url: reply.address,
+            id: reply.id,
+            server_kind: kind,
+        })
+        .collect()
+}
+
+/// Plex "G'Day Mate" discovery: replies are HTTP-style header blocks.
+fn discover_plex() -> Vec<DiscoveredServer> {
+    let dest = SocketAddr::from((Ipv4Addr::new(239, 0, 0, 250), 32414));
+    udp_probe(dest, b"M-SEARCH * HTTP/1.1\r\n\r\n", WAIT)
+        .into_iter()
+        .filter_map(|(reply, from)| {
+            let text = String::from_utf8_lossy(&reply);
+            let field = |name: &str| {
+                text.lines().find_map(|line| {
+                    let (key, value) = line.split_once(':')?;
+                    key.trim()
+                        .eq_ignore_ascii_case(name)
+                        .then(|| value.trim().to_string())
+                })
+            };
+            if !field("Content-Type")?.contains("plex/media-server") {
+                return None;
+            }
+            let port = field("Port").unwrap_or_else(|| "32400".to_string());
+            Some(DiscoveredServer {
+                product: Product::Plex,
+                name: field("Name").unwrap_or_else(|| from.ip().to_string()),
+                url: format!("http://{}:{}", from.ip(), port),
+                id: field("Resource-Identifier"),
+                server_kind: None,
+            })
+        })
+        .collect()
+}
+
+fn discover_kodi() -> Vec<DiscoveredServer> {
+    crate::mdns::browse("_xbmc-jsonrpc-h._tcp.local", WAIT)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|service| DiscoveredServer {
+            product: Product::Kodi,
+            name: service.instance,
+            url: format!("http://{}:{}", service.ip, service.port),
+            id: None,
+            server_kind: None,
+        })
+        .collect()
+}
+
+fn discover_hdhomerun() -> Vec<DiscoveredServer> {
+    crate::hdhomerun::discover_lan(WAIT)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|device| DiscoveredServer {
+            product: Product::Hdhomerun,
+            name: format!("HDHomeRun {}", device.device_id),
+            url: device.base_url,
+            id: Some(device.device_id),
+            server_kind: Some(ServerKind::Hdhomerun),
+        })
+        .collect()
+}
+
+fn discover_satip() -> Vec<DiscoveredServer> {
+    crate::ssdp::search("urn:ses-com:device:SatIPServer:1", WAIT)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|response| DiscoveredServer {
+            product: Product::Satip,
+            name: reqwest::Url::parse(&response.location)
+                .ok()
+                .and_then(|u| u.host_str().map(|h| format!("SAT>IP {}", h)))
+                .unwrap_or_else(|| "SAT>IP server".to_string()),
+            url: response.location,
+            id: None,
+            server_kind: Some(ServerKind::Satip),
+        })
+        .collect()
+}
+
+/// Scans the LAN for media servers (about two seconds) and returns every candidate found.
+#[tauri::command]
+pub async fn discover_servers() -> Result<Vec<DiscoveredServer>, String> {
+    let probes: Vec<fn() -> Vec<DiscoveredServer>> = vec![
+        || discover_jellyfin_like(Product::Jellyfin),
+        || discover_jellyfin_like(Product::Emby),
+        discover_plex,
+        discover_kodi,
+        discover_hdhomerun,
+        discover_satip,
+    ];
+    let handles: Vec<_> = probes
+        .into_iter()
+        .map(tauri::async_runtime::spawn_blocking)
+        .collect();
+    let mut found = Vec::new();
+    for handle in handles {
+        found.extend(handle.await.map_err(|e| e.to_string())?);
+    }
+    Ok(found)
+}
@@ -0,0 +1,348 @@
+//! Optional resolver for provider domains, used instead of the system resolver when a server
+//! (or Settings) names its own DNS servers or a DNS-over-HTTPS endpoint. ISP resolvers often
+//! block or poison these domains. Only A/AAAA lookups are needed, so the DNS wire format is
+//! handled here directly. The resolver also applies the IPv4/IPv6 preference from Settings.
+
+use std::collections::BTreeMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+const QUERY_TIMEOUT: Duration = Duration::from_secs(3);
+const MIN_TTL: u32 = 30;
+const MAX_TTL: u32 = 3600;
+const TYPE_A: u16 = 1;
+const TYPE_AAAA: u16 = 28;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum DnsKind {
+    /// The system resolver, even when a custom one is configured globally.
+    #[default]
+    System,
+    /// Plain DNS over UDP to `servers`.
+    Udp,
+    /// DNS-over-HTTPS (RFC 8484) to `doh_url`.
+    Doh,
+}
+
+/// Which address family to connect over. Connections try addresses in the order the resolver
+/// returns them and fall back to the other family shortly after (happy eyeballs), so
+/// preferring one family just sorts it first; the `*Only` variants drop the other one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum IpFamily {
+    #[default]
+    Auto,
+    PreferIpv4,
+    PreferIpv6,
+    Ipv4Only,
+    Ipv6Only,
+}
+
+impl IpFamily {
+    fn apply(self, mut ips: Vec<IpAddr>) -> Vec<IpAddr> {
+        match self {
+            IpFamily::Auto => {}
+            IpFamily::PreferIpv4 => ips.sort_by_key(|ip| ip.is_ipv6()),
+            IpFamily::PreferIpv6 => ips.sort_by_key(|ip| ip.is_ipv4()),
+            IpFamily::Ipv4Only => ips.retain(|ip| ip.is_ipv4()),
+            IpFamily::Ipv6Only => ips.retain(|ip| ip.is_ipv6()),
+        }
+        ips
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct DnsConfig {
+    #[serde(default)]
+    pub kind: DnsKind,
+    /// `ip` or `ip:port`, tried in order.
+    #[serde(default)]
+    pub servers: Vec<String>,
+    #[serde(default)]
+    pub doh_url: Option<String>,
+}
+
+impl DnsConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        match self.kind {
+            DnsKind::System => Ok(()),
+            DnsKind::Udp => {
+                if self.servers.is_empty() {
+                    return Err("Custom DNS needs at least one server".to_string());
+                }
+                self.server_addrs().map(|_| ())
+            }
+            DnsKind::Doh => {
+                let url = self.doh_url.as_deref().unwrap_or_default();
+                match reqwest::Url::parse(url) {
+                    Ok(url) if url.scheme() == "https" => Ok(()),
+                    _ => Err(format!("Invalid DNS-over-HTTPS URL: {}", url)),
+                }
+            }
+        }
+    }
+
+    fn server_addrs(&self) -> Result<Vec<SocketAddr>, String> {
+        self.servers
+            .iter()
+            .map(|s| {
+                let s = s.trim();
+                s.parse::<SocketAddr>()
+                    .or_else(|_| s.parse::<IpAddr>().map(|ip| SocketAddr::new(ip, 53)))
+                    .map_err(|_| format!("Invalid DNS server: {}", s))
+            })
+            .collect()
+    }
+
+    /// Cache key; lookups through different resolvers must not share answers.
+    fn cache_key(&self, host: &str) -> String {
+        match self.kind {
+            DnsKind::Doh => format!("{}|{}", self.doh_url.as_deref().unwrap_or_default(), host),
+            _ => format!("{}|{}", self.servers.join(","), host),
+        }
+    }
+}
+
+/// Answers by resolver and host name, with their expiry.
+static CACHE: Mutex<BTreeMap<String, (Vec<IpAddr>, Instant)>> = Mutex::new(BTreeMap::new());
+
+/// `reqwest` resolver for a non-system `DnsConfig` and/or an address family preference.
+pub struct Resolver {
+    config: Option<DnsConfig>,
+    family: IpFamily,
+}
+
+impl Resolver {
+    /// `None` when the system resolver would do the same, i.e. no custom DNS and `Auto`.
+    pub fn new(config: Option<DnsConfig>, family: IpFamily) -> Option<Self> {
+        let config = config.filter(|c| c.kind != DnsKind::System);
+        (config.is_some() || family != IpFamily::Auto).then_some(Self { config, family })
+    }
+}
+
+impl reqwest::dns::Resolve for Resolver {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        let config = self.config.clone();
+        let family = self.family;
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            let ips = match &config {
+                Some(config) => lookup(config, &host).await?,
+                None => lookup_system(&host).await?,
+            };
+            let ips = family.apply(ips);
+            if ips.is_empty() {
+                return Err(format!("{} has no address of the allowed IP version", host).into());
+            }
+            // reqwest fills in the port of the URL being requested
+            let addrs: reqwest::dns::Addrs =
+                Box::new(ips.into_iter().map(|ip| SocketAddr::new(ip, 0)));
+            Ok(addrs)
+        })
+    }
+}
+
+/// IPv4 and IPv6 addresses for `host` through `config`, IPv4 first.
+pub async fn lookup(
+    config: &DnsConfig,
+    host: &str,
+) -> Result<Vec<IpAddr>, Box<dyn std::error::Error + Send + Sync>> {
+    let key = config.cache_key(host);
+    let cached = CACHE
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&key)
+        .filter(|(_, expires)| *expires > Instant::now())
+        .map(|(ips, _)| ips.clone());
+    if let Some(ips) = cached {
+        return Ok(ips);
+    }
+
+    let mut ips = Vec::new();
+    let mut ttl = MAX_TTL;
+    let mut last_err = None;
+    for qtype in [TYPE_A, TYPE_AAAA] {
+        let answer = match config.kind {
+            DnsKind::Doh => query_doh(config, host, qtype).await,
+            _ => query_udp(config, host, qtype).await,
+        };
+        match answer {
+            Ok(answer) => {
+                ips.extend(answer.ips);
+                ttl = ttl.min(answer.ttl);
+            }
+            Err(e) => last_err = Some(e),
+        }
+    }
+    if ips.is_empty() {
+        let reason = last_err.unwrap_or_else(|| "no addresses".to_string());
+        return Err(format!("Could not resolve {}: {}", host, reason).into());
+    }
+
+    let expires = Instant::now() + Duration::from_secs(ttl.clamp(MIN_TTL, MAX_TTL).into());
+    CACHE
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(key, (ips.clone(), expires));
+    Ok(ips)
+}
+
+async fn lookup_system(
+    host: &str,
+) -> Result<Vec<IpAddr>, Box<dyn std::error::Error + Send + Sync>> {
+    let host = host.to_string();
+    let addrs = tauri::async_runtime::spawn_blocking(move || (host.as_str(), 0).to_socket_addrs())
+        .await
+        .map_err(|e| e.to_string())??;
+    Ok(addrs.map(|addr| addr.ip()).collect())
+}
+
+struct Answer {
+    ips: Vec<IpAddr>,
+    ttl: u32,
+}
+
+async fn query_udp(config: &DnsConfig, host: &str, qtype: u16) -> Result<Answer, String> {
+    let servers = config.server_addrs()?;
+    let host = host.to_string();
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut last_err = String::from("no DNS servers");
+        for server in servers {
+            match query_udp_server(server, &host, qtype) {
+                Ok(answer) => return Ok(answer),
+                Err(e) => last_err = format!("{}: {}", server, e),
+            }
+        }
+        Err(last_err)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+fn query_udp_server(server: SocketAddr, host: &str, qtype: u16) -> Result<Answer, String> {
+    let local: SocketAddr = if server.is_ipv4() {
+        (Ipv4Addr::UNSPECIFIED, 0).into()
+    } else {
+        (Ipv6Addr::UNSPECIFIED, 0).into()
+    };
+    let socket = UdpSocket::bind(local).map_err(|e| e.to_string())?;
+    socket
+        .set_read_timeout(Some(QUERY_TIMEOUT))
+        .map_err(|e| e.to_string())?;
+    let id = (uuid::Uuid::new_v4().as_u128() & 0xffff) as u16;
+    socket
+        .send_to(&encode_query(id, host, qtype)?, server)
+        .map_err(|e| e.to_string())?;
+    let mut buf = [0u8; 1500];
+    loop {
+        let (len, from) = socket.recv_from(&mut buf).map_err(|e| e.to_string())?;
+        // Ignore stray datagrams; a spoofed answer would also have to guess the id
+        if from == server {
+            return decode_answer(&buf[..len], id, qtype);
+        }
+    }
+}
+
+async fn query_doh(config: &DnsConfig, host: &str, qtype: u16) -> Result<Answer, String> {
+    let url = config.doh_url.as_deref().unwrap_or_default();
+    // The DoH endpoint itself is resolved by the system; use an IP URL if that's blocked too
+    let client = crate::http::direct_client(Some(QUERY_TIMEOUT))?;
+    let resp = client
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/dns-message")
+        .header(reqwest::header::ACCEPT, "application/dns-message")
+        .body(encode_query(0, host, qtype)?)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !resp.status().is_success() {
+        return Err(format!("DNS-over-HTTPS returned HTTP {}", resp.status()));
+    }
+    let body = resp.bytes().await.map_err(|e| e.to_string())?;
+    decode_answer(&body, 0, qtype)
+}
+
+fn encode_query(id: u16, host: &str, qtype: u16) -> Result<Vec<u8>, String> {
+    let mut msg = Vec::with_capacity(32 + host.len());
+    msg.extend_from_slice(&id.to_be_bytes());
+    // Standard query, recursion desired, one question
+    msg.extend_from_slice(&[0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+    for label in host.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(format!("Invalid host name: {}", host));
+        }
+        msg.push(label.len() as u8);
+        msg.extend_from_slice(label.as_bytes());
+    }
+    msg.push(0);
+    msg.extend_from_slice(&qtype.to_be_bytes());
+    msg.extend_from_slice(&1u16.to_be_bytes());
+    Ok(msg)
+}
+
+fn decode_answer(msg: &[u8], id: u16, qtype: u16) -> Result<Answer, String> {
+    let invalid = || "Malformed DNS response".to_string();
+    let u16_at = |pos: usize| -> Result<u16, String> {
+        msg.get(pos..pos + 2)
+            .map(|b| u16::from_be_bytes([b[0], b[1]]))
+            .ok_or_else(invalid)
+    };
+    if msg.len() < 12 || u16_at(0)? != id || msg[2] & 0x80 == 0 {
+        return Err(invalid());
+    }
+    match msg[3] & 0x0f {
+        0 => {}
+        3 => return Err("no such domain".to_string()),
+        rcode => return Err(format!("DNS error {}", rcode)),
+    }
+    let questions = u16_at(4)?;
+    let answers = u16_at(6)?;
+
+    let mut pos = 12;
+    for _ in 0..questions {
+        pos = skip_name(msg, pos).ok_or_else(invalid)? + 4;
+    }
+    let mut ips = Vec::new();
+    let mut ttl = MAX_TTL;
+    for _ in 0..answers {
+        pos = skip_name(msg, pos).ok_or_else(invalid)?;
+        let rtype = u16_at(pos)?;
+        let record_ttl = msg
+            .get(pos + 4..pos + 8)
+            .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+            .ok_or_else(invalid)?;
+        let len = u16_at(pos + 8)? as usize;
+        let data = msg.get(pos + 10..pos + 10 + len).ok_or_else(invalid)?;
+        pos += 10 + len;
+        // CNAMEs come before the records they point at, so only the addresses matter
+        if rtype != qtype {
+            continue;
+        }
+        let ip = match data.len() {
+            4 => IpAddr::from(<[u8; 4]>::try_from(data).map_err(|_| invalid())?),
+            16 => IpAddr::from(<[u8; 16]>::try_from(data).map_err(|_| invalid())?),
+            _ => return Err(invalid()),
+        };
+        ips.push(ip);
+        ttl = ttl.min(record_ttl);
+    }
+    Ok(Answer { ips, ttl })
+}
+
+/// Position after the (possibly compressed) name starting at `pos`.
+fn skip_name(msg: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        let len = *msg.get(pos)?;
+        match len {
+            0 => return Some(pos + 1),
+            // A compression pointer ends the name
+            l if l & 0xc0 == 0xc0 => return Some(pos + 2),
+            l => pos += 1 + l as usize,
+        }
+    }
+}
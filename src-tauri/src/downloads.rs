@@ -0,0 +1,1165 @@
+//! Downloads of VOD items for watching offline, persisted in `downloads.json`. Files are filed
+//! under the download folder from Settings, else `Videos/TvX Downloads`, the way media
+//! libraries expect: `Movies/{Title} ({Year})/{Title} ({Year}).ext` and
+//! `Series/{Show}/Season 01/{Show} - S01E02 - {Episode}.ext`. When the file is already there
+//! the collision policy renames, skips or overwrites; `preview_download_layout` shows where a
+//! batch would go without fetching anything. A download counts against its server's connection
+//! limit, is written to a `.part` file renamed once complete, and `download` events report it.
+//!
+//! Before the rename the file is verified: its size against the Content-Length the server sent,
+//! its SHA-256 against the one the request carries (when it does), and optionally that ffprobe
+//! finds something playable in it. A file that fails is corrupt and fetched again, up to the
+//! configured number of retries, before the download is marked corrupt.
+//!
+//! HLS items are fetched segment by segment over a few connections at once and joined in order
+//! into one MPEG-TS file, which is much faster than a single progressive connection with many
+//! providers. Playlists whose segments can't simply be joined (encrypted, fMP4, byte ranges)
+//! are copied by ffmpeg instead.
+
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::{Emitter, Manager, State};
+
+use crate::hls::MediaPlaylist;
+use crate::probe::ProbeResult;
+use crate::proxy::ProxyState;
+use crate::recorder::safe_file_name;
+use crate::servers::{ServerConfig, ServerStore};
+use crate::settings::SettingsStore;
+use crate::store::JsonStore;
+
+/// Least time between `download` progress events for one download.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+const DEFAULT_EXTENSION: &str = "mp4";
+const MAX_RETRIES: u32 = 5;
+const MAX_SEGMENT_CONNECTIONS: u32 = 8;
+/// Tries per HLS segment before the download fails.
+const SEGMENT_ATTEMPTS: u32 = 3;
+/// How often ffmpeg copying an HLS item is checked on.
+const REMUX_POLL: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CollisionPolicy {
+    /// Add ` (2)`, ` (3)`… to the file name.
+    #[default]
+    Rename,
+    /// Keep the existing file and don't download.
+    Skip,
+    /// Replace the existing file.
+    Overwrite,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DownloadSettings {
+    /// Root folder downloads are organized under; `Videos/TvX Downloads` when unset.
+    pub dir: Option<String>,
+    /// What happens when a download's file already exists.
+    pub collisions: CollisionPolicy,
+    /// Record every download's SHA-256, not only those with one to compare against.
+    pub checksums: bool,
+    /// Check with ffprobe that finished downloads hold a video or audio stream.
+    pub probe: bool,
+    /// Times a corrupt download is fetched again before giving up.
+    pub retries: u32,
+    /// HLS segments fetched at once.
+    pub segment_connections: u32,
+}
+
+impl Default for DownloadSettings {
+    fn default() -> Self {
+        Self {
+            dir: None,
+            collisions: CollisionPolicy::default(),
+            checksums: false,
+            probe: false,
+            retries: 2,
+            segment_connections: 4,
+        }
+    }
+}
+
+impl DownloadSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.retries > MAX_RETRIES {
+            return Err(format!(
+                "Corrupt downloads can be retried at most {} times",
+                MAX_RETRIES
+            ));
+        }
+        if self.segment_connections == 0 || self.segment_connections > MAX_SEGMENT_CONNECTIONS {
+            return Err(format!(
+                "HLS downloads use between 1 and {} connections",
+                MAX_SEGMENT_CONNECTIONS
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DownloadKind {
+    Movie,
+    Episode,
+}
+
+/// A VOD item to download, with what its place in the folder layout is worked out from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DownloadRequest {
+    pub server_id: String,
+    pub url: String,
+    pub kind: DownloadKind,
+    /// Movie title, or the episode's own title.
+    pub title: String,
+    #[serde(default)]
+    pub year: Option<u32>,
+    /// Show name; episodes without one are filed as movies.
+    #[serde(default)]
+    pub series: Option<String>,
+    #[serde(default)]
+    pub season: Option<u32>,
+    #[serde(default)]
+    pub episode: Option<u32>,
+    /// Container extension; taken from the URL when unset.
+    #[serde(default)]
+    pub extension: Option<String>,
+    /// Expected SHA-256 of the file, hex, when the provider publishes one.
+    #[serde(default)]
+    pub sha256: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PlacementAction {
+    /// Nothing is at the target yet.
+    New,
+    /// The target is taken; a numbered name is used instead.
+    Renamed,
+    Overwrite,
+    Skip,
+}
+
+/// Where a download would go, from `preview_download_layout`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlannedDownload {
+    pub title: String,
+    /// Path the layout gives the item.
+    pub target: String,
+    /// Path the file is written to; `None` when it is skipped.
+    pub path: Option<String>,
+    pub action: PlacementAction,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DownloadStatus {
+    Downloading,
+    Verifying,
+    Completed,
+    Failed,
+    Cancelled,
+    /// Every attempt failed verification; `error` says why.
+    Corrupt,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Download {
+    pub id: String,
+    pub request: DownloadRequest,
+    pub path: String,
+    pub status: DownloadStatus,
+    pub bytes: u64,
+    /// Size the server announced, when it did.
+    #[serde(default)]
+    pub total_bytes: Option<u64>,
+    /// Segments fetched of an HLS item joined segment by segment.
+    #[serde(default)]
+    pub segments: Option<SegmentProgress>,
+    #[serde(default)]
+    pub error: Option<String>,
+    /// Unix seconds.
+    pub started_at: u64,
+    #[serde(default)]
+    pub finished_at: Option<u64>,
+    /// Times the file was fetched, counting corrupt attempts.
+    #[serde(default)]
+    pub attempts: u32,
+    /// SHA-256 of the file, hex, when one was computed.
+    #[serde(default)]
+    pub sha256: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SegmentProgress {
+    pub done: u32,
+    pub total: u32,
+}
+
+pub type DownloadStore = JsonStore<Vec<Download>>;
+
+/// Cancel flags of the downloads in progress.
+static RUNNING: Mutex<BTreeMap<String, Arc<AtomicBool>>> = Mutex::new(BTreeMap::new());
+
+fn running() -> std::sync::MutexGuard<'static, BTreeMap<String, Arc<AtomicBool>>> {
+    RUNNING.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Opens the store; downloads the app quit in the middle of are marked failed.
+pub fn open(app: &tauri::AppHandle) -> DownloadStore {
+    let store: DownloadStore = JsonStore::open(app, "downloads.json");
+    let interrupted = store.update(|downloads| {
+        for d in downloads.iter_mut().filter(|d| {
+            matches!(
+                d.status,
+                DownloadStatus::Downloading | DownloadStatus::Verifying
+            )
+        }) {
+            d.status = DownloadStatus::Failed;
+            d.error = Some("Interrupted when the app quit".to_string());
+        }
+    });
+    if let Err(e) = interrupted {
+        tracing::warn!("Could not update the download list: {}", e);
+    }
+    store
+}
+
+fn root(app: &tauri::AppHandle, settings: &DownloadSettings) -> Result<PathBuf, String> {
+    match &settings.dir {
+        Some(dir) => Ok(PathBuf::from(dir)),
+        None => Ok(app
+            .path()
+            .video_dir()
+            .map_err(|e| e.to_string())?
+            .join("TvX Downloads")),
+    }
+}
+
+fn extension(request: &DownloadRequest) -> String {
+    request
+        .extension
+        .clone()
+        .or_else(|| {
+            let url = reqwest::Url::parse(&request.url).ok()?;
+            let (_, extension) = url.path().rsplit_once('.')?;
+            (!extension.is_empty()
+                && extension.len() <= 4
+                && extension.chars().all(|c| c.is_ascii_alphanumeric()))
+            .then(|| extension.to_ascii_lowercase())
+        })
+        .filter(|e| !e.is_empty())
+        // HLS items are saved as the MPEG-TS their segments join into
+        .map(|e| if e == "m3u8" { "ts".to_string() } else { e })
+        .unwrap_or_else(|| DEFAULT_EXTENSION.to_string())
+}
+
+/// Path of `request` relative to the download root.
+fn relative_path(request: &DownloadRequest) -> PathBuf {
+    let title = match safe_file_name(&request.title) {
+        t if t.is_empty() => "Untitled".to_string(),
+        t => t,
+    };
+    let extension = extension(request);
+    let series = request
+        .series
+        .as_deref()
+        .map(safe_file_name)
+        .filter(|s| !s.is_empty());
+    match (request.kind, series) {
+        (DownloadKind::Episode, Some(show)) => {
+            let season = request.season.unwrap_or(1);
+            let name = match request.episode {
+                Some(episode) => format!("{} - S{:02}E{:02} - {}", show, season, episode, title),
+                None => format!("{} - S{:02} - {}", show, season, title),
+            };
+            PathBuf::from("Series")
+                .join(&show)
+                .join(format!("Season {:02}", season))
+                .join(format!("{}.{}", name, extension))
+        }
+        _ => {
+            let name = match request.year {
+                Some(year) => format!("{} ({})", title, year),
+                None => title,
+            };
+            PathBuf::from("Movies")
+                .join(&name)
+                .join(format!("{}.{}", name, extension))
+        }
+    }
+}
+
+/// `path` with ` (2)`, ` (3)`… added to its file name, the first that isn't `taken`.
+fn numbered(path: &Path, taken: impl Fn(&Path) -> bool) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned();
+    let extension = path.extension().map(|e| e.to_string_lossy().into_owned());
+    (2..)
+        .map(|n| {
+            let name = match &extension {
+                Some(extension) => format!("{} ({}).{}", stem, n, extension),
+                None => format!("{} ({})", stem, n),
+            };
+            path.with_file_name(name)
+        })
+        .find(|p| !taken(p))
+        .expect("some numbered name is free")
+}
+
+/// Where the file for `target` goes under `policy`, `None` when it is skipped. `taken` tells
+/// whether a path is in use, on disk or by another download.
+fn place(
+    target: &Path,
+    policy: CollisionPolicy,
+    taken: impl Fn(&Path) -> bool,
+) -> (Option<PathBuf>, PlacementAction) {
+    if !taken(target) {
+        return (Some(target.to_path_buf()), PlacementAction::New);
+    }
+    match policy {
+        CollisionPolicy::Rename => (Some(numbered(target, taken)), PlacementAction::Renamed),
+        CollisionPolicy::Skip => (None, PlacementAction::Skip),
+        CollisionPolicy::Overwrite => (Some(target.to_path_buf()), PlacementAction::Overwrite),
+    }
+}
+
+fn part_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".part");
+    path.with_file_name(name)
+}
+
+/// Paths downloads in progress are writing to, which count as taken even before they exist.
+fn in_progress(store: &DownloadStore) -> HashSet<PathBuf> {
+    store.read(|downloads| {
+        downloads
+            .iter()
+            .filter(|d| {
+                matches!(
+                    d.status,
+                    DownloadStatus::Downloading | DownloadStatus::Verifying
+                )
+            })
+            .map(|d| PathBuf::from(&d.path))
+            .collect()
+    })
+}
+
+/// Places each of `requests` in turn, so items of one batch that land on the same path collide
+/// with each other as they would on disk.
+fn plan(
+    root: &Path,
+    settings: &DownloadSettings,
+    mut taken: HashSet<PathBuf>,
+    requests: &[DownloadRequest],
+) -> Vec<PlannedDownload> {
+    requests
+        .iter()
+        .map(|request| {
+            let target = root.join(relative_path(request));
+            let (path, action) = place(&target, settings.collisions, |p| {
+                taken.contains(p) || p.exists()
+            });
+            if let Some(path) = &path {
+                taken.insert(path.clone());
+            }
+            PlannedDownload {
+                title: request.title.clone(),
+                target: target.to_string_lossy().into_owned(),
+                path: path.map(|p| p.to_string_lossy().into_owned()),
+                action,
+            }
+        })
+        .collect()
+}
+
+fn emit(app: &tauri::AppHandle, download: &Download) {
+    let _ = app.emit("download", download);
+}
+
+fn save(app: &tauri::AppHandle, download: &Download) {
+    let saved = app.state::<DownloadStore>().update(|downloads| {
+        match downloads.iter_mut().find(|d| d.id == download.id) {
+            Some(existing) => *existing = download.clone(),
+            None => downloads.push(download.clone()),
+        }
+    });
+    if let Err(e) = saved {
+        tracing::warn!("Could not update the download list: {}", e);
+    }
+    emit(app, download);
+}
+
+/// Fetches `download` into its `.part` file, reporting progress. Returns the file's SHA-256
+/// when `hash` is set.
+async fn fetch(
+    app: &tauri::AppHandle,
+    server: &ServerConfig,
+    download: &mut Download,
+    cancel: &AtomicBool,
+    hash: bool,
+) -> Result<Option<String>, String> {
+    let part = part_path(Path::new(&download.path));
+    if let Some(dir) = part.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    let resolved = crate::resolve::resolve(Some(server), &download.request.url).await;
+    let mut req = crate::http::stream_client(Some(server))?.get(&resolved.url);
+    if let Some(cookie) = &resolved.cookie {
+        req = req.header(reqwest::header::COOKIE, cookie);
+    }
+    let mut resp = crate::http::send(req)
+        .await
+        .map_err(|e| format!("Failed to download: {}", e))?;
+    if !resp.status().is_success() {
+        return Err(format!("The server returned HTTP {}", resp.status()));
+    }
+    if is_playlist(&resp) {
+        let base = resp.url().clone();
+        let text = resp
+            .text()
+            .await
+            .map_err(|e| format!("Failed to read the playlist: {}", e))?;
+        fetch_hls(app, server, download, &resolved, base, &text, cancel).await?;
+        return if hash {
+            hash_file(&part).map(Some)
+        } else {
+            Ok(None)
+        };
+    }
+    download.total_bytes = resp.content_length();
+    let mut file = std::fs::File::create(&part)
+        .map_err(|e| format!("Failed to create {}: {}", part.display(), e))?;
+    let mut hasher = hash.then(Sha256::new);
+    let mut reported = Instant::now();
+    while let Some(chunk) = resp
+        .chunk()
+        .await
+        .map_err(|e| format!("Failed to download: {}", e))?
+    {
+        if cancel.load(Ordering::Relaxed) {
+            return Err("Cancelled".to_string());
+        }
+        file.write_all(&chunk)
+            .map_err(|e| format!("Failed to write {}: {}", part.display(), e))?;
+        if let Some(hasher) = hasher.as_mut() {
+            hasher.update(&chunk);
+        }
+        download.bytes += chunk.len() as u64;
+        if reported.elapsed() >= PROGRESS_INTERVAL {
+            reported = Instant::now();
+            emit(app, download);
+        }
+    }
+    file.flush()
+        .map_err(|e| format!("Failed to write {}: {}", part.display(), e))?;
+    Ok(hasher.map(hex))
+}
+
+fn hex(hasher: Sha256) -> String {
+    hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn hash_file(path: &Path) -> Result<String, String> {
+    let mut file = std::fs::File::open(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        let n = file
+            .read(&mut buf)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        if n == 0 {
+            return Ok(hex(hasher));
+        }
+        hasher.update(&buf[..n]);
+    }
+}
+
+fn is_playlist(resp: &reqwest::Response) -> bool {
+    resp.url().path().to_ascii_lowercase().ends_with(".m3u8")
+        || resp
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|t| t.to_str().ok())
+            .is_some_and(|t| t.to_ascii_lowercase().contains("mpegurl"))
+}
+
+async fn get_text(
+    client: &reqwest::Client,
+    url: &str,
+    cookie: Option<&str>,
+) -> Result<(reqwest::Url, String), String> {
+    let mut req = client.get(url);
+    if let Some(cookie) = cookie {
+        req = req.header(reqwest::header::COOKIE, cookie);
+    }
+    let resp = crate::http::send(req)
+        .await
+        .map_err(|e| format!("Failed to fetch the playlist: {}", e))?;
+    if !resp.status().is_success() {
+        return Err(format!("The playlist returned HTTP {}", resp.status()));
+    }
+    let base = resp.url().clone();
+    let text = resp
+        .text()
+        .await
+        .map_err(|e| format!("Failed to read the playlist: {}", e))?;
+    Ok((base, text))
+}
+
+/// Fetches the HLS item whose playlist `text` came from `base` into the `.part` file of
+/// `download`. A master playlist is followed to its best variant that plays alone.
+async fn fetch_hls(
+    app: &tauri::AppHandle,
+    server: &ServerConfig,
+    download: &mut Download,
+    resolved: &crate::resolve::Resolved,
+    base: reqwest::Url,
+    text: &str,
+    cancel: &AtomicBool,
+) -> Result<(), String> {
+    let client = crate::http::stream_client(Some(server))?;
+    let cookie = resolved.cookie.as_deref();
+    let (url, playlist) = match crate::hls::parse_master(text, &base) {
+        Some(manifest) => {
+            let variant = manifest
+                .variants
+                .iter()
+                .find(|v| v.standalone)
+                .ok_or("The playlist has no variant that plays on its own")?;
+            let (media_base, media) = get_text(&client, &variant.url, cookie).await?;
+            (
+                variant.url.clone(),
+                crate::hls::parse_media(&media, &media_base),
+            )
+        }
+        None => (base.to_string(), crate::hls::parse_media(text, &base)),
+    };
+    if !playlist.ended {
+        return Err("The playlist is a live stream, not a VOD item".to_string());
+    }
+    if playlist.segments.is_empty() {
+        return Err("The playlist has no segments".to_string());
+    }
+    let part = part_path(Path::new(&download.path));
+    if !playlist.stitchable() {
+        tracing::info!(id = %download.id, "Copying the HLS item with ffmpeg");
+        let source = crate::resolve::Resolved {
+            url,
+            cookie: resolved.cookie.clone(),
+        };
+        return remux(app, server, &source, &part, cancel).await;
+    }
+    let connections = app
+        .state::<SettingsStore>()
+        .read(|s| s.downloads.segment_connections)
+        .clamp(1, MAX_SEGMENT_CONNECTIONS);
+    stitch(
+        app,
+        &client,
+        cookie,
+        &playlist,
+        connections,
+        download,
+        &part,
+        cancel,
+    )
+    .await
+}
+
+/// One segment's bytes, tried a few times since a single lost segment fails the download.
+async fn segment(
+    client: reqwest::Client,
+    url: String,
+    cookie: Option<String>,
+) -> Result<Vec<u8>, String> {
+    let mut error = String::new();
+    for _ in 0..SEGMENT_ATTEMPTS {
+        let mut req = client.get(&url);
+        if let Some(cookie) = &cookie {
+            req = req.header(reqwest::header::COOKIE, cookie);
+        }
+        let resp = match crate::http::send(req).await {
+            Ok(resp) if resp.status().is_success() => resp,
+            Ok(resp) => {
+                error = format!("A segment returned HTTP {}", resp.status());
+                continue;
+            }
+            Err(e) => {
+                error = format!("Failed to fetch a segment: {}", e);
+                continue;
+            }
+        };
+        let expected = resp.content_length();
+        match resp.bytes().await {
+            Ok(bytes) if expected.is_none_or(|n| n == bytes.len() as u64) => {
+                return Ok(bytes.to_vec())
+            }
+            Ok(bytes) => {
+                error = format!(
+                    "Got {} bytes of a segment the server announced as {}",
+                    bytes.len(),
+                    expected.unwrap_or_default()
+                );
+            }
+            Err(e) => error = format!("Failed to fetch a segment: {}", e),
+        }
+    }
+    Err(error)
+}
+
+/// Fetches the segments of `playlist`, `connections` at a time, and appends them to `part` in
+/// play order. Segments that arrive early wait for the ones before them, so at most
+/// `connections` are held in memory.
+#[allow(clippy::too_many_arguments)]
+async fn stitch(
+    app: &tauri::AppHandle,
+    client: &reqwest::Client,
+    cookie: Option<&str>,
+    playlist: &MediaPlaylist,
+    connections: u32,
+    download: &mut Download,
+    part: &Path,
+    cancel: &AtomicBool,
+) -> Result<(), String> {
+    let mut file = std::fs::File::create(part)
+        .map_err(|e| format!("Failed to create {}: {}", part.display(), e))?;
+    let mut progress = SegmentProgress {
+        done: 0,
+        total: playlist.segments.len() as u32,
+    };
+    download.segments = Some(progress);
+    let mut queued = playlist.segments.iter();
+    let mut pending = VecDeque::new();
+    let mut reported = Instant::now();
+    let result = loop {
+        while pending.len() < connections as usize {
+            let Some(url) = queued.next() else {
+                break;
+            };
+            pending.push_back(tauri::async_runtime::spawn(segment(
+                client.clone(),
+                url.clone(),
+                cookie.map(str::to_string),
+            )));
+        }
+        let Some(next) = pending.pop_front() else {
+            break Ok(());
+        };
+        let bytes = match next.await {
+            Ok(Ok(bytes)) => bytes,
+            Ok(Err(e)) => break Err(e),
+            Err(e) => break Err(e.to_string()),
+        };
+        if cancel.load(Ordering::Relaxed) {
+            break Err("Cancelled".to_string());
+        }
+        if let Err(e) = file.write_all(&bytes) {
+            break Err(format!("Failed to write {}: {}", part.display(), e));
+        }
+        download.bytes += bytes.len() as u64;
+        progress.done += 1;
+        download.segments = Some(progress);
+        if reported.elapsed() >= PROGRESS_INTERVAL {
+            reported = Instant::now();
+            emit(app, download);
+        }
+    };
+    for task in pending {
+        task.abort();
+    }
+    result?;
+    file.flush()
+        .map_err(|e| format!("Failed to write {}: {}", part.display(), e))
+}
+
+/// Copies the HLS item at `source` into `part` with ffmpeg, through the relay when the server
+/// needs it.
+async fn remux(
+    app: &tauri::AppHandle,
+    server: &ServerConfig,
+    source: &crate::resolve::Resolved,
+    part: &Path,
+    cancel: &AtomicBool,
+) -> Result<(), String> {
+    let proxy = app.state::<ProxyState>();
+    let mut url = source.url.clone();
+    let mut relay = None;
+    if source.needs_relay(Some(server)) {
+        let session = proxy.relay(source, Some(server))?;
+        url = session.url;
+        relay = Some(session.id);
+    }
+    let result = run_ffmpeg(&url, part, cancel).await;
+    if let Some(relay) = &relay {
+        proxy.stop(relay);
+    }
+    result
+}
+
+async fn run_ffmpeg(url: &str, part: &Path, cancel: &AtomicBool) -> Result<(), String> {
+    let mut child = std::process::Command::new(crate::ffmpeg::ffmpeg_path()?)
+        .args(["-hide_banner", "-loglevel", "error", "-y"])
+        .args(crate::transcode::input_args(url))
+        .args(["-map", "0", "-c", "copy", "-f", "mpegts"])
+        .arg(part)
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to start ffmpeg: {}", e))?;
+    // Drained as it comes, so a chatty ffmpeg can't block on a full pipe
+    let errors = child.stderr.take().map(|mut pipe| {
+        std::thread::spawn(move || {
+            let mut text = String::new();
+            let _ = pipe.read_to_string(&mut text);
+            text
+        })
+    });
+    let status = loop {
+        if cancel.load(Ordering::Relaxed) {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err("Cancelled".to_string());
+        }
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) => tokio::time::sleep(REMUX_POLL).await,
+            Err(e) => return Err(format!("Failed to wait for ffmpeg: {}", e)),
+        }
+    };
+    if status.success() {
+        return Ok(());
+    }
+    let stderr = errors
+        .and_then(|reader| reader.join().ok())
+        .unwrap_or_default();
+    Err(match crate::failure::classify_ffmpeg(&stderr) {
+        Some(failure) => failure.message,
+        None => format!(
+            "ffmpeg failed: {}",
+            stderr.lines().last().unwrap_or_default().trim()
+        ),
+    })
+}
+
+/// Why a fetched file doesn't match what the server announced or the request expects.
+fn mismatch(download: &Download, sha256: Option<&str>) -> Option<String> {
+    if let Some(total) = download.total_bytes.filter(|t| *t != download.bytes) {
+        return Some(format!(
+            "Got {} bytes of the {} the server announced",
+            download.bytes, total
+        ));
+    }
+    let expected = download.request.sha256.as_deref()?;
+    let actual = sha256?;
+    (!expected.trim().eq_ignore_ascii_case(actual))
+        .then(|| "The file's checksum doesn't match the provider's".to_string())
+}
+
+/// Why ffprobe's view of a file says it won't play.
+fn unplayable(probed: &ProbeResult) -> Option<String> {
+    if probed.first("video").is_none() && probed.first("audio").is_none() {
+        return Some("The file has no video or audio".to_string());
+    }
+    probed
+        .duration_secs
+        .is_none()
+        .then(|| "The file has no duration; it is likely cut short".to_string())
+}
+
+/// Checks the fetched `.part` file of `download`, returning why it is corrupt.
+async fn verify(
+    download: &Download,
+    sha256: Option<&str>,
+    settings: &DownloadSettings,
+) -> Option<String> {
+    if let Some(reason) = mismatch(download, sha256) {
+        return Some(reason);
+    }
+    if !settings.probe {
+        return None;
+    }
+    if let Err(e) = crate::ffmpeg::ffprobe_path() {
+        tracing::warn!(id = %download.id, "Skipping the ffprobe check: {}", e);
+        return None;
+    }
+    let part = part_path(Path::new(&download.path))
+        .to_string_lossy()
+        .into_owned();
+    let probed =
+        tauri::async_runtime::spawn_blocking(move || crate::probe::run_ffprobe(&part)).await;
+    match probed {
+        Ok(Ok(probed)) => unplayable(&probed),
+        Ok(Err(e)) => Some(e),
+        Err(e) => {
+            tracing::warn!(id = %download.id, "Skipping the ffprobe check: {}", e);
+            None
+        }
+    }
+}
+
+/// One attempt: fetches the file, verifies it and moves it into place. `Ok(Some(_))` says why
+/// the file is corrupt.
+async fn attempt(
+    app: &tauri::AppHandle,
+    server: &ServerConfig,
+    download: &mut Download,
+    settings: &DownloadSettings,
+    cancel: &AtomicBool,
+) -> Result<Option<String>, String> {
+    let hash = settings.checksums || download.request.sha256.is_some();
+    let sha256 = fetch(app, server, download, cancel, hash).await?;
+    download.sha256 = sha256.clone();
+    download.status = DownloadStatus::Verifying;
+    emit(app, download);
+    if let Some(reason) = verify(download, sha256.as_deref(), settings).await {
+        return Ok(Some(reason));
+    }
+    let part = part_path(Path::new(&download.path));
+    std::fs::rename(&part, &download.path)
+        .map_err(|e| format!("Failed to move {} into place: {}", part.display(), e))?;
+    Ok(None)
+}
+
+async fn run(app: tauri::AppHandle, server: ServerConfig, mut download: Download) {
+    let cancel = running().entry(download.id.clone()).or_default().clone();
+    let settings = app.state::<SettingsStore>().read(|s| s.downloads.clone());
+    let result = loop {
+        download.attempts += 1;
+        download.bytes = 0;
+        download.segments = None;
+        download.status = DownloadStatus::Downloading;
+        let result = attempt(&app, &server, &mut download, &settings, &cancel).await;
+        let Ok(Some(reason)) = &result else {
+            break result;
+        };
+        let _ = std::fs::remove_file(part_path(Path::new(&download.path)));
+        if download.attempts > settings.retries {
+            break result;
+        }
+        tracing::warn!(
+            id = %download.id,
+            attempt = download.attempts,
+            "Downloaded file is corrupt, fetching it again: {}",
+            reason
+        );
+        download.error = Some(reason.clone());
+    };
+    running().remove(&download.id);
+    crate::connections::release(&format!("download-{}", download.id));
+    download.finished_at = Some(crate::now_secs());
+    match result {
+        Ok(None) => {
+            download.status = DownloadStatus::Completed;
+            download.error = None;
+            tracing::info!(
+                id = %download.id,
+                path = %download.path,
+                bytes = download.bytes,
+                "Download finished"
+            );
+        }
+        Ok(Some(reason)) => {
+            tracing::warn!(
+                id = %download.id,
+                attempts = download.attempts,
+                "Download is corrupt: {}",
+                reason
+            );
+            download.status = DownloadStatus::Corrupt;
+            download.error = Some(reason);
+        }
+        Err(e) => {
+            let _ = std::fs::remove_file(part_path(Path::new(&download.path)));
+            if cancel.load(Ordering::Relaxed) {
+                download.status = DownloadStatus::Cancelled;
+            } else {
+                tracing::warn!(id = %download.id, "Download failed: {}", e);
+                download.status = DownloadStatus::Failed;
+                download.error = Some(e);
+            }
+        }
+    }
+    save(&app, &download);
+}
+
+/// Where each of `requests` would be saved under the current settings, without downloading.
+#[tauri::command]
+pub fn preview_download_layout(
+    app: tauri::AppHandle,
+    settings: State<'_, SettingsStore>,
+    downloads: State<'_, DownloadStore>,
+    requests: Vec<DownloadRequest>,
+) -> Result<Vec<PlannedDownload>, String> {
+    let settings = settings.read(|s| s.downloads.clone());
+    let root = root(&app, &settings)?;
+    Ok(plan(&root, &settings, in_progress(&downloads), &requests))
+}
+
+/// Starts downloading `request` into its place in the folder layout. Fails when the file
+/// exists and the collision policy is `Skip`.
+#[tauri::command]
+pub async fn start_download(
+    app: tauri::AppHandle,
+    servers: State<'_, ServerStore>,
+    settings: State<'_, SettingsStore>,
+    downloads: State<'_, DownloadStore>,
+    request: DownloadRequest,
+) -> Result<Download, String> {
+    let server = crate::servers::get(&servers, &request.server_id)?;
+    let settings = settings.read(|s| s.downloads.clone());
+    let root = root(&app, &settings)?;
+    let planned = plan(
+        &root,
+        &settings,
+        in_progress(&downloads),
+        std::slice::from_ref(&request),
+    )
+    .remove(0);
+    let path = planned
+        .path
+        .ok_or_else(|| format!("{} is already downloaded", planned.target))?;
+    let reservation =
+        crate::connections::reserve(&app, &server, &request.title, false, None).await?;
+    let download = Download {
+        id: uuid::Uuid::new_v4().simple().to_string(),
+        request,
+        path,
+        status: DownloadStatus::Downloading,
+        bytes: 0,
+        total_bytes: None,
+        segments: None,
+        error: None,
+        started_at: crate::now_secs(),
+        finished_at: None,
+        attempts: 0,
+        sha256: None,
+    };
+    reservation.keep(&format!("download-{}", download.id));
+    save(&app, &download);
+    tracing::info!(id = %download.id, path = %download.path, "Download started");
+    tauri::async_runtime::spawn(run(app.clone(), server, download.clone()));
+    Ok(download)
+}
+
+/// Downloads, oldest first.
+#[tauri::command]
+pub fn list_downloads(downloads: State<'_, DownloadStore>) -> Vec<Download> {
+    downloads.read(|d| d.clone())
+}
+
+/// Stops a download in progress; its partial file is deleted.
+#[tauri::command]
+pub fn cancel_download(id: String) -> Result<(), String> {
+    let running = running();
+    let cancel = running
+        .get(&id)
+        .ok_or_else(|| "The download is not in progress".to_string())?;
+    cancel.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Drops a finished download from the list, deleting its file too when `delete_file` is set.
+#[tauri::command]
+pub fn remove_download(
+    downloads: State<'_, DownloadStore>,
+    id: String,
+    delete_file: bool,
+) -> Result<(), String> {
+    if running().contains_key(&id) {
+        return Err("Cancel the download first".to_string());
+    }
+    let removed = downloads.update(|list| {
+        let index = list.iter().position(|d| d.id == id)?;
+        Some(list.remove(index))
+    })?;
+    let download = removed.ok_or_else(|| format!("No download {}", id))?;
+    if delete_file && download.status == DownloadStatus::Completed {
+        std::fs::remove_file(&download.path)
+            .map_err(|e| format!("Failed to delete {}: {}", download.path, e))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(kind: DownloadKind, title: &str) -> DownloadRequest {
+        DownloadRequest {
+            server_id: "s".to_string(),
+            url: "http://example.com/movie/u/p/42.mkv".to_string(),
+            kind,
+            title: title.to_string(),
+            year: None,
+            series: None,
+            season: None,
+            episode: None,
+            extension: None,
+            sha256: None,
+        }
+    }
+
+    #[test]
+    fn movies_and_episodes_get_their_folders() {
+        let mut movie = request(DownloadKind::Movie, "Alien: Covenant");
+        movie.year = Some(2017);
+        assert_eq!(
+            relative_path(&movie),
+            Path::new("Movies/Alien_ Covenant (2017)/Alien_ Covenant (2017).mkv")
+        );
+        let mut episode = request(DownloadKind::Episode, "Pilot");
+        episode.series = Some("The Show".to_string());
+        episode.season = Some(1);
+        episode.episode = Some(2);
+        episode.extension = Some("mp4".to_string());
+        assert_eq!(
+            relative_path(&episode),
+            Path::new("Series/The Show/Season 01/The Show - S01E02 - Pilot.mp4")
+        );
+        episode.series = None;
+        assert_eq!(relative_path(&episode), Path::new("Movies/Pilot/Pilot.mp4"));
+    }
+
+    #[test]
+    fn extensions_fall_back_to_mp4() {
+        let mut movie = request(DownloadKind::Movie, "Film");
+        movie.url = "http://example.com/play?id=42".to_string();
+        assert_eq!(extension(&movie), "mp4");
+        movie.url = "http://example.com/vod/42/index.m3u8".to_string();
+        assert_eq!(extension(&movie), "ts");
+    }
+
+    #[test]
+    fn collisions_follow_the_policy() {
+        let target = Path::new("/d/Movies/Film/Film.mkv");
+        let taken = |p: &Path| p == target || p == Path::new("/d/Movies/Film/Film (2).mkv");
+        assert_eq!(
+            place(target, CollisionPolicy::Rename, taken),
+            (
+                Some(PathBuf::from("/d/Movies/Film/Film (3).mkv")),
+                PlacementAction::Renamed
+            )
+        );
+        assert_eq!(
+            place(target, CollisionPolicy::Skip, taken),
+            (None, PlacementAction::Skip)
+        );
+        assert_eq!(
+            place(target, CollisionPolicy::Overwrite, taken).1,
+            PlacementAction::Overwrite
+        );
+        assert_eq!(
+            place(target, CollisionPolicy::Skip, |_| false).1,
+            PlacementAction::New
+        );
+    }
+
+    #[test]
+    fn a_batch_collides_with_itself() {
+        let root = std::env::temp_dir().join(format!("tvx-downloads-{}", uuid::Uuid::new_v4()));
+        let requests = vec![
+            request(DownloadKind::Movie, "Film"),
+            request(DownloadKind::Movie, "Film"),
+        ];
+        let planned = plan(
+            &root,
+            &DownloadSettings::default(),
+            HashSet::new(),
+            &requests,
+        );
+        assert_eq!(planned[0].action, PlacementAction::New);
+        assert_eq!(planned[1].action, PlacementAction::Renamed);
+        assert!(planned[1]
+            .path
+            .as_deref()
+            .unwrap()
+            .ends_with("Film (2).mkv"));
+    }
+
+    fn finished(total: Option<u64>, bytes: u64) -> Download {
+        Download {
+            id: "d".to_string(),
+            request: request(DownloadKind::Movie, "Film"),
+            path: "/d/Film.mkv".to_string(),
+            status: DownloadStatus::Verifying,
+            bytes,
+            total_bytes: total,
+            segments: None,
+            error: None,
+            started_at: 0,
+            finished_at: None,
+            attempts: 1,
+            sha256: None,
+        }
+    }
+
+    #[test]
+    fn short_files_are_corrupt() {
+        assert!(mismatch(&finished(Some(100), 60), None).is_some());
+        assert!(mismatch(&finished(Some(100), 100), None).is_none());
+        assert!(mismatch(&finished(None, 60), None).is_none());
+    }
+
+    #[test]
+    fn checksums_are_compared_when_the_request_has_one() {
+        let mut download = finished(Some(3), 3);
+        assert!(mismatch(&download, Some("abc")).is_none());
+        download.request.sha256 = Some("ABC".to_string());
+        assert!(mismatch(&download, Some("abc")).is_none());
+        assert!(mismatch(&download, Some("abd")).is_some());
+    }
+
+    #[test]
+    fn files_without_streams_or_duration_are_unplayable() {
+        let stream = |kind: &str| crate::probe::ProbeStream {
+            index: 0,
+            kind: kind.to_string(),
+            codec: "h264".to_string(),
+            profile: None,
+            width: None,
+            height: None,
+            channels: None,
+            interlaced: false,
+            language: None,
+            title: None,
+            default: true,
+            forced: false,
+        };
+        let mut probed = ProbeResult {
+            format: "matroska,webm".to_string(),
+            duration_secs: Some(5400.0),
+            bit_rate: None,
+            streams: vec![stream("video")],
+        };
+        assert!(unplayable(&probed).is_none());
+        probed.duration_secs = None;
+        assert!(unplayable(&probed).is_some());
+        probed.duration_secs = Some(5400.0);
+        probed.streams = vec![stream("subtitle")];
+        assert!(unplayable(&probed).is_some());
+    }
+}
@@ -0,0 +1,202 @@
+//! ClearKey decryption for playlist channels. Playlists made for Kodi's inputstream.adaptive
+//! give the keys of ClearKey-protected (CENC) streams in `#KODIPROP` lines:
+//!
+//! ```text
+//! #KODIPROP:inputstream.adaptive.license_type=clearkey
+//! #KODIPROP:inputstream.adaptive.license_key=<kid hex>:<key hex>
+//! ```
+//!
+//! The key may also be a JSON Web Key set (`{"keys":[{"kid":…,"k":…}]}`, base64url) or a
+//! `{"<kid hex>":"<key hex>"}` map. ffmpeg decrypts with the key while remuxing to HLS, so
+//! these streams always go through a transcoder session. ffmpeg takes one key for every
+//! track and only decrypts MP4 segments this way, so channels with several different keys and
+//! DASH manifests are refused. License server URLs (Widevine-style key exchange) aren't
+//! supported; nor is any other DRM system.
+
+use std::collections::HashMap;
+
+use base64::Engine;
+
+use crate::servers::{ServerConfig, ServerKind};
+
+const LICENSE_TYPE: &str = "inputstream.adaptive.license_type";
+const LICENSE_KEY: &str = "inputstream.adaptive.license_key";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClearKey {
+    /// Lowercase hex; `None` when the playlist gives only the key.
+    pub kid: Option<String>,
+    /// Lowercase hex, 16 bytes.
+    pub key: String,
+}
+
+/// The ClearKey `license_key` of a playlist entry's KODIPROP lines, if it names one.
+pub fn clearkey_license(properties: &HashMap<String, String>) -> Option<String> {
+    let clearkey = properties.get(LICENSE_TYPE).is_some_and(|t| {
+        matches!(
+            t.to_ascii_lowercase().as_str(),
+            "clearkey" | "org.w3.clearkey"
+        )
+    });
+    clearkey
+        .then(|| properties.get(LICENSE_KEY).cloned())
+        .flatten()
+        .filter(|k| !k.trim().is_empty())
+}
+
+fn hex_key(text: &str) -> Option<String> {
+    let text = text.trim().replace('-', "").to_ascii_lowercase();
+    (text.len() == 32 && text.chars().all(|c| c.is_ascii_hexdigit())).then_some(text)
+}
+
+fn base64url_key(text: &str) -> Option<String> {
+    let bytes = base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(text.trim().trim_end_matches('='))
+        .ok()?;
+    (bytes.len() == 16).then(|| bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// The keys in a `license_key` value.
+pub fn parse_license_key(value: &str) -> Result<Vec<ClearKey>, String> {
+    let value = value.trim();
+    if value.starts_with("http://") || value.starts_with("https://") {
+        return Err(
+            "ClearKey license servers are not supported; the playlist needs to include the keys"
+                .to_string(),
+        );
+    }
+    let invalid = || "The playlist's ClearKey keys are not valid".to_string();
+    let keys = if value.starts_with('{') {
+        let json: serde_json::Value = serde_json::from_str(value).map_err(|_| invalid())?;
+        match json.get("keys").and_then(|k| k.as_array()) {
+            Some(keys) => keys
+                .iter()
+                .map(|k| {
+                    Some(ClearKey {
+                        kid: k["kid"].as_str().and_then(base64url_key),
+                        key: base64url_key(k["k"].as_str()?)?,
+                    })
+                })
+                .collect::<Option<Vec<_>>>(),
+            None => json.as_object().and_then(|map| {
+                map.iter()
+                    .map(|(kid, key)| {
+                        Some(ClearKey {
+                            kid: Some(hex_key(kid)?),
+                            key: hex_key(key.as_str()?)?,
+                        })
+                    })
+                    .collect()
+            }),
+        }
+    } else {
+        value
+            .split(',')
+            .map(|pair| match pair.split_once(':') {
+                Some((kid, key)) => Some(ClearKey {
+                    kid: Some(hex_key(kid)?),
+                    key: hex_key(key)?,
+                }),
+                None => Some(ClearKey {
+                    kid: None,
+                    key: hex_key(pair)?,
+                }),
+            })
+            .collect()
+    };
+    keys.filter(|k: &Vec<ClearKey>| !k.is_empty())
+        .ok_or_else(invalid)
+}
+
+/// ffmpeg input options decrypting `url` with `keys`, to go before the input.
+pub fn input_args(keys: &[ClearKey], url: &str) -> Result<Vec<String>, String> {
+    let path = reqwest::Url::parse(url).map_or_else(|_| url.to_string(), |u| u.path().to_string());
+    if path.to_ascii_lowercase().ends_with(".mpd") {
+        return Err("ClearKey-protected DASH streams are not supported".to_string());
+    }
+    // `-decryption_key` applies to every track, so it can only stand for one key
+    let Some(first) = keys.first() else {
+        return Ok(Vec::new());
+    };
+    if keys.iter().any(|k| k.key != first.key) {
+        return Err(
+            "The channel uses a different ClearKey key per track, which is not supported"
+                .to_string(),
+        );
+    }
+    Ok(vec!["-decryption_key".to_string(), first.key.clone()])
+}
+
+/// The ClearKey keys of the playlist channel playing `url` from `server`, if it has any.
+pub(crate) async fn keys_for(
+    app: &tauri::AppHandle,
+    server: Option<&ServerConfig>,
+    url: &str,
+) -> Result<Option<Vec<ClearKey>>, String> {
+    let Some(server) = server.filter(|s| s.kind == ServerKind::M3u) else {
+        return Ok(None);
+    };
+    let license = match crate::catalog::channels(app, server).await {
+        Ok((_, channels)) => channels
+            .iter()
+            .find(|c| c.id == url)
+            .and_then(|c| c.clearkey.clone()),
+        Err(_) => None,
+    };
+    license.map(|l| parse_license_key(&l)).transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KID: &str = "0123456789abcdef0123456789abcdef";
+    const KEY: &str = "fedcba9876543210fedcba9876543210";
+
+    #[test]
+    fn license_key_reads_hex_pairs() {
+        let keys = parse_license_key(&format!("{}:{}", KID.to_uppercase(), KEY)).unwrap();
+        assert_eq!(
+            keys,
+            vec![ClearKey {
+                kid: Some(KID.to_string()),
+                key: KEY.to_string(),
+            }]
+        );
+        let keys = parse_license_key(&format!("{}:{},{}", KID, KEY, KEY)).unwrap();
+        assert_eq!(keys.len(), 2);
+        assert_eq!(keys[1].kid, None);
+    }
+
+    #[test]
+    fn license_key_reads_jwk_sets() {
+        // base64url of KID and KEY
+        let value = r#"{"keys":[{"kty":"oct","kid":"ASNFZ4mrze8BI0VniavN7w","k":"_ty6mHZUMhD-3LqYdlQyEA"}]}"#;
+        assert_eq!(
+            parse_license_key(value).unwrap(),
+            vec![ClearKey {
+                kid: Some(KID.to_string()),
+                key: KEY.to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn license_key_reads_json_maps() {
+        let value = format!(r#"{{"{}":"{}"}}"#, KID, KEY);
+        assert_eq!(
+            parse_license_key(&value).unwrap(),
+            vec![ClearKey {
+                kid: Some(KID.to_string()),
+                key: KEY.to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn license_key_rejects_servers_and_bad_keys() {
+        assert!(parse_license_key("https://license.example/clearkey").is_err());
+        assert!(parse_license_key(&format!("{}:1234", KID)).is_err());
+        assert!(parse_license_key("").is_err());
+    }
+}
@@ -0,0 +1,240 @@
+//! User curation of channel lists: renamed, hidden and reordered channels and groups, kept per
+//! server in `channel-edits.json` as an overlay on the provider's list. `catalog` applies it
+//! whenever an index is built, so edits survive every refresh; channels are matched by their
+//! backend id and groups by the provider's name, and edits for entries the provider dropped
+//! simply stop applying. Hidden channels stay in the index flagged `hidden`, and every query
+//! surface leaves them out unless it asks for them.
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+use tauri::{Manager, State};
+
+use crate::catalog::{self, CatalogChannel};
+use crate::servers::ServerStore;
+use crate::store::JsonStore;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ItemEdit {
+    pub name: Option<String>,
+    pub hidden: bool,
+}
+
+impl ItemEdit {
+    fn is_empty(&self) -> bool {
+        self.name.is_none() && !self.hidden
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ServerEdits {
+    /// Keyed by channel id.
+    pub channels: HashMap<String, ItemEdit>,
+    /// Keyed by the provider's group name.
+    pub groups: HashMap<String, ItemEdit>,
+    /// Provider group names in display order; unlisted groups follow in provider order.
+    pub group_order: Vec<String>,
+    /// Channel ids shown first within their group, in this order.
+    pub channel_order: Vec<String>,
+}
+
+impl ServerEdits {
+    fn is_empty(&self) -> bool {
+        self.channels.is_empty()
+            && self.groups.is_empty()
+            && self.group_order.is_empty()
+            && self.channel_order.is_empty()
+    }
+}
+
+/// Edits keyed by server id.
+pub type ChannelEditStore = JsonStore<BTreeMap<String, ServerEdits>>;
+
+pub fn open(app: &tauri::AppHandle) -> ChannelEditStore {
+    JsonStore::open(app, "channel-edits.json")
+}
+
+/// The provider's list with the server's edits applied.
+pub fn apply(
+    app: &tauri::AppHandle,
+    server_id: &str,
+    channels: &[CatalogChannel],
+) -> Vec<CatalogChannel> {
+    let store = app.state::<ChannelEditStore>();
+    store.read(|all| match all.get(server_id) {
+        Some(edits) if !edits.is_empty() => apply_edits(edits, channels),
+        _ => channels.to_vec(),
+    })
+}
+
+fn apply_edits(edits: &ServerEdits, channels: &[CatalogChannel]) -> Vec<CatalogChannel> {
+    let group_rank: HashMap<&str, usize> = edits
+        .group_order
+        .iter()
+        .enumerate()
+        .map(|(i, g)| (g.as_str(), i))
+        .collect();
+    let channel_rank: HashMap<&str, usize> = edits
+        .channel_order
+        .iter()
+        .enumerate()
+        .map(|(i, c)| (c.as_str(), i))
+        .collect();
+    // Unlisted groups keep the provider's order after the listed ones
+    let mut first_seen: HashMap<&str, usize> = HashMap::new();
+    for c in channels {
+        if let Some(group) = c.group.as_deref() {
+            let next = first_seen.len();
+            first_seen.entry(group).or_insert(next);
+        }
+    }
+    let mut kept: Vec<((usize, usize, usize), CatalogChannel)> = channels
+        .iter()
+        .enumerate()
+        .map(|(at, c)| {
+            let group = c.group.as_deref().and_then(|g| edits.groups.get(g));
+            let edit = edits.channels.get(&c.id);
+            let group_key = c.group.as_deref().map_or(usize::MAX, |g| {
+                group_rank
+                    .get(g)
+                    .copied()
+                    .unwrap_or_else(|| group_rank.len() + first_seen[g])
+            });
+            let channel_key = channel_rank
+                .get(c.id.as_str())
+                .copied()
+                .unwrap_or(usize::MAX);
+            let mut c = c.clone();
+            c.hidden = group.is_some_and(|g| g.hidden) || edit.is_some_and(|e| e.hidden);
+            if let Some(name) = edit.and_then(|e| e.name.clone()) {
+                c.name = name;
+            }
+            if let Some(name) = group.and_then(|g| g.name.clone()) {
+                c.group = Some(name);
+            }
+            ((group_key, channel_key, at), c)
+        })
+        .collect();
+    if !group_rank.is_empty() || !channel_rank.is_empty() {
+        kept.sort_by_key(|(key, _)| *key);
+    }
+    kept.into_iter().map(|(_, c)| c).collect()
+}
+
+/// Changes the server's edits and rebuilds its index with them.
+fn edit(
+    app: &tauri::AppHandle,
+    server_id: &str,
+    f: impl FnOnce(&mut ServerEdits),
+) -> Result<(), String> {
+    app.state::<ChannelEditStore>().update(|all| {
+        let edits = all.entry(server_id.to_string()).or_default();
+        f(edits);
+        edits.channels.retain(|_, e| !e.is_empty());
+        edits.groups.retain(|_, e| !e.is_empty());
+        if edits.is_empty() {
+            all.remove(server_id);
+        }
+    })?;
+    catalog::reapply_edits(app, server_id);
+    Ok(())
+}
+
+fn new_name(name: Option<String>) -> Option<String> {
+    name.map(|n| n.trim().to_string()).filter(|n| !n.is_empty())
+}
+
+#[tauri::command]
+pub fn get_channel_edits(store: State<'_, ChannelEditStore>, server_id: String) -> ServerEdits {
+    store.read(|all| all.get(&server_id).cloned().unwrap_or_default())
+}
+
+/// Channels hidden on the server (channel or group), for un-hiding them.
+#[tauri::command]
+pub async fn list_hidden_channels(
+    app: tauri::AppHandle,
+    servers: State<'_, ServerStore>,
+    server_id: String,
+) -> Result<Vec<CatalogChannel>, String> {
+    let server = crate::servers::get(&servers, &server_id)?;
+    let (_, channels) = catalog::channels(&app, &server).await?;
+    Ok(channels.iter().filter(|c| c.hidden).cloned().collect())
+}
+
+/// Renames a channel; no name restores the provider's.
+#[tauri::command]
+pub fn rename_channel(
+    app: tauri::AppHandle,
+    server_id: String,
+    channel_id: String,
+    name: Option<String>,
+) -> Result<(), String> {
+    edit(&app, &server_id, |edits| {
+        edits.channels.entry(channel_id).or_default().name = new_name(name);
+    })
+}
+
+#[tauri::command]
+pub fn set_channel_hidden(
+    app: tauri::AppHandle,
+    server_id: String,
+    channel_id: String,
+    hidden: bool,
+) -> Result<(), String> {
+    edit(&app, &server_id, |edits| {
+        edits.channels.entry(channel_id).or_default().hidden = hidden;
+    })
+}
+
+/// Renames a group, given by the provider's name; no name restores it.
+#[tauri::command]
+pub fn rename_group(
+    app: tauri::AppHandle,
+    server_id: String,
+    group: String,
+    name: Option<String>,
+) -> Result<(), String> {
+    edit(&app, &server_id, |edits| {
+        edits.groups.entry(group).or_default().name = new_name(name);
+    })
+}
+
+#[tauri::command]
+pub fn set_group_hidden(
+    app: tauri::AppHandle,
+    server_id: String,
+    group: String,
+    hidden: bool,
+) -> Result<(), String> {
+    edit(&app, &server_id, |edits| {
+        edits.groups.entry(group).or_default().hidden = hidden;
+    })
+}
+
+/// Sets the group order (provider names); an empty list restores the provider's.
+#[tauri::command]
+pub fn reorder_groups(
+    app: tauri::AppHandle,
+    server_id: String,
+    groups: Vec<String>,
+) -> Result<(), String> {
+    edit(&app, &server_id, |edits| edits.group_order = groups)
+}
+
+/// Puts `channel_ids` first within their groups, in this order; an empty list restores the
+/// provider's order.
+#[tauri::command]
+pub fn reorder_channels(
+    app: tauri::AppHandle,
+    server_id: String,
+    channel_ids: Vec<String>,
+) -> Result<(), String> {
+    edit(&app, &server_id, |edits| edits.channel_order = channel_ids)
+}
+
+#[tauri::command]
+pub fn reset_channel_edits(app: tauri::AppHandle, server_id: String) -> Result<(), String> {
+    edit(&app, &server_id, |edits| *edits = ServerEdits::default())
+}
@@ -0,0 +1,819 @@
+//! Emby server client: username/password and Emby Connect sign-in, library items and Live TV.
+//! Catalogs fall back to the offline cache, and favorites/progress go through the outbox.
+
+use std::collections::HashMap;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tauri::{Manager, State};
+
+use crate::cache::CacheKind;
+use crate::offline::{self, Cached, Mutation, MutationError, Submitted};
+use crate::servers::{self, ServerConfig, ServerKind, ServerStore};
+use crate::vod::{VodItem, VodKind};
+use crate::wol;
+
+const CLIENT_NAME: &str = "TvX";
+const CLIENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+const CONNECT_URL: &str = "https://connect.emby.media/service";
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct AuthResult {
+    access_token: String,
+    user: AuthUser,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct AuthUser {
+    id: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ConnectServer {
+    name: String,
+    #[serde(default)]
+    url: Option<String>,
+    #[serde(default)]
+    local_address: Option<String>,
+    access_key: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ExchangeResult {
+    local_user_id: String,
+    access_token: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ItemsResult {
+    #[serde(default)]
+    items: Vec<RawItem>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct RawItem {
+    id: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default, rename = "Type")]
+    item_type: String,
+    #[serde(default)]
+    overview: Option<String>,
+    #[serde(default)]
+    production_year: Option<u32>,
+    #[serde(default)]
+    run_time_ticks: Option<u64>,
+    #[serde(default)]
+    series_name: Option<String>,
+    #[serde(default)]
+    parent_index_number: Option<u32>,
+    #[serde(default)]
+    index_number: Option<u32>,
+    #[serde(default, alias = "Number")]
+    channel_number: Option<String>,
+    #[serde(default)]
+    channel_id: Option<String>,
+    #[serde(default)]
+    start_date: Option<String>,
+    #[serde(default)]
+    end_date: Option<String>,
+    #[serde(default)]
+    episode_title: Option<String>,
+    #[serde(default)]
+    genres: Vec<String>,
+    #[serde(default)]
+    people: Vec<RawPerson>,
+    #[serde(default)]
+    community_rating: Option<f32>,
+    #[serde(default)]
+    image_tags: HashMap<String, String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct RawPerson {
+    name: String,
+    #[serde(default, rename = "Type")]
+    kind: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct PlaybackInfo {
+    #[serde(default)]
+    media_sources: Vec<MediaSource>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct MediaSource {
+    id: String,
+    #[serde(default)]
+    container: Option<String>,
+    #[serde(default)]
+    live_stream_id: Option<String>,
+    #[serde(default)]
+    direct_stream_url: Option<String>,
+    #[serde(default)]
+    transcoding_url: Option<String>,
+}
+
+/// Library item (movie, series, season, episode) as sent to the frontend.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmbyItem {
+    pub id: String,
+    pub name: String,
+    pub item_type: String,
+    pub overview: Option<String>,
+    pub year: Option<u32>,
+    pub duration_secs: Option<u64>,
+    pub series_name: Option<String>,
+    pub season_number: Option<u32>,
+    pub episode_number: Option<u32>,
+    pub image_url: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmbyChannel {
+    pub id: String,
+    pub name: String,
+    pub number: Option<String>,
+    pub image_url: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmbyProgram {
+    pub id: String,
+    pub channel_id: String,
+    pub title: String,
+    pub episode_title: Option<String>,
+    pub overview: Option<String>,
+    pub start: Option<String>,
+    pub end: Option<String>,
+    #[serde(default)]
+    pub genres: Vec<String>,
+}
+
+fn client(server: &ServerConfig) -> Result<reqwest::Client, String> {
+    crate::http::client(Some(server))
+}
+
+fn device_name() -> String {
+    std::env::var("COMPUTERNAME")
+        .or_else(|_| std::env::var("HOSTNAME"))
+        .unwrap_or_else(|_| "Desktop".to_string())
+}
+
+/// Value for the `X-Emby-Authorization` header every Emby endpoint expects.
+fn auth_header(server: &ServerConfig) -> String {
+    format!(
+        r#"Emby UserId="{}", Client="{}", Device="{}", DeviceId="tvx-{}", Version="{}""#,
+        server.user_id.as_deref().unwrap_or_default(),
+        CLIENT_NAME,
+        device_name(),
+        server.id,
+        CLIENT_VERSION
+    )
+}
+
+fn request(
+    client: &reqwest::Client,
+    method: reqwest::Method,
+    server: &ServerConfig,
+    path: &str,
+) -> reqwest::RequestBuilder {
+    let mut req = client
+        .request(method, format!("{}{}", server.base_url(), path))
+        .header("X-Emby-Authorization", auth_header(server));
+    if let Some(token) = &server.access_token {
+        req = req.header("X-Emby-Token", token.as_str());
+    }
+    req
+}
+
+async fn send_json<T: DeserializeOwned>(
+    server: &ServerConfig,
+    req: reqwest::RequestBuilder,
+) -> Result<T, String> {
+    let _permit = crate::http::queue(Some(server)).await;
+    let resp = wol::send_waking(server, req)
+        .await
+        .map_err(|e| format!("Emby request failed: {}", e))?;
+    let status = resp.status();
+    if status == reqwest::StatusCode::UNAUTHORIZED {
+        return Err("Emby rejected the credentials or session token".to_string());
+    }
+    if !status.is_success() {
+        return Err(format!("Emby returned HTTP {}", status));
+    }
+    resp.json()
+        .await
+        .map_err(|e| format!("Invalid Emby response: {}", e))
+}
+
+fn image_url(server: &ServerConfig, item: &RawItem) -> Option<String> {
+    item.image_tags.get("Primary").map(|tag| {
+        format!(
+            "{}/Items/{}/Images/Primary?tag={}",
+            server.base_url(),
+            item.id,
+            tag
+        )
+    })
+}
+
+/// Signs in with the server's username/password, returning it with the new session.
+pub(crate) async fn sign_in(mut server: ServerConfig) -> Result<ServerConfig, String> {
+    if server.kind != ServerKind::Emby {
+        return Err(format!("{} is not an Emby server", server.name));
+    }
+    server.access_token = None;
+    server.user_id = None;
+    let body = serde_json::json!({ "Username": server.username, "Pw": server.password });
+    let auth: AuthResult = send_json(
+        &server,
+        request(
+            &client(&server)?,
+            reqwest::Method::POST,
+            &server,
+            "/Users/AuthenticateByName",
+        )
+        .json(&body),
+    )
+    .await?;
+    server.access_token = Some(auth.access_token);
+    server.user_id = Some(auth.user.id);
+    Ok(server)
+}
+
+/// Authenticates with the stored username/password and saves the resulting session.
+async fn authenticate(store: &ServerStore, server: ServerConfig) -> Result<ServerConfig, String> {
+    servers::upsert(store, sign_in(server).await?)
+}
+
+/// Returns the server with a valid session, signing in first if needed.
+async fn session(store: &ServerStore, server_id: &str) -> Result<ServerConfig, String> {
+    let server = servers::get(store, server_id)?;
+    if server.kind != ServerKind::Emby {
+        return Err(format!("{} is not an Emby server", server.name));
+    }
+    if server.access_token.is_some() && server.user_id.is_some() {
+        return Ok(server);
+    }
+    authenticate(store, server).await
+}
+
+async fn get_items(
+    server: &ServerConfig,
+    path: &str,
+    query: &[(&str, String)],
+) -> Result<Vec<RawItem>, String> {
+    let result: ItemsResult = send_json(
+        server,
+        request(&client(server)?, reqwest::Method::GET, server, path).query(query),
+    )
+    .await?;
+    Ok(result.items)
+}
+
+/// Signs in again when the server no longer accepts the saved session token, e.g. after it
+/// expired while the machine slept.
+pub(crate) async fn revalidate(store: &ServerStore, server_id: &str) -> Result<(), String> {
+    let server = session(store, server_id).await?;
+    let path = format!("/Users/{}", server.user_id.as_deref().unwrap_or_default());
+    let req = request(&client(&server)?, reqwest::Method::GET, &server, &path);
+    let resp = wol::send_waking(&server, req)
+        .await
+        .map_err(|e| format!("Emby request failed: {}", e))?;
+    if resp.status() == reqwest::StatusCode::UNAUTHORIZED {
+        authenticate(store, server).await?;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn emby_sign_in(
+    store: State<'_, ServerStore>,
+    server_id: String,
+) -> Result<ServerConfig, String> {
+    let server = servers::get(&store, &server_id)?;
+    authenticate(&store, server).await
+}
+
+/// Signs in to Emby Connect and saves every linked server, exchanging the Connect access key
+/// for a local session on each. Servers that can't be reached are skipped.
+#[tauri::command]
+pub async fn emby_connect_sign_in(
+    store: State<'_, ServerStore>,
+    username: String,
+    password: String,
+) -> Result<Vec<ServerConfig>, String> {
+    let app_header = format!("{}/{}", CLIENT_NAME, CLIENT_VERSION);
+    // The Connect service itself is never asleep, so it gets no wake-on-LAN settings
+    let connect = ServerConfig::default();
+    let client = client(&connect)?;
+    let auth: AuthResult = send_json(
+        &connect,
+        client
+            .post(format!("{}/user/authenticate", CONNECT_URL))
+            .header("X-Application", app_header.as_str())
+            .form(&[
+                ("nameOrEmail", username.as_str()),
+                ("rawpw", password.as_str()),
+            ]),
+    )
+    .await
+    .map_err(|e| format!("Emby Connect sign-in failed: {}", e))?;
+    let linked: Vec<ConnectServer> = send_json(
+        &connect,
+        client
+            .get(format!("{}/servers", CONNECT_URL))
+            .query(&[("userId", auth.user.id.as_str())])
+            .header("X-Application", app_header.as_str())
+            .header("X-Connect-UserToken", auth.access_token.as_str()),
+    )
+    .await?;
+
+    let mut saved = Vec::new();
+    for linked_server in linked {
+        let addresses = [
+            linked_server.local_address.as_deref(),
+            linked_server.url.as_deref(),
+        ];
+        for address in addresses.into_iter().flatten() {
+            // Re-use the existing entry when signing in again so ids and device ids stay stable
+            let existing_id = store.read(|servers| {
+                servers
+                    .iter()
+                    .find(|s| {
+                        s.kind == ServerKind::Emby && s.base_url() == address.trim_end_matches('/')
+                    })
+                    .map(|s| s.id.clone())
+            });
+            let candidate = ServerConfig {
+                id: existing_id.unwrap_or_default(),
+                name: linked_server.name.clone(),
+                kind: ServerKind::Emby,
+                url: address.to_string(),
+                access_token: Some(linked_server.access_key.clone()),
+                ..Default::default()
+            };
+            let exchange: Result<ExchangeResult, String> = send_json(
+                &candidate,
+                request(
+                    &client,
+                    reqwest::Method::GET,
+                    &candidate,
+                    "/Connect/Exchange",
+                )
+                .query(&[("format", "json"), ("ConnectUserId", auth.user.id.as_str())]),
+            )
+            .await;
+            if let Ok(exchange) = exchange {
+                let server = ServerConfig {
+                    access_token: Some(exchange.access_token),
+                    user_id: Some(exchange.local_user_id),
+                    ..candidate
+                };
+                saved.push(servers::upsert(&store, server)?);
+                break;
+            }
+        }
+    }
+    if saved.is_empty() {
+        return Err(
+            "No reachable Emby servers are linked to this Emby Connect account".to_string(),
+        );
+    }
+    Ok(saved)
+}
+
+/// Lists library items of `item_type` (Movie, Series, Season, Episode), optionally under a parent.
+#[tauri::command]
+pub async fn emby_items(
+    app: tauri::AppHandle,
+    store: State<'_, ServerStore>,
+    server_id: String,
+    item_type: String,
+    parent_id: Option<String>,
+) -> Result<Cached<Vec<EmbyItem>>, String> {
+    let key = format!(
+        "items:{}:{}",
+        item_type,
+        parent_id.as_deref().unwrap_or_default()
+    );
+    offline::cached(
+        &app,
+        CacheKind::Catalogs,
+        &server_id,
+        &key,
+        fetch_items(&store, &server_id, item_type, parent_id),
+    )
+    .await
+}
+
+async fn fetch_items(
+    store: &ServerStore,
+    server_id: &str,
+    item_type: String,
+    parent_id: Option<String>,
+) -> Result<Vec<EmbyItem>, String> {
+    let server = session(store, server_id).await?;
+    let user_id = server.user_id.clone().unwrap_or_default();
+    let mut query = vec![
+        ("IncludeItemTypes", item_type),
+        ("Recursive", "true".to_string()),
+        ("Fields", "Overview,ProductionYear".to_string()),
+        ("SortBy", "SortName".to_string()),
+    ];
+    if let Some(parent_id) = parent_id {
+        query.push(("ParentId", parent_id));
+    }
+    let items = get_items(&server, &format!("/Users/{}/Items", user_id), &query).await?;
+    Ok(items
+        .into_iter()
+        .map(|item| library_item(&server, item))
+        .collect())
+}
+
+fn library_item(server: &ServerConfig, item: RawItem) -> EmbyItem {
+    EmbyItem {
+        image_url: image_url(server, &item),
+        // Emby durations are in 100ns ticks
+        duration_secs: item.run_time_ticks.map(|t| t / 10_000_000),
+        id: item.id,
+        name: item.name,
+        item_type: item.item_type,
+        overview: item.overview,
+        year: item.production_year,
+        series_name: item.series_name,
+        season_number: item.parent_index_number,
+        episode_number: item.index_number,
+    }
+}
+
+/// A series' episodes in airing order.
+pub(crate) async fn episodes(
+    store: &ServerStore,
+    server_id: &str,
+    series_id: &str,
+) -> Result<Vec<EmbyItem>, String> {
+    let server = session(store, server_id).await?;
+    let query = [
+        ("UserId", server.user_id.clone().unwrap_or_default()),
+        ("Fields", "Overview".to_string()),
+    ];
+    let items = get_items(&server, &format!("/Shows/{}/Episodes", series_id), &query).await?;
+    Ok(items
+        .into_iter()
+        .map(|item| library_item(&server, item))
+        .collect())
+}
+
+/// Movies and series with genres and people, for the VOD index (see `vod`).
+pub(crate) async fn library(
+    app: &tauri::AppHandle,
+    server_id: &str,
+) -> Result<Vec<VodItem>, String> {
+    let store = app.state::<ServerStore>();
+    let server = session(&store, server_id).await?;
+    let user_id = server.user_id.clone().unwrap_or_default();
+    let query = [
+        ("IncludeItemTypes", "Movie,Series".to_string()),
+        ("Recursive", "true".to_string()),
+        (
+            "Fields",
+            "Genres,People,ProductionYear,CommunityRating".to_string(),
+        ),
+    ];
+    let items = get_items(&server, &format!("/Users/{}/Items", user_id), &query).await?;
+    Ok(items
+        .into_iter()
+        .map(|item| {
+            let people = |kind: &str| {
+                item.people
+                    .iter()
+                    .filter(|p| p.kind == kind)
+                    .map(|p| p.name.clone())
+                    .collect::<Vec<_>>()
+            };
+            VodItem {
+                server_id: server.id.clone(),
+                kind: if item.item_type == "Series" {
+                    VodKind::Series
+                } else {
+                    VodKind::Movie
+                },
+                cast: people("Actor").into_iter().take(10).collect(),
+                director: people("Director").into_iter().next(),
+                image: image_url(&server, &item),
+                year: item.production_year,
+                rating: item.community_rating,
+                category: None,
+                genres: item.genres,
+                name: item.name,
+                id: item.id,
+            }
+        })
+        .collect())
+}
+
+#[tauri::command]
+pub async fn emby_live_channels(
+    app: tauri::AppHandle,
+    store: State<'_, ServerStore>,
+    server_id: String,
+) -> Result<Cached<Vec<EmbyChannel>>, String> {
+    offline::cached(&app, CacheKind::Catalogs, &server_id, "channels", async {
+        fetch_live_channels(&store, &server_id).await
+    })
+    .await
+}
+
+async fn fetch_live_channels(
+    store: &ServerStore,
+    server_id: &str,
+) -> Result<Vec<EmbyChannel>, String> {
+    live_channels(&session(store, server_id).await?).await
+}
+
+/// Live TV channels of a signed-in server.
+pub(crate) async fn live_channels(server: &ServerConfig) -> Result<Vec<EmbyChannel>, String> {
+    let query = [
+        ("UserId", server.user_id.clone().unwrap_or_default()),
+        ("EnableImages", "true".to_string()),
+    ];
+    let items = get_items(server, "/LiveTv/Channels", &query).await?;
+    Ok(items
+        .into_iter()
+        .map(|item| EmbyChannel {
+            image_url: image_url(server, &item),
+            id: item.id,
+            name: item.name,
+            number: item.channel_number,
+        })
+        .collect())
+}
+
+/// Guide data for the given channels. `from`/`to` are ISO 8601 timestamps bounding the window.
+#[tauri::command]
+pub async fn emby_live_programs(
+    app: tauri::AppHandle,
+    store: State<'_, ServerStore>,
+    server_id: String,
+    channel_ids: Vec<String>,
+    from: Option<String>,
+    to: Option<String>,
+) -> Result<Cached<Vec<EmbyProgram>>, String> {
+    let key = format!(
+        "programs:{}:{}:{}",
+        channel_ids.join(","),
+        from.as_deref().unwrap_or_default(),
+        to.as_deref().unwrap_or_default()
+    );
+    offline::cached(
+        &app,
+        CacheKind::Epg,
+        &server_id,
+        &key,
+        fetch_live_programs(&store, &server_id, channel_ids, from, to),
+    )
+    .await
+}
+
+async fn fetch_live_programs(
+    store: &ServerStore,
+    server_id: &str,
+    channel_ids: Vec<String>,
+    from: Option<String>,
+    to: Option<String>,
+) -> Result<Vec<EmbyProgram>, String> {
+    let server = session(store, server_id).await?;
+    let mut query = vec![
+        ("UserId", server.user_id.clone().unwrap_or_default()),
+        ("ChannelIds", channel_ids.join(",")),
+        ("Fields", "Overview,Genres".to_string()),
+    ];
+    if let Some(from) = from {
+        query.push(("MinEndDate", from));
+    }
+    if let Some(to) = to {
+        query.push(("MaxStartDate", to));
+    }
+    let items = get_items(&server, "/LiveTv/Programs", &query).await?;
+    Ok(items
+        .into_iter()
+        .map(|item| EmbyProgram {
+            id: item.id,
+            channel_id: item.channel_id.unwrap_or_default(),
+            title: item.name,
+            episode_title: item.episode_title,
+            overview: item.overview,
+            start: item.start_date,
+            end: item.end_date,
+            genres: item.genres,
+        })
+        .collect())
+}
+
+/// Resolves a playable URL for a library item or Live TV channel via PlaybackInfo,
+/// which also opens the live stream on the server for channels.
+#[tauri::command]
+pub async fn emby_stream_url(
+    store: State<'_, ServerStore>,
+    server_id: String,
+    item_id: String,
+) -> Result<String, String> {
+    stream_url(&store, &server_id, &item_id).await
+}
+
+pub(crate) async fn stream_url(
+    store: &ServerStore,
+    server_id: &str,
+    item_id: &str,
+) -> Result<String, String> {
+    let server = session(store, server_id).await?;
+    let user_id = server.user_id.clone().unwrap_or_default();
+    let token = server.access_token.clone().unwrap_or_default();
+    let info: PlaybackInfo = send_json(
+        &server,
+        request(
+            &client(&server)?,
+            reqwest::Method::POST,
+            &server,
+            &format!("/Items/{}/PlaybackInfo", item_id),
+        )
+        .query(&[
+            ("UserId", user_id.as_str()),
+            ("IsPlayback", "true"),
+            ("AutoOpenLiveStream", "true"),
+        ])
+        .json(&serde_json::json!({})),
+    )
+    .await?;
+    let source = info
+        .media_sources
+        .into_iter()
+        .next()
+        .ok_or_else(|| "Emby returned no playable media source".to_string())?;
+
+    if let Some(path) = source.direct_stream_url.or(source.transcoding_url) {
+        let separator = if path.contains('?') { '&' } else { '?' };
+        let mut url = format!("{}{}", server.base_url(), path);
+        if !path.contains("api_key=") {
+            url = format!("{}{}api_key={}", url, separator, token);
+        }
+        return Ok(url);
+    }
+    let mut url = format!(
+        "{}/Videos/{}/stream.{}?Static=true&MediaSourceId={}&api_key={}",
+        server.base_url(),
+        item_id,
+        source.container.as_deref().unwrap_or("ts"),
+        urlencoding::encode(&source.id),
+        token
+    );
+    if let Some(live_stream_id) = source.live_stream_id {
+        url.push_str(&format!(
+            "&LiveStreamId={}",
+            urlencoding::encode(&live_stream_id)
+        ));
+    }
+    Ok(url)
+}
+
+/// Sends a request whose response body doesn't matter, telling apart an unreachable server
+/// (worth retrying later) from one that refused the change.
+async fn send_mutation(
+    server: &ServerConfig,
+    req: reqwest::RequestBuilder,
+) -> Result<(), MutationError> {
+    let resp = match wol::send_waking(server, req).await {
+        Ok(resp) => resp,
+        Err(e) if e.is_unreachable() => {
+            return Err(MutationError::Unreachable(format!(
+                "Emby request failed: {}",
+                e
+            )))
+        }
+        Err(e) => {
+            return Err(MutationError::Rejected(format!(
+                "Emby request failed: {}",
+                e
+            )))
+        }
+    };
+    match resp.status() {
+        status if status.is_success() => Ok(()),
+        status if status.is_server_error() => Err(MutationError::Unreachable(format!(
+            "Emby returned HTTP {}",
+            status
+        ))),
+        status => Err(MutationError::Rejected(format!(
+            "Emby returned HTTP {}",
+            status
+        ))),
+    }
+}
+
+pub(crate) async fn apply_favorite(
+    server: &ServerConfig,
+    item_id: &str,
+    favorite: bool,
+) -> Result<(), MutationError> {
+    let method = if favorite {
+        reqwest::Method::POST
+    } else {
+        reqwest::Method::DELETE
+    };
+    let path = format!(
+        "/Users/{}/FavoriteItems/{}",
+        server.user_id.as_deref().unwrap_or_default(),
+        item_id
+    );
+    let client = client(server).map_err(MutationError::Rejected)?;
+    send_mutation(server, request(&client, method, server, &path)).await
+}
+
+pub(crate) async fn apply_progress(
+    server: &ServerConfig,
+    item_id: &str,
+    position_secs: f64,
+    played: bool,
+) -> Result<(), MutationError> {
+    let path = format!(
+        "/Users/{}/Items/{}/UserData",
+        server.user_id.as_deref().unwrap_or_default(),
+        item_id
+    );
+    let body = serde_json::json!({
+        // Emby positions are in 100ns ticks
+        "PlaybackPositionTicks": (position_secs.max(0.0) * 10_000_000.0) as u64,
+        "Played": played,
+    });
+    let client = client(server).map_err(MutationError::Rejected)?;
+    send_mutation(
+        server,
+        request(&client, reqwest::Method::POST, server, &path).json(&body),
+    )
+    .await
+}
+
+/// Sets an item's played state on the server, queueing the change while offline. Clears the
+/// resume position either way.
+pub(crate) async fn set_played(
+    app: &tauri::AppHandle,
+    server_id: &str,
+    item_id: &str,
+    played: bool,
+) -> Result<Submitted, String> {
+    let server = session(&app.state::<ServerStore>(), server_id).await?;
+    let mutation = Mutation::EmbyProgress {
+        item_id: item_id.to_string(),
+        position_secs: 0.0,
+        played,
+    };
+    offline::submit(app, &server, mutation).await
+}
+
+/// Marks or unmarks a library item as a favorite, queueing the change while offline.
+#[tauri::command]
+pub async fn emby_set_favorite(
+    app: tauri::AppHandle,
+    store: State<'_, ServerStore>,
+    server_id: String,
+    item_id: String,
+    favorite: bool,
+) -> Result<Submitted, String> {
+    let server = session(&store, &server_id).await?;
+    offline::submit(&app, &server, Mutation::EmbyFavorite { item_id, favorite }).await
+}
+
+/// Saves the resume position (or watched state) of a library item on the server, queueing the
+/// change while offline.
+#[tauri::command]
+pub async fn emby_report_progress(
+    app: tauri::AppHandle,
+    store: State<'_, ServerStore>,
+    server_id: String,
+    item_id: String,
+    position_secs: f64,
+    played: bool,
+) -> Result<Submitted, String> {
+    let server = session(&store, &server_id).await?;
+    let mutation = Mutation::EmbyProgress {
+        item_id,
+        position_secs,
+        played,
+    };
+    offline::submit(&app, &server, mutation).await
+}
@@ -0,0 +1,352 @@
+//! Import of Enigma2 bouquets (`userbouquet.*.tv`, and `.radio` for radio stations, whose
+//! channels are flagged `radio`). Each bouquet becomes a group and its
+//! services its channels, named from the bouquet's `#DESCRIPTION` lines or, for DVB services,
+//! the receiver's `lamedb`. IPTV services keep their own URL; DVB services are streamed from
+//! the receiver itself (its stream port, 8001), so they need its address. The result is saved
+//! as a generated M3U lineup (see `merge::save_lineup`).
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use tauri::State;
+
+use crate::catalog::CatalogChannel;
+use crate::servers::{ServerConfig, ServerStore};
+
+const STREAM_PORT: u16 = 8001;
+/// Service type codes of IPTV references (DVB is 1).
+const IPTV_TYPES: [&str; 4] = ["4097", "5001", "5002", "8193"];
+/// `flags` bit of markers (section titles) and sub-bouquet references.
+const MARKER_FLAG: u32 = 0x40;
+const DIRECTORY_FLAG: u32 = 0x01;
+
+/// DVB triplet plus namespace, the key lamedb names services by.
+type ServiceKey = (u32, u32, u32, u32);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Enigma2Import {
+    pub server: ServerConfig,
+    pub channels: usize,
+    pub groups: usize,
+    /// Services left out: DVB ones when no receiver address was given, and unnamed ones.
+    pub skipped: usize,
+}
+
+struct Service {
+    reference: String,
+    name: Option<String>,
+}
+
+struct Bouquet {
+    name: String,
+    services: Vec<Service>,
+}
+
+fn hex(field: &str) -> Option<u32> {
+    u32::from_str_radix(field.trim(), 16).ok()
+}
+
+/// `(sid, tsid, onid, namespace)` of a DVB service reference.
+fn service_key(fields: &[&str]) -> Option<ServiceKey> {
+    Some((
+        hex(fields.get(3)?)?,
+        hex(fields.get(4)?)?,
+        hex(fields.get(5)?)?,
+        hex(fields.get(6)?)?,
+    ))
+}
+
+fn parse_bouquet(text: &str, fallback_name: &str) -> Bouquet {
+    let mut bouquet = Bouquet {
+        name: fallback_name.to_string(),
+        services: Vec::new(),
+    };
+    let mut skipping = false;
+    for line in text.lines().map(str::trim) {
+        if let Some(name) = line.strip_prefix("#NAME ") {
+            bouquet.name = name.trim().to_string();
+        } else if let Some(reference) = line.strip_prefix("#SERVICE ") {
+            // Type and flags are decimal; only the fields after them are hex
+            let flags = reference
+                .split(':')
+                .nth(1)
+                .and_then(|f| f.trim().parse::<u32>().ok())
+                .unwrap_or_default();
+            skipping = flags & (MARKER_FLAG | DIRECTORY_FLAG) != 0;
+            if !skipping {
+                bouquet.services.push(Service {
+                    reference: reference.trim().to_string(),
+                    name: None,
+                });
+            }
+        } else if let Some(description) = line.strip_prefix("#DESCRIPTION ") {
+            // Describes the service line above it
+            if let Some(service) = bouquet.services.last_mut().filter(|_| !skipping) {
+                service.name = Some(description.trim().to_string());
+            }
+        }
+    }
+    bouquet
+}
+
+/// Service names from `lamedb` (version 4: `sid:ns:tsid:onid:type:number` then the name on
+/// the next line) or `lamedb5` (`s:sid:ns:tsid:onid:type:number,"name",...`).
+fn parse_lamedb(text: &str) -> HashMap<ServiceKey, String> {
+    let mut names = HashMap::new();
+    let key = |fields: &[&str]| -> Option<ServiceKey> {
+        Some((
+            hex(fields.first()?)?,
+            hex(fields.get(2)?)?,
+            hex(fields.get(3)?)?,
+            hex(fields.get(1)?)?,
+        ))
+    };
+    let mut lines = text.lines();
+    let mut in_services = false;
+    while let Some(line) = lines.next() {
+        let line = line.trim_end();
+        if let Some(rest) = line.strip_prefix("s:") {
+            let (fields, name) = rest.split_once(",\"").unwrap_or((rest, ""));
+            let fields: Vec<&str> = fields.split(':').collect();
+            let name = name.split('"').next().unwrap_or_default();
+            if let Some(key) = key(&fields).filter(|_| !name.is_empty()) {
+                names.insert(key, name.to_string());
+            }
+        } else if line == "services" {
+            in_services = true;
+        } else if line == "end" {
+            in_services = false;
+        } else if in_services {
+            let fields: Vec<&str> = line.split(':').collect();
+            let name = lines.next().unwrap_or_default().trim();
+            // Provider line
+            lines.next();
+            if let Some(key) = key(&fields).filter(|_| !name.is_empty()) {
+                names.insert(key, name.to_string());
+            }
+        }
+    }
+    names
+}
+
+/// `http://host:8001/` for a receiver address given with or without scheme and port.
+fn receiver_base(address: &str) -> Result<reqwest::Url, String> {
+    let address = address.trim().trim_end_matches('/');
+    let address = if address.contains("://") {
+        address.to_string()
+    } else {
+        format!("http://{}", address)
+    };
+    let mut url =
+        reqwest::Url::parse(&address).map_err(|e| format!("Invalid receiver address: {}", e))?;
+    if url.port().is_none() {
+        let _ = url.set_port(Some(STREAM_PORT));
+    }
+    url.set_path("/");
+    Ok(url)
+}
+
+/// Bouquet files in `paths`, expanding directories (an Enigma2 settings folder) to their
+/// `userbouquet.*.tv` and `.radio` files in `bouquets.tv`/`bouquets.radio` order, plus the
+/// lamedb found there.
+fn collect(paths: &[String]) -> Result<(Vec<PathBuf>, Option<PathBuf>), String> {
+    let mut files = Vec::new();
+    let mut lamedb = None;
+    for path in paths.iter().map(Path::new) {
+        if !path.is_dir() {
+            files.push(path.to_path_buf());
+            continue;
+        }
+        for name in ["lamedb5", "lamedb"] {
+            if lamedb.is_none() && path.join(name).is_file() {
+                lamedb = Some(path.join(name));
+            }
+        }
+        let listed: Vec<PathBuf> = ["bouquets.tv", "bouquets.radio"]
+            .iter()
+            .map(|index| fs::read_to_string(path.join(index)).unwrap_or_default())
+            .collect::<Vec<_>>()
+            .iter()
+            .flat_map(|text| text.lines())
+            .filter_map(|l| l.split("FROM BOUQUET \"").nth(1)?.split('"').next())
+            .map(|name| path.join(name))
+            .filter(|p| p.is_file())
+            .collect();
+        let mut others: Vec<PathBuf> = fs::read_dir(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| {
+                p.file_name().and_then(|n| n.to_str()).is_some_and(|n| {
+                    n.starts_with("userbouquet.") && (n.ends_with(".tv") || n.ends_with(".radio"))
+                }) && !listed.contains(p)
+            })
+            .collect();
+        others.sort();
+        files.extend(listed);
+        files.extend(others);
+    }
+    Ok((files, lamedb))
+}
+
+/// Imports Enigma2 bouquet files (or settings folders) as the lineup `name`. `lamedb_path`
+/// names DVB services when the bouquets don't; `receiver` is the box DVB services stream
+/// from. Passing `lineup_id` replaces an earlier import.
+#[tauri::command]
+pub fn import_enigma2_bouquets(
+    app: tauri::AppHandle,
+    store: State<'_, ServerStore>,
+    name: String,
+    paths: Vec<String>,
+    lamedb_path: Option<String>,
+    receiver: Option<String>,
+    lineup_id: Option<String>,
+) -> Result<Enigma2Import, String> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err("The imported lineup needs a name".to_string());
+    }
+    let receiver = receiver
+        .as_deref()
+        .filter(|r| !r.trim().is_empty())
+        .map(receiver_base)
+        .transpose()?;
+    let (files, found_lamedb) = collect(&paths)?;
+    if files.is_empty() {
+        return Err("No userbouquet.*.tv or .radio files found".to_string());
+    }
+    let lamedb = match lamedb_path.map(PathBuf::from).or(found_lamedb) {
+        Some(path) => {
+            let bytes =
+                fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            parse_lamedb(&crate::charset::decode(&bytes, None).0)
+        }
+        None => HashMap::new(),
+    };
+
+    let mut channels = Vec::new();
+    let mut groups = 0;
+    let mut skipped = 0;
+    for file in &files {
+        let bytes =
+            fs::read(file).map_err(|e| format!("Failed to read {}: {}", file.display(), e))?;
+        let fallback = file
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("Bouquet")
+            .trim_start_matches("userbouquet.");
+        let bouquet = parse_bouquet(&crate::charset::decode(&bytes, None).0, fallback);
+        let radio = file.extension().is_some_and(|e| e == "radio");
+        let before = channels.len();
+        for service in bouquet.services {
+            let fields: Vec<&str> = service.reference.split(':').collect();
+            let (url, name) = if IPTV_TYPES.contains(&fields[0]) {
+                // `type:flags:...:url:name`, with `:` in the URL encoded as %3a
+                let Some(url) = fields.get(10).filter(|u| !u.is_empty()) else {
+                    continue;
+                };
+                let url = urlencoding::decode(url)
+                    .map(|u| u.into_owned())
+                    .unwrap_or_else(|_| url.to_string());
+                (url, fields.get(11).map(|n| n.to_string()))
+            } else {
+                let Some(base) = &receiver else {
+                    skipped += 1;
+                    continue;
+                };
+                // The receiver expects the reference with its trailing colon
+                let reference = fields
+                    .iter()
+                    .take(10)
+                    .copied()
+                    .collect::<Vec<_>>()
+                    .join(":");
+                let name = service_key(&fields).and_then(|k| lamedb.get(&k).cloned());
+                (format!("{}{}:", base, reference), name)
+            };
+            let Some(name) = service.name.or(name).filter(|n| !n.is_empty()) else {
+                skipped += 1;
+                continue;
+            };
+            channels.push(CatalogChannel {
+                id: url,
+                name,
+                number: None,
+                group: Some(bouquet.name.clone()),
+                logo: None,
+                epg_id: None,
+                radio,
+                clearkey: None,
+                hidden: false,
+            });
+        }
+        if channels.len() > before {
+            groups += 1;
+        }
+    }
+    if channels.is_empty() {
+        return Err(if skipped > 0 {
+            "The bouquets only have DVB services; enter the receiver's address to stream them"
+                .to_string()
+        } else {
+            "The bouquets have no services".to_string()
+        });
+    }
+    let existing = match &lineup_id {
+        Some(id) => Some(crate::servers::get(&store, id)?),
+        None => None,
+    };
+    let count = channels.len();
+    let server = crate::merge::save_lineup(
+        &app,
+        &store,
+        ServerConfig {
+            id: lineup_id.unwrap_or_default(),
+            name,
+            ..existing.unwrap_or_default()
+        },
+        channels,
+    )?;
+    tracing::info!(lineup = %server.id, channels = count, groups, skipped, "Imported Enigma2 bouquets");
+    Ok(Enigma2Import {
+        server,
+        channels: count,
+        groups,
+        skipped,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bouquet_skips_markers_and_directories() {
+        let text = "\
+#NAME Favourites (TV)
+#SERVICE 1:64:0:0:0:0:0:0:0:0::News
+#DESCRIPTION News
+#SERVICE 1:0:19:283D:3FB:1:C00000:0:0:0:
+#DESCRIPTION Das Erste HD
+#SERVICE 1:832:d:0:0:0:0:0:0:0:
+#DESCRIPTION Numbered marker
+#SERVICE 1:320:0:0:0:0:0:0:0:0::Sports
+#SERVICE 1:7:1:0:0:0:0:0:0:0:FROM BOUQUET \"userbouquet.sub.tv\" ORDER BY bouquet
+#SERVICE 4097:0:1:0:0:0:0:0:0:0:http%3a//example.com/stream.ts:Web channel
+";
+        let bouquet = parse_bouquet(text, "fallback");
+        assert_eq!(bouquet.name, "Favourites (TV)");
+        let names: Vec<Option<&str>> = bouquet.services.iter().map(|s| s.name.as_deref()).collect();
+        assert_eq!(names, [Some("Das Erste HD"), None]);
+        assert!(bouquet.services[1].reference.starts_with("4097:0:1:"));
+    }
+
+    #[test]
+    fn service_key_reads_hex_fields() {
+        let fields: Vec<&str> = "1:0:19:283D:3FB:1:C00000:0:0:0:".split(':').collect();
+        assert_eq!(service_key(&fields), Some((0x283D, 0x3FB, 0x1, 0xC00000)));
+    }
+}
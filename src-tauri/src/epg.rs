@@ -0,0 +1,702 @@
+//! Guide data from every backend, normalized to unix-second programmes and kept in memory per
+//! server as the UI loads it (`get_epg`). The merged store feeds XMLTV export and search.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::sync::Mutex;
+
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::{Manager, State};
+
+use crate::cache::CacheKind;
+use crate::conflicts::{ConflictStrategy, ScheduleOutcome};
+use crate::genre::{self, Genre, GenreInfo};
+use crate::offline;
+use crate::servers::{self, ServerConfig, ServerKind, ServerStore};
+
+/// Programmes that ended longer ago than this are dropped from the store; a week, as long as
+/// provider archives usually keep them for catch-up.
+const KEEP_PAST_SECS: i64 = 7 * 24 * 3600;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Programme {
+    /// Provider event id (TVHeadend event id, Emby program id, portal programme id), where
+    /// there is one.
+    pub id: Option<String>,
+    pub server_id: String,
+    /// Catalog channel id (see `catalog::CatalogChannel::id`).
+    pub channel_id: String,
+    /// Unix seconds.
+    pub start: i64,
+    /// Unix seconds.
+    pub stop: i64,
+    pub title: String,
+    pub subtitle: Option<String>,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub genre: Option<Genre>,
+    /// Can be played back from the provider's archive (see `catchup`).
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub catchup: bool,
+}
+
+/// Unix-second window, `from` inclusive.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EpgRange {
+    pub from: i64,
+    pub to: i64,
+}
+
+static STORE: Mutex<BTreeMap<String, Vec<Programme>>> = Mutex::new(BTreeMap::new());
+
+fn store() -> std::sync::MutexGuard<'static, BTreeMap<String, Vec<Programme>>> {
+    STORE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn digits(s: &str, range: std::ops::Range<usize>) -> Option<i64> {
+    let part = s.get(range)?;
+    if !part.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    part.parse().ok()
+}
+
+/// Parses ISO 8601 timestamps as Emby sends them (`2024-05-01T12:00:00.0000000Z`, optionally
+/// with a `+hh:mm` offset instead of `Z`).
+pub fn parse_iso(s: &str) -> Option<i64> {
+    let s = s.trim();
+    let date = crate::days_from_civil(
+        digits(s, 0..4)?,
+        digits(s, 5..7)? as u32,
+        digits(s, 8..10)? as u32,
+    );
+    let secs = digits(s, 11..13)? * 3600 + digits(s, 14..16)? * 60 + digits(s, 17..19)?;
+    let rest = s[19..].trim_start_matches(|c: char| c == '.' || c.is_ascii_digit());
+    let offset = match rest.as_bytes().first() {
+        Some(b'+') | Some(b'-') => {
+            let sign = if rest.starts_with('-') { -1 } else { 1 };
+            let hhmm = rest[1..].replace(':', "");
+            sign * (digits(&hhmm, 0..2)? * 3600 + digits(&hhmm, 2..4).unwrap_or(0) * 60)
+        }
+        _ => 0,
+    };
+    Some(date * 86_400 + secs - offset)
+}
+
+fn time_parts(unix: i64) -> ((i64, u32, u32), i64, i64, i64) {
+    let secs = unix.rem_euclid(86_400);
+    (
+        crate::civil_date(unix.max(0) as u64),
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60,
+    )
+}
+
+/// `2024-05-01T12:00:00Z`.
+pub fn format_iso(unix: i64) -> String {
+    let ((y, mo, d), h, mi, s) = time_parts(unix);
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", y, mo, d, h, mi, s)
+}
+
+/// XMLTV's `20240501120000 +0000`.
+fn format_xmltv(unix: i64) -> String {
+    let ((y, mo, d), h, mi, s) = time_parts(unix);
+    format!("{:04}{:02}{:02}{:02}{:02}{:02} +0000", y, mo, d, h, mi, s)
+}
+
+/// Xtream panels base64-encode guide text.
+fn decode_text(value: &Value) -> Option<String> {
+    let raw = value.as_str()?;
+    let text = base64::engine::general_purpose::STANDARD
+        .decode(raw)
+        .ok()
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+        .unwrap_or_else(|| raw.to_string());
+    let text = text.trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+fn number(value: &Value) -> Option<i64> {
+    match value {
+        Value::Number(n) => n.as_i64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+async fn fetch_xtream_channel(
+    server: ServerConfig,
+    channel_id: String,
+) -> Result<Vec<Programme>, String> {
+    let _permit = crate::http::queue(Some(&server)).await;
+    let req = crate::http::client(Some(&server))?
+        .get(format!("{}/player_api.php", server.base_url()))
+        .query(&[
+            ("username", server.username.as_str()),
+            ("password", server.password.as_str()),
+            ("action", "get_simple_data_table"),
+            ("stream_id", channel_id.as_str()),
+        ]);
+    let resp = crate::wol::send_waking(&server, req)
+        .await
+        .map_err(|e| format!("Xtream request failed: {}", e))?;
+    if !resp.status().is_success() {
+        return Err(format!("Xtream server returned HTTP {}", resp.status()));
+    }
+    let body: Value = resp
+        .json()
+        .await
+        .map_err(|e| format!("Invalid Xtream response: {}", e))?;
+    let listings = body["epg_listings"].as_array().cloned().unwrap_or_default();
+    Ok(listings
+        .iter()
+        .filter_map(|l| {
+            Some(Programme {
+                id: crate::catalog::text(&l["id"]),
+                server_id: server.id.clone(),
+                channel_id: channel_id.clone(),
+                start: number(&l["start_timestamp"])?,
+                stop: number(&l["stop_timestamp"])?,
+                title: decode_text(&l["title"]).unwrap_or_default(),
+                subtitle: None,
+                description: decode_text(&l["description"]),
+                genre: None,
+                catchup: number(&l["has_archive"]) == Some(1),
+            })
+        })
+        .collect())
+}
+
+async fn fetch_xtream(
+    app: &tauri::AppHandle,
+    server: &ServerConfig,
+    channel_ids: &[String],
+) -> Result<Vec<Programme>, String> {
+    // `http::queue` bounds how many of these actually run at once
+    let tasks: Vec<_> = channel_ids
+        .iter()
+        .map(|id| {
+            let app = app.clone();
+            let server = server.clone();
+            let id = id.clone();
+            tauri::async_runtime::spawn(async move {
+                let key = format!("epg:{}", id);
+                offline::cached(
+                    &app,
+                    CacheKind::Epg,
+                    &server.id.clone(),
+                    &key,
+                    fetch_xtream_channel(server, id),
+                )
+                .await
+                .map(|cached| cached.data)
+            })
+        })
+        .collect();
+    let mut programmes = Vec::new();
+    let mut last_error = None;
+    for task in tasks {
+        match task.await.map_err(|e| e.to_string()).and_then(|r| r) {
+            Ok(found) => programmes.extend(found),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    match last_error {
+        Some(e) if programmes.is_empty() && !channel_ids.is_empty() => Err(e),
+        _ => Ok(programmes),
+    }
+}
+
+async fn fetch(
+    app: &tauri::AppHandle,
+    server: &ServerConfig,
+    channel_ids: &[String],
+    range: EpgRange,
+) -> Result<Vec<Programme>, String> {
+    let id = server.id.clone();
+    match server.kind {
+        ServerKind::Xtream => fetch_xtream(app, server, channel_ids).await,
+        ServerKind::Emby => {
+            let programs = crate::emby::emby_live_programs(
+                app.clone(),
+                app.state(),
+                id.clone(),
+                channel_ids.to_vec(),
+                Some(format_iso(range.from)),
+                Some(format_iso(range.to)),
+            )
+            .await?;
+            Ok(programs
+                .data
+                .into_iter()
+                .filter_map(|p| {
+                    Some(Programme {
+                        id: Some(p.id),
+                        server_id: id.clone(),
+                        channel_id: p.channel_id,
+                        start: parse_iso(p.start.as_deref()?)?,
+                        stop: parse_iso(p.end.as_deref()?)?,
+                        title: p.title,
+                        subtitle: p.episode_title,
+                        description: p.overview,
+                        genre: genre::from_texts(&p.genres),
+                        catchup: false,
+                    })
+                })
+                .collect())
+        }
+        ServerKind::Tvheadend => {
+            let mut programmes = Vec::new();
+            for channel_id in channel_ids {
+                let events = crate::tvheadend::tvh_epg(
+                    app.clone(),
+                    app.state(),
+                    id.clone(),
+                    Some(channel_id.clone()),
+                )
+                .await?;
+                programmes.extend(events.data.into_iter().map(|e| Programme {
+                    id: Some(e.event_id.to_string()),
+                    server_id: id.clone(),
+                    channel_id: e.channel_uuid,
+                    start: e.start,
+                    stop: e.stop,
+                    title: e.title,
+                    subtitle: e.subtitle,
+                    description: e.description,
+                    genre: e.genre.iter().find_map(|&code| genre::from_dvb(code)),
+                    catchup: false,
+                }));
+            }
+            Ok(programmes)
+        }
+        ServerKind::Stalker => {
+            let mut found: Vec<_> = crate::stalker::guide(app, server)
+                .await?
+                .data
+                .into_iter()
+                .filter(|p| channel_ids.contains(&p.channel_id))
+                .collect();
+            // The guide looks ahead; past programmes come from the archive listings
+            if range.from < crate::now_secs() as i64 {
+                for channel_id in channel_ids {
+                    match crate::stalker::archive(app, server, channel_id, range.from, range.to)
+                        .await
+                    {
+                        Ok(past) => found.extend(past),
+                        Err(e) => tracing::warn!(channel_id, "No portal archive: {}", e),
+                    }
+                }
+            }
+            found.sort_by(|a, b| (&a.channel_id, a.start).cmp(&(&b.channel_id, b.start)));
+            found.dedup_by(|later, kept| {
+                let same = later.channel_id == kept.channel_id && later.start == kept.start;
+                kept.archived |= same && later.archived;
+                same
+            });
+            Ok(found
+                .into_iter()
+                .map(|p| Programme {
+                    id: p.id,
+                    server_id: id.clone(),
+                    channel_id: p.channel_id,
+                    start: p.start,
+                    stop: p.stop,
+                    title: p.title,
+                    subtitle: None,
+                    description: p.description,
+                    genre: p.category.as_deref().and_then(genre::from_text),
+                    catchup: p.archived,
+                })
+                .collect())
+        }
+        // No guide source of their own
+        ServerKind::Hdhomerun | ServerKind::Satip | ServerKind::M3u => Ok(Vec::new()),
+    }
+}
+
+/// Replaces the stored programmes of `channel_ids` within `range` with `fresh`.
+fn merge(server_id: &str, channel_ids: &[String], range: EpgRange, fresh: &[Programme]) {
+    let cutoff = crate::now_secs() as i64 - KEEP_PAST_SECS;
+    let mut store = store();
+    let programmes = store.entry(server_id.to_string()).or_default();
+    programmes.retain(|p| {
+        p.stop > cutoff
+            && !(channel_ids.contains(&p.channel_id) && p.stop > range.from && p.start < range.to)
+    });
+    programmes.extend(fresh.iter().cloned());
+    programmes.sort_by(|a, b| (&a.channel_id, a.start).cmp(&(&b.channel_id, b.start)));
+    programmes.dedup_by(|a, b| a.channel_id == b.channel_id && a.start == b.start);
+}
+
+/// Fetches guide data for the channels and adds it to the store, shifted by the server's
+/// `epg_offset_minutes`.
+pub async fn load(
+    app: &tauri::AppHandle,
+    server: &ServerConfig,
+    channel_ids: &[String],
+    range: EpgRange,
+) -> Result<Vec<Programme>, String> {
+    let shift = i64::from(server.epg_offset_minutes.unwrap_or(0)) * 60;
+    let source_range = EpgRange {
+        from: range.from - shift,
+        to: range.to - shift,
+    };
+    let fresh: Vec<Programme> = fetch(app, server, channel_ids, source_range)
+        .await?
+        .into_iter()
+        .map(|mut p| {
+            p.start += shift;
+            p.stop += shift;
+            p
+        })
+        .filter(|p| p.stop > range.from && p.start < range.to)
+        .collect();
+    merge(&server.id, channel_ids, range, &fresh);
+    Ok(fresh)
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EpgSearchGroup {
+    pub server_id: String,
+    pub channel_id: String,
+    /// From the channel index when it's loaded.
+    pub channel_name: Option<String>,
+    pub logo: Option<String>,
+    /// Matching airings, earliest first.
+    pub programmes: Vec<Programme>,
+}
+
+/// Whether every word of the query appears in the title, subtitle or description.
+fn matches(p: &Programme, terms: &[String]) -> bool {
+    let mut haystack = p.title.to_lowercase();
+    for extra in [&p.subtitle, &p.description].into_iter().flatten() {
+        haystack.push('\n');
+        haystack.push_str(&extra.to_lowercase());
+    }
+    terms.iter().all(|t| haystack.contains(t.as_str()))
+}
+
+/// `s` escaped for XML text and attribute values.
+pub(crate) fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            // Not allowed in XML 1.0
+            c if (c as u32) < 0x20 && !matches!(c, '\t' | '\n' | '\r') => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Guide data for the channels in `range`, fetching it from the server.
+#[tauri::command]
+pub async fn get_epg(
+    app: tauri::AppHandle,
+    store: State<'_, ServerStore>,
+    server_id: String,
+    channel_ids: Vec<String>,
+    range: EpgRange,
+) -> Result<Vec<Programme>, String> {
+    let server = servers::get(&store, &server_id)?;
+    load(&app, &server, &channel_ids, range).await
+}
+
+/// Writes the server's guide for the channels in `range` as XMLTV. Channels are identified
+/// by their XMLTV id where the provider has one, so other tools can match them to their own
+/// lineups. Returns the number of programmes written.
+#[tauri::command]
+pub async fn export_epg_xmltv(
+    app: tauri::AppHandle,
+    store: State<'_, ServerStore>,
+    server_id: String,
+    channel_ids: Vec<String>,
+    range: EpgRange,
+    path: String,
+) -> Result<usize, String> {
+    let server = servers::get(&store, &server_id)?;
+    let catalog = crate::catalog::channels(&app, &server).await?.1;
+    let programmes = load(&app, &server, &channel_ids, range).await?;
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<tv generator-info-name=\"TvX\">\n",
+    );
+    let mut xmltv_ids = BTreeMap::new();
+    for id in &channel_ids {
+        let channel = catalog.iter().find(|c| &c.id == id);
+        let xmltv_id = channel
+            .and_then(|c| c.epg_id.clone())
+            .unwrap_or_else(|| format!("{}.{}", id, server.id));
+        let _ = writeln!(xml, "  <channel id=\"{}\">", escape(&xmltv_id));
+        let name = channel.map_or(id.as_str(), |c| c.name.as_str());
+        let _ = writeln!(xml, "    <display-name>{}</display-name>", escape(name));
+        if let Some(logo) = channel.and_then(|c| c.logo.as_deref()) {
+            let _ = writeln!(xml, "    <icon src=\"{}\"/>", escape(logo));
+        }
+        xml.push_str("  </channel>\n");
+        xmltv_ids.insert(id.clone(), xmltv_id);
+    }
+    let mut written = 0;
+    for p in &programmes {
+        let Some(channel) = xmltv_ids.get(&p.channel_id) else {
+            continue;
+        };
+        let _ = writeln!(
+            xml,
+            "  <programme start=\"{}\" stop=\"{}\" channel=\"{}\">",
+            format_xmltv(p.start),
+            format_xmltv(p.stop),
+            escape(channel)
+        );
+        let _ = writeln!(xml, "    <title>{}</title>", escape(&p.title));
+        if let Some(subtitle) = &p.subtitle {
+            let _ = writeln!(xml, "    <sub-title>{}</sub-title>", escape(subtitle));
+        }
+        if let Some(desc) = &p.description {
+            let _ = writeln!(xml, "    <desc>{}</desc>", escape(desc));
+        }
+        xml.push_str("  </programme>\n");
+        written += 1;
+    }
+    xml.push_str("</tv>\n");
+    std::fs::write(&path, xml).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    Ok(written)
+}
+
+/// Searches the guide data loaded so far for programmes airing between `from` and `to` (unix
+/// seconds). Results are grouped by channel; groups with a title match come first, then by
+/// earliest airing. Use `add_epg_reminder` or `record_programme` on a result to act on it.
+#[tauri::command]
+pub fn search_epg(query: String, from: i64, to: i64) -> Vec<EpgSearchGroup> {
+    let terms: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+    if terms.is_empty() {
+        return Vec::new();
+    }
+    let hidden = crate::catalog::hidden_channels();
+    let mut groups: BTreeMap<(String, String), Vec<Programme>> = BTreeMap::new();
+    for (server_id, programmes) in store().iter() {
+        let hidden = hidden.get(server_id);
+        for p in programmes
+            .iter()
+            .filter(|p| p.stop > from && p.start < to && matches(p, &terms))
+            .filter(|p| !hidden.is_some_and(|ids| ids.contains(&p.channel_id)))
+        {
+            groups
+                .entry((server_id.clone(), p.channel_id.clone()))
+                .or_default()
+                .push(p.clone());
+        }
+    }
+    let mut groups: Vec<EpgSearchGroup> = groups
+        .into_iter()
+        .map(|((server_id, channel_id), programmes)| {
+            let channel = crate::catalog::lookup(&server_id, &channel_id);
+            EpgSearchGroup {
+                channel_name: channel.as_ref().map(|c| c.name.clone()),
+                logo: channel.and_then(|c| c.logo),
+                server_id,
+                channel_id,
+                programmes,
+            }
+        })
+        .collect();
+    let title_match = |g: &EpgSearchGroup| {
+        g.programmes.iter().any(|p| {
+            let title = p.title.to_lowercase();
+            terms.iter().all(|t| title.contains(t.as_str()))
+        })
+    };
+    groups.sort_by_key(|g| (!title_match(g), g.programmes[0].start));
+    groups
+}
+
+/// Records a guide entry on the server it came from, where the server can record. Collisions
+/// with the server's recording limit are resolved with `strategy`, or reported without
+/// scheduling anything when there is none (see `conflicts`). `priority` is 0 (important) to 4
+/// (unimportant).
+#[tauri::command]
+pub async fn record_programme(
+    store: State<'_, ServerStore>,
+    programme: Programme,
+    strategy: Option<ConflictStrategy>,
+    priority: Option<u32>,
+) -> Result<ScheduleOutcome, String> {
+    crate::conflicts::record(store, programme, strategy, priority).await
+}
+
+/// Loaded guide entries on any server overlapping `from..to`.
+pub(crate) fn airing_between(from: i64, to: i64) -> Vec<Programme> {
+    store()
+        .values()
+        .flatten()
+        .filter(|p| p.start < to && p.stop > from)
+        .cloned()
+        .collect()
+}
+
+/// The loaded guide entry airing on a channel at `at`.
+pub(crate) fn airing_at(server_id: &str, channel_id: &str, at: i64) -> Option<Programme> {
+    store()
+        .get(server_id)?
+        .iter()
+        .find(|p| p.channel_id == channel_id && p.start <= at && at < p.stop)
+        .cloned()
+}
+
+/// Loaded guide entries on a channel overlapping `from..to`.
+pub(crate) fn airing_on(server_id: &str, channel_id: &str, from: i64, to: i64) -> Vec<Programme> {
+    store()
+        .get(server_id)
+        .into_iter()
+        .flatten()
+        .filter(|p| p.channel_id == channel_id && p.start < to && p.stop > from)
+        .cloned()
+        .collect()
+}
+
+/// Later airings of `programme` (same title, and subtitle where both have one) on its server
+/// in the loaded guide data, soonest first.
+pub(crate) fn airings(programme: &Programme) -> Vec<Programme> {
+    let now = crate::now_secs() as i64;
+    let title = programme.title.to_lowercase();
+    let mut airings: Vec<Programme> = store()
+        .get(&programme.server_id)
+        .into_iter()
+        .flatten()
+        .filter(|p| {
+            p.start > now
+                && (p.channel_id != programme.channel_id || p.start != programme.start)
+                && p.title.to_lowercase() == title
+                && (p.subtitle.is_none()
+                    || programme.subtitle.is_none()
+                    || p.subtitle == programme.subtitle)
+        })
+        .cloned()
+        .collect();
+    airings.sort_by_key(|p| p.start);
+    airings
+}
+
+/// Genres with their display labels and guide colours.
+#[tauri::command]
+pub fn get_epg_genres() -> Vec<GenreInfo> {
+    Genre::ALL.into_iter().map(Genre::info).collect()
+}
+
+/// Programmes airing at `at` (default now) across the loaded guide data, optionally only
+/// one genre ("Sports now").
+#[tauri::command]
+pub fn get_epg_on_now(genre: Option<Genre>, at: Option<i64>) -> Vec<Programme> {
+    let at = at.unwrap_or_else(|| crate::now_secs() as i64);
+    let hidden = crate::catalog::hidden_channels();
+    store()
+        .iter()
+        .flat_map(|(server_id, programmes)| {
+            let hidden = hidden.get(server_id);
+            programmes
+                .iter()
+                .filter(move |p| !hidden.is_some_and(|ids| ids.contains(&p.channel_id)))
+        })
+        .filter(|p| p.start <= at && p.stop > at && genre.is_none_or(|g| p.genre == Some(g)))
+        .cloned()
+        .collect()
+}
+
+/// The programme airing on a channel at `at` and the one after it, from the loaded guide data.
+pub fn now_next(
+    server_id: &str,
+    channel_id: &str,
+    at: i64,
+) -> (Option<Programme>, Option<Programme>) {
+    let store = store();
+    // Programmes are kept in start order
+    let mut upcoming = store
+        .get(server_id)
+        .into_iter()
+        .flatten()
+        .filter(|p| p.channel_id == channel_id && p.stop > at);
+    match upcoming.next() {
+        Some(now) if now.start <= at => (Some(now.clone()), upcoming.next().cloned()),
+        next => (None, next.cloned()),
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OffsetSuggestion {
+    /// Value for the server's `epgOffsetMinutes` (replacing, not adding to, the current one).
+    pub offset_minutes: i32,
+    /// How far that moves the guide from its current position.
+    pub change_minutes: i32,
+    /// The guide entry the observed title matched.
+    pub programme: Programme,
+}
+
+/// Suggests a guide offset for a server from what a channel is actually showing: `title` is
+/// the programme seen on the stream at `at` (default now). Looks for that title in the guide
+/// within 12 hours and returns the smallest shift, in whole quarter hours where possible,
+/// that puts `at` inside it. `None` when the title isn't in the guide.
+#[tauri::command]
+pub async fn suggest_epg_offset(
+    app: tauri::AppHandle,
+    store: State<'_, ServerStore>,
+    server_id: String,
+    channel_id: String,
+    title: String,
+    at: Option<i64>,
+) -> Result<Option<OffsetSuggestion>, String> {
+    const WINDOW: i64 = 12 * 3600;
+    const QUARTER: i64 = 15 * 60;
+    let server = servers::get(&store, &server_id)?;
+    let at = at.unwrap_or_else(|| crate::now_secs() as i64);
+    let range = EpgRange {
+        from: at - WINDOW,
+        to: at + WINDOW,
+    };
+    let observed = title.trim().to_lowercase();
+    if observed.is_empty() {
+        return Err("Enter the title that is on now".to_string());
+    }
+    let programmes = load(&app, &server, std::slice::from_ref(&channel_id), range).await?;
+    let covers = |p: &Programme, shift: i64| p.start + shift <= at && at < p.stop + shift;
+    let best = programmes
+        .into_iter()
+        .filter(|p| {
+            let title = p.title.trim().to_lowercase();
+            // An untitled entry is contained in every title
+            !title.is_empty() && (title.contains(&observed) || observed.contains(&title))
+        })
+        .map(|p| {
+            let exact = if covers(&p, 0) {
+                0
+            } else if at < p.start {
+                at - p.start
+            } else {
+                at - p.stop + 60
+            };
+            let rounded = (exact as f64 / QUARTER as f64).round() as i64 * QUARTER;
+            let shift = if covers(&p, rounded) { rounded } else { exact };
+            (shift, p)
+        })
+        .min_by_key(|(shift, _)| shift.abs());
+    let current = server.epg_offset_minutes.unwrap_or(0);
+    Ok(best.map(|(shift, programme)| {
+        let change_minutes = (shift / 60) as i32;
+        OffsetSuggestion {
+            offset_minutes: current + change_minutes,
+            change_minutes,
+            programme,
+        }
+    }))
+}
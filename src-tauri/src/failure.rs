@@ -0,0 +1,289 @@
+//! Diagnosis of streams that won't play. Upstream answers (HTTP status, the start of an error
+//! body, geo-block pages served with 200, ffprobe's messages) are classified into a
+//! `FailureCode`, and for Xtream servers the account is checked to tell an expired
+//! subscription from one whose connections are all in use, so the player can say which
+//! instead of showing a generic error.
+
+use serde::Serialize;
+use tauri::State;
+
+use crate::proxy::ProxyState;
+use crate::servers::{ServerConfig, ServerKind, ServerStore};
+
+/// How much of an error body is kept to look for hints.
+pub const BODY_SNIFF: usize = 4096;
+
+const GEO_HINTS: &[&str] = &[
+    "geo",
+    "country",
+    "region",
+    "your location",
+    "not available in your",
+];
+const LIMIT_HINTS: &[&str] = &[
+    "max connection",
+    "maximum connection",
+    "max_connections",
+    "connection limit",
+    "too many connections",
+    "already in use",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureCode {
+    /// Login rejected, or the subscription expired or was disabled.
+    AuthExpired,
+    /// Every connection the subscription allows is in use.
+    MaxConnections,
+    GeoBlocked,
+    /// The stream no longer exists on the server.
+    DeadLink,
+    ServerError,
+    Unreachable,
+    Unknown,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamFailure {
+    pub code: FailureCode,
+    /// Upstream HTTP status, when there was one.
+    pub status: Option<u16>,
+    /// What to tell the user.
+    pub message: String,
+    pub max_connections: Option<u32>,
+    pub active_connections: Option<u32>,
+}
+
+impl StreamFailure {
+    pub fn new(code: FailureCode, status: Option<u16>) -> Self {
+        let http = status.map(|s| format!(" (HTTP {})", s)).unwrap_or_default();
+        let message = match code {
+            FailureCode::AuthExpired => "The provider rejected your login. Your subscription may \
+                have expired or been disabled."
+                .to_string(),
+            FailureCode::MaxConnections => "Your subscription's connection limit is reached. Stop \
+                playback on another device or app and try again."
+                .to_string(),
+            FailureCode::GeoBlocked => "This stream isn't available in your country. The \
+                server's proxy setting or a VPN may get around it."
+                .to_string(),
+            FailureCode::DeadLink => "This stream no longer exists on the server. Refreshing the \
+                channel list may fix the link."
+                .to_string(),
+            FailureCode::ServerError => {
+                format!(
+                    "The server failed to deliver the stream{}. Try again later.",
+                    http
+                )
+            }
+            FailureCode::Unreachable => "The server could not be reached.".to_string(),
+            FailureCode::Unknown => format!("The server refused the stream{}.", http),
+        };
+        Self {
+            code,
+            status,
+            message,
+            max_connections: None,
+            active_connections: None,
+        }
+    }
+}
+
+fn mentions(body: &str, hints: &[&str]) -> bool {
+    let body = body.to_lowercase();
+    hints.iter().any(|h| body.contains(h))
+}
+
+/// Classifies an upstream response from its status, content type and the start of its body;
+/// `None` when it looks like a stream.
+pub fn classify(status: u16, content_type: &str, body: &[u8]) -> Option<FailureCode> {
+    let body = String::from_utf8_lossy(body);
+    let code = match status {
+        // Geo-blocking CDNs often answer with an HTML page instead of an error status
+        200..=299 if content_type.starts_with("text/html") && mentions(&body, GEO_HINTS) => {
+            FailureCode::GeoBlocked
+        }
+        200..=399 => return None,
+        451 => FailureCode::GeoBlocked,
+        // Used by Xtream panels for "maximum connections reached"
+        456 | 458 => FailureCode::MaxConnections,
+        _ if mentions(&body, LIMIT_HINTS) => FailureCode::MaxConnections,
+        403 if mentions(&body, GEO_HINTS) => FailureCode::GeoBlocked,
+        401 | 403 => FailureCode::AuthExpired,
+        404 | 410 => FailureCode::DeadLink,
+        500..=599 => FailureCode::ServerError,
+        _ => FailureCode::Unknown,
+    };
+    Some(code)
+}
+
+/// Classifies ffmpeg/ffprobe error output.
+pub fn classify_ffmpeg(stderr: &str) -> Option<StreamFailure> {
+    let status = ["Server returned ", "HTTP error "]
+        .iter()
+        .find_map(|marker| {
+            let (_, rest) = stderr.split_once(marker)?;
+            rest.get(..3)?.parse::<u16>().ok()
+        });
+    if let Some(status) = status {
+        let code = classify(status, "", stderr.as_bytes())?;
+        return Some(StreamFailure::new(code, Some(status)));
+    }
+    if stderr.contains("Server returned 4XX") {
+        return Some(StreamFailure::new(FailureCode::Unknown, None));
+    }
+    if stderr.contains("Server returned 5XX") {
+        return Some(StreamFailure::new(FailureCode::ServerError, None));
+    }
+    let unreachable = [
+        "Connection refused",
+        "Connection timed out",
+        "Failed to resolve hostname",
+        "Name or service not known",
+        "No route to host",
+    ];
+    unreachable
+        .iter()
+        .any(|m| stderr.contains(m))
+        .then(|| StreamFailure::new(FailureCode::Unreachable, None))
+}
+
+/// Refines `failure` with the Xtream account's state: an inactive or expired subscription, or
+/// all of its connections in use.
+async fn check_account(server: &ServerConfig, failure: StreamFailure) -> StreamFailure {
+    let Ok(info) = crate::account::fetch(server).await else {
+        return failure;
+    };
+    if !info.authorized {
+        return StreamFailure::new(FailureCode::AuthExpired, failure.status);
+    }
+    if let Some(expires) = info.expires_at.filter(|_| info.is_expired()) {
+        let (year, month, day) = crate::civil_date(expires);
+        return StreamFailure {
+            message: format!(
+                "Your subscription expired on {:04}-{:02}-{:02}. Renew it with your provider to keep watching.",
+                year, month, day
+            ),
+            ..StreamFailure::new(FailureCode::AuthExpired, failure.status)
+        };
+    }
+    if let Some(status) = info.status.filter(|s| !s.eq_ignore_ascii_case("active")) {
+        return StreamFailure {
+            message: format!(
+                "Your subscription is {}. Contact your provider to reactivate it.",
+                status.to_lowercase()
+            ),
+            ..StreamFailure::new(FailureCode::AuthExpired, failure.status)
+        };
+    }
+    match (info.max_connections, info.active_connections) {
+        (Some(max), Some(active)) if max > 0 && active >= max => StreamFailure {
+            message: format!(
+                "Your subscription allows {} and {} in use. Stop playback on another device or app and try again.",
+                if max == 1 {
+                    "1 connection".to_string()
+                } else {
+                    format!("{} connections", max)
+                },
+                if max == 1 { "it's" } else { "they're all" }
+            ),
+            max_connections: Some(max),
+            active_connections: Some(active),
+            ..StreamFailure::new(FailureCode::MaxConnections, failure.status)
+        },
+        (max, active) => StreamFailure {
+            max_connections: max,
+            active_connections: active,
+            ..failure
+        },
+    }
+}
+
+/// Adds what the server's account reveals, for failures an account problem can cause.
+pub async fn explain(server: Option<&ServerConfig>, failure: StreamFailure) -> StreamFailure {
+    match server {
+        Some(server)
+            if server.kind == ServerKind::Xtream
+                && matches!(
+                    failure.code,
+                    FailureCode::AuthExpired | FailureCode::MaxConnections | FailureCode::Unknown
+                ) =>
+        {
+            check_account(server, failure).await
+        }
+        _ => failure,
+    }
+}
+
+/// Requests `url` and classifies the answer; `None` when a stream comes back.
+async fn fetch(url: &str, server: Option<&ServerConfig>) -> Option<StreamFailure> {
+    let req = crate::http::stream_client(server).ok()?.get(url);
+    let mut resp = match crate::http::send(req).await {
+        Ok(resp) => resp,
+        Err(e) if e.is_unreachable() => {
+            return Some(StreamFailure::new(FailureCode::Unreachable, None))
+        }
+        Err(e) => {
+            return Some(StreamFailure {
+                message: format!("The stream could not be opened: {}", e),
+                ..StreamFailure::new(FailureCode::Unknown, None)
+            })
+        }
+    };
+    let status = resp.status().as_u16();
+    let content_type = resp
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    if resp.status().is_success() && !content_type.starts_with("text/html") {
+        return None;
+    }
+    let mut body = Vec::new();
+    while body.len() < BODY_SNIFF {
+        match resp.chunk().await {
+            Ok(Some(chunk)) => body.extend_from_slice(&chunk),
+            _ => break,
+        }
+    }
+    let code = classify(status, &content_type, &body)?;
+    Some(StreamFailure::new(code, Some(status)))
+}
+
+/// Works out why a stream failed. `window_label` lets the proxy's record of the window's relay
+/// and the window's watch session fill in what the frontend doesn't know; `None` when the
+/// stream answers normally now.
+#[tauri::command]
+pub async fn diagnose_stream(
+    servers: State<'_, ServerStore>,
+    proxy: State<'_, ProxyState>,
+    url: String,
+    server_id: Option<String>,
+    window_label: Option<String>,
+) -> Result<Option<StreamFailure>, String> {
+    let relayed = window_label
+        .as_deref()
+        .and_then(|label| proxy.failure_owned_by(label));
+    let server_id = server_id
+        .or_else(|| relayed.as_ref().and_then(|(_, id)| id.clone()))
+        .or_else(|| {
+            let label = window_label.as_deref()?;
+            crate::history::watching_server(label)
+                .or_else(|| crate::history::live_channel(label).map(|(server_id, _)| server_id))
+        });
+    let server = server_id.and_then(|id| crate::servers::get(&servers, &id).ok());
+    let failure = match relayed {
+        Some((failure, _)) => Some(failure),
+        // The proxy forwards the upstream answer, so local URLs are fetched without the
+        // server's outbound proxy
+        None if proxy.is_local(&url) => fetch(&url, None).await,
+        None => fetch(&url, server.as_ref()).await,
+    };
+    Ok(match failure {
+        Some(failure) => Some(explain(server.as_ref(), failure).await),
+        None => None,
+    })
+}
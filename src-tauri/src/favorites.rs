@@ -0,0 +1,44 @@
+//! Favorite channels per server, in the user's order, persisted in `favorite-channels.json`.
+//! The frontend owns the favorites UI and mirrors each server's list here so background work
+//! (recommendations, zapping, remote clients) can use it.
+
+use std::collections::BTreeMap;
+
+use tauri::State;
+
+use crate::store::JsonStore;
+
+pub type FavoriteStore = JsonStore<BTreeMap<String, Vec<String>>>;
+
+pub fn open(app: &tauri::AppHandle) -> FavoriteStore {
+    JsonStore::open(app, "favorite-channels.json")
+}
+
+pub fn is_favorite(store: &FavoriteStore, server_id: &str, channel_id: &str) -> bool {
+    store.read(|favorites| {
+        favorites
+            .get(server_id)
+            .is_some_and(|ids| ids.iter().any(|id| id == channel_id))
+    })
+}
+
+/// Replaces a server's favorite channels (catalog channel ids, in display order).
+#[tauri::command]
+pub fn set_favorite_channels(
+    store: State<'_, FavoriteStore>,
+    server_id: String,
+    channel_ids: Vec<String>,
+) -> Result<(), String> {
+    store.update(|favorites| {
+        if channel_ids.is_empty() {
+            favorites.remove(&server_id);
+        } else {
+            favorites.insert(server_id, channel_ids);
+        }
+    })
+}
+
+#[tauri::command]
+pub fn get_favorite_channels(store: State<'_, FavoriteStore>, server_id: String) -> Vec<String> {
+    store.read(|favorites| favorites.get(&server_id).cloned().unwrap_or_default())
+}
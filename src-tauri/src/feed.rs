@@ -0,0 +1,111 @@
+//! Fan-out of backend events to remote clients (WebSocket now, other transports later).
+//! App events already emitted to the frontend are forwarded as `{"event", "payload"}` JSON,
+//! along with `epg-now-next` whenever the guide's current or next programme on a window's
+//! channel changes.
+
+use std::collections::BTreeMap;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::Serialize;
+use tauri::{Listener, Manager};
+
+/// Frontend events that are also useful to remote clients.
+const FORWARDED: [&str; 9] = [
+    "playback-progress",
+    "playback-warning",
+    "hdhomerun-recording",
+    "mpv-property-change",
+    "video-window-opened",
+    "video-window-closed",
+    "audio-focus-changed",
+    "recording",
+    "epg-reminder",
+];
+
+/// How often windows' channels are checked against the guide.
+const NOW_NEXT_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Default)]
+pub struct EventFeed {
+    subscribers: Mutex<Vec<Sender<String>>>,
+}
+
+#[derive(Serialize)]
+struct FeedMessage<'a> {
+    event: &'a str,
+    payload: serde_json::Value,
+}
+
+impl EventFeed {
+    pub fn subscribe(&self) -> Receiver<String> {
+        let (tx, rx) = mpsc::channel();
+        self.subscribers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(tx);
+        rx
+    }
+
+    /// Sends an event to every subscriber, dropping the ones that went away.
+    pub fn publish(&self, event: &str, payload: serde_json::Value) {
+        let Ok(message) = serde_json::to_string(&FeedMessage { event, payload }) else {
+            return;
+        };
+        self.subscribers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .retain(|tx| tx.send(message.clone()).is_ok());
+    }
+}
+
+/// Forwards `FORWARDED` app events into the feed and starts watching the guide.
+pub fn start(app: &tauri::AppHandle) {
+    for event in FORWARDED {
+        let handle = app.clone();
+        app.listen_any(event, move |e| {
+            let payload = serde_json::from_str(e.payload()).unwrap_or(serde_json::Value::Null);
+            handle.state::<EventFeed>().publish(event, payload);
+        });
+    }
+    let app = app.clone();
+    std::thread::spawn(move || watch_now_next(app));
+}
+
+/// The guide's current and next programme on the channel playing in `label`, as the
+/// `epg-now-next` payload. `None` when the window isn't playing a catalog channel.
+pub fn now_next(label: &str) -> Option<serde_json::Value> {
+    let (server_id, channel_id, _) = crate::zap::current(label)?;
+    let (now, next) = crate::epg::now_next(&server_id, &channel_id, crate::now_secs() as i64);
+    Some(serde_json::json!({
+        "label": label,
+        "serverId": server_id,
+        "channelId": channel_id,
+        "now": now,
+        "next": next,
+    }))
+}
+
+/// Publishes `epg-now-next` for windows whose now or next changed since the last check: a
+/// new channel, a programme ending or fresh guide data.
+fn watch_now_next(app: tauri::AppHandle) {
+    let mut sent: BTreeMap<String, serde_json::Value> = BTreeMap::new();
+    loop {
+        let windows = crate::remote::video_windows(&app);
+        sent.retain(|label, _| windows.iter().any(|w| w.label() == label));
+        for window in windows {
+            let label = window.label();
+            let Some(payload) = now_next(label) else {
+                sent.remove(label);
+                continue;
+            };
+            if sent.get(label) != Some(&payload) {
+                app.state::<EventFeed>()
+                    .publish("epg-now-next", payload.clone());
+                sent.insert(label.to_string(), payload);
+            }
+        }
+        std::thread::sleep(NOW_NEXT_INTERVAL);
+    }
+}
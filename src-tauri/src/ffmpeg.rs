@@ -0,0 +1,36 @@
+//! Locating the ffmpeg/ffprobe binaries used for remuxing, transcoding and probing.
+
+use std::path::PathBuf;
+
+fn find(name: &str) -> Option<PathBuf> {
+    if let Ok(path) = which::which(name) {
+        return Some(path);
+    }
+    let candidates: Vec<PathBuf> = if cfg!(target_os = "windows") {
+        let exe = format!("{}.exe", name);
+        ["ProgramFiles", "ProgramFiles(x86)", "LOCALAPPDATA"]
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .map(|dir| PathBuf::from(dir).join("ffmpeg").join("bin").join(&exe))
+            .collect()
+    } else {
+        // GUI apps on macOS don't inherit the shell PATH, so Homebrew installs are missed
+        ["/opt/homebrew/bin", "/usr/local/bin", "/usr/bin"]
+            .iter()
+            .map(|dir| PathBuf::from(dir).join(name))
+            .collect()
+    };
+    candidates.into_iter().find(|p| p.exists())
+}
+
+pub fn ffmpeg_path() -> Result<PathBuf, String> {
+    find("ffmpeg").ok_or_else(|| {
+        "ffmpeg not found. Install ffmpeg and ensure it is in your PATH.".to_string()
+    })
+}
+
+pub fn ffprobe_path() -> Result<PathBuf, String> {
+    find("ffprobe").ok_or_else(|| {
+        "ffprobe not found. Install ffmpeg and ensure it is in your PATH.".to_string()
+    })
+}
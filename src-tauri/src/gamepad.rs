@@ -0,0 +1,327 @@
+//! Game controller input for couch use. Buttons and the left stick are mapped to a small set of
+//! actions and sent to the focused window: video windows are driven directly (pause, seek,
+//! zap, close), other windows get `gamepad-input` and move focus themselves. Held directions
+//! repeat like a held arrow key. Needs the `gamepad` cargo feature (gilrs); without it no
+//! controller is read and `list_gamepads` reports that.
+
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tauri::{Emitter, Manager};
+
+use crate::remote::PlayerCommand;
+use crate::zap::ZapDirection;
+
+/// Seconds skipped back and forward in video windows.
+const SEEK_BACK_SECS: f64 = -10.0;
+const SEEK_FORWARD_SECS: f64 = 30.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+// Only read from a controller with the `gamepad` feature
+#[cfg_attr(not(feature = "gamepad"), allow(dead_code))]
+pub enum GamepadAction {
+    Up,
+    Down,
+    Left,
+    Right,
+    /// A / cross.
+    Select,
+    /// B / circle.
+    Back,
+    /// Start.
+    Menu,
+    /// X / square.
+    PlayPause,
+    /// Y / triangle.
+    Info,
+    /// Right bumper.
+    ChannelUp,
+    /// Left bumper.
+    ChannelDown,
+    /// Left trigger.
+    SeekBack,
+    /// Right trigger.
+    SeekForward,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Gamepad {
+    pub id: usize,
+    pub name: String,
+}
+
+static CONNECTED: Mutex<Vec<Gamepad>> = Mutex::new(Vec::new());
+
+fn connected() -> std::sync::MutexGuard<'static, Vec<Gamepad>> {
+    CONNECTED.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn focused_window(app: &tauri::AppHandle) -> Option<tauri::WebviewWindow> {
+    app.webview_windows()
+        .into_values()
+        .find(|w| w.is_focused().unwrap_or(false))
+}
+
+/// Carries out `action` in a video window; false for actions the page handles itself.
+fn control_video(app: &tauri::AppHandle, label: &str, action: GamepadAction) -> bool {
+    let command = match action {
+        GamepadAction::PlayPause | GamepadAction::Select => PlayerCommand::TogglePause,
+        GamepadAction::Left | GamepadAction::SeekBack => PlayerCommand::SeekBy {
+            seconds: SEEK_BACK_SECS,
+        },
+        GamepadAction::Right | GamepadAction::SeekForward => PlayerCommand::SeekBy {
+            seconds: SEEK_FORWARD_SECS,
+        },
+        GamepadAction::Up
+        | GamepadAction::Down
+        | GamepadAction::ChannelUp
+        | GamepadAction::ChannelDown => {
+            let direction = if matches!(action, GamepadAction::Up | GamepadAction::ChannelUp) {
+                ZapDirection::Next
+            } else {
+                ZapDirection::Previous
+            };
+            let app = app.clone();
+            let label = label.to_string();
+            tauri::async_runtime::spawn(async move {
+                let favorites = app.state();
+                if let Err(e) =
+                    crate::zap::zap(app.clone(), favorites, label, direction, None).await
+                {
+                    tracing::debug!("Gamepad zap failed: {}", e);
+                }
+            });
+            return true;
+        }
+        GamepadAction::Back => {
+            if let Some(window) = app.get_webview_window(label) {
+                let _ = window.close();
+            }
+            return true;
+        }
+        GamepadAction::Menu | GamepadAction::Info => return false,
+    };
+    if let Err(e) = crate::remote::send_player_command(app, label, &command) {
+        tracing::debug!("Gamepad command for {} failed: {}", label, e);
+    }
+    true
+}
+
+/// Sends `action` to the focused window.
+fn dispatch(app: &tauri::AppHandle, action: GamepadAction) {
+    let Some(window) = focused_window(app) else {
+        return;
+    };
+    let label = window.label().to_string();
+    // While locked the buttons only reach the page, i.e. the lock screen
+    if !crate::lock::is_locked()
+        && label.starts_with("video-")
+        && control_video(app, &label, action)
+    {
+        return;
+    }
+    let _ = app.emit_to(label.as_str(), "gamepad-input", action);
+}
+
+/// Reads controllers for the life of the app.
+pub fn start(app: &tauri::AppHandle) {
+    let app = app.clone();
+    std::thread::spawn(move || {
+        let emit_app = app.clone();
+        let result = native::run(
+            move |action| dispatch(&app, action),
+            move |pads| {
+                *connected() = pads;
+                let _ = emit_app.emit("gamepads-changed", connected().clone());
+            },
+        );
+        if let Err(e) = result {
+            tracing::info!("Gamepad input unavailable: {}", e);
+        }
+    });
+}
+
+/// Controllers currently connected.
+#[tauri::command]
+pub fn list_gamepads() -> Result<Vec<Gamepad>, String> {
+    native::available()?;
+    Ok(connected().clone())
+}
+
+#[cfg(feature = "gamepad")]
+mod native {
+    use std::time::{Duration, Instant};
+
+    use gilrs::{Axis, Button, EventType, Gilrs};
+
+    use super::{Gamepad, GamepadAction};
+
+    const POLL_INTERVAL: Duration = Duration::from_millis(16);
+    /// How long a direction is held before it repeats, and how often it then repeats.
+    const REPEAT_DELAY: Duration = Duration::from_millis(400);
+    const REPEAT_INTERVAL: Duration = Duration::from_millis(120);
+    /// Stick deflection that counts as a direction, and below which it is released.
+    const STICK_PRESS: f32 = 0.6;
+    const STICK_RELEASE: f32 = 0.3;
+
+    pub fn available() -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Directions repeat while held; buttons fire once per press.
+    fn repeats(action: GamepadAction) -> bool {
+        matches!(
+            action,
+            GamepadAction::Up | GamepadAction::Down | GamepadAction::Left | GamepadAction::Right
+        )
+    }
+
+    /// Turns a held direction into repeated actions.
+    #[derive(Default)]
+    struct Repeat {
+        held: Option<(GamepadAction, Instant)>,
+    }
+
+    impl Repeat {
+        /// Records a press; returns whether the action fires now.
+        fn press(&mut self, action: GamepadAction) -> bool {
+            if repeats(action) {
+                if self.held.is_some_and(|(held, _)| held == action) {
+                    return false;
+                }
+                self.held = Some((action, Instant::now() + REPEAT_DELAY));
+            }
+            true
+        }
+
+        fn release(&mut self, action: GamepadAction) {
+            if self.held.is_some_and(|(held, _)| held == action) {
+                self.held = None;
+            }
+        }
+
+        /// The held action, if it is due to repeat.
+        fn due(&mut self) -> Option<GamepadAction> {
+            let (action, at) = self.held?;
+            let now = Instant::now();
+            if now < at {
+                return None;
+            }
+            self.held = Some((action, now + REPEAT_INTERVAL));
+            Some(action)
+        }
+    }
+
+    fn action(button: Button) -> Option<GamepadAction> {
+        Some(match button {
+            Button::DPadUp => GamepadAction::Up,
+            Button::DPadDown => GamepadAction::Down,
+            Button::DPadLeft => GamepadAction::Left,
+            Button::DPadRight => GamepadAction::Right,
+            Button::South => GamepadAction::Select,
+            Button::East => GamepadAction::Back,
+            Button::Start => GamepadAction::Menu,
+            Button::West => GamepadAction::PlayPause,
+            Button::North => GamepadAction::Info,
+            Button::RightTrigger => GamepadAction::ChannelUp,
+            Button::LeftTrigger => GamepadAction::ChannelDown,
+            Button::LeftTrigger2 => GamepadAction::SeekBack,
+            Button::RightTrigger2 => GamepadAction::SeekForward,
+            _ => return None,
+        })
+    }
+
+    fn pads(gilrs: &Gilrs) -> Vec<Gamepad> {
+        gilrs
+            .gamepads()
+            .map(|(id, pad)| Gamepad {
+                id: id.into(),
+                name: pad.name().to_string(),
+            })
+            .collect()
+    }
+
+    pub fn run(
+        on_action: impl Fn(GamepadAction),
+        on_connected: impl Fn(Vec<Gamepad>),
+    ) -> Result<(), String> {
+        let mut gilrs = Gilrs::new().map_err(|e| e.to_string())?;
+        on_connected(pads(&gilrs));
+        let mut repeat = Repeat::default();
+        // Direction the stick is held in, per axis
+        let mut stick: [Option<GamepadAction>; 2] = [None, None];
+        let press = |repeat: &mut Repeat, action: GamepadAction| {
+            if repeat.press(action) {
+                on_action(action);
+            }
+        };
+        loop {
+            while let Some(event) = gilrs.next_event() {
+                match event.event {
+                    EventType::ButtonPressed(button, _) => {
+                        if let Some(action) = action(button) {
+                            press(&mut repeat, action);
+                        }
+                    }
+                    EventType::ButtonReleased(button, _) => {
+                        if let Some(action) = action(button) {
+                            repeat.release(action);
+                        }
+                    }
+                    EventType::AxisChanged(axis, value, _) => {
+                        let (slot, negative, positive) = match axis {
+                            Axis::LeftStickX => (0, GamepadAction::Left, GamepadAction::Right),
+                            // Up is positive
+                            Axis::LeftStickY => (1, GamepadAction::Down, GamepadAction::Up),
+                            _ => continue,
+                        };
+                        let held = if value <= -STICK_PRESS {
+                            Some(negative)
+                        } else if value >= STICK_PRESS {
+                            Some(positive)
+                        } else if value.abs() > STICK_RELEASE {
+                            stick[slot]
+                        } else {
+                            None
+                        };
+                        if held != stick[slot] {
+                            if let Some(previous) = stick[slot] {
+                                repeat.release(previous);
+                            }
+                            if let Some(action) = held {
+                                press(&mut repeat, action);
+                            }
+                            stick[slot] = held;
+                        }
+                    }
+                    EventType::Connected | EventType::Disconnected => {
+                        on_connected(pads(&gilrs));
+                    }
+                    _ => {}
+                }
+            }
+            if let Some(action) = repeat.due() {
+                on_action(action);
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    }
+}
+
+#[cfg(not(feature = "gamepad"))]
+mod native {
+    use super::{Gamepad, GamepadAction};
+
+    pub fn available() -> Result<(), String> {
+        Err("This build of TvX does not include gamepad support".to_string())
+    }
+
+    pub fn run(
+        _on_action: impl Fn(GamepadAction),
+        _on_connected: impl Fn(Vec<Gamepad>),
+    ) -> Result<(), String> {
+        available()
+    }
+}
@@ -0,0 +1,273 @@
+//! Normalized programme genres. Providers describe genres as free text in any language (Emby,
+//! XMLTV `<category>`) or as DVB content nibbles (TVHeadend); both map onto one small set so
+//! the guide can colour-code and filter across sources.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Genre {
+    Movie,
+    Series,
+    News,
+    Sports,
+    Kids,
+    Music,
+    Documentary,
+    Entertainment,
+    Arts,
+    Education,
+    Lifestyle,
+    Religion,
+    Adult,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GenreInfo {
+    pub genre: Genre,
+    pub label: &'static str,
+    /// `#rrggbb` for guide cells.
+    pub color: &'static str,
+}
+
+impl Genre {
+    pub const ALL: [Genre; 13] = [
+        Genre::Movie,
+        Genre::Series,
+        Genre::News,
+        Genre::Sports,
+        Genre::Kids,
+        Genre::Music,
+        Genre::Documentary,
+        Genre::Entertainment,
+        Genre::Arts,
+        Genre::Education,
+        Genre::Lifestyle,
+        Genre::Religion,
+        Genre::Adult,
+    ];
+
+    pub fn info(self) -> GenreInfo {
+        let (label, color) = match self {
+            Genre::Movie => ("Movie", "#8e44ad"),
+            Genre::Series => ("Series", "#2980b9"),
+            Genre::News => ("News", "#c0392b"),
+            Genre::Sports => ("Sports", "#27ae60"),
+            Genre::Kids => ("Kids", "#f39c12"),
+            Genre::Music => ("Music", "#e84393"),
+            Genre::Documentary => ("Documentary", "#16a085"),
+            Genre::Entertainment => ("Entertainment", "#d35400"),
+            Genre::Arts => ("Arts & Culture", "#6c5ce7"),
+            Genre::Education => ("Education", "#0984e3"),
+            Genre::Lifestyle => ("Lifestyle", "#00b894"),
+            Genre::Religion => ("Religion", "#b2bec3"),
+            Genre::Adult => ("Adult", "#636e72"),
+        };
+        GenreInfo {
+            genre: self,
+            label,
+            color,
+        }
+    }
+}
+
+/// Keyword stems per genre, matched against lowercased provider text. Order matters: the first
+/// genre with a match wins, so specific genres come before catch-alls like series.
+const KEYWORDS: &[(Genre, &[&str])] = &[
+    (Genre::Adult, &["adult", "erotic", "xxx"]),
+    (
+        Genre::Sports,
+        &[
+            "sport",
+            "football",
+            "soccer",
+            "fútbol",
+            "futbol",
+            "fußball",
+            "fussball",
+            "calcio",
+            "tennis",
+            "golf",
+            "racing",
+            "motor",
+            "basketball",
+            "hockey",
+            "baseball",
+            "boxing",
+            "rugby",
+            "cricket",
+            "wrestling",
+            "olymp",
+        ],
+    ),
+    (
+        Genre::News,
+        &[
+            "news",
+            "nachrichten",
+            "actualité",
+            "informations",
+            "noticias",
+            "notizie",
+            "journal",
+            "current affairs",
+            "weather",
+            "wetter",
+        ],
+    ),
+    (
+        Genre::Kids,
+        &[
+            "kids",
+            "child",
+            "kinder",
+            "enfant",
+            "infantil",
+            "cartoon",
+            "anime",
+            "animation",
+            "zeichentrick",
+            "jeunesse",
+        ],
+    ),
+    (
+        Genre::Documentary,
+        &[
+            "documentar",
+            "doku",
+            "docu",
+            "nature",
+            "natur",
+            "history",
+            "geschichte",
+            "wildlife",
+        ],
+    ),
+    (
+        Genre::Music,
+        &["music", "musik", "musique", "música", "concert", "konzert"],
+    ),
+    (
+        Genre::Education,
+        &[
+            "education",
+            "science",
+            "wissen",
+            "bildung",
+            "educat",
+            "technology",
+            "ciencia",
+        ],
+    ),
+    (
+        Genre::Lifestyle,
+        &[
+            "lifestyle",
+            "cooking",
+            "kochen",
+            "cuisine",
+            "food",
+            "travel",
+            "reise",
+            "garden",
+            "fashion",
+            "health",
+            "shopping",
+            "leisure",
+            "hobby",
+        ],
+    ),
+    (
+        Genre::Arts,
+        &[
+            "arts", "kunst", "kultur", "culture", "theat", "opera", "ballet", "literat",
+        ],
+    ),
+    (Genre::Religion, &["relig", "church", "kirche", "faith"]),
+    (
+        Genre::Movie,
+        &[
+            "movie",
+            "film",
+            "cinema",
+            "kino",
+            "cinéma",
+            "película",
+            "thriller",
+            "western",
+            "horror",
+        ],
+    ),
+    (
+        Genre::Entertainment,
+        &[
+            "entertainment",
+            "unterhaltung",
+            "show",
+            "game",
+            "quiz",
+            "reality",
+            "talk",
+            "comedy",
+            "divertissement",
+            "variety",
+        ],
+    ),
+    (
+        Genre::Series,
+        &[
+            "series", "serie", "série", "drama", "soap", "sitcom", "crime", "krimi", "episode",
+        ],
+    ),
+];
+
+/// Maps provider genre text (`"Sports"`, `"Sport / Fußball"`, `"Documentaire"`).
+pub fn from_text(text: &str) -> Option<Genre> {
+    let text = text.to_lowercase();
+    KEYWORDS
+        .iter()
+        .find(|(_, words)| words.iter().any(|w| text.contains(w)))
+        .map(|(genre, _)| *genre)
+}
+
+/// The first recognisable genre of several provider genre strings.
+pub fn from_texts<S: AsRef<str>>(texts: &[S]) -> Option<Genre> {
+    texts.iter().find_map(|t| from_text(t.as_ref()))
+}
+
+/// Maps a DVB content descriptor (ETSI EN 300 468 table 29) as TVHeadend reports it: the level
+/// 1 nibble in the high four bits of a byte.
+pub fn from_dvb(code: u32) -> Option<Genre> {
+    let level2 = code & 0x0f;
+    match (code >> 4) & 0x0f {
+        // Movie/drama: soap/melodrama and adult are split out
+        0x1 => Some(match level2 {
+            0x5 => Genre::Series,
+            0x8 => Genre::Adult,
+            _ => Genre::Movie,
+        }),
+        0x2 => Some(if level2 == 0x3 {
+            Genre::Documentary
+        } else {
+            Genre::News
+        }),
+        0x3 => Some(Genre::Entertainment),
+        0x4 => Some(Genre::Sports),
+        0x5 => Some(Genre::Kids),
+        0x6 => Some(Genre::Music),
+        0x7 => Some(if level2 == 0x3 {
+            Genre::Religion
+        } else {
+            Genre::Arts
+        }),
+        0x8 => Some(Genre::News),
+        0x9 => Some(if level2 == 0x1 || level2 == 0x2 {
+            Genre::Documentary
+        } else {
+            Genre::Education
+        }),
+        0xa => Some(Genre::Lifestyle),
+        _ => None,
+    }
+}
@@ -0,0 +1,494 @@
+//! HDHomeRun network tuners: LAN discovery (UDP 65001), lineups, and tuner allocation so two
+//! windows (or a window and a recording) never ask the same device for more tuners than it has.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, Manager, State};
+
+use crate::servers::{self, ServerConfig, ServerKind, ServerStore};
+
+const DISCOVER_PORT: u16 = 65001;
+const TYPE_DISCOVER_REQ: u16 = 0x0002;
+const TYPE_DISCOVER_RPY: u16 = 0x0003;
+const TAG_DEVICE_TYPE: u8 = 0x01;
+const TAG_DEVICE_ID: u8 = 0x02;
+const TAG_TUNER_COUNT: u8 = 0x10;
+const TAG_BASE_URL: u8 = 0x2A;
+const DEVICE_TYPE_TUNER: u32 = 0x0000_0001;
+const DEVICE_ID_WILDCARD: u32 = 0xFFFF_FFFF;
+
+/// A tuner found on the LAN, ready to be saved as an `hdhomerun` server.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HdhrDevice {
+    pub device_id: String,
+    pub base_url: String,
+    pub tuner_count: u32,
+    pub friendly_name: Option<String>,
+    pub model_number: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct DiscoverJson {
+    #[serde(default)]
+    friendly_name: Option<String>,
+    #[serde(default)]
+    model_number: Option<String>,
+    #[serde(default)]
+    tuner_count: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all(deserialize = "PascalCase", serialize = "camelCase"))]
+pub struct HdhrChannel {
+    pub guide_number: String,
+    pub guide_name: String,
+    #[serde(rename(deserialize = "URL"))]
+    pub url: String,
+    #[serde(default, rename(deserialize = "HD"))]
+    pub hd: u8,
+    #[serde(default, rename(deserialize = "DRM"))]
+    pub drm: u8,
+    #[serde(default)]
+    pub favorite: u8,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct TunerStatus {
+    #[serde(default)]
+    target_ip: Option<String>,
+    #[serde(default)]
+    vct_number: Option<String>,
+}
+
+/// A tuner held by a video window or recording, keyed by the holder's label.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TunerLease {
+    pub server_id: String,
+    pub guide_number: String,
+}
+
+#[derive(Default)]
+pub struct HdhrState {
+    leases: Mutex<HashMap<String, TunerLease>>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RecordingEvent {
+    label: String,
+    path: String,
+    status: &'static str,
+    error: Option<String>,
+}
+
+fn client() -> Result<reqwest::Client, String> {
+    crate::http::direct_client(Some(Duration::from_secs(15)))
+}
+
+fn frame(packet_type: u16, payload: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(payload.len() + 8);
+    buf.extend_from_slice(&packet_type.to_be_bytes());
+    buf.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+    buf.extend_from_slice(payload);
+    let crc = crc32fast::hash(&buf);
+    buf.extend_from_slice(&crc.to_le_bytes());
+    buf
+}
+
+fn discover_request() -> Vec<u8> {
+    let mut payload = Vec::new();
+    for (tag, value) in [
+        (TAG_DEVICE_TYPE, DEVICE_TYPE_TUNER),
+        (TAG_DEVICE_ID, DEVICE_ID_WILDCARD),
+    ] {
+        payload.push(tag);
+        payload.push(4);
+        payload.extend_from_slice(&value.to_be_bytes());
+    }
+    frame(TYPE_DISCOVER_REQ, &payload)
+}
+
+/// Parses a discover reply into a device, ignoring anything that isn't a valid tuner reply.
+fn parse_reply(packet: &[u8], from: SocketAddr) -> Option<HdhrDevice> {
+    if packet.len() < 8 {
+        return None;
+    }
+    let (body, crc) = packet.split_at(packet.len() - 4);
+    if crc32fast::hash(body).to_le_bytes() != crc {
+        return None;
+    }
+    if u16::from_be_bytes([body[0], body[1]]) != TYPE_DISCOVER_RPY {
+        return None;
+    }
+    let mut payload = &body[4..];
+    let mut device_id = None;
+    let mut base_url = None;
+    let mut tuner_count = 0;
+    while payload.len() >= 2 {
+        let tag = payload[0];
+        // Lengths above 127 use a second byte (LSB 7 bits first)
+        let (len, header) = if payload[1] & 0x80 != 0 && payload.len() >= 3 {
+            (
+                ((payload[1] & 0x7F) as usize) | ((payload[2] as usize) << 7),
+                3,
+            )
+        } else {
+            (payload[1] as usize, 2)
+        };
+        let value = payload.get(header..header + len)?;
+        match tag {
+            TAG_DEVICE_ID if len == 4 => {
+                device_id = Some(format!(
+                    "{:08X}",
+                    u32::from_be_bytes([value[0], value[1], value[2], value[3]])
+                ));
+            }
+            TAG_TUNER_COUNT if len == 1 => tuner_count = value[0] as u32,
+            TAG_BASE_URL => base_url = Some(String::from_utf8_lossy(value).into_owned()),
+            _ => {}
+        }
+        payload = &payload[header + len..];
+    }
+    Some(HdhrDevice {
+        device_id: device_id?,
+        // Older firmware doesn't send a base URL; the HTTP API is on port 80
+        base_url: base_url.unwrap_or_else(|| format!("http://{}", from.ip())),
+        tuner_count,
+        friendly_name: None,
+        model_number: None,
+    })
+}
+
+pub(crate) fn discover_lan(wait: Duration) -> Result<Vec<HdhrDevice>, String> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).map_err(|e| e.to_string())?;
+    socket.set_broadcast(true).map_err(|e| e.to_string())?;
+    socket
+        .set_read_timeout(Some(Duration::from_millis(200)))
+        .map_err(|e| e.to_string())?;
+    socket
+        .send_to(&discover_request(), (Ipv4Addr::BROADCAST, DISCOVER_PORT))
+        .map_err(|e| format!("Failed to send discovery broadcast: {}", e))?;
+
+    let deadline = Instant::now() + wait;
+    let mut devices: Vec<HdhrDevice> = Vec::new();
+    let mut buf = [0u8; 2048];
+    while Instant::now() < deadline {
+        if let Ok((n, from)) = socket.recv_from(&mut buf) {
+            if let Some(device) = parse_reply(&buf[..n], from) {
+                if !devices.iter().any(|d| d.device_id == device.device_id) {
+                    devices.push(device);
+                }
+            }
+        }
+    }
+    Ok(devices)
+}
+
+fn hdhr_server(store: &ServerStore, server_id: &str) -> Result<ServerConfig, String> {
+    let server = servers::get(store, server_id)?;
+    if server.kind != ServerKind::Hdhomerun {
+        return Err(format!("{} is not an HDHomeRun tuner", server.name));
+    }
+    Ok(server)
+}
+
+async fn get_json<T: serde::de::DeserializeOwned>(url: String) -> Result<T, String> {
+    let resp = crate::http::send(client()?.get(&url))
+        .await
+        .map_err(|e| format!("HDHomeRun request failed: {}", e))?;
+    if !resp.status().is_success() {
+        return Err(format!("HDHomeRun returned HTTP {}", resp.status()));
+    }
+    resp.json()
+        .await
+        .map_err(|e| format!("Invalid HDHomeRun response: {}", e))
+}
+
+pub(crate) async fn lineup(server: &ServerConfig) -> Result<Vec<HdhrChannel>, String> {
+    get_json(format!("{}/lineup.json", server.base_url())).await
+}
+
+/// Reserves a tuner on `server` for `holder`, checking both the device's own status (other
+/// apps may be using it) and leases held by our windows and recordings.
+async fn acquire(
+    state: &HdhrState,
+    server: &ServerConfig,
+    holder: &str,
+    guide_number: &str,
+) -> Result<(), String> {
+    let info: DiscoverJson = get_json(format!("{}/discover.json", server.base_url())).await?;
+    let tuners: Vec<TunerStatus> = get_json(format!("{}/status.json", server.base_url())).await?;
+    let tuner_count = info.tuner_count.unwrap_or(tuners.len() as u32) as usize;
+    let busy_on_device = tuners
+        .iter()
+        .filter(|t| t.target_ip.is_some() || t.vct_number.is_some())
+        .count();
+
+    let mut leases = state.leases.lock().unwrap_or_else(|e| e.into_inner());
+    // A holder re-tuning gives its previous tuner back first
+    let previous = leases.remove(holder);
+    let ours = leases.values().filter(|l| l.server_id == server.id).count();
+    // Device status can lag a just-opened stream, so trust whichever count is higher
+    if busy_on_device.max(ours) >= tuner_count {
+        if let Some(previous) = previous {
+            leases.insert(holder.to_string(), previous);
+        }
+        let holders: Vec<&str> = leases
+            .iter()
+            .filter(|(_, l)| l.server_id == server.id)
+            .map(|(label, _)| label.as_str())
+            .collect();
+        return Err(format!(
+            "All {} tuners on {} are in use{}",
+            tuner_count,
+            server.name,
+            if holders.is_empty() {
+                String::new()
+            } else {
+                format!(" (held by {})", holders.join(", "))
+            }
+        ));
+    }
+    leases.insert(
+        holder.to_string(),
+        TunerLease {
+            server_id: server.id.clone(),
+            guide_number: guide_number.to_string(),
+        },
+    );
+    Ok(())
+}
+
+/// Frees the tuner held by `holder`, if any. Called when a video window is destroyed.
+pub fn release(app: &tauri::AppHandle, holder: &str) {
+    if let Some(state) = app.try_state::<HdhrState>() {
+        state
+            .leases
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(holder);
+    }
+}
+
+/// Broadcasts a discovery request and returns the tuners that answered, with their names.
+#[tauri::command]
+pub async fn hdhomerun_discover() -> Result<Vec<HdhrDevice>, String> {
+    let mut devices = tauri::async_runtime::spawn_blocking(|| discover_lan(Duration::from_secs(2)))
+        .await
+        .map_err(|e| e.to_string())??;
+    for device in &mut devices {
+        if let Ok(info) =
+            get_json::<DiscoverJson>(format!("{}/discover.json", device.base_url)).await
+        {
+            device.friendly_name = info.friendly_name;
+            device.model_number = info.model_number;
+            if let Some(count) = info.tuner_count {
+                device.tuner_count = count;
+            }
+        }
+    }
+    Ok(devices)
+}
+
+#[tauri::command]
+pub async fn hdhomerun_lineup(
+    store: State<'_, ServerStore>,
+    server_id: String,
+) -> Result<Vec<HdhrChannel>, String> {
+    let server = hdhr_server(&store, &server_id)?;
+    lineup(&server).await
+}
+
+/// Current tuner leases by holder label (video window or recording).
+#[tauri::command]
+pub fn hdhomerun_leases(state: State<'_, HdhrState>) -> HashMap<String, TunerLease> {
+    state
+        .leases
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+}
+
+/// Reserves a tuner and opens a video window for the channel. Returns the window label.
+#[tauri::command]
+pub async fn hdhomerun_play(
+    app: tauri::AppHandle,
+    store: State<'_, ServerStore>,
+    state: State<'_, HdhrState>,
+    server_id: String,
+    guide_number: String,
+) -> Result<String, String> {
+    let server = hdhr_server(&store, &server_id)?;
+    let channel = lineup(&server)
+        .await?
+        .into_iter()
+        .find(|c| c.guide_number == guide_number)
+        .ok_or_else(|| format!("Channel {} is not in the lineup", guide_number))?;
+    // The window label isn't known until it exists, so hold the tuner under a placeholder
+    let placeholder = format!("pending-{}", uuid::Uuid::new_v4());
+    acquire(&state, &server, &placeholder, &guide_number).await?;
+    let label = crate::create_video_window(
+        &app,
+        &format!("{} {}", channel.guide_number, channel.guide_name),
+        &channel.url,
+    );
+    let mut leases = state.leases.lock().unwrap_or_else(|e| e.into_inner());
+    let lease = leases.remove(&placeholder);
+    let label = label?;
+    if let Some(lease) = lease {
+        leases.insert(label.clone(), lease);
+    }
+    Ok(label)
+}
+
+/// Writes `resp` to `file` until the stream ends (true) or the system starts to suspend
+/// (false), then closes the connection.
+async fn copy_until_suspend(
+    mut resp: reqwest::Response,
+    file: &mut std::fs::File,
+) -> Result<bool, String> {
+    loop {
+        if crate::power::suspended() {
+            return Ok(false);
+        }
+        // Bounded waits, so a suspend is noticed even while the tuner sends nothing
+        let Ok(chunk) = tokio::time::timeout(Duration::from_secs(1), resp.chunk()).await else {
+            continue;
+        };
+        match chunk.map_err(|e| e.to_string())? {
+            Some(chunk) => file.write_all(&chunk).map_err(|e| e.to_string())?,
+            None => return Ok(true),
+        }
+    }
+}
+
+/// Records `duration_secs` of a channel to `path` using the tuner's own `duration` stream
+/// parameter. Emits `hdhomerun-recording` when the recording starts and finishes. Before the
+/// system sleeps the file is closed cleanly and the tuner released (`paused`); on resume the
+/// rest of the slot is appended (`resumed`).
+#[tauri::command]
+pub async fn hdhomerun_record(
+    app: tauri::AppHandle,
+    store: State<'_, ServerStore>,
+    state: State<'_, HdhrState>,
+    server_id: String,
+    guide_number: String,
+    duration_secs: u64,
+    path: String,
+) -> Result<String, String> {
+    let server = hdhr_server(&store, &server_id)?;
+    let channel = lineup(&server)
+        .await?
+        .into_iter()
+        .find(|c| c.guide_number == guide_number)
+        .ok_or_else(|| format!("Channel {} is not in the lineup", guide_number))?;
+    let label = format!("record-{}", uuid::Uuid::new_v4());
+    let title = format!("{} {}", channel.guide_number, channel.guide_name);
+    let reservation = crate::connections::reserve(&app, &server, &title, true, None).await?;
+    acquire(&state, &server, &label, &guide_number).await?;
+    reservation.keep(&label);
+
+    let mut file = match std::fs::File::create(&path) {
+        Ok(file) => file,
+        Err(e) => {
+            release(&app, &label);
+            crate::connections::release(&label);
+            return Err(format!("Failed to create {}: {}", path, e));
+        }
+    };
+    let task_label = label.clone();
+    let started_at = crate::now_secs() as i64;
+    let ends_at = started_at as u64 + duration_secs;
+    let server_id = server.id.clone();
+    tauri::async_runtime::spawn(async move {
+        let emit = |status: &'static str, error: Option<String>| {
+            let _ = app.emit(
+                "hdhomerun-recording",
+                RecordingEvent {
+                    label: task_label.clone(),
+                    path: path.clone(),
+                    status,
+                    error,
+                },
+            );
+        };
+        emit("started", None);
+        let result: Result<(), String> = async {
+            loop {
+                let remaining = ends_at.saturating_sub(crate::now_secs());
+                if remaining == 0 {
+                    return Ok(());
+                }
+                let url = format!("{}?duration={}", channel.url, remaining);
+                // No overall timeout: the stream runs for the whole recording
+                let resp = crate::http::send(crate::http::direct_client(None)?.get(&url))
+                    .await
+                    .map_err(|e| e.to_string())?;
+                if !resp.status().is_success() {
+                    return Err(format!("HDHomeRun returned HTTP {}", resp.status()));
+                }
+                let hold = crate::power::hold();
+                if copy_until_suspend(resp, &mut file).await? {
+                    return Ok(());
+                }
+                file.sync_all().map_err(|e| e.to_string())?;
+                drop(hold);
+                release(&app, &task_label);
+                emit("paused", None);
+                crate::power::wait_resumed().await;
+                if ends_at <= crate::now_secs() {
+                    return Ok(());
+                }
+                acquire(
+                    &app.state::<HdhrState>(),
+                    &server,
+                    &task_label,
+                    &guide_number,
+                )
+                .await?;
+                emit("resumed", None);
+            }
+        }
+        .await;
+        release(&app, &task_label);
+        crate::connections::release(&task_label);
+        match result {
+            Ok(()) => {
+                tracing::info!("HDHomeRun recording {} finished", path);
+                let added = crate::recordings::add(
+                    &app,
+                    path.clone(),
+                    server_id,
+                    channel.url,
+                    format!("{} {}", channel.guide_number, channel.guide_name),
+                    started_at,
+                )
+                .await;
+                if let Err(e) = added {
+                    tracing::warn!("Could not add {} to the recordings library: {}", path, e);
+                }
+                emit("finished", None)
+            }
+            Err(e) => {
+                tracing::error!("HDHomeRun recording {} failed: {}", path, e);
+                emit("failed", Some(e))
+            }
+        }
+    });
+    Ok(label)
+}
+
+/// Releases a tuner held by a recording or window before it ends on its own.
+#[tauri::command]
+pub fn hdhomerun_release(app: tauri::AppHandle, holder: String) {
+    release(&app, &holder);
+}
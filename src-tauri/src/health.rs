@@ -0,0 +1,243 @@
+//! Per-host circuit breaker behind `http::send`. After `FAILURE_THRESHOLD` consecutive
+//! failures (connection errors, timeouts, 5xx) requests to the host fail fast for a cooldown,
+//! then a single trial request is let through (half-open): success closes the circuit, failure
+//! reopens it with a longer cooldown. Health is reported per saved server.
+
+use std::collections::BTreeMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tauri::{Emitter, Manager, State};
+
+use crate::servers::{ServerConfig, ServerStore};
+
+const FAILURE_THRESHOLD: u32 = 5;
+const BASE_COOLDOWN: Duration = Duration::from_secs(30);
+const MAX_COOLDOWN: Duration = Duration::from_secs(300);
+/// A trial request that never reported back (e.g. it was cancelled) stops blocking others.
+const TRIAL_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Circuit {
+    Closed,
+    Open { until: Instant },
+    HalfOpen { since: Instant },
+}
+
+#[derive(Debug, Clone)]
+struct Host {
+    circuit: Circuit,
+    failures: u32,
+    cooldown: Duration,
+    last_success: Option<u64>,
+    last_failure: Option<u64>,
+    last_error: Option<String>,
+}
+
+impl Default for Host {
+    fn default() -> Self {
+        Self {
+            circuit: Circuit::Closed,
+            failures: 0,
+            cooldown: BASE_COOLDOWN,
+            last_success: None,
+            last_failure: None,
+            last_error: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    /// No recent failures (or never contacted).
+    Healthy,
+    /// Some consecutive failures, but requests still go out.
+    Degraded,
+    /// Circuit open: requests fail immediately until `retryAt`.
+    Down,
+    /// A trial request is checking whether the server is back.
+    Recovering,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerHealth {
+    pub server_id: String,
+    pub status: HealthStatus,
+    pub consecutive_failures: u32,
+    pub last_success: Option<u64>,
+    pub last_failure: Option<u64>,
+    pub last_error: Option<String>,
+    /// Unix seconds when requests are let through again, while down.
+    pub retry_at: Option<u64>,
+}
+
+static HOSTS: Mutex<BTreeMap<String, Host>> = Mutex::new(BTreeMap::new());
+/// For `server-health-changed` events; the breaker itself runs without an app handle.
+static APP: OnceLock<tauri::AppHandle> = OnceLock::new();
+
+fn hosts() -> std::sync::MutexGuard<'static, BTreeMap<String, Host>> {
+    HOSTS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+pub fn init(app: &tauri::AppHandle) {
+    let _ = APP.set(app.clone());
+}
+
+/// Whether a request to `host` may go out now. `Err` carries how long the circuit stays open.
+pub fn check(host: &str) -> Result<(), Duration> {
+    let now = Instant::now();
+    let mut hosts = hosts();
+    let Some(entry) = hosts.get_mut(host) else {
+        return Ok(());
+    };
+    match entry.circuit {
+        Circuit::Closed => Ok(()),
+        Circuit::Open { until } if until > now => Err(until - now),
+        Circuit::HalfOpen { since } if now - since < TRIAL_TIMEOUT => Err(Duration::ZERO),
+        // Cooldown over (or the last trial got lost): this request is the trial
+        _ => {
+            entry.circuit = Circuit::HalfOpen { since: now };
+            drop(hosts);
+            changed(host);
+            Ok(())
+        }
+    }
+}
+
+pub fn record_success(host: &str) {
+    let mut hosts = hosts();
+    let entry = hosts.entry(host.to_string()).or_default();
+    let was_healthy = entry.circuit == Circuit::Closed && entry.failures == 0;
+    entry.circuit = Circuit::Closed;
+    entry.failures = 0;
+    entry.cooldown = BASE_COOLDOWN;
+    entry.last_success = Some(crate::now_secs());
+    drop(hosts);
+    if !was_healthy {
+        changed(host);
+    }
+}
+
+pub fn record_failure(host: &str, error: &str) {
+    let now = Instant::now();
+    let mut hosts = hosts();
+    let entry = hosts.entry(host.to_string()).or_default();
+    entry.failures += 1;
+    entry.last_failure = Some(crate::now_secs());
+    entry.last_error = Some(error.to_string());
+    match entry.circuit {
+        Circuit::HalfOpen { .. } => {
+            entry.cooldown = (entry.cooldown * 2).min(MAX_COOLDOWN);
+            entry.circuit = Circuit::Open {
+                until: now + entry.cooldown,
+            };
+            tracing::warn!(
+                "{} is still failing; retrying in {:?}",
+                host,
+                entry.cooldown
+            );
+        }
+        Circuit::Closed if entry.failures >= FAILURE_THRESHOLD => {
+            entry.circuit = Circuit::Open {
+                until: now + entry.cooldown,
+            };
+            tracing::warn!(
+                "{} failed {} times in a row; pausing requests for {:?}",
+                host,
+                entry.failures,
+                entry.cooldown
+            );
+        }
+        _ => {}
+    }
+    drop(hosts);
+    changed(host);
+}
+
+pub(crate) fn host_of(server: &ServerConfig) -> Option<String> {
+    reqwest::Url::parse(&server.url)
+        .ok()?
+        .host_str()
+        .map(str::to_string)
+}
+
+fn health_of(server: &ServerConfig, hosts: &BTreeMap<String, Host>) -> ServerHealth {
+    let entry = host_of(server)
+        .and_then(|host| hosts.get(&host).cloned())
+        .unwrap_or_default();
+    let (status, retry_at) = match entry.circuit {
+        Circuit::Open { until } => {
+            let remaining = until.saturating_duration_since(Instant::now());
+            (
+                HealthStatus::Down,
+                Some(crate::now_secs() + remaining.as_secs()),
+            )
+        }
+        Circuit::HalfOpen { .. } => (HealthStatus::Recovering, None),
+        Circuit::Closed if entry.failures > 0 => (HealthStatus::Degraded, None),
+        Circuit::Closed => (HealthStatus::Healthy, None),
+    };
+    ServerHealth {
+        server_id: server.id.clone(),
+        status,
+        consecutive_failures: entry.failures,
+        last_success: entry.last_success,
+        last_failure: entry.last_failure,
+        last_error: entry.last_error,
+        retry_at,
+    }
+}
+
+/// Emits `server-health-changed` for every saved server on `host`.
+fn changed(host: &str) {
+    let Some(app) = APP.get() else {
+        return;
+    };
+    let servers = app.state::<ServerStore>().read(|servers| servers.clone());
+    let hosts = hosts().clone();
+    for server in servers
+        .iter()
+        .filter(|s| host_of(s).as_deref() == Some(host))
+    {
+        let _ = app.emit("server-health-changed", health_of(server, &hosts));
+    }
+}
+
+#[tauri::command]
+pub fn get_server_health(store: State<'_, ServerStore>) -> Vec<ServerHealth> {
+    let hosts = hosts().clone();
+    store.read(|servers| servers.iter().map(|s| health_of(s, &hosts)).collect())
+}
+
+/// Forgets every host's failures, e.g. after waking from sleep when they are all stale.
+pub fn reset_all() {
+    let reset: Vec<String> = {
+        let mut hosts = hosts();
+        let failing: Vec<String> = hosts
+            .iter()
+            .filter(|(_, entry)| entry.circuit != Circuit::Closed || entry.failures > 0)
+            .map(|(host, _)| host.clone())
+            .collect();
+        for host in &failing {
+            hosts.remove(host);
+        }
+        failing
+    };
+    for host in reset {
+        changed(&host);
+    }
+}
+
+/// Forgets a server's failures so requests go out again right away ("retry now").
+#[tauri::command]
+pub fn reset_server_health(store: State<'_, ServerStore>, server_id: String) -> Result<(), String> {
+    let server = crate::servers::get(&store, &server_id)?;
+    if let Some(host) = host_of(&server) {
+        hosts().remove(&host);
+        changed(&host);
+    }
+    Ok(())
+}
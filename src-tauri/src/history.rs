@@ -0,0 +1,531 @@
+//! Watch history with time actually spent watching, persisted in `history.json`. A video
+//! window opens a session with `start_watching` and reports `report_watching` while it plays;
+//! the session is saved when the window changes content or closes. Feeds `get_watch_stats`
+//! and `export_history`.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tauri::{Manager, State};
+
+use crate::genre::Genre;
+use crate::progress::ProgressStore;
+use crate::servers::ServerStore;
+use crate::settings::SettingsStore;
+use crate::store::JsonStore;
+
+/// Heartbeats further apart than this count as this much; the window was likely asleep.
+const MAX_BEAT_GAP_SECS: i64 = 60;
+/// Sessions shorter than this (channel surfing) aren't kept.
+const MIN_SESSION_SECS: u64 = 30;
+/// History older than this is dropped, keeping a year and a bit for yearly summaries.
+const KEEP_SECS: i64 = 400 * 86_400;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WatchKind {
+    Live,
+    Movie,
+    Episode,
+    Recording,
+    /// A live radio station.
+    Radio,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchSession {
+    pub id: String,
+    pub server_id: String,
+    pub kind: WatchKind,
+    pub title: String,
+    /// Progress key of the item (see `progress`), for VOD and recordings.
+    #[serde(default)]
+    pub content_key: Option<String>,
+    #[serde(default)]
+    pub channel_id: Option<String>,
+    #[serde(default)]
+    pub channel_name: Option<String>,
+    #[serde(default)]
+    pub genre: Option<Genre>,
+    /// Unix seconds.
+    pub started_at: i64,
+    /// Seconds spent playing, excluding pauses.
+    pub watched_secs: u64,
+    /// Share of the item played, for items with a known duration.
+    #[serde(default)]
+    pub percent: Option<f64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchStart {
+    pub server_id: String,
+    pub kind: WatchKind,
+    pub title: String,
+    #[serde(default)]
+    pub content_key: Option<String>,
+    #[serde(default)]
+    pub channel_id: Option<String>,
+    #[serde(default)]
+    pub channel_name: Option<String>,
+    /// Looked up from the guide for live channels when not given.
+    #[serde(default)]
+    pub genre: Option<Genre>,
+}
+
+struct Active {
+    session: WatchSession,
+    last_beat: i64,
+    playing: bool,
+}
+
+impl Active {
+    fn beat(&mut self, now: i64) {
+        if self.playing {
+            self.session.watched_secs += (now - self.last_beat).clamp(0, MAX_BEAT_GAP_SECS) as u64;
+        }
+        self.last_beat = now;
+    }
+}
+
+pub type HistoryStore = JsonStore<Vec<WatchSession>>;
+
+pub fn open(app: &tauri::AppHandle) -> HistoryStore {
+    JsonStore::open(app, "history.json")
+}
+
+static ACTIVE: Mutex<BTreeMap<String, Active>> = Mutex::new(BTreeMap::new());
+
+fn active() -> std::sync::MutexGuard<'static, BTreeMap<String, Active>> {
+    ACTIVE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn save(app: &tauri::AppHandle, mut active: Active) {
+    active.beat(crate::now_secs() as i64);
+    if active.session.watched_secs < MIN_SESSION_SECS {
+        return;
+    }
+    let cutoff = crate::now_secs() as i64 - KEEP_SECS;
+    let store = app.state::<HistoryStore>();
+    let saved = store.update(|sessions| {
+        sessions.retain(|s| s.started_at >= cutoff);
+        sessions.push(active.session);
+    });
+    if let Err(e) = saved {
+        tracing::warn!("Could not save watch history: {}", e);
+    }
+}
+
+/// Server and channel id of the live channel (or radio station) a window is watching.
+pub(crate) fn live_channel(label: &str) -> Option<(String, String)> {
+    let active = active();
+    let session = &active.get(label)?.session;
+    if !matches!(session.kind, WatchKind::Live | WatchKind::Radio) {
+        return None;
+    }
+    Some((session.server_id.clone(), session.channel_id.clone()?))
+}
+
+/// Server of whatever a window is watching.
+pub(crate) fn watching_server(label: &str) -> Option<String> {
+    Some(active().get(label)?.session.server_id.clone())
+}
+
+/// Seconds spent playing an item across saved and active sessions.
+pub(crate) fn played_secs(app: &tauri::AppHandle, content_key: &str) -> u64 {
+    let is_item = |s: &WatchSession| s.content_key.as_deref() == Some(content_key);
+    let saved: u64 = app.state::<HistoryStore>().read(|sessions| {
+        sessions
+            .iter()
+            .filter(|s| is_item(s))
+            .map(|s| s.watched_secs)
+            .sum()
+    });
+    let now = crate::now_secs() as i64;
+    let active: u64 = active()
+        .values_mut()
+        .filter(|a| is_item(&a.session))
+        .map(|a| {
+            a.beat(now);
+            a.session.watched_secs
+        })
+        .sum();
+    saved + active
+}
+
+/// Ends the session of a closed window.
+pub fn release(app: &tauri::AppHandle, label: &str) {
+    let ended = active().remove(label);
+    if let Some(ended) = ended {
+        save(app, ended);
+    }
+}
+
+/// Starts a watch session for a window, ending the one it had. Returns the session id.
+#[tauri::command]
+pub fn start_watching(app: tauri::AppHandle, label: String, watch: WatchStart) -> String {
+    let now = crate::now_secs() as i64;
+    let genre = watch.genre.or_else(|| {
+        let channel_id = watch.channel_id.as_deref()?;
+        crate::epg::airing_at(&watch.server_id, channel_id, now)?.genre
+    });
+    let session = WatchSession {
+        id: uuid::Uuid::new_v4().to_string(),
+        server_id: watch.server_id,
+        kind: watch.kind,
+        title: watch.title,
+        content_key: watch.content_key,
+        channel_id: watch.channel_id,
+        channel_name: watch.channel_name,
+        genre,
+        started_at: now,
+        watched_secs: 0,
+        percent: None,
+    };
+    let id = session.id.clone();
+    if let (WatchKind::Live | WatchKind::Radio, Some(channel_id)) =
+        (session.kind, &session.channel_id)
+    {
+        crate::volume::reapply(&app, &label, &session.server_id, channel_id);
+    }
+    let previous = active().insert(
+        label,
+        Active {
+            session,
+            last_beat: now,
+            playing: true,
+        },
+    );
+    if let Some(previous) = previous {
+        save(&app, previous);
+    }
+    id
+}
+
+/// Heartbeat from a playing window, every few seconds and on pause/resume.
+#[tauri::command]
+pub fn report_watching(
+    label: String,
+    paused: bool,
+    position_secs: Option<f64>,
+    duration_secs: Option<f64>,
+) {
+    let mut active = active();
+    let Some(session) = active.get_mut(&label) else {
+        return;
+    };
+    session.beat(crate::now_secs() as i64);
+    session.playing = !paused;
+    if let (Some(pos), Some(dur)) = (position_secs, duration_secs.filter(|d| *d > 0.0)) {
+        session.session.percent = Some((pos / dur * 100.0).clamp(0.0, 100.0));
+    }
+}
+
+/// Unix-second window, both ends optional.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TimeRange {
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+}
+
+impl TimeRange {
+    fn contains(&self, t: i64) -> bool {
+        self.from.is_none_or(|from| t >= from) && self.to.is_none_or(|to| t < to)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChannelStat {
+    pub server_id: String,
+    pub channel_id: String,
+    pub channel_name: Option<String>,
+    pub secs: u64,
+    pub sessions: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GenreStat {
+    /// `None` for programmes without a known genre.
+    pub genre: Option<Genre>,
+    pub secs: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DayStat {
+    /// `YYYY-MM-DD` in the display time zone.
+    pub date: String,
+    pub secs: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KindStat {
+    pub kind: WatchKind,
+    pub secs: u64,
+    pub sessions: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchStats {
+    pub total_secs: u64,
+    pub sessions: usize,
+    /// Most watched first.
+    pub by_channel: Vec<ChannelStat>,
+    /// Most watched first.
+    pub by_genre: Vec<GenreStat>,
+    /// Chronological, days without viewing omitted.
+    pub by_day: Vec<DayStat>,
+    pub by_kind: Vec<KindStat>,
+}
+
+/// Watch time in `range` (by session start) per channel, genre, day and kind. Sessions still
+/// playing are included.
+#[tauri::command]
+pub fn get_watch_stats(
+    history: State<'_, HistoryStore>,
+    settings: State<'_, SettingsStore>,
+    range: Option<TimeRange>,
+) -> WatchStats {
+    let range = range.unwrap_or_default();
+    let now = crate::now_secs() as i64;
+    let mut sessions: Vec<WatchSession> = history.read(|sessions| {
+        sessions
+            .iter()
+            .filter(|s| range.contains(s.started_at))
+            .cloned()
+            .collect()
+    });
+    sessions.extend(active().values_mut().filter_map(|a| {
+        a.beat(now);
+        range
+            .contains(a.session.started_at)
+            .then(|| a.session.clone())
+    }));
+    let zone = crate::tz::zone(&crate::tz::display_zone_name(&settings)).ok();
+
+    let mut channels: BTreeMap<(String, String), ChannelStat> = BTreeMap::new();
+    let mut genres: BTreeMap<Option<Genre>, u64> = BTreeMap::new();
+    let mut days: BTreeMap<String, u64> = BTreeMap::new();
+    let mut kinds: BTreeMap<WatchKind, (u64, usize)> = BTreeMap::new();
+    for s in &sessions {
+        if let Some(channel_id) = &s.channel_id {
+            let stat = channels
+                .entry((s.server_id.clone(), channel_id.clone()))
+                .or_insert_with(|| ChannelStat {
+                    server_id: s.server_id.clone(),
+                    channel_id: channel_id.clone(),
+                    channel_name: None,
+                    secs: 0,
+                    sessions: 0,
+                });
+            stat.secs += s.watched_secs;
+            stat.sessions += 1;
+            if s.channel_name.is_some() {
+                stat.channel_name = s.channel_name.clone();
+            }
+        }
+        *genres.entry(s.genre).or_default() += s.watched_secs;
+        let offset = zone.as_ref().map_or(0, |z| z.offset_at(s.started_at));
+        let (year, month, day, _, _) = crate::tz::local_parts(s.started_at, offset);
+        *days
+            .entry(format!("{:04}-{:02}-{:02}", year, month, day))
+            .or_default() += s.watched_secs;
+        let kind = kinds.entry(s.kind).or_default();
+        kind.0 += s.watched_secs;
+        kind.1 += 1;
+    }
+
+    let mut by_channel: Vec<ChannelStat> = channels.into_values().collect();
+    by_channel.sort_by_key(|c| std::cmp::Reverse(c.secs));
+    let mut by_genre: Vec<GenreStat> = genres
+        .into_iter()
+        .map(|(genre, secs)| GenreStat { genre, secs })
+        .collect();
+    by_genre.sort_by_key(|g| std::cmp::Reverse(g.secs));
+    WatchStats {
+        total_secs: sessions.iter().map(|s| s.watched_secs).sum(),
+        sessions: sessions.len(),
+        by_channel,
+        by_genre,
+        by_day: days
+            .into_iter()
+            .map(|(date, secs)| DayStat { date, secs })
+            .collect(),
+        by_kind: kinds
+            .into_iter()
+            .map(|(kind, (secs, sessions))| KindStat {
+                kind,
+                secs,
+                sessions,
+            })
+            .collect(),
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+/// One exported row: a watch session, or a saved position with no session behind it.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportRow {
+    record: &'static str,
+    title: String,
+    server: Option<String>,
+    kind: Option<WatchKind>,
+    content_key: Option<String>,
+    channel: Option<String>,
+    /// ISO 8601 UTC.
+    time: String,
+    watched_secs: Option<u64>,
+    position_secs: Option<f64>,
+    duration_secs: Option<f64>,
+    percent: Option<f64>,
+    watched: Option<bool>,
+}
+
+fn iso_time(unix_secs: i64) -> String {
+    let secs = unix_secs.max(0) as u64;
+    let (year, month, day) = crate::civil_date(secs);
+    let time = secs % 86_400;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
+
+fn csv_field(out: &mut String, value: &str) {
+    if value.contains([',', '"', '\n', '\r']) {
+        let _ = write!(out, "\"{}\"", value.replace('"', "\"\""));
+    } else {
+        out.push_str(value);
+    }
+}
+
+fn to_csv(rows: &[ExportRow]) -> Result<String, String> {
+    let mut out = String::from(
+        "record,title,server,kind,content_key,channel,time,watched_secs,position_secs,duration_secs,percent,watched\n",
+    );
+    for row in rows {
+        // Every column is a plain JSON value, so its text form doubles as the CSV cell
+        let value = serde_json::to_value(row).map_err(|e| e.to_string())?;
+        let cells = [
+            "record",
+            "title",
+            "server",
+            "kind",
+            "contentKey",
+            "channel",
+            "time",
+            "watchedSecs",
+            "positionSecs",
+            "durationSecs",
+            "percent",
+            "watched",
+        ];
+        for (i, key) in cells.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            match &value[*key] {
+                serde_json::Value::Null => {}
+                serde_json::Value::String(s) => csv_field(&mut out, s),
+                other => csv_field(&mut out, &other.to_string()),
+            }
+        }
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+/// Writes watch sessions and saved positions to `path` as CSV or JSON. Returns the number of
+/// rows written.
+#[tauri::command]
+pub fn export_history(
+    history: State<'_, HistoryStore>,
+    progress: State<'_, ProgressStore>,
+    servers: State<'_, ServerStore>,
+    format: ExportFormat,
+    path: String,
+) -> Result<usize, String> {
+    let names: BTreeMap<String, String> = servers.read(|servers| {
+        servers
+            .iter()
+            .map(|s| (s.id.clone(), s.name.clone()))
+            .collect()
+    });
+    let server_name = |id: &str| Some(names.get(id).cloned().unwrap_or_else(|| id.to_string()));
+    let sessions = history.read(|sessions| sessions.clone());
+    let mut titles: BTreeMap<String, String> = BTreeMap::new();
+    let mut rows: Vec<ExportRow> = sessions
+        .into_iter()
+        .map(|s| {
+            if let Some(key) = &s.content_key {
+                titles.insert(key.clone(), s.title.clone());
+            }
+            ExportRow {
+                record: "session",
+                server: server_name(&s.server_id),
+                kind: Some(s.kind),
+                channel: s.channel_name.or(s.channel_id),
+                time: iso_time(s.started_at),
+                watched_secs: Some(s.watched_secs),
+                position_secs: None,
+                duration_secs: None,
+                percent: s.percent,
+                watched: None,
+                title: s.title,
+                content_key: s.content_key,
+            }
+        })
+        .collect();
+    let mut entries: Vec<_> = progress.read(|entries| entries.values().cloned().collect());
+    entries.sort_by_key(|e| e.updated_at);
+    rows.extend(entries.into_iter().map(|e| {
+        // Keys look like `{serverId}:{kind}:{id}`
+        let server = e
+            .content_key
+            .split(':')
+            .next()
+            .and_then(|id| names.get(id).cloned());
+        ExportRow {
+            record: "progress",
+            title: titles.get(&e.content_key).cloned().unwrap_or_default(),
+            server,
+            kind: None,
+            channel: None,
+            time: iso_time(e.updated_at as i64),
+            watched_secs: None,
+            percent: e
+                .duration_secs
+                .map(|d| (e.position_secs / d * 100.0).clamp(0.0, 100.0)),
+            position_secs: Some(e.position_secs),
+            duration_secs: e.duration_secs,
+            watched: Some(e.watched),
+            content_key: Some(e.content_key),
+        }
+    }));
+    let text = match format {
+        ExportFormat::Csv => to_csv(&rows)?,
+        ExportFormat::Json => serde_json::to_string_pretty(&rows).map_err(|e| e.to_string())?,
+    };
+    std::fs::write(&path, text).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    Ok(rows.len())
+}
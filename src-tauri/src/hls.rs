@@ -0,0 +1,431 @@
+//! HLS master playlists: the quality variants and alternate renditions a stream offers, so the
+//! user can pick one (or the preferred quality setting picks) and the player gets that media
+//! playlist instead of adapting on its own. Variants whose audio comes from a separate
+//! rendition playlist can't be played alone; those are listed but never substituted.
+//! Media playlists are read for their segments, which downloads fetch one by one.
+
+use serde::{Deserialize, Serialize};
+
+use crate::servers::ServerConfig;
+use crate::settings::SettingsStore;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HlsVariant {
+    /// Absolute URL of the variant's media playlist.
+    pub url: String,
+    /// Peak bits per second.
+    pub bandwidth: u64,
+    pub average_bandwidth: Option<u64>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub frame_rate: Option<f64>,
+    pub codecs: Option<String>,
+    /// `GROUP-ID` of the audio renditions the variant plays with.
+    pub audio_group: Option<String>,
+    /// Whether the media playlist carries its own audio, so it can be played without the
+    /// master playlist.
+    pub standalone: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RenditionKind {
+    Audio,
+    Video,
+    Subtitles,
+    ClosedCaptions,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HlsRendition {
+    pub kind: RenditionKind,
+    pub group_id: String,
+    pub name: String,
+    pub language: Option<String>,
+    pub default: bool,
+    /// `None` when the rendition is muxed into the variants.
+    pub url: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HlsManifest {
+    /// Highest bandwidth first.
+    pub variants: Vec<HlsVariant>,
+    pub renditions: Vec<HlsRendition>,
+}
+
+/// `KEY=value` pairs of a tag's attribute list, with quotes removed from quoted values.
+fn attributes(list: &str) -> Vec<(String, String)> {
+    let mut pairs = Vec::new();
+    let mut rest = list.trim();
+    while !rest.is_empty() {
+        let Some((key, after)) = rest.split_once('=') else {
+            break;
+        };
+        let (value, after) = match after.strip_prefix('"') {
+            Some(quoted) => {
+                let end = quoted.find('"').unwrap_or(quoted.len());
+                let after = quoted.get(end + 1..).unwrap_or_default();
+                (&quoted[..end], after)
+            }
+            None => match after.find(',') {
+                Some(end) => (&after[..end], &after[end..]),
+                None => (after, ""),
+            },
+        };
+        pairs.push((key.trim().to_ascii_uppercase(), value.trim().to_string()));
+        rest = after.trim_start_matches(',').trim_start();
+    }
+    pairs
+}
+
+fn attribute<'a>(pairs: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    pairs
+        .iter()
+        .find(|(k, _)| k == key)
+        .map(|(_, v)| v.as_str())
+}
+
+fn resolve(base: &reqwest::Url, uri: &str) -> String {
+    base.join(uri)
+        .map_or_else(|_| uri.to_string(), |url| url.to_string())
+}
+
+/// The variants and renditions of a master playlist fetched from `base`, or `None` for a
+/// media playlist.
+pub fn parse_master(text: &str, base: &reqwest::Url) -> Option<HlsManifest> {
+    let mut manifest = HlsManifest::default();
+    let mut pending: Option<Vec<(String, String)>> = None;
+    for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        if let Some(list) = line.strip_prefix("#EXT-X-STREAM-INF:") {
+            pending = Some(attributes(list));
+        } else if let Some(list) = line.strip_prefix("#EXT-X-MEDIA:") {
+            let pairs = attributes(list);
+            let kind = match attribute(&pairs, "TYPE") {
+                Some("AUDIO") => RenditionKind::Audio,
+                Some("VIDEO") => RenditionKind::Video,
+                Some("SUBTITLES") => RenditionKind::Subtitles,
+                Some("CLOSED-CAPTIONS") => RenditionKind::ClosedCaptions,
+                _ => continue,
+            };
+            manifest.renditions.push(HlsRendition {
+                kind,
+                group_id: attribute(&pairs, "GROUP-ID")
+                    .unwrap_or_default()
+                    .to_string(),
+                name: attribute(&pairs, "NAME").unwrap_or_default().to_string(),
+                language: attribute(&pairs, "LANGUAGE").map(str::to_string),
+                default: attribute(&pairs, "DEFAULT") == Some("YES"),
+                url: attribute(&pairs, "URI").map(|uri| resolve(base, uri)),
+            });
+        } else if line.starts_with('#') {
+            continue;
+        } else if let Some(pairs) = pending.take() {
+            let (width, height) = attribute(&pairs, "RESOLUTION")
+                .and_then(|r| r.split_once('x'))
+                .map_or((None, None), |(w, h)| (w.parse().ok(), h.parse().ok()));
+            manifest.variants.push(HlsVariant {
+                url: resolve(base, line),
+                bandwidth: attribute(&pairs, "BANDWIDTH")
+                    .and_then(|b| b.parse().ok())
+                    .unwrap_or(0),
+                average_bandwidth: attribute(&pairs, "AVERAGE-BANDWIDTH")
+                    .and_then(|b| b.parse().ok()),
+                width,
+                height,
+                frame_rate: attribute(&pairs, "FRAME-RATE").and_then(|f| f.parse().ok()),
+                codecs: attribute(&pairs, "CODECS").map(str::to_string),
+                audio_group: attribute(&pairs, "AUDIO").map(str::to_string),
+                standalone: true,
+            });
+        }
+    }
+    if manifest.variants.is_empty() {
+        return None;
+    }
+    for variant in &mut manifest.variants {
+        variant.standalone = !manifest.renditions.iter().any(|r| {
+            r.kind == RenditionKind::Audio
+                && r.url.is_some()
+                && variant.audio_group.as_deref() == Some(r.group_id.as_str())
+        });
+    }
+    manifest
+        .variants
+        .sort_by_key(|v| std::cmp::Reverse(v.bandwidth));
+    Some(manifest)
+}
+
+/// The segments of a media playlist and what stands in the way of joining them end to end.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MediaPlaylist {
+    /// Absolute segment URLs in play order.
+    pub segments: Vec<String>,
+    /// `EXT-X-ENDLIST` was seen, so the list is complete rather than a live window.
+    pub ended: bool,
+    /// Segments are encrypted (`EXT-X-KEY` other than `NONE`).
+    pub encrypted: bool,
+    /// Segments are fMP4 fragments needing an `EXT-X-MAP` init section.
+    pub fragmented: bool,
+    /// Segments are byte ranges of shared files (`EXT-X-BYTERANGE`).
+    pub byte_ranges: bool,
+}
+
+impl MediaPlaylist {
+    /// Whether the segments make a playable file when simply concatenated, as MPEG-TS ones do.
+    pub fn stitchable(&self) -> bool {
+        !self.encrypted && !self.fragmented && !self.byte_ranges
+    }
+}
+
+/// The segments of a media playlist fetched from `base`.
+pub fn parse_media(text: &str, base: &reqwest::Url) -> MediaPlaylist {
+    let mut playlist = MediaPlaylist::default();
+    for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        if let Some(list) = line.strip_prefix("#EXT-X-KEY:") {
+            let pairs = attributes(list);
+            playlist.encrypted |= attribute(&pairs, "METHOD").is_some_and(|m| m != "NONE");
+        } else if line.starts_with("#EXT-X-MAP:") {
+            playlist.fragmented = true;
+        } else if line.starts_with("#EXT-X-BYTERANGE:") {
+            playlist.byte_ranges = true;
+        } else if line == "#EXT-X-ENDLIST" {
+            playlist.ended = true;
+        } else if !line.starts_with('#') {
+            playlist.segments.push(resolve(base, line));
+        }
+    }
+    playlist
+}
+
+/// Fetches `url` and parses it as a master playlist.
+pub async fn fetch_master(
+    url: &str,
+    server: Option<&ServerConfig>,
+) -> Result<Option<HlsManifest>, String> {
+    let resp = crate::http::send(crate::http::client(server)?.get(url))
+        .await
+        .map_err(|e| e.to_string())?;
+    if !resp.status().is_success() {
+        return Err(format!("The playlist returned HTTP {}", resp.status()));
+    }
+    let base = resp.url().clone();
+    // Don't start reading a media stream that was handed in by mistake
+    let playlist = base.path().ends_with(".m3u8")
+        || resp
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|t| t.to_str().ok())
+            .is_some_and(|t| t.to_ascii_lowercase().contains("mpegurl"));
+    if !playlist {
+        return Ok(None);
+    }
+    let text = resp.text().await.map_err(|e| e.to_string())?;
+    if !text.trim_start().starts_with("#EXTM3U") {
+        return Ok(None);
+    }
+    Ok(parse_master(&text, &base))
+}
+
+/// The tallest standalone variant no taller than `max_height`, or the shortest when none fit.
+/// Variants without a resolution (audio-only ones among them) are never picked, so a master
+/// that lists none keeps playing adaptively.
+pub fn pick(manifest: &HlsManifest, max_height: u32) -> Option<&HlsVariant> {
+    let sized = || {
+        manifest
+            .variants
+            .iter()
+            .filter(|v| v.standalone && v.height.is_some())
+    };
+    sized()
+        .filter(|v| v.height.is_some_and(|h| h <= max_height))
+        .max_by_key(|v| (v.height, v.bandwidth))
+        .or_else(|| sized().min_by_key(|v| (v.height, v.bandwidth)))
+}
+
+/// The media playlist to play instead of `url` under the preferred quality setting, when `url`
+/// is a master playlist with a variant that fits.
+pub async fn preferred_variant(
+    settings: &SettingsStore,
+    url: &str,
+    server: Option<&ServerConfig>,
+) -> Option<String> {
+    let max_height = settings.read(|s| s.preferred_quality)?;
+    match fetch_master(url, server).await {
+        Ok(manifest) => {
+            let variant = pick(manifest.as_ref()?, max_height)?;
+            tracing::info!(
+                height = ?variant.height,
+                bandwidth = variant.bandwidth,
+                "Playing the preferred HLS variant"
+            );
+            Some(variant.url.clone())
+        }
+        Err(e) => {
+            tracing::debug!(
+                "Could not read the HLS playlist {}: {}",
+                crate::diagnostics::sanitize_url(url),
+                e
+            );
+            None
+        }
+    }
+}
+
+/// Variants and renditions of an HLS stream, or `None` when it isn't a master playlist.
+#[tauri::command]
+pub async fn get_stream_variants(
+    stream_url: String,
+    server: Option<ServerConfig>,
+) -> Result<Option<HlsManifest>, String> {
+    fetch_master(&stream_url, server.as_ref()).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MASTER: &str = "#EXTM3U
+#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"aac\",NAME=\"English\",LANGUAGE=\"en\",DEFAULT=YES,URI=\"audio/en.m3u8\"
+#EXT-X-MEDIA:TYPE=SUBTITLES,GROUP-ID=\"subs\",NAME=\"Deutsch, Untertitel\",LANGUAGE=\"de\",URI=\"subs/de.m3u8\"
+#EXT-X-MEDIA:TYPE=CLOSED-CAPTIONS,GROUP-ID=\"cc\",NAME=\"CC1\",INSTREAM-ID=\"CC1\"
+#EXT-X-MEDIA:TYPE=UNKNOWN,GROUP-ID=\"x\",NAME=\"Skipped\"
+
+#EXT-X-STREAM-INF:BANDWIDTH=1280000,RESOLUTION=640x360,CODECS=\"avc1.4d401e,mp4a.40.2\"
+low/index.m3u8
+#EXT-X-STREAM-INF:BANDWIDTH=5000000,AVERAGE-BANDWIDTH=4500000,RESOLUTION=1920x1080,FRAME-RATE=50.000,AUDIO=\"aac\"
+https://cdn.example.com/high.m3u8
+#EXT-X-STREAM-INF:BANDWIDTH=2560000,RESOLUTION=1280x720
+mid.m3u8?token=abc
+#EXT-X-STREAM-INF:BANDWIDTH=64000,CODECS=\"mp4a.40.2\"
+audio-only.m3u8
+";
+
+    fn base() -> reqwest::Url {
+        reqwest::Url::parse("http://example.com/live/channel/master.m3u8").unwrap()
+    }
+
+    #[test]
+    fn attribute_lists_keep_commas_in_quotes() {
+        let pairs = attributes("BANDWIDTH=1000,codecs=\"avc1,mp4a\", NAME=\"A\",DEFAULT=YES");
+        assert_eq!(attribute(&pairs, "BANDWIDTH"), Some("1000"));
+        assert_eq!(attribute(&pairs, "CODECS"), Some("avc1,mp4a"));
+        assert_eq!(attribute(&pairs, "NAME"), Some("A"));
+        assert_eq!(attribute(&pairs, "DEFAULT"), Some("YES"));
+        // An unterminated quote takes the rest of the line
+        assert_eq!(
+            attributes("URI=\"a,b"),
+            [("URI".to_string(), "a,b".to_string())]
+        );
+    }
+
+    #[test]
+    fn master_playlists_list_variants_best_first() {
+        let manifest = parse_master(MASTER, &base()).unwrap();
+        let urls: Vec<&str> = manifest.variants.iter().map(|v| v.url.as_str()).collect();
+        assert_eq!(
+            urls,
+            [
+                "https://cdn.example.com/high.m3u8",
+                "http://example.com/live/channel/mid.m3u8?token=abc",
+                "http://example.com/live/channel/low/index.m3u8",
+                "http://example.com/live/channel/audio-only.m3u8",
+            ]
+        );
+        let high = &manifest.variants[0];
+        assert_eq!((high.width, high.height), (Some(1920), Some(1080)));
+        assert_eq!(high.average_bandwidth, Some(4_500_000));
+        assert_eq!(high.frame_rate, Some(50.0));
+        assert_eq!(
+            manifest.variants[2].codecs.as_deref(),
+            Some("avc1.4d401e,mp4a.40.2")
+        );
+        assert_eq!(manifest.variants[3].height, None);
+    }
+
+    #[test]
+    fn variants_with_separate_audio_are_not_standalone() {
+        let manifest = parse_master(MASTER, &base()).unwrap();
+        let standalone: Vec<bool> = manifest.variants.iter().map(|v| v.standalone).collect();
+        assert_eq!(standalone, [false, true, true, true]);
+    }
+
+    #[test]
+    fn renditions_are_read_with_their_uris() {
+        let manifest = parse_master(MASTER, &base()).unwrap();
+        assert_eq!(manifest.renditions.len(), 3);
+        let audio = &manifest.renditions[0];
+        assert_eq!(audio.kind, RenditionKind::Audio);
+        assert_eq!(audio.language.as_deref(), Some("en"));
+        assert!(audio.default);
+        assert_eq!(
+            audio.url.as_deref(),
+            Some("http://example.com/live/channel/audio/en.m3u8")
+        );
+        assert_eq!(manifest.renditions[1].name, "Deutsch, Untertitel");
+        assert!(!manifest.renditions[1].default);
+        assert_eq!(manifest.renditions[2].kind, RenditionKind::ClosedCaptions);
+        assert_eq!(manifest.renditions[2].url, None);
+    }
+
+    #[test]
+    fn media_playlists_are_not_masters() {
+        let media = "#EXTM3U\n#EXT-X-TARGETDURATION:6\n#EXTINF:6.0,\nsegment1.ts\n";
+        assert!(parse_master(media, &base()).is_none());
+    }
+
+    #[test]
+    fn media_playlists_list_their_segments() {
+        let playlist = parse_media(
+            "#EXTM3U\n#EXT-X-TARGETDURATION:6\n#EXTINF:6.0,\nseg1.ts\n#EXTINF:6.0,\n\
+             https://cdn.example.com/seg2.ts\n#EXT-X-ENDLIST\n",
+            &base(),
+        );
+        assert_eq!(
+            playlist.segments,
+            [
+                "http://example.com/live/channel/seg1.ts",
+                "https://cdn.example.com/seg2.ts"
+            ]
+        );
+        assert!(playlist.ended && playlist.stitchable());
+        let live = parse_media("#EXTM3U\n#EXTINF:6.0,\nseg1.ts\n", &base());
+        assert!(!live.ended);
+    }
+
+    #[test]
+    fn encrypted_and_fragmented_segments_are_not_stitchable() {
+        let encrypted = parse_media(
+            "#EXTM3U\n#EXT-X-KEY:METHOD=AES-128,URI=\"k.key\"\n#EXTINF:6.0,\nseg1.ts\n",
+            &base(),
+        );
+        assert!(encrypted.encrypted && !encrypted.stitchable());
+        let clear = parse_media("#EXTM3U\n#EXT-X-KEY:METHOD=NONE\nseg1.ts\n", &base());
+        assert!(clear.stitchable());
+        let fragmented = parse_media(
+            "#EXTM3U\n#EXT-X-MAP:URI=\"init.mp4\"\n#EXTINF:6.0,\nseg1.m4s\n",
+            &base(),
+        );
+        assert!(!fragmented.stitchable());
+    }
+
+    #[test]
+    fn pick_prefers_the_tallest_that_fits() {
+        let manifest = parse_master(MASTER, &base()).unwrap();
+        let height = |max| pick(&manifest, max).and_then(|v| v.height);
+        // 1080p needs the separate audio, so 720p is the best that plays alone
+        assert_eq!(height(2160), Some(720));
+        assert_eq!(height(720), Some(720));
+        assert_eq!(height(480), Some(360));
+        assert_eq!(height(240), Some(360));
+        let without_sizes = parse_master(
+            "#EXTM3U\n#EXT-X-STREAM-INF:BANDWIDTH=1\na.m3u8\n#EXT-X-STREAM-INF:BANDWIDTH=2\nb.m3u8\n",
+            &base(),
+        )
+        .unwrap();
+        assert!(pick(&without_sizes, 1080).is_none());
+    }
+}
@@ -0,0 +1,785 @@
+//! Outbound HTTP shared by the server modules, the stream relay and frontend-driven (Xtream)
+//! servers. Clients go through the server's own proxy and resolver when it has them, else the
+//! global ones from Settings; a server can also opt out of the global ones with a `direct`
+//! proxy or `system` resolver. Clients are pooled per set of options, and requests sent
+//! through `send` are rate limited per host (or by the server's own limit), retried with
+//! backoff, and carry the server's cookies (see `cookies`).
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::cache::CacheKind;
+use crate::dns::{self, DnsConfig, IpFamily};
+use crate::health;
+use crate::offline;
+use crate::servers::ServerConfig;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ProxyKind {
+    /// No proxy, even when a global one is configured.
+    Direct,
+    #[default]
+    Http,
+    Socks5,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ProxyConfig {
+    #[serde(default)]
+    pub kind: ProxyKind,
+    #[serde(default)]
+    pub host: String,
+    #[serde(default)]
+    pub port: u16,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+}
+
+impl ProxyConfig {
+    /// Proxy URL with credentials. SOCKS5 uses `socks5h` so host names are resolved through
+    /// the tunnel, which matters when the provider's domain is blocked locally.
+    fn url(&self) -> Option<String> {
+        let scheme = match self.kind {
+            ProxyKind::Direct => return None,
+            ProxyKind::Http => "http",
+            ProxyKind::Socks5 => "socks5h",
+        };
+        let auth = match (&self.username, &self.password) {
+            (Some(user), password) if !user.is_empty() => format!(
+                "{}:{}@",
+                urlencoding::encode(user),
+                urlencoding::encode(password.as_deref().unwrap_or_default())
+            ),
+            _ => String::new(),
+        };
+        Some(format!("{}://{}{}:{}", scheme, auth, self.host, self.port))
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.kind != ProxyKind::Direct && (self.host.trim().is_empty() || self.port == 0) {
+            return Err("A proxy needs a host and port".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TlsKind {
+    /// Normal certificate verification.
+    #[default]
+    Verify,
+    /// Trust exactly `certificate` (typically self-signed), whatever host name it names.
+    Pinned,
+    /// No verification at all. Anyone on the network path can impersonate the server.
+    Insecure,
+}
+
+/// Per-server override of certificate verification, for home servers with self-signed certs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct TlsConfig {
+    #[serde(default)]
+    pub kind: TlsKind,
+    /// Base64 DER of the pinned certificate, as returned by `fetch_server_certificate`.
+    #[serde(default)]
+    pub certificate: Option<String>,
+    /// SHA-256 fingerprint of `certificate`, shown to the user when pinning it.
+    #[serde(default)]
+    pub fingerprint: Option<String>,
+}
+
+impl TlsConfig {
+    fn pinned_der(&self) -> Result<Vec<u8>, String> {
+        let der = base64::engine::general_purpose::STANDARD
+            .decode(self.certificate.as_deref().unwrap_or_default())
+            .map_err(|_| "The pinned certificate is missing or invalid".to_string())?;
+        if der.is_empty() {
+            return Err("The pinned certificate is missing or invalid".to_string());
+        }
+        Ok(der)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.kind != TlsKind::Pinned {
+            return Ok(());
+        }
+        let actual = fingerprint(&self.pinned_der()?);
+        match &self.fingerprint {
+            Some(expected) if !same_fingerprint(expected, &actual) => Err(format!(
+                "The pinned certificate does not match fingerprint {}",
+                expected
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn apply(&self, builder: reqwest::ClientBuilder) -> Result<reqwest::ClientBuilder, String> {
+        Ok(match self.kind {
+            TlsKind::Verify => builder,
+            // Only the pinned certificate is trusted, so ignoring the host name is safe: nobody
+            // else can present a chain ending in it
+            TlsKind::Pinned => {
+                let cert = reqwest::Certificate::from_der(&self.pinned_der()?)
+                    .map_err(|e| format!("Invalid pinned certificate: {}", e))?;
+                builder
+                    .tls_built_in_root_certs(false)
+                    .add_root_certificate(cert)
+                    .danger_accept_invalid_hostnames(true)
+            }
+            TlsKind::Insecure => builder.danger_accept_invalid_certs(true),
+        })
+    }
+}
+
+/// SHA-256 fingerprint in the usual colon-separated hex form.
+fn fingerprint(der: &[u8]) -> String {
+    Sha256::digest(der)
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+fn same_fingerprint(a: &str, b: &str) -> bool {
+    let normalize = |s: &str| {
+        s.chars()
+            .filter(char::is_ascii_hexdigit)
+            .collect::<String>()
+            .to_ascii_uppercase()
+    };
+    normalize(a) == normalize(b)
+}
+
+/// Per-server request rate, for panels that temporarily ban accounts making too many requests
+/// (typically while refreshing catalogs).
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RateLimit {
+    /// Sustained requests per second; below 1 spaces requests more than a second apart.
+    pub requests_per_second: f64,
+    /// Requests that may go out back to back after a quiet spell.
+    #[serde(default = "RateLimit::default_burst")]
+    pub burst: u32,
+}
+
+impl RateLimit {
+    /// One request every ~17 minutes up to a thousand a second.
+    const MIN_RATE: f64 = 0.001;
+    const MAX_RATE: f64 = 1000.0;
+    const MAX_BURST: u32 = 1000;
+
+    fn default_burst() -> u32 {
+        1
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !(Self::MIN_RATE..=Self::MAX_RATE).contains(&self.requests_per_second) {
+            return Err(format!(
+                "The request rate must be between {} and {} per second",
+                Self::MIN_RATE,
+                Self::MAX_RATE
+            ));
+        }
+        if !(1..=Self::MAX_BURST).contains(&self.burst) {
+            return Err(format!(
+                "The request burst must be between 1 and {}",
+                Self::MAX_BURST
+            ));
+        }
+        Ok(())
+    }
+
+    /// Time between requests; limits saved before they were validated are clamped.
+    fn interval(&self) -> Duration {
+        let rate = self
+            .requests_per_second
+            .clamp(Self::MIN_RATE, Self::MAX_RATE);
+        Duration::try_from_secs_f64(1.0 / rate).unwrap_or(Duration::from_secs(1000))
+    }
+}
+
+/// Network options in Settings that apply to every server unless overridden per server.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct NetworkSettings {
+    pub proxy: Option<ProxyConfig>,
+    pub dns: Option<DnsConfig>,
+    /// IPv4/IPv6 preference for API and stream connections.
+    pub ip_family: IpFamily,
+    pub connect_timeout_secs: u64,
+    /// Overall limit for API requests; streams have none.
+    pub request_timeout_secs: u64,
+    /// Retries of idempotent requests after connection errors, timeouts, 5xx and 429.
+    pub max_retries: u32,
+    /// Requests per second to any one host without a server rate limit of its own; 0 disables
+    /// the limit.
+    pub max_requests_per_second: u32,
+    /// Catalog/EPG fetches in flight across all servers (see `queue`).
+    pub max_concurrent_fetches: u32,
+}
+
+impl NetworkSettings {
+    const DEFAULT: Self = Self {
+        proxy: None,
+        dns: None,
+        ip_family: IpFamily::Auto,
+        connect_timeout_secs: 10,
+        request_timeout_secs: 30,
+        max_retries: 2,
+        max_requests_per_second: 10,
+        max_concurrent_fetches: 8,
+    };
+}
+
+impl Default for NetworkSettings {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Copy of the saved network settings, so clients can be built without the settings store.
+static NETWORK: RwLock<NetworkSettings> = RwLock::new(NetworkSettings::DEFAULT);
+
+/// Clients by the options they were built with. `reqwest::Client` is a handle to a shared
+/// connection pool, so reusing them keeps connections to each server alive between requests.
+static CLIENTS: Mutex<BTreeMap<String, reqwest::Client>> = Mutex::new(BTreeMap::new());
+
+/// Rate limits of saved servers by host, so `send` can apply them without the server store.
+static SERVER_LIMITS: RwLock<BTreeMap<String, RateLimit>> = RwLock::new(BTreeMap::new());
+
+/// When the next request to each host would start if none were allowed in bursts, for the
+/// rate limits.
+static NEXT_SLOT: Mutex<BTreeMap<String, Instant>> = Mutex::new(BTreeMap::new());
+
+/// Fetch queue semaphores with the limit they were created with: `""` is the global one,
+/// other keys are server ids (or hosts, for unsaved servers).
+static FETCH_SLOTS: Mutex<BTreeMap<String, (u32, Arc<Semaphore>)>> = Mutex::new(BTreeMap::new());
+
+/// Catalog fetches in flight to one server unless it sets `max_connections`.
+const DEFAULT_SERVER_FETCHES: u32 = 4;
+
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(10);
+
+fn network() -> std::sync::RwLockReadGuard<'static, NetworkSettings> {
+    NETWORK.read().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Applies saved network settings to clients built from now on.
+pub fn configure(settings: &NetworkSettings) -> Result<(), String> {
+    if let Some(proxy) = &settings.proxy {
+        proxy.validate()?;
+    }
+    if let Some(dns) = &settings.dns {
+        dns.validate()?;
+    }
+    *NETWORK.write().unwrap_or_else(|e| e.into_inner()) = settings.clone();
+    CLIENTS.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    Ok(())
+}
+
+/// Applies the saved servers' rate limits. Servers sharing a host get the strictest of them.
+pub fn configure_servers(servers: &[ServerConfig]) {
+    let mut limits: BTreeMap<String, RateLimit> = BTreeMap::new();
+    for server in servers {
+        let (Some(limit), Some(host)) = (server.rate_limit, crate::health::host_of(server)) else {
+            continue;
+        };
+        if limit.validate().is_err() {
+            continue;
+        }
+        let host = host.to_ascii_lowercase();
+        let stricter = limits
+            .get(&host)
+            .is_none_or(|current| limit.interval() > current.interval());
+        if stricter {
+            limits.insert(host, limit);
+        }
+    }
+    *SERVER_LIMITS.write().unwrap_or_else(|e| e.into_inner()) = limits;
+}
+
+/// The rate limit for requests to `host`: its server's own, else `max_requests_per_second`.
+fn rate_limit(host: &str, per_second: u32) -> Option<RateLimit> {
+    let own = SERVER_LIMITS
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&host.to_ascii_lowercase())
+        .copied();
+    own.or((per_second > 0).then_some(RateLimit {
+        requests_per_second: f64::from(per_second),
+        burst: 1,
+    }))
+}
+
+/// The proxy to use given a per-server override, falling back to the global one.
+pub fn effective_proxy(own: Option<&ProxyConfig>) -> Option<ProxyConfig> {
+    let proxy = match own {
+        Some(proxy) => proxy.clone(),
+        None => network().proxy.clone()?,
+    };
+    (proxy.kind != ProxyKind::Direct).then_some(proxy)
+}
+
+/// The resolver to use given a per-server override, falling back to the global one.
+/// `None` means the system resolver.
+pub fn effective_dns(own: Option<&DnsConfig>) -> Option<DnsConfig> {
+    let dns = match own {
+        Some(dns) => dns.clone(),
+        None => network().dns.clone()?,
+    };
+    (dns.kind != dns::DnsKind::System).then_some(dns)
+}
+
+/// Whether streams from `server` have to be relayed through the local proxy, because the
+/// webview and ffmpeg can't use its proxy, resolver or IP version preference themselves.
+pub fn needs_relay(server: Option<&ServerConfig>) -> bool {
+    effective_proxy(server.and_then(|s| s.proxy.as_ref())).is_some()
+        || effective_dns(server.and_then(|s| s.dns.as_ref())).is_some()
+        || network().ip_family != IpFamily::Auto
+        || server
+            .and_then(|s| s.tls.as_ref())
+            .is_some_and(|tls| tls.kind != TlsKind::Verify)
+}
+
+fn connect_timeout() -> Duration {
+    Duration::from_secs(network().connect_timeout_secs.max(1))
+}
+
+/// Client builder for requests to `server` (`None` means no particular server, so only the
+/// global settings apply), with a connect timeout but no overall timeout.
+fn builder(server: Option<&ServerConfig>) -> Result<reqwest::ClientBuilder, String> {
+    let mut builder = reqwest::Client::builder()
+        .connect_timeout(connect_timeout())
+        .pool_idle_timeout(Duration::from_secs(90));
+    match effective_proxy(server.and_then(|s| s.proxy.as_ref())).and_then(|p| p.url()) {
+        Some(url) => {
+            let proxy = reqwest::Proxy::all(url.as_str())
+                .map_err(|e| format!("Invalid proxy settings: {}", e))?;
+            builder = builder.proxy(proxy);
+        }
+        // Don't pick up proxies from the environment when one was explicitly disabled
+        None => builder = builder.no_proxy(),
+    }
+    let resolver = dns::Resolver::new(
+        effective_dns(server.and_then(|s| s.dns.as_ref())),
+        network().ip_family,
+    );
+    if let Some(resolver) = resolver {
+        builder = builder.dns_resolver(std::sync::Arc::new(resolver));
+    }
+    if let Some(tls) = server.and_then(|s| s.tls.as_ref()) {
+        builder = tls.apply(builder)?;
+    }
+    Ok(builder)
+}
+
+fn cached(
+    key: String,
+    build: impl FnOnce() -> Result<reqwest::ClientBuilder, String>,
+) -> Result<reqwest::Client, String> {
+    let mut clients = CLIENTS.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(client) = clients.get(&key) {
+        return Ok(client.clone());
+    }
+    let client = build()?.build().map_err(|e| e.to_string())?;
+    clients.insert(key, client.clone());
+    Ok(client)
+}
+
+fn cache_key(purpose: &str, server: Option<&ServerConfig>) -> String {
+    serde_json::json!({
+        "purpose": purpose,
+        "proxy": effective_proxy(server.and_then(|s| s.proxy.as_ref())),
+        "dns": effective_dns(server.and_then(|s| s.dns.as_ref())),
+        "tls": server.and_then(|s| s.tls.as_ref()),
+    })
+    .to_string()
+}
+
+/// Shared client for API requests to `server`, with the configured request timeout.
+pub fn client(server: Option<&ServerConfig>) -> Result<reqwest::Client, String> {
+    cached(cache_key("api", server), || {
+        let timeout = Duration::from_secs(network().request_timeout_secs.max(1));
+        Ok(builder(server)?.timeout(timeout))
+    })
+}
+
+/// Shared client for following stream links hop by hop (see `resolve`): the request timeout,
+/// and redirects are returned instead of followed.
+pub fn resolve_client(server: Option<&ServerConfig>) -> Result<reqwest::Client, String> {
+    cached(cache_key("resolve", server), || {
+        let timeout = Duration::from_secs(network().request_timeout_secs.max(1));
+        Ok(builder(server)?
+            .timeout(timeout)
+            .redirect(reqwest::redirect::Policy::none()))
+    })
+}
+
+/// Shared client for streams and other long transfers from `server`: no overall timeout.
+pub fn stream_client(server: Option<&ServerConfig>) -> Result<reqwest::Client, String> {
+    cached(cache_key("stream", server), || builder(server))
+}
+
+/// Shared client that ignores the proxy and resolver settings, for LAN devices (tuners,
+/// webhook receivers) and the DNS-over-HTTPS lookups themselves. `timeout` is the overall
+/// request timeout, `None` for streams.
+pub fn direct_client(timeout: Option<Duration>) -> Result<reqwest::Client, String> {
+    let key = format!("direct:{:?}", timeout);
+    cached(key, || {
+        let builder = reqwest::Client::builder()
+            .connect_timeout(Duration::from_secs(5))
+            .no_proxy();
+        Ok(match timeout {
+            Some(timeout) => builder.timeout(timeout),
+            None => builder,
+        })
+    })
+}
+
+/// A slot in the fetch queue. Dropping it lets the next queued fetch start.
+pub struct FetchPermit {
+    _permits: Vec<OwnedSemaphorePermit>,
+}
+
+fn fetch_slots(key: &str, limit: u32) -> Arc<Semaphore> {
+    let limit = limit.max(1);
+    let mut slots = FETCH_SLOTS.lock().unwrap_or_else(|e| e.into_inner());
+    match slots.get(key) {
+        Some((current, semaphore)) if *current == limit => semaphore.clone(),
+        // New key or changed limit; fetches holding the old semaphore's permits finish normally
+        _ => {
+            let semaphore = Arc::new(Semaphore::new(limit as usize));
+            slots.insert(key.to_string(), (limit, semaphore.clone()));
+            semaphore
+        }
+    }
+}
+
+/// Waits for a slot in the fetch queue, within both the global `max_concurrent_fetches` and
+/// `server`'s own `max_connections`. Hold the permit until the response body has been read,
+/// so that importing several catalogs at once doesn't open more connections than a provider
+/// allows per account. Streams don't go through the queue.
+pub async fn queue(server: Option<&ServerConfig>) -> FetchPermit {
+    let global = fetch_slots("", network().max_concurrent_fetches);
+    let per_server = server.map(|s| {
+        let key = match s.id.as_str() {
+            "" => s.base_url(),
+            id => id,
+        };
+        fetch_slots(key, s.max_connections.unwrap_or(DEFAULT_SERVER_FETCHES))
+    });
+    let mut permits = Vec::with_capacity(2);
+    // Server first, so fetches queued for a busy server don't hold global slots
+    for semaphore in per_server.into_iter().chain([global]) {
+        // Never fails: the semaphores are never closed
+        if let Ok(permit) = semaphore.acquire_owned().await {
+            permits.push(permit);
+        }
+    }
+    FetchPermit { _permits: permits }
+}
+
+/// Error from `send`: the request failed, or its host's circuit is open (see `health`).
+#[derive(Debug)]
+pub enum SendError {
+    Request(reqwest::Error),
+    CircuitOpen { host: String, retry_in: Duration },
+}
+
+impl SendError {
+    /// Whether the host didn't answer at all, as opposed to answering with an error.
+    pub fn is_unreachable(&self) -> bool {
+        match self {
+            SendError::Request(e) => e.is_connect() || e.is_timeout(),
+            SendError::CircuitOpen { .. } => true,
+        }
+    }
+}
+
+impl std::fmt::Display for SendError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SendError::Request(e) => e.fmt(f),
+            SendError::CircuitOpen { host, retry_in } => write!(
+                f,
+                "{} is not responding; trying again in {}s",
+                host,
+                retry_in.as_secs().max(1)
+            ),
+        }
+    }
+}
+
+impl From<reqwest::Error> for SendError {
+    fn from(e: reqwest::Error) -> Self {
+        SendError::Request(e)
+    }
+}
+
+/// Sends `req`, waiting for its host's rate limit slot and retrying idempotent requests with
+/// exponential backoff and jitter after connection errors, timeouts, 5xx and 429 responses.
+/// The last response is returned as-is once the retries run out. Fails fast while the host's
+/// circuit is open.
+pub async fn send(req: reqwest::RequestBuilder) -> Result<reqwest::Response, SendError> {
+    send_with(req, true).await
+}
+
+/// `send` without the circuit check, for callers that expect the host to be down for a while
+/// and keep probing it (waking a server). Results still count towards its health.
+pub async fn send_ignoring_circuit(
+    req: reqwest::RequestBuilder,
+) -> Result<reqwest::Response, SendError> {
+    send_with(req, false).await
+}
+
+async fn send_with(
+    req: reqwest::RequestBuilder,
+    check_circuit: bool,
+) -> Result<reqwest::Response, SendError> {
+    // Requests with streaming bodies can't be cloned, so they get a single attempt
+    let Some(template) = req.try_clone() else {
+        return Ok(req.send().await?);
+    };
+    let Some(Ok(probe)) = template.try_clone().map(|r| r.build()) else {
+        return Ok(req.send().await?);
+    };
+    let host = probe.url().host_str().unwrap_or_default().to_string();
+    // Cookies the caller set itself (see `resolve`) replace the server's jar
+    let (req, template) = match crate::cookies::header_for(probe.url())
+        .filter(|_| !probe.headers().contains_key(reqwest::header::COOKIE))
+    {
+        Some(cookie) => (
+            req.header(reqwest::header::COOKIE, &cookie),
+            template.header(reqwest::header::COOKIE, cookie),
+        ),
+        None => (req, template),
+    };
+    if check_circuit {
+        if let Err(retry_in) = health::check(&host) {
+            return Err(SendError::CircuitOpen { host, retry_in });
+        }
+    }
+    let idempotent = matches!(
+        *probe.method(),
+        reqwest::Method::GET
+            | reqwest::Method::HEAD
+            | reqwest::Method::PUT
+            | reqwest::Method::DELETE
+            | reqwest::Method::OPTIONS
+    );
+    let (max_retries, per_second) = {
+        let network = network();
+        (network.max_retries, network.max_requests_per_second)
+    };
+
+    let mut next = Some(req);
+    let mut attempt = 0;
+    loop {
+        let Some(req) = next.take().or_else(|| template.try_clone()) else {
+            return Ok(template.send().await?);
+        };
+        throttle(&host, rate_limit(&host, per_second)).await;
+        let result = req.send().await;
+        if let Ok(resp) = &result {
+            crate::cookies::store(resp);
+        }
+        let retry_after = match &result {
+            Ok(resp) if resp.status() == reqwest::StatusCode::TOO_MANY_REQUESTS => {
+                Some(retry_after(resp))
+            }
+            Ok(resp) if resp.status().is_server_error() => Some(None),
+            Ok(_) => None,
+            Err(e) if e.is_connect() || e.is_timeout() => Some(None),
+            Err(_) => None,
+        };
+        let Some(retry_after) = retry_after.filter(|_| idempotent && attempt < max_retries) else {
+            record_health(&host, &result);
+            return Ok(result?);
+        };
+        let delay = retry_after.unwrap_or_else(|| backoff(attempt));
+        tracing::debug!("Retrying request to {} in {:?}", host, delay);
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+fn record_health(host: &str, result: &Result<reqwest::Response, reqwest::Error>) {
+    match result {
+        Ok(resp) if resp.status().is_server_error() => {
+            health::record_failure(host, &format!("HTTP {}", resp.status()))
+        }
+        Ok(_) => health::record_success(host),
+        Err(e) if e.is_connect() || e.is_timeout() => health::record_failure(host, &e.to_string()),
+        // Anything else (bad URL, body errors) says nothing about the server
+        Err(_) => {}
+    }
+}
+
+/// `RETRY_BASE_DELAY * 2^attempt`, capped, with the upper half randomised so clients that
+/// failed together don't retry together.
+fn backoff(attempt: u32) -> Duration {
+    let ceiling = RETRY_BASE_DELAY
+        .saturating_mul(1 << attempt.min(16))
+        .min(RETRY_MAX_DELAY);
+    let jitter = (uuid::Uuid::new_v4().as_u128() % 1000) as u32;
+    ceiling / 2 + ceiling / 2 * jitter / 1000
+}
+
+/// A `Retry-After` given in seconds, capped so a server can't stall us for long.
+fn retry_after(resp: &reqwest::Response) -> Option<Duration> {
+    let secs: u64 = resp
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    Some(Duration::from_secs(secs).min(RETRY_MAX_DELAY))
+}
+
+/// Waits until a request to `host` fits within `limit`: up to `burst` requests at once, then
+/// one per interval.
+async fn throttle(host: &str, limit: Option<RateLimit>) {
+    let Some(limit) = limit.filter(|_| !host.is_empty()) else {
+        return;
+    };
+    let interval = limit.interval();
+    // How far ahead of the schedule a burst may run
+    let tolerance = interval.saturating_mul(limit.burst.clamp(1, RateLimit::MAX_BURST) - 1);
+    let wait = {
+        let mut slots = NEXT_SLOT.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        let slot = slots
+            .get(host)
+            .copied()
+            .filter(|slot| *slot > now)
+            .unwrap_or(now);
+        slots.insert(host.to_string(), slot.checked_add(interval).unwrap_or(slot));
+        (slot - now).saturating_sub(tolerance)
+    };
+    if !wait.is_zero() {
+        tokio::time::sleep(wait).await;
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FetchResponse {
+    pub status: u16,
+    pub body: String,
+    /// True when the server couldn't be reached and this is the last cached copy.
+    #[serde(default)]
+    pub stale: bool,
+    /// Unix seconds when the body was fetched, for cached or cacheable responses.
+    #[serde(default)]
+    pub fetched_at: Option<u64>,
+}
+
+async fn fetch(url: &str, server: Option<&ServerConfig>) -> Result<FetchResponse, String> {
+    let _permit = queue(server).await;
+    let resp = send(client(server)?.get(url))
+        .await
+        .map_err(|e| format!("Network error: {}", e))?;
+    let status = resp.status().as_u16();
+    let body = resp
+        .text()
+        .await
+        .map_err(|e| format!("Network error: {}", e))?;
+    Ok(FetchResponse {
+        status,
+        body,
+        stale: false,
+        fetched_at: None,
+    })
+}
+
+/// GET for servers whose API client lives in the frontend, so their requests still use the
+/// configured proxy and resolver. `server` may be unsaved, e.g. while logging in. Successful
+/// responses for a server with an id are cached and returned, flagged stale, when a later
+/// request can't reach it (offline mode).
+#[tauri::command]
+pub async fn http_fetch(
+    app: tauri::AppHandle,
+    url: String,
+    server: Option<ServerConfig>,
+) -> Result<FetchResponse, String> {
+    if let Some(server) = &server {
+        server.validate_network()?;
+    }
+    let server_id = server.as_ref().map(|s| s.id.clone()).unwrap_or_default();
+    if server_id.is_empty() {
+        return fetch(&url, server.as_ref()).await;
+    }
+    // Xtream guide calls: get_short_epg, get_simple_data_table, xmltv.php
+    let kind = if url.contains("epg") || url.contains("get_simple_data_table") {
+        CacheKind::Epg
+    } else {
+        CacheKind::Catalogs
+    };
+    match fetch(&url, server.as_ref()).await {
+        Ok(mut resp) => {
+            if (200..300).contains(&resp.status) {
+                resp.fetched_at = Some(offline::save(&app, kind, &server_id, &url, &resp.body));
+            }
+            Ok(resp)
+        }
+        Err(e) => {
+            let (body, fetched_at) =
+                offline::load::<String>(&app, kind, &server_id, &url).ok_or(e)?;
+            Ok(FetchResponse {
+                status: 200,
+                body,
+                stale: true,
+                fetched_at: Some(fetched_at),
+            })
+        }
+    }
+}
+
+/// Certificate presented by the server at `url`, without verifying it, so the user can check
+/// its fingerprint before pinning it.
+#[tauri::command]
+pub async fn fetch_server_certificate(
+    url: String,
+    server: Option<ServerConfig>,
+) -> Result<TlsConfig, String> {
+    if !url.starts_with("https://") {
+        return Err("Only https:// servers have a certificate".to_string());
+    }
+    // Keep the server's proxy and resolver, but not its TLS override
+    let server = server.map(|s| ServerConfig { tls: None, ..s });
+    let client = builder(server.as_ref())?
+        .danger_accept_invalid_certs(true)
+        .tls_info(true)
+        .timeout(Duration::from_secs(15))
+        .build()
+        .map_err(|e| e.to_string())?;
+    let resp = client
+        .head(&url)
+        .send()
+        .await
+        .map_err(|e| format!("Network error: {}", e))?;
+    let der = resp
+        .extensions()
+        .get::<reqwest::tls::TlsInfo>()
+        .and_then(|info| info.peer_certificate())
+        .ok_or_else(|| "The server did not present a certificate".to_string())?;
+    Ok(TlsConfig {
+        kind: TlsKind::Pinned,
+        certificate: Some(base64::engine::general_purpose::STANDARD.encode(der)),
+        fingerprint: Some(fingerprint(der)),
+    })
+}
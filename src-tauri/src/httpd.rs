@@ -0,0 +1,265 @@
+//! Minimal HTTP/1.1 server plumbing for the remote-control API: one thread per connection,
+//! `Connection: close`, bodies sized by `Content-Length` only. Connections beyond
+//! `MAX_CONNECTIONS` get a 503, and a client has `IO_TIMEOUT` for each read and write, so
+//! sockets that never send anything don't hold threads.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+const MAX_HEADER_BYTES: usize = 64 * 1024;
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+/// Connections handled at once, WebSocket clients included.
+const MAX_CONNECTIONS: usize = 32;
+/// For each read and write on a connection.
+const IO_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct Request {
+    pub method: String,
+    /// Path without the query string.
+    pub path: String,
+    pub query: Vec<(String, String)>,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    /// Header value by case-insensitive name.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn query_param(&self, name: &str) -> Option<&str> {
+        self.query
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T, String> {
+        serde_json::from_slice(&self.body).map_err(|e| format!("Invalid request body: {}", e))
+    }
+}
+
+/// Reads one request from `stream`. The reader keeps nothing buffered past the body, so the
+/// stream can be handed on (e.g. after a WebSocket upgrade).
+pub fn read_request(stream: &TcpStream) -> Result<Request, String> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader
+        .read_line(&mut line)
+        .map_err(|e| format!("Failed to read request: {}", e))?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Err("Malformed request line".to_string());
+    };
+    let (method, target) = (method.to_string(), target.to_string());
+
+    let mut headers = Vec::new();
+    let mut header_bytes = 0;
+    loop {
+        line.clear();
+        let n = reader
+            .read_line(&mut line)
+            .map_err(|e| format!("Failed to read request: {}", e))?;
+        header_bytes += n;
+        if n == 0 || line.trim_end().is_empty() {
+            break;
+        }
+        if header_bytes > MAX_HEADER_BYTES {
+            return Err("Request headers too large".to_string());
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+
+    let length: usize = headers
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, v)| v.parse().ok())
+        .unwrap_or(0);
+    if length > MAX_BODY_BYTES {
+        return Err("Request body too large".to_string());
+    }
+    let mut body = vec![0; length];
+    reader
+        .read_exact(&mut body)
+        .map_err(|e| format!("Failed to read request body: {}", e))?;
+
+    let (path, query) = target.split_once('?').unwrap_or((&target, ""));
+    let query = query
+        .split('&')
+        .filter(|p| !p.is_empty())
+        .map(|pair| {
+            let (k, v) = pair.split_once('=').unwrap_or((pair, ""));
+            let decode = |s: &str| {
+                urlencoding::decode(&s.replace('+', " "))
+                    .map(|c| c.into_owned())
+                    .unwrap_or_else(|_| s.to_string())
+            };
+            (decode(k), decode(v))
+        })
+        .collect();
+    Ok(Request {
+        method,
+        path: path.to_string(),
+        query,
+        headers,
+        body,
+    })
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        429 => "Too Many Requests",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    }
+}
+
+pub fn respond(stream: &mut TcpStream, status: u16, content_type: &str, body: &[u8]) {
+    let head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        reason(status),
+        content_type,
+        body.len()
+    );
+    let _ = stream.write_all(head.as_bytes());
+    let _ = stream.write_all(body);
+}
+
+pub fn respond_json<T: Serialize>(stream: &mut TcpStream, status: u16, value: &T) {
+    let body = serde_json::to_vec(value).unwrap_or_default();
+    respond(stream, status, "application/json", &body);
+}
+
+/// Error responses are `{"error": "..."}`.
+pub fn respond_error(stream: &mut TcpStream, status: u16, message: &str) {
+    respond_json(stream, status, &serde_json::json!({ "error": message }));
+}
+
+/// A listening server; dropping it stops accepting connections.
+pub struct Server {
+    addr: SocketAddr,
+    stop: Arc<AtomicBool>,
+}
+
+impl Server {
+    pub fn port(&self) -> u16 {
+        self.addr.port()
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        // Wake the blocking accept so the thread sees the flag
+        let wake = match self.addr {
+            SocketAddr::V4(a) if a.ip().is_unspecified() => {
+                SocketAddr::from(([127, 0, 0, 1], a.port()))
+            }
+            SocketAddr::V6(a) if a.ip().is_unspecified() => {
+                SocketAddr::from((std::net::Ipv6Addr::LOCALHOST, a.port()))
+            }
+            addr => addr,
+        };
+        let _ = TcpStream::connect(wake);
+    }
+}
+
+/// A slot in the connection limit, freed when the handler returns.
+struct Slot(Arc<AtomicUsize>);
+
+impl Slot {
+    fn take(active: &Arc<AtomicUsize>) -> Option<Slot> {
+        active
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| {
+                (n < MAX_CONNECTIONS).then_some(n + 1)
+            })
+            .ok()
+            .map(|_| Slot(active.clone()))
+    }
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Binds `addr` and calls `handler` on its own thread for every connection, up to
+/// `MAX_CONNECTIONS` at once.
+pub fn serve(
+    addr: SocketAddr,
+    handler: impl Fn(TcpStream) + Send + Sync + 'static,
+) -> Result<Server, String> {
+    let listener =
+        TcpListener::bind(addr).map_err(|e| format!("Failed to listen on {}: {}", addr, e))?;
+    let addr = listener.local_addr().map_err(|e| e.to_string())?;
+    let stop = Arc::new(AtomicBool::new(false));
+    let stopped = stop.clone();
+    let handler = Arc::new(handler);
+    let active = Arc::new(AtomicUsize::new(0));
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            if stopped.load(Ordering::SeqCst) {
+                break;
+            }
+            let Ok(mut stream) = stream else {
+                continue;
+            };
+            if stream.set_read_timeout(Some(IO_TIMEOUT)).is_err()
+                || stream.set_write_timeout(Some(IO_TIMEOUT)).is_err()
+            {
+                continue;
+            }
+            let Some(slot) = Slot::take(&active) else {
+                respond_error(&mut stream, 503, "Too many connections");
+                continue;
+            };
+            let handler = handler.clone();
+            std::thread::spawn(move || {
+                let _slot = slot;
+                handler(stream)
+            });
+        }
+    });
+    Ok(Server { addr, stop })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slots_cap_concurrent_connections() {
+        let active = Arc::new(AtomicUsize::new(0));
+        let mut slots: Vec<Slot> = (0..MAX_CONNECTIONS)
+            .map(|_| Slot::take(&active).unwrap())
+            .collect();
+        assert!(Slot::take(&active).is_none());
+        slots.pop();
+        assert!(Slot::take(&active).is_some());
+        drop(slots);
+        assert_eq!(active.load(Ordering::SeqCst), 0);
+    }
+}
@@ -0,0 +1,153 @@
+//! Hardware video acceleration detection via ffmpeg. Used to pick transcoder encoders and to
+//! warn before playing content the machine probably can't decode in real time.
+
+use std::process::Command;
+
+use serde::Serialize;
+
+/// Hardware encoders we know how to drive; the first working H.264 one is preferred.
+const HW_ENCODERS: [&str; 9] = [
+    "h264_nvenc",
+    "hevc_nvenc",
+    "h264_qsv",
+    "hevc_qsv",
+    "h264_vaapi",
+    "hevc_vaapi",
+    "h264_videotoolbox",
+    "hevc_videotoolbox",
+    "h264_amf",
+];
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HwCaps {
+    /// Decode acceleration methods ffmpeg was built with (`cuda`, `qsv`, `vaapi`, `dxva2`,
+    /// `d3d11va`, `videotoolbox`, ...).
+    pub hwaccels: Vec<String>,
+    /// Hardware encoders that compiled in and opened a test encode on this machine.
+    pub encoders: Vec<String>,
+    /// Preferred H.264 hardware encoder for transcoding, if any.
+    pub h264_encoder: Option<String>,
+    /// Whether 4K HEVC can likely be decoded in hardware.
+    pub hevc_4k_decode: bool,
+    pub cpu_threads: usize,
+}
+
+fn ffmpeg_stdout(args: &[&str]) -> Result<String, String> {
+    let output = Command::new(crate::ffmpeg::ffmpeg_path()?)
+        .args(["-hide_banner"])
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to run ffmpeg: {}", e))?;
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// A compiled-in encoder can still fail without a GPU/driver, so encode one blank frame.
+fn encoder_works(encoder: &str) -> bool {
+    let Ok(ffmpeg) = crate::ffmpeg::ffmpeg_path() else {
+        return false;
+    };
+    let mut cmd = Command::new(ffmpeg);
+    cmd.args(["-hide_banner", "-loglevel", "error", "-nostdin"]);
+    if encoder.ends_with("_vaapi") {
+        cmd.args(["-vaapi_device", "/dev/dri/renderD128"]);
+    }
+    cmd.args(["-f", "lavfi", "-i", "color=black:s=256x256:d=0.1"]);
+    if encoder.ends_with("_vaapi") {
+        cmd.args(["-vf", "format=nv12,hwupload"]);
+    }
+    cmd.args(["-frames:v", "1", "-c:v", encoder, "-f", "null", "-"])
+        .status()
+        .is_ok_and(|s| s.success())
+}
+
+fn detect() -> Result<HwCaps, String> {
+    let hwaccels: Vec<String> = ffmpeg_stdout(&["-hwaccels"])?
+        .lines()
+        .skip_while(|l| !l.starts_with("Hardware acceleration methods"))
+        .skip(1)
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .map(String::from)
+        .collect();
+    let listed = ffmpeg_stdout(&["-encoders"])?;
+    let encoders: Vec<String> = HW_ENCODERS
+        .iter()
+        .copied()
+        .filter(|name| listed.split_whitespace().any(|w| w == *name))
+        .filter(|name| encoder_works(name))
+        .map(String::from)
+        .collect();
+    let h264_encoder = encoders.iter().find(|e| e.starts_with("h264_")).cloned();
+    let hevc_4k_decode = hwaccels.iter().any(|h| {
+        matches!(
+            h.as_str(),
+            "cuda" | "qsv" | "vaapi" | "videotoolbox" | "d3d11va" | "dxva2"
+        )
+    });
+    Ok(HwCaps {
+        hwaccels,
+        encoders,
+        h264_encoder,
+        hevc_4k_decode,
+        cpu_threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+    })
+}
+
+/// Detected capabilities, probed once per run (the test encodes take a few seconds).
+pub async fn caps() -> Result<HwCaps, String> {
+    static CAPS: std::sync::OnceLock<HwCaps> = std::sync::OnceLock::new();
+    if let Some(caps) = CAPS.get() {
+        return Ok(caps.clone());
+    }
+    let detected = tauri::async_runtime::spawn_blocking(detect)
+        .await
+        .map_err(|e| e.to_string())??;
+    Ok(CAPS.get_or_init(|| detected).clone())
+}
+
+/// Encoder arguments replacing `libx264` when a working hardware H.264 encoder exists.
+pub fn h264_args(caps: &HwCaps) -> Vec<String> {
+    let args: &[&str] = match caps.h264_encoder.as_deref() {
+        Some("h264_nvenc") => &["-c:v", "h264_nvenc", "-preset", "p4", "-cq", "23"],
+        Some("h264_qsv") => &[
+            "-c:v",
+            "h264_qsv",
+            "-preset",
+            "veryfast",
+            "-global_quality",
+            "23",
+        ],
+        Some("h264_videotoolbox") => &["-c:v", "h264_videotoolbox", "-q:v", "65"],
+        Some("h264_amf") => &["-c:v", "h264_amf", "-quality", "speed"],
+        // VAAPI needs a device and hwupload filter chain; software is simpler and reliable
+        _ => &["-c:v", "libx264", "-preset", "veryfast", "-crf", "21"],
+    };
+    let mut args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+    args.extend(["-pix_fmt", "yuv420p", "-g", "48"].map(String::from));
+    args
+}
+
+/// A user-facing warning when `probe` is 4K video this machine likely can't decode smoothly.
+pub fn playback_warning(probe: &crate::probe::ProbeResult, caps: &HwCaps) -> Option<String> {
+    let video = probe.video()?;
+    if video.height.unwrap_or(0) < 2000 {
+        return None;
+    }
+    let hw_decode = match video.codec.as_str() {
+        "hevc" | "vp9" | "av1" => caps.hevc_4k_decode,
+        _ => !caps.hwaccels.is_empty(),
+    };
+    if hw_decode || caps.cpu_threads >= 8 {
+        return None;
+    }
+    Some(format!(
+        "This is 4K {} video and no hardware decoder was found; playback may stutter.",
+        video.codec.to_uppercase()
+    ))
+}
+
+#[tauri::command]
+pub async fn get_hw_caps() -> Result<HwCaps, String> {
+    caps().await
+}
@@ -0,0 +1,225 @@
+//! ICY (Shoutcast/Icecast) metadata in radio streams. Asked for with `Icy-MetaData: 1`, a
+//! server that supports it says how many audio bytes it sends between metadata blocks in
+//! `icy-metaint`; each block is a length byte (in units of 16) followed by text such as
+//! `StreamTitle='Artist - Song';`. The relay strips the blocks so the player only sees audio,
+//! and reports the current song (see `proxy`).
+
+use serde::Serialize;
+use tauri::State;
+
+use crate::proxy::ProxyState;
+
+/// Request header asking the server to interleave metadata.
+pub const REQUEST_HEADER: &str = "Icy-MetaData";
+const METAINT_HEADER: &str = "icy-metaint";
+const NAME_HEADER: &str = "icy-name";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NowPlaying {
+    /// Station name from `icy-name`.
+    pub station: Option<String>,
+    pub artist: Option<String>,
+    /// The song, or the whole `StreamTitle` when it isn't `Artist - Song`.
+    pub title: String,
+}
+
+enum Position {
+    /// Audio bytes left before the next metadata block.
+    Audio(usize),
+    Length,
+    /// Metadata bytes left in the current block.
+    Metadata(usize),
+}
+
+/// Separates the metadata blocks of one response from its audio.
+pub struct IcyStream {
+    metaint: usize,
+    station: Option<String>,
+    position: Position,
+    block: Vec<u8>,
+}
+
+impl IcyStream {
+    /// Reads the response's ICY headers; `None` when the server doesn't interleave metadata.
+    pub fn from_headers(headers: &reqwest::header::HeaderMap) -> Option<IcyStream> {
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .map(|v| {
+                    crate::charset::decode(v.as_bytes(), None)
+                        .0
+                        .trim()
+                        .to_string()
+                })
+                .filter(|v| !v.is_empty())
+        };
+        let metaint = header(METAINT_HEADER)?.parse().ok().filter(|&n| n > 0)?;
+        Some(IcyStream {
+            metaint,
+            station: header(NAME_HEADER),
+            position: Position::Audio(metaint),
+            block: Vec::new(),
+        })
+    }
+
+    /// Appends the audio in `chunk` to `audio`, returning the title of the last metadata
+    /// block completed in it, if any carried one.
+    pub fn feed(&mut self, mut chunk: &[u8], audio: &mut Vec<u8>) -> Option<NowPlaying> {
+        let mut now_playing = None;
+        while !chunk.is_empty() {
+            match self.position {
+                Position::Audio(left) => {
+                    let take = left.min(chunk.len());
+                    audio.extend_from_slice(&chunk[..take]);
+                    chunk = &chunk[take..];
+                    self.position = if take == left {
+                        Position::Length
+                    } else {
+                        Position::Audio(left - take)
+                    };
+                }
+                Position::Length => {
+                    let len = chunk[0] as usize * 16;
+                    chunk = &chunk[1..];
+                    self.block.clear();
+                    self.position = if len == 0 {
+                        Position::Audio(self.metaint)
+                    } else {
+                        Position::Metadata(len)
+                    };
+                }
+                Position::Metadata(left) => {
+                    let take = left.min(chunk.len());
+                    self.block.extend_from_slice(&chunk[..take]);
+                    chunk = &chunk[take..];
+                    if take < left {
+                        self.position = Position::Metadata(left - take);
+                        continue;
+                    }
+                    self.position = Position::Audio(self.metaint);
+                    let text = crate::charset::decode(&self.block, None).0;
+                    if let Some(title) = stream_title(&text) {
+                        now_playing = Some(self.now_playing(title));
+                    }
+                }
+            }
+        }
+        now_playing
+    }
+
+    fn now_playing(&self, stream_title: &str) -> NowPlaying {
+        let (artist, title) = match stream_title.split_once(" - ") {
+            Some((artist, title)) if !artist.trim().is_empty() && !title.trim().is_empty() => {
+                (Some(artist.trim().to_string()), title.trim().to_string())
+            }
+            _ => (None, stream_title.to_string()),
+        };
+        NowPlaying {
+            station: self.station.clone(),
+            artist,
+            title,
+        }
+    }
+}
+
+/// The `StreamTitle` of a metadata block. Titles may contain quotes, so the value runs to the
+/// `';` that ends the field rather than the next quote.
+fn stream_title(block: &str) -> Option<&str> {
+    let start = block.find("StreamTitle='")? + "StreamTitle='".len();
+    let rest = block[start..].trim_end_matches('\0');
+    let end = rest
+        .find("';")
+        .or_else(|| rest.rfind('\''))
+        .unwrap_or(rest.len());
+    Some(rest[..end].trim()).filter(|t| !t.is_empty())
+}
+
+/// What the radio station in the window `label` is playing, as last reported.
+#[tauri::command]
+pub fn get_now_playing(proxy: State<'_, ProxyState>, label: String) -> Option<NowPlaying> {
+    proxy.now_playing_owned_by(&label)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stream(metaint: &str, name: Option<&str>) -> Option<IcyStream> {
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert(METAINT_HEADER, metaint.parse().unwrap());
+        if let Some(name) = name {
+            headers.insert(NAME_HEADER, name.parse().unwrap());
+        }
+        IcyStream::from_headers(&headers)
+    }
+
+    /// `text` as a metadata block: its length byte and the text padded to 16 bytes.
+    fn block(text: &str) -> Vec<u8> {
+        let mut out = vec![text.len().div_ceil(16) as u8];
+        out.extend_from_slice(text.as_bytes());
+        out.resize(1 + out[0] as usize * 16, 0);
+        out
+    }
+
+    #[test]
+    fn headers_need_a_metadata_interval() {
+        assert!(stream("0", None).is_none());
+        assert!(stream("many", None).is_none());
+        assert!(IcyStream::from_headers(&reqwest::header::HeaderMap::new()).is_none());
+        assert_eq!(stream(" 16000 ", None).unwrap().metaint, 16000);
+    }
+
+    #[test]
+    fn metadata_is_stripped_from_the_audio() {
+        let mut icy = stream("4", Some("Jazz FM")).unwrap();
+        let mut data = b"abcd".to_vec();
+        data.extend(block("StreamTitle='Miles Davis - So What';"));
+        data.extend_from_slice(b"efgh");
+        // An empty block carries no title
+        data.push(0);
+        data.extend_from_slice(b"ij");
+        let mut audio = Vec::new();
+        let now_playing = icy.feed(&data, &mut audio);
+        assert_eq!(audio, b"abcdefghij");
+        assert_eq!(
+            now_playing,
+            Some(NowPlaying {
+                station: Some("Jazz FM".to_string()),
+                artist: Some("Miles Davis".to_string()),
+                title: "So What".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn blocks_split_across_chunks() {
+        let mut icy = stream("2", None).unwrap();
+        let mut data = b"ab".to_vec();
+        data.extend(block("StreamTitle='News';"));
+        data.extend_from_slice(b"cd");
+        let mut audio = Vec::new();
+        let mut titles = Vec::new();
+        for chunk in data.chunks(3) {
+            titles.extend(icy.feed(chunk, &mut audio));
+        }
+        assert_eq!(audio, b"abcd");
+        assert_eq!(titles.len(), 1);
+        assert_eq!(titles[0].artist, None);
+        assert_eq!(titles[0].title, "News");
+    }
+
+    #[test]
+    fn titles_run_to_the_end_of_the_field() {
+        assert_eq!(
+            stream_title("StreamTitle='Guns N' Roses - Don't Cry';StreamUrl='';"),
+            Some("Guns N' Roses - Don't Cry")
+        );
+        assert_eq!(
+            stream_title("StreamTitle='Unterminated'\0\0"),
+            Some("Unterminated")
+        );
+        assert_eq!(stream_title("StreamTitle='';"), None);
+        assert_eq!(stream_title("StreamUrl='http://example.com';"), None);
+    }
+}
@@ -0,0 +1,95 @@
+//! Non-HTTP live sources: RTSP cameras and encoders, and UDP/RTP multicast (`udp://@group:port`)
+//! as campus and ISP IPTV lineups carry them. The webview can't open these at all, so they
+//! always go through an ffmpeg HLS session (see `transcode`), which serves them over HTTP from
+//! the local proxy. This module has the ffmpeg options that make the inputs behave: RTSP over
+//! TCP, which survives NAT and lossy Wi-Fi, and a larger, non-fatal receive buffer and a read
+//! timeout for multicast.
+//!
+//! Most home multicast setups are consumed through a udpxy gateway on the router instead.
+//! Servers with a udpxy address get their `udp://@group:port` and `rtp://@group:port`
+//! channels rewritten to the gateway's `/udp/group:port` HTTP stream.
+
+use crate::servers::ServerConfig;
+
+/// Microseconds without data before a multicast read gives up.
+const UDP_TIMEOUT_US: u64 = 10_000_000;
+/// Receive buffer for multicast, in 188-byte packets.
+const UDP_FIFO_PACKETS: u32 = 100_000;
+
+/// Whether `url` is a source only ffmpeg can read.
+pub fn is_ingest(url: &str) -> bool {
+    let scheme = url.split_once("://").map(|(s, _)| s.to_ascii_lowercase());
+    matches!(scheme.as_deref(), Some("rtsp" | "rtsps" | "udp" | "rtp"))
+}
+
+fn is_multicast(url: &str) -> bool {
+    let scheme = url.split_once("://").map(|(s, _)| s.to_ascii_lowercase());
+    matches!(scheme.as_deref(), Some("udp" | "rtp"))
+}
+
+/// `url` through `server`'s udpxy gateway, when it has one and `url` is multicast.
+pub fn via_udpxy(server: Option<&ServerConfig>, url: &str) -> Option<String> {
+    let gateway = server?.udpxy_url.as_deref()?.trim().trim_end_matches('/');
+    if gateway.is_empty() {
+        return None;
+    }
+    let (scheme, rest) = url.split_once("://")?;
+    let scheme = scheme.to_ascii_lowercase();
+    if !matches!(scheme.as_str(), "udp" | "rtp") {
+        return None;
+    }
+    // `source@group:port` (source-specific multicast) and `@group:port` both join the group
+    let rest = rest.split(['?', '/']).next().unwrap_or_default();
+    let group = rest.rsplit('@').next().unwrap_or(rest);
+    if group.is_empty() {
+        return None;
+    }
+    Some(format!("{}/{}/{}", gateway, scheme, group))
+}
+
+/// `url` with the multicast receive options added to its query, for ffmpeg and ffprobe.
+pub fn input_url(url: &str) -> String {
+    if !is_multicast(url) {
+        return url.to_string();
+    }
+    let keys: Vec<&str> = url
+        .split_once('?')
+        .map(|(_, query)| {
+            query
+                .split('&')
+                .map(|pair| pair.split('=').next().unwrap_or_default())
+                .collect()
+        })
+        .unwrap_or_default();
+    let mut options = Vec::new();
+    for (key, value) in [
+        ("fifo_size", UDP_FIFO_PACKETS.to_string()),
+        ("overrun_nonfatal", "1".to_string()),
+        ("timeout", UDP_TIMEOUT_US.to_string()),
+    ] {
+        // Options already in the playlist's URL win
+        if !keys.contains(&key) {
+            options.push(format!("{}={}", key, value));
+        }
+    }
+    if options.is_empty() {
+        return url.to_string();
+    }
+    let separator = if url.contains('?') { '&' } else { '?' };
+    format!("{}{}{}", url, separator, options.join("&"))
+}
+
+/// ffmpeg and ffprobe options for `url`, to go before the input.
+pub fn input_options(url: &str) -> Vec<String> {
+    if url.to_ascii_lowercase().starts_with("rtsp") {
+        // Not `-timeout`: before ffmpeg 5 that puts RTSP in listen mode
+        ["-rtsp_transport", "tcp", "-rw_timeout", "10000000"]
+            .map(String::from)
+            .to_vec()
+    } else if is_multicast(url) {
+        // Multicast joins mid-stream, without timestamps to start from
+        ["-fflags", "+genpts"].map(String::from).to_vec()
+    } else {
+        Vec::new()
+    }
+}
@@ -1,56 +1,340 @@
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 
+mod account;
+mod audio;
+mod audiofocus;
+mod autoplay;
+mod backup;
+mod cache;
+mod catalog;
+mod catchup;
+mod chapters;
+mod charset;
+mod clips;
+mod conflicts;
+mod connections;
+mod cookies;
+mod crash;
+mod datausage;
+mod diagnostics;
+mod discovery;
+mod dns;
+mod downloads;
+mod drm;
+mod edits;
+mod emby;
+mod enigma2;
+mod epg;
+mod failure;
+mod favorites;
+mod feed;
+mod ffmpeg;
+mod gamepad;
+mod genre;
+mod hdhomerun;
+mod health;
+mod history;
+mod hls;
+mod http;
+mod httpd;
+mod hwaccel;
+mod icy;
+mod ingest;
+mod library;
+mod lock;
+mod logging;
+mod m3u;
+mod mdns;
+mod merge;
+mod mpv;
+mod mqtt;
+mod network;
+mod offline;
+mod opensubtitles;
+mod pairing;
+mod playback;
+mod playlist;
+mod power;
+mod probe;
+mod progress;
+mod proxy;
+mod quality;
+mod queue;
+mod recommend;
+mod recorder;
+mod recordings;
+mod reminders;
+mod remote;
+mod resolve;
+mod satip;
+mod screenshot;
+mod servers;
+mod settings;
+mod shortcuts;
+mod snapshot;
+mod ssdp;
+mod stalker;
+mod store;
+mod streamstats;
+mod subtitles;
+mod sync;
+mod timeshift;
+mod tracks;
+mod transcode;
+mod tvheadend;
+mod tz;
+mod updater;
+mod verify;
+mod vlc;
+mod vod;
+mod volume;
+mod watchlater;
+mod webhooks;
+mod websocket;
+mod wol;
+mod zap;
+mod zip;
+
 use std::path::PathBuf;
-use std::process::Command;
-use tauri::Manager;
+use tauri::{Emitter, Manager};
 
-/// Tries to open the given URL in VLC. Tries common install paths on Windows.
+/// Plays a stream the way `playback::choose` decides: in a video window, through the
+/// transcoder, or in VLC. Returns the video window's label, `None` for VLC. The stream is
+/// resolved, relayed and counted against `server`'s connection limit as `play_stream` does,
+/// and probed once, on what the player will read. With `reuse` (by default the
+/// `reuse_video_window` setting) the newest open video window plays streams it can play
+/// directly, and is replaced by the transcoder's window otherwise. Async to avoid Windows
+/// deadlock.
 #[tauri::command]
-fn open_in_vlc(url: String) -> Result<(), String> {
-    let vlc_path = if cfg!(target_os = "windows") {
-        // Try PATH first, then common install locations
-        let path_vlc = which::which("vlc").ok();
-        let path_vlc_exe = which::which("vlc.exe").ok();
-        path_vlc
-            .or(path_vlc_exe)
-            .map(|p| p.to_string_lossy().into_owned())
-            .or_else(|| {
-                let pf = std::env::var("ProgramFiles").ok()?;
-                let path = PathBuf::from(pf).join("VideoLAN").join("VLC").join("vlc.exe");
-                path.exists().then(|| path.to_string_lossy().into_owned())
-            })
-            .or_else(|| {
-                let pf = std::env::var("ProgramFiles(x86)").ok()?;
-                let path = PathBuf::from(pf).join("VideoLAN").join("VLC").join("vlc.exe");
-                path.exists().then(|| path.to_string_lossy().into_owned())
-            })
-    } else if cfg!(target_os = "macos") {
-        which::which("vlc").ok().map(|p| p.to_string_lossy().into_owned()).or_else(|| {
-            let p = PathBuf::from("/Applications/VLC.app/Contents/MacOS/VLC");
-            p.exists().then(|| p.to_string_lossy().into_owned())
-        })
-    } else {
-        which::which("vlc").ok().map(|p| p.to_string_lossy().into_owned())
-    };
-
-    let vlc_path = vlc_path.ok_or_else(|| {
-        "VLC not found. Install VLC and ensure it is in your PATH or in Program Files (Windows).".to_string()
-    })?;
-
-    Command::new(&vlc_path)
-        .arg(&url)
-        .spawn()
-        .map_err(|e| format!("Failed to start VLC: {}", e))?;
+async fn open_video_window(
+    app: tauri::AppHandle,
+    title: String,
+    stream_url: String,
+    content_key: Option<String>,
+    server: Option<servers::ServerConfig>,
+    reuse: Option<bool>,
+) -> Result<Option<String>, String> {
+    let reuse = reuse.unwrap_or_else(|| {
+        app.state::<settings::SettingsStore>()
+            .read(|s| s.reuse_video_window)
+    });
+    let reused = reuse
+        .then(|| remote::video_windows(&app).pop())
+        .flatten()
+        .map(|w| w.label().to_string());
+    let proxy = app.state::<proxy::ProxyState>();
+    let settings = app.state::<settings::SettingsStore>();
+    let source = transcode::prepare(
+        &app,
+        &proxy,
+        &settings,
+        server.as_ref(),
+        &title,
+        stream_url,
+        None,
+        false,
+        reused.as_deref(),
+    )
+    .await?;
+    let (decision, probed) = playback::choose(&app, &source.url).await;
+    // VLC can't decrypt, so encrypted streams stay with ffmpeg
+    if decision.path == playback::PlaybackPath::External && source.decryption.is_none() {
+        let holder = format!("vlc-{}", uuid::Uuid::new_v4().simple());
+        let url = source.url.clone();
+        source.finish(&proxy, Ok(holder.clone()))?;
+        vlc::launch(
+            &app,
+            &app.state(),
+            &settings,
+            &url,
+            content_key,
+            None,
+            None,
+            Some(holder),
+        )?;
+        playback::report(&app, None, &url, &decision);
+        return Ok(None);
+    }
+    let opened = transcode::open_player(
+        &app,
+        &proxy,
+        &settings,
+        &app.state(),
+        title,
+        &source,
+        probed,
+        None,
+        content_key,
+        false,
+        reused.as_deref(),
+    )
+    .await;
+    let label = source.finish(&proxy, opened)?;
+    if let Some(window) = reused
+        .filter(|reused| *reused != label)
+        .and_then(|reused| app.get_webview_window(&reused))
+    {
+        let _ = window.close();
+    }
+    Ok(Some(label))
+}
+
+/// Switches video window `label` to `stream_url`, stopping what the proxy served it before.
+pub(crate) fn play_in(
+    app: &tauri::AppHandle,
+    label: &str,
+    title: &str,
+    stream_url: &str,
+    content_key: Option<String>,
+) -> Result<(), String> {
+    let window = video_window(app, label)?;
+    app.state::<proxy::ProxyState>().stop_owned_by(label);
+    release_playback(app, label);
+    remote::send_player_command(
+        app,
+        label,
+        &remote::PlayerCommand::Load {
+            url: stream_url.to_string(),
+            content_key,
+        },
+    )?;
+    let _ = window.set_title(title);
     Ok(())
 }
 
-/// Opens a new Tauri window with the video player. Async to avoid Windows deadlock.
+/// Ends what window `label` was playing: its watch session, tuner, stream slot, channel and
+/// series or queue tracking. Done when the window closes or loads other media.
+fn release_playback(app: &tauri::AppHandle, label: &str) {
+    hdhomerun::release(app, label);
+    history::release(app, label);
+    autoplay::release(label);
+    queue::release(label);
+    zap::release(label);
+    connections::release(label);
+}
+
+pub(crate) fn video_window(
+    app: &tauri::AppHandle,
+    label: &str,
+) -> Result<tauri::WebviewWindow, String> {
+    app.get_webview_window(label)
+        .filter(|_| label.starts_with("video-"))
+        .ok_or_else(|| format!("No video window {}", label))
+}
+
+/// Plays `stream_url` in the existing video window `label`.
 #[tauri::command]
-async fn open_video_window(
+async fn play_in_window(
     app: tauri::AppHandle,
+    label: String,
     title: String,
     stream_url: String,
+    content_key: Option<String>,
 ) -> Result<(), String> {
+    play_in(&app, &label, &title, &stream_url, content_key)
+}
+
+#[tauri::command]
+async fn close_video_window(app: tauri::AppHandle, label: String) -> Result<(), String> {
+    video_window(&app, &label)?
+        .close()
+        .map_err(|e| e.to_string())
+}
+
+/// Brings video window `label` to the front.
+#[tauri::command]
+async fn focus_video_window(app: tauri::AppHandle, label: String) -> Result<(), String> {
+    let window = video_window(&app, &label)?;
+    let _ = window.unminimize();
+    window.show().map_err(|e| e.to_string())?;
+    window.set_focus().map_err(|e| e.to_string())
+}
+
+/// Current unix time in seconds.
+pub(crate) fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// UTC calendar date `(year, month, day)` of a unix timestamp (Howard Hinnant's algorithm).
+pub(crate) fn civil_date(unix_secs: u64) -> (i64, u32, u32) {
+    let days = (unix_secs / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let doe = days.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    (yoe + era * 400 + i64::from(month <= 2), month, day)
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date (inverse of `civil_date`).
+pub(crate) fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = (i64::from(month) + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+const VIDEO_WINDOW_SIZE: (f64, f64) = (960.0, 640.0);
+/// Room for the station name, play/pause and volume.
+const AUDIO_WINDOW_SIZE: (f64, f64) = (420.0, 160.0);
+
+/// Builds a video player window for `stream_url` and returns its label.
+/// Must not be called from a synchronous command (Windows deadlock).
+pub(crate) fn create_video_window(
+    app: &tauri::AppHandle,
+    title: &str,
+    stream_url: &str,
+) -> Result<String, String> {
+    create_video_window_with(app, title, stream_url, &[])
+}
+
+/// Like `create_video_window`, passing extra query parameters to the video window page. With
+/// `audio=1` among them the page shows its compact audio-only player, in a window to match.
+pub(crate) fn create_video_window_with(
+    app: &tauri::AppHandle,
+    title: &str,
+    stream_url: &str,
+    params: &[(&str, String)],
+) -> Result<String, String> {
+    let mut path = format!("video-window?url={}", urlencoding::encode(stream_url));
+    for (key, value) in params {
+        path.push_str(&format!("&{}={}", key, urlencoding::encode(value)));
+    }
+    if params
+        .iter()
+        .any(|(key, value)| *key == "audio" && value == "1")
+    {
+        return build_player_window(app, title, &path, false, AUDIO_WINDOW_SIZE);
+    }
+    build_video_window(app, title, &path, false)
+}
+
+/// Builds a video window loading `path` (relative to the app URL). `transparent` is for
+/// native renderers drawing behind the webview; it is ignored on macOS.
+pub(crate) fn build_video_window(
+    app: &tauri::AppHandle,
+    title: &str,
+    path: &str,
+    transparent: bool,
+) -> Result<String, String> {
+    build_player_window(app, title, path, transparent, VIDEO_WINDOW_SIZE)
+}
+
+fn build_player_window(
+    app: &tauri::AppHandle,
+    title: &str,
+    path: &str,
+    transparent: bool,
+    (width, height): (f64, f64),
+) -> Result<String, String> {
     let label = format!(
         "video-{}",
         std::time::SystemTime::now()
@@ -58,23 +342,335 @@ async fn open_video_window(
             .unwrap_or_default()
             .as_millis()
     );
-    let encoded = urlencoding::encode(&stream_url);
     // Path relative to app URL (dev server or tauri://localhost in prod)
-    let path = format!("video-window?url={}", encoded);
     let url = tauri::WebviewUrl::App(PathBuf::from(path));
-    tauri::WebviewWindowBuilder::new(&app, &label, url)
+    let builder = tauri::WebviewWindowBuilder::new(app, &label, url)
         .title(title)
-        .inner_size(960.0, 640.0)
-        .build()
-        .map_err(|e| e.to_string())?;
-    Ok(())
+        .inner_size(width, height);
+    // Transparent webviews need the private API on macOS
+    #[cfg(not(target_os = "macos"))]
+    let builder = builder.transparent(transparent);
+    #[cfg(target_os = "macos")]
+    let _ = transparent;
+    builder.build().map_err(|e| e.to_string())?;
+    audiofocus::opened(app, &label);
+    let _ = app.emit(
+        "video-window-opened",
+        serde_json::json!({ "label": label, "title": title }),
+    );
+    Ok(label)
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
-        .invoke_handler(tauri::generate_handler![open_video_window, open_in_vlc])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .plugin(tauri_plugin_dialog::init())
+        .plugin(tauri_plugin_updater::Builder::new().build())
+        .setup(|app| {
+            app.manage(servers::open(app.handle()));
+            health::init(app.handle());
+            app.manage(cookies::open(app.handle()));
+            app.manage(progress::open(app.handle()));
+            app.manage(offline::open(app.handle()));
+            app.manage(reminders::open(app.handle()));
+            app.manage(recordings::open(app.handle()));
+            app.manage(history::open(app.handle()));
+            app.manage(favorites::open(app.handle()));
+            app.manage(queue::open(app.handle()));
+            app.manage(watchlater::open(app.handle()));
+            app.manage(downloads::open(app.handle()));
+            app.manage(volume::open(app.handle()));
+            app.manage(edits::open(app.handle()));
+            app.manage(quality::open(app.handle()));
+            app.manage(datausage::open(app.handle()));
+            app.manage(account::open(app.handle()));
+            app.manage(settings::open(app.handle()));
+            app.manage(lock::open(app.handle()));
+            lock::start(app.handle());
+            app.manage(logging::init(app.handle()));
+            app.manage(crash::install(app.handle()));
+            let network = app
+                .state::<settings::SettingsStore>()
+                .read(|s| s.network.clone());
+            if let Err(e) = http::configure(&network) {
+                tracing::warn!("Ignoring saved network settings: {}", e);
+            }
+            app.manage(tracks::open_item_store(app.handle()));
+            app.manage(hdhomerun::HdhrState::default());
+            app.manage(mpv::MpvState::default());
+            app.manage(opensubtitles::OpenSubtitlesState::default());
+            app.manage(feed::EventFeed::default());
+            feed::start(app.handle());
+            app.manage(pairing::open(app.handle()));
+            app.manage(pairing::PairingState::default());
+            app.manage(remote::RemoteState::default());
+            if let Err(e) = remote::apply(app.handle()) {
+                tracing::error!("Remote API not started: {}", e);
+            }
+            app.manage(mqtt::MqttState::default());
+            mqtt::apply(app.handle());
+            app.manage(webhooks::open(app.handle()));
+            app.manage(webhooks::DeliveryLog::default());
+            webhooks::start(app.handle());
+            app.manage(sync::SyncState::default());
+            app.manage(updater::UpdaterState::default());
+            updater::start(app.handle());
+            offline::start(app.handle());
+            catalog::preload(app.handle());
+            cache::start(app.handle());
+            reminders::start(app.handle());
+            recommend::start(app.handle());
+            quality::start(app.handle());
+            datausage::start(app.handle());
+            power::start(app.handle());
+            network::start(app.handle());
+            account::start(app.handle());
+            stalker::start(app.handle());
+            gamepad::start(app.handle());
+            app.manage(proxy::start(app.handle())?);
+            Ok(())
+        })
+        .on_window_event(|window, event| {
+            if let tauri::WindowEvent::Destroyed = event {
+                if window.label().starts_with("video-") {
+                    let closed = serde_json::json!({ "label": window.label() });
+                    let _ = window.app_handle().emit("video-window-closed", closed);
+                }
+                release_playback(window.app_handle(), window.label());
+                proxy::release(window.app_handle(), window.label());
+                audiofocus::release(window.app_handle(), window.label());
+                mpv::release(window.app_handle(), window.label());
+                sync::release(window.app_handle(), window.label());
+            }
+        })
+        .invoke_handler(logging::log_invocations(lock::guard(
+            tauri::generate_handler![
+                open_video_window,
+                play_in_window,
+                close_video_window,
+                focus_video_window,
+                vlc::open_in_vlc,
+                progress::report_progress,
+                progress::get_progress,
+                progress::list_progress,
+                progress::mark_watched,
+                progress::mark_unwatched,
+                progress::get_continue_watching,
+                watchlater::add_watch_later,
+                watchlater::remove_watch_later,
+                watchlater::list_watch_later,
+                autoplay::track_series_playback,
+                autoplay::play_next_episode,
+                queue::get_queue,
+                queue::queue_add,
+                queue::queue_remove,
+                queue::queue_move,
+                queue::queue_shuffle,
+                queue::queue_clear,
+                queue::queue_play,
+                queue::queue_next,
+                zap::zap,
+                zap::zap_back,
+                volume::report_channel_volume,
+                volume::get_channel_volume,
+                audio::list_audio_devices,
+                audio::get_audio_device,
+                audio::set_audio_device,
+                audio::set_window_audio_device,
+                audiofocus::get_audio_focus,
+                audiofocus::set_audio_focus,
+                quality::get_provider_quality,
+                quality::report_rebuffer,
+                quality::clear_provider_quality,
+                datausage::get_data_usage,
+                failure::diagnose_stream,
+                merge::merge_playlists,
+                enigma2::import_enigma2_bouquets,
+                edits::get_channel_edits,
+                edits::list_hidden_channels,
+                edits::rename_channel,
+                edits::set_channel_hidden,
+                edits::rename_group,
+                edits::set_group_hidden,
+                edits::reorder_groups,
+                edits::reorder_channels,
+                edits::reset_channel_edits,
+                lock::get_lock_status,
+                lock::set_lock_pin,
+                lock::set_lock_options,
+                lock::lock_app,
+                lock::unlock_app,
+                lock::report_activity,
+                account::get_account_info,
+                connections::list_active_streams,
+                connections::check_connection_limit,
+                connections::stop_active_stream,
+                recorder::record_current,
+                recorder::list_active_recordings,
+                recorder::stop_recording,
+                timeshift::get_timeshift_status,
+                screenshot::capture_screenshot,
+                gamepad::list_gamepads,
+                shortcuts::get_shortcuts,
+                shortcuts::set_shortcut,
+                shortcuts::reset_shortcuts,
+                discovery::discover_servers,
+                servers::list_servers,
+                servers::save_server,
+                servers::remove_server,
+                verify::test_server_connection,
+                wol::wake_server,
+                emby::emby_sign_in,
+                emby::emby_connect_sign_in,
+                emby::emby_items,
+                emby::emby_live_channels,
+                emby::emby_live_programs,
+                emby::emby_stream_url,
+                tvheadend::tvh_channel_tags,
+                tvheadend::tvh_channels,
+                tvheadend::tvh_epg,
+                tvheadend::tvh_stream_profiles,
+                tvheadend::tvh_stream_url,
+                tvheadend::tvh_schedule_recording,
+                tvheadend::tvh_schedule_manual_recording,
+                tvheadend::tvh_recordings,
+                tvheadend::tvh_cancel_recording,
+                stalker::stalker_channels,
+                stalker::stalker_stream_url,
+                stalker::stalker_device,
+                stalker::stalker_archive,
+                hdhomerun::hdhomerun_discover,
+                hdhomerun::hdhomerun_lineup,
+                hdhomerun::hdhomerun_leases,
+                hdhomerun::hdhomerun_play,
+                hdhomerun::hdhomerun_record,
+                hdhomerun::hdhomerun_release,
+                satip::satip_discover,
+                satip::satip_channels,
+                satip::satip_tune_url,
+                satip::satip_play,
+                mpv::mpv_open,
+                mpv::mpv_load,
+                mpv::mpv_pause,
+                mpv::mpv_seek,
+                mpv::mpv_set_track,
+                mpv::mpv_set_volume,
+                probe::probe_stream,
+                playback::choose_playback_path,
+                transcode::play_stream,
+                hwaccel::get_hw_caps,
+                icy::get_now_playing,
+                tracks::get_track_preferences,
+                tracks::set_track_preferences,
+                tracks::list_tracks,
+                subtitles::pick_subtitle_file,
+                subtitles::load_subtitle_file,
+                mpv::mpv_add_subtitle,
+                settings::get_settings,
+                settings::save_settings,
+                opensubtitles::find_subtitles,
+                opensubtitles::download_subtitle,
+                subtitles::extract_subtitle,
+                tracks::get_item_tracks,
+                tracks::set_item_subtitle_delay,
+                tracks::set_item_audio_track,
+                remote::get_remote_api,
+                remote::configure_remote_api,
+                remote::reset_remote_token,
+                pairing::start_pairing,
+                pairing::cancel_pairing,
+                pairing::list_paired_devices,
+                pairing::revoke_paired_device,
+                mqtt::get_mqtt_status,
+                mqtt::configure_mqtt,
+                webhooks::list_webhooks,
+                webhooks::save_webhook,
+                webhooks::remove_webhook,
+                webhooks::test_webhook,
+                webhooks::list_webhook_deliveries,
+                sync::start_sync_session,
+                sync::join_sync_session,
+                sync::leave_sync_session,
+                sync::get_sync_session,
+                sync::sync_report_state,
+                logging::set_log_level,
+                logging::get_recent_logs,
+                diagnostics::export_diagnostics,
+                backup::export_backup,
+                backup::inspect_backup,
+                backup::import_backup,
+                downloads::preview_download_layout,
+                downloads::start_download,
+                downloads::list_downloads,
+                downloads::cancel_download,
+                downloads::remove_download,
+                crash::get_crash_status,
+                crash::set_crash_reporting,
+                crash::upload_crash_reports,
+                crash::clear_crash_reports,
+                updater::check_for_updates,
+                updater::install_update,
+                updater::set_update_channel,
+                http::http_fetch,
+                http::fetch_server_certificate,
+                health::get_server_health,
+                health::reset_server_health,
+                cookies::clear_server_cookies,
+                emby::emby_set_favorite,
+                emby::emby_report_progress,
+                offline::get_pending_mutations,
+                offline::sync_pending_mutations,
+                offline::discard_pending_mutation,
+                catalog::refresh_channels,
+                catalog::get_channel_index,
+                catalog::query_channels,
+                catalog::stream_channels,
+                playlist::import_playlist,
+                playlist::cancel_import,
+                cache::get_cache_stats,
+                cache::clear_cache,
+                epg::get_epg,
+                epg::export_epg_xmltv,
+                epg::search_epg,
+                epg::record_programme,
+                epg::get_epg_genres,
+                epg::get_epg_on_now,
+                epg::suggest_epg_offset,
+                reminders::add_epg_reminder,
+                reminders::list_epg_reminders,
+                reminders::remove_epg_reminder,
+                catchup::get_catchup_url,
+                tz::get_display_timezone,
+                conflicts::check_recording_conflicts,
+                recordings::list_recordings,
+                recordings::play_recording,
+                recordings::set_recording_watched,
+                recordings::delete_recording,
+                recordings::rename_recording,
+                chapters::detect_commercials,
+                chapters::get_chapters,
+                clips::export_clip,
+                hls::get_stream_variants,
+                history::start_watching,
+                history::report_watching,
+                history::get_watch_stats,
+                history::export_history,
+                recommend::get_recommendations,
+                recommend::get_on_now_for_you,
+                favorites::set_favorite_channels,
+                favorites::get_favorite_channels
+            ],
+        )))
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(|app, event| {
+            if let tauri::RunEvent::Exit = event {
+                app.state::<proxy::ProxyState>().stop_all();
+                recorder::stop_all();
+                quality::shutdown(app);
+                datausage::shutdown(app);
+                crash::clean_exit(app);
+            }
+        });
 }
@@ -0,0 +1,158 @@
+//! Library naming for finished recordings, so the recordings folder can be added to Kodi, Plex
+//! or Jellyfin as is. Guide data has no season or episode numbers, so episodes are filed the
+//! date-based way all three understand: `Show/Season 2024/Show - 2024-05-01 - Episode.ts`,
+//! with a `tvshow.nfo` per show and an NFO next to each episode. Programmes the guide marks as
+//! films go to `Title/Title.ts` with a `movie.nfo`. Recordings without a guide entry keep
+//! their name.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::epg::{escape, Programme};
+use crate::genre::Genre;
+use crate::recorder::{local_time, safe_file_name};
+
+/// `path`, or the first of `name (2).ext`, `name (3).ext`… that doesn't exist.
+fn unused(path: PathBuf) -> PathBuf {
+    if !path.exists() {
+        return path;
+    }
+    let stem = path
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned();
+    let ext = path.extension().map(|e| e.to_string_lossy().into_owned());
+    (2..)
+        .map(|n| {
+            let name = match &ext {
+                Some(ext) => format!("{} ({}).{}", stem, n, ext),
+                None => format!("{} ({})", stem, n),
+            };
+            path.with_file_name(name)
+        })
+        .find(|p| !p.exists())
+        .unwrap_or(path)
+}
+
+fn element(name: &str, value: Option<&str>) -> String {
+    value
+        .filter(|v| !v.trim().is_empty())
+        .map(|v| format!("  <{0}>{1}</{0}>\n", name, escape(v.trim())))
+        .unwrap_or_default()
+}
+
+fn nfo(root: &str, elements: &[(&str, Option<&str>)]) -> String {
+    let mut xml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n<{}>\n",
+        root
+    );
+    for (name, value) in elements {
+        xml.push_str(&element(name, *value));
+    }
+    xml.push_str(&format!("</{}>\n", root));
+    xml
+}
+
+/// The NFO next to the recording at `path`.
+pub(crate) fn nfo_path(path: &Path) -> PathBuf {
+    path.with_extension("nfo")
+}
+
+fn write_nfo(path: &Path, xml: String) -> Result<(), String> {
+    fs::write(path, xml).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Moves the recording at `path` into the library layout under its folder and writes its NFO
+/// files. Returns the new path.
+pub(crate) fn organize(
+    app: &tauri::AppHandle,
+    path: &Path,
+    programme: &Programme,
+    channel_name: &str,
+) -> Result<PathBuf, String> {
+    let root = path.parent().unwrap_or(Path::new("."));
+    let ext = path
+        .extension()
+        .map_or("ts".to_string(), |e| e.to_string_lossy().into_owned());
+    let title = safe_file_name(&programme.title);
+    if title.is_empty() {
+        return Ok(path.to_path_buf());
+    }
+    let (year, month, day, _, _) = local_time(app, programme.start);
+    let aired = format!("{:04}-{:02}-{:02}", year, month, day);
+    let genre = programme.genre.map(|g| g.info().label);
+    let plot = programme.description.as_deref();
+
+    let (dir, name) = if programme.genre == Some(Genre::Movie) {
+        (root.join(&title), title.clone())
+    } else {
+        let episode = programme
+            .subtitle
+            .as_deref()
+            .map(safe_file_name)
+            .filter(|s| !s.is_empty());
+        let name = match episode {
+            Some(episode) => format!("{} - {} - {}", title, aired, episode),
+            None => format!("{} - {}", title, aired),
+        };
+        (root.join(&title).join(format!("Season {:04}", year)), name)
+    };
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let target = unused(dir.join(format!("{}.{}", name, ext)));
+    fs::rename(path, &target).map_err(|e| {
+        format!(
+            "Failed to move {} to {}: {}",
+            path.display(),
+            target.display(),
+            e
+        )
+    })?;
+
+    let year = year.to_string();
+    if programme.genre == Some(Genre::Movie) {
+        write_nfo(
+            &nfo_path(&target),
+            nfo(
+                "movie",
+                &[
+                    ("title", Some(&programme.title)),
+                    ("plot", plot),
+                    ("genre", genre),
+                    ("premiered", Some(&aired)),
+                    ("studio", Some(channel_name)),
+                ],
+            ),
+        )?;
+    } else {
+        let show = root.join(&title).join("tvshow.nfo");
+        if !show.exists() {
+            write_nfo(
+                &show,
+                nfo(
+                    "tvshow",
+                    &[("title", Some(&programme.title)), ("genre", genre)],
+                ),
+            )?;
+        }
+        write_nfo(
+            &nfo_path(&target),
+            nfo(
+                "episodedetails",
+                &[
+                    (
+                        "title",
+                        Some(programme.subtitle.as_deref().unwrap_or(&aired)),
+                    ),
+                    ("showtitle", Some(&programme.title)),
+                    ("season", Some(&year)),
+                    ("plot", plot),
+                    ("aired", Some(&aired)),
+                    ("genre", genre),
+                    ("studio", Some(channel_name)),
+                ],
+            ),
+        )?;
+    }
+    Ok(target)
+}
@@ -0,0 +1,323 @@
+//! App lock: a PIN that has to be entered on launch and after a stretch of inactivity, kept
+//! in `app-lock.json` as a salted, stretched SHA-256 hash. While locked every window blanks
+//! itself and the command handler refuses everything but the commands needed to unlock, so a
+//! page that skips the lock screen still gets no data. Separate from any per-content controls:
+//! it locks the whole app. The frontend reports input with `report_activity`, since the
+//! backend can't see it.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::{Emitter, Manager, State};
+
+use crate::store::JsonStore;
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(15);
+const HASH_ROUNDS: u32 = 100_000;
+const MIN_PIN_LEN: usize = 4;
+/// Wrong PINs allowed before unlocking is refused for `BACKOFF`.
+const MAX_FAILURES: u32 = 5;
+const BACKOFF: Duration = Duration::from_secs(30);
+/// Commands that still run while locked.
+const ALLOWED_WHILE_LOCKED: &[&str] = &["get_lock_status", "unlock_app", "report_activity"];
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LockConfig {
+    /// Hex SHA-256 of salt and PIN, stretched; `None` while the lock is off.
+    pub pin_hash: Option<String>,
+    pub salt: String,
+    /// Minutes without input before locking; 0 never locks on idle.
+    pub idle_minutes: u32,
+    pub lock_on_launch: bool,
+}
+
+pub type LockStore = JsonStore<LockConfig>;
+
+pub fn open(app: &tauri::AppHandle) -> LockStore {
+    JsonStore::open(app, "app-lock.json")
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LockStatus {
+    pub enabled: bool,
+    pub locked: bool,
+    pub idle_minutes: u32,
+    pub lock_on_launch: bool,
+    /// Seconds until another PIN can be tried, after too many wrong ones.
+    pub retry_in: Option<u64>,
+}
+
+struct LockState {
+    locked: bool,
+    last_activity: Option<Instant>,
+    failures: u32,
+    retry_at: Option<Instant>,
+}
+
+static STATE: Mutex<LockState> = Mutex::new(LockState {
+    locked: false,
+    last_activity: None,
+    failures: 0,
+    retry_at: None,
+});
+
+fn state() -> std::sync::MutexGuard<'static, LockState> {
+    STATE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+pub fn is_locked() -> bool {
+    state().locked
+}
+
+fn hash_pin(salt: &str, pin: &str) -> String {
+    let mut digest = Sha256::new()
+        .chain_update(salt.as_bytes())
+        .chain_update(pin.as_bytes())
+        .finalize();
+    for _ in 1..HASH_ROUNDS {
+        digest = Sha256::new()
+            .chain_update(digest)
+            .chain_update(salt.as_bytes())
+            .finalize();
+    }
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn pin_matches(config: &LockConfig, pin: &str) -> bool {
+    config
+        .pin_hash
+        .as_deref()
+        .is_some_and(|hash| hash_pin(&config.salt, pin) == hash)
+}
+
+/// Time left before another PIN may be tried, if unlocking is backed off.
+fn backoff_left(state: &LockState, now: Instant) -> Option<Duration> {
+    state
+        .retry_at
+        .map(|at| at.saturating_duration_since(now))
+        .filter(|left| !left.is_zero())
+}
+
+/// Counts a wrong PIN, backing off for `BACKOFF` once `MAX_FAILURES` are reached.
+fn record_failure(state: &mut LockState, now: Instant) {
+    state.failures += 1;
+    if state.failures >= MAX_FAILURES {
+        state.failures = 0;
+        state.retry_at = Some(now + BACKOFF);
+    }
+}
+
+fn set_locked(app: &tauri::AppHandle, locked: bool) {
+    {
+        let mut state = state();
+        if state.locked == locked {
+            return;
+        }
+        state.locked = locked;
+        state.last_activity = Some(Instant::now());
+    }
+    tracing::info!("App {}", if locked { "locked" } else { "unlocked" });
+    let _ = app.emit(if locked { "app-locked" } else { "app-unlocked" }, ());
+}
+
+/// Locks on launch when configured, then locks whenever input stops for the idle time.
+pub fn start(app: &tauri::AppHandle) {
+    let config = app.state::<LockStore>().read(|c| c.clone());
+    if config.pin_hash.is_some() && config.lock_on_launch {
+        state().locked = true;
+    }
+    let app = app.clone();
+    std::thread::spawn(move || loop {
+        std::thread::sleep(CHECK_INTERVAL);
+        let (enabled, idle_minutes) = app
+            .state::<LockStore>()
+            .read(|c| (c.pin_hash.is_some(), c.idle_minutes));
+        if !enabled || idle_minutes == 0 {
+            continue;
+        }
+        let idle = {
+            let mut state = state();
+            let last = *state.last_activity.get_or_insert_with(Instant::now);
+            !state.locked && last.elapsed() >= Duration::from_secs(idle_minutes as u64 * 60)
+        };
+        if idle {
+            set_locked(&app, true);
+        }
+    });
+}
+
+/// Wraps the command handler so that, while locked, only `ALLOWED_WHILE_LOCKED` runs.
+pub fn guard(
+    handler: impl Fn(tauri::ipc::Invoke) -> bool + Send + Sync + 'static,
+) -> impl Fn(tauri::ipc::Invoke) -> bool + Send + Sync + 'static {
+    move |invoke| {
+        let command = invoke.message.command();
+        if is_locked() && !ALLOWED_WHILE_LOCKED.contains(&command) {
+            tracing::debug!(command, "Refused while locked");
+            invoke.resolver.reject("The app is locked");
+            return true;
+        }
+        handler(invoke)
+    }
+}
+
+fn status(config: &LockConfig) -> LockStatus {
+    let state = state();
+    LockStatus {
+        enabled: config.pin_hash.is_some(),
+        locked: state.locked,
+        idle_minutes: config.idle_minutes,
+        lock_on_launch: config.lock_on_launch,
+        retry_in: backoff_left(&state, Instant::now())
+            .map(|left| left.as_secs())
+            .filter(|&s| s > 0),
+    }
+}
+
+#[tauri::command]
+pub fn get_lock_status(store: State<'_, LockStore>) -> LockStatus {
+    store.read(status)
+}
+
+/// Sets, changes or (with no `pin`) removes the PIN. An existing PIN must be given as
+/// `current_pin`.
+#[tauri::command]
+pub fn set_lock_pin(
+    app: tauri::AppHandle,
+    store: State<'_, LockStore>,
+    current_pin: Option<String>,
+    pin: Option<String>,
+) -> Result<LockStatus, String> {
+    let config = store.read(|c| c.clone());
+    if config.pin_hash.is_some() && !pin_matches(&config, current_pin.as_deref().unwrap_or("")) {
+        return Err("The current PIN is wrong".to_string());
+    }
+    let pin = pin.map(|p| p.trim().to_string()).filter(|p| !p.is_empty());
+    if pin
+        .as_ref()
+        .is_some_and(|p| p.chars().count() < MIN_PIN_LEN)
+    {
+        return Err(format!("The PIN needs at least {} characters", MIN_PIN_LEN));
+    }
+    let status = store.update(|c| {
+        c.salt = uuid::Uuid::new_v4().simple().to_string();
+        c.pin_hash = pin.as_deref().map(|p| hash_pin(&c.salt, p));
+        status(c)
+    })?;
+    if !status.enabled {
+        set_locked(&app, false);
+    }
+    Ok(status)
+}
+
+#[tauri::command]
+pub fn set_lock_options(
+    store: State<'_, LockStore>,
+    idle_minutes: u32,
+    lock_on_launch: bool,
+) -> Result<LockStatus, String> {
+    store.update(|c| {
+        c.idle_minutes = idle_minutes;
+        c.lock_on_launch = lock_on_launch;
+        status(c)
+    })
+}
+
+#[tauri::command]
+pub fn lock_app(app: tauri::AppHandle, store: State<'_, LockStore>) -> Result<(), String> {
+    if store.read(|c| c.pin_hash.is_none()) {
+        return Err("Set a PIN before locking the app".to_string());
+    }
+    set_locked(&app, true);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn unlock_app(
+    app: tauri::AppHandle,
+    store: State<'_, LockStore>,
+    pin: String,
+) -> Result<(), String> {
+    if let Some(left) = backoff_left(&state(), Instant::now()) {
+        return Err(format!(
+            "Too many wrong PINs; try again in {} s",
+            left.as_secs().max(1)
+        ));
+    }
+    if !store.read(|c| c.pin_hash.is_none() || pin_matches(c, pin.trim())) {
+        record_failure(&mut state(), Instant::now());
+        return Err("Wrong PIN".to_string());
+    }
+    {
+        let mut state = state();
+        state.failures = 0;
+        state.retry_at = None;
+    }
+    set_locked(&app, false);
+    Ok(())
+}
+
+/// Called by the frontend on user input, throttled; resets the idle timer.
+#[tauri::command]
+pub fn report_activity() {
+    let mut state = state();
+    if !state.locked {
+        state.last_activity = Some(Instant::now());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fresh() -> LockState {
+        LockState {
+            locked: true,
+            last_activity: None,
+            failures: 0,
+            retry_at: None,
+        }
+    }
+
+    #[test]
+    fn backs_off_after_the_failure_limit() {
+        let now = Instant::now();
+        let mut state = fresh();
+        for _ in 1..MAX_FAILURES {
+            record_failure(&mut state, now);
+            assert_eq!(backoff_left(&state, now), None);
+        }
+        record_failure(&mut state, now);
+        assert_eq!(backoff_left(&state, now), Some(BACKOFF));
+        assert_eq!(state.failures, 0);
+    }
+
+    #[test]
+    fn backoff_runs_out() {
+        let now = Instant::now();
+        let mut state = fresh();
+        for _ in 0..MAX_FAILURES {
+            record_failure(&mut state, now);
+        }
+        let later = now + BACKOFF / 2;
+        assert_eq!(backoff_left(&state, later), Some(BACKOFF / 2));
+        assert_eq!(backoff_left(&state, now + BACKOFF), None);
+    }
+
+    #[test]
+    fn pins_are_checked_against_the_salted_hash() {
+        let salt = "salt".to_string();
+        let config = LockConfig {
+            pin_hash: Some(hash_pin(&salt, "1234")),
+            salt,
+            ..Default::default()
+        };
+        assert!(pin_matches(&config, "1234"));
+        assert!(!pin_matches(&config, "4321"));
+        assert!(!pin_matches(&LockConfig::default(), "1234"));
+    }
+}
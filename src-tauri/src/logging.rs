@@ -0,0 +1,292 @@
+//! Log output for self-diagnosis. Backend code logs through `tracing`; events at or above the
+//! configured level are appended to `logs/tvx.log` in the app data dir, which rotates at 5 MB
+//! keeping the last few files. Spans are accepted but not recorded.
+
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::sync::{Arc, Mutex};
+
+use tauri::{Manager, State};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::subscriber::Interest;
+use tracing::{Event, Level, Metadata};
+
+use crate::settings::SettingsStore;
+
+const LOG_FILE: &str = "tvx.log";
+const MAX_FILE_BYTES: u64 = 5 * 1024 * 1024;
+/// Rotated files kept besides the current one: `tvx.1.log` (newest) to `tvx.4.log`.
+const KEEP_FILES: usize = 4;
+const DEFAULT_LEVEL: Level = Level::INFO;
+
+fn level_from_name(name: &str) -> Option<Level> {
+    match name.to_ascii_lowercase().as_str() {
+        "error" => Some(Level::ERROR),
+        "warn" | "warning" => Some(Level::WARN),
+        "info" => Some(Level::INFO),
+        "debug" => Some(Level::DEBUG),
+        "trace" => Some(Level::TRACE),
+        _ => None,
+    }
+}
+
+fn level_index(level: Level) -> u8 {
+    match level {
+        Level::ERROR => 0,
+        Level::WARN => 1,
+        Level::INFO => 2,
+        Level::DEBUG => 3,
+        Level::TRACE => 4,
+    }
+}
+
+struct LogFile {
+    dir: PathBuf,
+    file: Option<File>,
+    written: u64,
+}
+
+impl LogFile {
+    fn path(&self, generation: usize) -> PathBuf {
+        match generation {
+            0 => self.dir.join(LOG_FILE),
+            n => self.dir.join(format!("tvx.{}.log", n)),
+        }
+    }
+
+    fn open(&mut self) -> Option<&mut File> {
+        if self.file.is_none() {
+            let _ = fs::create_dir_all(&self.dir);
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(self.path(0))
+                .ok()?;
+            self.written = file.metadata().map(|m| m.len()).unwrap_or(0);
+            self.file = Some(file);
+        }
+        self.file.as_mut()
+    }
+
+    /// Shifts `tvx.log` to `tvx.1.log`, `tvx.1.log` to `tvx.2.log` and so on.
+    fn rotate(&mut self) {
+        self.file = None;
+        let _ = fs::remove_file(self.path(KEEP_FILES));
+        for generation in (0..KEEP_FILES).rev() {
+            let _ = fs::rename(self.path(generation), self.path(generation + 1));
+        }
+    }
+
+    fn write_line(&mut self, line: &str) {
+        if self.written + line.len() as u64 > MAX_FILE_BYTES {
+            self.rotate();
+        }
+        if let Some(file) = self.open() {
+            if file.write_all(line.as_bytes()).is_ok() {
+                self.written += line.len() as u64;
+            }
+        }
+    }
+}
+
+struct Shared {
+    level: AtomicU8,
+    next_span: AtomicU64,
+    file: Mutex<LogFile>,
+}
+
+/// Handle to the installed logger, managed as app state.
+#[derive(Clone)]
+pub struct Logger(Arc<Shared>);
+
+impl Logger {
+    fn enabled_for(&self, level: &Level) -> bool {
+        level_index(*level) <= self.0.level.load(Ordering::Relaxed)
+    }
+
+    pub fn set_level(&self, level: Level) {
+        self.0.level.store(level_index(level), Ordering::Relaxed);
+    }
+
+    /// Existing log files, current first.
+    pub fn files(&self) -> Vec<PathBuf> {
+        let file = self.0.file.lock().unwrap_or_else(|e| e.into_inner());
+        (0..=KEEP_FILES)
+            .map(|generation| file.path(generation))
+            .filter(|path| path.exists())
+            .collect()
+    }
+
+    /// The last `lines` lines across the current and rotated files, oldest first.
+    pub fn recent(&self, lines: usize) -> Vec<String> {
+        let file = self.0.file.lock().unwrap_or_else(|e| e.into_inner());
+        let mut recent = VecDeque::with_capacity(lines);
+        for generation in 0..=KEEP_FILES {
+            if recent.len() >= lines {
+                break;
+            }
+            let Ok(text) = fs::read_to_string(file.path(generation)) else {
+                break;
+            };
+            for line in text.lines().rev() {
+                if recent.len() >= lines {
+                    break;
+                }
+                recent.push_front(line.to_string());
+            }
+        }
+        recent.into()
+    }
+}
+
+/// Collects an event's fields as `message key=value ...`.
+struct Fields(String);
+
+impl Visit for Fields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.0.insert_str(0, value);
+        } else {
+            let _ = write!(self.0, " {}={:?}", field.name(), value);
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            self.0.insert_str(0, &format!("{:?}", value));
+        } else {
+            let _ = write!(self.0, " {}={:?}", field.name(), value);
+        }
+    }
+}
+
+/// `YYYY-MM-DDTHH:MM:SS.mmmZ` for the current time.
+fn timestamp() -> String {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    let secs = now.as_secs();
+    let (year, month, day) = crate::civil_date(secs);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        secs / 3600 % 24,
+        secs / 60 % 60,
+        secs % 60,
+        now.subsec_millis()
+    )
+}
+
+impl tracing::Subscriber for Logger {
+    // The level can change at runtime, so callsites must not cache the answer
+    fn register_callsite(&self, _: &'static Metadata<'static>) -> Interest {
+        Interest::sometimes()
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        self.enabled_for(metadata.level())
+    }
+
+    fn new_span(&self, _: &Attributes<'_>) -> Id {
+        Id::from_u64(self.0.next_span.fetch_add(1, Ordering::Relaxed))
+    }
+
+    fn record(&self, _: &Id, _: &Record<'_>) {}
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let metadata = event.metadata();
+        let mut fields = Fields(String::new());
+        event.record(&mut fields);
+        let line = format!(
+            "{} {:5} {}: {}\n",
+            timestamp(),
+            metadata.level(),
+            metadata.target(),
+            fields.0
+        );
+        if cfg!(debug_assertions) {
+            eprint!("{}", line);
+        }
+        self.0
+            .file
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .write_line(&line);
+    }
+
+    fn enter(&self, _: &Id) {}
+
+    fn exit(&self, _: &Id) {}
+}
+
+fn log_dir(app: &tauri::AppHandle) -> PathBuf {
+    app.path()
+        .app_data_dir()
+        .map(|dir| dir.join("logs"))
+        .unwrap_or_else(|_| Path::new("logs").to_path_buf())
+}
+
+/// Installs the global logger at the saved level and returns its handle for app state.
+pub fn init(app: &tauri::AppHandle) -> Logger {
+    let level = app
+        .state::<SettingsStore>()
+        .read(|s| s.log_level.clone())
+        .and_then(|name| level_from_name(&name))
+        .unwrap_or(DEFAULT_LEVEL);
+    let logger = Logger(Arc::new(Shared {
+        level: AtomicU8::new(level_index(level)),
+        next_span: AtomicU64::new(1),
+        file: Mutex::new(LogFile {
+            dir: log_dir(app),
+            file: None,
+            written: 0,
+        }),
+    }));
+    if tracing::subscriber::set_global_default(logger.clone()).is_err() {
+        eprintln!("A global logger was already installed");
+    }
+    tracing::info!(version = %app.package_info().version, "TvX starting");
+    logger
+}
+
+/// Sets the minimum level written to the log: error, warn, info, debug or trace.
+#[tauri::command]
+pub fn set_log_level(
+    logger: State<'_, Logger>,
+    settings: State<'_, SettingsStore>,
+    level: String,
+) -> Result<(), String> {
+    let parsed = level_from_name(&level).ok_or_else(|| format!("Unknown log level: {}", level))?;
+    settings.update(|s| s.log_level = Some(parsed.as_str().to_ascii_lowercase()))?;
+    logger.set_level(parsed);
+    tracing::info!(level = parsed.as_str(), "Log level changed");
+    Ok(())
+}
+
+/// The last `lines` log lines (200 by default), oldest first.
+#[tauri::command]
+pub fn get_recent_logs(logger: State<'_, Logger>, lines: Option<usize>) -> Vec<String> {
+    logger.recent(lines.unwrap_or(200).min(10_000))
+}
+
+/// Wraps the app's invoke handler so every command invocation is logged at debug level and
+/// remembered for crash reports.
+pub fn log_invocations(
+    handler: impl Fn(tauri::ipc::Invoke) -> bool + Send + Sync + 'static,
+) -> impl Fn(tauri::ipc::Invoke) -> bool + Send + Sync + 'static {
+    move |invoke| {
+        let command = invoke.message.command();
+        tracing::debug!(command, "Command invoked");
+        crate::crash::note_command(command);
+        handler(invoke)
+    }
+}
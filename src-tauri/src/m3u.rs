@@ -0,0 +1,116 @@
+//! Extended M3U playlist parsing (`#EXTINF` with tvg-*/group-title attributes).
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct M3uEntry {
+    pub name: String,
+    pub url: String,
+    pub tvg_id: Option<String>,
+    pub tvg_name: Option<String>,
+    pub logo: Option<String>,
+    pub group: Option<String>,
+    /// All `key="value"` attributes of the `#EXTINF` line, including the ones above.
+    pub attributes: HashMap<String, String>,
+    /// `#KODIPROP:key=value` lines of the entry, keys lowercased.
+    pub properties: HashMap<String, String>,
+}
+
+/// Parses `key="value"` pairs from the attribute part of an `#EXTINF` line.
+fn parse_attributes(s: &str) -> HashMap<String, String> {
+    let mut attrs = HashMap::new();
+    let mut rest = s;
+    while let Some(eq) = rest.find("=\"") {
+        let key = rest[..eq]
+            .rsplit(|c: char| c.is_whitespace())
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        let value_start = eq + 2;
+        let Some(len) = rest[value_start..].find('"') else {
+            break;
+        };
+        if !key.is_empty() {
+            attrs.insert(key, rest[value_start..value_start + len].to_string());
+        }
+        rest = &rest[value_start + len + 1..];
+    }
+    attrs
+}
+
+/// Splits `#EXTINF:-1 attrs,Title` at the title comma, skipping commas inside quotes.
+fn split_extinf(line: &str) -> (&str, &str) {
+    let mut in_quotes = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => return (&line[..i], line[i + 1..].trim()),
+            _ => {}
+        }
+    }
+    (line, "")
+}
+
+/// Line-at-a-time parser for playlists too large to hold as one string.
+#[derive(Default)]
+pub struct Parser {
+    pending: Option<M3uEntry>,
+}
+
+impl Parser {
+    /// Feeds one line; returns an entry once its URL line is reached.
+    pub fn line(&mut self, line: &str) -> Option<M3uEntry> {
+        let line = line.trim();
+        if line.is_empty() {
+            return None;
+        }
+        if let Some(info) = line.strip_prefix("#EXTINF:") {
+            let (head, title) = split_extinf(info);
+            let attributes = parse_attributes(head);
+            // Some playlists put their KODIPROP lines before #EXTINF
+            let properties = self
+                .pending
+                .take()
+                .filter(|p| p.name.is_empty())
+                .map(|p| p.properties)
+                .unwrap_or_default();
+            self.pending = Some(M3uEntry {
+                name: title.to_string(),
+                tvg_id: attributes.get("tvg-id").cloned(),
+                tvg_name: attributes.get("tvg-name").cloned(),
+                logo: attributes.get("tvg-logo").cloned(),
+                group: attributes.get("group-title").cloned(),
+                attributes,
+                properties,
+                ..Default::default()
+            });
+        } else if let Some(property) = line.strip_prefix("#KODIPROP:") {
+            let entry = self.pending.get_or_insert_with(M3uEntry::default);
+            if let Some((key, value)) = property.split_once('=') {
+                entry
+                    .properties
+                    .insert(key.trim().to_ascii_lowercase(), value.trim().to_string());
+            }
+        } else if let Some(group) = line.strip_prefix("#EXTGRP:") {
+            if let Some(entry) = self.pending.as_mut() {
+                entry.group.get_or_insert_with(|| group.trim().to_string());
+            }
+        } else if !line.starts_with('#') {
+            let mut entry = self.pending.take().unwrap_or_default();
+            entry.url = line.to_string();
+            if entry.name.is_empty() {
+                entry.name = line.to_string();
+            }
+            return Some(entry);
+        }
+        None
+    }
+}
+
+pub fn parse(text: &str) -> Vec<M3uEntry> {
+    let mut parser = Parser::default();
+    text.lines().filter_map(|line| parser.line(line)).collect()
+}
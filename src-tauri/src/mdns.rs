@@ -0,0 +1,157 @@
+//! Minimal one-shot mDNS (DNS-SD) browsing: a legacy unicast PTR query answered directly to
+//! our socket, without joining the multicast group or running a responder.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+
+const MDNS_ADDR: (Ipv4Addr, u16) = (Ipv4Addr::new(224, 0, 0, 251), 5353);
+const TYPE_A: u16 = 1;
+const TYPE_PTR: u16 = 12;
+const TYPE_SRV: u16 = 33;
+const CLASS_IN_UNICAST: u16 = 0x8001;
+
+#[derive(Debug, Clone)]
+pub struct MdnsService {
+    /// Instance name without the service suffix, e.g. "Living Room".
+    pub instance: String,
+    pub ip: IpAddr,
+    pub port: u16,
+}
+
+fn query(service: &str) -> Vec<u8> {
+    let mut packet = vec![0u8; 12];
+    packet[5] = 1; // one question
+    for label in service.trim_end_matches('.').split('.') {
+        packet.push(label.len() as u8);
+        packet.extend_from_slice(label.as_bytes());
+    }
+    packet.push(0);
+    packet.extend_from_slice(&TYPE_PTR.to_be_bytes());
+    packet.extend_from_slice(&CLASS_IN_UNICAST.to_be_bytes());
+    packet
+}
+
+/// Reads a possibly-compressed name at `pos`, returning it and the offset after it.
+fn read_name(packet: &[u8], mut pos: usize) -> Option<(String, usize)> {
+    let mut labels = Vec::new();
+    let mut end = None;
+    // Bounds the number of compression jumps so a malicious packet can't loop forever
+    for _ in 0..64 {
+        let len = *packet.get(pos)? as usize;
+        if len == 0 {
+            return Some((labels.join("."), end.unwrap_or(pos + 1)));
+        }
+        if len & 0xC0 == 0xC0 {
+            let target = ((len & 0x3F) << 8) | *packet.get(pos + 1)? as usize;
+            end.get_or_insert(pos + 2);
+            pos = target;
+            continue;
+        }
+        let label = packet.get(pos + 1..pos + 1 + len)?;
+        labels.push(String::from_utf8_lossy(label).into_owned());
+        pos += 1 + len;
+    }
+    None
+}
+
+fn be16(packet: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([
+        *packet.get(pos)?,
+        *packet.get(pos + 1)?,
+    ]))
+}
+
+#[derive(Default)]
+struct Records {
+    ptr: Vec<String>,
+    srv: HashMap<String, (String, u16)>,
+    a: HashMap<String, Ipv4Addr>,
+}
+
+fn parse_records(packet: &[u8], records: &mut Records) -> Option<()> {
+    let questions = be16(packet, 4)?;
+    let answers =
+        be16(packet, 6)? as usize + be16(packet, 8)? as usize + be16(packet, 10)? as usize;
+    let mut pos = 12;
+    for _ in 0..questions {
+        pos = read_name(packet, pos)?.1 + 4;
+    }
+    for _ in 0..answers {
+        let (name, after) = read_name(packet, pos)?;
+        let rtype = be16(packet, after)?;
+        let rdlen = be16(packet, after + 8)? as usize;
+        let rdata = after + 10;
+        packet.get(rdata..rdata + rdlen)?;
+        match rtype {
+            TYPE_PTR => records.ptr.push(read_name(packet, rdata)?.0),
+            TYPE_SRV => {
+                let port = be16(packet, rdata + 4)?;
+                let target = read_name(packet, rdata + 6)?.0;
+                records
+                    .srv
+                    .insert(name.to_ascii_lowercase(), (target, port));
+            }
+            TYPE_A if rdlen == 4 => {
+                let ip = Ipv4Addr::new(
+                    packet[rdata],
+                    packet[rdata + 1],
+                    packet[rdata + 2],
+                    packet[rdata + 3],
+                );
+                records.a.insert(name.to_ascii_lowercase(), ip);
+            }
+            _ => {}
+        }
+        pos = rdata + rdlen;
+    }
+    Some(())
+}
+
+/// Browses for instances of `service` (e.g. `_xbmc-jsonrpc-h._tcp.local`) for `wait`. Blocking.
+pub fn browse(service: &str, wait: Duration) -> Result<Vec<MdnsService>, String> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).map_err(|e| e.to_string())?;
+    socket
+        .set_read_timeout(Some(Duration::from_millis(200)))
+        .map_err(|e| e.to_string())?;
+    socket
+        .send_to(&query(service), MDNS_ADDR)
+        .map_err(|e| format!("Failed to send mDNS query: {}", e))?;
+
+    let suffix = format!(".{}", service.trim_end_matches('.'));
+    let deadline = Instant::now() + wait;
+    let mut buf = [0u8; 9000];
+    let mut found: Vec<MdnsService> = Vec::new();
+    while Instant::now() < deadline {
+        let Ok((n, from)) = socket.recv_from(&mut buf) else {
+            continue;
+        };
+        let mut records = Records::default();
+        if parse_records(&buf[..n], &mut records).is_none() {
+            continue;
+        }
+        for instance in records.ptr {
+            let Some((target, port)) = records.srv.get(&instance.to_ascii_lowercase()) else {
+                continue;
+            };
+            // Responders usually include the A record; fall back to the sender's address
+            let ip = match (records.a.get(&target.to_ascii_lowercase()), from) {
+                (Some(ip), _) => IpAddr::V4(*ip),
+                (None, SocketAddr::V4(v4)) => IpAddr::V4(*v4.ip()),
+                (None, SocketAddr::V6(v6)) => IpAddr::V6(*v6.ip()),
+            };
+            let name = instance
+                .strip_suffix(&suffix)
+                .unwrap_or(&instance)
+                .to_string();
+            if !found.iter().any(|s| s.ip == ip && s.port == *port) {
+                found.push(MdnsService {
+                    instance: name,
+                    ip,
+                    port: *port,
+                });
+            }
+        }
+    }
+    Ok(found)
+}
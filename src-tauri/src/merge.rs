@@ -0,0 +1,269 @@
+//! Merged lineups: several M3U playlists combined into one. Channels with the same stream URL
+//! are kept once (the first playlist listed wins); channels that only look alike (same guide
+//! id, or the same name once quality tags and country prefixes are stripped) are all kept and
+//! reported for review. The lineup is written as an M3U file in the app data dir and saved as
+//! an M3U server of its own, so it is browsed and played like any other playlist.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+
+use serde::Serialize;
+use tauri::{Manager, State};
+
+use crate::cache::CacheKind;
+use crate::catalog::{self, CatalogChannel};
+use crate::offline;
+use crate::servers::{self, ServerConfig, ServerKind, ServerStore};
+
+/// Name tokens that describe the feed rather than the channel.
+const QUALITY_TAGS: &[&str] = &[
+    "hd", "fhd", "uhd", "sd", "4k", "8k", "hq", "hevc", "h264", "h265", "1080p", "720p", "576p",
+    "raw",
+];
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChannelRef {
+    pub server_id: String,
+    pub server_name: String,
+    pub name: String,
+    pub url: String,
+    pub group: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Likeness {
+    SameGuideId,
+    SameName,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NearDuplicate {
+    pub likeness: Likeness,
+    /// The guide id or normalized name they share.
+    pub key: String,
+    pub channels: Vec<ChannelRef>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergeReport {
+    /// The lineup's M3U server.
+    pub server: ServerConfig,
+    pub total: usize,
+    /// Channels dropped because an earlier playlist had the same URL.
+    pub duplicates_removed: usize,
+    pub near_duplicates: Vec<NearDuplicate>,
+}
+
+/// Lowercase name without a country prefix (`UK:`, `US |`, `[DE]`), quality tags or
+/// punctuation, e.g. `UK: BBC One FHD` → `bbc one`.
+fn normalize_name(name: &str) -> String {
+    let mut name = name.trim().to_lowercase();
+    if let Some(rest) = name.strip_prefix('[').and_then(|r| r.split_once(']')) {
+        if rest.0.len() <= 3 {
+            name = rest.1.to_string();
+        }
+    }
+    if let Some(at) = name.find([':', '|']) {
+        if name[..at].trim().chars().all(char::is_alphabetic) && name[..at].trim().len() <= 3 {
+            name = name[at + 1..].to_string();
+        }
+    }
+    name.split(|c: char| !c.is_alphanumeric() && c != '+')
+        .filter(|t| !t.is_empty() && !QUALITY_TAGS.contains(t))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn attribute(key: &str, value: &Option<String>) -> String {
+    match value {
+        // Quotes would end the attribute early
+        Some(v) if !v.is_empty() => format!(" {}=\"{}\"", key, v.replace('"', "'")),
+        _ => String::new(),
+    }
+}
+
+fn to_m3u(channels: &[CatalogChannel]) -> String {
+    let mut out = String::from("#EXTM3U\n");
+    for c in channels {
+        out.push_str(&format!(
+            "#EXTINF:-1{}{}{}{}{},{}\n{}\n",
+            attribute("tvg-id", &c.epg_id),
+            attribute("tvg-chno", &c.number),
+            attribute("tvg-logo", &c.logo),
+            attribute("group-title", &c.group),
+            attribute("radio", &c.radio.then(|| "true".to_string())),
+            c.name.replace(['\r', '\n'], " "),
+            c.id
+        ));
+    }
+    out
+}
+
+/// Groups of kept channels that look like the same channel, guide id matches first.
+fn near_duplicates(kept: &[(String, String, CatalogChannel)]) -> Vec<NearDuplicate> {
+    let mut by_guide: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+    let mut by_name: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+    for (i, (_, _, c)) in kept.iter().enumerate() {
+        if let Some(epg_id) = c.epg_id.as_deref().filter(|e| !e.trim().is_empty()) {
+            by_guide.entry(epg_id.to_lowercase()).or_default().push(i);
+        }
+        let name = normalize_name(&c.name);
+        if !name.is_empty() {
+            by_name.entry(name).or_default().push(i);
+        }
+    }
+    let mut reported: HashSet<Vec<usize>> = HashSet::new();
+    let groups = by_guide
+        .into_iter()
+        .map(|(key, members)| (Likeness::SameGuideId, key, members))
+        .chain(
+            by_name
+                .into_iter()
+                .map(|(key, members)| (Likeness::SameName, key, members)),
+        );
+    let mut found = Vec::new();
+    for (likeness, key, members) in groups {
+        if members.len() < 2 || !reported.insert(members.clone()) {
+            continue;
+        }
+        found.push(NearDuplicate {
+            likeness,
+            key,
+            channels: members
+                .iter()
+                .map(|&i| {
+                    let (server_id, server_name, c) = &kept[i];
+                    ChannelRef {
+                        server_id: server_id.clone(),
+                        server_name: server_name.clone(),
+                        name: c.name.clone(),
+                        url: c.id.clone(),
+                        group: c.group.clone(),
+                    }
+                })
+                .collect(),
+        });
+    }
+    found
+}
+
+/// Writes `channels` as the M3U file of a generated lineup and saves `server` (a new one when
+/// its id is empty) as the M3U server playing it, with its index loaded.
+pub(crate) fn save_lineup(
+    app: &tauri::AppHandle,
+    store: &ServerStore,
+    mut server: ServerConfig,
+    channels: Vec<CatalogChannel>,
+) -> Result<ServerConfig, String> {
+    if server.id.is_empty() {
+        server.id = uuid::Uuid::new_v4().to_string();
+    }
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?
+        .join("lineups");
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let path = dir.join(format!("{}.m3u", server.id));
+    fs::write(&path, to_m3u(&channels))
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    server.kind = ServerKind::M3u;
+    server.url = path.to_string_lossy().into_owned();
+    let server = servers::upsert(store, server)?;
+    let fetched_at = offline::save(app, CacheKind::Catalogs, &server.id, "catalog", &channels);
+    catalog::replace(
+        app,
+        &server.id,
+        offline::Cached {
+            data: channels,
+            stale: false,
+            fetched_at,
+            error: None,
+        },
+    );
+    Ok(server)
+}
+
+/// Merges the playlists `server_ids` into the lineup `name`. Passing `lineup_id` rebuilds that
+/// lineup, from its saved playlists when `server_ids` is empty.
+#[tauri::command]
+pub async fn merge_playlists(
+    app: tauri::AppHandle,
+    store: State<'_, ServerStore>,
+    name: String,
+    server_ids: Vec<String>,
+    lineup_id: Option<String>,
+) -> Result<MergeReport, String> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err("The merged lineup needs a name".to_string());
+    }
+    let existing = match &lineup_id {
+        Some(id) => Some(servers::get(&store, id)?),
+        None => None,
+    };
+    let server_ids = match &existing {
+        Some(lineup) if server_ids.is_empty() => lineup.merged_from.clone(),
+        _ => server_ids,
+    };
+    if server_ids.len() < 2 {
+        return Err("Pick at least two playlists to merge".to_string());
+    }
+    let mut sources = Vec::new();
+    for id in &server_ids {
+        let server = servers::get(&store, id)?;
+        if server.kind != ServerKind::M3u {
+            return Err(format!("{} is not an M3U playlist", server.name));
+        }
+        if lineup_id.as_deref() == Some(id.as_str()) {
+            return Err("A lineup can't be merged into itself".to_string());
+        }
+        sources.push(server);
+    }
+
+    let mut seen = HashSet::new();
+    let mut kept = Vec::new();
+    let mut duplicates_removed = 0;
+    for server in &sources {
+        let (_, channels) = catalog::channels(&app, server).await?;
+        for channel in channels.iter().filter(|c| !c.hidden) {
+            if seen.insert(channel.id.clone()) {
+                kept.push((server.id.clone(), server.name.clone(), channel.clone()));
+            } else {
+                duplicates_removed += 1;
+            }
+        }
+    }
+    let near_duplicates = near_duplicates(&kept);
+    let channels: Vec<CatalogChannel> = kept.into_iter().map(|(_, _, c)| c).collect();
+
+    let total = channels.len();
+    let server = save_lineup(
+        &app,
+        &store,
+        ServerConfig {
+            id: lineup_id.unwrap_or_default(),
+            name,
+            merged_from: server_ids,
+            ..existing.unwrap_or_default()
+        },
+        channels,
+    )?;
+    tracing::info!(
+        lineup = %server.id,
+        total,
+        duplicates_removed,
+        near_duplicates = near_duplicates.len(),
+        "Merged playlists"
+    );
+    Ok(MergeReport {
+        server,
+        total,
+        duplicates_removed,
+        near_duplicates,
+    })
+}
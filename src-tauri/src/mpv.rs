@@ -0,0 +1,492 @@
+//! Optional libmpv playback path for streams the webview can't decode (MPEG-2, AC3, interlaced
+//! H.264). mpv renders into the video window's native handle and the webview on top is
+//! transparent, showing only controls. Needs the `mpv` cargo feature and libmpv at runtime;
+//! without it the commands report that the engine is unavailable.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tauri::{Emitter, Manager, State};
+
+use crate::remote::PlayerCommand;
+use crate::settings::SettingsStore;
+pub(crate) use native::audio_device_list;
+use native::Player;
+
+/// Properties pushed to the window as `mpv-property-change` events.
+const OBSERVED: [&str; 7] = [
+    "time-pos",
+    "duration",
+    "pause",
+    "paused-for-cache",
+    "eof-reached",
+    "track-list",
+    "volume",
+];
+
+#[derive(Default)]
+pub struct MpvState {
+    players: Mutex<HashMap<String, Player>>,
+}
+
+impl MpvState {
+    /// Whether window `label` is played by mpv.
+    pub fn has(&self, label: &str) -> bool {
+        self.players
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .contains_key(label)
+    }
+
+    /// Switches the output device of the mpv player of `label`; `auto` is the system default.
+    pub fn set_audio_device(&self, label: &str, device: &str) -> Result<(), String> {
+        with_player(self, label, |p| p.set_property("audio-device", device))
+    }
+
+    /// Saves the frame the mpv player of `label` shows to `path`, without subtitles or OSD.
+    pub fn screenshot(&self, label: &str, path: &str) -> Result<(), String> {
+        with_player(self, label, |p| {
+            p.command(&["screenshot-to-file", path, "video"])
+        })
+    }
+
+    /// Applies a remote/shortcut player command to the mpv player of `label`.
+    pub fn control(&self, label: &str, command: &PlayerCommand) -> Result<(), String> {
+        with_player(self, label, |p| match command {
+            PlayerCommand::Play => p.set_property("pause", "no"),
+            PlayerCommand::Pause => p.set_property("pause", "yes"),
+            PlayerCommand::TogglePause => p.command(&["cycle", "pause"]),
+            PlayerCommand::Seek { position } => {
+                p.command(&["seek", &position.to_string(), "absolute"])
+            }
+            PlayerCommand::SeekBy { seconds } => {
+                p.command(&["seek", &seconds.to_string(), "relative"])
+            }
+            PlayerCommand::Volume { level } => {
+                p.set_property("volume", &level.clamp(0.0, 100.0).to_string())
+            }
+            PlayerCommand::Mute { muted } => {
+                p.set_property("mute", if *muted { "yes" } else { "no" })
+            }
+            PlayerCommand::Load { url, .. } => p.command(&["loadfile", url, "replace"]),
+            // Window state, handled by the caller
+            PlayerCommand::Fullscreen { .. } => Ok(()),
+        })
+    }
+}
+
+#[derive(Clone, Serialize)]
+struct PropertyChange {
+    name: String,
+    value: serde_json::Value,
+}
+
+/// mpv reports values as strings; this restores numbers, flags and JSON (track-list).
+fn parse_value(raw: &str) -> serde_json::Value {
+    match raw {
+        "yes" => serde_json::Value::Bool(true),
+        "no" => serde_json::Value::Bool(false),
+        _ => serde_json::from_str(raw).unwrap_or_else(|_| serde_json::Value::String(raw.into())),
+    }
+}
+
+#[cfg(feature = "mpv")]
+mod native {
+    use std::ffi::{c_char, c_int, c_void, CStr, CString};
+
+    #[repr(C)]
+    struct MpvHandle {
+        _private: [u8; 0],
+    }
+
+    #[repr(C)]
+    struct MpvEvent {
+        event_id: c_int,
+        error: c_int,
+        reply_userdata: u64,
+        data: *mut c_void,
+    }
+
+    #[repr(C)]
+    struct MpvEventProperty {
+        name: *const c_char,
+        format: c_int,
+        data: *mut c_void,
+    }
+
+    const FORMAT_STRING: c_int = 1;
+    const EVENT_SHUTDOWN: c_int = 1;
+    const EVENT_PROPERTY_CHANGE: c_int = 22;
+
+    #[link(name = "mpv")]
+    extern "C" {
+        fn mpv_create() -> *mut MpvHandle;
+        fn mpv_initialize(ctx: *mut MpvHandle) -> c_int;
+        fn mpv_set_option_string(
+            ctx: *mut MpvHandle,
+            name: *const c_char,
+            data: *const c_char,
+        ) -> c_int;
+        fn mpv_set_property_string(
+            ctx: *mut MpvHandle,
+            name: *const c_char,
+            data: *const c_char,
+        ) -> c_int;
+        fn mpv_command(ctx: *mut MpvHandle, args: *mut *const c_char) -> c_int;
+        fn mpv_observe_property(
+            ctx: *mut MpvHandle,
+            reply_userdata: u64,
+            name: *const c_char,
+            format: c_int,
+        ) -> c_int;
+        fn mpv_get_property_string(ctx: *mut MpvHandle, name: *const c_char) -> *mut c_char;
+        fn mpv_free(data: *mut c_void);
+        fn mpv_wait_event(ctx: *mut MpvHandle, timeout: f64) -> *mut MpvEvent;
+        fn mpv_terminate_destroy(ctx: *mut MpvHandle);
+        fn mpv_error_string(error: c_int) -> *const c_char;
+    }
+
+    /// Pointer wrapper so the handle can move to the event thread; the client API is thread-safe.
+    #[derive(Clone, Copy)]
+    struct Ctx(*mut MpvHandle);
+    unsafe impl Send for Ctx {}
+    unsafe impl Sync for Ctx {}
+
+    pub struct Player {
+        ctx: Ctx,
+    }
+
+    fn cstring(s: &str) -> Result<CString, String> {
+        CString::new(s).map_err(|_| "mpv arguments must not contain NUL bytes".to_string())
+    }
+
+    fn check(code: c_int) -> Result<(), String> {
+        if code >= 0 {
+            return Ok(());
+        }
+        // SAFETY: mpv_error_string returns a static string for any error code
+        let msg = unsafe { CStr::from_ptr(mpv_error_string(code)) };
+        Err(format!("mpv: {}", msg.to_string_lossy()))
+    }
+
+    /// The `audio-device-list` property as JSON, read from a short-lived headless instance.
+    pub fn audio_device_list() -> Result<String, String> {
+        // SAFETY: plain constructor; null means out of memory or a broken install
+        let ctx = unsafe { mpv_create() };
+        if ctx.is_null() {
+            return Err("Failed to create mpv instance".to_string());
+        }
+        let read = || {
+            let (vo, null) = (cstring("vo")?, cstring("null")?);
+            // SAFETY: ctx is valid and not yet initialized; strings outlive the call
+            check(unsafe { mpv_set_option_string(ctx, vo.as_ptr(), null.as_ptr()) })?;
+            // SAFETY: ctx is valid
+            check(unsafe { mpv_initialize(ctx) })?;
+            let name = cstring("audio-device-list")?;
+            // SAFETY: ctx is initialized; the returned string is ours to free
+            let value = unsafe { mpv_get_property_string(ctx, name.as_ptr()) };
+            if value.is_null() {
+                return Err("mpv did not report any audio devices".to_string());
+            }
+            // SAFETY: value is a valid C string until freed below
+            let list = unsafe { CStr::from_ptr(value) }
+                .to_string_lossy()
+                .into_owned();
+            // SAFETY: value came from mpv_get_property_string
+            unsafe { mpv_free(value as *mut c_void) };
+            Ok(list)
+        };
+        let list = read();
+        // SAFETY: no event thread was started, so nothing else holds ctx
+        unsafe { mpv_terminate_destroy(ctx) };
+        list
+    }
+
+    impl Player {
+        /// Creates an mpv instance rendering into native window `wid` and starts its event
+        /// thread, which calls `on_property` for observed properties and destroys the
+        /// instance once mpv shuts down.
+        pub fn new(
+            wid: i64,
+            observed: &[&str],
+            on_property: impl Fn(&str, &str) + Send + 'static,
+        ) -> Result<Player, String> {
+            // SAFETY: plain constructor; null means out of memory or a broken install
+            let ctx = unsafe { mpv_create() };
+            if ctx.is_null() {
+                return Err("Failed to create mpv instance".to_string());
+            }
+            let player = Player { ctx: Ctx(ctx) };
+            let options = [
+                ("wid", wid.to_string()),
+                ("hwdec", "auto-safe".to_string()),
+                ("keep-open", "yes".to_string()),
+                ("idle", "yes".to_string()),
+                ("input-default-bindings", "no".to_string()),
+                ("osc", "no".to_string()),
+            ];
+            for (name, value) in options {
+                let (name, value) = (cstring(name)?, cstring(&value)?);
+                // SAFETY: ctx is valid and not yet initialized; strings outlive the call
+                check(unsafe { mpv_set_option_string(ctx, name.as_ptr(), value.as_ptr()) })?;
+            }
+            // SAFETY: ctx is valid
+            check(unsafe { mpv_initialize(ctx) })?;
+            for name in observed {
+                let name = cstring(name)?;
+                // SAFETY: ctx is initialized
+                check(unsafe { mpv_observe_property(ctx, 0, name.as_ptr(), FORMAT_STRING) })?;
+            }
+
+            let thread_ctx = player.ctx;
+            std::thread::spawn(move || {
+                let ctx = thread_ctx;
+                loop {
+                    // SAFETY: ctx stays valid until we destroy it below
+                    let event = unsafe { &*mpv_wait_event(ctx.0, -1.0) };
+                    if event.event_id == EVENT_SHUTDOWN {
+                        break;
+                    }
+                    if event.event_id != EVENT_PROPERTY_CHANGE || event.data.is_null() {
+                        continue;
+                    }
+                    // SAFETY: property-change events carry an mpv_event_property
+                    let prop = unsafe { &*(event.data as *const MpvEventProperty) };
+                    if prop.format != FORMAT_STRING || prop.data.is_null() {
+                        continue;
+                    }
+                    // SAFETY: for FORMAT_STRING, data points to a char* owned by the event
+                    let (name, value) = unsafe {
+                        (
+                            CStr::from_ptr(prop.name).to_string_lossy(),
+                            CStr::from_ptr(*(prop.data as *const *const c_char)).to_string_lossy(),
+                        )
+                    };
+                    on_property(&name, &value);
+                }
+                // SAFETY: shutdown was acknowledged and no one else holds ctx any more
+                unsafe { mpv_terminate_destroy(ctx.0) };
+            });
+            Ok(player)
+        }
+
+        pub fn command(&self, args: &[&str]) -> Result<(), String> {
+            let owned = args
+                .iter()
+                .map(|a| cstring(a))
+                .collect::<Result<Vec<_>, _>>()?;
+            let mut ptrs: Vec<*const c_char> = owned.iter().map(|a| a.as_ptr()).collect();
+            ptrs.push(std::ptr::null());
+            // SAFETY: null-terminated array of valid C strings
+            check(unsafe { mpv_command(self.ctx.0, ptrs.as_mut_ptr()) })
+        }
+
+        pub fn set_property(&self, name: &str, value: &str) -> Result<(), String> {
+            let (name, value) = (cstring(name)?, cstring(value)?);
+            // SAFETY: ctx is initialized; strings outlive the call
+            check(unsafe { mpv_set_property_string(self.ctx.0, name.as_ptr(), value.as_ptr()) })
+        }
+
+        /// Asks mpv to shut down; the event thread destroys the instance afterwards.
+        pub fn quit(&self) {
+            let _ = self.command(&["quit"]);
+        }
+    }
+}
+
+#[cfg(not(feature = "mpv"))]
+mod native {
+    /// Uninhabited: without the `mpv` feature no player can be created.
+    pub struct Player(std::convert::Infallible);
+
+    pub fn audio_device_list() -> Result<String, String> {
+        Err("This build of TvX does not include the mpv playback engine".to_string())
+    }
+
+    impl Player {
+        pub fn new(
+            _wid: i64,
+            _observed: &[&str],
+            _on_property: impl Fn(&str, &str) + Send + 'static,
+        ) -> Result<Player, String> {
+            Err("This build of TvX does not include the mpv playback engine".to_string())
+        }
+
+        pub fn command(&self, _args: &[&str]) -> Result<(), String> {
+            match self.0 {}
+        }
+
+        pub fn set_property(&self, _name: &str, _value: &str) -> Result<(), String> {
+            match self.0 {}
+        }
+
+        pub fn quit(&self) {
+            match self.0 {}
+        }
+    }
+}
+
+/// Native handle mpv accepts as `wid`: HWND, NSView* or X11 window id.
+fn native_window_id(window: &tauri::WebviewWindow) -> Result<i64, String> {
+    use raw_window_handle::{HasWindowHandle, RawWindowHandle};
+    let handle = window.window_handle().map_err(|e| e.to_string())?;
+    match handle.as_raw() {
+        RawWindowHandle::Win32(h) => Ok(h.hwnd.get() as i64),
+        RawWindowHandle::AppKit(h) => Ok(h.ns_view.as_ptr() as i64),
+        RawWindowHandle::Xlib(h) => Ok(h.window as i64),
+        RawWindowHandle::Xcb(h) => Ok(h.window.get() as i64),
+        _ => Err("mpv embedding is not supported on this windowing system".to_string()),
+    }
+}
+
+fn with_player<R>(
+    state: &MpvState,
+    label: &str,
+    f: impl FnOnce(&Player) -> Result<R, String>,
+) -> Result<R, String> {
+    let players = state.players.lock().unwrap_or_else(|e| e.into_inner());
+    let player = players
+        .get(label)
+        .ok_or_else(|| format!("No mpv player in window {}", label))?;
+    f(player)
+}
+
+/// Stops the player of a closed video window.
+pub fn release(app: &tauri::AppHandle, label: &str) {
+    if let Some(state) = app.try_state::<MpvState>() {
+        let player = state
+            .players
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(label);
+        if let Some(player) = player {
+            player.quit();
+        }
+    }
+}
+
+/// Opens a video window driven by mpv and starts playing `stream_url`. Returns the label.
+#[tauri::command]
+pub async fn mpv_open(
+    app: tauri::AppHandle,
+    state: State<'_, MpvState>,
+    settings: State<'_, SettingsStore>,
+    title: String,
+    stream_url: String,
+    profile_id: Option<String>,
+) -> Result<String, String> {
+    let label = crate::build_video_window(&app, &title, "video-window?engine=mpv", true)?;
+    let window = app
+        .get_webview_window(&label)
+        .ok_or_else(|| "Video window closed before mpv started".to_string())?;
+    let started = native_window_id(&window).and_then(|wid| {
+        let events = app.clone();
+        let target = label.clone();
+        Player::new(wid, &OBSERVED, move |name, value| {
+            let change = PropertyChange {
+                name: name.to_string(),
+                value: parse_value(value),
+            };
+            let _ = events.emit_to(target.as_str(), "mpv-property-change", change);
+        })
+    });
+    let player = match started {
+        Ok(player) => player,
+        Err(e) => {
+            tracing::error!("mpv failed to start: {}", e);
+            let _ = window.close();
+            return Err(e);
+        }
+    };
+    if let Some(device) =
+        crate::audio::device_for(&settings, &crate::settings::profile_id(profile_id))
+    {
+        if let Err(e) = player.set_property("audio-device", &device) {
+            tracing::warn!("mpv could not use audio device {}: {}", device, e);
+        }
+    }
+    player.command(&["loadfile", &stream_url])?;
+    state
+        .players
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(label.clone(), player);
+    Ok(label)
+}
+
+/// Replaces the current stream in an mpv window.
+#[tauri::command]
+pub fn mpv_load(
+    state: State<'_, MpvState>,
+    label: String,
+    stream_url: String,
+) -> Result<(), String> {
+    with_player(&state, &label, |p| p.command(&["loadfile", &stream_url]))
+}
+
+#[tauri::command]
+pub fn mpv_pause(state: State<'_, MpvState>, label: String, paused: bool) -> Result<(), String> {
+    with_player(&state, &label, |p| {
+        p.set_property("pause", if paused { "yes" } else { "no" })
+    })
+}
+
+/// Seeks to `seconds` (absolute) or by `seconds` (relative).
+#[tauri::command]
+pub fn mpv_seek(
+    state: State<'_, MpvState>,
+    label: String,
+    seconds: f64,
+    relative: Option<bool>,
+) -> Result<(), String> {
+    let mode = if relative.unwrap_or(false) {
+        "relative"
+    } else {
+        "absolute"
+    };
+    with_player(&state, &label, |p| {
+        p.command(&["seek", &seconds.to_string(), mode])
+    })
+}
+
+/// Selects a track by mpv track id; `kind` is `audio`, `sub` or `video`. `None` disables it.
+#[tauri::command]
+pub fn mpv_set_track(
+    state: State<'_, MpvState>,
+    label: String,
+    kind: String,
+    track_id: Option<i64>,
+) -> Result<(), String> {
+    let property = match kind.as_str() {
+        "audio" => "aid",
+        "sub" => "sid",
+        "video" => "vid",
+        _ => return Err(format!("Unknown track kind: {}", kind)),
+    };
+    let value = track_id.map_or_else(|| "no".to_string(), |id| id.to_string());
+    with_player(&state, &label, |p| p.set_property(property, &value))
+}
+
+#[tauri::command]
+pub fn mpv_set_volume(
+    state: State<'_, MpvState>,
+    volumes: State<'_, crate::volume::VolumeStore>,
+    label: String,
+    volume: f64,
+) -> Result<(), String> {
+    with_player(&state, &label, |p| {
+        p.set_property("volume", &volume.clamp(0.0, 130.0).to_string())
+    })?;
+    crate::volume::report(&volumes, &label, volume)
+}
+
+/// Adds an external subtitle file (any format mpv reads) and selects it.
+#[tauri::command]
+pub fn mpv_add_subtitle(
+    state: State<'_, MpvState>,
+    label: String,
+    path: String,
+) -> Result<(), String> {
+    with_player(&state, &label, |p| p.command(&["sub-add", &path, "select"]))
+}
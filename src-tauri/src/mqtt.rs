@@ -0,0 +1,383 @@
+//! Optional MQTT 3.1.1 bridge for home automation (Home Assistant, Node-RED). Publishes the
+//! event feed under `{base}/events/{event}`, a retained `{base}/state` snapshot and an
+//! online/offline availability topic, and accepts JSON commands on `{base}/command`.
+
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tauri::{Manager, State};
+
+use crate::feed::EventFeed;
+use crate::remote::{self, PlayerCommand};
+use crate::settings::SettingsStore;
+
+const KEEP_ALIVE_SECS: u16 = 60;
+const PING_INTERVAL: Duration = Duration::from_secs(30);
+const RECONNECT_DELAY: Duration = Duration::from_secs(10);
+
+fn default_port() -> u16 {
+    1883
+}
+
+fn default_base_topic() -> String {
+    "tvx".to_string()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MqttSettings {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub host: String,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    #[serde(default)]
+    pub username: String,
+    #[serde(default)]
+    pub password: String,
+    #[serde(default = "default_base_topic")]
+    pub base_topic: String,
+}
+
+impl Default for MqttSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            host: String::new(),
+            port: default_port(),
+            username: String::new(),
+            password: String::new(),
+            base_topic: default_base_topic(),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct MqttState {
+    running: Mutex<Option<Arc<AtomicBool>>>,
+    connected: Arc<AtomicBool>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MqttStatus {
+    pub enabled: bool,
+    pub connected: bool,
+}
+
+/// Payload of `{base}/command`: a player command for `label` (default: the newest video
+/// window), or `{"action": "tune", "url": ..., "title": ...}` to start playback.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MqttCommand {
+    #[serde(default)]
+    label: Option<String>,
+    #[serde(default)]
+    url: Option<String>,
+    #[serde(default)]
+    title: Option<String>,
+    #[serde(flatten)]
+    command: Option<PlayerCommand>,
+}
+
+fn encode_len(mut len: usize, out: &mut Vec<u8>) {
+    loop {
+        let mut byte = (len % 128) as u8;
+        len /= 128;
+        if len > 0 {
+            byte |= 0x80;
+        }
+        out.push(byte);
+        if len == 0 {
+            break;
+        }
+    }
+}
+
+fn encode_str(s: &[u8], out: &mut Vec<u8>) {
+    out.extend_from_slice(&(s.len() as u16).to_be_bytes());
+    out.extend_from_slice(s);
+}
+
+fn packet(header: u8, body: &[u8]) -> Vec<u8> {
+    let mut out = vec![header];
+    encode_len(body.len(), &mut out);
+    out.extend_from_slice(body);
+    out
+}
+
+fn connect_packet(config: &MqttSettings, client_id: &str, will_topic: &str) -> Vec<u8> {
+    let mut flags = 0x02 | 0x04 | 0x20; // clean session, will, retained will
+    if !config.username.is_empty() {
+        flags |= 0x80;
+        if !config.password.is_empty() {
+            flags |= 0x40;
+        }
+    }
+    let mut body = Vec::new();
+    encode_str(b"MQTT", &mut body);
+    body.push(4);
+    body.push(flags);
+    body.extend_from_slice(&KEEP_ALIVE_SECS.to_be_bytes());
+    encode_str(client_id.as_bytes(), &mut body);
+    encode_str(will_topic.as_bytes(), &mut body);
+    encode_str(b"offline", &mut body);
+    if !config.username.is_empty() {
+        encode_str(config.username.as_bytes(), &mut body);
+        if !config.password.is_empty() {
+            encode_str(config.password.as_bytes(), &mut body);
+        }
+    }
+    packet(0x10, &body)
+}
+
+fn publish_packet(topic: &str, payload: &[u8], retain: bool) -> Vec<u8> {
+    let mut body = Vec::new();
+    encode_str(topic.as_bytes(), &mut body);
+    body.extend_from_slice(payload);
+    packet(0x30 | u8::from(retain), &body)
+}
+
+fn subscribe_packet(topic: &str) -> Vec<u8> {
+    let mut body = 1u16.to_be_bytes().to_vec();
+    encode_str(topic.as_bytes(), &mut body);
+    body.push(0);
+    packet(0x82, &body)
+}
+
+/// Reads one packet, returning its header byte and body.
+fn read_packet(stream: &mut TcpStream) -> std::io::Result<(u8, Vec<u8>)> {
+    let mut header = [0u8; 1];
+    stream.read_exact(&mut header)?;
+    let (mut len, mut shift) = (0usize, 0);
+    loop {
+        let mut byte = [0u8; 1];
+        stream.read_exact(&mut byte)?;
+        len |= ((byte[0] & 0x7F) as usize) << shift;
+        if byte[0] & 0x80 == 0 {
+            break;
+        }
+        shift += 7;
+        if shift > 21 {
+            return Err(std::io::Error::other("Malformed MQTT length"));
+        }
+    }
+    let mut body = vec![0u8; len];
+    stream.read_exact(&mut body)?;
+    Ok((header[0], body))
+}
+
+/// Splits an incoming PUBLISH body into topic and payload.
+fn parse_publish(header: u8, body: &[u8]) -> Option<(String, Vec<u8>)> {
+    let topic_len = u16::from_be_bytes([*body.first()?, *body.get(1)?]) as usize;
+    let topic = String::from_utf8(body.get(2..2 + topic_len)?.to_vec()).ok()?;
+    // QoS 1/2 messages carry a packet id before the payload
+    let payload_start = 2 + topic_len + if header & 0x06 != 0 { 2 } else { 0 };
+    Some((topic, body.get(payload_start..)?.to_vec()))
+}
+
+fn handle_command(app: &tauri::AppHandle, payload: &[u8]) {
+    if crate::lock::is_locked() {
+        return tracing::debug!("Ignoring MQTT command while the app is locked");
+    }
+    let command = match serde_json::from_slice::<MqttCommand>(payload) {
+        Ok(command) => command,
+        Err(e) => return tracing::debug!("Ignoring malformed MQTT command: {}", e),
+    };
+    if let Some(url) = command.url {
+        let app = app.clone();
+        tauri::async_runtime::spawn(async move {
+            let played = crate::transcode::play_stream(
+                app.clone(),
+                app.state(),
+                app.state(),
+                app.state(),
+                command.title.unwrap_or_else(|| "MQTT".to_string()),
+                url,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await;
+            if let Err(e) = played {
+                tracing::warn!("MQTT play command failed: {}", e);
+            }
+        });
+        return;
+    }
+    let Some(player_command) = command.command else {
+        return;
+    };
+    let label = command.label.or_else(|| {
+        remote::video_windows(app)
+            .last()
+            .map(|w| w.label().to_string())
+    });
+    if let Some(label) = label {
+        if let Err(e) = remote::send_player_command(app, &label, &player_command) {
+            tracing::warn!("MQTT command for {} failed: {}", label, e);
+        }
+    }
+}
+
+/// One broker session: connect, subscribe, then forward feed events until an error or `stop`.
+fn session(
+    app: &tauri::AppHandle,
+    config: &MqttSettings,
+    stop: &AtomicBool,
+    connected: &AtomicBool,
+) -> Result<(), String> {
+    let base = config.base_topic.trim_end_matches('/');
+    let availability = format!("{}/availability", base);
+    let command_topic = format!("{}/command", base);
+    let mut stream = TcpStream::connect((config.host.as_str(), config.port))
+        .map_err(|e| format!("MQTT connect failed: {}", e))?;
+    let client_id = format!("tvx-{}", &uuid::Uuid::new_v4().simple().to_string()[..8]);
+    stream
+        .write_all(&connect_packet(config, &client_id, &availability))
+        .map_err(|e| e.to_string())?;
+    let (header, body) = read_packet(&mut stream).map_err(|e| e.to_string())?;
+    if header >> 4 != 2 || body.get(1) != Some(&0) {
+        return Err("MQTT broker refused the connection".to_string());
+    }
+    let events = app.state::<EventFeed>().subscribe();
+    stream
+        .write_all(&subscribe_packet(&command_topic))
+        .and_then(|_| stream.write_all(&publish_packet(&availability, b"online", true)))
+        .and_then(|_| {
+            let state = remote::status_json(app).to_string();
+            stream.write_all(&publish_packet(
+                &format!("{}/state", base),
+                state.as_bytes(),
+                true,
+            ))
+        })
+        .map_err(|e| e.to_string())?;
+    connected.store(true, Ordering::SeqCst);
+
+    let mut reader = stream.try_clone().map_err(|e| e.to_string())?;
+    let reader_app = app.clone();
+    std::thread::spawn(move || {
+        while let Ok((header, body)) = read_packet(&mut reader) {
+            if header >> 4 != 3 {
+                continue;
+            }
+            if let Some((topic, payload)) = parse_publish(header, &body) {
+                if topic == command_topic {
+                    handle_command(&reader_app, &payload);
+                }
+            }
+        }
+    });
+
+    let mut last_ping = Instant::now();
+    let result = loop {
+        if stop.load(Ordering::SeqCst) {
+            let _ = stream.write_all(&publish_packet(&availability, b"offline", true));
+            let _ = stream.write_all(&[0xE0, 0]);
+            break Ok(());
+        }
+        let sent = match events.recv_timeout(Duration::from_secs(1)) {
+            // Nothing is published while the app is locked
+            Ok(_) if crate::lock::is_locked() => Ok(()),
+            Ok(message) => {
+                let event = serde_json::from_str::<serde_json::Value>(&message)
+                    .ok()
+                    .and_then(|v| v["event"].as_str().map(String::from))
+                    .unwrap_or_default();
+                let mut result = stream.write_all(&publish_packet(
+                    &format!("{}/events/{}", base, event),
+                    message.as_bytes(),
+                    false,
+                ));
+                if event.starts_with("video-window-") {
+                    let state = remote::status_json(app).to_string();
+                    result = result.and_then(|_| {
+                        stream.write_all(&publish_packet(
+                            &format!("{}/state", base),
+                            state.as_bytes(),
+                            true,
+                        ))
+                    });
+                }
+                result
+            }
+            Err(RecvTimeoutError::Timeout) => Ok(()),
+            Err(RecvTimeoutError::Disconnected) => break Ok(()),
+        };
+        let sent = sent.and_then(|_| {
+            if last_ping.elapsed() >= PING_INTERVAL {
+                last_ping = Instant::now();
+                stream.write_all(&[0xC0, 0])
+            } else {
+                Ok(())
+            }
+        });
+        if let Err(e) = sent {
+            break Err(format!("MQTT connection lost: {}", e));
+        }
+    };
+    connected.store(false, Ordering::SeqCst);
+    let _ = stream.shutdown(std::net::Shutdown::Both);
+    result
+}
+
+/// Starts or stops the bridge to match the saved settings.
+pub fn apply(app: &tauri::AppHandle) {
+    let config = app.state::<SettingsStore>().read(|s| s.mqtt.clone());
+    let state = app.state::<MqttState>();
+    let mut running = state.running.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(stop) = running.take() {
+        stop.store(true, Ordering::SeqCst);
+    }
+    if !config.enabled || config.host.is_empty() {
+        return;
+    }
+    let stop = Arc::new(AtomicBool::new(false));
+    *running = Some(stop.clone());
+    let connected = state.connected.clone();
+    let app = app.clone();
+    std::thread::spawn(move || {
+        while !stop.load(Ordering::SeqCst) {
+            match session(&app, &config, &stop, &connected) {
+                Ok(()) => continue,
+                Err(e) => tracing::warn!("MQTT broker {}:{}: {}", config.host, config.port, e),
+            }
+            // Broker down or unreachable: retry until disabled
+            let until = Instant::now() + RECONNECT_DELAY;
+            while Instant::now() < until && !stop.load(Ordering::SeqCst) {
+                std::thread::sleep(Duration::from_millis(250));
+            }
+        }
+    });
+}
+
+#[tauri::command]
+pub fn get_mqtt_status(
+    settings: State<'_, SettingsStore>,
+    state: State<'_, MqttState>,
+) -> MqttStatus {
+    MqttStatus {
+        enabled: settings.read(|s| s.mqtt.enabled),
+        connected: state.connected.load(Ordering::SeqCst),
+    }
+}
+
+#[tauri::command]
+pub fn configure_mqtt(
+    app: tauri::AppHandle,
+    settings: State<'_, SettingsStore>,
+    mqtt: MqttSettings,
+) -> Result<(), String> {
+    settings.update(|s| s.mqtt = mqtt)?;
+    apply(&app);
+    Ok(())
+}
@@ -0,0 +1,128 @@
+//! Network change detection. The local addresses the OS would use for the default route are
+//! polled every few seconds (connecting a UDP socket picks a route without sending anything),
+//! so coming back online, switching from Wi-Fi to Ethernet or a VPN coming up all show as a
+//! change. Each change is emitted as `network-changed`; once the new network has settled,
+//! `reconnect` retries failed servers and reloads open players with freshly resolved URLs.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::time::Duration;
+
+use serde::Serialize;
+use tauri::{Emitter, Manager};
+
+use crate::servers::{ServerKind, ServerStore};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// Interfaces often change address more than once while connecting (DHCP, VPN handshakes).
+const SETTLE: Duration = Duration::from_secs(3);
+/// Public resolvers, only used to pick a route; no packet is sent.
+const PROBE_V4: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(1, 1, 1, 1)), 53);
+const PROBE_V6: SocketAddr = SocketAddr::new(
+    IpAddr::V6(Ipv6Addr::new(0x2606, 0x4700, 0x4700, 0, 0, 0, 0, 0x1111)),
+    53,
+);
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NetworkState {
+    /// Local address of the default IPv4 route.
+    pub ipv4: Option<IpAddr>,
+    pub ipv6: Option<IpAddr>,
+}
+
+impl NetworkState {
+    pub fn online(&self) -> bool {
+        self.ipv4.is_some() || self.ipv6.is_some()
+    }
+}
+
+fn route_source(probe: SocketAddr) -> Option<IpAddr> {
+    let bind: SocketAddr = match probe {
+        SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+        SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+    };
+    let socket = UdpSocket::bind(bind).ok()?;
+    socket.connect(probe).ok()?;
+    Some(socket.local_addr().ok()?.ip()).filter(|ip| !ip.is_unspecified())
+}
+
+fn current() -> NetworkState {
+    NetworkState {
+        ipv4: route_source(PROBE_V4),
+        ipv6: route_source(PROBE_V6),
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct NetworkChanged {
+    online: bool,
+    #[serde(flatten)]
+    state: NetworkState,
+}
+
+/// Retries failed servers and reconnects open players: failing circuits are reset, queued
+/// changes sent, channel lists served from the offline cache reloaded and Emby sessions
+/// checked; live windows are re-tuned to freshly resolved URLs and other video windows get
+/// `stream-reconnect`.
+pub async fn reconnect(app: &tauri::AppHandle) {
+    crate::health::reset_all();
+    crate::offline::flush(app).await;
+    crate::catalog::refresh_stale(app);
+
+    let servers = app.state::<ServerStore>().read(|servers| servers.clone());
+    for server in servers.iter().filter(|s| s.kind == ServerKind::Emby) {
+        if let Err(e) = crate::emby::revalidate(&app.state::<ServerStore>(), &server.id).await {
+            tracing::warn!("Could not renew the session with {}: {}", server.name, e);
+        }
+    }
+    for window in crate::remote::video_windows(app) {
+        let label = window.label().to_string();
+        if crate::history::live_channel(&label).is_some() {
+            match crate::zap::retune(app, &label).await {
+                Ok(_) => continue,
+                Err(e) => tracing::warn!("Could not re-tune {}: {}", label, e),
+            }
+        }
+        let _ = app.emit_to(label.as_str(), "stream-reconnect", ());
+    }
+}
+
+/// Watches for network changes for the life of the app.
+pub fn start(app: &tauri::AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let mut known = tauri::async_runtime::spawn_blocking(current)
+            .await
+            .unwrap_or_default();
+        loop {
+            tokio::time::sleep(POLL_INTERVAL).await;
+            let Ok(state) = tauri::async_runtime::spawn_blocking(current).await else {
+                continue;
+            };
+            if state == known {
+                continue;
+            }
+            tokio::time::sleep(SETTLE).await;
+            let Ok(settled) = tauri::async_runtime::spawn_blocking(current).await else {
+                continue;
+            };
+            if settled == known {
+                continue;
+            }
+            tracing::info!(?settled, "Network changed");
+            let _ = app.emit(
+                "network-changed",
+                NetworkChanged {
+                    online: settled.online(),
+                    state: settled.clone(),
+                },
+            );
+            // Going offline leaves nothing to reconnect to
+            if settled.online() {
+                reconnect(&app).await;
+            }
+            known = settled;
+        }
+    });
+}
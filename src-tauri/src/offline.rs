@@ -0,0 +1,344 @@
+//! Offline mode. Catalog responses (channel lists, VOD libraries, EPG) are cached on disk per
+//! server and served, flagged stale, when a later fetch fails. Changes meant for a server
+//! (favorites, watch progress) go through an outbox: they're sent right away when possible,
+//! otherwise queued and replayed in order once the server answers again.
+
+use std::fs;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::{Emitter, Manager, State};
+
+use crate::cache::{self, CacheKind};
+use crate::servers::{self, ServerConfig, ServerStore};
+use crate::store::JsonStore;
+
+const FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// A catalog response, possibly from the cache.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Cached<T> {
+    pub data: T,
+    /// True when the fetch failed and `data` is the last cached copy.
+    pub stale: bool,
+    /// Unix seconds when `data` was fetched from the server.
+    pub fetched_at: u64,
+    /// Why the fetch failed, when stale.
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CacheEntry<T> {
+    fetched_at: u64,
+    data: T,
+}
+
+/// The server's directory in the `kind` cache.
+pub fn server_dir(
+    app: &tauri::AppHandle,
+    kind: CacheKind,
+    server_id: &str,
+) -> Result<PathBuf, String> {
+    let name: String = server_id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    Ok(cache::dir(app, kind)?.join(name))
+}
+
+/// Keys can contain credentials (Xtream URLs), so files are named by their hash.
+fn cache_path(
+    app: &tauri::AppHandle,
+    kind: CacheKind,
+    server_id: &str,
+    key: &str,
+) -> Result<PathBuf, String> {
+    let digest = Sha256::digest(key.as_bytes());
+    let name: String = digest[..16].iter().map(|b| format!("{:02x}", b)).collect();
+    Ok(server_dir(app, kind, server_id)?.join(format!("{}.json", name)))
+}
+
+fn write_entry<T: Serialize>(path: &Path, entry: &CacheEntry<T>) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create catalog cache: {}", e))?;
+    }
+    let json = serde_json::to_vec(entry).map_err(|e| e.to_string())?;
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, json).map_err(|e| format!("Failed to write catalog cache: {}", e))?;
+    fs::rename(&tmp, path).map_err(|e| format!("Failed to write catalog cache: {}", e))
+}
+
+fn read_entry<T: DeserializeOwned>(path: &Path) -> Option<CacheEntry<T>> {
+    serde_json::from_slice(&fs::read(path).ok()?).ok()
+}
+
+/// Caches `data` under `key`, logging rather than failing: a full disk shouldn't fail the
+/// request that produced it. Returns the fetch time recorded.
+pub fn save<T: Serialize>(
+    app: &tauri::AppHandle,
+    kind: CacheKind,
+    server_id: &str,
+    key: &str,
+    data: &T,
+) -> u64 {
+    let entry = CacheEntry {
+        fetched_at: crate::now_secs(),
+        data,
+    };
+    if let Err(e) =
+        cache_path(app, kind, server_id, key).and_then(|path| write_entry(&path, &entry))
+    {
+        tracing::warn!("{}", e);
+    }
+    entry.fetched_at
+}
+
+/// The cached copy of `key` with its fetch time, if any.
+pub fn load<T: DeserializeOwned>(
+    app: &tauri::AppHandle,
+    kind: CacheKind,
+    server_id: &str,
+    key: &str,
+) -> Option<(T, u64)> {
+    let path = cache_path(app, kind, server_id, key).ok()?;
+    let entry = read_entry::<T>(&path)?;
+    cache::touch(&path);
+    Some((entry.data, entry.fetched_at))
+}
+
+/// Runs `fetch`, caching its result under `key` for `server_id`. If it fails and a cached copy
+/// exists, returns that copy marked stale instead of the error.
+pub async fn cached<T, F>(
+    app: &tauri::AppHandle,
+    kind: CacheKind,
+    server_id: &str,
+    key: &str,
+    fetch: F,
+) -> Result<Cached<T>, String>
+where
+    T: Serialize + DeserializeOwned,
+    F: Future<Output = Result<T, String>>,
+{
+    match fetch.await {
+        Ok(data) => Ok(Cached {
+            fetched_at: save(app, kind, server_id, key, &data),
+            data,
+            stale: false,
+            error: None,
+        }),
+        Err(e) => {
+            let Some((data, fetched_at)) = load(app, kind, server_id, key) else {
+                return Err(e);
+            };
+            tracing::info!("Serving cached catalog for {}: {}", server_id, e);
+            Ok(Cached {
+                data,
+                stale: true,
+                fetched_at,
+                error: Some(e),
+            })
+        }
+    }
+}
+
+/// A change to replay on a server.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Mutation {
+    #[serde(rename_all = "camelCase")]
+    EmbyFavorite { item_id: String, favorite: bool },
+    #[serde(rename_all = "camelCase")]
+    EmbyProgress {
+        item_id: String,
+        position_secs: f64,
+        played: bool,
+    },
+}
+
+impl Mutation {
+    /// Mutations of the same thing supersede each other; only the latest is worth sending.
+    fn target(&self) -> (&'static str, &str) {
+        match self {
+            Mutation::EmbyFavorite { item_id, .. } => ("favorite", item_id),
+            Mutation::EmbyProgress { item_id, .. } => ("progress", item_id),
+        }
+    }
+}
+
+/// Why applying a mutation failed: queue it (`Unreachable`) or give up on it (`Rejected`).
+#[derive(Debug)]
+pub enum MutationError {
+    Unreachable(String),
+    Rejected(String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingMutation {
+    pub id: String,
+    pub server_id: String,
+    pub mutation: Mutation,
+    pub queued_at: u64,
+    #[serde(default)]
+    pub attempts: u32,
+    #[serde(default)]
+    pub last_error: Option<String>,
+}
+
+pub type Outbox = JsonStore<Vec<PendingMutation>>;
+
+pub fn open(app: &tauri::AppHandle) -> Outbox {
+    JsonStore::open(app, "outbox.json")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Submitted {
+    Sent,
+    Queued,
+}
+
+async fn apply(server: &ServerConfig, mutation: &Mutation) -> Result<(), MutationError> {
+    match mutation {
+        Mutation::EmbyFavorite { item_id, favorite } => {
+            crate::emby::apply_favorite(server, item_id, *favorite).await
+        }
+        Mutation::EmbyProgress {
+            item_id,
+            position_secs,
+            played,
+        } => crate::emby::apply_progress(server, item_id, *position_secs, *played).await,
+    }
+}
+
+/// Sends `mutation` now, or queues it when the server can't be reached. Changes already queued
+/// for the server are sent first so they arrive in order.
+pub async fn submit(
+    app: &tauri::AppHandle,
+    server: &ServerConfig,
+    mutation: Mutation,
+) -> Result<Submitted, String> {
+    let outbox = app.state::<Outbox>();
+    let backlog = outbox.read(|items| items.iter().any(|m| m.server_id == server.id));
+    if !backlog {
+        match apply(server, &mutation).await {
+            Ok(()) => return Ok(Submitted::Sent),
+            Err(MutationError::Rejected(e)) => return Err(e),
+            Err(MutationError::Unreachable(e)) => {
+                tracing::info!("{} is unreachable, queueing change: {}", server.name, e)
+            }
+        }
+    }
+    let id = uuid::Uuid::new_v4().to_string();
+    let pending = PendingMutation {
+        id: id.clone(),
+        server_id: server.id.clone(),
+        mutation,
+        queued_at: crate::now_secs(),
+        attempts: 0,
+        last_error: None,
+    };
+    outbox.update(|items| {
+        items.retain(|m| {
+            m.server_id != pending.server_id || m.mutation.target() != pending.mutation.target()
+        });
+        items.push(pending);
+    })?;
+    let _ = app.emit("outbox-changed", outbox.read(|items| items.len()));
+    if backlog {
+        flush(app).await;
+        if outbox.read(|items| !items.iter().any(|m| m.id == id)) {
+            return Ok(Submitted::Sent);
+        }
+    }
+    Ok(Submitted::Queued)
+}
+
+/// Replays queued mutations, oldest first. A server that's still unreachable keeps the rest
+/// of its queue; rejected mutations are dropped. Returns how many are still pending.
+pub async fn flush(app: &tauri::AppHandle) -> usize {
+    let outbox = app.state::<Outbox>();
+    let store = app.state::<ServerStore>();
+    let pending = outbox.read(|items| items.clone());
+    let mut blocked: Vec<String> = Vec::new();
+    let mut changed = false;
+    for item in pending {
+        if blocked.contains(&item.server_id) {
+            continue;
+        }
+        let result = match servers::get(&store, &item.server_id) {
+            Ok(server) => apply(&server, &item.mutation).await,
+            Err(e) => Err(MutationError::Rejected(e)),
+        };
+        let outcome = outbox.update(|items| match result {
+            Ok(()) => items.retain(|m| m.id != item.id),
+            Err(MutationError::Rejected(e)) => {
+                tracing::warn!("Dropping queued change for {}: {}", item.server_id, e);
+                items.retain(|m| m.id != item.id);
+            }
+            Err(MutationError::Unreachable(e)) => {
+                if let Some(m) = items.iter_mut().find(|m| m.id == item.id) {
+                    m.attempts += 1;
+                    m.last_error = Some(e);
+                }
+                blocked.push(item.server_id.clone());
+            }
+        });
+        changed |= outcome.is_ok();
+    }
+    let remaining = outbox.read(|items| items.len());
+    if changed {
+        let _ = app.emit("outbox-changed", remaining);
+    }
+    remaining
+}
+
+/// Retries the outbox in the background every minute.
+pub fn start(app: &tauri::AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(FLUSH_INTERVAL).await;
+            if app.state::<Outbox>().read(|items| !items.is_empty()) {
+                flush(&app).await;
+            }
+        }
+    });
+}
+
+#[tauri::command]
+pub fn get_pending_mutations(outbox: State<'_, Outbox>) -> Vec<PendingMutation> {
+    outbox.read(|items| items.clone())
+}
+
+/// Replays the outbox now. Returns how many changes are still pending.
+#[tauri::command]
+pub async fn sync_pending_mutations(app: tauri::AppHandle) -> Result<usize, String> {
+    Ok(flush(&app).await)
+}
+
+#[tauri::command]
+pub fn discard_pending_mutation(
+    app: tauri::AppHandle,
+    outbox: State<'_, Outbox>,
+    id: String,
+) -> Result<(), String> {
+    let remaining = outbox.update(|items| {
+        items.retain(|m| m.id != id);
+        items.len()
+    })?;
+    let _ = app.emit("outbox-changed", remaining);
+    Ok(())
+}
@@ -0,0 +1,273 @@
+//! opensubtitles.com REST client: search by file hash and/or title, download, and cache.
+//! Needs a consumer API key in settings; anonymous downloads are limited per day by the API.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::cache::CacheKind;
+use crate::proxy::ProxyState;
+use crate::settings::SettingsStore;
+use crate::subtitles::{self, SubtitleTrack};
+
+const API_BASE: &str = "https://api.opensubtitles.com/api/v1";
+const USER_AGENT: &str = concat!("TvX v", env!("CARGO_PKG_VERSION"));
+/// The hash covers the first and last 64 KiB of the file.
+const HASH_CHUNK: u64 = 64 * 1024;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubtitleResult {
+    pub file_id: u64,
+    pub file_name: String,
+    pub language: String,
+    pub release: String,
+    pub download_count: u64,
+    /// Matched by file hash, so it is synced to this exact release.
+    pub hash_match: bool,
+}
+
+/// Search results keyed by `(content_id, language)` for this run.
+#[derive(Default)]
+pub struct OpenSubtitlesState {
+    searches: Mutex<HashMap<(String, String), Vec<SubtitleResult>>>,
+}
+
+#[derive(Deserialize)]
+struct SearchResponse {
+    #[serde(default)]
+    data: Vec<SearchItem>,
+}
+
+#[derive(Deserialize)]
+struct SearchItem {
+    attributes: SearchAttributes,
+}
+
+#[derive(Deserialize)]
+struct SearchAttributes {
+    #[serde(default)]
+    language: String,
+    #[serde(default)]
+    release: String,
+    #[serde(default)]
+    download_count: u64,
+    #[serde(default)]
+    moviehash_match: bool,
+    #[serde(default)]
+    files: Vec<SearchFile>,
+}
+
+#[derive(Deserialize)]
+struct SearchFile {
+    file_id: u64,
+    #[serde(default)]
+    file_name: String,
+}
+
+#[derive(Deserialize)]
+struct DownloadResponse {
+    link: String,
+}
+
+fn client() -> Result<reqwest::Client, String> {
+    crate::http::client(None)
+}
+
+/// Request to the OpenSubtitles API, which rejects clients without a registered user agent.
+fn api_request(
+    method: reqwest::Method,
+    path: &str,
+    key: &str,
+) -> Result<reqwest::RequestBuilder, String> {
+    Ok(client()?
+        .request(method, format!("{}{}", API_BASE, path))
+        .header(reqwest::header::USER_AGENT, USER_AGENT)
+        .header("Api-Key", key))
+}
+
+fn api_key(settings: &SettingsStore) -> Result<String, String> {
+    settings
+        .read(|s| s.opensubtitles_api_key.clone())
+        .filter(|k| !k.is_empty())
+        .ok_or_else(|| "Add an OpenSubtitles API key in Settings first".to_string())
+}
+
+async fn fetch_range(client: &reqwest::Client, url: &str, start: u64) -> Result<Vec<u8>, String> {
+    let req = client.get(url).header(
+        "Range",
+        format!("bytes={}-{}", start, start + HASH_CHUNK - 1),
+    );
+    let resp = crate::http::send(req).await.map_err(|e| e.to_string())?;
+    if resp.status() != reqwest::StatusCode::PARTIAL_CONTENT {
+        return Err("Server does not support range requests".to_string());
+    }
+    Ok(resp.bytes().await.map_err(|e| e.to_string())?.to_vec())
+}
+
+fn checksum(bytes: &[u8]) -> u64 {
+    bytes.chunks_exact(8).fold(0u64, |sum, word| {
+        sum.wrapping_add(u64::from_le_bytes(word.try_into().unwrap_or_default()))
+    })
+}
+
+/// OpenSubtitles "moviehash" of a remote file: size plus the 64-bit word sums of its first and
+/// last 64 KiB. Fails for live streams and servers without range support.
+pub async fn movie_hash(url: &str) -> Result<String, String> {
+    let client = client()?;
+    let head = crate::http::send(client.head(url))
+        .await
+        .map_err(|e| e.to_string())?;
+    let size = head
+        .content_length()
+        .filter(|s| *s >= HASH_CHUNK)
+        .ok_or_else(|| "Unknown or too small content length".to_string())?;
+    let first = fetch_range(&client, url, 0).await?;
+    let last = fetch_range(&client, url, size - HASH_CHUNK).await?;
+    let hash = size
+        .wrapping_add(checksum(&first))
+        .wrapping_add(checksum(&last));
+    Ok(format!("{:016x}", hash))
+}
+
+/// Searches OpenSubtitles for `content_id`, by file hash when `stream_url` is a seekable file
+/// and by title otherwise. Results are cached per content and language for this run.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn find_subtitles(
+    settings: State<'_, SettingsStore>,
+    state: State<'_, OpenSubtitlesState>,
+    content_id: String,
+    language: String,
+    title: Option<String>,
+    year: Option<u32>,
+    season: Option<u32>,
+    episode: Option<u32>,
+    stream_url: Option<String>,
+) -> Result<Vec<SubtitleResult>, String> {
+    let cache_key = (content_id, language.clone());
+    if let Some(cached) = state
+        .searches
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&cache_key)
+    {
+        return Ok(cached.clone());
+    }
+    let key = api_key(&settings)?;
+    let mut query: Vec<(&str, String)> = vec![("languages", language)];
+    if let Some(url) = stream_url {
+        if let Ok(hash) = movie_hash(&url).await {
+            query.push(("moviehash", hash));
+        }
+    }
+    if let Some(title) = title {
+        query.push(("query", title));
+    }
+    if let Some(year) = year {
+        query.push(("year", year.to_string()));
+    }
+    if let Some(season) = season {
+        query.push(("season_number", season.to_string()));
+    }
+    if let Some(episode) = episode {
+        query.push(("episode_number", episode.to_string()));
+    }
+    if query.len() == 1 {
+        return Err("Need a title or a stream URL to search subtitles".to_string());
+    }
+    let req = api_request(reqwest::Method::GET, "/subtitles", &key)?.query(&query);
+    let resp = crate::http::send(req)
+        .await
+        .map_err(|e| format!("OpenSubtitles request failed: {}", e))?;
+    if !resp.status().is_success() {
+        return Err(format!("OpenSubtitles returned HTTP {}", resp.status()));
+    }
+    let found: SearchResponse = resp
+        .json()
+        .await
+        .map_err(|e| format!("Invalid OpenSubtitles response: {}", e))?;
+    let mut results: Vec<SubtitleResult> = found
+        .data
+        .into_iter()
+        .flat_map(|item| {
+            let a = item.attributes;
+            a.files.into_iter().map(move |f| SubtitleResult {
+                file_id: f.file_id,
+                file_name: f.file_name,
+                language: a.language.clone(),
+                release: a.release.clone(),
+                download_count: a.download_count,
+                hash_match: a.moviehash_match,
+            })
+        })
+        .collect();
+    results.sort_by_key(|r| {
+        (
+            std::cmp::Reverse(r.hash_match),
+            std::cmp::Reverse(r.download_count),
+        )
+    });
+    state
+        .searches
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(cache_key, results.clone());
+    Ok(results)
+}
+
+fn cache_dir(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let dir = crate::cache::dir(app, CacheKind::Subtitles)?;
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create subtitle cache: {}", e))?;
+    Ok(dir)
+}
+
+/// Downloads a search result (or reuses the cached copy), serves it as WebVTT and, with
+/// `label`, adds it to that video window.
+#[tauri::command]
+pub async fn download_subtitle(
+    app: tauri::AppHandle,
+    settings: State<'_, SettingsStore>,
+    proxy: State<'_, ProxyState>,
+    file_id: u64,
+    label: Option<String>,
+) -> Result<SubtitleTrack, String> {
+    // Downloads are always SRT unless another format is requested
+    let path = cache_dir(&app)?.join(format!("{}.srt", file_id));
+    if path.exists() {
+        crate::cache::touch(&path);
+    } else {
+        let req = api_request(reqwest::Method::POST, "/download", &api_key(&settings)?)?
+            .json(&serde_json::json!({ "file_id": file_id }));
+        let resp = crate::http::send(req)
+            .await
+            .map_err(|e| format!("OpenSubtitles request failed: {}", e))?;
+        if resp.status() == reqwest::StatusCode::NOT_ACCEPTABLE {
+            return Err("OpenSubtitles daily download limit reached".to_string());
+        }
+        if !resp.status().is_success() {
+            return Err(format!("OpenSubtitles returned HTTP {}", resp.status()));
+        }
+        let download: DownloadResponse = resp
+            .json()
+            .await
+            .map_err(|e| format!("Invalid OpenSubtitles response: {}", e))?;
+        let req = client()?
+            .get(&download.link)
+            .header(reqwest::header::USER_AGENT, USER_AGENT);
+        let body = crate::http::send(req)
+            .await
+            .map_err(|e| format!("Subtitle download failed: {}", e))?
+            .bytes()
+            .await
+            .map_err(|e| format!("Subtitle download failed: {}", e))?;
+        fs::write(&path, &body).map_err(|e| format!("Failed to save subtitle: {}", e))?;
+    }
+    let track = subtitles::load(&proxy, &path)?;
+    subtitles::attach(&app, label, &track);
+    Ok(track)
+}
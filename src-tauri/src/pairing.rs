@@ -0,0 +1,285 @@
+//! Pairing remote clients with the remote API. The app shows a QR code with its address and a
+//! short-lived code; the remote exchanges the code for its own token, which can be revoked
+//! per device without resetting everyone else.
+
+use std::collections::BTreeMap;
+use std::net::{IpAddr, Ipv4Addr, UdpSocket};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, State};
+
+use crate::settings::SettingsStore;
+use crate::store::JsonStore;
+
+/// How long a pairing code stays valid.
+const PAIRING_TTL_SECS: u64 = 300;
+/// Wrong codes accepted before the pairing window closes, so a code can't be guessed.
+const MAX_PAIRING_ATTEMPTS: u32 = 5;
+/// How often a device's `last_seen` is written to disk at most.
+const LAST_SEEN_SAVE_SECS: u64 = 60;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PairedDevice {
+    pub id: String,
+    pub name: String,
+    /// Bearer token issued to this device.
+    #[serde(skip_serializing_if = "String::is_empty", default)]
+    pub token: String,
+    pub paired_at: u64,
+    #[serde(default)]
+    pub last_seen: Option<u64>,
+}
+
+pub type PairedDeviceStore = JsonStore<Vec<PairedDevice>>;
+
+pub fn open(app: &tauri::AppHandle) -> PairedDeviceStore {
+    JsonStore::open(app, "paired_devices.json")
+}
+
+struct PendingPairing {
+    code: String,
+    expires_at: u64,
+    failed_attempts: u32,
+}
+
+#[derive(Default)]
+pub struct PairingState {
+    pending: Mutex<Option<PendingPairing>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PairingOffer {
+    pub code: String,
+    pub address: String,
+    pub port: u16,
+    pub expires_at: u64,
+    /// Text to encode in the QR code.
+    pub qr_payload: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PairRequest {
+    pub code: String,
+    #[serde(default)]
+    pub device_name: Option<String>,
+}
+
+/// The LAN address other devices reach us on: the source address of the default route.
+/// Connecting a UDP socket sends no packets.
+pub(crate) fn lan_address() -> IpAddr {
+    UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))
+        .and_then(|s| {
+            s.connect((Ipv4Addr::new(192, 0, 2, 1), 9))?;
+            s.local_addr()
+        })
+        .map(|a| a.ip())
+        .unwrap_or(IpAddr::V4(Ipv4Addr::LOCALHOST))
+}
+
+/// Uses up the pending pairing code if `code` is it, counting wrong ones against the limit.
+fn redeem(state: &PairingState, code: &str, now: u64) -> Result<(), String> {
+    let mut pending = state.pending.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(offer) = pending.as_mut().filter(|p| p.expires_at > now) else {
+        return Err("Invalid or expired pairing code".to_string());
+    };
+    if !tokens_match(code, &offer.code) {
+        offer.failed_attempts += 1;
+        if offer.failed_attempts >= MAX_PAIRING_ATTEMPTS {
+            tracing::warn!(
+                "Closing pairing after {} wrong codes",
+                offer.failed_attempts
+            );
+            *pending = None;
+        }
+        return Err("Invalid or expired pairing code".to_string());
+    }
+    // Codes are single-use
+    *pending = None;
+    Ok(())
+}
+
+/// Completes pairing for a remote presenting `code`. Returns the new device with its token.
+pub fn pair(
+    app: &tauri::AppHandle,
+    state: &PairingState,
+    devices: &PairedDeviceStore,
+    request: PairRequest,
+) -> Result<PairedDevice, String> {
+    redeem(state, &request.code, crate::now_secs())?;
+    let device = PairedDevice {
+        id: uuid::Uuid::new_v4().to_string(),
+        name: request
+            .device_name
+            .filter(|n| !n.trim().is_empty())
+            .unwrap_or_else(|| "Remote".to_string()),
+        token: uuid::Uuid::new_v4().simple().to_string(),
+        paired_at: crate::now_secs(),
+        last_seen: None,
+    };
+    devices.update(|list| list.push(device.clone()))?;
+    let mut announced = device.clone();
+    announced.token.clear();
+    let _ = app.emit("remote-paired", announced);
+    Ok(device)
+}
+
+/// Compares tokens in time that depends only on their lengths, so a remote can't find a valid
+/// token byte by byte from response times.
+pub(crate) fn tokens_match(presented: &str, expected: &str) -> bool {
+    presented.len() == expected.len()
+        && presented
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// When each device (by id) was last seen, ahead of what was saved.
+static LAST_SEEN: Mutex<BTreeMap<String, u64>> = Mutex::new(BTreeMap::new());
+
+fn last_seen() -> std::sync::MutexGuard<'static, BTreeMap<String, u64>> {
+    LAST_SEEN.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Checks a bearer token against paired devices, recording when the device was last seen.
+pub fn authorize(devices: &PairedDeviceStore, token: &str) -> bool {
+    if token.is_empty() {
+        return false;
+    }
+    // Every device is compared, so the time taken doesn't tell which one matched
+    let found = devices.read(|list| {
+        list.iter().fold(None, |found, d| {
+            let matches = tokens_match(token, &d.token);
+            found.or_else(|| matches.then(|| (d.id.clone(), d.last_seen)))
+        })
+    });
+    let Some((id, saved)) = found else {
+        return false;
+    };
+    let now = crate::now_secs();
+    last_seen().insert(id.clone(), now);
+    // Saved only now and then, not on every request
+    if saved.is_none_or(|seen| now.saturating_sub(seen) >= LAST_SEEN_SAVE_SECS) {
+        let _ = devices.update(|list| {
+            if let Some(device) = list.iter_mut().find(|d| d.id == id) {
+                device.last_seen = Some(now);
+            }
+        });
+    }
+    true
+}
+
+/// Starts a pairing window, enabling the remote API if needed. The QR payload is a
+/// `tvx://pair` URL carrying the address, port and code.
+#[tauri::command]
+pub fn start_pairing(
+    app: tauri::AppHandle,
+    settings: State<'_, SettingsStore>,
+    state: State<'_, PairingState>,
+) -> Result<PairingOffer, String> {
+    if !settings.read(|s| s.remote_api.enabled) {
+        settings.update(|s| s.remote_api.enabled = true)?;
+        crate::remote::apply(&app)?;
+    }
+    let port = settings.read(|s| s.remote_api.port);
+    // Six digits so it can also be typed in by hand
+    let code = format!("{:06}", uuid::Uuid::new_v4().as_u128() % 1_000_000);
+    let expires_at = crate::now_secs() + PAIRING_TTL_SECS;
+    *state.pending.lock().unwrap_or_else(|e| e.into_inner()) = Some(PendingPairing {
+        code: code.clone(),
+        expires_at,
+        failed_attempts: 0,
+    });
+    let address = lan_address().to_string();
+    Ok(PairingOffer {
+        qr_payload: format!("tvx://pair?host={}&port={}&code={}", address, port, code),
+        code,
+        address,
+        port,
+        expires_at,
+    })
+}
+
+#[tauri::command]
+pub fn cancel_pairing(state: State<'_, PairingState>) {
+    *state.pending.lock().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+/// Paired devices without their tokens.
+#[tauri::command]
+pub fn list_paired_devices(devices: State<'_, PairedDeviceStore>) -> Vec<PairedDevice> {
+    let seen = last_seen();
+    devices.read(|list| {
+        list.iter()
+            .cloned()
+            .map(|mut d| {
+                d.token.clear();
+                d.last_seen = seen.get(&d.id).copied().max(d.last_seen);
+                d
+            })
+            .collect()
+    })
+}
+
+#[tauri::command]
+pub fn revoke_paired_device(
+    devices: State<'_, PairedDeviceStore>,
+    device_id: String,
+) -> Result<(), String> {
+    devices.update(|list| list.retain(|d| d.id != device_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn offering(code: &str, expires_at: u64) -> PairingState {
+        let state = PairingState::default();
+        *state.pending.lock().unwrap() = Some(PendingPairing {
+            code: code.to_string(),
+            expires_at,
+            failed_attempts: 0,
+        });
+        state
+    }
+
+    #[test]
+    fn codes_are_single_use() {
+        let state = offering("123456", 1000);
+        assert!(redeem(&state, "123456", 10).is_ok());
+        assert!(redeem(&state, "123456", 10).is_err());
+    }
+
+    #[test]
+    fn expired_codes_are_refused() {
+        let state = offering("123456", 1000);
+        assert!(redeem(&state, "123456", 1000).is_err());
+    }
+
+    #[test]
+    fn wrong_codes_close_pairing_at_the_limit() {
+        let state = offering("123456", 1000);
+        for _ in 1..MAX_PAIRING_ATTEMPTS {
+            assert!(redeem(&state, "000000", 10).is_err());
+        }
+        assert!(redeem(&state, "123456", 10).is_ok());
+
+        let state = offering("123456", 1000);
+        for _ in 0..MAX_PAIRING_ATTEMPTS {
+            assert!(redeem(&state, "000000", 10).is_err());
+        }
+        assert!(redeem(&state, "123456", 10).is_err());
+    }
+
+    #[test]
+    fn tokens_must_match_exactly() {
+        assert!(tokens_match("abc", "abc"));
+        assert!(!tokens_match("abd", "abc"));
+        assert!(!tokens_match("abc", "abcd"));
+        assert!(!tokens_match("", "abc"));
+    }
+}
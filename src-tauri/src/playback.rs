@@ -0,0 +1,170 @@
+//! Choosing how to play a stream from what ffprobe finds and what this machine can do: in the
+//! webview as is, remuxed to HLS (streams copied), transcoded, or in VLC when transcoding
+//! would not keep up (4K the machine can't decode smoothly) or ffmpeg can't read the stream
+//! at all. `open_video_window` applies the choice, and every opened window reports the path
+//! it plays through as `playback-path`.
+
+use serde::Serialize;
+use tauri::{Emitter, Manager};
+
+use crate::probe::ProbeResult;
+use crate::settings::SettingsStore;
+use crate::tracks::TrackSelection;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PlaybackPath {
+    /// The webview plays the stream itself.
+    Direct,
+    /// ffmpeg copies the streams into HLS for the webview.
+    Remux,
+    /// ffmpeg re-encodes video or audio the webview can't decode.
+    Transcode,
+    /// VLC plays the stream in its own window.
+    External,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaybackDecision {
+    pub path: PlaybackPath,
+    /// Why, for the playback info panel.
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PlaybackPathEvent<'a> {
+    /// Video window playing the stream; `None` for VLC.
+    label: Option<&'a str>,
+    url: &'a str,
+    #[serde(flatten)]
+    decision: &'a PlaybackDecision,
+}
+
+/// The path for ffmpeg output arguments from `transcode::plan` (`None`: plays directly).
+pub(crate) fn path_of(output: Option<&[String]>) -> PlaybackPath {
+    let Some(output) = output else {
+        return PlaybackPath::Direct;
+    };
+    let encodes = output
+        .windows(2)
+        .any(|w| matches!(w[0].as_str(), "-c:v" | "-c:a" | "-c") && w[1] != "copy");
+    if encodes {
+        PlaybackPath::Transcode
+    } else {
+        PlaybackPath::Remux
+    }
+}
+
+/// Why `probed` takes `path`, for the playback info panel.
+pub(crate) fn explain(path: PlaybackPath, probed: &ProbeResult) -> String {
+    let video = probed
+        .video()
+        .map_or("audio-only".to_string(), |v| v.codec.clone());
+    match path {
+        PlaybackPath::Direct => format!("{} in {} plays in the webview", video, probed.format),
+        PlaybackPath::Remux => format!(
+            "The webview doesn't play {} streams; they are copied into HLS",
+            probed.format
+        ),
+        PlaybackPath::Transcode => format!(
+            "{} in {} needs re-encoding for the webview",
+            video, probed.format
+        ),
+        PlaybackPath::External => format!(
+            "Transcoding {} at this resolution would not keep up; VLC plays it instead",
+            video
+        ),
+    }
+}
+
+/// Reports the path a window (or VLC) plays `url` through.
+pub(crate) fn report(
+    app: &tauri::AppHandle,
+    label: Option<&str>,
+    url: &str,
+    decision: &PlaybackDecision,
+) {
+    tracing::info!(label = ?label, path = ?decision.path, "{}", decision.reason);
+    let _ = app.emit(
+        "playback-path",
+        PlaybackPathEvent {
+            label,
+            url,
+            decision,
+        },
+    );
+}
+
+/// Whether `url` is an HLS playlist or MP4 file over HTTP, which the webview plays without
+/// a probe to decide.
+fn is_web_stream(url: &str) -> bool {
+    reqwest::Url::parse(url).is_ok_and(|u| {
+        let path = u.path().to_ascii_lowercase();
+        matches!(u.scheme(), "http" | "https")
+            && (path.ends_with(".m3u8") || path.ends_with(".mp4"))
+    })
+}
+
+/// Decides how to play `url`, with what probing it found, if it was probed.
+pub(crate) async fn choose(
+    app: &tauri::AppHandle,
+    url: &str,
+) -> (PlaybackDecision, Option<ProbeResult>) {
+    // Probing can take many seconds on a slow stream
+    if is_web_stream(url) {
+        let decision = PlaybackDecision {
+            path: PlaybackPath::Direct,
+            reason: "HLS and MP4 streams play in the webview".to_string(),
+        };
+        return (decision, None);
+    }
+    let vlc = crate::vlc::find_vlc().is_ok();
+    let probed = match crate::probe::probe(url.to_string()).await {
+        Ok(probed) => probed,
+        Err(e) => {
+            let unplayable = crate::ingest::is_ingest(url)
+                || reqwest::Url::parse(url).is_ok_and(|u| u.path().ends_with(".mpd"));
+            let decision = if unplayable && vlc {
+                PlaybackDecision {
+                    path: PlaybackPath::External,
+                    reason: format!(
+                        "ffmpeg could not read the stream ({}); VLC plays it instead",
+                        e
+                    ),
+                }
+            } else {
+                PlaybackDecision {
+                    path: PlaybackPath::Direct,
+                    reason: format!(
+                        "The stream could not be probed ({}); the webview tries it as is",
+                        e
+                    ),
+                }
+            };
+            return (decision, None);
+        }
+    };
+    let hw = crate::hwaccel::caps().await.unwrap_or_default();
+    let normalize = app.state::<SettingsStore>().read(|s| s.normalize_audio);
+    let output = crate::transcode::plan(&probed, &hw, &TrackSelection::default(), normalize);
+    let mut path = path_of(output.as_deref());
+    if path == PlaybackPath::Transcode
+        && vlc
+        && crate::hwaccel::playback_warning(&probed, &hw).is_some()
+    {
+        path = PlaybackPath::External;
+    }
+    let decision = PlaybackDecision {
+        path,
+        reason: explain(path, &probed),
+    };
+    (decision, Some(probed))
+}
+
+/// How `url` would be played, without playing it.
+#[tauri::command]
+pub async fn choose_playback_path(app: tauri::AppHandle, url: String) -> PlaybackDecision {
+    choose(&app, &url).await.0
+}
@@ -0,0 +1,226 @@
+//! Incremental M3U playlist import. The playlist is read and parsed in chunks, reporting
+//! `import-progress` as it goes, and can be cancelled by import id. The channel index only
+//! changes once the whole playlist has been read, so a cancelled or failed import leaves the
+//! previous list in place.
+
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+use tauri::{Emitter, State};
+
+use crate::cache::CacheKind;
+use crate::catalog::{self, CatalogChannel, CatalogInfo};
+use crate::m3u::{M3uEntry, Parser};
+use crate::offline;
+use crate::servers::{self, ServerConfig, ServerKind, ServerStore};
+
+/// Entries parsed between progress events and cancellation checks.
+const BATCH: usize = 1000;
+const READ_CHUNK: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ImportProgress {
+    import_id: String,
+    server_id: String,
+    processed: usize,
+    bytes_read: u64,
+    total_bytes: Option<u64>,
+    /// 0-100, when the playlist size is known.
+    percent: Option<f64>,
+    done: bool,
+}
+
+static IMPORTS: Mutex<BTreeMap<String, Arc<AtomicBool>>> = Mutex::new(BTreeMap::new());
+
+fn imports() -> std::sync::MutexGuard<'static, BTreeMap<String, Arc<AtomicBool>>> {
+    IMPORTS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Registers a cancellation token for the import, removed again on drop.
+struct Token {
+    id: String,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Token {
+    fn new(id: &str) -> Self {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        imports().insert(id.to_string(), cancelled.clone());
+        Self {
+            id: id.to_string(),
+            cancelled,
+        }
+    }
+
+    fn check(&self) -> Result<(), String> {
+        if self.cancelled.load(Ordering::Relaxed) {
+            Err("Import cancelled".to_string())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Drop for Token {
+    fn drop(&mut self) {
+        imports().remove(&self.id);
+    }
+}
+
+/// Splits incoming bytes into lines for the parser and reports progress every `BATCH` entries.
+struct Importer<'a> {
+    app: &'a tauri::AppHandle,
+    token: Token,
+    server_id: String,
+    parser: Parser,
+    partial: Vec<u8>,
+    entries: Vec<M3uEntry>,
+    bytes_read: u64,
+    total_bytes: Option<u64>,
+}
+
+impl Importer<'_> {
+    fn feed(&mut self, chunk: &[u8]) -> Result<(), String> {
+        self.bytes_read += chunk.len() as u64;
+        self.partial.extend_from_slice(chunk);
+        let Some(end) = self.partial.iter().rposition(|&b| b == b'\n') else {
+            return Ok(());
+        };
+        let complete: Vec<u8> = self.partial.drain(..=end).collect();
+        for line in String::from_utf8_lossy(&complete).lines() {
+            if let Some(entry) = self.parser.line(line) {
+                self.entries.push(entry);
+                if self.entries.len().is_multiple_of(BATCH) {
+                    self.token.check()?;
+                    self.progress(false);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<Vec<M3uEntry>, String> {
+        let rest = std::mem::take(&mut self.partial);
+        if let Some(entry) = self.parser.line(&String::from_utf8_lossy(&rest)) {
+            self.entries.push(entry);
+        }
+        self.token.check()?;
+        self.progress(true);
+        Ok(std::mem::take(&mut self.entries))
+    }
+
+    fn progress(&self, done: bool) {
+        let percent = self
+            .total_bytes
+            .filter(|&total| total > 0)
+            .map(|total| (self.bytes_read as f64 / total as f64 * 100.0).min(100.0));
+        let _ = self.app.emit(
+            "import-progress",
+            ImportProgress {
+                import_id: self.token.id.clone(),
+                server_id: self.server_id.clone(),
+                processed: self.entries.len(),
+                bytes_read: self.bytes_read,
+                total_bytes: self.total_bytes,
+                percent: if done { Some(100.0) } else { percent },
+                done,
+            },
+        );
+    }
+}
+
+/// Reads and parses the server's playlist. `url` is fetched over HTTP(S) with the server's
+/// network settings, anything else is read as a local path.
+pub async fn import(
+    app: &tauri::AppHandle,
+    server: &ServerConfig,
+    import_id: &str,
+) -> Result<Vec<M3uEntry>, String> {
+    let mut importer = Importer {
+        app,
+        token: Token::new(import_id),
+        server_id: server.id.clone(),
+        parser: Parser::default(),
+        partial: Vec::new(),
+        entries: Vec::new(),
+        bytes_read: 0,
+        total_bytes: None,
+    };
+    let source = server.url.trim();
+    if source.starts_with("http://") || source.starts_with("https://") {
+        let _permit = crate::http::queue(Some(server)).await;
+        let mut resp = crate::http::send(crate::http::stream_client(Some(server))?.get(source))
+            .await
+            .map_err(|e| format!("Failed to download playlist: {}", e))?;
+        if !resp.status().is_success() {
+            return Err(format!("Playlist download returned HTTP {}", resp.status()));
+        }
+        importer.total_bytes = resp.content_length();
+        while let Some(chunk) = resp
+            .chunk()
+            .await
+            .map_err(|e| format!("Failed to download playlist: {}", e))?
+        {
+            importer.feed(&chunk)?;
+        }
+    } else {
+        let path = source.strip_prefix("file://").unwrap_or(source);
+        let mut file =
+            std::fs::File::open(path).map_err(|e| format!("Failed to open playlist: {}", e))?;
+        importer.total_bytes = file.metadata().ok().map(|m| m.len());
+        let mut buf = vec![0; READ_CHUNK];
+        loop {
+            let n = file
+                .read(&mut buf)
+                .map_err(|e| format!("Failed to read playlist: {}", e))?;
+            if n == 0 {
+                break;
+            }
+            importer.feed(&buf[..n])?;
+        }
+    }
+    importer.finish()
+}
+
+/// Imports an M3U server's playlist into the channel index, emitting `import-progress`
+/// events tagged with `importId`. Errors with "Import cancelled" after `cancel_import`.
+#[tauri::command]
+pub async fn import_playlist(
+    app: tauri::AppHandle,
+    store: State<'_, ServerStore>,
+    server_id: String,
+    import_id: String,
+) -> Result<CatalogInfo, String> {
+    let server = servers::get(&store, &server_id)?;
+    if server.kind != ServerKind::M3u {
+        return Err(format!("{} is not an M3U playlist", server.name));
+    }
+    let channels: Vec<CatalogChannel> = import(&app, &server, &import_id)
+        .await?
+        .into_iter()
+        .map(CatalogChannel::from)
+        .collect();
+    let fetched_at = offline::save(&app, CacheKind::Catalogs, &server_id, "catalog", &channels);
+    Ok(catalog::replace(
+        &app,
+        &server_id,
+        offline::Cached {
+            data: channels,
+            stale: false,
+            fetched_at,
+            error: None,
+        },
+    ))
+}
+
+/// Stops a running import at its next batch. Unknown ids (already finished) are ignored.
+#[tauri::command]
+pub fn cancel_import(import_id: String) {
+    if let Some(cancelled) = imports().get(&import_id) {
+        cancelled.store(true, Ordering::Relaxed);
+    }
+}
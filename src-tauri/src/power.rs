@@ -0,0 +1,163 @@
+//! System suspend and resume. On Linux, logind's `PrepareForSleep` signal (watched with
+//! `dbus-monitor`) announces both, and a delay inhibitor gives recordings a few seconds to
+//! close their files first. Elsewhere, and as a fallback, a jump of the wall clock past a
+//! sleeping thread reveals that the machine was asleep. After resume everything cut off is
+//! reconnected (see `network::reconnect`).
+
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant, SystemTime};
+
+use tauri::Emitter;
+use tokio::sync::watch;
+
+/// How long suspending waits for holders (recordings) to finish writing.
+const SUSPEND_GRACE: Duration = Duration::from_secs(5);
+const CLOCK_CHECK: Duration = Duration::from_secs(10);
+/// The wall clock running this far ahead of a clock check means the machine slept.
+const CLOCK_JUMP: Duration = Duration::from_secs(30);
+const LOGIND_RULE: &str =
+    "type='signal',interface='org.freedesktop.login1.Manager',member='PrepareForSleep'";
+
+fn state() -> &'static watch::Sender<bool> {
+    static STATE: OnceLock<watch::Sender<bool>> = OnceLock::new();
+    STATE.get_or_init(|| watch::channel(false).0)
+}
+
+/// Work that must finish before the machine sleeps.
+static HOLDS: AtomicUsize = AtomicUsize::new(0);
+/// Unix seconds of the last reconnect after a resume, so logind and the clock check don't
+/// both reconnect.
+static LAST_RESUME: AtomicU64 = AtomicU64::new(0);
+
+/// Keeps suspend waiting (up to a few seconds) while alive.
+pub struct Hold(());
+
+impl Drop for Hold {
+    fn drop(&mut self) {
+        HOLDS.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+pub fn hold() -> Hold {
+    HOLDS.fetch_add(1, Ordering::SeqCst);
+    Hold(())
+}
+
+/// Whether the system is about to sleep or asleep.
+pub fn suspended() -> bool {
+    *state().borrow()
+}
+
+/// Returns once the system is awake.
+pub async fn wait_resumed() {
+    let mut rx = state().subscribe();
+    while *rx.borrow_and_update() {
+        if rx.changed().await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Flags the suspend and waits for holders to let go. Blocking.
+fn on_suspend(app: &tauri::AppHandle) {
+    tracing::info!("System is going to sleep");
+    state().send_replace(true);
+    let _ = app.emit("system-suspend", ());
+    let deadline = Instant::now() + SUSPEND_GRACE;
+    while HOLDS.load(Ordering::SeqCst) > 0 && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(100));
+    }
+}
+
+/// Reconnects everything that was cut off while the machine slept.
+async fn on_resume(app: tauri::AppHandle) {
+    tracing::info!("System resumed");
+    state().send_replace(false);
+    let _ = app.emit("system-resume", ());
+    // Several sources report the same wake-up; reconnecting once is enough
+    let now = crate::now_secs();
+    if now.saturating_sub(LAST_RESUME.swap(now, Ordering::SeqCst)) < CLOCK_JUMP.as_secs() {
+        return;
+    }
+    crate::network::reconnect(&app).await;
+}
+
+fn spawn_resume(app: &tauri::AppHandle) {
+    tauri::async_runtime::spawn(on_resume(app.clone()));
+}
+
+/// Holds a logind delay lock so there is time to act on `PrepareForSleep`.
+fn inhibit() -> Option<Child> {
+    Command::new("systemd-inhibit")
+        .args([
+            "--what=sleep",
+            "--mode=delay",
+            "--who=TvX",
+            "--why=Closing recordings before sleep",
+            "sleep",
+            "infinity",
+        ])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()
+}
+
+/// Follows logind's sleep signals until `dbus-monitor` exits.
+fn watch_logind(app: &tauri::AppHandle) {
+    let Ok(mut monitor) = Command::new("dbus-monitor")
+        .args(["--system", LOGIND_RULE])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+    else {
+        tracing::debug!("dbus-monitor unavailable; detecting resume from the clock only");
+        return;
+    };
+    let Some(stdout) = monitor.stdout.take() else {
+        return;
+    };
+    let mut inhibitor = inhibit();
+    for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+        match line.trim() {
+            "boolean true" => {
+                on_suspend(app);
+                if let Some(mut child) = inhibitor.take() {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                }
+            }
+            "boolean false" => {
+                inhibitor = inhibit();
+                spawn_resume(app);
+            }
+            _ => {}
+        }
+    }
+    if let Some(mut child) = inhibitor {
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+}
+
+/// Starts watching for suspend and resume.
+pub fn start(app: &tauri::AppHandle) {
+    if cfg!(target_os = "linux") {
+        let app = app.clone();
+        std::thread::spawn(move || watch_logind(&app));
+    }
+    let app = app.clone();
+    std::thread::spawn(move || loop {
+        let before = SystemTime::now();
+        std::thread::sleep(CLOCK_CHECK);
+        let slept = before.elapsed().unwrap_or_default();
+        if slept > CLOCK_CHECK + CLOCK_JUMP {
+            spawn_resume(&app);
+        }
+    });
+}
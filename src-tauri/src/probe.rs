@@ -0,0 +1,198 @@
+//! Stream inspection with ffprobe: container, duration and per-stream codecs, used to decide
+//! whether the webview can play a URL directly. Results are kept for 30 seconds, so deciding
+//! how to play a stream and then playing it probes it once.
+
+use std::collections::BTreeMap;
+use std::process::Command;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize)]
+struct RawProbe {
+    #[serde(default)]
+    streams: Vec<RawStream>,
+    format: Option<RawFormat>,
+}
+
+#[derive(Deserialize)]
+struct RawFormat {
+    #[serde(default)]
+    format_name: String,
+    duration: Option<String>,
+    bit_rate: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RawStream {
+    index: u32,
+    #[serde(default)]
+    codec_type: String,
+    #[serde(default)]
+    codec_name: String,
+    profile: Option<String>,
+    width: Option<u32>,
+    height: Option<u32>,
+    channels: Option<u32>,
+    field_order: Option<String>,
+    #[serde(default)]
+    tags: RawTags,
+    #[serde(default)]
+    disposition: RawDisposition,
+}
+
+#[derive(Deserialize, Default)]
+struct RawTags {
+    language: Option<String>,
+    title: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+struct RawDisposition {
+    #[serde(default)]
+    default: u8,
+    #[serde(default)]
+    forced: u8,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProbeStream {
+    /// Stream index within the container (ffmpeg `-map 0:N`).
+    pub index: u32,
+    /// `video`, `audio`, `subtitle`, `data` or `attachment`.
+    pub kind: String,
+    pub codec: String,
+    pub profile: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub channels: Option<u32>,
+    pub interlaced: bool,
+    pub language: Option<String>,
+    pub title: Option<String>,
+    pub default: bool,
+    pub forced: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProbeResult {
+    /// ffmpeg demuxer names, e.g. `mpegts` or `mov,mp4,m4a,3gp,3g2,mj2`.
+    pub format: String,
+    /// Missing for live streams.
+    pub duration_secs: Option<f64>,
+    pub bit_rate: Option<u64>,
+    pub streams: Vec<ProbeStream>,
+}
+
+impl ProbeResult {
+    pub fn first(&self, kind: &str) -> Option<&ProbeStream> {
+        self.streams.iter().find(|s| s.kind == kind)
+    }
+
+    /// Whether the stream is an MPEG-DASH manifest.
+    pub fn is_dash(&self) -> bool {
+        self.format.split(',').any(|f| f == "dash")
+    }
+
+    /// The video stream to play. DASH manifests list one per representation; the tallest wins.
+    pub fn video(&self) -> Option<&ProbeStream> {
+        if !self.is_dash() {
+            return self.first("video");
+        }
+        self.streams
+            .iter()
+            .filter(|s| s.kind == "video")
+            .max_by_key(|s| s.height)
+    }
+}
+
+pub(crate) fn run_ffprobe(url: &str) -> Result<ProbeResult, String> {
+    let output = Command::new(crate::ffmpeg::ffprobe_path()?)
+        .args([
+            "-v",
+            "error",
+            "-print_format",
+            "json",
+            "-show_format",
+            "-show_streams",
+        ])
+        // Keep live streams from stalling the probe: 10s I/O timeout, ~5s of analysis
+        .args(["-rw_timeout", "10000000", "-analyzeduration", "5000000"])
+        .args(crate::ingest::input_options(url))
+        .arg(crate::ingest::input_url(url))
+        .output()
+        .map_err(|e| format!("Failed to run ffprobe: {}", e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(match crate::failure::classify_ffmpeg(&stderr) {
+            Some(failure) => failure.message,
+            None => format!("ffprobe failed: {}", stderr.trim()),
+        });
+    }
+    let raw: RawProbe = serde_json::from_slice(&output.stdout)
+        .map_err(|e| format!("Invalid ffprobe output: {}", e))?;
+    let format = raw.format;
+    Ok(ProbeResult {
+        format: format
+            .as_ref()
+            .map(|f| f.format_name.clone())
+            .unwrap_or_default(),
+        duration_secs: format
+            .as_ref()
+            .and_then(|f| f.duration.as_deref()?.parse().ok())
+            .filter(|d: &f64| *d > 0.0),
+        bit_rate: format.and_then(|f| f.bit_rate?.parse().ok()),
+        streams: raw
+            .streams
+            .into_iter()
+            .map(|s| ProbeStream {
+                index: s.index,
+                kind: s.codec_type,
+                codec: s.codec_name,
+                profile: s.profile,
+                width: s.width,
+                height: s.height,
+                channels: s.channels,
+                interlaced: s
+                    .field_order
+                    .is_some_and(|f| f != "progressive" && f != "unknown"),
+                language: s.tags.language,
+                title: s.tags.title,
+                default: s.disposition.default != 0,
+                forced: s.disposition.forced != 0,
+            })
+            .collect(),
+    })
+}
+
+const RECENT_TTL: Duration = Duration::from_secs(30);
+
+static RECENT: Mutex<BTreeMap<String, (Instant, ProbeResult)>> = Mutex::new(BTreeMap::new());
+
+fn recent() -> std::sync::MutexGuard<'static, BTreeMap<String, (Instant, ProbeResult)>> {
+    RECENT.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Probes `url` off the async runtime, reusing a result from the last few seconds.
+pub async fn probe(url: String) -> Result<ProbeResult, String> {
+    if let Some((at, probed)) = recent().get(&url) {
+        if at.elapsed() < RECENT_TTL {
+            return Ok(probed.clone());
+        }
+    }
+    let key = url.clone();
+    let probed = tauri::async_runtime::spawn_blocking(move || run_ffprobe(&url))
+        .await
+        .map_err(|e| e.to_string())??;
+    let mut recent = recent();
+    recent.retain(|_, (at, _)| at.elapsed() < RECENT_TTL);
+    recent.insert(key, (Instant::now(), probed.clone()));
+    Ok(probed)
+}
+
+#[tauri::command]
+pub async fn probe_stream(url: String) -> Result<ProbeResult, String> {
+    probe(url).await
+}
@@ -0,0 +1,285 @@
+//! Playback positions for resume, reported by the video window and by tracked external players.
+//! Every update is persisted and broadcast as a `playback-progress` event. An item becomes
+//! watched once playback passes the configurable [`WatchedRules`], or when marked by hand;
+//! marks on Emby items are sent to the server as well. `get_continue_watching` combines
+//! started items with the watch-later list.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, Manager, State};
+
+use crate::offline::Submitted;
+use crate::recordings::RecordingStore;
+use crate::servers::{ServerKind, ServerStore};
+use crate::settings::SettingsStore;
+use crate::store::JsonStore;
+use crate::watchlater::{WatchLaterItem, WatchLaterStore};
+
+/// When playback counts as having watched an item.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct WatchedRules {
+    /// Share of the runtime, in percent, playback must reach.
+    pub threshold_percent: f64,
+    /// Seconds actually played before an item can count as watched, so skipping to the end
+    /// doesn't.
+    pub min_watch_secs: u64,
+    /// Whether end credits are part of the runtime the threshold applies to. When they aren't,
+    /// reaching the credits (where the player knows them) is enough.
+    pub count_credits: bool,
+}
+
+impl Default for WatchedRules {
+    fn default() -> Self {
+        Self {
+            threshold_percent: 90.0,
+            min_watch_secs: 60,
+            count_credits: true,
+        }
+    }
+}
+
+impl WatchedRules {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.threshold_percent > 0.0 && self.threshold_percent <= 100.0) {
+            return Err("The watched threshold must be between 1 and 100%".to_string());
+        }
+        Ok(())
+    }
+
+    fn reached(
+        &self,
+        position_secs: f64,
+        duration_secs: Option<f64>,
+        credits_start_secs: Option<f64>,
+        played_secs: u64,
+    ) -> bool {
+        let Some(duration) = duration_secs else {
+            return false;
+        };
+        let runtime = match credits_start_secs {
+            Some(credits) if !self.count_credits && credits > 0.0 => credits.min(duration),
+            _ => duration,
+        };
+        played_secs >= self.min_watch_secs
+            && position_secs >= runtime * self.threshold_percent / 100.0
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProgressEntry {
+    /// Caller-chosen key identifying the item, e.g. `{serverId}:movie:{id}`.
+    pub content_key: String,
+    pub position_secs: f64,
+    pub duration_secs: Option<f64>,
+    /// Where the end credits start, when the player knows.
+    #[serde(default)]
+    pub credits_start_secs: Option<f64>,
+    /// Unix seconds of the last update.
+    pub updated_at: u64,
+    /// Set once the watched rules are met or by `mark_watched`; rewatching doesn't clear it.
+    #[serde(default)]
+    pub watched: bool,
+}
+
+pub type ProgressStore = JsonStore<HashMap<String, ProgressEntry>>;
+
+pub fn open(app: &tauri::AppHandle) -> ProgressStore {
+    JsonStore::open(app, "progress.json")
+}
+
+/// Saves a position, applying the watched rules, and emits `playback-progress`.
+pub fn record(
+    app: &tauri::AppHandle,
+    store: &ProgressStore,
+    content_key: &str,
+    position_secs: f64,
+    duration_secs: Option<f64>,
+    credits_start_secs: Option<f64>,
+) -> Result<ProgressEntry, String> {
+    let previous = store.read(|entries| entries.get(content_key).cloned());
+    let mut entry = ProgressEntry {
+        content_key: content_key.to_string(),
+        position_secs: position_secs.max(0.0),
+        duration_secs: duration_secs.filter(|d| *d > 0.0),
+        credits_start_secs: credits_start_secs
+            .or_else(|| previous.as_ref().and_then(|p| p.credits_start_secs)),
+        updated_at: crate::now_secs(),
+        watched: previous.is_some_and(|p| p.watched),
+    };
+    if !entry.watched {
+        let rules = app.state::<SettingsStore>().read(|s| s.watched.clone());
+        entry.watched = rules.reached(
+            entry.position_secs,
+            entry.duration_secs,
+            entry.credits_start_secs,
+            crate::history::played_secs(app, content_key),
+        );
+        if entry.watched {
+            crate::watchlater::on_watched(app, content_key);
+        }
+    }
+    store.update(|entries| entries.insert(content_key.to_string(), entry.clone()))?;
+    let _ = app.emit("playback-progress", &entry);
+    crate::autoplay::on_progress(
+        app,
+        content_key,
+        entry.position_secs,
+        entry.duration_secs,
+        entry.credits_start_secs,
+    );
+    crate::queue::on_progress(app, content_key, entry.position_secs, entry.duration_secs);
+    Ok(entry)
+}
+
+#[tauri::command]
+pub fn report_progress(
+    app: tauri::AppHandle,
+    store: State<'_, ProgressStore>,
+    content_key: String,
+    position_secs: f64,
+    duration_secs: Option<f64>,
+    credits_start_secs: Option<f64>,
+) -> Result<ProgressEntry, String> {
+    record(
+        &app,
+        &store,
+        &content_key,
+        position_secs,
+        duration_secs,
+        credits_start_secs,
+    )
+}
+
+#[tauri::command]
+pub fn get_progress(store: State<'_, ProgressStore>, content_key: String) -> Option<ProgressEntry> {
+    store.read(|entries| entries.get(&content_key).cloned())
+}
+
+/// All saved positions, most recently updated first.
+#[tauri::command]
+pub fn list_progress(store: State<'_, ProgressStore>) -> Vec<ProgressEntry> {
+    let mut entries: Vec<ProgressEntry> = store.read(|entries| entries.values().cloned().collect());
+    entries.sort_by_key(|e| std::cmp::Reverse(e.updated_at));
+    entries
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContinueItem {
+    pub content_key: String,
+    /// Saved position, for items already started.
+    pub progress: Option<ProgressEntry>,
+    /// Details, for items on the watch-later list.
+    pub watch_later: Option<WatchLaterItem>,
+}
+
+/// Items to pick up: started but unwatched ones, most recent first, then the rest of the
+/// watch-later list in the order it was added.
+#[tauri::command]
+pub fn get_continue_watching(
+    store: State<'_, ProgressStore>,
+    watch_later: State<'_, WatchLaterStore>,
+    limit: Option<usize>,
+) -> Vec<ContinueItem> {
+    let mut later = crate::watchlater::items(&watch_later, &store);
+    let mut started: Vec<ProgressEntry> = store.read(|entries| {
+        entries
+            .values()
+            .filter(|e| !e.watched && e.position_secs > 0.0)
+            .cloned()
+            .collect()
+    });
+    started.sort_by_key(|e| std::cmp::Reverse(e.updated_at));
+    let mut items: Vec<ContinueItem> = started
+        .into_iter()
+        .map(|entry| {
+            let listed = later
+                .iter()
+                .position(|i| i.content_key == entry.content_key);
+            ContinueItem {
+                content_key: entry.content_key.clone(),
+                progress: Some(entry),
+                watch_later: listed.map(|i| later.remove(i)),
+            }
+        })
+        .collect();
+    items.extend(later.into_iter().map(|item| ContinueItem {
+        content_key: item.content_key.clone(),
+        progress: None,
+        watch_later: Some(item),
+    }));
+    items.truncate(limit.unwrap_or(usize::MAX));
+    items
+}
+
+/// Applies a manual mark locally and, for Emby items, on the server. Returns how the server
+/// change went, `None` when the item has no upstream watched state.
+async fn set_watched(
+    app: &tauri::AppHandle,
+    content_key: &str,
+    watched: bool,
+) -> Result<Option<Submitted>, String> {
+    let store = app.state::<ProgressStore>();
+    if watched {
+        let entry = store.update(|entries| {
+            let entry = entries
+                .entry(content_key.to_string())
+                .or_insert_with(|| ProgressEntry {
+                    content_key: content_key.to_string(),
+                    position_secs: 0.0,
+                    duration_secs: None,
+                    credits_start_secs: None,
+                    updated_at: 0,
+                    watched: false,
+                });
+            entry.watched = true;
+            entry.updated_at = crate::now_secs();
+            entry.clone()
+        })?;
+        let _ = app.emit("playback-progress", &entry);
+        crate::watchlater::on_watched(app, content_key);
+    } else {
+        // The position goes too, or a mostly played item would count as watched again
+        store.update(|entries| entries.remove(content_key))?;
+    }
+
+    if let Some(id) = content_key.strip_prefix("recording:") {
+        crate::recordings::set_watched(&app.state::<RecordingStore>(), id, watched)?;
+        return Ok(None);
+    }
+    // `{serverId}:{kind}:{id}`
+    let mut parts = content_key.splitn(3, ':');
+    let (Some(server_id), Some(_), Some(item_id)) = (parts.next(), parts.next(), parts.next())
+    else {
+        return Ok(None);
+    };
+    let Ok(server) = crate::servers::get(&app.state::<ServerStore>(), server_id) else {
+        return Ok(None);
+    };
+    match server.kind {
+        ServerKind::Emby => crate::emby::set_played(app, &server.id, item_id, watched)
+            .await
+            .map(Some),
+        // Xtream, M3U and the tuners have no per-user watched state
+        _ => Ok(None),
+    }
+}
+
+#[tauri::command]
+pub async fn mark_watched(
+    app: tauri::AppHandle,
+    content_key: String,
+) -> Result<Option<Submitted>, String> {
+    set_watched(&app, &content_key, true).await
+}
+
+#[tauri::command]
+pub async fn mark_unwatched(
+    app: tauri::AppHandle,
+    content_key: String,
+) -> Result<Option<Submitted>, String> {
+    set_watched(&app, &content_key, false).await
+}
@@ -0,0 +1,914 @@
+//! Local HTTP server on 127.0.0.1 serving ffmpeg outputs (HLS remuxes/transcodes) to the
+//! webview, which can't consume RTSP, MPEG-TS over UDP, or most non-browser codecs directly.
+//!
+//! Each ffmpeg process is a session writing into its own directory under the app cache dir;
+//! the server maps `/s/{session}/{file}` onto it. Relays (`/r/{relay}/{path}`) forward to an
+//! upstream URL through the backend HTTP client, for streams that must go through an outbound
+//! proxy; relative paths resolve against the upstream URL. HLS playlists are rewritten on the
+//! way through so every URI in them, absolute or not, comes back to the relay. Relays are
+//! metered, and windows owning one get `stream-stats` every second (see `streamstats`). The last
+//! upstream error a relay saw is kept, classified, for `failure::diagnose_stream`. Radio relays
+//! ask for ICY metadata, strip it from the audio and emit `now-playing` when the song changes.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tauri::{Emitter, Manager};
+
+use crate::failure::{FailureCode, StreamFailure};
+use crate::icy::{IcyStream, NowPlaying};
+use crate::resolve::Resolved;
+use crate::servers::ServerConfig;
+use crate::streamstats::Meter;
+
+/// How long a request for a playlist waits for ffmpeg to write it.
+const PLAYLIST_WAIT: Duration = Duration::from_secs(15);
+const STATS_INTERVAL: Duration = Duration::from_secs(1);
+/// Length of the HLS segments ffmpeg writes.
+const SEGMENT_SECS: u64 = 2;
+/// Relay paths starting with this carry a percent-encoded absolute upstream URL, written by
+/// `rewrite_playlist`.
+const ABSOLUTE_PATH: &str = "~/";
+/// File a session writes its `start_hls` subtitle track to.
+const SUBTITLE_FILE: &str = "subtitles.vtt";
+/// Longest playlist rewritten; anything past it is cut off.
+const MAX_PLAYLIST: usize = 4 << 20;
+
+struct Session {
+    dir: PathBuf,
+    child: Child,
+    /// Label of the video window using this session; stopped when that window closes.
+    owner: Option<String>,
+    /// Seconds of live stream kept for pausing and rewinding, for timeshift sessions.
+    timeshift_secs: Option<u64>,
+}
+
+struct Relay {
+    /// Last URL fetched for this relay, after redirects; relative paths resolve against it.
+    upstream: reqwest::Url,
+    client: reqwest::Client,
+    owner: Option<String>,
+    /// Server the stream comes from, for quality telemetry.
+    server_id: Option<String>,
+    meter: Arc<Mutex<Meter>>,
+    /// Classified error of the last upstream response, if it failed.
+    failure: Option<StreamFailure>,
+    /// `Cookie` header the upstream needs, from resolving the stream link.
+    cookie: Option<String>,
+    /// Ask for ICY metadata (radio streams).
+    icy: bool,
+    now_playing: Option<NowPlaying>,
+    /// `now_playing` changed since it was last emitted.
+    now_playing_changed: bool,
+}
+
+type Relays = Arc<Mutex<HashMap<String, Relay>>>;
+
+pub struct ProxyState {
+    port: u16,
+    root: PathBuf,
+    sessions: Mutex<HashMap<String, Session>>,
+    relays: Relays,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct NowPlayingEvent<'a> {
+    /// Window playing the station.
+    label: &'a str,
+    #[serde(flatten)]
+    now_playing: &'a NowPlaying,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProxySession {
+    pub id: String,
+    pub url: String,
+}
+
+fn content_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("m3u8") => "application/vnd.apple.mpegurl",
+        Some("ts") => "video/mp2t",
+        Some("mp4") | Some("m4s") => "video/mp4",
+        Some("aac") => "audio/aac",
+        Some("vtt") => "text/vtt; charset=utf-8",
+        Some("png") => "image/png",
+        _ => "application/octet-stream",
+    }
+}
+
+fn respond(stream: &mut TcpStream, status: &str, content_type: &str, body: &[u8]) {
+    let head = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nAccess-Control-Allow-Origin: *\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    );
+    let _ = stream.write_all(head.as_bytes());
+    let _ = stream.write_all(body);
+}
+
+/// Resolves `/s/{session}/{file}` to a path under `root`, rejecting anything that could escape it.
+fn resolve(root: &Path, request_path: &str) -> Option<PathBuf> {
+    let path = request_path.split('?').next()?;
+    let mut parts = path.trim_start_matches('/').split('/');
+    if parts.next()? != "s" {
+        return None;
+    }
+    let session = parts.next()?;
+    let file = parts.next()?;
+    if parts.next().is_some() {
+        return None;
+    }
+    let safe = |s: &str| {
+        !s.is_empty()
+            && s.chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+            && !s.starts_with('.')
+    };
+    (safe(session) && safe(file)).then(|| root.join(session).join(file))
+}
+
+fn set_failure(relays: &Relays, id: &str, failure: Option<StreamFailure>) {
+    if let Some(relay) = relays.lock().unwrap_or_else(|e| e.into_inner()).get_mut(id) {
+        relay.failure = failure;
+    }
+}
+
+fn set_now_playing(relays: &Relays, id: &str, now_playing: NowPlaying) {
+    if let Some(relay) = relays.lock().unwrap_or_else(|e| e.into_inner()).get_mut(id) {
+        if relay.now_playing.as_ref() != Some(&now_playing) {
+            tracing::debug!(relay = id, title = %now_playing.title, "Now playing");
+            relay.now_playing = Some(now_playing);
+            relay.now_playing_changed = true;
+        }
+    }
+}
+
+/// Whether a response for `url` with `content_type` is an HLS playlist.
+fn is_playlist(url: &reqwest::Url, content_type: &str) -> bool {
+    content_type.contains("mpegurl") || url.path().to_ascii_lowercase().ends_with(".m3u8")
+}
+
+/// The relay path for `uri` found in a playlist fetched from `base`, or `None` to leave it
+/// alone (`data:` and `skd:` key URIs).
+fn relay_uri(uri: &str, base: &reqwest::Url, relay_path: &str) -> Option<String> {
+    let url = base.join(uri).ok()?;
+    matches!(url.scheme(), "http" | "https").then(|| {
+        format!(
+            "{}{}{}",
+            relay_path,
+            ABSOLUTE_PATH,
+            urlencoding::encode(url.as_str())
+        )
+    })
+}
+
+/// Points every URI in an HLS playlist fetched from `base` at the relay under `relay_path`
+/// (`/r/{relay}/`): segment and variant playlist lines, and the `URI` attributes of tags such
+/// as `EXT-X-KEY`, `EXT-X-MAP` and `EXT-X-MEDIA`.
+fn rewrite_playlist(body: &str, base: &reqwest::Url, relay_path: &str) -> String {
+    let mut out = String::with_capacity(body.len() * 2);
+    for line in body.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            out.push_str(line);
+        } else if trimmed.starts_with('#') {
+            let mut rest = line;
+            while let Some(start) = rest.find("URI=\"") {
+                let value = start + "URI=\"".len();
+                let Some(len) = rest[value..].find('"') else {
+                    break;
+                };
+                let uri = &rest[value..value + len];
+                out.push_str(&rest[..value]);
+                out.push_str(&relay_uri(uri, base, relay_path).unwrap_or_else(|| uri.to_string()));
+                rest = &rest[value + len..];
+            }
+            out.push_str(rest);
+        } else {
+            out.push_str(&relay_uri(trimmed, base, relay_path).unwrap_or_else(|| line.to_string()));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// The upstream URL a relay request's `path` stands for: an absolute one written by
+/// `rewrite_playlist`, else `path` relative to the relay's upstream.
+fn upstream_url(upstream: &reqwest::Url, path: &str) -> Option<reqwest::Url> {
+    match path.strip_prefix(ABSOLUTE_PATH) {
+        Some(encoded) => {
+            let url = reqwest::Url::parse(&urlencoding::decode(encoded).ok()?).ok()?;
+            matches!(url.scheme(), "http" | "https").then_some(url)
+        }
+        None => upstream.join(path).ok(),
+    }
+}
+
+/// Forwards a request for `/r/{relay}/{path}` upstream and streams the response back.
+fn relay(
+    mut stream: TcpStream,
+    relays: &Relays,
+    method: &str,
+    target: &str,
+    range: Option<String>,
+) {
+    let rest = target.trim_start_matches("/r/");
+    let (id, path) = rest.split_once('/').unwrap_or((rest, ""));
+    let found = relays
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(id)
+        .and_then(|r| {
+            let url = upstream_url(&r.upstream, path)?;
+            Some((
+                r.client.clone(),
+                url,
+                r.meter.clone(),
+                r.cookie.clone(),
+                r.icy,
+            ))
+        });
+    let Some((client, url, meter, cookie, icy)) = found else {
+        respond(&mut stream, "404 Not Found", "text/plain", b"");
+        return;
+    };
+    tauri::async_runtime::block_on(async move {
+        let mut req = if method == "HEAD" {
+            client.head(url.clone())
+        } else {
+            client.get(url.clone())
+        };
+        if let Some(range) = range {
+            req = req.header(reqwest::header::RANGE, range);
+        }
+        if let Some(cookie) = cookie {
+            req = req.header(reqwest::header::COOKIE, cookie);
+        }
+        if icy {
+            req = req.header(crate::icy::REQUEST_HEADER, "1");
+        }
+        let mut resp = match crate::http::send(req).await {
+            Ok(resp) => resp,
+            Err(e) => {
+                tracing::warn!(
+                    "Relay request for {} failed: {}",
+                    crate::diagnostics::sanitize_url(url.as_str()),
+                    e
+                );
+                let code = if e.is_unreachable() {
+                    FailureCode::Unreachable
+                } else {
+                    FailureCode::Unknown
+                };
+                set_failure(relays, id, Some(StreamFailure::new(code, None)));
+                respond(
+                    &mut stream,
+                    "502 Bad Gateway",
+                    "text/plain",
+                    e.to_string().as_bytes(),
+                );
+                return;
+            }
+        };
+        // Follow redirects for later relative requests (segments of a redirected playlist)
+        if resp.url() != &url && !path.starts_with(ABSOLUTE_PATH) {
+            if let Some(relay) = relays.lock().unwrap_or_else(|e| e.into_inner()).get_mut(id) {
+                relay.upstream = resp.url().clone();
+            }
+        }
+        let status = resp.status();
+        let mut icy = icy
+            .then(|| IcyStream::from_headers(resp.headers()))
+            .flatten();
+        let content_type = resp
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        let playlist = status.is_success() && icy.is_none() && is_playlist(&url, &content_type);
+        let mut head = format!(
+            "HTTP/1.1 {} {}\r\n",
+            status.as_u16(),
+            status.canonical_reason().unwrap_or("")
+        );
+        for name in [
+            reqwest::header::CONTENT_TYPE,
+            reqwest::header::CONTENT_LENGTH,
+            reqwest::header::CONTENT_RANGE,
+            reqwest::header::ACCEPT_RANGES,
+        ] {
+            // Stripping metadata or rewriting a playlist changes the length
+            if (icy.is_some() || playlist)
+                && matches!(
+                    name,
+                    reqwest::header::CONTENT_LENGTH | reqwest::header::CONTENT_RANGE
+                )
+            {
+                continue;
+            }
+            if let Some(value) = resp.headers().get(&name).and_then(|v| v.to_str().ok()) {
+                head.push_str(&format!("{}: {}\r\n", name, value));
+            }
+        }
+        head.push_str("Access-Control-Allow-Origin: *\r\nConnection: close\r\n\r\n");
+        if stream.write_all(head.as_bytes()).is_err() || method == "HEAD" {
+            return;
+        }
+        let transport_stream =
+            url.path().ends_with(".ts") || content_type.starts_with("video/mp2t");
+        // Error bodies and HTML pages are kept to classify the failure once forwarded
+        let sniff = !status.is_success() || content_type.starts_with("text/html");
+        if !sniff {
+            set_failure(relays, id, None);
+        }
+        let mut sniffed = Vec::new();
+        let meter = || meter.lock().unwrap_or_else(|e| e.into_inner());
+        meter().begin_response();
+        let mut last = Instant::now();
+        let base = resp.url().clone();
+        let mut listed = Vec::new();
+        while let Ok(Some(mut chunk)) = resp.chunk().await {
+            let gap = last.elapsed();
+            last = Instant::now();
+            meter().record(&chunk, gap, transport_stream);
+            crate::datausage::add(chunk.len());
+            if sniff && sniffed.len() < crate::failure::BODY_SNIFF {
+                sniffed.extend_from_slice(&chunk);
+            }
+            if let Some(icy) = &mut icy {
+                let mut audio = Vec::with_capacity(chunk.len());
+                if let Some(now_playing) = icy.feed(&chunk, &mut audio) {
+                    set_now_playing(relays, id, now_playing);
+                }
+                chunk = audio.into();
+            }
+            if playlist {
+                listed.extend_from_slice(&chunk);
+                if listed.len() > MAX_PLAYLIST {
+                    tracing::warn!("Relayed playlist is over {} bytes; cut off", MAX_PLAYLIST);
+                    let whole_lines = listed.iter().rposition(|&b| b == b'\n');
+                    listed.truncate(whole_lines.map_or(0, |i| i + 1));
+                    break;
+                }
+                continue;
+            }
+            if stream.write_all(&chunk).is_err() {
+                break;
+            }
+        }
+        if playlist {
+            let body = String::from_utf8_lossy(&listed);
+            let rewritten = rewrite_playlist(&body, &base, &format!("/r/{}/", id));
+            let _ = stream.write_all(rewritten.as_bytes());
+        }
+        if sniff {
+            let failure = crate::failure::classify(status.as_u16(), &content_type, &sniffed)
+                .map(|code| StreamFailure::new(code, Some(status.as_u16())));
+            if let Some(failure) = &failure {
+                tracing::warn!(
+                    code = ?failure.code,
+                    "Relay upstream {} failed",
+                    crate::diagnostics::sanitize_url(url.as_str())
+                );
+            }
+            set_failure(relays, id, failure);
+        }
+    });
+}
+
+fn handle(mut stream: TcpStream, root: &Path, relays: &Relays) {
+    let mut reader = BufReader::new(&stream);
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line).is_err() {
+        return;
+    }
+    let mut range = None;
+    let mut line = String::new();
+    while reader.read_line(&mut line).is_ok_and(|n| n > 0) && !line.trim().is_empty() {
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("range") {
+                range = Some(value.trim().to_string());
+            }
+        }
+        line.clear();
+    }
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return;
+    };
+    if method == "OPTIONS" {
+        respond(&mut stream, "204 No Content", "text/plain", b"");
+        return;
+    }
+    if method != "GET" && method != "HEAD" {
+        respond(&mut stream, "405 Method Not Allowed", "text/plain", b"");
+        return;
+    }
+    if target.starts_with("/r/") {
+        return relay(stream, relays, method, target, range);
+    }
+    let Some(path) = resolve(root, target) else {
+        respond(&mut stream, "404 Not Found", "text/plain", b"");
+        return;
+    };
+    // ffmpeg needs a few seconds before the first playlist exists
+    if path.extension().is_some_and(|e| e == "m3u8") {
+        let deadline = Instant::now() + PLAYLIST_WAIT;
+        while !path.exists() && Instant::now() < deadline && path.parent().is_some_and(Path::exists)
+        {
+            std::thread::sleep(Duration::from_millis(250));
+        }
+    }
+    match fs::read(&path) {
+        Ok(body) if method == "HEAD" => {
+            let head = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nAccess-Control-Allow-Origin: *\r\nConnection: close\r\n\r\n",
+                content_type(&path),
+                body.len()
+            );
+            let _ = stream.write_all(head.as_bytes());
+        }
+        Ok(body) => {
+            crate::datausage::add(body.len());
+            respond(&mut stream, "200 OK", content_type(&path), &body)
+        }
+        Err(_) => respond(&mut stream, "404 Not Found", "text/plain", b""),
+    }
+}
+
+type OwnedMeter = (String, String, Option<String>, Arc<Mutex<Meter>>);
+
+/// Sends each owned, active relay's statistics to its window and new stalls to `quality`, and
+/// announces song changes on radio relays.
+fn emit_stats(app: &tauri::AppHandle, relays: &Relays) {
+    let songs: Vec<(String, NowPlaying)> = relays
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .values_mut()
+        .filter(|r| r.now_playing_changed && r.owner.is_some())
+        .filter_map(|r| {
+            r.now_playing_changed = false;
+            Some((r.owner.clone()?, r.now_playing.clone()?))
+        })
+        .collect();
+    for (label, now_playing) in &songs {
+        let _ = app.emit("now-playing", NowPlayingEvent { label, now_playing });
+    }
+    let meters: Vec<OwnedMeter> = relays
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .filter_map(|(id, r)| {
+            let owner = r.owner.clone()?;
+            Some((id.clone(), owner, r.server_id.clone(), r.meter.clone()))
+        })
+        .collect();
+    for (id, owner, server_id, meter) in meters {
+        let mut meter = meter.lock().unwrap_or_else(|e| e.into_inner());
+        if !meter.active() {
+            continue;
+        }
+        let _ = app.emit_to(owner.as_str(), "stream-stats", meter.snapshot(&id));
+        let stalls = meter.take_stalls();
+        if stalls.stalls > 0 {
+            crate::quality::record(&owner, server_id.as_deref(), stalls);
+        }
+    }
+}
+
+/// Binds the proxy on a random loopback port and starts serving in a background thread.
+pub fn start(app: &tauri::AppHandle) -> Result<ProxyState, String> {
+    let root = app
+        .path()
+        .app_cache_dir()
+        .map_err(|e| e.to_string())?
+        .join("proxy");
+    // Leftovers from a previous run are useless and may be large
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).map_err(|e| format!("Failed to create proxy dir: {}", e))?;
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+        .map_err(|e| format!("Failed to start local proxy: {}", e))?;
+    let port = listener.local_addr().map_err(|e| e.to_string())?.port();
+    let serve_root = root.clone();
+    let relays = Relays::default();
+    let serve_relays = relays.clone();
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let root = serve_root.clone();
+            let relays = serve_relays.clone();
+            std::thread::spawn(move || handle(stream, &root, &relays));
+        }
+    });
+    let stats_relays = relays.clone();
+    let app = app.clone();
+    std::thread::spawn(move || loop {
+        std::thread::sleep(STATS_INTERVAL);
+        emit_stats(&app, &stats_relays);
+    });
+    Ok(ProxyState {
+        port,
+        root,
+        sessions: Mutex::new(HashMap::new()),
+        relays,
+    })
+}
+
+impl ProxyState {
+    fn sessions(&self) -> std::sync::MutexGuard<'_, HashMap<String, Session>> {
+        self.sessions.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// URL at which the proxy serves `file` of `session_id`.
+    pub fn url(&self, session_id: &str, file: &str) -> String {
+        format!("http://127.0.0.1:{}/s/{}/{}", self.port, session_id, file)
+    }
+
+    /// Creates an empty session directory that isn't backed by a process (e.g. static files).
+    pub fn session_dir(&self, session_id: &str) -> Result<PathBuf, String> {
+        let dir = self.root.join(session_id);
+        fs::create_dir_all(&dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        Ok(dir)
+    }
+
+    /// Runs ffmpeg with `input_args` (everything up to and including `-i <url>`) and
+    /// `output_args` (codec selection), writing an HLS playlist served by the proxy. Live
+    /// playlists keep a sliding window; `vod` keeps every segment so the player can seek.
+    /// With `subtitle`, the same ffmpeg also converts that input stream to WebVTT, for
+    /// `finished_subtitles`.
+    pub fn start_hls(
+        &self,
+        input_args: &[String],
+        output_args: &[String],
+        vod: bool,
+        subtitle: Option<u32>,
+    ) -> Result<ProxySession, String> {
+        let list_args = if vod {
+            ["-hls_list_size", "0", "-hls_playlist_type", "event"]
+        } else {
+            [
+                "-hls_list_size",
+                "10",
+                "-hls_flags",
+                "delete_segments+omit_endlist",
+            ]
+        };
+        let id = uuid::Uuid::new_v4().simple().to_string();
+        let subtitle_output = match subtitle {
+            Some(index) => vec![
+                "-map".to_string(),
+                format!("0:{}", index),
+                "-c:s".to_string(),
+                "webvtt".to_string(),
+                "-f".to_string(),
+                "webvtt".to_string(),
+                self.root
+                    .join(&id)
+                    .join(SUBTITLE_FILE)
+                    .to_string_lossy()
+                    .into_owned(),
+            ],
+            None => Vec::new(),
+        };
+        self.spawn_hls(
+            id,
+            input_args,
+            output_args,
+            &list_args,
+            &subtitle_output,
+            None,
+        )
+    }
+
+    /// Like a live `start_hls`, but the playlist keeps the last `buffer_secs` of the stream on
+    /// disk, older segments being deleted, so the player can pause and rewind within it.
+    pub fn start_timeshift(
+        &self,
+        input_args: &[String],
+        output_args: &[String],
+        buffer_secs: u64,
+    ) -> Result<ProxySession, String> {
+        let list_size = (buffer_secs / SEGMENT_SECS).max(1).to_string();
+        self.spawn_hls(
+            uuid::Uuid::new_v4().simple().to_string(),
+            input_args,
+            output_args,
+            &[
+                "-hls_list_size",
+                &list_size,
+                "-hls_flags",
+                "delete_segments+omit_endlist",
+            ],
+            &[],
+            Some(buffer_secs),
+        )
+    }
+
+    /// Starts session `id`. `extra_output` is another ffmpeg output, after the playlist.
+    fn spawn_hls(
+        &self,
+        id: String,
+        input_args: &[String],
+        output_args: &[String],
+        list_args: &[&str],
+        extra_output: &[String],
+        timeshift_secs: Option<u64>,
+    ) -> Result<ProxySession, String> {
+        let dir = self.session_dir(&id)?;
+        let child = Command::new(crate::ffmpeg::ffmpeg_path()?)
+            .args(["-hide_banner", "-loglevel", "error", "-nostdin"])
+            .args(input_args)
+            .args(output_args)
+            .args(["-f", "hls", "-hls_time", &SEGMENT_SECS.to_string()])
+            .args(list_args)
+            .arg(dir.join("index.m3u8"))
+            .args(extra_output)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("Failed to start ffmpeg: {}", e))?;
+        tracing::info!(session = %id, ?timeshift_secs, "Started ffmpeg HLS session");
+        self.sessions().insert(
+            id.clone(),
+            Session {
+                dir,
+                child,
+                owner: None,
+                timeshift_secs,
+            },
+        );
+        Ok(ProxySession {
+            url: self.url(&id, "index.m3u8"),
+            id,
+        })
+    }
+
+    fn relays(&self) -> std::sync::MutexGuard<'_, HashMap<String, Relay>> {
+        self.relays.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Starts relaying a stream link resolved by `resolve` with `server`'s network settings
+    /// and the cookies collected while resolving it. The returned URL keeps the upstream file
+    /// name and query so relative references inside it (HLS segments) resolve through the
+    /// relay.
+    pub fn relay(
+        &self,
+        resolved: &Resolved,
+        server: Option<&ServerConfig>,
+    ) -> Result<ProxySession, String> {
+        self.start_relay(&resolved.url, server, resolved.cookie.clone(), false)
+    }
+
+    /// Like `relay`, for a radio station: asks for ICY metadata and reports the
+    /// current song.
+    pub fn relay_radio(
+        &self,
+        resolved: &Resolved,
+        server: Option<&ServerConfig>,
+    ) -> Result<ProxySession, String> {
+        self.start_relay(&resolved.url, server, resolved.cookie.clone(), true)
+    }
+
+    fn start_relay(
+        &self,
+        url: &str,
+        server: Option<&ServerConfig>,
+        cookie: Option<String>,
+        icy: bool,
+    ) -> Result<ProxySession, String> {
+        let upstream =
+            reqwest::Url::parse(url).map_err(|e| format!("Invalid stream URL: {}", e))?;
+        let client = crate::http::stream_client(server)?;
+        let id = uuid::Uuid::new_v4().simple().to_string();
+        let file = upstream
+            .path_segments()
+            .and_then(|mut segments| segments.next_back())
+            .unwrap_or_default()
+            .to_string();
+        let query = upstream
+            .query()
+            .map(|q| format!("?{}", q))
+            .unwrap_or_default();
+        self.relays().insert(
+            id.clone(),
+            Relay {
+                upstream,
+                client,
+                owner: None,
+                server_id: server.map(|s| s.id.clone()),
+                meter: Arc::default(),
+                failure: None,
+                cookie,
+                icy,
+                now_playing: None,
+                now_playing_changed: false,
+            },
+        );
+        Ok(ProxySession {
+            url: format!("http://127.0.0.1:{}/r/{}/{}{}", self.port, id, file, query),
+            id,
+        })
+    }
+
+    /// Ties a session or relay to a video window so it stops when the window closes.
+    pub fn set_owner(&self, session_id: &str, label: &str) {
+        if let Some(session) = self.sessions().get_mut(session_id) {
+            session.owner = Some(label.to_string());
+        }
+        if let Some(relay) = self.relays().get_mut(session_id) {
+            relay.owner = Some(label.to_string());
+        }
+    }
+
+    pub fn stop(&self, session_id: &str) {
+        if let Some(session) = self.sessions().remove(session_id) {
+            stop_session(session);
+        }
+        self.relays().remove(session_id);
+    }
+
+    /// Last upstream failure of a relay owned by `label`, with the relay's server.
+    pub fn failure_owned_by(&self, label: &str) -> Option<(StreamFailure, Option<String>)> {
+        self.relays()
+            .values()
+            .filter(|r| r.owner.as_deref() == Some(label))
+            .find_map(|r| Some((r.failure.clone()?, r.server_id.clone())))
+    }
+
+    /// Current song of the radio relay owned by `label`.
+    pub fn now_playing_owned_by(&self, label: &str) -> Option<NowPlaying> {
+        self.relays()
+            .values()
+            .filter(|r| r.owner.as_deref() == Some(label))
+            .find_map(|r| r.now_playing.clone())
+    }
+
+    /// Directory and buffer length of the timeshift session owned by `label`.
+    pub fn timeshift_owned_by(&self, label: &str) -> Option<(PathBuf, u64)> {
+        self.sessions()
+            .values()
+            .filter(|s| s.owner.as_deref() == Some(label))
+            .find_map(|s| Some((s.dir.clone(), s.timeshift_secs?)))
+    }
+
+    /// The WebVTT file `start_hls` had session `session_id` write, once ffmpeg has finished
+    /// it: `Ok(None)` while it still runs, `Err` when the session is gone or ffmpeg failed.
+    pub fn finished_subtitles(&self, session_id: &str) -> Result<Option<PathBuf>, String> {
+        let mut sessions = self.sessions();
+        let session = sessions
+            .get_mut(session_id)
+            .ok_or_else(|| "The session has stopped".to_string())?;
+        match session.child.try_wait() {
+            Ok(None) => Ok(None),
+            Ok(Some(status)) if status.success() => Ok(Some(session.dir.join(SUBTITLE_FILE))),
+            Ok(Some(status)) => Err(format!("ffmpeg exited with {}", status)),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    /// Whether `url` points at this proxy.
+    pub fn is_local(&self, url: &str) -> bool {
+        url.starts_with(&format!("http://127.0.0.1:{}/", self.port))
+    }
+
+    pub fn stop_owned_by(&self, label: &str) {
+        self.relays()
+            .retain(|_, relay| relay.owner.as_deref() != Some(label));
+        let owned: Vec<Session> = {
+            let mut sessions = self.sessions();
+            let ids: Vec<String> = sessions
+                .iter()
+                .filter(|(_, s)| s.owner.as_deref() == Some(label))
+                .map(|(id, _)| id.clone())
+                .collect();
+            ids.iter().filter_map(|id| sessions.remove(id)).collect()
+        };
+        owned.into_iter().for_each(stop_session);
+    }
+
+    pub fn stop_all(&self) {
+        let all: Vec<Session> = self.sessions().drain().map(|(_, s)| s).collect();
+        all.into_iter().for_each(stop_session);
+    }
+}
+
+fn stop_session(mut session: Session) {
+    let _ = session.child.kill();
+    let _ = session.child.wait();
+    let _ = fs::remove_dir_all(&session.dir);
+}
+
+/// Stops proxy sessions owned by a closed video window.
+pub fn release(app: &tauri::AppHandle, label: &str) {
+    if let Some(proxy) = app.try_state::<ProxyState>() {
+        proxy.stop_owned_by(label);
+    }
+}
+
+/// Opens a video window for a proxied session and ties the session to it. `params` are extra
+/// query parameters for the video window page.
+pub fn open_window(
+    app: &tauri::AppHandle,
+    proxy: &ProxyState,
+    session: &ProxySession,
+    title: &str,
+    params: &[(&str, String)],
+) -> Result<String, String> {
+    match crate::create_video_window_with(app, title, &session.url, params) {
+        Ok(label) => {
+            proxy.set_owner(&session.id, &label);
+            Ok(label)
+        }
+        Err(e) => {
+            proxy.stop(&session.id);
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(s: &str) -> reqwest::Url {
+        reqwest::Url::parse(s).unwrap()
+    }
+
+    #[test]
+    fn playlist_uris_go_through_the_relay() {
+        let base = url("http://cdn.example/live/chan/index.m3u8?token=t");
+        let body = "#EXTM3U\n\
+            #EXT-X-KEY:METHOD=AES-128,URI=\"/keys/1.key\",IV=0x1\n\
+            #EXT-X-MAP:URI=\"init.mp4\"\n\
+            #EXTINF:2.0,\n\
+            seg1.ts\n\
+            #EXTINF:2.0,\n\
+            http://other.example/seg2.ts\n";
+        let rewritten = rewrite_playlist(body, &base, "/r/abc/");
+        let lines: Vec<&str> = rewritten.lines().collect();
+        assert_eq!(
+            lines[1],
+            "#EXT-X-KEY:METHOD=AES-128,URI=\"/r/abc/~/http%3A%2F%2Fcdn.example%2Fkeys%2F1.key\",IV=0x1"
+        );
+        assert_eq!(
+            lines[2],
+            "#EXT-X-MAP:URI=\"/r/abc/~/http%3A%2F%2Fcdn.example%2Flive%2Fchan%2Finit.mp4\""
+        );
+        assert_eq!(
+            lines[4],
+            "/r/abc/~/http%3A%2F%2Fcdn.example%2Flive%2Fchan%2Fseg1.ts"
+        );
+        assert_eq!(lines[6], "/r/abc/~/http%3A%2F%2Fother.example%2Fseg2.ts");
+    }
+
+    #[test]
+    fn variant_and_rendition_playlists_go_through_the_relay() {
+        let base = url("https://cdn.example/master.m3u8");
+        let body = "#EXTM3U\n\
+            #EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"a\",NAME=\"English\",URI=\"audio/en.m3u8\"\n\
+            #EXT-X-STREAM-INF:BANDWIDTH=1000000,AUDIO=\"a\"\n\
+            720/index.m3u8\n";
+        let rewritten = rewrite_playlist(body, &base, "/r/abc/");
+        assert!(rewritten.contains(
+            "NAME=\"English\",URI=\"/r/abc/~/https%3A%2F%2Fcdn.example%2Faudio%2Fen.m3u8\""
+        ));
+        assert!(rewritten.contains("\n/r/abc/~/https%3A%2F%2Fcdn.example%2F720%2Findex.m3u8\n"));
+        assert!(rewritten.contains("GROUP-ID=\"a\""));
+    }
+
+    #[test]
+    fn key_uris_that_are_not_http_stay() {
+        let base = url("https://cdn.example/index.m3u8");
+        let line = "#EXT-X-KEY:METHOD=SAMPLE-AES,URI=\"skd://key-1\"";
+        assert_eq!(rewrite_playlist(line, &base, "/r/abc/").trim_end(), line);
+    }
+
+    #[test]
+    fn relay_paths_map_back_to_upstream_urls() {
+        let upstream = url("http://cdn.example/live/index.m3u8");
+        assert_eq!(
+            upstream_url(&upstream, "seg1.ts").unwrap().as_str(),
+            "http://cdn.example/live/seg1.ts"
+        );
+        assert_eq!(
+            upstream_url(&upstream, "~/http%3A%2F%2Fother.example%2Fa.ts%3Fx%3D1")
+                .unwrap()
+                .as_str(),
+            "http://other.example/a.ts?x=1"
+        );
+        assert!(upstream_url(&upstream, "~/file%3A%2F%2F%2Fetc%2Fpasswd").is_none());
+    }
+}
@@ -0,0 +1,264 @@
+//! Stream quality telemetry. Stalls seen by the proxy relays and rebuffers reported by the video
+//! window are tallied per server and live channel, collected in memory and merged into daily
+//! totals in `stream-quality.json` once a minute. `get_provider_quality` sets them against the
+//! time watched (from `history`) and ranks servers by how much of it was interrupted, so
+//! subscriptions can be compared.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::{Manager, State};
+
+use crate::history::HistoryStore;
+use crate::servers::ServerStore;
+use crate::store::JsonStore;
+
+const FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+const RETENTION_DAYS: u64 = 90;
+const DEFAULT_DAYS: u64 = 30;
+const DAY_SECS: u64 = 86_400;
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Tally {
+    pub stalls: u32,
+    pub stalled_secs: f64,
+    pub rebuffers: u32,
+    pub rebuffer_secs: f64,
+}
+
+impl Tally {
+    fn add(&mut self, other: &Tally) {
+        self.stalls += other.stalls;
+        self.stalled_secs += other.stalled_secs;
+        self.rebuffers += other.rebuffers;
+        self.rebuffer_secs += other.rebuffer_secs;
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QualityDay {
+    pub server_id: String,
+    /// Live channel; `None` for VOD and windows that didn't report a channel.
+    #[serde(default)]
+    pub channel_id: Option<String>,
+    /// Days since the Unix epoch, UTC.
+    pub day: u64,
+    #[serde(flatten)]
+    pub tally: Tally,
+}
+
+pub type QualityStore = JsonStore<Vec<QualityDay>>;
+
+pub fn open(app: &tauri::AppHandle) -> QualityStore {
+    JsonStore::open(app, "stream-quality.json")
+}
+
+type Pending = BTreeMap<(String, Option<String>), Tally>;
+
+static PENDING: Mutex<Pending> = Mutex::new(BTreeMap::new());
+
+fn pending() -> std::sync::MutexGuard<'static, Pending> {
+    PENDING.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Adds to the window's tally; the server and channel come from its watch session, else
+/// `server_id`.
+pub fn record(label: &str, server_id: Option<&str>, tally: Tally) {
+    let key = match crate::history::live_channel(label) {
+        Some((server_id, channel_id)) => (server_id, Some(channel_id)),
+        None => match crate::history::watching_server(label).or(server_id.map(str::to_string)) {
+            Some(server_id) => (server_id, None),
+            None => return,
+        },
+    };
+    pending().entry(key).or_default().add(&tally);
+}
+
+/// Merges pending tallies into today's totals and drops days past retention.
+fn flush(app: &tauri::AppHandle) {
+    let pending = std::mem::take(&mut *pending());
+    if pending.is_empty() {
+        return;
+    }
+    let today = crate::now_secs() / DAY_SECS;
+    let saved = app.state::<QualityStore>().update(|days| {
+        days.retain(|d| d.day + RETENTION_DAYS > today);
+        for ((server_id, channel_id), tally) in pending {
+            match days
+                .iter_mut()
+                .find(|d| d.day == today && d.server_id == server_id && d.channel_id == channel_id)
+            {
+                Some(day) => day.tally.add(&tally),
+                None => days.push(QualityDay {
+                    server_id,
+                    channel_id,
+                    day: today,
+                    tally,
+                }),
+            }
+        }
+    });
+    if let Err(e) = saved {
+        tracing::warn!("Could not save stream quality: {}", e);
+    }
+}
+
+/// Saves collected telemetry every minute.
+pub fn start(app: &tauri::AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(FLUSH_INTERVAL).await;
+            flush(&app);
+        }
+    });
+}
+
+/// Saves what was collected since the last flush, on exit.
+pub fn shutdown(app: &tauri::AppHandle) {
+    flush(app);
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChannelQuality {
+    pub channel_id: String,
+    pub channel_name: Option<String>,
+    pub play_secs: f64,
+    #[serde(flatten)]
+    pub tally: Tally,
+    pub interruptions_per_hour: f64,
+    /// Share of playing time spent stalled or rebuffering, 0–100.
+    pub interrupted_percent: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProviderQuality {
+    pub server_id: String,
+    pub server_name: String,
+    pub play_secs: f64,
+    #[serde(flatten)]
+    pub tally: Tally,
+    pub interruptions_per_hour: f64,
+    pub interrupted_percent: f64,
+    /// Worst channels first.
+    pub channels: Vec<ChannelQuality>,
+}
+
+/// Interruptions per hour and percent of `play_secs` interrupted.
+fn rates(play_secs: f64, tally: &Tally) -> (f64, f64) {
+    if play_secs <= 0.0 {
+        return (0.0, 0.0);
+    }
+    let interruptions = f64::from(tally.stalls + tally.rebuffers);
+    let interrupted = tally.stalled_secs + tally.rebuffer_secs;
+    (
+        interruptions * 3600.0 / play_secs,
+        (interrupted / play_secs * 100.0).min(100.0),
+    )
+}
+
+#[derive(Default)]
+struct Totals {
+    play_secs: f64,
+    tally: Tally,
+}
+
+/// Per-server interruptions over the last `days` (30 by default), best first.
+#[tauri::command]
+pub fn get_provider_quality(
+    app: tauri::AppHandle,
+    store: State<'_, QualityStore>,
+    history: State<'_, HistoryStore>,
+    servers: State<'_, ServerStore>,
+    days: Option<u64>,
+) -> Vec<ProviderQuality> {
+    flush(&app);
+    let since = (crate::now_secs() / DAY_SECS).saturating_sub(days.unwrap_or(DEFAULT_DAYS));
+    let mut totals: BTreeMap<String, (Totals, BTreeMap<String, Totals>)> = BTreeMap::new();
+    store.read(|days| {
+        for d in days.iter().filter(|d| d.day > since) {
+            let (server, channels) = totals.entry(d.server_id.clone()).or_default();
+            server.tally.add(&d.tally);
+            if let Some(channel_id) = &d.channel_id {
+                channels
+                    .entry(channel_id.clone())
+                    .or_default()
+                    .tally
+                    .add(&d.tally);
+            }
+        }
+    });
+    history.read(|sessions| {
+        for s in sessions
+            .iter()
+            .filter(|s| s.started_at.max(0) as u64 / DAY_SECS > since)
+        {
+            let (server, channels) = totals.entry(s.server_id.clone()).or_default();
+            server.play_secs += s.watched_secs as f64;
+            if let Some(channel_id) = &s.channel_id {
+                channels.entry(channel_id.clone()).or_default().play_secs += s.watched_secs as f64;
+            }
+        }
+    });
+    let mut report: Vec<ProviderQuality> = totals
+        .into_iter()
+        .map(|(server_id, (server, channels))| {
+            let mut channels: Vec<ChannelQuality> = channels
+                .into_iter()
+                .map(|(channel_id, c)| {
+                    let (per_hour, percent) = rates(c.play_secs, &c.tally);
+                    ChannelQuality {
+                        channel_name: crate::catalog::lookup(&server_id, &channel_id)
+                            .map(|c| c.name),
+                        channel_id,
+                        play_secs: c.play_secs,
+                        tally: c.tally,
+                        interruptions_per_hour: per_hour,
+                        interrupted_percent: percent,
+                    }
+                })
+                .collect();
+            channels.sort_by(|a, b| b.interrupted_percent.total_cmp(&a.interrupted_percent));
+            let (per_hour, percent) = rates(server.play_secs, &server.tally);
+            ProviderQuality {
+                server_name: crate::servers::get(&servers, &server_id)
+                    .map(|s| s.name)
+                    .unwrap_or_else(|_| server_id.clone()),
+                server_id,
+                play_secs: server.play_secs,
+                tally: server.tally,
+                interruptions_per_hour: per_hour,
+                interrupted_percent: percent,
+                channels,
+            }
+        })
+        .collect();
+    report.sort_by(|a, b| a.interrupted_percent.total_cmp(&b.interrupted_percent));
+    report
+}
+
+/// Reported by the video window when playback resumes after waiting for data.
+#[tauri::command]
+pub fn report_rebuffer(window_label: String, duration_secs: f64) {
+    record(
+        &window_label,
+        None,
+        Tally {
+            rebuffers: 1,
+            rebuffer_secs: duration_secs.max(0.0),
+            ..Tally::default()
+        },
+    );
+}
+
+#[tauri::command]
+pub fn clear_provider_quality(store: State<'_, QualityStore>) -> Result<(), String> {
+    pending().clear();
+    store.update(|days| days.clear())
+}
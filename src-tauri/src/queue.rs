@@ -0,0 +1,320 @@
+//! Play queue for VOD, persisted in `queue.json` so it survives restarts. The first item opens
+//! a video window; each following one is loaded into that same window when the previous ends
+//! (reported by the window, or by progress reaching the end). Every change is broadcast as
+//! `queue-changed`.
+
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, Manager, State};
+
+use crate::proxy::ProxyState;
+use crate::remote::PlayerCommand;
+use crate::servers::ServerStore;
+use crate::settings::SettingsStore;
+use crate::store::JsonStore;
+use crate::tracks::ItemTrackStore;
+
+/// Progress this close to the end counts as the item having finished.
+const END_SLACK_SECS: f64 = 3.0;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueueItem {
+    pub id: String,
+    pub title: String,
+    pub stream_url: String,
+    /// Progress key; `queue:{id}` when the caller has none.
+    pub content_key: String,
+    #[serde(default)]
+    pub server_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueueItemInput {
+    pub title: String,
+    pub stream_url: String,
+    #[serde(default)]
+    pub content_key: Option<String>,
+    #[serde(default)]
+    pub server_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayQueue {
+    pub items: Vec<QueueItem>,
+    /// Index of the item playing or to play next.
+    #[serde(default)]
+    pub position: usize,
+}
+
+pub type QueueStore = JsonStore<PlayQueue>;
+
+pub fn open(app: &tauri::AppHandle) -> QueueStore {
+    JsonStore::open(app, "queue.json")
+}
+
+/// Label of the video window the queue is playing in.
+static WINDOW: Mutex<Option<String>> = Mutex::new(None);
+
+fn window() -> std::sync::MutexGuard<'static, Option<String>> {
+    WINDOW.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+pub fn release(label: &str) {
+    let mut window = window();
+    if window.as_deref() == Some(label) {
+        *window = None;
+    }
+}
+
+fn change(
+    app: &tauri::AppHandle,
+    store: &QueueStore,
+    f: impl FnOnce(&mut PlayQueue),
+) -> Result<PlayQueue, String> {
+    let queue = store.update(|queue| {
+        f(queue);
+        queue.position = queue.position.min(queue.items.len());
+        queue.clone()
+    })?;
+    let _ = app.emit("queue-changed", &queue);
+    Ok(queue)
+}
+
+/// Plays the item at the queue position: loaded into the queue's window when it is still
+/// open, else in a new one.
+async fn play_current(app: &tauri::AppHandle) -> Result<Option<QueueItem>, String> {
+    let item = app
+        .state::<QueueStore>()
+        .read(|queue| queue.items.get(queue.position).cloned());
+    let Some(item) = item else {
+        return Ok(None);
+    };
+    let server = item
+        .server_id
+        .as_deref()
+        .and_then(|id| crate::servers::get(&app.state::<ServerStore>(), id).ok());
+    let open = window()
+        .clone()
+        .filter(|label| app.get_webview_window(label).is_some());
+    if let Some(label) = open {
+        let proxy = app.state::<ProxyState>();
+        let resolved = crate::resolve::resolve(server.as_ref(), &item.stream_url).await;
+        let mut url = resolved.url.clone();
+        if resolved.needs_relay(server.as_ref()) {
+            proxy.stop_owned_by(&label);
+            let relay = proxy.relay(&resolved, server.as_ref())?;
+            proxy.set_owner(&relay.id, &label);
+            url = relay.url;
+        }
+        let command = PlayerCommand::Load {
+            url,
+            content_key: Some(item.content_key.clone()),
+        };
+        crate::remote::send_player_command(app, &label, &command)?;
+        if let Some(window) = app.get_webview_window(&label) {
+            let _ = window.set_title(&item.title);
+        }
+    } else {
+        let label = crate::transcode::play_stream(
+            app.clone(),
+            app.state::<ProxyState>(),
+            app.state::<SettingsStore>(),
+            app.state::<ItemTrackStore>(),
+            item.title.clone(),
+            item.stream_url.clone(),
+            None,
+            Some(item.content_key.clone()),
+            server,
+            None,
+            None,
+        )
+        .await?;
+        *window() = Some(label);
+    }
+    Ok(Some(item))
+}
+
+/// Called on every progress update; moves on once the queue's current item has finished.
+pub fn on_progress(
+    app: &tauri::AppHandle,
+    content_key: &str,
+    position_secs: f64,
+    duration_secs: Option<f64>,
+) {
+    if window().is_none() || duration_secs.is_none_or(|d| position_secs < d - END_SLACK_SECS) {
+        return;
+    }
+    let is_current = |queue: &PlayQueue| {
+        queue
+            .items
+            .get(queue.position)
+            .is_some_and(|item| item.content_key == content_key)
+    };
+    let store = app.state::<QueueStore>();
+    // Reports keep coming until the next item plays; most find the queue already moved on
+    if !store.read(is_current) {
+        return;
+    }
+    // Checked again and moved in one update, so reports arriving close together advance once
+    let mut finished = false;
+    let advanced = change(app, &store, |queue| {
+        finished = is_current(queue);
+        if finished {
+            queue.position += 1;
+        }
+    });
+    if !finished || advanced.is_err() {
+        return;
+    }
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = play_current(&app).await {
+            tracing::warn!("Could not play the next queue item: {}", e);
+        }
+    });
+}
+
+async fn advance(app: &tauri::AppHandle) -> Result<Option<QueueItem>, String> {
+    let store = app.state::<QueueStore>();
+    change(app, &store, |queue| queue.position += 1)?;
+    play_current(app).await
+}
+
+#[tauri::command]
+pub fn get_queue(store: State<'_, QueueStore>) -> PlayQueue {
+    store.read(|queue| queue.clone())
+}
+
+/// Appends items, or inserts them after the current one with `next`.
+#[tauri::command]
+pub fn queue_add(
+    app: tauri::AppHandle,
+    store: State<'_, QueueStore>,
+    items: Vec<QueueItemInput>,
+    next: Option<bool>,
+) -> Result<PlayQueue, String> {
+    let items: Vec<QueueItem> = items
+        .into_iter()
+        .map(|input| {
+            let id = uuid::Uuid::new_v4().to_string();
+            QueueItem {
+                content_key: input.content_key.unwrap_or_else(|| format!("queue:{}", id)),
+                id,
+                title: input.title,
+                stream_url: input.stream_url,
+                server_id: input.server_id,
+            }
+        })
+        .collect();
+    change(&app, &store, |queue| {
+        let at = if next.unwrap_or(false) {
+            (queue.position + 1).min(queue.items.len())
+        } else {
+            queue.items.len()
+        };
+        queue.items.splice(at..at, items);
+    })
+}
+
+#[tauri::command]
+pub fn queue_remove(
+    app: tauri::AppHandle,
+    store: State<'_, QueueStore>,
+    id: String,
+) -> Result<PlayQueue, String> {
+    change(&app, &store, |queue| {
+        if let Some(index) = queue.items.iter().position(|i| i.id == id) {
+            queue.items.remove(index);
+            if index < queue.position {
+                queue.position -= 1;
+            }
+        }
+    })
+}
+
+/// Moves an item to `index`, the current item staying current.
+#[tauri::command]
+pub fn queue_move(
+    app: tauri::AppHandle,
+    store: State<'_, QueueStore>,
+    id: String,
+    index: usize,
+) -> Result<PlayQueue, String> {
+    change(&app, &store, |queue| {
+        let Some(from) = queue.items.iter().position(|i| i.id == id) else {
+            return;
+        };
+        let current = queue.items.get(queue.position).map(|i| i.id.clone());
+        let item = queue.items.remove(from);
+        let to = index.min(queue.items.len());
+        queue.items.insert(to, item);
+        if let Some(current) = current {
+            queue.position = queue
+                .items
+                .iter()
+                .position(|i| i.id == current)
+                .unwrap_or(queue.position);
+        }
+    })
+}
+
+/// Shuffles the items after the current one.
+#[tauri::command]
+pub fn queue_shuffle(
+    app: tauri::AppHandle,
+    store: State<'_, QueueStore>,
+) -> Result<PlayQueue, String> {
+    change(&app, &store, |queue| {
+        let start = (queue.position + 1).min(queue.items.len());
+        let rest = &mut queue.items[start..];
+        // Fisher-Yates, drawing from v4 UUIDs to avoid a dependency on `rand`
+        for i in (1..rest.len()).rev() {
+            let j = (uuid::Uuid::new_v4().as_u128() % (i as u128 + 1)) as usize;
+            rest.swap(i, j);
+        }
+    })
+}
+
+#[tauri::command]
+pub fn queue_clear(
+    app: tauri::AppHandle,
+    store: State<'_, QueueStore>,
+) -> Result<PlayQueue, String> {
+    change(&app, &store, |queue| *queue = PlayQueue::default())
+}
+
+/// Starts playing the queue, from item `id` when given, else from the current position.
+#[tauri::command]
+pub async fn queue_play(
+    app: tauri::AppHandle,
+    store: State<'_, QueueStore>,
+    id: Option<String>,
+) -> Result<Option<QueueItem>, String> {
+    if let Some(id) = id {
+        change(&app, &store, |queue| {
+            if let Some(index) = queue.items.iter().position(|i| i.id == id) {
+                queue.position = index;
+            }
+        })?;
+    }
+    play_current(&app).await
+}
+
+/// Skips to the next item. A window passing its label (e.g. when its video ended) only
+/// advances the queue it is playing.
+#[tauri::command]
+pub async fn queue_next(
+    app: tauri::AppHandle,
+    window_label: Option<String>,
+) -> Result<Option<QueueItem>, String> {
+    if let Some(label) = window_label {
+        if window().as_deref() != Some(label.as_str()) {
+            return Ok(None);
+        }
+    }
+    advance(&app).await
+}
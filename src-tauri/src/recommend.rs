@@ -0,0 +1,336 @@
+//! Recommendations from local history. "Because you watched": watched VOD items build a taste
+//! profile of weighted features (genres, cast, director, category), weighted by watch time and
+//! fading with age, and unwatched items are ranked by how well they match it. "On now for you":
+//! programmes airing now or soon, ranked by favorite channels and the genres, channels and
+//! series watched live. A background loop keeps the favorites' guide fresh and emits
+//! `on-now-updated` with new picks.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::Duration;
+
+use serde::Serialize;
+use tauri::{Emitter, Manager, State};
+
+use crate::epg::{EpgRange, Programme};
+use crate::favorites::FavoriteStore;
+use crate::history::{HistoryStore, WatchKind};
+use crate::progress::ProgressStore;
+use crate::servers::{ServerKind, ServerStore};
+use crate::vod::VodItem;
+
+const DEFAULT_LIMIT: usize = 20;
+/// A watch this old counts half as much as one today.
+const HALF_LIFE_SECS: f64 = 90.0 * 86_400.0;
+const GENRE_WEIGHT: f64 = 1.0;
+const CAST_WEIGHT: f64 = 0.6;
+const DIRECTOR_WEIGHT: f64 = 0.8;
+const CATEGORY_WEIGHT: f64 = 0.4;
+const ON_NOW_INTERVAL: Duration = Duration::from_secs(300);
+/// Programmes starting within this count as "soon".
+const SOON_SECS: i64 = 30 * 60;
+/// Programmes ending sooner than this aren't worth tuning to.
+const MIN_LEFT_SECS: i64 = 5 * 60;
+const FAVORITE_SCORE: f64 = 3.0;
+const SERIES_SCORE: f64 = 2.5;
+const GENRE_SCORE: f64 = 2.0;
+const CHANNEL_SCORE: f64 = 2.0;
+/// Upcoming programmes rank a little below ones already on.
+const SOON_FACTOR: f64 = 0.8;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Recommendation {
+    pub item: VodItem,
+    pub score: f64,
+    /// The watched title it is most like.
+    pub because: Option<String>,
+}
+
+fn features(item: &VodItem) -> Vec<(String, f64)> {
+    let mut features: Vec<(String, f64)> = item
+        .genres
+        .iter()
+        .map(|g| (format!("g:{}", g.to_lowercase()), GENRE_WEIGHT))
+        .collect();
+    features.extend(
+        item.cast
+            .iter()
+            .map(|c| (format!("c:{}", c.to_lowercase()), CAST_WEIGHT)),
+    );
+    features.extend(
+        item.director
+            .iter()
+            .map(|d| (format!("d:{}", d.to_lowercase()), DIRECTOR_WEIGHT)),
+    );
+    features.extend(
+        item.category
+            .iter()
+            .map(|c| (format!("k:{}", c.to_lowercase()), CATEGORY_WEIGHT)),
+    );
+    features
+}
+
+/// Feature overlap between two items, for picking the `because` title.
+fn similarity(a: &[(String, f64)], b: &[(String, f64)]) -> f64 {
+    a.iter()
+        .filter(|(f, _)| b.iter().any(|(g, _)| f == g))
+        .map(|(_, w)| w)
+        .sum()
+}
+
+/// Ranks unwatched VOD items across all servers by similarity to what was watched.
+#[tauri::command]
+pub async fn get_recommendations(
+    app: tauri::AppHandle,
+    servers: State<'_, ServerStore>,
+    history: State<'_, HistoryStore>,
+    progress: State<'_, ProgressStore>,
+    limit: Option<usize>,
+) -> Result<Vec<Recommendation>, String> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT);
+    let now = crate::now_secs() as f64;
+    let decay = |at: f64| 0.5f64.powf((now - at).max(0.0) / HALF_LIFE_SECS);
+
+    // How much each content key was watched, and how recently
+    let mut watched: BTreeMap<String, f64> = BTreeMap::new();
+    let mut watched_titles: BTreeSet<String> = BTreeSet::new();
+    history.read(|sessions| {
+        for s in sessions
+            .iter()
+            .filter(|s| matches!(s.kind, WatchKind::Movie | WatchKind::Episode))
+        {
+            let weight = (1.0 + s.watched_secs as f64 / 60.0).ln() * decay(s.started_at as f64);
+            if let Some(key) = &s.content_key {
+                *watched.entry(key.clone()).or_default() += weight;
+            }
+            watched_titles.insert(s.title.to_lowercase());
+        }
+    });
+    progress.read(|entries| {
+        for e in entries.values() {
+            let weight = (1.0 + e.position_secs / 60.0).ln() * decay(e.updated_at as f64);
+            let w = watched.entry(e.content_key.clone()).or_default();
+            *w = w.max(weight);
+        }
+    });
+
+    let servers: Vec<_> = servers.read(|servers| {
+        servers
+            .iter()
+            .filter(|s| matches!(s.kind, ServerKind::Xtream | ServerKind::Emby))
+            .cloned()
+            .collect()
+    });
+    let mut items = Vec::new();
+    for server in &servers {
+        match crate::vod::items(&app, server).await {
+            Ok(list) => items.push(list),
+            Err(e) => tracing::warn!("No VOD index for {}: {}", server.name, e),
+        }
+    }
+    let all = || items.iter().flat_map(|list| list.iter());
+
+    let mut profile: BTreeMap<String, f64> = BTreeMap::new();
+    let mut seen: Vec<(&VodItem, Vec<(String, f64)>)> = Vec::new();
+    for item in all() {
+        let weight = watched.get(&item.content_key()).copied().or_else(|| {
+            // History from before content keys, or from other screens, matches by title
+            watched_titles
+                .contains(&item.name.to_lowercase())
+                .then_some(1.0)
+        });
+        let Some(weight) = weight else {
+            continue;
+        };
+        let features = features(item);
+        for (feature, w) in &features {
+            *profile.entry(feature.clone()).or_default() += w * weight;
+        }
+        seen.push((item, features));
+    }
+    if profile.is_empty() {
+        return Ok(Vec::new());
+    }
+    let seen_keys: BTreeSet<String> = seen.iter().map(|(i, _)| i.content_key()).collect();
+
+    let mut scored: Vec<(f64, &VodItem)> = all()
+        .filter(|item| !seen_keys.contains(&item.content_key()))
+        .filter_map(|item| {
+            let features = features(item);
+            if features.is_empty() {
+                return None;
+            }
+            let matched: f64 = features
+                .iter()
+                .filter_map(|(f, w)| profile.get(f).map(|p| p * w))
+                .sum();
+            // Normalizing by feature count keeps items with long cast lists from dominating
+            let score = matched / (features.len() as f64).sqrt()
+                * (1.0 + item.rating.unwrap_or(5.0) as f64 / 20.0);
+            (score > 0.0).then_some((score, item))
+        })
+        .collect();
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+    // The same title often exists on several servers; recommend it once
+    let mut names = BTreeSet::new();
+    scored.retain(|(_, item)| names.insert(item.name.to_lowercase()));
+    scored.truncate(limit);
+
+    Ok(scored
+        .into_iter()
+        .map(|(score, item)| {
+            let features = features(item);
+            let because = seen
+                .iter()
+                .map(|(w, f)| (similarity(&features, f), w))
+                .filter(|(s, _)| *s > 0.0)
+                .max_by(|a, b| a.0.total_cmp(&b.0))
+                .map(|(_, w)| w.name.clone());
+            Recommendation {
+                item: item.clone(),
+                score,
+                because,
+            }
+        })
+        .collect())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PickReason {
+    /// On a favorite channel.
+    Favorite,
+    /// A title watched live before.
+    Series,
+    /// A genre often watched.
+    Genre,
+    /// A channel often watched.
+    Channel,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OnNowPick {
+    pub programme: Programme,
+    pub channel_name: Option<String>,
+    pub logo: Option<String>,
+    pub score: f64,
+    pub reasons: Vec<PickReason>,
+}
+
+/// Programmes in the loaded guide airing now or within half an hour, best match first.
+fn on_now(app: &tauri::AppHandle, limit: usize) -> Vec<OnNowPick> {
+    let now = crate::now_secs() as i64;
+    let decay = |at: i64| 0.5f64.powf((now - at).max(0) as f64 / HALF_LIFE_SECS);
+
+    let mut genres = BTreeMap::new();
+    let mut channels: BTreeMap<(String, String), f64> = BTreeMap::new();
+    let mut titles = BTreeSet::new();
+    let mut total = 0.0;
+    app.state::<HistoryStore>().read(|sessions| {
+        for s in sessions.iter().filter(|s| s.kind == WatchKind::Live) {
+            let weight = s.watched_secs as f64 * decay(s.started_at);
+            total += weight;
+            if let Some(genre) = s.genre {
+                *genres.entry(genre).or_insert(0.0) += weight;
+            }
+            if let Some(channel_id) = &s.channel_id {
+                *channels
+                    .entry((s.server_id.clone(), channel_id.clone()))
+                    .or_default() += weight;
+            }
+            titles.insert(s.title.to_lowercase());
+        }
+    });
+    let share = |w: Option<&f64>| {
+        if total > 0.0 {
+            w.copied().unwrap_or(0.0) / total
+        } else {
+            0.0
+        }
+    };
+    let favorites = app.state::<FavoriteStore>();
+
+    let mut picks: Vec<OnNowPick> = crate::epg::airing_between(now, now + SOON_SECS)
+        .into_iter()
+        .filter(|p| p.stop - now >= MIN_LEFT_SECS)
+        .filter_map(|p| {
+            let mut score = 0.0;
+            let mut reasons = Vec::new();
+            if crate::favorites::is_favorite(&favorites, &p.server_id, &p.channel_id) {
+                score += FAVORITE_SCORE;
+                reasons.push(PickReason::Favorite);
+            }
+            if titles.contains(&p.title.to_lowercase()) {
+                score += SERIES_SCORE;
+                reasons.push(PickReason::Series);
+            }
+            let genre = p.genre.map_or(0.0, |g| share(genres.get(&g)));
+            if genre > 0.0 {
+                score += GENRE_SCORE * genre;
+                reasons.push(PickReason::Genre);
+            }
+            let channel = share(channels.get(&(p.server_id.clone(), p.channel_id.clone())));
+            if channel > 0.0 {
+                score += CHANNEL_SCORE * channel;
+                reasons.push(PickReason::Channel);
+            }
+            if p.start > now {
+                score *= SOON_FACTOR;
+            }
+            let channel = crate::catalog::lookup(&p.server_id, &p.channel_id);
+            (score > 0.0 && !channel.as_ref().is_some_and(|c| c.hidden)).then(|| OnNowPick {
+                channel_name: channel.as_ref().map(|c| c.name.clone()),
+                logo: channel.and_then(|c| c.logo),
+                programme: p,
+                score,
+                reasons,
+            })
+        })
+        .collect();
+    picks.sort_by(|a, b| b.score.total_cmp(&a.score));
+    picks.truncate(limit);
+    picks
+}
+
+/// Loads the guide around now for every server's favorite channels, unless saving data.
+async fn refresh_favorites_guide(app: &tauri::AppHandle) {
+    if crate::datausage::pause_prefetch(app) {
+        return;
+    }
+    let now = crate::now_secs() as i64;
+    let range = EpgRange {
+        from: now,
+        to: now + SOON_SECS + 3600,
+    };
+    let favorites = app.state::<FavoriteStore>().read(|f| f.clone());
+    for (server_id, channel_ids) in favorites {
+        let Ok(server) = crate::servers::get(&app.state::<ServerStore>(), &server_id) else {
+            continue;
+        };
+        if let Err(e) = crate::epg::load(app, &server, &channel_ids, range).await {
+            tracing::debug!("Guide refresh for {} failed: {}", server.name, e);
+        }
+    }
+}
+
+/// Refreshes on-now picks every five minutes.
+pub fn start(app: &tauri::AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            refresh_favorites_guide(&app).await;
+            let picks = on_now(&app, DEFAULT_LIMIT);
+            if !picks.is_empty() {
+                let _ = app.emit("on-now-updated", &picks);
+            }
+            tokio::time::sleep(ON_NOW_INTERVAL).await;
+        }
+    });
+}
+
+/// Programmes airing now or soon that match favorites and viewing habits.
+#[tauri::command]
+pub fn get_on_now_for_you(app: tauri::AppHandle, limit: Option<usize>) -> Vec<OnNowPick> {
+    on_now(&app, limit.unwrap_or(DEFAULT_LIMIT))
+}
@@ -0,0 +1,344 @@
+//! Instant recordings of what a video window is showing. The window's channel is fetched again
+//! from its server and copied with ffmpeg (no re-encoding) into the Videos folder, named after
+//! the programme airing, for the rest of that programme unless a duration is given. The
+//! recording counts against the server's connection limit. When the system suspends, ffmpeg is
+//! asked to finish the file, so the recording ends there rather than being cut off mid-write.
+//! When it ends it joins the library (`recordings`). `recording` events report each one
+//! starting and ending, for the recording indicator.
+
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, Command, Stdio};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::Serialize;
+use tauri::{Emitter, Manager, State};
+
+use crate::proxy::ProxyState;
+use crate::servers::ServerStore;
+use crate::settings::SettingsStore;
+
+/// Length of a recording when neither a duration nor the guide says how long.
+const DEFAULT_DURATION_SECS: u64 = 3600;
+/// Kept recording after the programme's scheduled end, since they often overrun.
+const END_PADDING_SECS: u64 = 120;
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// How long ffmpeg gets to finish the file after being asked to stop.
+const STOP_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActiveRecording {
+    pub label: String,
+    /// Video window the recording was started from.
+    pub window_label: String,
+    pub server_id: String,
+    pub channel_id: String,
+    pub channel_name: String,
+    pub title: String,
+    pub path: String,
+    /// Unix seconds.
+    pub started_at: i64,
+    pub ends_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RecordingEvent<'a> {
+    status: &'static str,
+    recording: &'a ActiveRecording,
+    error: Option<String>,
+}
+
+struct Recorder {
+    recording: ActiveRecording,
+    child: Child,
+    /// Relay the stream goes through, stopped with the recording.
+    relay: Option<String>,
+    /// Last line ffmpeg wrote to stderr, for the failure message.
+    error: Option<String>,
+}
+
+static RECORDERS: Mutex<BTreeMap<String, Recorder>> = Mutex::new(BTreeMap::new());
+
+fn recorders() -> std::sync::MutexGuard<'static, BTreeMap<String, Recorder>> {
+    RECORDERS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// `text` with the characters file systems refuse replaced.
+pub(crate) fn safe_file_name(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+/// `(year, month, day, hour, minute)` of `at` in the display time zone.
+pub(crate) fn local_time(app: &tauri::AppHandle, at: i64) -> (i64, u32, u32, u32, u32) {
+    let zone = crate::tz::zone(&crate::tz::display_zone_name(&app.state::<SettingsStore>())).ok();
+    let offset = zone.as_ref().map_or(0, |z| z.offset_at(at));
+    crate::tz::local_parts(at, offset)
+}
+
+/// `{title} {date} {time}.{extension}`, in the display time zone, with characters file systems
+/// refuse replaced.
+pub(crate) fn file_name(app: &tauri::AppHandle, title: &str, at: i64, extension: &str) -> String {
+    let (year, month, day, hour, minute) = local_time(app, at);
+    format!(
+        "{} {:04}-{:02}-{:02} {:02}-{:02}.{}",
+        safe_file_name(title),
+        year,
+        month,
+        day,
+        hour,
+        minute,
+        extension
+    )
+}
+
+fn emit(
+    app: &tauri::AppHandle,
+    status: &'static str,
+    recording: &ActiveRecording,
+    error: Option<String>,
+) {
+    let _ = app.emit(
+        "recording",
+        RecordingEvent {
+            status,
+            recording,
+            error,
+        },
+    );
+}
+
+/// Logs what ffmpeg reports on stderr, keeping the last line for the failure message.
+fn read_errors(label: String, stderr: std::process::ChildStderr) {
+    for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+        tracing::warn!(label = %label, "ffmpeg: {}", line);
+        if let Some(recorder) = recorders().get_mut(&label) {
+            recorder.error = Some(line);
+        }
+    }
+}
+
+/// Waits for the recording's ffmpeg to exit, then adds the file to the library. A suspend
+/// stops it, and holds the suspend for the few seconds ffmpeg needs to finish the file.
+fn watch(app: tauri::AppHandle, label: String) {
+    let hold = crate::power::hold();
+    let mut stopping = false;
+    let status = loop {
+        std::thread::sleep(POLL_INTERVAL);
+        if !stopping && crate::power::suspended() {
+            tracing::info!(label = %label, "Stopping the recording for suspend");
+            stopping = true;
+            let _ = stop_recording(label.clone());
+        }
+        let mut recorders = recorders();
+        let Some(recorder) = recorders.get_mut(&label) else {
+            return;
+        };
+        match recorder.child.try_wait() {
+            Ok(Some(status)) => break Ok(status),
+            Ok(None) => continue,
+            Err(e) => break Err(e.to_string()),
+        }
+    };
+    drop(hold);
+    let Some(recorder) = recorders().remove(&label) else {
+        return;
+    };
+    if let Some(relay) = &recorder.relay {
+        app.state::<ProxyState>().stop(relay);
+    }
+    crate::connections::release(&label);
+    let recording = recorder.recording;
+    let written = std::fs::metadata(&recording.path).is_ok_and(|m| m.len() > 0);
+    let error = match status {
+        _ if written => None,
+        Ok(status) => Some(match recorder.error {
+            Some(error) => format!("ffmpeg exited with {}: {}", status, error),
+            None => format!("ffmpeg exited with {} before writing anything", status),
+        }),
+        Err(e) => Some(e),
+    };
+    if let Some(error) = error {
+        tracing::warn!(label = %label, "Recording failed: {}", error);
+        let _ = std::fs::remove_file(&recording.path);
+        emit(&app, "failed", &recording, Some(error));
+        return;
+    }
+    tracing::info!(label = %label, path = %recording.path, "Recording finished");
+    tauri::async_runtime::block_on(async {
+        let added = crate::recordings::add(
+            &app,
+            recording.path.clone(),
+            recording.server_id.clone(),
+            recording.channel_id.clone(),
+            recording.channel_name.clone(),
+            recording.started_at,
+        )
+        .await;
+        emit(&app, "finished", &recording, added.err());
+    });
+}
+
+/// Starts recording the live channel shown in `window_label`, for `duration_secs` or until
+/// the current programme ends.
+#[tauri::command]
+pub async fn record_current(
+    app: tauri::AppHandle,
+    store: State<'_, ServerStore>,
+    proxy: State<'_, ProxyState>,
+    window_label: String,
+    duration_secs: Option<u64>,
+) -> Result<ActiveRecording, String> {
+    let (server_id, channel_id) = crate::zap::current(&window_label)
+        .map(|(server_id, channel_id, _)| (server_id, channel_id))
+        .ok_or_else(|| "The window is not playing a live channel".to_string())?;
+    if recorders()
+        .values()
+        .any(|r| r.recording.server_id == server_id && r.recording.channel_id == channel_id)
+    {
+        return Err("This channel is already being recorded".to_string());
+    }
+    let server = crate::servers::get(&store, &server_id)?;
+    let channel = crate::catalog::channels(&app, &server)
+        .await?
+        .1
+        .iter()
+        .find(|c| c.id == channel_id)
+        .cloned()
+        .ok_or_else(|| "The channel is no longer in the channel list".to_string())?;
+    let now = crate::now_secs() as i64;
+    let programme = crate::epg::airing_at(&server_id, &channel_id, now);
+    let duration = duration_secs
+        .or_else(|| {
+            programme
+                .as_ref()
+                .map(|p| (p.stop - now).max(0) as u64 + END_PADDING_SECS)
+        })
+        .unwrap_or(DEFAULT_DURATION_SECS);
+    if duration == 0 {
+        return Err("The recording needs a duration".to_string());
+    }
+    let title = programme
+        .as_ref()
+        .map(|p| p.title.clone())
+        .unwrap_or_else(|| channel.name.clone());
+    let dir = app
+        .path()
+        .video_dir()
+        .map_err(|e| e.to_string())?
+        .join("TvX");
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let path = dir.join(file_name(&app, &title, now, "ts"));
+
+    let reservation = crate::connections::reserve(&app, &server, &title, true, None).await?;
+    let label = format!("record-{}", uuid::Uuid::new_v4());
+    let url = crate::catalog::stream_url(&app, &server, &channel_id).await?;
+    let resolved = crate::resolve::resolve(Some(&server), &url).await;
+    let mut url = resolved.url.clone();
+    let mut relay = None;
+    if resolved.needs_relay(Some(&server)) {
+        let session = proxy.relay(&resolved, Some(&server))?;
+        url = session.url;
+        relay = Some(session.id);
+    }
+    let spawned = Command::new(crate::ffmpeg::ffmpeg_path()?)
+        .args(["-hide_banner", "-loglevel", "error"])
+        .args(crate::transcode::input_args(&url))
+        .args(["-map", "0", "-c", "copy", "-t"])
+        .arg(duration.to_string())
+        .args(["-f", "mpegts"])
+        .arg(&path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to start ffmpeg: {}", e));
+    let mut child = match spawned {
+        Ok(child) => child,
+        Err(e) => {
+            if let Some(relay) = &relay {
+                proxy.stop(relay);
+            }
+            return Err(e);
+        }
+    };
+    let recording = ActiveRecording {
+        label: label.clone(),
+        window_label,
+        server_id,
+        channel_id,
+        channel_name: channel.name.clone(),
+        title,
+        path: path.to_string_lossy().into_owned(),
+        started_at: now,
+        ends_at: now + duration as i64,
+    };
+    reservation.keep(&label);
+    let stderr = child.stderr.take();
+    recorders().insert(
+        label.clone(),
+        Recorder {
+            recording: recording.clone(),
+            child,
+            relay,
+            error: None,
+        },
+    );
+    if let Some(stderr) = stderr {
+        let error_label = label.clone();
+        std::thread::spawn(move || read_errors(error_label, stderr));
+    }
+    tracing::info!(label = %label, path = %recording.path, duration, "Recording started");
+    emit(&app, "started", &recording, None);
+    let watch_app = app.clone();
+    std::thread::spawn(move || watch(watch_app, label));
+    Ok(recording)
+}
+
+/// Recordings in progress.
+#[tauri::command]
+pub fn list_active_recordings() -> Vec<ActiveRecording> {
+    recorders().values().map(|r| r.recording.clone()).collect()
+}
+
+/// Ends a recording early; what was recorded so far is kept.
+#[tauri::command]
+pub fn stop_recording(label: String) -> Result<(), String> {
+    {
+        let mut recorders = recorders();
+        let recorder = recorders
+            .get_mut(&label)
+            .ok_or_else(|| format!("No recording {}", label))?;
+        // `q` lets ffmpeg finish the file; it is killed if it doesn't
+        if let Some(stdin) = recorder.child.stdin.as_mut() {
+            let _ = stdin.write_all(b"q");
+            let _ = stdin.flush();
+        }
+    }
+    std::thread::spawn(move || {
+        std::thread::sleep(STOP_TIMEOUT);
+        if let Some(recorder) = recorders().get_mut(&label) {
+            let _ = recorder.child.kill();
+        }
+    });
+    Ok(())
+}
+
+/// Stops every recording, for app exit.
+pub fn stop_all() {
+    for recorder in recorders().values_mut() {
+        let _ = recorder.child.kill();
+        let _ = recorder.child.wait();
+    }
+}
@@ -0,0 +1,276 @@
+//! Library of finished local recordings, persisted in `recordings.json`. Recorders add an entry
+//! when a file is complete; watched state comes from an explicit flag or the saved playback
+//! progress (key `recording:{id}`).
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tauri::{Manager, State};
+
+use crate::epg::Programme;
+use crate::progress::ProgressStore;
+use crate::settings::SettingsStore;
+use crate::store::JsonStore;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Recording {
+    pub id: String,
+    pub path: String,
+    pub server_id: String,
+    /// Catalog channel id (see `catalog::CatalogChannel::id`).
+    pub channel_id: String,
+    pub channel_name: String,
+    pub title: String,
+    /// Unix seconds.
+    pub started_at: i64,
+    /// From ffprobe; `None` if the file couldn't be probed.
+    pub duration_secs: Option<f64>,
+    /// The guide entry airing when the recording started, where the guide had one.
+    #[serde(default)]
+    pub programme: Option<Programme>,
+    /// Set by `set_recording_watched`; also counts as watched once mostly played.
+    #[serde(default)]
+    pub watched: bool,
+}
+
+impl Recording {
+    fn content_key(&self) -> String {
+        format!("recording:{}", self.id)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordingItem {
+    #[serde(flatten)]
+    pub recording: Recording,
+    pub position_secs: Option<f64>,
+    /// `None` when the file no longer exists.
+    pub size_bytes: Option<u64>,
+}
+
+pub type RecordingStore = JsonStore<Vec<Recording>>;
+
+pub fn open(app: &tauri::AppHandle) -> RecordingStore {
+    JsonStore::open(app, "recordings.json")
+}
+
+/// Adds a finished recording to the library, probing its duration and looking up the guide
+/// entry for its start, and files it the way media centers expect when library naming is on
+/// (see `library`). Recordings spanning several programmes get a chapter for each; ad
+/// break detection follows when enabled in Settings.
+pub async fn add(
+    app: &tauri::AppHandle,
+    path: String,
+    server_id: String,
+    channel_id: String,
+    channel_name: String,
+    started_at: i64,
+) -> Result<Recording, String> {
+    let duration_secs = match crate::probe::probe(path.clone()).await {
+        Ok(probe) => probe.duration_secs,
+        Err(e) => {
+            tracing::warn!("Could not probe recording {}: {}", path, e);
+            None
+        }
+    };
+    let programme = crate::epg::airing_at(&server_id, &channel_id, started_at);
+    let path = match &programme {
+        Some(programme) if app.state::<SettingsStore>().read(|s| s.library_naming) => {
+            match crate::library::organize(app, Path::new(&path), programme, &channel_name) {
+                Ok(organized) => organized.to_string_lossy().into_owned(),
+                Err(e) => {
+                    tracing::warn!("Could not file {} in the library layout: {}", path, e);
+                    path
+                }
+            }
+        }
+        _ => path,
+    };
+    let recording = Recording {
+        id: uuid::Uuid::new_v4().to_string(),
+        title: programme
+            .as_ref()
+            .map(|p| p.title.clone())
+            .unwrap_or_else(|| channel_name.clone()),
+        path,
+        server_id,
+        channel_id,
+        channel_name,
+        started_at,
+        duration_secs,
+        programme,
+        watched: false,
+    };
+    app.state::<RecordingStore>()
+        .update(|recordings| recordings.push(recording.clone()))?;
+    let programme_chapters = recording.duration_secs.map_or_else(Vec::new, |duration| {
+        let programmes = crate::epg::airing_on(
+            &recording.server_id,
+            &recording.channel_id,
+            started_at,
+            started_at + duration.ceil() as i64,
+        );
+        crate::chapters::programme_chapters(&programmes, started_at, duration)
+    });
+    let detect = app.state::<SettingsStore>().read(|s| s.detect_commercials);
+    if !programme_chapters.is_empty() || detect {
+        let app = app.clone();
+        let (id, path) = (recording.id.clone(), PathBuf::from(&recording.path));
+        // Chapters come first: detection names the stretches between breaks after them
+        tauri::async_runtime::spawn_blocking(move || {
+            if !programme_chapters.is_empty() {
+                match crate::chapters::write(&path, &programme_chapters) {
+                    Ok(_) => tracing::info!(
+                        "Marked {} programmes in {}",
+                        programme_chapters.len(),
+                        path.display()
+                    ),
+                    Err(e) => tracing::warn!("Could not write chapters: {}", e),
+                }
+            }
+            if detect {
+                crate::chapters::spawn_detection(&app, id);
+            }
+        });
+    }
+    Ok(recording)
+}
+
+fn find(store: &RecordingStore, id: &str) -> Result<Recording, String> {
+    store
+        .read(|recordings| recordings.iter().find(|r| r.id == id).cloned())
+        .ok_or_else(|| "Recording not found".to_string())
+}
+
+/// Finished recordings, newest first.
+#[tauri::command]
+pub fn list_recordings(
+    store: State<'_, RecordingStore>,
+    progress: State<'_, ProgressStore>,
+) -> Vec<RecordingItem> {
+    let mut items: Vec<RecordingItem> = store
+        .read(|recordings| recordings.clone())
+        .into_iter()
+        .map(|mut recording| {
+            let entry = progress.read(|entries| entries.get(&recording.content_key()).cloned());
+            recording.watched |= entry.as_ref().is_some_and(|e| e.watched);
+            let position_secs = entry.map(|e| e.position_secs);
+            RecordingItem {
+                size_bytes: std::fs::metadata(&recording.path).ok().map(|m| m.len()),
+                position_secs,
+                recording,
+            }
+        })
+        .collect();
+    items.sort_by_key(|item| std::cmp::Reverse(item.recording.started_at));
+    items
+}
+
+/// Opens a recording in a video window, resuming from its saved position. Returns the label.
+#[tauri::command]
+pub async fn play_recording(
+    app: tauri::AppHandle,
+    store: State<'_, RecordingStore>,
+    id: String,
+) -> Result<String, String> {
+    let recording = find(&store, &id)?;
+    if !Path::new(&recording.path).exists() {
+        return Err(format!("{} no longer exists", recording.path));
+    }
+    crate::create_video_window_with(
+        &app,
+        &recording.title,
+        &recording.path,
+        &[("contentKey", recording.content_key())],
+    )
+}
+
+pub(crate) fn set_watched(store: &RecordingStore, id: &str, watched: bool) -> Result<(), String> {
+    store.update(|recordings| {
+        if let Some(r) = recordings.iter_mut().find(|r| r.id == id) {
+            r.watched = watched;
+        }
+    })
+}
+
+#[tauri::command]
+pub fn set_recording_watched(
+    store: State<'_, RecordingStore>,
+    progress: State<'_, ProgressStore>,
+    id: String,
+    watched: bool,
+) -> Result<(), String> {
+    let key = find(&store, &id)?.content_key();
+    set_watched(&store, &id, watched)?;
+    // Unwatching also forgets the position, or a mostly played recording would stay watched
+    if !watched {
+        progress.update(|entries| entries.remove(&key))?;
+    }
+    Ok(())
+}
+
+/// Deletes the file, its chapters and NFO, and removes the recording from the library. A file
+/// already gone is fine.
+#[tauri::command]
+pub fn delete_recording(
+    store: State<'_, RecordingStore>,
+    progress: State<'_, ProgressStore>,
+    id: String,
+) -> Result<(), String> {
+    let recording = find(&store, &id)?;
+    match std::fs::remove_file(&recording.path) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(format!("Failed to delete {}: {}", recording.path, e)),
+    }
+    let _ = std::fs::remove_file(crate::chapters::sidecar_path(Path::new(&recording.path)));
+    let _ = std::fs::remove_file(crate::library::nfo_path(Path::new(&recording.path)));
+    store.update(|recordings| recordings.retain(|r| r.id != id))?;
+    progress.update(|entries| entries.remove(&recording.content_key()))?;
+    Ok(())
+}
+
+/// Renames the file, with its chapters and NFO, within its folder. `name` is the new file
+/// name; the extension is kept when `name` has none.
+#[tauri::command]
+pub fn rename_recording(
+    store: State<'_, RecordingStore>,
+    id: String,
+    name: String,
+) -> Result<Recording, String> {
+    let name = name.trim();
+    if name.is_empty() || name.contains(['/', '\\']) || name == "." || name == ".." {
+        return Err(format!("\"{}\" is not a valid file name", name));
+    }
+    let recording = find(&store, &id)?;
+    let old = PathBuf::from(&recording.path);
+    let mut new = old.with_file_name(name);
+    if new.extension().is_none() {
+        if let Some(ext) = old.extension() {
+            new.set_extension(ext);
+        }
+    }
+    if new.exists() {
+        return Err(format!("{} already exists", new.display()));
+    }
+    std::fs::rename(&old, &new)
+        .map_err(|e| format!("Failed to rename {}: {}", old.display(), e))?;
+    let sidecar = crate::chapters::sidecar_path(&old);
+    if sidecar.exists() {
+        let _ = std::fs::rename(&sidecar, crate::chapters::sidecar_path(&new));
+    }
+    let nfo = crate::library::nfo_path(&old);
+    if nfo.exists() {
+        let _ = std::fs::rename(&nfo, crate::library::nfo_path(&new));
+    }
+    let path = new.to_string_lossy().into_owned();
+    store
+        .update(|recordings| {
+            let r = recordings.iter_mut().find(|r| r.id == id)?;
+            r.path = path;
+            Some(r.clone())
+        })?
+        .ok_or_else(|| "Recording not found".to_string())
+}
@@ -0,0 +1,103 @@
+//! Reminders for upcoming programmes, persisted in `reminders.json`. A background thread
+//! emits `epg-reminder` with the reminder once its programme is about to start.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, Manager, State};
+
+use crate::epg::Programme;
+use crate::store::JsonStore;
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+const DEFAULT_LEAD_MINUTES: u32 = 5;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Reminder {
+    pub id: String,
+    pub programme: Programme,
+    /// Minutes before the start to remind.
+    pub lead_minutes: u32,
+}
+
+impl Reminder {
+    fn due_at(&self) -> i64 {
+        self.programme.start - i64::from(self.lead_minutes) * 60
+    }
+}
+
+pub type ReminderStore = JsonStore<Vec<Reminder>>;
+
+pub fn open(app: &tauri::AppHandle) -> ReminderStore {
+    JsonStore::open(app, "reminders.json")
+}
+
+pub fn start(app: &tauri::AppHandle) {
+    let app = app.clone();
+    std::thread::spawn(move || loop {
+        let now = crate::now_secs() as i64;
+        let store = app.state::<ReminderStore>();
+        let due: Vec<Reminder> = store.read(|reminders| {
+            reminders
+                .iter()
+                .filter(|r| r.due_at() <= now)
+                .cloned()
+                .collect()
+        });
+        if !due.is_empty() {
+            let _ = store.update(|reminders| reminders.retain(|r| r.due_at() > now));
+            for reminder in due {
+                // Reminders for programmes that ended while the app was closed are dropped quietly
+                if reminder.programme.stop > now {
+                    let _ = app.emit("epg-reminder", reminder);
+                }
+            }
+        }
+        std::thread::sleep(CHECK_INTERVAL);
+    });
+}
+
+/// Sets a reminder for a programme from the guide or `search_epg`. A programme already
+/// reminded of gets its lead time updated instead of a second reminder.
+#[tauri::command]
+pub fn add_epg_reminder(
+    store: State<'_, ReminderStore>,
+    programme: Programme,
+    lead_minutes: Option<u32>,
+) -> Result<Reminder, String> {
+    if programme.stop <= crate::now_secs() as i64 {
+        return Err(format!("\"{}\" has already aired", programme.title));
+    }
+    let lead_minutes = lead_minutes.unwrap_or(DEFAULT_LEAD_MINUTES);
+    store.update(|reminders| {
+        let same = |r: &&mut Reminder| {
+            r.programme.server_id == programme.server_id
+                && r.programme.channel_id == programme.channel_id
+                && r.programme.start == programme.start
+        };
+        if let Some(existing) = reminders.iter_mut().find(same) {
+            existing.lead_minutes = lead_minutes;
+            return existing.clone();
+        }
+        let reminder = Reminder {
+            id: uuid::Uuid::new_v4().to_string(),
+            programme,
+            lead_minutes,
+        };
+        reminders.push(reminder.clone());
+        reminder
+    })
+}
+
+#[tauri::command]
+pub fn list_epg_reminders(store: State<'_, ReminderStore>) -> Vec<Reminder> {
+    let mut reminders = store.read(|reminders| reminders.clone());
+    reminders.sort_by_key(|r| r.programme.start);
+    reminders
+}
+
+#[tauri::command]
+pub fn remove_epg_reminder(store: State<'_, ReminderStore>, id: String) -> Result<(), String> {
+    store.update(|reminders| reminders.retain(|r| r.id != id))
+}
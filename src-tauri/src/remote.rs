@@ -0,0 +1,588 @@
+//! REST API for remote control from phones, home automation or scripts. Off by default;
+//! when enabled it listens on the LAN, refuses peers outside private and loopback networks,
+//! and every request needs the bearer token shown in Settings (`Authorization: Bearer
+//! <token>`). `GET /api/v1/events` upgrades to a WebSocket pushing live state; browsers can't
+//! set headers on those, so the upgrade also takes `?token=`. Playback is limited to catalog
+//! channels: remotes pick a server and channel id, never a URL or path.
+
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpStream};
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, Manager, State};
+
+use crate::feed::EventFeed;
+use crate::httpd::{self, Request};
+use crate::mpv::MpvState;
+use crate::pairing::{self, PairedDeviceStore};
+use crate::servers::ServerStore;
+use crate::settings::SettingsStore;
+use crate::websocket;
+
+fn default_port() -> u16 {
+    8765
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RemoteApiSettings {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    #[serde(default)]
+    pub token: String,
+}
+
+impl Default for RemoteApiSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: default_port(),
+            token: String::new(),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct RemoteState {
+    server: Mutex<Option<httpd::Server>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RemoteApiStatus {
+    pub enabled: bool,
+    pub running: bool,
+    pub port: u16,
+    pub token: String,
+}
+
+/// Commands understood by every player window, whichever engine it uses.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "camelCase")]
+pub enum PlayerCommand {
+    Play,
+    Pause,
+    TogglePause,
+    /// Absolute position in seconds.
+    Seek {
+        position: f64,
+    },
+    /// Relative seek in seconds.
+    SeekBy {
+        seconds: f64,
+    },
+    /// 0-100.
+    Volume {
+        level: f64,
+    },
+    Mute {
+        muted: bool,
+    },
+    Fullscreen {
+        enabled: bool,
+    },
+    /// Replaces the stream in place, e.g. with the next episode.
+    Load {
+        url: String,
+        #[serde(default)]
+        content_key: Option<String>,
+    },
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct WindowInfo {
+    label: String,
+    title: String,
+    engine: &'static str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ServerInfo {
+    id: String,
+    name: String,
+    kind: crate::servers::ServerKind,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PlayRequest {
+    server_id: String,
+    channel_id: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ChannelRequest {
+    server_id: String,
+    channel_id: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RecordRequest {
+    programme: crate::epg::Programme,
+    #[serde(default)]
+    strategy: Option<crate::conflicts::ConflictStrategy>,
+    #[serde(default)]
+    priority: Option<u32>,
+}
+
+/// Sends `command` to video window `label`: mpv windows are driven directly, webview players
+/// get a `player-command` event.
+pub fn send_player_command(
+    app: &tauri::AppHandle,
+    label: &str,
+    command: &PlayerCommand,
+) -> Result<(), String> {
+    let window = app
+        .get_webview_window(label)
+        .ok_or_else(|| format!("No window {}", label))?;
+    if let PlayerCommand::Fullscreen { enabled } = command {
+        return window.set_fullscreen(*enabled).map_err(|e| e.to_string());
+    }
+    let mpv = app.state::<MpvState>();
+    if mpv.has(label) {
+        return mpv.control(label, command);
+    }
+    window
+        .emit_to(label, "player-command", command.clone())
+        .map_err(|e| e.to_string())
+}
+
+pub fn video_windows(app: &tauri::AppHandle) -> Vec<tauri::WebviewWindow> {
+    let mut windows: Vec<tauri::WebviewWindow> = app
+        .webview_windows()
+        .into_values()
+        .filter(|w| w.label().starts_with("video-"))
+        .collect();
+    windows.sort_by(|a, b| a.label().cmp(b.label()));
+    windows
+}
+
+/// Whether `ip` is on this machine or a private network: loopback, RFC 1918, link-local and
+/// IPv6 unique local addresses.
+fn is_local_peer(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => v4.is_loopback() || v4.is_private() || v4.is_link_local(),
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => is_local_peer(IpAddr::V4(v4)),
+            None => {
+                v6.is_loopback()
+                    || (v6.segments()[0] & 0xfe00) == 0xfc00
+                    || (v6.segments()[0] & 0xffc0) == 0xfe80
+            }
+        },
+    }
+}
+
+/// The token a request presents. `?token=` counts only when `query` is set (WebSocket
+/// upgrades), so tokens stay out of ordinary request URLs.
+fn presented_token(req: &Request, query: bool) -> Option<&str> {
+    req.header("authorization")
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .or_else(|| query.then(|| req.query_param("token")).flatten())
+}
+
+/// Accepts the main token from Settings or a paired device's token.
+fn authorized(app: &tauri::AppHandle, req: &Request, query: bool) -> bool {
+    let Some(presented) = presented_token(req, query) else {
+        return false;
+    };
+    let token = app
+        .state::<SettingsStore>()
+        .read(|s| s.remote_api.token.clone());
+    (!token.is_empty() && pairing::tokens_match(presented, &token))
+        || pairing::authorize(&app.state::<PairedDeviceStore>(), presented)
+}
+
+/// Open video windows and app version, shared by the REST, WebSocket and MQTT surfaces.
+pub fn status_json(app: &tauri::AppHandle) -> serde_json::Value {
+    let mpv = app.state::<MpvState>();
+    let windows: Vec<WindowInfo> = video_windows(app)
+        .iter()
+        .map(|w| WindowInfo {
+            label: w.label().to_string(),
+            title: w.title().unwrap_or_default(),
+            engine: if mpv.has(w.label()) { "mpv" } else { "webview" },
+        })
+        .collect();
+    serde_json::json!({
+        "app": "TvX",
+        "version": app.package_info().version.to_string(),
+        "windows": windows,
+    })
+}
+
+/// Serves the event feed on an upgraded connection: a `status` snapshot and the guide's now
+/// and next for each window first, then every feed event, with a ping every 30s so dead
+/// clients are noticed.
+fn stream_events(app: &tauri::AppHandle, mut stream: TcpStream, req: &Request) {
+    if websocket::accept(&mut stream, req).is_err() {
+        return;
+    }
+    // Clients may stay quiet for long; pings notice the dead ones instead
+    if stream.set_read_timeout(None).is_err() {
+        return;
+    }
+    let Ok(reader) = stream.try_clone() else {
+        return;
+    };
+    let writer = Arc::new(Mutex::new(stream));
+    let events = app.state::<EventFeed>().subscribe();
+    let mut snapshot = vec![serde_json::json!({ "event": "status", "payload": status_json(app) })];
+    if !crate::lock::is_locked() {
+        snapshot.extend(video_windows(app).iter().filter_map(|w| {
+            let payload = crate::feed::now_next(w.label())?;
+            Some(serde_json::json!({ "event": "epg-now-next", "payload": payload }))
+        }));
+    }
+    for message in snapshot {
+        if websocket::send_text(&writer, &message.to_string()).is_err() {
+            return;
+        }
+    }
+    let pongs = writer.clone();
+    std::thread::spawn(move || websocket::read_loop(reader, pongs, |_| {}));
+    loop {
+        let sent = match events.recv_timeout(Duration::from_secs(30)) {
+            // Nothing is pushed while the app is locked
+            Ok(_) if crate::lock::is_locked() => Ok(()),
+            Ok(message) => websocket::send_text(&writer, &message),
+            Err(RecvTimeoutError::Timeout) => websocket::send_ping(&writer),
+            Err(RecvTimeoutError::Disconnected) => return,
+        };
+        if sent.is_err() {
+            let _ = writer
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .shutdown(std::net::Shutdown::Both);
+            return;
+        }
+    }
+}
+
+/// Plays a catalog channel the way the app's own channel list does. Only ids are accepted, so
+/// a remote can't make the app open arbitrary URLs or local files. Returns the video window's
+/// label, `None` when VLC plays it.
+async fn play_channel(app: &tauri::AppHandle, play: PlayRequest) -> Result<Option<String>, String> {
+    let server = crate::servers::get(&app.state::<ServerStore>(), &play.server_id)?;
+    let channel = crate::catalog::channels(app, &server)
+        .await?
+        .1
+        .iter()
+        .find(|c| c.id == play.channel_id)
+        .cloned()
+        .ok_or_else(|| "The channel is not in the channel list".to_string())?;
+    let url = crate::catalog::stream_url(app, &server, &channel.id).await?;
+    crate::open_video_window(app.clone(), channel.name, url, None, Some(server), None).await
+}
+
+/// A player command from a remote. `Load` is refused: it would let a remote play any URL or
+/// local file, while remotes only pick catalog channels.
+fn remote_command(req: &Request) -> Result<PlayerCommand, String> {
+    match req.json()? {
+        PlayerCommand::Load { .. } => {
+            Err("Remotes can't load URLs; play a channel instead".to_string())
+        }
+        command => Ok(command),
+    }
+}
+
+fn route(app: &tauri::AppHandle, req: &Request, stream: &mut TcpStream) {
+    let segments: Vec<&str> = req.path.trim_matches('/').split('/').collect();
+    match (req.method.as_str(), segments.as_slice()) {
+        ("GET", ["api", "v1", "status"]) => httpd::respond_json(stream, 200, &status_json(app)),
+        ("GET", ["api", "v1", "servers"]) => {
+            let servers: Vec<ServerInfo> = app.state::<ServerStore>().read(|servers| {
+                servers
+                    .iter()
+                    .map(|s| ServerInfo {
+                        id: s.id.clone(),
+                        name: s.name.clone(),
+                        kind: s.kind,
+                    })
+                    .collect()
+            });
+            httpd::respond_json(stream, 200, &servers);
+        }
+        ("GET", ["api", "v1", "shortcuts"]) => {
+            let shortcuts = crate::shortcuts::list(&app.state::<SettingsStore>());
+            httpd::respond_json(stream, 200, &shortcuts);
+        }
+        ("GET", ["api", "v1", "favorites"]) => {
+            let favorites = app
+                .state::<crate::favorites::FavoriteStore>()
+                .read(|f| f.clone());
+            httpd::respond_json(stream, 200, &favorites);
+        }
+        ("POST", ["api", "v1", "play"]) => {
+            let play: PlayRequest = match req.json() {
+                Ok(play) => play,
+                Err(e) => return httpd::respond_error(stream, 400, &e),
+            };
+            let result = tauri::async_runtime::block_on(play_channel(app, play));
+            match result {
+                Ok(label) => {
+                    httpd::respond_json(stream, 201, &serde_json::json!({ "label": label }))
+                }
+                Err(e) => {
+                    tracing::warn!("Remote play request failed: {}", e);
+                    httpd::respond_error(stream, 500, &e)
+                }
+            }
+        }
+        ("POST", ["api", "v1", "windows", label, "channel"]) => {
+            let channel: ChannelRequest = match req.json() {
+                Ok(channel) => channel,
+                Err(e) => return httpd::respond_error(stream, 400, &e),
+            };
+            if app.get_webview_window(label).is_none() || !label.starts_with("video-") {
+                return httpd::respond_error(stream, 404, "No such window");
+            }
+            let result = tauri::async_runtime::block_on(crate::zap::tune_channel(
+                app,
+                label,
+                &channel.server_id,
+                &channel.channel_id,
+            ));
+            match result {
+                Ok(change) => httpd::respond_json(stream, 200, &change),
+                Err(e) => httpd::respond_error(stream, 400, &e),
+            }
+        }
+        ("POST", ["api", "v1", "recordings"]) => {
+            let record: RecordRequest = match req.json() {
+                Ok(record) => record,
+                Err(e) => return httpd::respond_error(stream, 400, &e),
+            };
+            let result = tauri::async_runtime::block_on(crate::conflicts::record(
+                app.state(),
+                record.programme,
+                record.strategy,
+                record.priority,
+            ));
+            match result {
+                Ok(outcome) => httpd::respond_json(stream, 201, &outcome),
+                Err(e) => httpd::respond_error(stream, 400, &e),
+            }
+        }
+        ("POST", ["api", "v1", "windows", label, "command"]) => {
+            let command = match remote_command(req) {
+                Ok(command) => command,
+                Err(e) => return httpd::respond_error(stream, 400, &e),
+            };
+            if !label.starts_with("video-") {
+                return httpd::respond_error(stream, 404, "No such window");
+            }
+            match send_player_command(app, label, &command) {
+                Ok(()) => httpd::respond(stream, 204, "text/plain", b""),
+                Err(e) => httpd::respond_error(stream, 404, &e),
+            }
+        }
+        ("DELETE", ["api", "v1", "windows", label]) => match app.get_webview_window(label) {
+            Some(window) if label.starts_with("video-") => {
+                let _ = window.close();
+                httpd::respond(stream, 204, "text/plain", b"");
+            }
+            _ => httpd::respond_error(stream, 404, "No such window"),
+        },
+        _ => httpd::respond_error(stream, 404, "Not found"),
+    }
+}
+
+fn handle(app: &tauri::AppHandle, mut stream: TcpStream) {
+    // The listener is on every interface; only LAN and local clients may use it
+    match stream.peer_addr() {
+        Ok(peer) if is_local_peer(peer.ip()) => {}
+        peer => {
+            tracing::warn!(
+                "Refused remote API connection from {:?}",
+                peer.map(|p| p.ip())
+            );
+            return httpd::respond_error(
+                &mut stream,
+                403,
+                "Only local network clients are allowed",
+            );
+        }
+    }
+    let req = match httpd::read_request(&stream) {
+        Ok(req) => req,
+        Err(e) => return httpd::respond_error(&mut stream, 400, &e),
+    };
+    if req.method == "OPTIONS" {
+        return httpd::respond(&mut stream, 204, "text/plain", b"");
+    }
+    // The app lock covers remotes too, pairing included
+    if crate::lock::is_locked() {
+        return httpd::respond_error(&mut stream, 403, "The app is locked");
+    }
+    // The only unauthenticated endpoint: trading a pairing code for a device token
+    if req.method == "POST" && req.path == "/api/v1/pair" {
+        let result = req.json().and_then(|pair| {
+            pairing::pair(app, &app.state(), &app.state::<PairedDeviceStore>(), pair)
+        });
+        return match result {
+            Ok(device) => httpd::respond_json(&mut stream, 201, &device),
+            Err(e) => httpd::respond_error(&mut stream, 403, &e),
+        };
+    }
+    let upgrade = req.path == "/api/v1/events" && websocket::is_upgrade(&req);
+    if !authorized(app, &req, upgrade) {
+        return httpd::respond_error(&mut stream, 401, "Missing or invalid token");
+    }
+    if upgrade {
+        return stream_events(app, stream, &req);
+    }
+    route(app, &req, &mut stream);
+}
+
+/// Starts or stops the server to match the saved settings, creating a token on first use.
+pub fn apply(app: &tauri::AppHandle) -> Result<(), String> {
+    let settings = app.state::<SettingsStore>();
+    let config = settings.update(|s| {
+        if s.remote_api.token.is_empty() {
+            s.remote_api.token = uuid::Uuid::new_v4().simple().to_string();
+        }
+        s.remote_api.clone()
+    })?;
+    let state = app.state::<RemoteState>();
+    let mut server = state.server.lock().unwrap_or_else(|e| e.into_inner());
+    // Drop the old listener first so the port can be reused
+    *server = None;
+    if config.enabled {
+        let handle_app = app.clone();
+        let addr = SocketAddr::from((Ipv4Addr::UNSPECIFIED, config.port));
+        *server = Some(httpd::serve(addr, move |stream| {
+            handle(&handle_app, stream)
+        })?);
+    }
+    Ok(())
+}
+
+fn status(settings: &SettingsStore, state: &RemoteState) -> RemoteApiStatus {
+    let config = settings.read(|s| s.remote_api.clone());
+    let server = state.server.lock().unwrap_or_else(|e| e.into_inner());
+    RemoteApiStatus {
+        enabled: config.enabled,
+        running: server.is_some(),
+        port: server.as_ref().map_or(config.port, |s| s.port()),
+        token: config.token,
+    }
+}
+
+#[tauri::command]
+pub fn get_remote_api(
+    settings: State<'_, SettingsStore>,
+    state: State<'_, RemoteState>,
+) -> RemoteApiStatus {
+    status(&settings, &state)
+}
+
+#[tauri::command]
+pub fn configure_remote_api(
+    app: tauri::AppHandle,
+    settings: State<'_, SettingsStore>,
+    state: State<'_, RemoteState>,
+    enabled: bool,
+    port: Option<u16>,
+) -> Result<RemoteApiStatus, String> {
+    settings.update(|s| {
+        s.remote_api.enabled = enabled;
+        if let Some(port) = port {
+            s.remote_api.port = port;
+        }
+    })?;
+    apply(&app)?;
+    Ok(status(&settings, &state))
+}
+
+/// Invalidates the current token; remote clients must be set up again.
+#[tauri::command]
+pub fn reset_remote_token(
+    app: tauri::AppHandle,
+    settings: State<'_, SettingsStore>,
+    state: State<'_, RemoteState>,
+) -> Result<RemoteApiStatus, String> {
+    settings.update(|s| s.remote_api.token.clear())?;
+    apply(&app)?;
+    Ok(status(&settings, &state))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(headers: &[(&str, &str)], query: &[(&str, &str)], body: &str) -> Request {
+        let pairs = |list: &[(&str, &str)]| {
+            list.iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        };
+        Request {
+            method: "POST".to_string(),
+            path: "/api/v1/windows/video-1/command".to_string(),
+            query: pairs(query),
+            headers: pairs(headers),
+            body: body.as_bytes().to_vec(),
+        }
+    }
+
+    #[test]
+    fn tokens_come_from_the_bearer_header() {
+        let req = request(&[("Authorization", "Bearer abc")], &[("token", "xyz")], "");
+        assert_eq!(presented_token(&req, false), Some("abc"));
+        assert_eq!(presented_token(&req, true), Some("abc"));
+        let basic = request(&[("authorization", "Basic abc")], &[], "");
+        assert_eq!(presented_token(&basic, false), None);
+    }
+
+    #[test]
+    fn query_tokens_only_count_for_upgrades() {
+        let req = request(&[], &[("token", "xyz")], "");
+        assert_eq!(presented_token(&req, false), None);
+        assert_eq!(presented_token(&req, true), Some("xyz"));
+        assert_eq!(presented_token(&request(&[], &[], ""), true), None);
+    }
+
+    #[test]
+    fn remote_commands_cannot_load_urls() {
+        let load = request(
+            &[],
+            &[],
+            r#"{"action":"load","url":"file:///etc/passwd","contentKey":null}"#,
+        );
+        assert!(remote_command(&load).is_err());
+        let seek = request(&[], &[], r#"{"action":"seekBy","seconds":-10}"#);
+        assert!(matches!(
+            remote_command(&seek),
+            Ok(PlayerCommand::SeekBy { seconds }) if seconds == -10.0
+        ));
+        assert!(remote_command(&request(&[], &[], r#"{"action":"reboot"}"#)).is_err());
+    }
+
+    #[test]
+    fn only_private_peers_are_local() {
+        for ip in [
+            "127.0.0.1",
+            "192.168.1.20",
+            "10.0.0.5",
+            "172.16.3.4",
+            "169.254.1.1",
+        ] {
+            assert!(is_local_peer(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in ["::1", "fd00::1", "fe80::1", "::ffff:192.168.1.20"] {
+            assert!(is_local_peer(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in ["8.8.8.8", "172.32.0.1", "2001:db8::1", "::ffff:8.8.8.8"] {
+            assert!(!is_local_peer(ip.parse().unwrap()), "{}", ip);
+        }
+    }
+}
@@ -0,0 +1,174 @@
+//! Stream links that aren't the stream itself. Many providers hand out a link that redirects,
+//! sometimes several times, to a CDN URL with a short-lived token, and some answer with the
+//! tokenized URL in the body (plain text or a small JSON object) instead of redirecting.
+//! Cookies set along the way can be required by the final host. The link is resolved when
+//! playback starts, never cached, so the player and the relay always get a fresh URL. Relays
+//! send the collected cookies, each only to the hosts and paths it was set for; the webview and
+//! ffmpeg can't, so streams that need them are relayed. URLs that plainly name a media file
+//! aren't fetched at all.
+
+use crate::cookies::Cookie;
+use crate::diagnostics::sanitize_url;
+use crate::servers::ServerConfig;
+
+/// Redirects and link responses followed before giving up.
+const MAX_HOPS: usize = 10;
+/// Bodies larger than this are a stream or a page, not a link.
+const MAX_LINK_BYTES: u64 = 16 * 1024;
+/// Fields of a JSON link response that can hold the URL, at the top level or under `data` or `result`.
+const LINK_FIELDS: [&str; 6] = ["url", "stream_url", "streamUrl", "src", "link", "location"];
+/// Extensions of stream and media URLs.
+const MEDIA_EXTENSIONS: [&str; 12] = [
+    "m3u8", "mpd", "ts", "mp4", "m4v", "mkv", "webm", "mov", "avi", "flv", "mp3", "aac",
+];
+
+#[derive(Debug, Clone)]
+pub struct Resolved {
+    pub url: String,
+    /// `Cookie` header for the stream, from cookies set while resolving it.
+    pub cookie: Option<String>,
+}
+
+impl Resolved {
+    /// Whether the stream has to go through the local relay, which sends the cookies it needs.
+    pub fn needs_relay(&self, server: Option<&ServerConfig>) -> bool {
+        self.url.starts_with("http")
+            && (self.cookie.is_some()
+                || crate::http::needs_relay(server)
+                || reqwest::Url::parse(&self.url)
+                    .is_ok_and(|u| crate::cookies::header_for(&u).is_some()))
+    }
+}
+
+/// The absolute http(s) URL in a link response's body, if that is all it holds.
+fn link_in(body: &str, json: bool) -> Option<String> {
+    let candidate = if json {
+        let value: serde_json::Value = serde_json::from_str(body).ok()?;
+        let link = [&value, &value["data"], &value["result"]]
+            .into_iter()
+            .find_map(|v| LINK_FIELDS.iter().find_map(|f| v[*f].as_str()))?
+            .to_string();
+        link
+    } else {
+        body.trim().to_string()
+    };
+    let url = reqwest::Url::parse(&candidate).ok()?;
+    (matches!(url.scheme(), "http" | "https") && !candidate.contains(char::is_whitespace))
+        .then_some(candidate)
+}
+
+/// Whether `url` plainly names a stream or media file, which is played rather than fetched.
+fn is_media(url: &reqwest::Url) -> bool {
+    let path = url.path().to_ascii_lowercase();
+    path.rsplit_once('.')
+        .is_some_and(|(_, extension)| MEDIA_EXTENSIONS.contains(&extension))
+}
+
+fn store_cookies(cookies: &mut Vec<Cookie>, resp: &reqwest::Response, now: u64) {
+    let received = resp
+        .headers()
+        .get_all(reqwest::header::SET_COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .filter_map(|h| crate::cookies::parse(h, resp.url(), now))
+        .collect();
+    crate::cookies::merge(cookies, received, now);
+}
+
+async fn follow(server: Option<&ServerConfig>, url: &str) -> Result<Resolved, String> {
+    let client = crate::http::resolve_client(server)?;
+    let mut cookies = Vec::new();
+    let now = crate::now_secs();
+    let start = reqwest::Url::parse(url).map_err(|e| format!("Invalid URL: {}", e))?;
+    let mut current = start.clone();
+    for _ in 0..MAX_HOPS {
+        // A hop to a media file is the stream: played, like the link itself would be, rather
+        // than opened here
+        if current != start && is_media(&current) {
+            return Ok(Resolved {
+                cookie: crate::cookies::header(&cookies, &current, now),
+                url: current.to_string(),
+            });
+        }
+        let mut req = client.get(current.clone());
+        if let Some(header) = crate::cookies::header(&cookies, &current, now) {
+            req = req.header(reqwest::header::COOKIE, header);
+        }
+        let resp = crate::http::send(req).await.map_err(|e| e.to_string())?;
+        store_cookies(&mut cookies, &resp, now);
+        let status = resp.status();
+        if status.is_redirection() {
+            let location = resp
+                .headers()
+                .get(reqwest::header::LOCATION)
+                .and_then(|l| l.to_str().ok())
+                .ok_or_else(|| format!("HTTP {} without a location", status))?;
+            current = current
+                .join(location)
+                .map_err(|e| format!("Invalid redirect: {}", e))?;
+            continue;
+        }
+        if !status.is_success() {
+            return Err(format!("HTTP {}", status));
+        }
+        let content_type = resp
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|t| t.to_str().ok())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        let json = content_type.contains("json");
+        let small = resp.content_length().is_some_and(|l| l <= MAX_LINK_BYTES);
+        let link = if (json || content_type.starts_with("text/plain")) && small {
+            let body = resp.text().await.map_err(|e| e.to_string())?;
+            link_in(&body, json)
+        } else {
+            // Anything else is the stream; close it now so the player's request isn't turned
+            // away by a provider that allows one connection
+            drop(resp);
+            None
+        };
+        let Some(link) = link else {
+            // Keep the link as given when it is the stream, rather than its normalized form
+            return Ok(Resolved {
+                url: if current == start {
+                    url.to_string()
+                } else {
+                    current.to_string()
+                },
+                cookie: crate::cookies::header(&cookies, &current, now),
+            });
+        };
+        current = reqwest::Url::parse(&link).map_err(|e| format!("Invalid URL: {}", e))?;
+    }
+    Err("Too many redirects".to_string())
+}
+
+/// The URL to play for `url` from `server`. Links that can't be resolved are played as
+/// they are, so the player reports the failure.
+pub async fn resolve(server: Option<&ServerConfig>, url: &str) -> Resolved {
+    let unchanged = || Resolved {
+        url: url.to_string(),
+        cookie: None,
+    };
+    // Media URLs are opened by the player, which follows their redirects itself
+    if !url.starts_with("http") || reqwest::Url::parse(url).is_ok_and(|u| is_media(&u)) {
+        return unchanged();
+    }
+    match follow(server, url).await {
+        Ok(resolved) => {
+            if resolved.url != url {
+                tracing::debug!(
+                    "Resolved stream link {} to {}",
+                    sanitize_url(url),
+                    sanitize_url(&resolved.url)
+                );
+            }
+            resolved
+        }
+        Err(e) => {
+            tracing::debug!("Could not resolve stream link {}: {}", sanitize_url(url), e);
+            unchanged()
+        }
+    }
+}
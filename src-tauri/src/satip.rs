@@ -0,0 +1,174 @@
+//! SAT>IP servers as a channel source. RTSP setup and RTP reception are left to ffmpeg, which
+//! remuxes to HLS served by the local proxy so the webview player can use it.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::m3u::{self, M3uEntry};
+use crate::proxy::ProxyState;
+use crate::servers::{self, ServerConfig, ServerKind, ServerStore};
+use crate::ssdp;
+
+const SEARCH_TARGET: &str = "urn:ses-com:device:SatIPServer:1";
+
+/// A SAT>IP server found via SSDP. `location` is its UPnP description URL.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SatIpServer {
+    pub name: String,
+    pub host: String,
+    pub location: String,
+    pub has_channel_list: bool,
+}
+
+/// Tuning parameters for a SAT>IP RTSP request (see the SAT>IP spec, section 3.5.11).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SatIpTuning {
+    /// Signal source (DiSEqC position), 1-based. DVB-S only.
+    #[serde(default)]
+    pub src: Option<u8>,
+    /// Frequency in MHz.
+    pub freq: f64,
+    /// Polarisation: h, v, l or r. DVB-S only.
+    #[serde(default)]
+    pub pol: Option<String>,
+    /// Modulation system: dvbs, dvbs2, dvbt, dvbt2, dvbc.
+    pub msys: String,
+    /// Symbol rate in kSymb/s (DVB-S/C).
+    #[serde(default)]
+    pub sr: Option<u32>,
+    /// Bandwidth in MHz (DVB-T).
+    #[serde(default)]
+    pub bw: Option<u8>,
+    #[serde(default)]
+    pub mtype: Option<String>,
+    #[serde(default)]
+    pub fec: Option<String>,
+    /// PIDs to stream. Empty means `all`.
+    #[serde(default)]
+    pub pids: Vec<u16>,
+}
+
+fn host_of(location: &str) -> Option<String> {
+    reqwest::Url::parse(location)
+        .ok()?
+        .host_str()
+        .map(str::to_string)
+}
+
+async fn description(location: &str) -> Result<String, String> {
+    let client = crate::http::direct_client(Some(Duration::from_secs(5)))?;
+    crate::http::send(client.get(location))
+        .await
+        .map_err(|e| format!("SAT>IP request failed: {}", e))?
+        .text()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+fn satip_server(store: &ServerStore, server_id: &str) -> Result<ServerConfig, String> {
+    let server = servers::get(store, server_id)?;
+    if server.kind != ServerKind::Satip {
+        return Err(format!("{} is not a SAT>IP server", server.name));
+    }
+    Ok(server)
+}
+
+#[tauri::command]
+pub async fn satip_discover() -> Result<Vec<SatIpServer>, String> {
+    let responses = tauri::async_runtime::spawn_blocking(|| {
+        ssdp::search(SEARCH_TARGET, Duration::from_secs(2))
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+    let mut found = Vec::new();
+    for response in responses {
+        let Some(host) = host_of(&response.location) else {
+            continue;
+        };
+        let xml = description(&response.location).await.unwrap_or_default();
+        found.push(SatIpServer {
+            name: ssdp::xml_field(&xml, "friendlyName").unwrap_or_else(|| host.clone()),
+            has_channel_list: ssdp::xml_field(&xml, "X_SATIPM3U").is_some(),
+            host,
+            location: response.location,
+        });
+    }
+    Ok(found)
+}
+
+/// Channels from the server's own M3U list (`X_SATIPM3U` in its description), if it has one.
+/// Servers without one need channels tuned manually with `satip_tune_url`.
+#[tauri::command]
+pub async fn satip_channels(
+    store: State<'_, ServerStore>,
+    server_id: String,
+) -> Result<Vec<M3uEntry>, String> {
+    channel_list(&satip_server(&store, &server_id)?).await
+}
+
+pub(crate) async fn channel_list(server: &ServerConfig) -> Result<Vec<M3uEntry>, String> {
+    let xml = description(&server.url).await?;
+    let list = ssdp::xml_field(&xml, "X_SATIPM3U")
+        .ok_or_else(|| format!("{} does not provide a channel list", server.name))?;
+    let list_url = reqwest::Url::parse(&server.url)
+        .and_then(|base| base.join(&list))
+        .map_err(|e| format!("Invalid channel list URL: {}", e))?;
+    let text = description(list_url.as_str()).await?;
+    Ok(m3u::parse(&text))
+}
+
+/// Builds the `rtsp://` URL for a transponder/PID selection on a server.
+#[tauri::command]
+pub fn satip_tune_url(
+    store: State<'_, ServerStore>,
+    server_id: String,
+    tuning: SatIpTuning,
+) -> Result<String, String> {
+    let server = satip_server(&store, &server_id)?;
+    let host = host_of(&server.url).ok_or_else(|| "Invalid SAT>IP server URL".to_string())?;
+    let mut params = Vec::new();
+    if let Some(src) = tuning.src {
+        params.push(format!("src={}", src));
+    }
+    params.push(format!("freq={}", tuning.freq));
+    if let Some(pol) = &tuning.pol {
+        params.push(format!("pol={}", pol));
+    }
+    params.push(format!("msys={}", tuning.msys));
+    if let Some(sr) = tuning.sr {
+        params.push(format!("sr={}", sr));
+    }
+    if let Some(bw) = tuning.bw {
+        params.push(format!("bw={}", bw));
+    }
+    if let Some(mtype) = &tuning.mtype {
+        params.push(format!("mtype={}", mtype));
+    }
+    if let Some(fec) = &tuning.fec {
+        params.push(format!("fec={}", fec));
+    }
+    if tuning.pids.is_empty() {
+        params.push("pids=all".to_string());
+    } else {
+        let pids: Vec<String> = tuning.pids.iter().map(u16::to_string).collect();
+        params.push(format!("pids={}", pids.join(",")));
+    }
+    Ok(format!("rtsp://{}:554/?{}", host, params.join("&")))
+}
+
+/// Plays an `rtsp://` SAT>IP stream in a video window through the proxy. Returns the label.
+#[tauri::command]
+pub async fn satip_play(
+    app: tauri::AppHandle,
+    proxy: State<'_, ProxyState>,
+    rtsp_url: String,
+    title: String,
+) -> Result<String, String> {
+    let input = ["-i".to_string(), rtsp_url];
+    let session = proxy.start_hls(&input, &["-c".to_string(), "copy".to_string()], false, None)?;
+    crate::proxy::open_window(&app, &proxy, &session, &title, &[])
+}
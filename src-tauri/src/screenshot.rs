@@ -0,0 +1,109 @@
+//! Screenshots of what a video window plays. mpv windows save the frame on screen; for the
+//! webview player ffmpeg grabs a frame from the window's stream URL at the position the page
+//! reports (the live edge for live streams). Files go to the screenshots folder from Settings,
+//! else `Pictures/TvX`, and `screenshot-saved` reports each one.
+
+use std::path::PathBuf;
+
+use serde::Serialize;
+use tauri::{Emitter, Manager, State};
+
+use crate::mpv::MpvState;
+use crate::settings::SettingsStore;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ScreenshotSaved {
+    label: String,
+    path: String,
+}
+
+fn screenshots_dir(app: &tauri::AppHandle, settings: &SettingsStore) -> Result<PathBuf, String> {
+    let dir = match settings.read(|s| s.screenshots_dir.clone()) {
+        Some(dir) => PathBuf::from(dir),
+        None => app
+            .path()
+            .picture_dir()
+            .map_err(|e| e.to_string())?
+            .join("TvX"),
+    };
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    Ok(dir)
+}
+
+/// The stream the webview player of `window` plays, from its page URL.
+fn stream_url(window: &tauri::WebviewWindow) -> Result<String, String> {
+    window
+        .url()
+        .map_err(|e| e.to_string())?
+        .query_pairs()
+        .find(|(key, _)| key == "url")
+        .map(|(_, url)| url.into_owned())
+        .ok_or_else(|| "The window is not playing a stream".to_string())
+}
+
+async fn grab_frame(url: String, position_secs: Option<f64>, path: String) -> Result<(), String> {
+    let ffmpeg = crate::ffmpeg::ffmpeg_path()?;
+    let output = tauri::async_runtime::spawn_blocking(move || {
+        let mut command = std::process::Command::new(ffmpeg);
+        command.args(["-hide_banner", "-loglevel", "error", "-nostdin", "-y"]);
+        // Seeking before the input jumps to the nearest keyframe and decodes on from there
+        if let Some(position) = position_secs.filter(|p| *p > 0.0) {
+            command.args(["-ss", &format!("{:.3}", position)]);
+        }
+        command
+            .args(crate::transcode::input_args(&url))
+            .args(["-frames:v", "1", "-f", "image2", "-c:v", "png"])
+            .arg(&path)
+            .output()
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| format!("Failed to run ffmpeg: {}", e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("Could not capture a frame: {}", stderr.trim()));
+    }
+    Ok(())
+}
+
+/// Saves a PNG of the frame `window_label` plays to `path`, or to the screenshots folder.
+/// `position_secs` is the webview player's position, for streams that can seek. Returns the
+/// saved path.
+#[tauri::command]
+pub async fn capture_screenshot(
+    app: tauri::AppHandle,
+    settings: State<'_, SettingsStore>,
+    mpv: State<'_, MpvState>,
+    window_label: String,
+    path: Option<String>,
+    position_secs: Option<f64>,
+) -> Result<String, String> {
+    let window = crate::video_window(&app, &window_label)?;
+    let path = match path.filter(|p| !p.trim().is_empty()) {
+        Some(path) => path,
+        None => {
+            let title = window.title().unwrap_or_else(|_| "Screenshot".to_string());
+            let name = crate::recorder::file_name(&app, &title, crate::now_secs() as i64, "png");
+            screenshots_dir(&app, &settings)?
+                .join(name)
+                .to_string_lossy()
+                .into_owned()
+        }
+    };
+    if mpv.has(&window_label) {
+        mpv.screenshot(&window_label, &path)?;
+    } else {
+        grab_frame(stream_url(&window)?, position_secs, path.clone()).await?;
+    }
+    tracing::info!(label = %window_label, path = %path, "Saved screenshot");
+    let _ = app.emit(
+        "screenshot-saved",
+        ScreenshotSaved {
+            label: window_label,
+            path: path.clone(),
+        },
+    );
+    Ok(path)
+}
@@ -0,0 +1,194 @@
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::dns::DnsConfig;
+use crate::http::{ProxyConfig, RateLimit, TlsConfig, TlsKind};
+use crate::stalker::PortalDevice;
+use crate::store::JsonStore;
+
+/// Backend flavour of a configured server. Xtream servers are driven by the frontend API client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ServerKind {
+    #[default]
+    Xtream,
+    Emby,
+    Tvheadend,
+    Hdhomerun,
+    Satip,
+    /// Plain M3U playlist; `url` is an http(s) URL or a local file path.
+    M3u,
+    /// Stalker (Ministra) middleware portal; `url` is the portal page (see `stalker`).
+    Stalker,
+}
+
+/// Saved server connection. Field names match the frontend `ServerConnection` type.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerConfig {
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub kind: ServerKind,
+    pub url: String,
+    #[serde(default)]
+    pub username: String,
+    #[serde(default)]
+    pub password: String,
+    #[serde(default)]
+    pub last_connected: Option<u64>,
+    /// Session token issued by servers that authenticate once (Emby).
+    #[serde(default)]
+    pub access_token: Option<String>,
+    /// Server-side user id that goes with `access_token`.
+    #[serde(default)]
+    pub user_id: Option<String>,
+    /// Stream profile requested when building stream URLs (TVHeadend).
+    #[serde(default)]
+    pub stream_profile: Option<String>,
+    /// MAC address for Wake-on-LAN, e.g. `AA:BB:CC:DD:EE:FF`.
+    #[serde(default)]
+    pub mac_address: Option<String>,
+    /// Outbound proxy for this server; `None` uses the global one from Settings.
+    #[serde(default)]
+    pub proxy: Option<ProxyConfig>,
+    /// Resolver for this server's host; `None` uses the global one from Settings.
+    #[serde(default)]
+    pub dns: Option<DnsConfig>,
+    /// Certificate verification override for self-signed servers.
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+    /// Concurrent catalog/EPG fetches to this server; `None` uses the default of 4.
+    #[serde(default)]
+    pub max_connections: Option<u32>,
+    /// Shift applied to this server's guide times, for providers whose EPG is consistently off.
+    #[serde(default)]
+    pub epg_offset_minutes: Option<i32>,
+    /// IANA zone the provider's catch-up times are in, overriding what the server declares.
+    #[serde(default)]
+    pub timezone: Option<String>,
+    /// Recordings this server can run at once (its tuners or provider connections); falls back
+    /// to `max_connections`, and without either scheduling never reports conflicts.
+    #[serde(default)]
+    pub max_recordings: Option<u32>,
+    /// Playlists a merged lineup was built from (see `merge`); empty for other servers.
+    #[serde(default)]
+    pub merged_from: Vec<String>,
+    /// udpxy gateway (`http://router:4022`) that `udp://@group:port` channels are played
+    /// through; without one they are joined directly (see `ingest`).
+    #[serde(default)]
+    pub udpxy_url: Option<String>,
+    /// Request rate this server's panel tolerates; `None` uses the global per-host limit.
+    #[serde(default)]
+    pub rate_limit: Option<RateLimit>,
+    /// Set-top box identity a Stalker portal account is tied to; `None` derives one.
+    #[serde(default)]
+    pub portal_device: Option<PortalDevice>,
+}
+
+impl ServerConfig {
+    /// Base URL without trailing slashes.
+    pub fn base_url(&self) -> &str {
+        self.url.trim_end_matches('/')
+    }
+
+    /// Checks the per-server network overrides.
+    pub fn validate_network(&self) -> Result<(), String> {
+        if let Some(proxy) = &self.proxy {
+            proxy.validate()?;
+        }
+        if let Some(dns) = &self.dns {
+            dns.validate()?;
+        }
+        if let Some(tls) = &self.tls {
+            tls.validate()?;
+        }
+        if let Some(limit) = &self.rate_limit {
+            limit.validate()?;
+        }
+        if let Some(udpxy) = self.udpxy_url.as_deref().filter(|u| !u.trim().is_empty()) {
+            let valid = reqwest::Url::parse(udpxy.trim())
+                .is_ok_and(|u| matches!(u.scheme(), "http" | "https") && u.host().is_some());
+            if !valid {
+                return Err(format!("{} is not a valid udpxy address", udpxy));
+            }
+        }
+        Ok(())
+    }
+}
+
+pub type ServerStore = JsonStore<Vec<ServerConfig>>;
+
+pub fn open(app: &tauri::AppHandle) -> ServerStore {
+    let store: ServerStore = JsonStore::open(app, "servers.json");
+    store.read(|servers| crate::http::configure_servers(servers));
+    store
+}
+
+/// Looks up a server by id, returning a clone so callers don't hold the lock across awaits.
+pub fn get(store: &ServerStore, server_id: &str) -> Result<ServerConfig, String> {
+    store
+        .read(|servers| servers.iter().find(|s| s.id == server_id).cloned())
+        .ok_or_else(|| format!("Unknown server: {}", server_id))
+}
+
+/// Inserts or replaces a server by id, assigning a new id when empty.
+pub fn upsert(store: &ServerStore, mut server: ServerConfig) -> Result<ServerConfig, String> {
+    if let Some(mac) = server.mac_address.as_deref().filter(|m| !m.is_empty()) {
+        crate::wol::parse_mac(mac)?;
+    }
+    server.validate_network()?;
+    if let Some(device) = &server.portal_device {
+        device.validate()?;
+    }
+    if let Some(zone) = server.timezone.as_deref().filter(|z| !z.trim().is_empty()) {
+        crate::tz::zone(zone)?;
+    }
+    if server
+        .tls
+        .as_ref()
+        .is_some_and(|tls| tls.kind == TlsKind::Insecure)
+    {
+        tracing::warn!(
+            "Certificate verification is disabled for {}; its connections can be intercepted",
+            server.name
+        );
+    }
+    if server.id.is_empty() {
+        server.id = uuid::Uuid::new_v4().to_string();
+    }
+    let saved = server.clone();
+    store.update(
+        |servers| match servers.iter_mut().find(|s| s.id == server.id) {
+            Some(existing) => *existing = server,
+            None => servers.push(server),
+        },
+    )?;
+    store.read(|servers| crate::http::configure_servers(servers));
+    Ok(saved)
+}
+
+#[tauri::command]
+pub fn list_servers(store: State<'_, ServerStore>) -> Vec<ServerConfig> {
+    store.read(|servers| servers.clone())
+}
+
+#[tauri::command]
+pub fn save_server(
+    store: State<'_, ServerStore>,
+    server: ServerConfig,
+) -> Result<ServerConfig, String> {
+    upsert(&store, server)
+}
+
+#[tauri::command]
+pub fn remove_server(
+    store: State<'_, ServerStore>,
+    cookies: State<'_, crate::cookies::CookieStore>,
+    server_id: String,
+) -> Result<(), String> {
+    store.update(|servers| servers.retain(|s| s.id != server_id))?;
+    store.read(|servers| crate::http::configure_servers(servers));
+    crate::cookies::clear(&cookies, &server_id)
+}
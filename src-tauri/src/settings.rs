@@ -0,0 +1,133 @@
+//! App-wide settings persisted in `settings.json`. Per-server options live on `ServerConfig`.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::audiofocus::AudioFocusPolicy;
+use crate::cache::CacheLimits;
+use crate::connections::ConnectionLimitPolicy;
+use crate::datausage::DataSaverSettings;
+use crate::downloads::DownloadSettings;
+use crate::http::NetworkSettings;
+use crate::mqtt::MqttSettings;
+use crate::progress::WatchedRules;
+use crate::remote::RemoteApiSettings;
+use crate::shortcuts::ShortcutOverrides;
+use crate::store::JsonStore;
+use crate::timeshift::TimeshiftSettings;
+use crate::tracks::TrackPreferences;
+use crate::updater::UpdateSettings;
+
+/// Profile used until the app has user profiles of its own.
+pub const DEFAULT_PROFILE: &str = "default";
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct AppSettings {
+    /// Preferred audio/subtitle languages keyed by profile id.
+    #[serde(default)]
+    pub track_preferences: HashMap<String, TrackPreferences>,
+    /// Consumer API key from opensubtitles.com.
+    #[serde(default)]
+    pub opensubtitles_api_key: Option<String>,
+    #[serde(default)]
+    pub remote_api: RemoteApiSettings,
+    #[serde(default)]
+    pub mqtt: MqttSettings,
+    /// Minimum level written to the log file; info when unset.
+    #[serde(default)]
+    pub log_level: Option<String>,
+    /// Consent to sending crash reports; they are always kept locally.
+    #[serde(default)]
+    pub upload_crash_reports: bool,
+    #[serde(default)]
+    pub updates: UpdateSettings,
+    #[serde(default)]
+    pub network: NetworkSettings,
+    #[serde(default)]
+    pub cache_limits: CacheLimits,
+    /// IANA zone guide times are shown in; the system zone when unset.
+    #[serde(default)]
+    pub display_timezone: Option<String>,
+    /// Look for ad breaks in finished recordings and mark them as chapters.
+    #[serde(default)]
+    pub detect_commercials: bool,
+    /// File finished recordings the way Kodi, Plex and Jellyfin libraries expect, with NFO
+    /// sidecars (see `library`).
+    #[serde(default)]
+    pub library_naming: bool,
+    /// Tallest HLS variant to play, in lines (1080, 720…); HLS adapts on its own when unset.
+    #[serde(default)]
+    pub preferred_quality: Option<u32>,
+    #[serde(default)]
+    pub watched: WatchedRules,
+    /// Even out loudness with ffmpeg's loudnorm when a stream is being transcoded anyway.
+    #[serde(default)]
+    pub normalize_audio: bool,
+    /// Audio output device (mpv naming) keyed by profile id; the system default when unset.
+    #[serde(default)]
+    pub audio_devices: HashMap<String, String>,
+    #[serde(default)]
+    pub data_saver: DataSaverSettings,
+    /// Whether opening a video window mutes the others.
+    #[serde(default)]
+    pub audio_focus: AudioFocusPolicy,
+    /// Play streams opened with `open_video_window` in the newest video window, if any.
+    #[serde(default)]
+    pub reuse_video_window: bool,
+    /// What happens when a new stream would exceed a server's connection limit.
+    #[serde(default)]
+    pub connection_limit: ConnectionLimitPolicy,
+    /// Keyboard shortcuts rebound from the defaults (see `shortcuts`).
+    #[serde(default)]
+    pub shortcuts: ShortcutOverrides,
+    #[serde(default)]
+    pub timeshift: TimeshiftSettings,
+    /// Folder screenshots are saved to; `Pictures/TvX` when unset.
+    #[serde(default)]
+    pub screenshots_dir: Option<String>,
+    #[serde(default)]
+    pub downloads: DownloadSettings,
+}
+
+pub type SettingsStore = JsonStore<AppSettings>;
+
+pub fn open(app: &tauri::AppHandle) -> SettingsStore {
+    JsonStore::open(app, "settings.json")
+}
+
+/// Resolves an optional profile id from the frontend.
+pub fn profile_id(profile_id: Option<String>) -> String {
+    profile_id
+        .filter(|p| !p.is_empty())
+        .unwrap_or_else(|| DEFAULT_PROFILE.to_string())
+}
+
+#[tauri::command]
+pub fn get_settings(settings: State<'_, SettingsStore>) -> AppSettings {
+    settings.read(|s| s.clone())
+}
+
+#[tauri::command]
+pub fn save_settings(
+    app: tauri::AppHandle,
+    settings: State<'_, SettingsStore>,
+    mut new_settings: AppSettings,
+) -> Result<(), String> {
+    crate::http::configure(&new_settings.network)?;
+    if let Some(name) = &new_settings.display_timezone {
+        crate::tz::zone(name)?;
+    }
+    new_settings.watched.validate()?;
+    new_settings.data_saver.validate()?;
+    crate::shortcuts::validate(&mut new_settings.shortcuts)?;
+    new_settings.timeshift.validate()?;
+    new_settings.downloads.validate()?;
+    // Lowered limits apply right away rather than at the next periodic check
+    let limits = new_settings.cache_limits.clone();
+    settings.update(|s| *s = new_settings)?;
+    std::thread::spawn(move || crate::cache::enforce(&app, &limits));
+    Ok(())
+}
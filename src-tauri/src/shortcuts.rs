@@ -0,0 +1,246 @@
+//! Keyboard shortcuts, defined once here so every window (and remote clients, through
+//! `GET /api/v1/shortcuts`) applies the same map. Settings keep only the actions the user
+//! rebound; the rest use the defaults. Key chords are written the way the frontend's
+//! `useKeyboard` builds them: `ctrl`, `alt` and `shift` in that order, then the key, lowercase,
+//! joined with `+` (`ctrl+shift+s`). A chord can belong to one action only.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, State};
+
+use crate::settings::SettingsStore;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ShortcutAction {
+    PlayPause,
+    Mute,
+    Fullscreen,
+    SeekBack,
+    SeekForward,
+    ZapNext,
+    ZapPrevious,
+    /// Back to the channel before the current one.
+    ZapBack,
+    Record,
+    Screenshot,
+    PictureInPicture,
+    ToggleSubtitles,
+}
+
+impl ShortcutAction {
+    const ALL: [ShortcutAction; 12] = [
+        ShortcutAction::PlayPause,
+        ShortcutAction::Mute,
+        ShortcutAction::Fullscreen,
+        ShortcutAction::SeekBack,
+        ShortcutAction::SeekForward,
+        ShortcutAction::ZapNext,
+        ShortcutAction::ZapPrevious,
+        ShortcutAction::ZapBack,
+        ShortcutAction::Record,
+        ShortcutAction::Screenshot,
+        ShortcutAction::PictureInPicture,
+        ShortcutAction::ToggleSubtitles,
+    ];
+
+    fn default_keys(self) -> &'static [&'static str] {
+        match self {
+            ShortcutAction::PlayPause => &["space", "k"],
+            ShortcutAction::Mute => &["m"],
+            ShortcutAction::Fullscreen => &["f"],
+            ShortcutAction::SeekBack => &["arrowleft", "j"],
+            ShortcutAction::SeekForward => &["arrowright", "l"],
+            ShortcutAction::ZapNext => &["pageup", "arrowup"],
+            ShortcutAction::ZapPrevious => &["pagedown", "arrowdown"],
+            ShortcutAction::ZapBack => &["backspace"],
+            ShortcutAction::Record => &["r"],
+            ShortcutAction::Screenshot => &["s"],
+            ShortcutAction::PictureInPicture => &["p"],
+            ShortcutAction::ToggleSubtitles => &["c"],
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            ShortcutAction::PlayPause => "Play/pause",
+            ShortcutAction::Mute => "Mute",
+            ShortcutAction::Fullscreen => "Fullscreen",
+            ShortcutAction::SeekBack => "Seek back",
+            ShortcutAction::SeekForward => "Seek forward",
+            ShortcutAction::ZapNext => "Next channel",
+            ShortcutAction::ZapPrevious => "Previous channel",
+            ShortcutAction::ZapBack => "Last channel",
+            ShortcutAction::Record => "Record",
+            ShortcutAction::Screenshot => "Screenshot",
+            ShortcutAction::PictureInPicture => "Picture-in-picture",
+            ShortcutAction::ToggleSubtitles => "Subtitles",
+        }
+    }
+}
+
+/// Actions rebound by the user, with their chords; an empty list leaves the action unbound.
+pub type ShortcutOverrides = BTreeMap<ShortcutAction, Vec<String>>;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Shortcut {
+    pub action: ShortcutAction,
+    pub keys: Vec<String>,
+    pub default_keys: Vec<String>,
+}
+
+/// Writes `chord` in the canonical form, or explains what is wrong with it.
+fn normalize(chord: &str) -> Result<String, String> {
+    let (mut ctrl, mut alt, mut shift) = (false, false, false);
+    let mut key = None;
+    // A lone space is the key itself, as `KeyboardEvent.key` reports it
+    let parts = chord.split('+').map(|p| match p {
+        " " => "space".to_string(),
+        _ => p.trim().to_lowercase(),
+    });
+    for part in parts {
+        let modifier = match part.as_str() {
+            "ctrl" | "control" | "cmd" | "meta" => &mut ctrl,
+            "alt" | "option" => &mut alt,
+            "shift" => &mut shift,
+            "" => return Err(format!("\"{}\" has an empty key", chord)),
+            _ => {
+                if key.is_some() {
+                    return Err(format!("\"{}\" has more than one key", chord));
+                }
+                key = Some(match part.as_str() {
+                    "spacebar" => "space".to_string(),
+                    "esc" => "escape".to_string(),
+                    "up" | "down" | "left" | "right" => format!("arrow{}", part),
+                    _ => part,
+                });
+                continue;
+            }
+        };
+        *modifier = true;
+    }
+    let key = key.ok_or_else(|| format!("\"{}\" has only modifiers", chord))?;
+    let mut parts = Vec::new();
+    if ctrl {
+        parts.push("ctrl");
+    }
+    if alt {
+        parts.push("alt");
+    }
+    if shift {
+        parts.push("shift");
+    }
+    parts.push(&key);
+    Ok(parts.join("+"))
+}
+
+/// The chords of every action, overrides applied.
+fn effective(overrides: &ShortcutOverrides) -> Vec<Shortcut> {
+    ShortcutAction::ALL
+        .iter()
+        .map(|&action| {
+            let default_keys: Vec<String> = action
+                .default_keys()
+                .iter()
+                .map(|k| k.to_string())
+                .collect();
+            Shortcut {
+                action,
+                keys: overrides
+                    .get(&action)
+                    .cloned()
+                    .unwrap_or_else(|| default_keys.clone()),
+                default_keys,
+            }
+        })
+        .collect()
+}
+
+/// Normalizes the overrides' chords and checks that no chord ends up on two actions.
+pub fn validate(overrides: &mut ShortcutOverrides) -> Result<(), String> {
+    for keys in overrides.values_mut() {
+        let mut normalized = Vec::new();
+        for key in keys.iter() {
+            let key = normalize(key)?;
+            if !normalized.contains(&key) {
+                normalized.push(key);
+            }
+        }
+        *keys = normalized;
+    }
+    let mut owners: BTreeMap<&str, ShortcutAction> = BTreeMap::new();
+    let shortcuts = effective(overrides);
+    for shortcut in &shortcuts {
+        for key in &shortcut.keys {
+            if let Some(other) = owners.insert(key, shortcut.action) {
+                return Err(format!(
+                    "{} is already used for {}",
+                    key,
+                    other.label().to_lowercase()
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn list(settings: &SettingsStore) -> Vec<Shortcut> {
+    settings.read(|s| effective(&s.shortcuts))
+}
+
+fn save(
+    app: &tauri::AppHandle,
+    settings: &SettingsStore,
+    mut overrides: ShortcutOverrides,
+) -> Result<Vec<Shortcut>, String> {
+    validate(&mut overrides)?;
+    let shortcuts = effective(&overrides);
+    settings.update(|s| s.shortcuts = overrides)?;
+    let _ = app.emit("shortcuts-changed", &shortcuts);
+    Ok(shortcuts)
+}
+
+#[tauri::command]
+pub fn get_shortcuts(settings: State<'_, SettingsStore>) -> Vec<Shortcut> {
+    list(&settings)
+}
+
+/// Rebinds `action` to `keys` (none leaves it unbound), refusing chords another action uses.
+#[tauri::command]
+pub fn set_shortcut(
+    app: tauri::AppHandle,
+    settings: State<'_, SettingsStore>,
+    action: ShortcutAction,
+    keys: Vec<String>,
+) -> Result<Vec<Shortcut>, String> {
+    let mut overrides = settings.read(|s| s.shortcuts.clone());
+    overrides.insert(action, keys);
+    // Rebinding an action to its defaults is the same as not overriding it
+    if overrides.get(&action).is_some_and(|keys| {
+        keys.iter()
+            .map(|k| normalize(k).unwrap_or_default())
+            .eq(action.default_keys().iter().map(|k| k.to_string()))
+    }) {
+        overrides.remove(&action);
+    }
+    save(&app, &settings, overrides)
+}
+
+/// Restores the default chords of `action`, or of every action.
+#[tauri::command]
+pub fn reset_shortcuts(
+    app: tauri::AppHandle,
+    settings: State<'_, SettingsStore>,
+    action: Option<ShortcutAction>,
+) -> Result<Vec<Shortcut>, String> {
+    let mut overrides = settings.read(|s| s.shortcuts.clone());
+    match action {
+        Some(action) => {
+            overrides.remove(&action);
+        }
+        None => overrides.clear(),
+    }
+    save(&app, &settings, overrides)
+}
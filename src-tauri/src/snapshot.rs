@@ -0,0 +1,294 @@
+//! Compact binary snapshot of a server's channel index, written next to its catalog cache and
+//! read at startup so the channel list renders before the server (or the much larger JSON
+//! copy) has been loaded.
+//!
+//! Layout: `TVXS`, version byte, fetched-at (u64 LE), the group names, then per channel its
+//! id, name, number, group index, logo, EPG id and a flags byte (bit 0: radio, bit 1: a
+//! ClearKey license follows as a string). Lengths, counts and indexes are LEB128 varints;
+//! optional strings and the group index store 0 for none and value + 1 otherwise.
+
+use std::collections::BTreeMap;
+use std::fs;
+
+use crate::cache::CacheKind;
+use crate::catalog::CatalogChannel;
+
+const MAGIC: &[u8; 4] = b"TVXS";
+const VERSION: u8 = 3;
+const FLAG_RADIO: u8 = 1;
+const FLAG_CLEARKEY: u8 = 2;
+const FILE_NAME: &str = "index.bin";
+
+fn put_varint(out: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn put_str(out: &mut Vec<u8>, s: &str) {
+    put_varint(out, s.len() as u64);
+    out.extend_from_slice(s.as_bytes());
+}
+
+fn put_opt_str(out: &mut Vec<u8>, s: Option<&str>) {
+    match s {
+        Some(s) => {
+            put_varint(out, s.len() as u64 + 1);
+            out.extend_from_slice(s.as_bytes());
+        }
+        None => out.push(0),
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        if len > self.data.len() {
+            return None;
+        }
+        let (head, rest) = self.data.split_at(len);
+        self.data = rest;
+        Some(head)
+    }
+
+    fn varint(&mut self) -> Option<u64> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = *self.bytes(1)?.first()?;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+        None
+    }
+
+    fn len(&mut self) -> Option<usize> {
+        usize::try_from(self.varint()?).ok()
+    }
+
+    fn string(&mut self, len: usize) -> Option<String> {
+        String::from_utf8(self.bytes(len)?.to_vec()).ok()
+    }
+
+    fn str(&mut self) -> Option<String> {
+        let len = self.len()?;
+        self.string(len)
+    }
+
+    fn opt_str(&mut self) -> Option<Option<String>> {
+        match self.len()? {
+            0 => Some(None),
+            len => self.string(len - 1).map(Some),
+        }
+    }
+}
+
+pub fn encode(fetched_at: u64, channels: &[CatalogChannel]) -> Vec<u8> {
+    let mut groups: Vec<&str> = Vec::new();
+    let mut positions: BTreeMap<&str, u64> = BTreeMap::new();
+    for group in channels.iter().filter_map(|c| c.group.as_deref()) {
+        positions.entry(group).or_insert_with(|| {
+            groups.push(group);
+            groups.len() as u64
+        });
+    }
+    let mut out = Vec::with_capacity(channels.len() * 64);
+    out.extend_from_slice(MAGIC);
+    out.push(VERSION);
+    out.extend_from_slice(&fetched_at.to_le_bytes());
+    put_varint(&mut out, groups.len() as u64);
+    for group in &groups {
+        put_str(&mut out, group);
+    }
+    put_varint(&mut out, channels.len() as u64);
+    for channel in channels {
+        put_str(&mut out, &channel.id);
+        put_str(&mut out, &channel.name);
+        put_opt_str(&mut out, channel.number.as_deref());
+        let group = channel.group.as_deref().and_then(|g| positions.get(g));
+        put_varint(&mut out, group.copied().unwrap_or(0));
+        put_opt_str(&mut out, channel.logo.as_deref());
+        put_opt_str(&mut out, channel.epg_id.as_deref());
+        let mut flags = 0;
+        if channel.radio {
+            flags |= FLAG_RADIO;
+        }
+        if channel.clearkey.is_some() {
+            flags |= FLAG_CLEARKEY;
+        }
+        out.push(flags);
+        if let Some(license) = &channel.clearkey {
+            put_str(&mut out, license);
+        }
+    }
+    out
+}
+
+/// `None` for anything truncated, corrupt or from another format version.
+pub fn decode(data: &[u8]) -> Option<(u64, Vec<CatalogChannel>)> {
+    let mut r = Reader { data };
+    if r.bytes(4)? != MAGIC || r.bytes(1)? != [VERSION] {
+        return None;
+    }
+    let fetched_at = u64::from_le_bytes(r.bytes(8)?.try_into().ok()?);
+    let group_count = r.len()?;
+    let groups = (0..group_count)
+        .map(|_| r.str())
+        .collect::<Option<Vec<_>>>()?;
+    let count = r.len()?;
+    // Every channel takes at least seven bytes, which bounds the allocation for corrupt counts
+    let mut channels = Vec::with_capacity(count.min(r.data.len() / 7));
+    for _ in 0..count {
+        let id = r.str()?;
+        let name = r.str()?;
+        let number = r.opt_str()?;
+        let group = match r.len()? {
+            0 => None,
+            i => Some(groups.get(i - 1)?.clone()),
+        };
+        let logo = r.opt_str()?;
+        let epg_id = r.opt_str()?;
+        let flags = r.bytes(1)?[0];
+        let clearkey = match flags & FLAG_CLEARKEY {
+            0 => None,
+            _ => Some(r.str()?),
+        };
+        channels.push(CatalogChannel {
+            id,
+            name,
+            number,
+            group,
+            logo,
+            epg_id,
+            radio: flags & FLAG_RADIO != 0,
+            clearkey,
+            hidden: false,
+        });
+    }
+    Some((fetched_at, channels))
+}
+
+pub fn write(
+    app: &tauri::AppHandle,
+    server_id: &str,
+    fetched_at: u64,
+    channels: &[CatalogChannel],
+) -> Result<(), String> {
+    let dir = crate::offline::server_dir(app, CacheKind::Catalogs, server_id)?;
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create catalog cache: {}", e))?;
+    let path = dir.join(FILE_NAME);
+    let tmp = path.with_extension("bin.tmp");
+    fs::write(&tmp, encode(fetched_at, channels))
+        .map_err(|e| format!("Failed to write channel snapshot: {}", e))?;
+    fs::rename(&tmp, &path).map_err(|e| format!("Failed to write channel snapshot: {}", e))
+}
+
+pub fn read(app: &tauri::AppHandle, server_id: &str) -> Option<(u64, Vec<CatalogChannel>)> {
+    let path = crate::offline::server_dir(app, CacheKind::Catalogs, server_id)
+        .ok()?
+        .join(FILE_NAME);
+    let snapshot = decode(&fs::read(&path).ok()?)?;
+    crate::cache::touch(&path);
+    Some(snapshot)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn channel(id: &str, group: Option<&str>) -> CatalogChannel {
+        CatalogChannel {
+            id: id.to_string(),
+            name: format!("Channel {}", id),
+            number: None,
+            group: group.map(str::to_string),
+            logo: None,
+            epg_id: None,
+            radio: false,
+            clearkey: None,
+            hidden: false,
+        }
+    }
+
+    #[test]
+    fn round_trips_channels() {
+        let mut first = channel("1", Some("News"));
+        first.number = Some("101".to_string());
+        first.logo = Some("http://example.com/logo.png".to_string());
+        first.epg_id = Some("news.example".to_string());
+        first.clearkey = Some("0123:4567".to_string());
+        let mut second = channel("2", None);
+        second.radio = true;
+        // An empty string is stored apart from none
+        second.epg_id = Some(String::new());
+        let channels = [first, second, channel("3", Some("News"))];
+        let (fetched_at, decoded) = decode(&encode(1_700_000_000, &channels)).unwrap();
+        assert_eq!(fetched_at, 1_700_000_000);
+        assert_eq!(
+            serde_json::to_value(&decoded).unwrap(),
+            serde_json::to_value(channels).unwrap()
+        );
+    }
+
+    #[test]
+    fn varints_span_bytes() {
+        for value in [0, 127, 128, 300, u64::MAX] {
+            let mut out = Vec::new();
+            put_varint(&mut out, value);
+            assert_eq!(Reader { data: &out }.varint(), Some(value));
+        }
+        // Eleven continuation bytes run past 64 bits
+        assert_eq!(Reader { data: &[0x80; 11] }.varint(), None);
+    }
+
+    #[test]
+    fn rejects_truncated_and_foreign_data() {
+        let data = encode(1, &[channel("1", Some("News")), channel("2", None)]);
+        for len in 0..data.len() {
+            assert!(decode(&data[..len]).is_none(), "decoded {} bytes", len);
+        }
+        let mut other_version = data.clone();
+        other_version[4] = VERSION + 1;
+        assert!(decode(&other_version).is_none());
+        assert!(decode(b"JSON{}").is_none());
+    }
+
+    #[test]
+    fn rejects_group_indexes_out_of_range() {
+        let mut data = Vec::new();
+        data.extend_from_slice(MAGIC);
+        data.push(VERSION);
+        data.extend_from_slice(&0u64.to_le_bytes());
+        put_varint(&mut data, 0);
+        put_varint(&mut data, 1);
+        put_str(&mut data, "1");
+        put_str(&mut data, "One");
+        put_opt_str(&mut data, None);
+        put_varint(&mut data, 1);
+        put_opt_str(&mut data, None);
+        put_opt_str(&mut data, None);
+        data.push(0);
+        assert!(decode(&data).is_none());
+    }
+
+    #[test]
+    fn huge_counts_do_not_allocate() {
+        let mut data = Vec::new();
+        data.extend_from_slice(MAGIC);
+        data.push(VERSION);
+        data.extend_from_slice(&0u64.to_le_bytes());
+        put_varint(&mut data, 0);
+        put_varint(&mut data, u64::MAX);
+        assert!(decode(&data).is_none());
+    }
+}
@@ -0,0 +1,73 @@
+//! SSDP (UPnP) discovery and minimal device-description parsing.
+
+use std::net::{Ipv4Addr, UdpSocket};
+use std::time::{Duration, Instant};
+
+const SSDP_ADDR: (Ipv4Addr, u16) = (Ipv4Addr::new(239, 255, 255, 250), 1900);
+
+#[derive(Debug, Clone)]
+pub struct SsdpResponse {
+    pub location: String,
+}
+
+fn header<'a>(response: &'a str, name: &str) -> Option<&'a str> {
+    response.lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        key.trim().eq_ignore_ascii_case(name).then(|| value.trim())
+    })
+}
+
+/// Sends an M-SEARCH for `search_target` and collects unique responses for `wait`. Blocking.
+pub fn search(search_target: &str, wait: Duration) -> Result<Vec<SsdpResponse>, String> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).map_err(|e| e.to_string())?;
+    socket
+        .set_read_timeout(Some(Duration::from_millis(200)))
+        .map_err(|e| e.to_string())?;
+    let mx = wait.as_secs().clamp(1, 5);
+    let request = format!(
+        "M-SEARCH * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nMAN: \"ssdp:discover\"\r\nMX: {}\r\nST: {}\r\n\r\n",
+        mx, search_target
+    );
+    socket
+        .send_to(request.as_bytes(), SSDP_ADDR)
+        .map_err(|e| format!("Failed to send SSDP search: {}", e))?;
+
+    let deadline = Instant::now() + wait;
+    let mut responses: Vec<SsdpResponse> = Vec::new();
+    let mut buf = [0u8; 2048];
+    while Instant::now() < deadline {
+        let Ok((n, _)) = socket.recv_from(&mut buf) else {
+            continue;
+        };
+        let text = String::from_utf8_lossy(&buf[..n]);
+        let Some(location) = header(&text, "location") else {
+            continue;
+        };
+        if responses.iter().any(|r| r.location == location) {
+            continue;
+        }
+        responses.push(SsdpResponse {
+            location: location.to_string(),
+        });
+    }
+    Ok(responses)
+}
+
+/// Text content of the first `<tag>` (with or without a namespace prefix) in an XML document.
+/// Good enough for UPnP device descriptions, which are flat and unescaped in practice.
+pub fn xml_field(xml: &str, tag: &str) -> Option<String> {
+    let mut rest = xml;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start + 1..];
+        let end = rest.find('>')?;
+        let open = &rest[..end];
+        let name = open.split_whitespace().next().unwrap_or_default();
+        let local = name.rsplit(':').next().unwrap_or(name);
+        if local == tag && !open.ends_with('/') {
+            let body = &rest[end + 1..];
+            let close = body.find("</")?;
+            return Some(body[..close].trim().to_string());
+        }
+    }
+    None
+}
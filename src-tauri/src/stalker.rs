@@ -0,0 +1,913 @@
+//! Stalker (Ministra) middleware portal client, the protocol of MAG set-top boxes. The portal
+//! knows the box by its MAC address, sent as a cookie, and every call carries a bearer token
+//! from the portal's handshake. Providers usually tie an account to one MAC and sometimes to
+//! the box's serial and device ids too, so those can be set per server (`PortalDevice`); the
+//! rest is derived from the MAC the way MAG boxes derive it.
+//!
+//! Portals expire tokens after a while and drop sessions that stop calling the watchdog, which
+//! breaks streams and links partway through an evening. Tokens are renewed ahead of
+//! `TOKEN_LIFETIME` and whenever the portal refuses one, and `start` keeps every portal that
+//! is playing or was used lately alive with the watchdog.
+//!
+//! Channels with an archive can be played back for up to `ARCHIVE_DAYS`: their past
+//! programmes are fetched a day at a time, marked in the guide, and played with a
+//! `tv_archive` link (see `catchup`).
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tauri::{Manager, State};
+
+use crate::cache::CacheKind;
+use crate::catalog::text;
+use crate::offline::{self, Cached};
+use crate::servers::{self, ServerConfig, ServerKind, ServerStore};
+use crate::wol;
+
+/// Renew tokens this long after the handshake; portals commonly expire them after 20 minutes.
+const TOKEN_LIFETIME: Duration = Duration::from_secs(10 * 60);
+const WATCHDOG_INTERVAL: Duration = Duration::from_secs(60);
+/// Portals that aren't playing are let go this long after their last call.
+const IDLE_AFTER: Duration = Duration::from_secs(15 * 60);
+/// Hours of guide fetched ahead.
+const GUIDE_HOURS: u32 = 24;
+/// Days of archive offered, however long the portal keeps it.
+const ARCHIVE_DAYS: u32 = 7;
+/// Pages of one day's archive listing read at most, against portals that never say it ended.
+const MAX_ARCHIVE_PAGES: u32 = 20;
+const USER_AGENT: &str = "Mozilla/5.0 (QtEmbedded; U; Linux; C) AppleWebKit/533.3 (KHTML, like Gecko) MAG200 stbapp ver: 2 rev: 250 Safari/533.3";
+const DEFAULT_MODEL: &str = "MAG250";
+const DEFAULT_FIRMWARE: &str = "218";
+const HW_VERSION: &str = "1.7-BD-00";
+const STB_VERSION: &str = "ImageDescription: 0.2.18-r14-pub-250; ImageDate: Fri Jan 15 15:20:44 EET 2016; PORTAL version: 5.6.1; API Version: JS API version: 328; STB API version: 134; Player Engine version: 0x566";
+
+/// A portal server's set-top box identity, saved with the server like its password. Unset
+/// fields are derived (see `device`).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PortalDevice {
+    /// MAC registered with the provider, e.g. `00:1A:79:12:34:56`.
+    pub mac: Option<String>,
+    pub serial: Option<String>,
+    pub device_id: Option<String>,
+    pub device_id2: Option<String>,
+    pub signature: Option<String>,
+    /// MAG model sent as the box type, e.g. `MAG250` or `MAG322`.
+    pub model: Option<String>,
+    /// Firmware image version, e.g. `218`.
+    pub firmware: Option<String>,
+}
+
+impl PortalDevice {
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(mac) = given(&self.mac) {
+            crate::wol::parse_mac(&mac)?;
+        }
+        for (label, value) in [
+            ("serial number", &self.serial),
+            ("device id", &self.device_id),
+            ("second device id", &self.device_id2),
+            ("signature", &self.signature),
+            ("model", &self.model),
+            ("firmware version", &self.firmware),
+        ] {
+            if given(value).is_some_and(|v| !v.chars().all(|c| c.is_ascii_graphic())) {
+                return Err(format!("The portal {} can't contain spaces", label));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The identity sent to the portal, with every field filled in.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Device {
+    pub mac: String,
+    pub serial: String,
+    pub device_id: String,
+    pub device_id2: String,
+    pub signature: String,
+    pub model: String,
+    pub firmware: String,
+}
+
+fn given(value: &Option<String>) -> Option<String> {
+    value
+        .as_deref()
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(str::to_string)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02X}", b)).collect()
+}
+
+/// The server's box identity. Without a configured MAC one is derived from the portal address,
+/// so it stays the same across launches and between testing a server and saving it; serial,
+/// device ids and signature are hashed from the MAC unless given.
+pub(crate) fn device(server: &ServerConfig) -> Device {
+    let custom = server.portal_device.clone().unwrap_or_default();
+    let mac = match given(&custom.mac).and_then(|m| crate::wol::parse_mac(&m).ok()) {
+        Some(bytes) => bytes
+            .iter()
+            .map(|b| format!("{:02X}", b))
+            .collect::<Vec<_>>()
+            .join(":"),
+        None => {
+            let seed = Sha256::digest(server.base_url().as_bytes());
+            // 00:1A:79 is the prefix of Infomir's MAG boxes, which portals expect
+            format!("00:1A:79:{:02X}:{:02X}:{:02X}", seed[0], seed[1], seed[2])
+        }
+    };
+    let serial = given(&custom.serial)
+        .unwrap_or_else(|| hex(&Sha256::digest(mac.as_bytes()))[..13].to_string());
+    let device_id =
+        given(&custom.device_id).unwrap_or_else(|| hex(&Sha256::digest(serial.as_bytes())));
+    let signature = given(&custom.signature).unwrap_or_else(|| {
+        hex(&Sha256::new()
+            .chain_update(serial.as_bytes())
+            .chain_update(mac.as_bytes())
+            .finalize())
+    });
+    Device {
+        device_id2: given(&custom.device_id2).unwrap_or_else(|| device_id.clone()),
+        model: given(&custom.model).unwrap_or_else(|| DEFAULT_MODEL.to_string()),
+        firmware: given(&custom.firmware).unwrap_or_else(|| DEFAULT_FIRMWARE.to_string()),
+        mac,
+        serial,
+        device_id,
+        signature,
+    }
+}
+
+struct Session {
+    token: String,
+    /// The box the token was issued to; a changed identity needs a new handshake.
+    device: Device,
+    issued: Instant,
+    /// Last call made on the user's behalf; watchdog calls don't count.
+    used: Instant,
+}
+
+static SESSIONS: Mutex<BTreeMap<String, Session>> = Mutex::new(BTreeMap::new());
+
+fn sessions() -> std::sync::MutexGuard<'static, BTreeMap<String, Session>> {
+    SESSIONS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// The last channel list per server, for the `cmd` and archive of a channel being played.
+static CHANNELS: Mutex<BTreeMap<String, HashMap<String, PortalChannel>>> =
+    Mutex::new(BTreeMap::new());
+
+fn channels() -> std::sync::MutexGuard<'static, BTreeMap<String, HashMap<String, PortalChannel>>> {
+    CHANNELS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PortalChannel {
+    pub id: String,
+    pub name: String,
+    pub number: Option<String>,
+    /// Name of the channel's genre, the portal's grouping.
+    pub genre: Option<String>,
+    pub logo: Option<String>,
+    pub xmltv_id: Option<String>,
+    /// What `create_link` is asked for to play the channel.
+    pub cmd: String,
+    /// Hours the portal keeps in the channel's archive; 0 without one.
+    #[serde(default)]
+    pub archive_hours: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PortalProgramme {
+    pub id: Option<String>,
+    pub channel_id: String,
+    /// Unix seconds.
+    pub start: i64,
+    /// Unix seconds.
+    pub stop: i64,
+    pub title: String,
+    pub description: Option<String>,
+    pub category: Option<String>,
+    /// In the channel's archive, so it can be played back.
+    #[serde(default)]
+    pub archived: bool,
+}
+
+enum CallError {
+    /// The portal turned the token or the device down.
+    Refused,
+    Failed(String),
+}
+
+impl CallError {
+    fn message(self) -> String {
+        match self {
+            CallError::Refused => {
+                "The portal refused this device; check that its MAC address is registered"
+                    .to_string()
+            }
+            CallError::Failed(e) => e,
+        }
+    }
+}
+
+/// The portal page without its `/c` suffix, which relative logo paths hang off.
+fn portal_root(server: &ServerConfig) -> &str {
+    let base = server.base_url();
+    base.strip_suffix("/c").unwrap_or(base)
+}
+
+/// The API endpoint. Users enter the portal page (`http://host/stalker_portal/c/`,
+/// `http://host:8080/c/`): Ministra answers under `server/load.php` next to it, Xtream-based
+/// portals at `portal.php`. An address naming a `.php` is used as it is.
+fn api_url(server: &ServerConfig) -> String {
+    let base = server.base_url();
+    if base.ends_with(".php") {
+        return base.to_string();
+    }
+    let root = portal_root(server);
+    if root.ends_with("/stalker_portal") {
+        format!("{}/server/load.php", root)
+    } else {
+        format!("{}/portal.php", root)
+    }
+}
+
+fn client(server: &ServerConfig) -> Result<reqwest::Client, String> {
+    crate::http::client(Some(server))
+}
+
+/// Calls the portal with `token` (empty for the handshake) and returns its `js` payload.
+async fn call(
+    server: &ServerConfig,
+    token: &str,
+    query: &[(&str, &str)],
+) -> Result<Value, CallError> {
+    let device = device(server);
+    let mut req = client(server)
+        .map_err(CallError::Failed)?
+        .get(api_url(server))
+        .header("User-Agent", USER_AGENT)
+        .header(
+            "X-User-Agent",
+            format!("Model: {}; Link: WiFi", device.model),
+        )
+        .header("Referer", format!("{}/c/", portal_root(server)))
+        .header(
+            "Cookie",
+            format!(
+                "mac={}; stb_lang=en; timezone=UTC",
+                urlencoding::encode(&device.mac)
+            ),
+        )
+        .query(query)
+        .query(&[("JsHttpRequest", "1-xml")]);
+    if !token.is_empty() {
+        req = req.header("Authorization", format!("Bearer {}", token));
+    }
+    let _permit = crate::http::queue(Some(server)).await;
+    let resp = wol::send_waking(server, req)
+        .await
+        .map_err(|e| CallError::Failed(format!("Portal request failed: {}", e)))?;
+    let status = resp.status();
+    if matches!(status.as_u16(), 401 | 403) {
+        return Err(CallError::Refused);
+    }
+    if !status.is_success() {
+        return Err(CallError::Failed(format!(
+            "The portal returned HTTP {}",
+            status
+        )));
+    }
+    let body = resp
+        .text()
+        .await
+        .map_err(|e| CallError::Failed(format!("Portal request failed: {}", e)))?;
+    // Expired tokens get a plain-text answer rather than JSON
+    if body.trim_start().starts_with("Authorization failed") {
+        return Err(CallError::Refused);
+    }
+    let mut value: Value = serde_json::from_str(&body)
+        .map_err(|e| CallError::Failed(format!("Invalid portal response: {}", e)))?;
+    Ok(value["js"].take())
+}
+
+/// Handshakes for a token and activates it with the box's profile.
+pub(crate) async fn handshake(server: &ServerConfig) -> Result<String, String> {
+    let js = call(
+        server,
+        "",
+        &[("type", "stb"), ("action", "handshake"), ("token", "")],
+    )
+    .await
+    .map_err(CallError::message)?;
+    let token = js["token"]
+        .as_str()
+        .filter(|t| !t.is_empty())
+        .ok_or("The portal did not hand out a token")?
+        .to_string();
+    let device = device(server);
+    let timestamp = crate::now_secs().to_string();
+    let profile = call(
+        server,
+        &token,
+        &[
+            ("type", "stb"),
+            ("action", "get_profile"),
+            ("hd", "1"),
+            ("ver", STB_VERSION),
+            ("num_banks", "2"),
+            ("sn", &device.serial),
+            ("stb_type", &device.model),
+            ("image_version", &device.firmware),
+            ("video_out", "hdmi"),
+            ("device_id", &device.device_id),
+            ("device_id2", &device.device_id2),
+            ("signature", &device.signature),
+            ("auth_second_step", "1"),
+            ("hw_version", HW_VERSION),
+            ("not_valid_token", "0"),
+            ("client_type", "STB"),
+            ("timestamp", &timestamp),
+            ("api_signature", "262"),
+        ],
+    )
+    .await
+    .map_err(CallError::message)?;
+    if let Some(reason) = profile["block_msg"]
+        .as_str()
+        .filter(|m| !m.trim().is_empty())
+    {
+        return Err(format!("The portal blocked this device: {}", reason.trim()));
+    }
+    Ok(token)
+}
+
+/// The server's token, handshaking first when there is none, it is due for renewal, the box
+/// identity changed, or `renew` is set.
+async fn session_token(server: &ServerConfig, renew: bool) -> Result<String, String> {
+    let device = device(server);
+    if !renew {
+        if let Some(session) = sessions()
+            .get(&server.id)
+            .filter(|s| s.issued.elapsed() < TOKEN_LIFETIME && s.device == device)
+        {
+            return Ok(session.token.clone());
+        }
+    }
+    let token = handshake(server).await?;
+    let now = Instant::now();
+    let used = sessions().get(&server.id).map_or(now, |s| s.used);
+    sessions().insert(
+        server.id.clone(),
+        Session {
+            token: token.clone(),
+            device,
+            issued: now,
+            used,
+        },
+    );
+    Ok(token)
+}
+
+/// Calls the portal with the server's session, renewing the token once if it was refused.
+async fn portal_call(server: &ServerConfig, query: &[(&str, &str)]) -> Result<Value, String> {
+    let result = match call(server, &session_token(server, false).await?, query).await {
+        Err(CallError::Refused) => call(server, &session_token(server, true).await?, query).await,
+        other => other,
+    };
+    if let Some(session) = sessions().get_mut(&server.id) {
+        session.used = Instant::now();
+    }
+    result.map_err(CallError::message)
+}
+
+/// Renews the token when it is due and calls the watchdog, which tells the portal the box is
+/// still there (and whether it is playing).
+async fn keep_alive(server: &ServerConfig, playing: bool) -> Result<(), String> {
+    let due = sessions()
+        .get(&server.id)
+        .is_none_or(|s| s.issued.elapsed() + WATCHDOG_INTERVAL >= TOKEN_LIFETIME);
+    let token = session_token(server, due).await?;
+    let query = [
+        ("type", "watchdog"),
+        ("action", "get_events"),
+        ("init", "0"),
+        ("cur_play_type", if playing { "1" } else { "0" }),
+        ("event_active_id", "0"),
+    ];
+    match call(server, &token, &query).await {
+        // A fresh session counts as alive until the next round
+        Err(CallError::Refused) => session_token(server, true).await.map(|_| ()),
+        other => other.map(|_| ()).map_err(CallError::message),
+    }
+}
+
+/// Keeps portal sessions alive in the background. Portals that aren't playing and haven't been
+/// used for `IDLE_AFTER` are dropped, and handshake again when next needed.
+pub fn start(app: &tauri::AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(WATCHDOG_INTERVAL).await;
+            let ids: Vec<String> = sessions().keys().cloned().collect();
+            for id in ids {
+                let Ok(server) = servers::get(&app.state::<ServerStore>(), &id) else {
+                    sessions().remove(&id);
+                    continue;
+                };
+                let playing = !crate::connections::list_active_streams(Some(id.clone())).is_empty();
+                let idle = sessions()
+                    .get(&id)
+                    .is_some_and(|s| s.used.elapsed() >= IDLE_AFTER);
+                if idle && !playing {
+                    tracing::debug!(server = %server.name, "Letting the idle portal session go");
+                    sessions().remove(&id);
+                    continue;
+                }
+                if let Err(e) = keep_alive(&server, playing).await {
+                    tracing::warn!(server = %server.name, "Portal keep-alive failed: {}", e);
+                }
+            }
+        }
+    });
+}
+
+fn portal_server(store: &ServerStore, server_id: &str) -> Result<ServerConfig, String> {
+    let server = servers::get(store, server_id)?;
+    if server.kind != ServerKind::Stalker {
+        return Err(format!("{} is not a Stalker portal", server.name));
+    }
+    Ok(server)
+}
+
+fn parse_channels(server: &ServerConfig, genres: &Value, channels: &Value) -> Vec<PortalChannel> {
+    let genres: HashMap<String, String> = genres
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|g| Some((text(&g["id"])?, text(&g["title"])?)))
+        .collect();
+    channels["data"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|c| {
+            Some(PortalChannel {
+                id: text(&c["id"])?,
+                cmd: text(&c["cmd"])?,
+                name: text(&c["name"]).unwrap_or_default(),
+                number: text(&c["number"]),
+                genre: text(&c["tv_genre_id"]).and_then(|id| genres.get(&id).cloned()),
+                logo: text(&c["logo"]).map(|logo| {
+                    if logo.starts_with("http") {
+                        logo
+                    } else {
+                        format!("{}/{}", portal_root(server), logo.trim_start_matches('/'))
+                    }
+                }),
+                xmltv_id: text(&c["xmltv_id"]),
+                archive_hours: if text(&c["tv_archive"]).as_deref() == Some("1")
+                    || text(&c["enable_tv_archive"]).as_deref() == Some("1")
+                {
+                    text(&c["tv_archive_duration"])
+                        .and_then(|h| h.parse().ok())
+                        .unwrap_or(ARCHIVE_DAYS * 24)
+                } else {
+                    0
+                },
+            })
+        })
+        .collect()
+}
+
+pub(crate) async fn fetch_channels(server: &ServerConfig) -> Result<Vec<PortalChannel>, String> {
+    let genres = portal_call(server, &[("type", "itv"), ("action", "get_genres")]).await?;
+    let channels = portal_call(server, &[("type", "itv"), ("action", "get_all_channels")]).await?;
+    Ok(parse_channels(server, &genres, &channels))
+}
+
+#[tauri::command]
+pub async fn stalker_channels(
+    app: tauri::AppHandle,
+    store: State<'_, ServerStore>,
+    server_id: String,
+) -> Result<Cached<Vec<PortalChannel>>, String> {
+    let server = portal_server(&store, &server_id)?;
+    let cached = offline::cached(
+        &app,
+        CacheKind::Catalogs,
+        &server_id,
+        "channels",
+        fetch_channels(&server),
+    )
+    .await?;
+    channels().insert(
+        server_id,
+        cached
+            .data
+            .iter()
+            .map(|c| (c.id.clone(), c.clone()))
+            .collect(),
+    );
+    Ok(cached)
+}
+
+/// One of the server's channels, loading the channel list when it hasn't been yet.
+async fn channel(
+    app: &tauri::AppHandle,
+    server: &ServerConfig,
+    channel_id: &str,
+) -> Result<PortalChannel, String> {
+    let known = || {
+        channels()
+            .get(&server.id)
+            .and_then(|list| list.get(channel_id).cloned())
+    };
+    if let Some(channel) = known() {
+        return Ok(channel);
+    }
+    stalker_channels(app.clone(), app.state(), server.id.clone()).await?;
+    known().ok_or_else(|| format!("Unknown portal channel: {}", channel_id))
+}
+
+/// The URL in a `create_link` answer, which comes after a player hint (`ffmpeg http://…`).
+fn link(cmd: &str) -> Option<String> {
+    cmd.split_whitespace()
+        .find(|part| part.contains("://"))
+        .map(str::to_string)
+}
+
+async fn create_link(server: &ServerConfig, link_type: &str, cmd: &str) -> Result<String, String> {
+    let js = portal_call(
+        server,
+        &[("type", link_type), ("action", "create_link"), ("cmd", cmd)],
+    )
+    .await?;
+    js["cmd"]
+        .as_str()
+        .and_then(link)
+        .ok_or_else(|| "The portal returned no stream link".to_string())
+}
+
+/// A playable URL for a channel. Portal links are single-use and expire, so one is asked for
+/// every time a channel is played.
+pub(crate) async fn stream_url(
+    app: &tauri::AppHandle,
+    server: &ServerConfig,
+    channel_id: &str,
+) -> Result<String, String> {
+    let cmd = channel(app, server, channel_id).await?.cmd;
+    create_link(server, "itv", &cmd).await
+}
+
+/// A playback URL for a programme in a channel's archive.
+pub(crate) async fn archive_url(
+    server: &ServerConfig,
+    programme_id: &str,
+) -> Result<String, String> {
+    create_link(
+        server,
+        "tv_archive",
+        &format!("auto /media/{}.mpg", programme_id),
+    )
+    .await
+}
+
+/// The box identity the server's portal sees, derived fields included, so the MAC can be
+/// registered with the provider.
+#[tauri::command]
+pub fn stalker_device(store: State<'_, ServerStore>, server_id: String) -> Result<Device, String> {
+    Ok(device(&portal_server(&store, &server_id)?))
+}
+
+#[tauri::command]
+pub async fn stalker_stream_url(
+    app: tauri::AppHandle,
+    store: State<'_, ServerStore>,
+    server_id: String,
+    channel_id: String,
+) -> Result<String, String> {
+    let server = portal_server(&store, &server_id)?;
+    stream_url(&app, &server, &channel_id).await
+}
+
+fn parse_programme(p: &Value) -> Option<PortalProgramme> {
+    Some(PortalProgramme {
+        id: text(&p["id"]),
+        channel_id: text(&p["ch_id"])?,
+        start: text(&p["start_timestamp"])?.parse().ok()?,
+        stop: text(&p["stop_timestamp"])?.parse().ok()?,
+        title: text(&p["name"]).unwrap_or_default(),
+        description: text(&p["descr"]),
+        category: text(&p["category"]),
+        archived: text(&p["mark_archive"]).as_deref() == Some("1"),
+    })
+}
+
+fn parse_guide(js: &Value) -> Vec<PortalProgramme> {
+    js["data"]
+        .as_object()
+        .into_iter()
+        .flat_map(|channels| channels.values())
+        .filter_map(Value::as_array)
+        .flatten()
+        .filter_map(parse_programme)
+        .collect()
+}
+
+/// A page of a day's listing, and whether more pages follow.
+fn parse_archive_page(js: &Value) -> (Vec<PortalProgramme>, bool) {
+    let programmes: Vec<PortalProgramme> = js["data"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(parse_programme)
+        .collect();
+    let number = |key: &str| text(&js[key]).and_then(|n| n.parse::<u64>().ok());
+    let more = match (
+        number("total_items"),
+        number("max_page_items"),
+        number("cur_page"),
+    ) {
+        (Some(total), Some(per_page), Some(page)) => per_page > 0 && page * per_page < total,
+        _ => false,
+    };
+    let more = more && !programmes.is_empty();
+    (programmes, more)
+}
+
+/// One channel's listing for the UTC day `date` (`YYYY-MM-DD`), past programmes included.
+async fn fetch_day(
+    server: &ServerConfig,
+    channel_id: &str,
+    date: &str,
+) -> Result<Vec<PortalProgramme>, String> {
+    let mut programmes = Vec::new();
+    for page in 1..=MAX_ARCHIVE_PAGES {
+        let page = page.to_string();
+        let js = portal_call(
+            server,
+            &[
+                ("type", "epg"),
+                ("action", "get_simple_data_table"),
+                ("ch_id", channel_id),
+                ("date", date),
+                ("p", &page),
+            ],
+        )
+        .await?;
+        let (found, more) = parse_archive_page(&js);
+        programmes.extend(found);
+        if !more {
+            break;
+        }
+    }
+    Ok(programmes)
+}
+
+fn utc_date(secs: i64) -> String {
+    let (year, month, day) = crate::civil_date(secs.max(0) as u64);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Past programmes of a channel between `from` and `to` (unix seconds) that can be played
+/// back, within its archive and `ARCHIVE_DAYS`. Finished days are cached like other guide data.
+pub(crate) async fn archive(
+    app: &tauri::AppHandle,
+    server: &ServerConfig,
+    channel_id: &str,
+    from: i64,
+    to: i64,
+) -> Result<Vec<PortalProgramme>, String> {
+    let now = crate::now_secs() as i64;
+    let kept = channel(app, server, channel_id)
+        .await?
+        .archive_hours
+        .min(ARCHIVE_DAYS * 24);
+    let from = from.max(now - i64::from(kept) * 3600);
+    let to = to.min(now);
+    let mut programmes = Vec::new();
+    if kept == 0 || from >= to {
+        return Ok(programmes);
+    }
+    let mut day = from.div_euclid(86_400) * 86_400;
+    while day < to {
+        let date = utc_date(day);
+        let key = format!("archive:{}:{}", channel_id, date);
+        let listing = fetch_day(server, channel_id, &date);
+        let found = if day + 86_400 <= now {
+            offline::cached(app, CacheKind::Epg, &server.id, &key, listing)
+                .await?
+                .data
+        } else {
+            listing.await?
+        };
+        programmes.extend(
+            found
+                .into_iter()
+                .filter(|p| p.archived && p.stop <= now && p.stop > from && p.start < to),
+        );
+        day += 86_400;
+    }
+    Ok(programmes)
+}
+
+/// Programmes of the last `days` (at most `ARCHIVE_DAYS`) that can be played back from the
+/// channel's archive, oldest first.
+#[tauri::command]
+pub async fn stalker_archive(
+    app: tauri::AppHandle,
+    store: State<'_, ServerStore>,
+    server_id: String,
+    channel_id: String,
+    days: Option<u32>,
+) -> Result<Vec<PortalProgramme>, String> {
+    let server = portal_server(&store, &server_id)?;
+    let days = days.unwrap_or(ARCHIVE_DAYS).clamp(1, ARCHIVE_DAYS);
+    let now = crate::now_secs() as i64;
+    let from = now - i64::from(days) * 86_400;
+    archive(&app, &server, &channel_id, from, now).await
+}
+
+/// The guide for every channel over the next `GUIDE_HOURS`.
+pub(crate) async fn guide(
+    app: &tauri::AppHandle,
+    server: &ServerConfig,
+) -> Result<Cached<Vec<PortalProgramme>>, String> {
+    let hours = GUIDE_HOURS.to_string();
+    offline::cached(app, CacheKind::Epg, &server.id, "epg", async {
+        let js = portal_call(
+            server,
+            &[
+                ("type", "itv"),
+                ("action", "get_epg_info"),
+                ("period", &hours),
+            ],
+        )
+        .await?;
+        Ok(parse_guide(&js))
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn portal(url: &str) -> ServerConfig {
+        ServerConfig {
+            url: url.to_string(),
+            kind: ServerKind::Stalker,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn api_urls_follow_the_portal_flavour() {
+        assert_eq!(
+            api_url(&portal("http://host/stalker_portal/c/")),
+            "http://host/stalker_portal/server/load.php"
+        );
+        assert_eq!(
+            api_url(&portal("http://host:8080/c/")),
+            "http://host:8080/portal.php"
+        );
+        assert_eq!(
+            api_url(&portal("http://host:8080/")),
+            "http://host:8080/portal.php"
+        );
+        assert_eq!(
+            api_url(&portal("http://host/server/load.php")),
+            "http://host/server/load.php"
+        );
+    }
+
+    #[test]
+    fn devices_are_stable_per_portal() {
+        let a = device(&portal("http://host/c/"));
+        let b = device(&portal("http://host/c"));
+        let other = device(&portal("http://other/c/"));
+        assert_eq!(a.mac, b.mac);
+        assert_ne!(a.mac, other.mac);
+        assert!(a.mac.starts_with("00:1A:79:"));
+        assert_eq!(a.serial.len(), 13);
+    }
+
+    #[test]
+    fn configured_identities_replace_the_derived_one() {
+        let mut server = portal("http://host/c/");
+        server.portal_device = Some(PortalDevice {
+            mac: Some("00-1a-79-aa-bb-cc".to_string()),
+            model: Some("MAG322".to_string()),
+            ..Default::default()
+        });
+        let configured = device(&server);
+        assert_eq!(configured.mac, "00:1A:79:AA:BB:CC");
+        assert_eq!(configured.model, "MAG322");
+        assert_eq!(configured.firmware, DEFAULT_FIRMWARE);
+        // Fields left unset follow the configured MAC, not the portal address
+        assert_ne!(configured.serial, device(&portal("http://host/c/")).serial);
+
+        server.portal_device.as_mut().unwrap().serial = Some(" 0123456789ABC ".to_string());
+        assert_eq!(device(&server).serial, "0123456789ABC");
+    }
+
+    #[test]
+    fn portal_devices_are_validated() {
+        let bad_mac = PortalDevice {
+            mac: Some("00:1A:79".to_string()),
+            ..Default::default()
+        };
+        assert!(bad_mac.validate().is_err());
+        let spaced = PortalDevice {
+            serial: Some("01 23".to_string()),
+            ..Default::default()
+        };
+        assert!(spaced.validate().is_err());
+        assert!(PortalDevice::default().validate().is_ok());
+    }
+
+    #[test]
+    fn links_drop_the_player_hint() {
+        assert_eq!(
+            link("ffmpeg http://host/play/live.php?stream=1").as_deref(),
+            Some("http://host/play/live.php?stream=1")
+        );
+        assert_eq!(
+            link("http://host/a.ts").as_deref(),
+            Some("http://host/a.ts")
+        );
+        assert_eq!(link("ffrt"), None);
+    }
+
+    #[test]
+    fn channels_carry_their_genre_and_command() {
+        let genres = serde_json::json!([{ "id": "5", "title": "News" }]);
+        let channels = serde_json::json!({ "data": [
+            { "id": 12, "name": "One", "number": "1", "cmd": "ffrt http://localhost/ch/12_",
+              "tv_genre_id": "5", "logo": "misc/logos/12.png" },
+            { "id": 13, "name": "No command" },
+        ]});
+        let parsed = parse_channels(&portal("http://host/c/"), &genres, &channels);
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(parsed[0].id, "12");
+        assert_eq!(parsed[0].genre.as_deref(), Some("News"));
+        assert_eq!(parsed[0].cmd, "ffrt http://localhost/ch/12_");
+        assert_eq!(
+            parsed[0].logo.as_deref(),
+            Some("http://host/misc/logos/12.png")
+        );
+    }
+
+    #[test]
+    fn guide_entries_need_a_channel_and_times() {
+        let js = serde_json::json!({ "data": { "12": [
+            { "id": "1", "ch_id": "12", "name": "News", "start_timestamp": 100,
+              "stop_timestamp": "200" },
+            { "id": "2", "ch_id": "12", "name": "Broken" },
+        ]}});
+        let guide = parse_guide(&js);
+        assert_eq!(guide.len(), 1);
+        assert_eq!((guide[0].start, guide[0].stop), (100, 200));
+        assert!(!guide[0].archived);
+    }
+
+    #[test]
+    fn archive_pages_say_when_more_follow() {
+        let page = |cur: u64| {
+            serde_json::json!({
+                "total_items": 25, "max_page_items": "10", "cur_page": cur,
+                "data": [{ "id": "7", "ch_id": "12", "name": "Old news",
+                           "start_timestamp": 100, "stop_timestamp": 200, "mark_archive": 1 }],
+            })
+        };
+        let (first, more) = parse_archive_page(&page(1));
+        assert!(more);
+        assert!(first[0].archived);
+        assert!(!parse_archive_page(&page(3)).1);
+        assert!(!parse_archive_page(&serde_json::json!({ "data": [] })).1);
+    }
+
+    #[test]
+    fn archive_flags_come_with_a_duration() {
+        let channels = serde_json::json!({ "data": [
+            { "id": 1, "cmd": "a", "tv_archive": 1, "tv_archive_duration": "72" },
+            { "id": 2, "cmd": "b", "enable_tv_archive": "1" },
+            { "id": 3, "cmd": "c", "tv_archive": 0, "tv_archive_duration": "72" },
+        ]});
+        let parsed = parse_channels(&portal("http://host/c/"), &Value::Null, &channels);
+        let hours: Vec<u32> = parsed.iter().map(|c| c.archive_hours).collect();
+        assert_eq!(hours, [72, ARCHIVE_DAYS * 24, 0]);
+    }
+}
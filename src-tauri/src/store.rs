@@ -0,0 +1,72 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tauri::Manager;
+
+/// A value persisted as pretty-printed JSON in the app data dir, kept in memory behind a mutex.
+pub struct JsonStore<T> {
+    path: PathBuf,
+    data: Mutex<T>,
+}
+
+impl<T> JsonStore<T>
+where
+    T: Serialize + DeserializeOwned + Default,
+{
+    /// Loads `file_name` from the app data dir, falling back to `T::default()` when missing or unreadable.
+    pub fn open(app: &tauri::AppHandle, file_name: &str) -> Self {
+        let path = app
+            .path()
+            .app_data_dir()
+            .map(|dir| dir.join(file_name))
+            .unwrap_or_else(|_| PathBuf::from(file_name));
+        let data = fs::read_to_string(&path)
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+        Self {
+            path,
+            data: Mutex::new(data),
+        }
+    }
+
+    /// An empty store persisted at `path`, for tests that run without an app.
+    #[cfg(test)]
+    pub fn at(path: PathBuf) -> Self {
+        Self {
+            path,
+            data: Mutex::new(T::default()),
+        }
+    }
+
+    pub fn read<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        f(&self.lock())
+    }
+
+    /// Applies `f` and writes the result back to disk.
+    pub fn update<R>(&self, f: impl FnOnce(&mut T) -> R) -> Result<R, String> {
+        let mut data = self.lock();
+        let result = f(&mut data);
+        self.persist(&data)?;
+        Ok(result)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, T> {
+        self.data.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn persist(&self, data: &T) -> Result<(), String> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).map_err(|e| format!("Failed to create data dir: {}", e))?;
+        }
+        let json = serde_json::to_string_pretty(data).map_err(|e| e.to_string())?;
+        // Write to a temp file first so a crash mid-write can't truncate the store
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, json).map_err(|e| format!("Failed to write {}: {}", tmp.display(), e))?;
+        fs::rename(&tmp, &self.path)
+            .map_err(|e| format!("Failed to write {}: {}", self.path.display(), e))
+    }
+}
@@ -0,0 +1,152 @@
+//! Live statistics for streams relayed by the proxy: throughput, observed bitrate, stalls and
+//! MPEG-TS continuity errors. Each relay has a `Meter` fed with every chunk it forwards; the
+//! proxy snapshots the meters once a second and sends them to the owning window as
+//! `stream-stats`, for the diagnostics overlay.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::quality::Tally;
+
+/// A pause this long between chunks of one response counts as a stall.
+pub const STALL_GAP: Duration = Duration::from_secs(2);
+const TS_PACKET: usize = 188;
+const TS_SYNC: u8 = 0x47;
+const NULL_PID: u16 = 0x1fff;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamStats {
+    pub session_id: String,
+    /// Bytes relayed since the stream started.
+    pub bytes: u64,
+    /// Download rate while data was flowing during the last interval, in bits per second.
+    pub throughput_bps: f64,
+    /// Average rate since the first byte, in bits per second; close to the media bitrate for a
+    /// live stream.
+    pub bitrate_bps: f64,
+    pub stalls: u32,
+    pub stalled_secs: f64,
+    /// Packets whose continuity counter skipped, i.e. lost TS packets.
+    pub continuity_errors: u64,
+}
+
+/// Checks MPEG-TS continuity counters across chunks of a stream.
+#[derive(Default)]
+struct Continuity {
+    /// Bytes of a packet split across chunks.
+    carry: Vec<u8>,
+    counters: HashMap<u16, u8>,
+    errors: u64,
+}
+
+impl Continuity {
+    fn feed(&mut self, chunk: &[u8]) {
+        self.carry.extend_from_slice(chunk);
+        let mut at = 0;
+        while self.carry.len() - at >= TS_PACKET {
+            if self.carry[at] != TS_SYNC {
+                // Lost sync; skip to the next sync byte
+                at += 1;
+                continue;
+            }
+            let packet = &self.carry[at..at + TS_PACKET];
+            at += TS_PACKET;
+            let pid = u16::from(packet[1] & 0x1f) << 8 | u16::from(packet[2]);
+            let control = (packet[3] >> 4) & 0x3;
+            let counter = packet[3] & 0x0f;
+            if pid == NULL_PID || control & 0x1 == 0 {
+                // Counters only advance on packets carrying payload
+                continue;
+            }
+            let discontinuity = control & 0x2 != 0 && packet[4] > 0 && packet[5] & 0x80 != 0;
+            if let Some(last) = self.counters.insert(pid, counter) {
+                // A repeated counter is an allowed duplicate packet
+                if !discontinuity && counter != last && counter != (last + 1) & 0x0f {
+                    self.errors += 1;
+                }
+            }
+        }
+        self.carry.drain(..at);
+    }
+}
+
+#[derive(Default)]
+pub struct Meter {
+    started: Option<Instant>,
+    bytes: u64,
+    interval_bytes: u64,
+    /// Time spent receiving during the current interval.
+    interval_busy: Duration,
+    stalls: u32,
+    stalled: Duration,
+    /// Stalls already handed to `take_stalls`.
+    taken: (u32, Duration),
+    continuity: Continuity,
+}
+
+impl Meter {
+    /// Starts a new upstream response; a TS packet left over from the last one is dropped.
+    pub fn begin_response(&mut self) {
+        self.continuity.carry.clear();
+    }
+
+    /// Records a chunk that arrived `gap` after the previous one of the same response.
+    pub fn record(&mut self, chunk: &[u8], gap: Duration, transport_stream: bool) {
+        self.started.get_or_insert_with(Instant::now);
+        self.bytes += chunk.len() as u64;
+        self.interval_bytes += chunk.len() as u64;
+        self.interval_busy += gap;
+        if gap >= STALL_GAP {
+            self.stalls += 1;
+            self.stalled += gap;
+        }
+        if transport_stream {
+            self.continuity.feed(chunk);
+        }
+    }
+
+    /// Stalls since the last call, as a quality tally.
+    pub fn take_stalls(&mut self) -> Tally {
+        let (stalls, stalled) = self.taken;
+        self.taken = (self.stalls, self.stalled);
+        Tally {
+            stalls: self.stalls - stalls,
+            stalled_secs: (self.stalled - stalled).as_secs_f64(),
+            ..Tally::default()
+        }
+    }
+
+    /// Whether anything has been relayed yet.
+    pub fn active(&self) -> bool {
+        self.started.is_some()
+    }
+
+    /// Current statistics; starts a new interval for the throughput.
+    pub fn snapshot(&mut self, session_id: &str) -> StreamStats {
+        let elapsed = self.started.map_or(0.0, |s| s.elapsed().as_secs_f64());
+        let busy = self.interval_busy.as_secs_f64();
+        let stats = StreamStats {
+            session_id: session_id.to_string(),
+            bytes: self.bytes,
+            throughput_bps: if busy > 0.0 {
+                self.interval_bytes as f64 * 8.0 / busy
+            } else {
+                0.0
+            },
+            bitrate_bps: if elapsed > 0.0 {
+                self.bytes as f64 * 8.0 / elapsed
+            } else {
+                0.0
+            },
+            stalls: self.stalls,
+            stalled_secs: self.stalled.as_secs_f64(),
+            continuity_errors: self.continuity.errors,
+        };
+        self.interval_bytes = 0;
+        self.interval_busy = Duration::ZERO;
+        stats
+    }
+}
@@ -0,0 +1,584 @@
+//! External subtitle files: SRT, ASS/SSA, MicroDVD and WebVTT in any common charset are
+//! converted to UTF-8 WebVTT (the only format the HTML5 video element renders) and served to
+//! the video window through the local proxy.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use tauri::{Emitter, State};
+use tauri_plugin_dialog::DialogExt;
+
+use crate::cache::CacheKind;
+use crate::charset::{self, Charset};
+use crate::proxy::ProxyState;
+
+const EXTENSIONS: [&str; 5] = ["srt", "ass", "ssa", "sub", "vtt"];
+
+/// Embedded subtitle codecs ffmpeg can turn into WebVTT; bitmap ones (PGS, DVB) need OCR.
+const TEXT_CODECS: [&str; 6] = ["subrip", "ass", "ssa", "mov_text", "webvtt", "text"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cue {
+    pub start_ms: u64,
+    pub end_ms: u64,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubtitleTrack {
+    /// WebVTT URL served by the local proxy.
+    pub url: String,
+    /// Original file, for external players (`--sub-file`).
+    pub path: String,
+    pub label: String,
+    /// Language guessed from the file name (`Movie.en.srt`).
+    pub language: Option<String>,
+    /// Charset the file was decoded from, e.g. `windows-1251`.
+    pub encoding: String,
+}
+
+/// Parses `H:MM:SS,mmm` / `HH:MM:SS.mmm` / `MM:SS.mmm` (SRT, VTT) and `H:MM:SS.cc` (ASS).
+fn parse_time(s: &str) -> Option<u64> {
+    let s = s.trim().replace(',', ".");
+    let (clock, frac) = s.split_once('.').unwrap_or((&s, "0"));
+    let mut secs = 0u64;
+    for part in clock.split(':') {
+        secs = secs * 60 + part.trim().parse::<u64>().ok()?;
+    }
+    // Scale the fraction to milliseconds whatever its precision
+    let frac: String = frac.chars().take(3).collect();
+    let ms = frac.parse::<u64>().ok()? * 10u64.pow(3 - frac.len() as u32);
+    Some(secs * 1000 + ms)
+}
+
+fn format_time(ms: u64) -> String {
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        ms % 1000
+    )
+}
+
+/// Parses SRT (and the cue body of WebVTT, which shares the timing line syntax).
+pub fn parse_srt(text: &str) -> Vec<Cue> {
+    let mut cues = Vec::new();
+    let normalized = text.replace("\r\n", "\n");
+    for block in normalized.split("\n\n") {
+        let mut lines = block.lines().skip_while(|l| !l.contains("-->"));
+        let Some(timing) = lines.next() else {
+            continue;
+        };
+        let Some((start, rest)) = timing.split_once("-->") else {
+            continue;
+        };
+        // VTT allows cue settings after the end time
+        let end = rest.split_whitespace().next().unwrap_or_default();
+        let (Some(start_ms), Some(end_ms)) = (parse_time(start), parse_time(end)) else {
+            continue;
+        };
+        let text = lines.collect::<Vec<_>>().join("\n");
+        if !text.trim().is_empty() {
+            cues.push(Cue {
+                start_ms,
+                end_ms,
+                text,
+            });
+        }
+    }
+    cues
+}
+
+/// Strips ASS override blocks (`{\i1}`) and converts its escapes to plain text.
+fn clean_ass_text(text: &str) -> String {
+    let mut out = String::new();
+    let mut in_override = false;
+    for c in text.chars() {
+        match c {
+            '{' => in_override = true,
+            '}' => in_override = false,
+            _ if !in_override => out.push(c),
+            _ => {}
+        }
+    }
+    out.replace("\\N", "\n")
+        .replace("\\n", "\n")
+        .replace("\\h", " ")
+}
+
+/// Parses the `[Events]` section of an ASS/SSA script.
+pub fn parse_ass(text: &str) -> Vec<Cue> {
+    let mut cues = Vec::new();
+    let mut in_events = false;
+    let mut fields: Vec<String> = Vec::new();
+    for line in text.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_events = line.eq_ignore_ascii_case("[events]");
+            continue;
+        }
+        if !in_events {
+            continue;
+        }
+        if let Some(format) = line.strip_prefix("Format:") {
+            fields = format.split(',').map(|f| f.trim().to_lowercase()).collect();
+            continue;
+        }
+        let Some(dialogue) = line.strip_prefix("Dialogue:") else {
+            continue;
+        };
+        if fields.is_empty() {
+            continue;
+        }
+        // Text is the last field and may itself contain commas
+        let values: Vec<&str> = dialogue.splitn(fields.len(), ',').collect();
+        let field = |name: &str| {
+            fields
+                .iter()
+                .position(|f| f == name)
+                .and_then(|i| values.get(i))
+                .map(|v| v.trim())
+        };
+        let (Some(start), Some(end), Some(raw)) = (field("start"), field("end"), field("text"))
+        else {
+            continue;
+        };
+        let (Some(start_ms), Some(end_ms)) = (parse_time(start), parse_time(end)) else {
+            continue;
+        };
+        let text = clean_ass_text(raw);
+        if !text.trim().is_empty() {
+            cues.push(Cue {
+                start_ms,
+                end_ms,
+                text,
+            });
+        }
+    }
+    cues.sort_by_key(|c| c.start_ms);
+    cues
+}
+
+/// Frame rate assumed for MicroDVD files that don't declare one.
+const MICRODVD_DEFAULT_FPS: f64 = 23.976;
+
+/// Parses MicroDVD (`{start}{end}Line one|Line two`, in frames). A first cue of `{1}{1}25`
+/// declares the frame rate.
+pub fn parse_microdvd(text: &str) -> Vec<Cue> {
+    let mut fps = MICRODVD_DEFAULT_FPS;
+    let mut cues = Vec::new();
+    for (n, line) in text.lines().map(str::trim).enumerate() {
+        let mut frames = [0u64; 2];
+        let mut rest = line;
+        let mut ok = true;
+        for frame in &mut frames {
+            let Some(inner) = rest.strip_prefix('{') else {
+                ok = false;
+                break;
+            };
+            let Some((value, after)) = inner.split_once('}') else {
+                ok = false;
+                break;
+            };
+            // An empty end frame means "show for a while"
+            *frame = value.trim().parse().unwrap_or(0);
+            rest = after;
+        }
+        if !ok {
+            continue;
+        }
+        if n == 0 && frames[0] <= 1 {
+            if let Ok(declared) = rest.trim().parse::<f64>() {
+                fps = declared.max(1.0);
+                continue;
+            }
+        }
+        let to_ms = |frame: u64| (frame as f64 * 1000.0 / fps).round() as u64;
+        let start_ms = to_ms(frames[0]);
+        let end_ms = if frames[1] == 0 {
+            start_ms + 2000
+        } else {
+            to_ms(frames[1])
+        };
+        let text = rest.replace('|', "\n");
+        if !text.trim().is_empty() {
+            cues.push(Cue {
+                start_ms,
+                end_ms,
+                text,
+            });
+        }
+    }
+    cues
+}
+
+/// Strips styling WebVTT can't render: ASS override blocks left in SRT files (`{\an8}`),
+/// MicroDVD control codes (`{y:i}`) and HTML tags other than `<b>`, `<i>` and `<u>`.
+fn clean_text(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        if c == '{' {
+            if let Some(end) = rest.find('}') {
+                rest = &rest[end + 1..];
+                continue;
+            }
+        }
+        if c == '<' {
+            if let Some(end) = rest.find('>') {
+                let tag = rest[1..end].trim_start_matches('/').to_lowercase();
+                if matches!(tag.as_str(), "b" | "i" | "u") {
+                    out.push_str(&rest[..=end].to_lowercase());
+                }
+                rest = &rest[end + 1..];
+                continue;
+            }
+        }
+        out.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    out.trim().to_string()
+}
+
+pub fn to_vtt(cues: &[Cue]) -> String {
+    let mut out = String::from("WEBVTT\n\n");
+    for cue in cues {
+        out.push_str(&format!(
+            "{} --> {}\n{}\n\n",
+            format_time(cue.start_ms),
+            format_time(cue.end_ms),
+            // A blank line would end the cue early
+            clean_text(&cue.text).replace("\n\n", "\n")
+        ));
+    }
+    out
+}
+
+/// Converts subtitle `text` in the format given by `extension` to WebVTT.
+pub fn convert(text: &str, extension: &str) -> Result<String, String> {
+    let text = text.trim_start_matches('\u{feff}');
+    let cues = match extension.to_lowercase().as_str() {
+        "srt" | "vtt" => parse_srt(text),
+        "ass" | "ssa" => parse_ass(text),
+        "sub" if text.trim_start().starts_with('{') => parse_microdvd(text),
+        "sub" => return Err("Only text (MicroDVD) .sub files are supported".to_string()),
+        other => return Err(format!("Unsupported subtitle format: {}", other)),
+    };
+    if cues.is_empty() {
+        return Err("No subtitles found in file".to_string());
+    }
+    Ok(to_vtt(&cues))
+}
+
+fn guess_language(path: &Path) -> Option<String> {
+    let stem = path.file_stem()?.to_str()?;
+    let (_, tag) = stem.rsplit_once('.')?;
+    (2..=3)
+        .contains(&tag.len())
+        .then(|| tag.to_lowercase())
+        .filter(|t| t.chars().all(|c| c.is_ascii_alphabetic()))
+}
+
+/// Writes `vtt` into a static proxy session and returns its URL.
+pub fn serve_vtt(proxy: &ProxyState, vtt: &str) -> Result<String, String> {
+    let id = format!("subs-{}", uuid::Uuid::new_v4().simple());
+    let dir = proxy.session_dir(&id)?;
+    fs::write(dir.join("subtitles.vtt"), vtt)
+        .map_err(|e| format!("Failed to write subtitles: {}", e))?;
+    Ok(proxy.url(&id, "subtitles.vtt"))
+}
+
+/// Shows a file picker for subtitle files. Returns `None` when cancelled.
+#[tauri::command]
+pub async fn pick_subtitle_file(app: tauri::AppHandle) -> Result<Option<String>, String> {
+    let picked = tauri::async_runtime::spawn_blocking(move || {
+        app.dialog()
+            .file()
+            .add_filter("Subtitles", &EXTENSIONS)
+            .blocking_pick_file()
+    })
+    .await
+    .map_err(|e| e.to_string())?;
+    match picked {
+        Some(file) => {
+            let path = file.into_path().map_err(|e| e.to_string())?;
+            Ok(Some(path.to_string_lossy().into_owned()))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Converts the subtitle file at `file` to WebVTT and serves it. The charset is detected
+/// unless `encoding` names one.
+pub fn load_with_encoding(
+    proxy: &ProxyState,
+    file: &Path,
+    encoding: Option<&str>,
+) -> Result<SubtitleTrack, String> {
+    let extension = file
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default();
+    let bytes = fs::read(file).map_err(|e| format!("Failed to read {}: {}", file.display(), e))?;
+    let language = guess_language(file);
+    let (text, charset) = match encoding {
+        Some(name) => {
+            let charset = Charset::from_name(name)
+                .ok_or_else(|| format!("Unsupported encoding: {}", name))?;
+            (charset::decode_as(&bytes, charset), charset)
+        }
+        None => charset::decode(&bytes, language.as_deref()),
+    };
+    let vtt = convert(&text, extension)?;
+    let path = file.to_string_lossy().into_owned();
+    Ok(SubtitleTrack {
+        url: serve_vtt(proxy, &vtt)?,
+        label: file
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| path.clone()),
+        language,
+        encoding: charset.name().to_string(),
+        path,
+    })
+}
+
+/// Like `load_with_encoding`, detecting the charset.
+pub fn load(proxy: &ProxyState, file: &Path) -> Result<SubtitleTrack, String> {
+    load_with_encoding(proxy, file, None)
+}
+
+/// Tells video window `label` to add `track` (`subtitle-added`).
+pub fn attach(app: &tauri::AppHandle, label: Option<String>, track: &SubtitleTrack) {
+    if let Some(label) = label {
+        let _ = app.emit_to(label.as_str(), "subtitle-added", track.clone());
+    }
+}
+
+/// Converts a local subtitle file to WebVTT and serves it. With `label`, the video window is
+/// told to add the track. `encoding` overrides charset detection (e.g. `windows-1250`).
+#[tauri::command]
+pub fn load_subtitle_file(
+    app: tauri::AppHandle,
+    proxy: State<'_, ProxyState>,
+    path: String,
+    label: Option<String>,
+    encoding: Option<String>,
+) -> Result<SubtitleTrack, String> {
+    let track = load_with_encoding(&proxy, &PathBuf::from(path), encoding.as_deref())?;
+    attach(&app, label, &track);
+    Ok(track)
+}
+
+pub fn is_text_codec(codec: &str) -> bool {
+    TEXT_CODECS.contains(&codec)
+}
+
+/// Extracts subtitle stream `stream_index` of `url` to WebVTT with ffmpeg, caching the result
+/// in the app cache dir. This reads the whole container, so it can take a while for remote VOD.
+pub async fn extract(
+    app: &tauri::AppHandle,
+    url: &str,
+    stream_index: u32,
+) -> Result<PathBuf, String> {
+    let dir = crate::cache::dir(app, CacheKind::Subtitles)?;
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create subtitle cache: {}", e))?;
+    let key = crc32fast::hash(url.as_bytes());
+    let out = dir.join(format!("embedded-{:08x}-{}.vtt", key, stream_index));
+    if out.exists() {
+        crate::cache::touch(&out);
+        return Ok(out);
+    }
+    let ffmpeg = crate::ffmpeg::ffmpeg_path()?;
+    let tmp = out.with_extension("part.vtt");
+    let url = url.to_string();
+    let target = tmp.clone();
+    let output = tauri::async_runtime::spawn_blocking(move || {
+        std::process::Command::new(ffmpeg)
+            .args(["-hide_banner", "-loglevel", "error", "-nostdin", "-y"])
+            .arg("-i")
+            .arg(&url)
+            .args([
+                "-map",
+                &format!("0:{}", stream_index),
+                "-c:s",
+                "webvtt",
+                "-f",
+                "webvtt",
+            ])
+            .arg(&target)
+            .output()
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| format!("Failed to run ffmpeg: {}", e))?;
+    if !output.status.success() {
+        let _ = fs::remove_file(&tmp);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("Subtitle extraction failed: {}", stderr.trim()));
+    }
+    fs::rename(&tmp, &out).map_err(|e| format!("Failed to save subtitles: {}", e))?;
+    Ok(out)
+}
+
+/// Extracts an embedded text subtitle track (index from `list_tracks`), serves it as WebVTT
+/// and, with `label`, adds it to that video window.
+#[tauri::command]
+pub async fn extract_subtitle(
+    app: tauri::AppHandle,
+    proxy: State<'_, ProxyState>,
+    url: String,
+    stream_index: u32,
+    label: Option<String>,
+) -> Result<SubtitleTrack, String> {
+    let probed = crate::probe::probe(url.clone()).await?;
+    let stream = probed
+        .streams
+        .iter()
+        .find(|s| s.index == stream_index && s.kind == "subtitle")
+        .ok_or_else(|| format!("Stream {} is not a subtitle track", stream_index))?;
+    if !is_text_codec(&stream.codec) {
+        return Err(format!(
+            "{} subtitles are images and can't be converted to text",
+            stream.codec
+        ));
+    }
+    let path = extract(&app, &url, stream_index).await?;
+    let mut track = load(&proxy, &path)?;
+    track.label = stream
+        .title
+        .clone()
+        .or_else(|| stream.language.clone())
+        .unwrap_or_else(|| format!("Track {}", stream_index));
+    track.language = stream.language.clone();
+    attach(&app, label, &track);
+    Ok(track)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn times_scale_any_fraction_to_milliseconds() {
+        assert_eq!(parse_time("00:01:02,345"), Some(62_345));
+        assert_eq!(parse_time("1:02.5"), Some(62_500));
+        assert_eq!(parse_time("0:00:01.50"), Some(1_500));
+        assert_eq!(parse_time("00:00:01.2345"), Some(1_234));
+        assert_eq!(parse_time("00:xx:01,000"), None);
+        assert_eq!(format_time(3_723_004), "01:02:03.004");
+    }
+
+    #[test]
+    fn srt_cues_skip_broken_blocks() {
+        let text = "1\r\n00:00:01,000 --> 00:00:02,500\r\nHello\r\nworld\r\n\r\n\
+            2\r\nnot a timing line\r\n\r\n\
+            3\r\n00:00:03,000 --> 00:00:04,000\r\n\r\n\
+            4\r\n00:00:05,000 --> 00:00:06,000 align:start\r\nBye\r\n";
+        assert_eq!(
+            parse_srt(text),
+            [
+                Cue {
+                    start_ms: 1_000,
+                    end_ms: 2_500,
+                    text: "Hello\nworld".to_string(),
+                },
+                Cue {
+                    start_ms: 5_000,
+                    end_ms: 6_000,
+                    text: "Bye".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn ass_dialogue_follows_the_format_line() {
+        let text = "[Script Info]\nTitle: Test\n\n[Events]\n\
+            Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n\
+            Dialogue: 0,0:00:05.00,0:00:06.00,Default,,0,0,0,,Second\n\
+            Dialogue: 0,0:00:01.50,0:00:03.00,Default,,0,0,0,,{\\i1}First{\\i0}, line\\Nbreak\n\
+            Comment: 0,0:00:07.00,0:00:08.00,Default,,0,0,0,,Ignored\n";
+        let cues = parse_ass(text);
+        assert_eq!(cues.len(), 2);
+        assert_eq!(cues[0].start_ms, 1_500);
+        assert_eq!(cues[0].text, "First, line\nbreak");
+        assert_eq!(cues[1].text, "Second");
+    }
+
+    #[test]
+    fn ass_events_need_a_format() {
+        assert!(
+            parse_ass("[Events]\nDialogue: 0,0:00:01.00,0:00:02.00,Default,,0,0,0,,Hi\n")
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn microdvd_reads_the_declared_frame_rate() {
+        let cues = parse_microdvd("{1}{1}25\n{25}{50}One|Two\n{75}{}Open end\nnoise\n");
+        assert_eq!(
+            cues,
+            [
+                Cue {
+                    start_ms: 1_000,
+                    end_ms: 2_000,
+                    text: "One\nTwo".to_string(),
+                },
+                Cue {
+                    start_ms: 3_000,
+                    end_ms: 5_000,
+                    text: "Open end".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn vtt_keeps_only_basic_styling() {
+        let cues = [Cue {
+            start_ms: 0,
+            end_ms: 1_000,
+            text: "{\\an8}<I>Hi</I> <font color=\"red\">there</font>\n\nagain".to_string(),
+        }];
+        assert_eq!(
+            to_vtt(&cues),
+            "WEBVTT\n\n00:00:00.000 --> 00:00:01.000\n<i>Hi</i> there\nagain\n\n"
+        );
+    }
+
+    #[test]
+    fn convert_only_reads_text_sub_files() {
+        assert_eq!(
+            convert("{1}{1}25\n{25}{50}Hi\n", "sub").unwrap(),
+            "WEBVTT\n\n00:00:01.000 --> 00:00:02.000\nHi\n\n"
+        );
+        assert!(convert("binary", "sub").is_err());
+    }
+
+    #[test]
+    fn convert_picks_the_parser_by_extension() {
+        let srt = "\u{feff}1\n00:00:01,000 --> 00:00:02,000\nHi\n";
+        assert!(convert(srt, "SRT")
+            .unwrap()
+            .contains("00:00:01.000 --> 00:00:02.000\nHi"));
+        assert!(convert("1\n\n", "srt").is_err());
+        assert!(convert(srt, "txt").is_err());
+    }
+
+    #[test]
+    fn languages_come_from_the_file_name() {
+        assert_eq!(
+            guess_language(Path::new("/m/Movie.EN.srt")),
+            Some("en".to_string())
+        );
+        assert_eq!(
+            guess_language(Path::new("Movie.pol.srt")),
+            Some("pol".to_string())
+        );
+        assert_eq!(guess_language(Path::new("Movie.2024.srt")), None);
+        assert_eq!(guess_language(Path::new("Movie.srt")), None);
+    }
+}
@@ -0,0 +1,599 @@
+//! Watch-together sessions between TvX instances. One instance hosts a session for one of its
+//! video windows; others join with the host's address and a six-digit code and attach their own
+//! window playing the same item. Play, pause and seek from any participant are relayed to
+//! everyone through the host, and the host broadcasts its position every couple of seconds so
+//! guests that drift too far seek back in line.
+//!
+//! The wire format is newline-delimited JSON over plain TCP on the LAN.
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpStream, ToSocketAddrs};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, Manager, State};
+
+use crate::httpd;
+use crate::remote::{self, PlayerCommand};
+
+/// Preferred listening port; an ephemeral one is used when it is taken.
+const SYNC_PORT: u16 = 8766;
+const HEARTBEAT: Duration = Duration::from_secs(2);
+/// Guests further than this from the host's position seek back in line.
+const DRIFT_TOLERANCE_SECS: f64 = 1.0;
+/// Local state reports are not relayed for this long after applying a remote command, so the
+/// player's own pause/seek notifications don't echo back to the sender.
+const ECHO_WINDOW: Duration = Duration::from_millis(1500);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SyncRole {
+    Host,
+    Guest,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum SyncMessage {
+    Hello {
+        code: String,
+        name: String,
+    },
+    Welcome {
+        host: String,
+        position: f64,
+        paused: bool,
+    },
+    Rejected {
+        reason: String,
+    },
+    Command {
+        from: String,
+        command: PlayerCommand,
+    },
+    State {
+        position: f64,
+        paused: bool,
+    },
+}
+
+struct Peer {
+    name: String,
+    stream: TcpStream,
+}
+
+impl Peer {
+    fn send(&mut self, message: &SyncMessage) -> bool {
+        send(&mut self.stream, message).is_ok()
+    }
+}
+
+/// Last position reported by the local player, extrapolated while playing.
+struct Playback {
+    position: f64,
+    paused: bool,
+    at: Instant,
+}
+
+impl Playback {
+    fn now(&self) -> f64 {
+        if self.paused {
+            self.position
+        } else {
+            self.position + self.at.elapsed().as_secs_f64()
+        }
+    }
+}
+
+struct Session {
+    id: String,
+    role: SyncRole,
+    code: String,
+    address: String,
+    port: u16,
+    /// Local video window taking part in the session.
+    label: String,
+    name: String,
+    /// Guests when hosting, the host when joined.
+    peers: Vec<Peer>,
+    playback: Playback,
+    muted_until: Option<Instant>,
+    _server: Option<httpd::Server>,
+}
+
+impl Session {
+    fn info(&self) -> SyncSessionInfo {
+        SyncSessionInfo {
+            role: self.role,
+            code: self.code.clone(),
+            address: self.address.clone(),
+            port: self.port,
+            label: self.label.clone(),
+            peers: self.peers.iter().map(|p| p.name.clone()).collect(),
+            invite: format!(
+                "tvx://sync?host={}&port={}&code={}",
+                self.address, self.port, self.code
+            ),
+        }
+    }
+
+    /// Sends to every peer except `except`, dropping peers whose connection failed.
+    fn broadcast(&mut self, message: &SyncMessage, except: Option<&str>) {
+        self.peers.retain_mut(|p| {
+            let keep = Some(p.name.as_str()) == except || p.send(message);
+            if !keep {
+                let _ = p.stream.shutdown(std::net::Shutdown::Both);
+            }
+            keep
+        });
+    }
+
+    fn echo_muted(&self) -> bool {
+        self.muted_until.is_some_and(|until| until > Instant::now())
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        for peer in &self.peers {
+            let _ = peer.stream.shutdown(std::net::Shutdown::Both);
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct SyncState {
+    session: Mutex<Option<Session>>,
+}
+
+impl SyncState {
+    fn with_session<R>(&self, id: &str, f: impl FnOnce(&mut Session) -> R) -> Option<R> {
+        let mut session = self.session.lock().unwrap_or_else(|e| e.into_inner());
+        session.as_mut().filter(|s| s.id == id).map(f)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncSessionInfo {
+    pub role: SyncRole,
+    pub code: String,
+    pub address: String,
+    pub port: u16,
+    pub label: String,
+    /// Display names of connected guests, or of the host when joined.
+    pub peers: Vec<String>,
+    /// Text to share or encode in a QR code: a `tvx://sync` URL with address, port and code.
+    pub invite: String,
+}
+
+fn send(stream: &mut TcpStream, message: &SyncMessage) -> std::io::Result<()> {
+    let mut line = serde_json::to_vec(message).map_err(std::io::Error::other)?;
+    line.push(b'\n');
+    stream.write_all(&line)
+}
+
+fn read_message(reader: &mut impl BufRead) -> Option<SyncMessage> {
+    let mut line = String::new();
+    loop {
+        line.clear();
+        match reader.read_line(&mut line) {
+            Ok(0) | Err(_) => return None,
+            Ok(_) => {
+                // Unknown messages are skipped so newer peers can add types
+                if let Ok(message) = serde_json::from_str(&line) {
+                    return Some(message);
+                }
+            }
+        }
+    }
+}
+
+fn emit_changed(app: &tauri::AppHandle, session: &Session) {
+    let _ = app.emit("sync-session-changed", session.info());
+}
+
+fn end(app: &tauri::AppHandle, id: &str, reason: &str) {
+    let state = app.state::<SyncState>();
+    let ended = {
+        let mut session = state.session.lock().unwrap_or_else(|e| e.into_inner());
+        if session.as_ref().is_some_and(|s| s.id == id) {
+            session.take()
+        } else {
+            None
+        }
+    };
+    if ended.is_some() {
+        tracing::info!("Watch-together session ended: {}", reason);
+        let _ = app.emit(
+            "sync-session-ended",
+            serde_json::json!({ "reason": reason }),
+        );
+    }
+}
+
+/// Applies a command from a peer to the local window without relaying the resulting state.
+fn apply_remote(app: &tauri::AppHandle, session: &mut Session, command: &PlayerCommand) {
+    session.muted_until = Some(Instant::now() + ECHO_WINDOW);
+    match command {
+        PlayerCommand::Play => session.playback.paused = false,
+        PlayerCommand::Pause => {
+            session.playback.position = session.playback.now();
+            session.playback.paused = true;
+        }
+        PlayerCommand::Seek { position } => session.playback.position = *position,
+        _ => {}
+    }
+    session.playback.at = Instant::now();
+    let _ = remote::send_player_command(app, &session.label, command);
+}
+
+/// Commands reproducing a play state on another player: a seek, then play or pause.
+fn commands_for(position: f64, paused: bool) -> [PlayerCommand; 2] {
+    [
+        PlayerCommand::Seek { position },
+        if paused {
+            PlayerCommand::Pause
+        } else {
+            PlayerCommand::Play
+        },
+    ]
+}
+
+/// Reads a guest's messages until it disconnects. Runs on the connection's own thread.
+fn serve_guest(app: &tauri::AppHandle, id: &str, stream: TcpStream) {
+    let _ = stream.set_read_timeout(Some(Duration::from_secs(10)));
+    let _ = stream.set_write_timeout(Some(Duration::from_secs(5)));
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+    let mut reader = BufReader::new(stream);
+    let Some(SyncMessage::Hello { code, name }) = read_message(&mut reader) else {
+        return;
+    };
+    let state = app.state::<SyncState>();
+    let name = state.with_session(id, |session| {
+        if code != session.code {
+            let _ = send(
+                &mut writer,
+                &SyncMessage::Rejected {
+                    reason: "Wrong session code".to_string(),
+                },
+            );
+            return None;
+        }
+        // Names identify guests when relaying, so keep them unique
+        let mut unique = name.trim().to_string();
+        if unique.is_empty() {
+            unique = "Guest".to_string();
+        }
+        let base = unique.clone();
+        let mut n = 2;
+        while unique == session.name || session.peers.iter().any(|p| p.name == unique) {
+            unique = format!("{} ({})", base, n);
+            n += 1;
+        }
+        let welcome = SyncMessage::Welcome {
+            host: session.name.clone(),
+            position: session.playback.now(),
+            paused: session.playback.paused,
+        };
+        send(&mut writer, &welcome).ok()?;
+        session.peers.push(Peer {
+            name: unique.clone(),
+            stream: writer,
+        });
+        emit_changed(app, session);
+        Some(unique)
+    });
+    let Some(Some(name)) = name else {
+        return;
+    };
+    // Guests only speak when they act, so silence says nothing; a dead guest shows up as a
+    // failed heartbeat write, which shuts the socket and ends this read
+    let _ = reader.get_ref().set_read_timeout(None);
+    while let Some(message) = read_message(&mut reader) {
+        let SyncMessage::Command { command, .. } = message else {
+            continue;
+        };
+        let live = state.with_session(id, |session| {
+            apply_remote(app, session, &command);
+            let relayed = SyncMessage::Command {
+                from: name.clone(),
+                command,
+            };
+            session.broadcast(&relayed, Some(&name));
+        });
+        if live.is_none() {
+            return;
+        }
+    }
+    state.with_session(id, |session| {
+        session.peers.retain(|p| p.name != name);
+        emit_changed(app, session);
+    });
+}
+
+/// Host side: broadcasts the local position so guests can correct drift.
+fn heartbeat(app: &tauri::AppHandle, id: &str) {
+    let state = app.state::<SyncState>();
+    loop {
+        std::thread::sleep(HEARTBEAT);
+        let live = state.with_session(id, |session| {
+            if session.peers.is_empty() {
+                return;
+            }
+            let before = session.peers.len();
+            let beat = SyncMessage::State {
+                position: session.playback.now(),
+                paused: session.playback.paused,
+            };
+            session.broadcast(&beat, None);
+            if session.peers.len() != before {
+                emit_changed(app, session);
+            }
+        });
+        if live.is_none() {
+            return;
+        }
+    }
+}
+
+/// Guest side: follows the host's commands and heartbeats until either side leaves.
+fn follow_host(app: &tauri::AppHandle, id: &str, mut reader: BufReader<TcpStream>) {
+    let state = app.state::<SyncState>();
+    while let Some(message) = read_message(&mut reader) {
+        let live = state.with_session(id, |session| match message {
+            SyncMessage::Command { command, .. } => apply_remote(app, session, &command),
+            SyncMessage::State { position, paused } => {
+                let drift = session.playback.now() - position;
+                if paused != session.playback.paused || drift.abs() > DRIFT_TOLERANCE_SECS {
+                    for command in commands_for(position, paused) {
+                        apply_remote(app, session, &command);
+                    }
+                    let _ = app.emit_to(
+                        session.label.as_str(),
+                        "sync-drift-corrected",
+                        serde_json::json!({ "driftSecs": drift }),
+                    );
+                }
+            }
+            _ => {}
+        });
+        if live.is_none() {
+            return;
+        }
+    }
+    end(app, id, "The host ended the session");
+}
+
+fn ensure_video_window(app: &tauri::AppHandle, label: &str) -> Result<(), String> {
+    if !label.starts_with("video-") || app.get_webview_window(label).is_none() {
+        return Err(format!("No video window {}", label));
+    }
+    Ok(())
+}
+
+fn device_name() -> String {
+    std::env::var("COMPUTERNAME")
+        .or_else(|_| std::env::var("HOSTNAME"))
+        .unwrap_or_else(|_| "TvX".to_string())
+}
+
+/// Ends the session when its window closes.
+pub fn release(app: &tauri::AppHandle, label: &str) {
+    let Some(state) = app.try_state::<SyncState>() else {
+        return;
+    };
+    let id = state
+        .session
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_ref()
+        .filter(|s| s.label == label)
+        .map(|s| s.id.clone());
+    if let Some(id) = id {
+        end(app, &id, "The video window was closed");
+    }
+}
+
+/// Hosts a session for video window `label`, replacing any current session.
+#[tauri::command]
+pub fn start_sync_session(
+    app: tauri::AppHandle,
+    state: State<'_, SyncState>,
+    label: String,
+) -> Result<SyncSessionInfo, String> {
+    ensure_video_window(&app, &label)?;
+    let mut current = state.session.lock().unwrap_or_else(|e| e.into_inner());
+    // Drop the old session first so its port is free again
+    *current = None;
+    let id = uuid::Uuid::new_v4().to_string();
+    let handler_app = app.clone();
+    let handler_id = id.clone();
+    let handler = move |stream: TcpStream| serve_guest(&handler_app, &handler_id, stream);
+    let server = httpd::serve(
+        SocketAddr::from((Ipv4Addr::UNSPECIFIED, SYNC_PORT)),
+        handler.clone(),
+    )
+    .or_else(|_| httpd::serve(SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)), handler))?;
+    let session = Session {
+        id: id.clone(),
+        role: SyncRole::Host,
+        // Six digits so it can also be typed in by hand
+        code: format!("{:06}", uuid::Uuid::new_v4().as_u128() % 1_000_000),
+        address: crate::pairing::lan_address().to_string(),
+        port: server.port(),
+        label,
+        name: device_name(),
+        peers: Vec::new(),
+        playback: Playback {
+            position: 0.0,
+            paused: true,
+            at: Instant::now(),
+        },
+        muted_until: None,
+        _server: Some(server),
+    };
+    tracing::info!(port = session.port, "Hosting watch-together session");
+    let info = session.info();
+    *current = Some(session);
+    drop(current);
+    let beat_app = app.clone();
+    std::thread::spawn(move || heartbeat(&beat_app, &id));
+    Ok(info)
+}
+
+/// Joins a session hosted at `host:port`, attaching local video window `label`, which then
+/// jumps to the host's position.
+#[tauri::command]
+pub async fn join_sync_session(
+    app: tauri::AppHandle,
+    host: String,
+    port: u16,
+    code: String,
+    label: String,
+    name: Option<String>,
+) -> Result<SyncSessionInfo, String> {
+    ensure_video_window(&app, &label)?;
+    let name = name
+        .filter(|n| !n.trim().is_empty())
+        .unwrap_or_else(device_name);
+    let address = host.clone();
+    let code = code.trim().to_string();
+    let hello = SyncMessage::Hello {
+        code: code.clone(),
+        name: name.clone(),
+    };
+    let (stream, welcome) = tauri::async_runtime::spawn_blocking(move || {
+        let addr = (host.as_str(), port)
+            .to_socket_addrs()
+            .ok()
+            .and_then(|mut addrs| addrs.next())
+            .ok_or_else(|| format!("Unknown host {}", host))?;
+        let mut stream = TcpStream::connect_timeout(&addr, Duration::from_secs(10))
+            .map_err(|e| format!("Failed to reach {}:{}: {}", host, port, e))?;
+        let _ = stream.set_read_timeout(Some(Duration::from_secs(10)));
+        let _ = stream.set_write_timeout(Some(Duration::from_secs(5)));
+        send(&mut stream, &hello).map_err(|e| format!("Failed to join session: {}", e))?;
+        let mut reader = BufReader::new(stream.try_clone().map_err(|e| e.to_string())?);
+        let welcome = read_message(&mut reader);
+        Ok::<_, String>((stream, welcome.map(|w| (w, reader))))
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+    let (host_name, position, paused, reader) = match welcome {
+        Some((
+            SyncMessage::Welcome {
+                host,
+                position,
+                paused,
+            },
+            reader,
+        )) => (host, position, paused, reader),
+        Some((SyncMessage::Rejected { reason }, _)) => return Err(reason),
+        _ => return Err("The host did not answer".to_string()),
+    };
+    let _ = reader
+        .get_ref()
+        .set_read_timeout(Some(Duration::from_secs(60)));
+
+    let id = uuid::Uuid::new_v4().to_string();
+    let state = app.state::<SyncState>();
+    let info = {
+        let mut current = state.session.lock().unwrap_or_else(|e| e.into_inner());
+        let mut session = Session {
+            id: id.clone(),
+            role: SyncRole::Guest,
+            code,
+            address,
+            port,
+            label,
+            name,
+            peers: vec![Peer {
+                name: host_name,
+                stream,
+            }],
+            playback: Playback {
+                position,
+                paused,
+                at: Instant::now(),
+            },
+            muted_until: None,
+            _server: None,
+        };
+        for command in commands_for(position, paused) {
+            apply_remote(&app, &mut session, &command);
+        }
+        let info = session.info();
+        *current = Some(session);
+        info
+    };
+    tracing::info!(
+        "Joined watch-together session at {}:{}",
+        info.address,
+        info.port
+    );
+    let follow_app = app.clone();
+    std::thread::spawn(move || follow_host(&follow_app, &id, reader));
+    Ok(info)
+}
+
+#[tauri::command]
+pub fn leave_sync_session(app: tauri::AppHandle, state: State<'_, SyncState>) {
+    let id = state
+        .session
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_ref()
+        .map(|s| s.id.clone());
+    if let Some(id) = id {
+        end(&app, &id, "You left the session");
+    }
+}
+
+#[tauri::command]
+pub fn get_sync_session(state: State<'_, SyncState>) -> Option<SyncSessionInfo> {
+    state
+        .session
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_ref()
+        .map(Session::info)
+}
+
+/// Called by the video window on play, pause and seek, and periodically while playing. Changes
+/// made by the local viewer are relayed to the other participants.
+#[tauri::command]
+pub fn sync_report_state(
+    state: State<'_, SyncState>,
+    label: String,
+    position_secs: f64,
+    paused: bool,
+    seeked: bool,
+) {
+    let mut session = state.session.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(session) = session.as_mut().filter(|s| s.label == label) else {
+        return;
+    };
+    let changed = seeked || paused != session.playback.paused;
+    session.playback = Playback {
+        position: position_secs.max(0.0),
+        paused,
+        at: Instant::now(),
+    };
+    if !changed || session.echo_muted() {
+        return;
+    }
+    let from = session.name.clone();
+    for command in commands_for(position_secs, paused) {
+        session.broadcast(
+            &SyncMessage::Command {
+                from: from.clone(),
+                command,
+            },
+            None,
+        );
+    }
+}
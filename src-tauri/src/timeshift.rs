@@ -0,0 +1,92 @@
+//! Pausing live TV. With timeshift on, live streams always go through an ffmpeg HLS session
+//! (remuxed when they need no transcoding) whose playlist keeps the last few minutes on disk
+//! instead of the usual handful of segments; older segments are deleted as new ones arrive, so
+//! the buffer stays bounded. The player pauses and rewinds within the playlist and seeks to its
+//! end to return to live.
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::proxy::ProxyState;
+
+const MAX_BUFFER_MINUTES: u32 = 240;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TimeshiftSettings {
+    pub enabled: bool,
+    /// How far back a live stream can be rewound.
+    pub buffer_minutes: u32,
+}
+
+impl Default for TimeshiftSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            buffer_minutes: 30,
+        }
+    }
+}
+
+impl TimeshiftSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.buffer_minutes == 0 || self.buffer_minutes > MAX_BUFFER_MINUTES {
+            return Err(format!(
+                "The timeshift buffer must be between 1 and {} minutes",
+                MAX_BUFFER_MINUTES
+            ));
+        }
+        Ok(())
+    }
+
+    /// Buffer length for a stream, when timeshift applies to it.
+    pub fn buffer_secs(&self) -> Option<u64> {
+        self.enabled.then_some(self.buffer_minutes as u64 * 60)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimeshiftStatus {
+    /// Seconds the buffer can hold.
+    pub capacity_secs: u64,
+    /// Seconds it holds now; grows to `capacity_secs` after the stream has run that long.
+    pub buffered_secs: f64,
+    /// Disk space the buffered segments take.
+    pub bytes: u64,
+}
+
+/// Reads how much the session in `dir` has buffered from its playlist.
+fn status(dir: &Path, capacity_secs: u64) -> TimeshiftStatus {
+    let playlist = std::fs::read_to_string(dir.join("index.m3u8")).unwrap_or_default();
+    let mut buffered_secs = 0.0;
+    let mut bytes = 0;
+    for line in playlist.lines() {
+        if let Some(duration) = line.strip_prefix("#EXTINF:") {
+            buffered_secs += duration
+                .split(',')
+                .next()
+                .and_then(|d| d.trim().parse::<f64>().ok())
+                .unwrap_or(0.0);
+        } else if !line.is_empty() && !line.starts_with('#') {
+            bytes += std::fs::metadata(dir.join(line)).map_or(0, |m| m.len());
+        }
+    }
+    TimeshiftStatus {
+        capacity_secs,
+        buffered_secs,
+        bytes,
+    }
+}
+
+/// The timeshift buffer of the video window, or `None` when it plays without one.
+#[tauri::command]
+pub fn get_timeshift_status(
+    proxy: State<'_, ProxyState>,
+    window_label: String,
+) -> Option<TimeshiftStatus> {
+    let (dir, capacity_secs) = proxy.timeshift_owned_by(&window_label)?;
+    Some(status(&dir, capacity_secs))
+}
@@ -0,0 +1,274 @@
+//! Audio and subtitle track listing and default selection from per-profile language
+//! preferences.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::probe::{self, ProbeResult, ProbeStream};
+use crate::settings::{self, SettingsStore};
+use crate::store::JsonStore;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SubtitleMode {
+    Off,
+    /// Only forced subtitles (foreign-language dialogue), e.g. for the chosen audio language.
+    #[default]
+    Forced,
+    Always,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct TrackPreferences {
+    /// Language codes in order of preference (`en`, `eng` and `English` all work).
+    #[serde(default)]
+    pub audio_languages: Vec<String>,
+    #[serde(default)]
+    pub subtitle_languages: Vec<String>,
+    #[serde(default)]
+    pub subtitle_mode: SubtitleMode,
+}
+
+/// Stream indexes to select by default; `None` means the player's own default (audio) or
+/// no subtitles.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrackSelection {
+    pub audio: Option<u32>,
+    pub subtitle: Option<u32>,
+    pub audio_language: Option<String>,
+    pub subtitle_language: Option<String>,
+}
+
+/// Choices the user made while watching one item, reapplied on its next playback.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ItemTrackChoice {
+    /// Positive values show subtitles later.
+    #[serde(default)]
+    pub subtitle_delay_ms: i64,
+    #[serde(default)]
+    pub audio_index: Option<u32>,
+    #[serde(default)]
+    pub audio_language: Option<String>,
+}
+
+/// Per-item choices keyed by content key (same keys as playback progress).
+pub type ItemTrackStore = JsonStore<HashMap<String, ItemTrackChoice>>;
+
+pub fn open_item_store(app: &tauri::AppHandle) -> ItemTrackStore {
+    JsonStore::open(app, "item_tracks.json")
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamTracks {
+    pub audio: Vec<ProbeStream>,
+    pub subtitles: Vec<ProbeStream>,
+    pub selection: TrackSelection,
+}
+
+/// Normalizes a language tag to ISO 639-2/T for the languages streams commonly carry.
+fn normalize(lang: &str) -> String {
+    let lang = lang.trim().to_lowercase();
+    let primary = lang.split(['-', '_']).next().unwrap_or_default();
+    let code = match primary {
+        "en" | "eng" | "english" => "eng",
+        "de" | "ger" | "deu" | "german" | "deutsch" => "deu",
+        "fr" | "fre" | "fra" | "french" | "français" => "fra",
+        "es" | "spa" | "spanish" | "español" => "spa",
+        "it" | "ita" | "italian" => "ita",
+        "pt" | "por" | "portuguese" => "por",
+        "nl" | "dut" | "nld" | "dutch" => "nld",
+        "sv" | "swe" | "swedish" => "swe",
+        "no" | "nor" | "nob" | "norwegian" => "nor",
+        "da" | "dan" | "danish" => "dan",
+        "fi" | "fin" | "finnish" => "fin",
+        "pl" | "pol" | "polish" => "pol",
+        "cs" | "cze" | "ces" | "czech" => "ces",
+        "el" | "gre" | "ell" | "greek" => "ell",
+        "tr" | "tur" | "turkish" => "tur",
+        "ru" | "rus" | "russian" => "rus",
+        "ar" | "ara" | "arabic" => "ara",
+        "hi" | "hin" | "hindi" => "hin",
+        "zh" | "chi" | "zho" | "chinese" => "zho",
+        "ja" | "jpn" | "japanese" => "jpn",
+        "ko" | "kor" | "korean" => "kor",
+        "ro" | "rum" | "ron" | "romanian" => "ron",
+        "fa" | "per" | "fas" | "persian" => "fas",
+        other => other,
+    };
+    code.to_string()
+}
+
+fn same_language(a: &str, b: &str) -> bool {
+    normalize(a) == normalize(b)
+}
+
+fn in_language<'a>(streams: &[&'a ProbeStream], lang: &str) -> Option<&'a ProbeStream> {
+    streams.iter().copied().find(|s| {
+        s.language
+            .as_deref()
+            .is_some_and(|l| same_language(l, lang))
+    })
+}
+
+pub fn select(probe: &ProbeResult, prefs: &TrackPreferences) -> TrackSelection {
+    let audio: Vec<&ProbeStream> = probe.streams.iter().filter(|s| s.kind == "audio").collect();
+    let subs: Vec<&ProbeStream> = probe
+        .streams
+        .iter()
+        .filter(|s| s.kind == "subtitle")
+        .collect();
+
+    let chosen_audio = prefs
+        .audio_languages
+        .iter()
+        .find_map(|lang| in_language(&audio, lang))
+        .or_else(|| audio.iter().copied().find(|s| s.default))
+        .or_else(|| audio.first().copied());
+
+    let chosen_sub = match prefs.subtitle_mode {
+        SubtitleMode::Off => None,
+        SubtitleMode::Forced => {
+            let forced: Vec<&ProbeStream> = subs.iter().copied().filter(|s| s.forced).collect();
+            chosen_audio
+                .and_then(|a| a.language.as_deref())
+                .and_then(|lang| in_language(&forced, lang))
+                .or_else(|| {
+                    prefs
+                        .subtitle_languages
+                        .iter()
+                        .find_map(|lang| in_language(&forced, lang))
+                })
+        }
+        SubtitleMode::Always => prefs
+            .subtitle_languages
+            .iter()
+            .find_map(|lang| in_language(&subs, lang))
+            .or_else(|| subs.iter().copied().find(|s| s.default)),
+    };
+
+    TrackSelection {
+        audio: chosen_audio.map(|s| s.index),
+        subtitle: chosen_sub.map(|s| s.index),
+        audio_language: chosen_audio.and_then(|s| s.language.clone()),
+        subtitle_language: chosen_sub.and_then(|s| s.language.clone()),
+    }
+}
+
+/// Overrides the preference-based audio choice with the one saved for this item, matching
+/// by stream index first (same file) and language second (e.g. a re-encoded copy).
+pub fn apply_item_choice(
+    probe: &ProbeResult,
+    choice: &ItemTrackChoice,
+    selection: &mut TrackSelection,
+) {
+    let audio = |s: &&ProbeStream| s.kind == "audio";
+    let saved = choice
+        .audio_index
+        .and_then(|i| probe.streams.iter().filter(audio).find(|s| s.index == i))
+        .or_else(|| {
+            let lang = choice.audio_language.as_deref()?;
+            probe.streams.iter().filter(audio).find(|s| {
+                s.language
+                    .as_deref()
+                    .is_some_and(|l| same_language(l, lang))
+            })
+        });
+    if let Some(stream) = saved {
+        selection.audio = Some(stream.index);
+        selection.audio_language = stream.language.clone();
+    }
+}
+
+pub fn preferences(settings: &SettingsStore, profile_id: &str) -> TrackPreferences {
+    settings.read(|s| {
+        s.track_preferences
+            .get(profile_id)
+            .cloned()
+            .unwrap_or_default()
+    })
+}
+
+#[tauri::command]
+pub fn get_track_preferences(
+    settings: State<'_, SettingsStore>,
+    profile_id: Option<String>,
+) -> TrackPreferences {
+    preferences(&settings, &settings::profile_id(profile_id))
+}
+
+#[tauri::command]
+pub fn set_track_preferences(
+    settings: State<'_, SettingsStore>,
+    profile_id: Option<String>,
+    preferences: TrackPreferences,
+) -> Result<(), String> {
+    let profile_id = settings::profile_id(profile_id);
+    settings.update(|s| {
+        s.track_preferences.insert(profile_id, preferences);
+    })
+}
+
+/// Probes `url` and returns its audio/subtitle tracks with the profile's default selection.
+#[tauri::command]
+pub async fn list_tracks(
+    settings: State<'_, SettingsStore>,
+    url: String,
+    profile_id: Option<String>,
+) -> Result<StreamTracks, String> {
+    let prefs = preferences(&settings, &settings::profile_id(profile_id));
+    let probed = probe::probe(url).await?;
+    let selection = select(&probed, &prefs);
+    let of_kind = |kind: &str| -> Vec<ProbeStream> {
+        probed
+            .streams
+            .iter()
+            .filter(|s| s.kind == kind)
+            .cloned()
+            .collect()
+    };
+    Ok(StreamTracks {
+        audio: of_kind("audio"),
+        subtitles: of_kind("subtitle"),
+        selection,
+    })
+}
+
+#[tauri::command]
+pub fn get_item_tracks(
+    store: State<'_, ItemTrackStore>,
+    content_key: String,
+) -> Option<ItemTrackChoice> {
+    store.read(|items| items.get(&content_key).cloned())
+}
+
+#[tauri::command]
+pub fn set_item_subtitle_delay(
+    store: State<'_, ItemTrackStore>,
+    content_key: String,
+    delay_ms: i64,
+) -> Result<(), String> {
+    store.update(|items| {
+        items.entry(content_key).or_default().subtitle_delay_ms = delay_ms;
+    })
+}
+
+/// Remembers a non-default audio track. Players that only know languages (hls.js) pass no index.
+#[tauri::command]
+pub fn set_item_audio_track(
+    store: State<'_, ItemTrackStore>,
+    content_key: String,
+    index: Option<u32>,
+    language: Option<String>,
+) -> Result<(), String> {
+    store.update(|items| {
+        let item = items.entry(content_key).or_default();
+        item.audio_index = index;
+        item.audio_language = language;
+    })
+}
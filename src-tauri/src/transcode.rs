@@ -0,0 +1,572 @@
+//! Fallback for streams the webview can't play: probe the stream and, when the container or a
+//! codec is unsupported, remux/transcode it to HLS through the local proxy. Transcoder
+//! sessions are owned by their video window and stop when it closes. MPEG-DASH manifests always
+//! take the remux path, from their tallest video representation, as do RTSP and UDP
+//! multicast sources (see `ingest`).
+
+use std::time::Duration;
+
+use tauri::{Emitter, Manager, State};
+
+use crate::connections::{self, Reservation};
+use crate::hwaccel::{self, HwCaps};
+use crate::probe::{self, ProbeResult};
+use crate::proxy::{ProxySession, ProxyState};
+use crate::servers::ServerConfig;
+use crate::settings::{self, SettingsStore};
+use crate::subtitles;
+use crate::tracks::{self, ItemTrackChoice, ItemTrackStore, TrackSelection};
+
+/// How often a VOD session is checked for having finished its subtitle track.
+const SUBTITLE_POLL: Duration = Duration::from_secs(5);
+
+/// Containers the webview plays natively (HLS via hls.js). DASH isn't one: there is no DASH
+/// player in the page, so DASH streams are always remuxed to HLS.
+fn container_playable(format: &str) -> bool {
+    format
+        .split(',')
+        .any(|f| matches!(f, "hls" | "mp4" | "mov" | "webm" | "matroska"))
+        && !format.contains("mpegts")
+}
+
+fn video_playable(codec: &str) -> bool {
+    match codec {
+        "h264" | "vp8" | "vp9" | "av1" => true,
+        // Only WebKit on macOS decodes HEVC reliably
+        "hevc" => cfg!(target_os = "macos"),
+        _ => false,
+    }
+}
+
+fn audio_playable(codec: &str) -> bool {
+    matches!(codec, "aac" | "mp3" | "opus" | "vorbis" | "flac")
+}
+
+/// ffmpeg output arguments for a webview-playable HLS rendition of `probe` using the selected
+/// audio track, or `None` when the stream plays directly. With `normalize`, transcoded
+/// streams also get their loudness evened out.
+pub(crate) fn plan(
+    probe: &ProbeResult,
+    hw: &HwCaps,
+    selection: &TrackSelection,
+    normalize: bool,
+) -> Option<Vec<String>> {
+    let video = probe.video();
+    let audio = selection
+        .audio
+        .and_then(|index| probe.streams.iter().find(|s| s.index == index))
+        .or_else(|| probe.first("audio"));
+    let video_ok = video.is_none_or(|v| video_playable(&v.codec) && !v.interlaced);
+    let audio_ok = audio.is_none_or(|a| audio_playable(&a.codec));
+    if container_playable(&probe.format) && video_ok && audio_ok {
+        return None;
+    }
+    let mut args: Vec<String> = Vec::new();
+    if let Some(v) = video {
+        args.extend(["-map".into(), format!("0:{}", v.index)]);
+        if video_ok {
+            args.extend(["-c:v".into(), "copy".into()]);
+        } else {
+            args.extend(hwaccel::h264_args(hw));
+            if v.interlaced {
+                args.extend(["-vf".into(), "yadif".into()]);
+            }
+        }
+    }
+    if let Some(a) = audio {
+        args.extend(["-map".into(), format!("0:{}", a.index)]);
+        if audio_ok && !normalize {
+            args.extend(["-c:a".into(), "copy".into()]);
+        } else {
+            if normalize {
+                // Single-pass EBU R128 to a level typical of streaming services
+                args.extend(["-af", "loudnorm=I=-16:TP=-1.5:LRA=11"].map(String::from));
+            }
+            args.extend(["-c:a", "aac", "-b:a", "192k", "-ac", "2"].map(String::from));
+        }
+    }
+    Some(args)
+}
+
+/// ffmpeg output arguments copying the video and selected audio track of `probe` unchanged,
+/// for streams that play directly but go through HLS anyway (timeshift).
+fn remux(probe: &ProbeResult, selection: &TrackSelection) -> Vec<String> {
+    let audio = selection
+        .audio
+        .and_then(|index| probe.streams.iter().find(|s| s.index == index))
+        .or_else(|| probe.first("audio"));
+    let mut args: Vec<String> = Vec::new();
+    for stream in [probe.video(), audio].into_iter().flatten() {
+        args.extend(["-map".into(), format!("0:{}", stream.index)]);
+    }
+    args.extend(["-c".into(), "copy".into()]);
+    args
+}
+
+/// Whether `url` points at an MPEG-DASH manifest, going by its path.
+fn is_dash_url(url: &str) -> bool {
+    reqwest::Url::parse(url).map_or_else(
+        |_| url.to_ascii_lowercase().ends_with(".mpd"),
+        |u| u.path().to_ascii_lowercase().ends_with(".mpd"),
+    )
+}
+
+/// ffmpeg input arguments for `url`, reconnecting HTTP sources that drop and tuning RTSP and
+/// multicast ones (see `ingest`).
+pub(crate) fn input_args(url: &str) -> Vec<String> {
+    if crate::ingest::is_ingest(url) {
+        let mut args = crate::ingest::input_options(url);
+        args.extend(["-i".to_string(), crate::ingest::input_url(url)]);
+        return args;
+    }
+    let mut args = Vec::new();
+    if url.starts_with("http://") || url.starts_with("https://") {
+        args.extend(
+            [
+                "-reconnect",
+                "1",
+                "-reconnect_streamed",
+                "1",
+                "-reconnect_delay_max",
+                "5",
+            ]
+            .map(String::from),
+        );
+    }
+    args.extend(["-i".to_string(), url.to_string()]);
+    args
+}
+
+/// Query parameters telling the video window which tracks to select (hls.js switches tracks
+/// itself), the subtitle delay, and the content key to save changes under.
+fn window_params(
+    settings: &SettingsStore,
+    selection: &TrackSelection,
+    content_key: Option<&str>,
+    choice: Option<&ItemTrackChoice>,
+) -> Vec<(&'static str, String)> {
+    let mut params = Vec::new();
+    if crate::datausage::reduce_quality(settings) {
+        params.push(("maxHeight", crate::datausage::SAVER_MAX_HEIGHT.to_string()));
+    }
+    if let Some(lang) = &selection.audio_language {
+        params.push(("audioLang", lang.clone()));
+    }
+    if let Some(lang) = &selection.subtitle_language {
+        params.push(("subLang", lang.clone()));
+    }
+    if let Some(key) = content_key {
+        params.push(("contentKey", key.to_string()));
+    }
+    if let Some(delay) = choice.map(|c| c.subtitle_delay_ms).filter(|d| *d != 0) {
+        params.push(("subDelay", delay.to_string()));
+    }
+    params
+}
+
+/// Opens `stream_url` in a video window, routing it through an ffmpeg transcoder when the
+/// webview can't play it. Default tracks follow the profile's language preferences, or the
+/// choices saved for `content_key`. Streams are relayed through the local proxy when
+/// `server`'s (or the global) proxy or resolver applies, since neither the webview nor ffmpeg
+/// can use them. With `server` the stream counts against its connection limit (see
+/// `connections`). Radio stations (`audio_only`, or streams without video) get the compact
+/// audio-only window; `audio_only` HTTP streams are always relayed, to read the song titles
+/// the station sends (see `icy`). Playlist channels with ClearKey keys are decrypted by ffmpeg
+/// (see `drm`), and multicast ones go through the server's udpxy gateway when it has one. HLS
+/// master playlists play `variant_url` when given (see `get_stream_variants`), else the
+/// variant the preferred quality setting picks. Returns the window label.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn play_stream(
+    app: tauri::AppHandle,
+    proxy: State<'_, ProxyState>,
+    settings: State<'_, SettingsStore>,
+    item_tracks: State<'_, ItemTrackStore>,
+    title: String,
+    stream_url: String,
+    profile_id: Option<String>,
+    content_key: Option<String>,
+    server: Option<ServerConfig>,
+    audio_only: Option<bool>,
+    variant_url: Option<String>,
+) -> Result<String, String> {
+    let audio_only = audio_only.unwrap_or(false);
+    let source = prepare(
+        &app,
+        &proxy,
+        &settings,
+        server.as_ref(),
+        &title,
+        stream_url,
+        variant_url,
+        audio_only,
+        None,
+    )
+    .await?;
+    let opened = open_player(
+        &app,
+        &proxy,
+        &settings,
+        &item_tracks,
+        title,
+        &source,
+        None,
+        profile_id,
+        content_key,
+        audio_only,
+        None,
+    )
+    .await;
+    source.finish(&proxy, opened)
+}
+
+/// A stream made ready for a player by `prepare`.
+pub(crate) struct Source {
+    /// What the player reads: the relay's URL, else the resolved link.
+    pub url: String,
+    /// ffmpeg input options decrypting the stream (see `drm`).
+    pub decryption: Option<Vec<String>>,
+    relay: Option<ProxySession>,
+    reservation: Option<Reservation>,
+}
+
+impl Source {
+    /// Hands the relay and connection to the window (or VLC) that `opened` the stream, or
+    /// frees them when opening failed.
+    pub fn finish(
+        self,
+        proxy: &ProxyState,
+        opened: Result<String, String>,
+    ) -> Result<String, String> {
+        match (&opened, &self.relay) {
+            (Ok(label), Some(relay)) => proxy.set_owner(&relay.id, label),
+            (Err(_), Some(relay)) => proxy.stop(&relay.id),
+            _ => {}
+        }
+        if let (Ok(label), Some(reservation)) = (&opened, self.reservation) {
+            reservation.keep(label);
+        }
+        opened
+    }
+}
+
+/// Gets `stream_url` ready to play the way `play_stream` describes: reserves a connection of
+/// `server` (not counting the stream of `replacing`, the window switching to it), finds
+/// decryption keys, goes through udpxy, resolves the link and HLS variant, and relays it.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn prepare(
+    app: &tauri::AppHandle,
+    proxy: &ProxyState,
+    settings: &SettingsStore,
+    server: Option<&ServerConfig>,
+    title: &str,
+    stream_url: String,
+    variant_url: Option<String>,
+    audio_only: bool,
+    replacing: Option<&str>,
+) -> Result<Source, String> {
+    let decryption = crate::drm::keys_for(app, server, &stream_url)
+        .await?
+        .map(|keys| crate::drm::input_args(&keys, &stream_url))
+        .transpose()?;
+    let reservation = match server {
+        Some(server) => Some(connections::reserve(app, server, title, false, replacing).await?),
+        None => None,
+    };
+    let stream_url = crate::ingest::via_udpxy(server, &stream_url).unwrap_or(stream_url);
+    let mut resolved = crate::resolve::resolve(server, &stream_url).await;
+    if let Some(variant_url) = variant_url {
+        resolved.url = variant_url;
+    } else if let Some(variant_url) =
+        crate::hls::preferred_variant(settings, &resolved.url, server).await
+    {
+        resolved.url = variant_url;
+    }
+    let relay = if !resolved.url.starts_with("http") {
+        None
+    } else if audio_only {
+        Some(proxy.relay_radio(&resolved, server)?)
+    } else if resolved.needs_relay(server) {
+        Some(proxy.relay(&resolved, server)?)
+    } else {
+        None
+    };
+    Ok(Source {
+        url: relay.as_ref().map_or(resolved.url, |r| r.url.clone()),
+        decryption,
+        relay,
+        reservation,
+    })
+}
+
+/// How a probed stream reaches the webview, from `serve`.
+struct Served {
+    /// The ffmpeg session serving it, unless the webview plays the stream as is.
+    session: Option<ProxySession>,
+    /// Subtitle stream the session also converts to WebVTT.
+    subtitle: Option<u32>,
+    timeshift_secs: Option<u64>,
+    decision: crate::playback::PlaybackDecision,
+}
+
+/// Starts the ffmpeg session the webview needs to play `stream_url` with `selection`, if it
+/// needs one: a transcode or remux, or a timeshift buffer for live streams when that's on.
+/// VOD sessions also convert the selected text subtitle track.
+async fn serve(
+    app: &tauri::AppHandle,
+    proxy: &ProxyState,
+    settings: &SettingsStore,
+    probed: &ProbeResult,
+    selection: &TrackSelection,
+    stream_url: &str,
+    decryption: Option<Vec<String>>,
+) -> Result<Served, String> {
+    let hw = hwaccel::caps().await.unwrap_or_default();
+    if let Some(message) = hwaccel::playback_warning(probed, &hw) {
+        let _ = app.emit("playback-warning", message);
+    }
+    let normalize = settings.read(|s| s.normalize_audio);
+    // Live streams only: recordings and VOD can be paused and rewound anyway
+    let timeshift = settings
+        .read(|s| s.timeshift.buffer_secs())
+        .filter(|_| probed.duration_secs.is_none());
+    let mut output = plan(probed, &hw, selection, normalize);
+    // Encrypted streams only play once ffmpeg has decrypted them, RTSP and multicast once it
+    // serves them over HTTP
+    if decryption.is_some() || crate::ingest::is_ingest(stream_url) {
+        output = output.or_else(|| Some(remux(probed, selection)));
+    }
+    let mut input = decryption.unwrap_or_default();
+    input.extend(input_args(stream_url));
+    let path = match (&output, timeshift) {
+        (None, Some(_)) => crate::playback::PlaybackPath::Remux,
+        (output, _) => crate::playback::path_of(output.as_deref()),
+    };
+    let decision = crate::playback::PlaybackDecision {
+        path,
+        reason: crate::playback::explain(path, probed),
+    };
+    let subtitle = selection
+        .subtitle
+        .and_then(|index| probed.streams.iter().find(|s| s.index == index))
+        .filter(|s| subtitles::is_text_codec(&s.codec) && probed.duration_secs.is_some())
+        .map(|s| s.index);
+    let session = match (output, timeshift) {
+        (output, Some(buffer_secs)) => {
+            let output = output.unwrap_or_else(|| remux(probed, selection));
+            Some(proxy.start_timeshift(&input, &output, buffer_secs)?)
+        }
+        (None, None) => None,
+        (Some(output), None) => {
+            let vod = probed.duration_secs.is_some();
+            Some(proxy.start_hls(&input, &output, vod, subtitle)?)
+        }
+    };
+    Ok(Served {
+        subtitle: subtitle.filter(|_| session.is_some()),
+        session,
+        timeshift_secs: timeshift,
+        decision,
+    })
+}
+
+/// Adds the subtitle track session `session_id` converts (see `ProxyState::start_hls`) to
+/// window `label`, once ffmpeg has written all of it.
+async fn attach_subtitles(
+    app: tauri::AppHandle,
+    session_id: String,
+    label: String,
+    stream: crate::probe::ProbeStream,
+) {
+    let proxy = app.state::<ProxyState>();
+    let path = loop {
+        tokio::time::sleep(SUBTITLE_POLL).await;
+        match proxy.finished_subtitles(&session_id) {
+            Ok(None) => continue,
+            Ok(Some(path)) => break path,
+            Err(e) => {
+                tracing::debug!("Embedded subtitles not converted: {}", e);
+                return;
+            }
+        }
+    };
+    match subtitles::load(&proxy, &path) {
+        Ok(mut track) => {
+            track.label = stream
+                .title
+                .or_else(|| stream.language.clone())
+                .unwrap_or_else(|| format!("Track {}", stream.index));
+            track.language = stream.language;
+            subtitles::attach(&app, Some(label), &track);
+        }
+        Err(e) => tracing::warn!("Failed to load embedded subtitles: {}", e),
+    }
+}
+
+/// What open video window `label` should load to play `source`, the way `open_player` would
+/// serve it: the source itself, or the URL of an ffmpeg session started for it. The session,
+/// returned too, has no owner yet.
+pub(crate) async fn webview_stream(
+    app: &tauri::AppHandle,
+    proxy: &ProxyState,
+    settings: &SettingsStore,
+    source: &Source,
+    label: &str,
+) -> Result<(String, Option<ProxySession>), String> {
+    let probed = match probe::probe(source.url.clone()).await {
+        Ok(probed) => probed,
+        Err(e) if is_dash_url(&source.url) => {
+            return Err(format!("Could not read the DASH manifest: {}", e));
+        }
+        Err(e) if source.decryption.is_some() => {
+            return Err(format!("Could not read the encrypted stream: {}", e));
+        }
+        Err(e) if crate::ingest::is_ingest(&source.url) => {
+            return Err(format!("Could not open the stream: {}", e));
+        }
+        Err(e) => {
+            tracing::warn!("Probing failed, playing directly: {}", e);
+            return Ok((source.url.clone(), None));
+        }
+    };
+    let prefs = tracks::preferences(settings, &settings::profile_id(None));
+    let selection = tracks::select(&probed, &prefs);
+    let decryption = source.decryption.clone();
+    let served = serve(
+        app,
+        proxy,
+        settings,
+        &probed,
+        &selection,
+        &source.url,
+        decryption,
+    )
+    .await?;
+    crate::playback::report(app, Some(label), &source.url, &served.decision);
+    Ok(match served.session {
+        Some(session) => (session.url.clone(), Some(session)),
+        None => (source.url.clone(), None),
+    })
+}
+
+/// Plays `source` in the video window `reuse`, else in a new one.
+fn open_direct(
+    app: &tauri::AppHandle,
+    reuse: Option<&str>,
+    title: &str,
+    stream_url: &str,
+    content_key: Option<String>,
+    params: &[(&str, String)],
+) -> Result<String, String> {
+    match reuse {
+        Some(label) => {
+            crate::play_in(app, label, title, stream_url, content_key).map(|()| label.to_string())
+        }
+        None => crate::create_video_window_with(app, title, stream_url, params),
+    }
+}
+
+/// Opens a window playing `source`, probing it unless `probed` already has. The webview
+/// plays what it can in window `reuse` when given; ffmpeg sessions always get a new window
+/// since its page options are fixed when it's built.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn open_player(
+    app: &tauri::AppHandle,
+    proxy: &ProxyState,
+    settings: &SettingsStore,
+    item_tracks: &ItemTrackStore,
+    title: String,
+    source: &Source,
+    probed: Option<ProbeResult>,
+    profile_id: Option<String>,
+    content_key: Option<String>,
+    audio_only: bool,
+    reuse: Option<&str>,
+) -> Result<String, String> {
+    let stream_url = source.url.clone();
+    let decryption = source.decryption.clone();
+    let choice = content_key
+        .as_ref()
+        .and_then(|key| item_tracks.read(|items| items.get(key).cloned()));
+    let probed = match probed {
+        Some(probed) => Ok(probed),
+        None => probe::probe(stream_url.clone()).await,
+    };
+    // Without ffprobe (or if probing fails) let the webview try the stream as-is
+    let probed = match probed {
+        Ok(probed) => probed,
+        // The page can't play DASH at all, so there is no point trying
+        Err(e) if is_dash_url(&stream_url) => {
+            return Err(format!("Could not read the DASH manifest: {}", e));
+        }
+        Err(e) if decryption.is_some() => {
+            return Err(format!("Could not read the encrypted stream: {}", e));
+        }
+        Err(e) if crate::ingest::is_ingest(&stream_url) => {
+            return Err(format!("Could not open the stream: {}", e));
+        }
+        Err(e) => {
+            tracing::warn!("Probing failed, playing directly: {}", e);
+            let mut params = window_params(
+                settings,
+                &TrackSelection::default(),
+                content_key.as_deref(),
+                choice.as_ref(),
+            );
+            if audio_only {
+                params.push(("audio", "1".to_string()));
+            }
+            return open_direct(app, reuse, &title, &stream_url, content_key, &params);
+        }
+    };
+    let prefs = tracks::preferences(settings, &settings::profile_id(profile_id));
+    let mut selection = tracks::select(&probed, &prefs);
+    if let Some(choice) = &choice {
+        tracks::apply_item_choice(&probed, choice, &mut selection);
+    }
+    let mut params = window_params(
+        settings,
+        &selection,
+        content_key.as_deref(),
+        choice.as_ref(),
+    );
+    if audio_only || probed.video().is_none() {
+        params.push(("audio", "1".to_string()));
+    }
+    let served = serve(
+        app,
+        proxy,
+        settings,
+        &probed,
+        &selection,
+        &stream_url,
+        decryption,
+    )
+    .await?;
+    let session_id = served.session.as_ref().map(|s| s.id.clone());
+    let label = match served.session {
+        Some(session) => {
+            if let Some(buffer_secs) = served.timeshift_secs {
+                params.push(("timeshift", buffer_secs.to_string()));
+            }
+            crate::proxy::open_window(app, proxy, &session, &title, &params)?
+        }
+        None => open_direct(app, reuse, &title, &stream_url, content_key, &params)?,
+    };
+    crate::playback::report(app, Some(&label), &stream_url, &served.decision);
+    // The video element ignores embedded subtitles, so the session hands the preferred one
+    // over as VTT. Without a session reading the stream they stay on demand
+    // (`extract_subtitle`), which would take another connection.
+    let embedded = served
+        .subtitle
+        .and_then(|index| probed.streams.iter().find(|s| s.index == index));
+    if let (Some(session_id), Some(stream)) = (session_id, embedded) {
+        tauri::async_runtime::spawn(attach_subtitles(
+            app.clone(),
+            session_id,
+            label.clone(),
+            stream.clone(),
+        ));
+    }
+    Ok(label)
+}
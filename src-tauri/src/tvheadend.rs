@@ -0,0 +1,474 @@
+//! TVHeadend client over its HTTP/JSON API. Recordings are scheduled on the TVHeadend box
+//! itself (its DVR), not recorded locally.
+//!
+//! Authentication uses HTTP basic auth, so the TVHeadend user must be allowed plain auth
+//! ("Both plain and digest" under Configuration > General > Base > HTTP Server Settings).
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::cache::CacheKind;
+use crate::offline::{self, Cached};
+use crate::servers::{self, ServerConfig, ServerKind, ServerStore};
+use crate::wol;
+
+const GRID_LIMIT: u32 = 10_000;
+/// TVHeadend's "normal" DVR priority, used where an entry has none of its own.
+pub const PRIORITY_NORMAL: u32 = 2;
+/// The "not set" priority TVHeadend gives entries by default.
+const PRIORITY_DEFAULT: u32 = 6;
+
+#[derive(Deserialize)]
+struct Grid<T> {
+    #[serde(default = "Vec::new")]
+    entries: Vec<T>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TvhChannelTag {
+    pub uuid: String,
+    pub name: String,
+    #[serde(default)]
+    pub enabled: bool,
+}
+
+#[derive(Deserialize)]
+struct RawChannel {
+    uuid: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    number: Option<f64>,
+    #[serde(default)]
+    icon_public_url: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default = "enabled_default")]
+    enabled: bool,
+}
+
+fn enabled_default() -> bool {
+    true
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TvhChannel {
+    pub uuid: String,
+    pub name: String,
+    pub number: Option<f64>,
+    pub icon_url: Option<String>,
+    pub tag_uuids: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TvhEvent {
+    pub event_id: u64,
+    pub channel_uuid: String,
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub subtitle: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Unix seconds.
+    pub start: i64,
+    /// Unix seconds.
+    pub stop: i64,
+    #[serde(default)]
+    pub genre: Vec<u32>,
+}
+
+#[derive(Deserialize)]
+struct RawProfile {
+    key: String,
+    val: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TvhStreamProfile {
+    pub key: String,
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all(serialize = "camelCase"))]
+pub struct TvhRecording {
+    pub uuid: String,
+    #[serde(default, rename(deserialize = "disp_title"))]
+    pub title: String,
+    #[serde(default, rename(deserialize = "channelname"))]
+    pub channel_name: String,
+    #[serde(default)]
+    pub start: i64,
+    #[serde(default)]
+    pub stop: i64,
+    /// TVHeadend's status string, e.g. "Scheduled for recording", "Running", "Completed OK".
+    #[serde(default)]
+    pub status: String,
+    #[serde(default, rename(deserialize = "channel"))]
+    pub channel_uuid: String,
+    /// DVR priority, 0 (important) to 4 (unimportant); 6 is TVHeadend's "not set".
+    #[serde(default = "default_priority", rename(deserialize = "pri"))]
+    pub priority: u32,
+}
+
+fn default_priority() -> u32 {
+    PRIORITY_DEFAULT
+}
+
+impl TvhRecording {
+    /// Priority with "not set" read as normal; lower is more important.
+    pub fn effective_priority(&self) -> u32 {
+        if self.priority == PRIORITY_DEFAULT {
+            PRIORITY_NORMAL
+        } else {
+            self.priority
+        }
+    }
+}
+
+fn client(server: &ServerConfig) -> Result<reqwest::Client, String> {
+    crate::http::client(Some(server))
+}
+
+fn tvh_server(store: &ServerStore, server_id: &str) -> Result<ServerConfig, String> {
+    let server = servers::get(store, server_id)?;
+    if server.kind != ServerKind::Tvheadend {
+        return Err(format!("{} is not a TVHeadend server", server.name));
+    }
+    Ok(server)
+}
+
+fn request(
+    client: &reqwest::Client,
+    method: reqwest::Method,
+    server: &ServerConfig,
+    path: &str,
+) -> reqwest::RequestBuilder {
+    let req = client.request(method, format!("{}{}", server.base_url(), path));
+    if server.username.is_empty() {
+        req
+    } else {
+        req.basic_auth(&server.username, Some(&server.password))
+    }
+}
+
+async fn send_json<T: DeserializeOwned>(
+    server: &ServerConfig,
+    req: reqwest::RequestBuilder,
+) -> Result<T, String> {
+    let _permit = crate::http::queue(Some(server)).await;
+    let resp = wol::send_waking(server, req)
+        .await
+        .map_err(|e| format!("TVHeadend request failed: {}", e))?;
+    let status = resp.status();
+    if status == reqwest::StatusCode::UNAUTHORIZED {
+        return Err(
+            "TVHeadend rejected the credentials (plain HTTP auth must be enabled for this user)"
+                .to_string(),
+        );
+    }
+    if !status.is_success() {
+        return Err(format!("TVHeadend returned HTTP {}", status));
+    }
+    resp.json()
+        .await
+        .map_err(|e| format!("Invalid TVHeadend response: {}", e))
+}
+
+async fn grid<T: DeserializeOwned>(
+    server: &ServerConfig,
+    path: &str,
+    query: &[(&str, String)],
+) -> Result<Vec<T>, String> {
+    let result: Grid<T> = send_json(
+        server,
+        request(&client(server)?, reqwest::Method::GET, server, path)
+            .query(&[("limit", GRID_LIMIT.to_string())])
+            .query(query),
+    )
+    .await?;
+    Ok(result.entries)
+}
+
+#[tauri::command]
+pub async fn tvh_channel_tags(
+    app: tauri::AppHandle,
+    store: State<'_, ServerStore>,
+    server_id: String,
+) -> Result<Cached<Vec<TvhChannelTag>>, String> {
+    let server = tvh_server(&store, &server_id)?;
+    offline::cached(
+        &app,
+        CacheKind::Catalogs,
+        &server_id,
+        "tags",
+        grid(&server, "/api/channeltag/grid", &[]),
+    )
+    .await
+}
+
+/// Enabled channels sorted by channel number, each with the uuids of its tags.
+#[tauri::command]
+pub async fn tvh_channels(
+    app: tauri::AppHandle,
+    store: State<'_, ServerStore>,
+    server_id: String,
+) -> Result<Cached<Vec<TvhChannel>>, String> {
+    let server = tvh_server(&store, &server_id)?;
+    offline::cached(
+        &app,
+        CacheKind::Catalogs,
+        &server_id,
+        "channels",
+        fetch_channels(&server),
+    )
+    .await
+}
+
+pub(crate) async fn fetch_channels(server: &ServerConfig) -> Result<Vec<TvhChannel>, String> {
+    let raw: Vec<RawChannel> = grid(server, "/api/channel/grid", &[]).await?;
+    let mut channels: Vec<TvhChannel> = raw
+        .into_iter()
+        .filter(|ch| ch.enabled)
+        .map(|ch| TvhChannel {
+            // icon_public_url is relative (imagecache/N) unless the icon is external
+            icon_url: ch.icon_public_url.map(|icon| {
+                if icon.starts_with("http") {
+                    icon
+                } else {
+                    format!("{}/{}", server.base_url(), icon.trim_start_matches('/'))
+                }
+            }),
+            uuid: ch.uuid,
+            name: ch.name,
+            number: ch.number,
+            tag_uuids: ch.tags,
+        })
+        .collect();
+    channels.sort_by(|a, b| {
+        a.number
+            .unwrap_or(f64::MAX)
+            .total_cmp(&b.number.unwrap_or(f64::MAX))
+    });
+    Ok(channels)
+}
+
+/// EPG events, optionally limited to one channel.
+#[tauri::command]
+pub async fn tvh_epg(
+    app: tauri::AppHandle,
+    store: State<'_, ServerStore>,
+    server_id: String,
+    channel_uuid: Option<String>,
+) -> Result<Cached<Vec<TvhEvent>>, String> {
+    let server = tvh_server(&store, &server_id)?;
+    let key = format!("epg:{}", channel_uuid.as_deref().unwrap_or_default());
+    let mut query = Vec::new();
+    if let Some(channel_uuid) = channel_uuid {
+        query.push(("channel", channel_uuid));
+    }
+    offline::cached(
+        &app,
+        CacheKind::Epg,
+        &server_id,
+        &key,
+        grid(&server, "/api/epg/events/grid", &query),
+    )
+    .await
+}
+
+#[tauri::command]
+pub async fn tvh_stream_profiles(
+    store: State<'_, ServerStore>,
+    server_id: String,
+) -> Result<Vec<TvhStreamProfile>, String> {
+    let server = tvh_server(&store, &server_id)?;
+    let raw: Vec<RawProfile> = grid(&server, "/api/profile/list", &[]).await?;
+    Ok(raw
+        .into_iter()
+        .map(|p| TvhStreamProfile {
+            key: p.key,
+            name: p.val,
+        })
+        .collect())
+}
+
+/// Builds the stream URL for a channel. Uses `profile`, else the server's saved stream profile,
+/// else TVHeadend's default. Credentials are embedded since players can't send basic auth.
+#[tauri::command]
+pub fn tvh_stream_url(
+    store: State<'_, ServerStore>,
+    server_id: String,
+    channel_uuid: String,
+    profile: Option<String>,
+) -> Result<String, String> {
+    stream_url(&tvh_server(&store, &server_id)?, &channel_uuid, profile)
+}
+
+pub(crate) fn stream_url(
+    server: &ServerConfig,
+    channel_uuid: &str,
+    profile: Option<String>,
+) -> Result<String, String> {
+    let mut url = reqwest::Url::parse(&format!(
+        "{}/stream/channel/{}",
+        server.base_url(),
+        channel_uuid
+    ))
+    .map_err(|e| format!("Invalid TVHeadend URL: {}", e))?;
+    if !server.username.is_empty() {
+        let _ = url.set_username(&server.username);
+        let _ = url.set_password(Some(&server.password));
+    }
+    if let Some(profile) = profile.or_else(|| server.stream_profile.clone()) {
+        url.query_pairs_mut().append_pair("profile", &profile);
+    }
+    Ok(url.to_string())
+}
+
+/// Schedules a recording of an EPG event on the TVHeadend DVR. Returns the new DVR entry uuid.
+#[tauri::command]
+pub async fn tvh_schedule_recording(
+    store: State<'_, ServerStore>,
+    server_id: String,
+    event_id: u64,
+) -> Result<String, String> {
+    #[derive(Deserialize)]
+    struct Created {
+        #[serde(default)]
+        uuid: Vec<String>,
+    }
+    let server = tvh_server(&store, &server_id)?;
+    let created: Created = send_json(
+        &server,
+        request(
+            &client(&server)?,
+            reqwest::Method::POST,
+            &server,
+            "/api/dvr/entry/create_by_event",
+        )
+        .form(&[
+            ("event_id", event_id.to_string()),
+            ("config_uuid", String::new()),
+        ]),
+    )
+    .await?;
+    created
+        .uuid
+        .into_iter()
+        .next()
+        .ok_or_else(|| "TVHeadend did not create a recording for that event".to_string())
+}
+
+/// Schedules a time-based recording (no EPG event) on the TVHeadend DVR. Times are unix seconds.
+#[tauri::command]
+pub async fn tvh_schedule_manual_recording(
+    store: State<'_, ServerStore>,
+    server_id: String,
+    channel_uuid: String,
+    start: i64,
+    stop: i64,
+    title: String,
+) -> Result<String, String> {
+    #[derive(Deserialize)]
+    struct Created {
+        uuid: String,
+    }
+    if stop <= start {
+        return Err("Recording must end after it starts".to_string());
+    }
+    let server = tvh_server(&store, &server_id)?;
+    let conf = serde_json::json!({
+        "channel": channel_uuid,
+        "start": start,
+        "stop": stop,
+        "title": { "eng": title },
+        "comment": "Scheduled by TvX",
+    });
+    let created: Created = send_json(
+        &server,
+        request(
+            &client(&server)?,
+            reqwest::Method::POST,
+            &server,
+            "/api/dvr/entry/create",
+        )
+        .form(&[("conf", conf.to_string())]),
+    )
+    .await?;
+    Ok(created.uuid)
+}
+
+/// Scheduled and running DVR entries.
+pub(crate) async fn upcoming_recordings(
+    server: &ServerConfig,
+) -> Result<Vec<TvhRecording>, String> {
+    grid(server, "/api/dvr/entry/grid_upcoming", &[]).await
+}
+
+/// Sets a DVR entry's priority, which TVHeadend uses to pick what records when tuners run out.
+pub(crate) async fn set_priority(
+    server: &ServerConfig,
+    uuid: &str,
+    priority: u32,
+) -> Result<(), String> {
+    let node = serde_json::json!({ "uuid": uuid, "pri": priority });
+    let _: serde_json::Value = send_json(
+        server,
+        request(
+            &client(server)?,
+            reqwest::Method::POST,
+            server,
+            "/api/idnode/save",
+        )
+        .form(&[("node", node.to_string())]),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Upcoming and finished DVR entries on the TVHeadend box.
+#[tauri::command]
+pub async fn tvh_recordings(
+    store: State<'_, ServerStore>,
+    server_id: String,
+) -> Result<Vec<TvhRecording>, String> {
+    let server = tvh_server(&store, &server_id)?;
+    let mut upcoming = upcoming_recordings(&server).await?;
+    let finished: Vec<TvhRecording> = grid(&server, "/api/dvr/entry/grid_finished", &[]).await?;
+    upcoming.extend(finished);
+    Ok(upcoming)
+}
+
+/// Cancels a scheduled (or stops a running) recording and removes its DVR entry.
+#[tauri::command]
+pub async fn tvh_cancel_recording(
+    store: State<'_, ServerStore>,
+    server_id: String,
+    uuid: String,
+) -> Result<(), String> {
+    let server = tvh_server(&store, &server_id)?;
+    cancel_recording(&server, &uuid).await
+}
+
+pub(crate) async fn cancel_recording(server: &ServerConfig, uuid: &str) -> Result<(), String> {
+    let req = request(
+        &client(server)?,
+        reqwest::Method::POST,
+        server,
+        "/api/idnode/delete",
+    )
+    .form(&[("uuid", uuid)]);
+    let resp = wol::send_waking(server, req)
+        .await
+        .map_err(|e| format!("TVHeadend request failed: {}", e))?;
+    if !resp.status().is_success() {
+        return Err(format!("TVHeadend returned HTTP {}", resp.status()));
+    }
+    Ok(())
+}
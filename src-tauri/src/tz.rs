@@ -0,0 +1,564 @@
+//! IANA time zones from the system zoneinfo database (TZif files), for converting UTC guide
+//! times to a provider's or the user's local time across DST changes. Times past the last
+//! transition in the file use its POSIX TZ footer rule, which slim TZif files rely on.
+//! Without a zoneinfo database (Windows), common zones fall back to their current rule from a
+//! bundled table, which is right for guide times though not for dates before a rule changed.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+use tauri::State;
+
+use crate::settings::SettingsStore;
+
+const ZONEINFO_DIRS: [&str; 3] = [
+    "/usr/share/zoneinfo",
+    "/usr/lib/zoneinfo",
+    "/usr/share/lib/zoneinfo",
+];
+
+/// Current POSIX TZ rules of common zones (tzdata 2025), for systems without zoneinfo.
+const FALLBACK_RULES: [(&str, &str); 74] = [
+    ("Africa/Cairo", "EET-2EEST,M4.5.5/0,M10.5.4/24"),
+    ("Africa/Casablanca", "<+01>-1"),
+    ("Africa/Johannesburg", "SAST-2"),
+    ("Africa/Lagos", "WAT-1"),
+    ("Africa/Nairobi", "EAT-3"),
+    ("America/Anchorage", "AKST9AKDT,M3.2.0,M11.1.0"),
+    ("America/Argentina/Buenos_Aires", "<-03>3"),
+    ("America/Bogota", "<-05>5"),
+    ("America/Chicago", "CST6CDT,M3.2.0,M11.1.0"),
+    ("America/Denver", "MST7MDT,M3.2.0,M11.1.0"),
+    ("America/Halifax", "AST4ADT,M3.2.0,M11.1.0"),
+    ("America/Lima", "<-05>5"),
+    ("America/Los_Angeles", "PST8PDT,M3.2.0,M11.1.0"),
+    ("America/Mexico_City", "CST6"),
+    ("America/New_York", "EST5EDT,M3.2.0,M11.1.0"),
+    ("America/Phoenix", "MST7"),
+    ("America/Santiago", "<-04>4<-03>,M9.1.6/24,M4.1.6/24"),
+    ("America/Sao_Paulo", "<-03>3"),
+    ("America/St_Johns", "NST3:30NDT,M3.2.0,M11.1.0"),
+    ("America/Toronto", "EST5EDT,M3.2.0,M11.1.0"),
+    ("America/Vancouver", "PST8PDT,M3.2.0,M11.1.0"),
+    ("Asia/Bangkok", "<+07>-7"),
+    ("Asia/Dhaka", "<+06>-6"),
+    ("Asia/Dubai", "<+04>-4"),
+    ("Asia/Hong_Kong", "HKT-8"),
+    ("Asia/Jakarta", "WIB-7"),
+    ("Asia/Jerusalem", "IST-2IDT,M3.4.4/26,M10.5.0"),
+    ("Asia/Karachi", "PKT-5"),
+    ("Asia/Kathmandu", "<+0545>-5:45"),
+    ("Asia/Kolkata", "IST-5:30"),
+    ("Asia/Kuala_Lumpur", "<+08>-8"),
+    ("Asia/Manila", "PST-8"),
+    ("Asia/Riyadh", "<+03>-3"),
+    ("Asia/Seoul", "KST-9"),
+    ("Asia/Shanghai", "CST-8"),
+    ("Asia/Singapore", "<+08>-8"),
+    ("Asia/Taipei", "CST-8"),
+    ("Asia/Tehran", "<+0330>-3:30"),
+    ("Asia/Tokyo", "JST-9"),
+    ("Atlantic/Reykjavik", "GMT0"),
+    ("Australia/Adelaide", "ACST-9:30ACDT,M10.1.0,M4.1.0/3"),
+    ("Australia/Brisbane", "AEST-10"),
+    ("Australia/Melbourne", "AEST-10AEDT,M10.1.0,M4.1.0/3"),
+    ("Australia/Perth", "AWST-8"),
+    ("Australia/Sydney", "AEST-10AEDT,M10.1.0,M4.1.0/3"),
+    ("Europe/Amsterdam", "CET-1CEST,M3.5.0,M10.5.0/3"),
+    ("Europe/Athens", "EET-2EEST,M3.5.0/3,M10.5.0/4"),
+    ("Europe/Belgrade", "CET-1CEST,M3.5.0,M10.5.0/3"),
+    ("Europe/Berlin", "CET-1CEST,M3.5.0,M10.5.0/3"),
+    ("Europe/Brussels", "CET-1CEST,M3.5.0,M10.5.0/3"),
+    ("Europe/Bucharest", "EET-2EEST,M3.5.0/3,M10.5.0/4"),
+    ("Europe/Budapest", "CET-1CEST,M3.5.0,M10.5.0/3"),
+    ("Europe/Copenhagen", "CET-1CEST,M3.5.0,M10.5.0/3"),
+    ("Europe/Dublin", "GMT0IST,M3.5.0/1,M10.5.0"),
+    ("Europe/Helsinki", "EET-2EEST,M3.5.0/3,M10.5.0/4"),
+    ("Europe/Istanbul", "<+03>-3"),
+    ("Europe/Kyiv", "EET-2EEST,M3.5.0/3,M10.5.0/4"),
+    ("Europe/Lisbon", "WET0WEST,M3.5.0/1,M10.5.0"),
+    ("Europe/London", "GMT0BST,M3.5.0/1,M10.5.0"),
+    ("Europe/Madrid", "CET-1CEST,M3.5.0,M10.5.0/3"),
+    ("Europe/Moscow", "MSK-3"),
+    ("Europe/Oslo", "CET-1CEST,M3.5.0,M10.5.0/3"),
+    ("Europe/Paris", "CET-1CEST,M3.5.0,M10.5.0/3"),
+    ("Europe/Prague", "CET-1CEST,M3.5.0,M10.5.0/3"),
+    ("Europe/Riga", "EET-2EEST,M3.5.0/3,M10.5.0/4"),
+    ("Europe/Rome", "CET-1CEST,M3.5.0,M10.5.0/3"),
+    ("Europe/Sofia", "EET-2EEST,M3.5.0/3,M10.5.0/4"),
+    ("Europe/Stockholm", "CET-1CEST,M3.5.0,M10.5.0/3"),
+    ("Europe/Vienna", "CET-1CEST,M3.5.0,M10.5.0/3"),
+    ("Europe/Warsaw", "CET-1CEST,M3.5.0,M10.5.0/3"),
+    ("Europe/Zagreb", "CET-1CEST,M3.5.0,M10.5.0/3"),
+    ("Europe/Zurich", "CET-1CEST,M3.5.0,M10.5.0/3"),
+    ("Pacific/Auckland", "NZST-12NZDT,M9.5.0,M4.1.0/3"),
+    ("Pacific/Honolulu", "HST10"),
+];
+
+/// A POSIX TZ rule date: `Mm.w.d` (day `d` of week `w` of month `m`), `Jn` (Julian day,
+/// no Feb 29) or `n` (zero-based day of year).
+#[derive(Debug, Clone, Copy)]
+enum RuleDate {
+    MonthWeekDay { month: u32, week: u32, weekday: u32 },
+    Julian(u32),
+    ZeroBased(u32),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Rule {
+    std_offset: i32,
+    dst_offset: i32,
+    start: (RuleDate, i32),
+    end: (RuleDate, i32),
+}
+
+#[derive(Debug, Clone)]
+pub struct Zone {
+    /// `(utc, offset)`: from `utc` on, local time is UTC + `offset` seconds.
+    transitions: Vec<(i64, i32)>,
+    /// Offset before the first transition.
+    initial: i32,
+    /// POSIX footer for times after the last transition; `Err` holds a fixed offset.
+    rule: Option<Result<Rule, i32>>,
+}
+
+impl Zone {
+    /// Seconds east of UTC at `utc`.
+    pub fn offset_at(&self, utc: i64) -> i32 {
+        let after_last = self.transitions.last().is_none_or(|&(t, _)| utc >= t);
+        match &self.rule {
+            Some(Ok(rule)) if after_last => rule.offset_at(utc),
+            Some(Err(fixed)) if after_last => *fixed,
+            _ => match self.transitions.partition_point(|&(t, _)| t <= utc) {
+                0 => self.initial,
+                i => self.transitions[i - 1].1,
+            },
+        }
+    }
+}
+
+fn days_in_month(year: i64, month: u32) -> i64 {
+    let leap = (year % 4 == 0 && year % 100 != 0) || year % 400 == 0;
+    match month {
+        2 if leap => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+impl RuleDate {
+    /// Days since the epoch of this date in `year`.
+    fn day(self, year: i64) -> i64 {
+        let jan1 = crate::days_from_civil(year, 1, 1);
+        match self {
+            RuleDate::ZeroBased(n) => jan1 + i64::from(n),
+            RuleDate::Julian(n) => {
+                let leap_shift = i64::from(n >= 60 && days_in_month(year, 2) == 29);
+                jan1 + i64::from(n) - 1 + leap_shift
+            }
+            RuleDate::MonthWeekDay {
+                month,
+                week,
+                weekday,
+            } => {
+                let first = crate::days_from_civil(year, month, 1);
+                // 1970-01-01 was a Thursday
+                let first_weekday = (first + 4).rem_euclid(7);
+                let mut day = first + (i64::from(weekday) - first_weekday).rem_euclid(7);
+                day += 7 * (i64::from(week) - 1);
+                let last = first + days_in_month(year, month) - 1;
+                while day > last {
+                    day -= 7;
+                }
+                day
+            }
+        }
+    }
+}
+
+impl Rule {
+    fn offset_at(&self, utc: i64) -> i32 {
+        let (year, _, _) = crate::civil_date((utc + i64::from(self.std_offset)).max(0) as u64);
+        let transition = |(date, time): (RuleDate, i32), offset: i32| {
+            date.day(year) * 86_400 + i64::from(time) - i64::from(offset)
+        };
+        // The switch to DST happens in standard time and the switch back in DST
+        let start = transition(self.start, self.std_offset);
+        let end = transition(self.end, self.dst_offset);
+        let in_dst = if start < end {
+            utc >= start && utc < end
+        } else {
+            utc < end || utc >= start
+        };
+        if in_dst {
+            self.dst_offset
+        } else {
+            self.std_offset
+        }
+    }
+}
+
+struct Cursor<'a> {
+    s: &'a str,
+}
+
+impl Cursor<'_> {
+    fn peek(&self) -> Option<char> {
+        self.s.chars().next()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        match self.s.strip_prefix(c) {
+            Some(rest) => {
+                self.s = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn number(&mut self) -> Option<i64> {
+        let end = self
+            .s
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(self.s.len());
+        let (digits, rest) = self.s.split_at(end);
+        self.s = rest;
+        digits.parse().ok()
+    }
+
+    /// `EST` or `<+03>`.
+    fn name(&mut self) -> Option<()> {
+        if self.eat('<') {
+            let end = self.s.find('>')?;
+            self.s = &self.s[end + 1..];
+        } else {
+            let end = self
+                .s
+                .find(|c: char| !c.is_ascii_alphabetic())
+                .unwrap_or(self.s.len());
+            if end < 3 {
+                return None;
+            }
+            self.s = &self.s[end..];
+        }
+        Some(())
+    }
+
+    /// `[+-]hh[:mm[:ss]]` in seconds.
+    fn time(&mut self) -> Option<i32> {
+        let sign = if self.eat('-') {
+            -1
+        } else {
+            self.eat('+');
+            1
+        };
+        let mut secs = self.number()? * 3600;
+        if self.eat(':') {
+            secs += self.number()? * 60;
+            if self.eat(':') {
+                secs += self.number()?;
+            }
+        }
+        i32::try_from(sign * secs).ok()
+    }
+
+    fn rule_date(&mut self) -> Option<(RuleDate, i32)> {
+        let date = if self.eat('M') {
+            let month = self.number()? as u32;
+            self.eat('.').then_some(())?;
+            let week = self.number()? as u32;
+            self.eat('.').then_some(())?;
+            let weekday = self.number()? as u32;
+            if !(1..=12).contains(&month) || !(1..=5).contains(&week) || weekday > 6 {
+                return None;
+            }
+            RuleDate::MonthWeekDay {
+                month,
+                week,
+                weekday,
+            }
+        } else if self.eat('J') {
+            RuleDate::Julian(self.number()? as u32)
+        } else {
+            RuleDate::ZeroBased(self.number()? as u32)
+        };
+        let time = if self.eat('/') { self.time()? } else { 7200 };
+        Some((date, time))
+    }
+}
+
+/// Parses a POSIX TZ string like `CET-1CEST,M3.5.0,M10.5.0/3`. POSIX offsets count west of
+/// UTC, so they are negated. A zone without DST comes back as a fixed offset.
+fn parse_posix(s: &str) -> Option<Result<Rule, i32>> {
+    let mut c = Cursor { s };
+    c.name()?;
+    let std_offset = -c.time()?;
+    if c.peek().is_none() {
+        return Some(Err(std_offset));
+    }
+    c.name()?;
+    let dst_offset = match c.peek() {
+        Some(',') | None => std_offset + 3600,
+        _ => -c.time()?,
+    };
+    // Without explicit dates, POSIX leaves the rule implementation-defined; use the US one
+    let (start, end) = if c.eat(',') {
+        let start = c.rule_date()?;
+        c.eat(',').then_some(())?;
+        (start, c.rule_date()?)
+    } else {
+        (
+            (
+                RuleDate::MonthWeekDay {
+                    month: 3,
+                    week: 2,
+                    weekday: 0,
+                },
+                7200,
+            ),
+            (
+                RuleDate::MonthWeekDay {
+                    month: 11,
+                    week: 1,
+                    weekday: 0,
+                },
+                7200,
+            ),
+        )
+    };
+    Some(Ok(Rule {
+        std_offset,
+        dst_offset,
+        start,
+        end,
+    }))
+}
+
+fn be_u32(data: &[u8], at: usize) -> Option<usize> {
+    let bytes: [u8; 4] = data.get(at..at + 4)?.try_into().ok()?;
+    usize::try_from(u32::from_be_bytes(bytes)).ok()
+}
+
+/// Parses TZif data, preferring the 64-bit section of version 2+ files.
+fn parse_tzif(data: &[u8]) -> Option<Zone> {
+    struct Counts {
+        isut: usize,
+        isstd: usize,
+        leap: usize,
+        time: usize,
+        types: usize,
+        chars: usize,
+    }
+    let header = |at: usize| -> Option<Counts> {
+        if data.get(at..at + 4)? != b"TZif" {
+            return None;
+        }
+        Some(Counts {
+            isut: be_u32(data, at + 20)?,
+            isstd: be_u32(data, at + 24)?,
+            leap: be_u32(data, at + 28)?,
+            time: be_u32(data, at + 32)?,
+            types: be_u32(data, at + 36)?,
+            chars: be_u32(data, at + 40)?,
+        })
+    };
+    let block_len = |c: &Counts, time_size: usize| {
+        c.time * time_size
+            + c.time
+            + c.types * 6
+            + c.chars
+            + c.leap * (time_size + 4)
+            + c.isstd
+            + c.isut
+    };
+    let v1 = header(0)?;
+    let version = *data.get(4)?;
+    let (counts, start, time_size) = if version >= b'2' {
+        let at = 44 + block_len(&v1, 4);
+        (header(at)?, at + 44, 8)
+    } else {
+        (v1, 44, 4)
+    };
+    let times_at = start;
+    let indexes_at = times_at + counts.time * time_size;
+    let types_at = indexes_at + counts.time;
+    let offset_of = |ty: usize| -> Option<i32> {
+        let at = types_at + ty * 6;
+        Some(i32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?))
+    };
+    let mut transitions = Vec::with_capacity(counts.time);
+    for i in 0..counts.time {
+        let at = times_at + i * time_size;
+        let utc = if time_size == 8 {
+            i64::from_be_bytes(data.get(at..at + 8)?.try_into().ok()?)
+        } else {
+            i64::from(i32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?))
+        };
+        let ty = usize::from(*data.get(indexes_at + i)?);
+        transitions.push((utc, offset_of(ty)?));
+    }
+    let footer_at = start + block_len(&counts, time_size);
+    let rule = data
+        .get(footer_at..)
+        .and_then(|rest| std::str::from_utf8(rest).ok())
+        .map(|rest| rest.trim_matches('\n'))
+        .filter(|s| !s.is_empty())
+        .and_then(parse_posix);
+    Some(Zone {
+        transitions,
+        initial: if counts.types > 0 { offset_of(0)? } else { 0 },
+        rule,
+    })
+}
+
+fn zoneinfo_dirs() -> Vec<PathBuf> {
+    std::env::var_os("TZDIR")
+        .map(PathBuf::from)
+        .into_iter()
+        .chain(ZONEINFO_DIRS.iter().map(PathBuf::from))
+        .collect()
+}
+
+/// `name` from the bundled rules, when the system has no zoneinfo for it.
+fn fallback_zone(name: &str) -> Option<Zone> {
+    let (_, posix) = FALLBACK_RULES.iter().find(|(zone, _)| *zone == name)?;
+    let rule = parse_posix(posix)?;
+    Some(Zone {
+        transitions: Vec::new(),
+        initial: match rule {
+            Ok(rule) => rule.std_offset,
+            Err(fixed) => fixed,
+        },
+        rule: Some(rule),
+    })
+}
+
+static ZONES: Mutex<BTreeMap<String, Arc<Zone>>> = Mutex::new(BTreeMap::new());
+
+/// Loads an IANA zone such as `Europe/Berlin`. `UTC`/`GMT` work without a database.
+pub fn zone(name: &str) -> Result<Arc<Zone>, String> {
+    let name = name.trim();
+    let valid = !name.is_empty()
+        && !name.starts_with('/')
+        && name
+            .split('/')
+            .all(|part| !part.is_empty() && part != "." && part != "..");
+    if !valid {
+        return Err(format!("Invalid time zone \"{}\"", name));
+    }
+    if let Some(zone) = ZONES.lock().unwrap_or_else(|e| e.into_inner()).get(name) {
+        return Ok(zone.clone());
+    }
+    let zone = zoneinfo_dirs()
+        .into_iter()
+        .find_map(|dir| parse_tzif(&std::fs::read(dir.join(name)).ok()?))
+        .or_else(|| {
+            matches!(name, "UTC" | "Etc/UTC" | "GMT" | "Etc/GMT").then(|| Zone {
+                transitions: Vec::new(),
+                initial: 0,
+                rule: None,
+            })
+        })
+        .or_else(|| fallback_zone(name))
+        .ok_or_else(|| format!("Unknown time zone \"{}\"", name))?;
+    let zone = Arc::new(zone);
+    ZONES
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(name.to_string(), zone.clone());
+    Ok(zone)
+}
+
+/// The system's zone name, from `TZ`, `/etc/timezone` or the `/etc/localtime` link.
+pub fn system_zone_name() -> Option<String> {
+    if let Ok(tz) = std::env::var("TZ") {
+        let tz = tz.trim_start_matches(':');
+        if zone(tz).is_ok() {
+            return Some(tz.to_string());
+        }
+    }
+    if let Ok(name) = std::fs::read_to_string("/etc/timezone") {
+        let name = name.trim();
+        if !name.is_empty() {
+            return Some(name.to_string());
+        }
+    }
+    let target = std::fs::read_link("/etc/localtime").ok()?;
+    let target = target.to_string_lossy();
+    let (_, name) = target.split_once("zoneinfo/")?;
+    Some(name.to_string())
+}
+
+/// Splits `utc` shifted by `offset` seconds into `(year, month, day, hour, minute)`.
+pub fn local_parts(utc: i64, offset: i32) -> (i64, u32, u32, u32, u32) {
+    let local = utc + i64::from(offset);
+    let (year, month, day) = crate::civil_date(local.max(0) as u64);
+    let secs = local.rem_euclid(86_400);
+    (
+        year,
+        month,
+        day,
+        (secs / 3600) as u32,
+        (secs / 60 % 60) as u32,
+    )
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DisplayTimezone {
+    /// IANA name, `UTC` when neither a setting nor the system zone is known.
+    pub name: String,
+    /// Current offset from UTC.
+    pub offset_minutes: i32,
+    /// When the offset next changes (unix seconds), so the UI knows when to ask again.
+    pub next_change: Option<i64>,
+}
+
+impl Zone {
+    /// The first transition after `utc`, looking up to a year ahead for rule-based zones.
+    fn next_change(&self, utc: i64) -> Option<i64> {
+        let offset = self.offset_at(utc);
+        if let Some(&(t, _)) = self.transitions.iter().find(|&&(t, _)| t > utc) {
+            return Some(t);
+        }
+        // Rule transitions fall on whole minutes: find the hour, then the minute within it
+        let hour = (1..=366 * 24)
+            .map(|h| utc - utc.rem_euclid(3600) + h * 3600)
+            .find(|&t| self.offset_at(t) != offset)?;
+        (hour - 3600..=hour)
+            .step_by(60)
+            .find(|&t| self.offset_at(t) != offset)
+    }
+}
+
+/// Name of the zone times are shown in: the Settings choice, else the system zone.
+pub fn display_zone_name(settings: &SettingsStore) -> String {
+    settings
+        .read(|s| s.display_timezone.clone())
+        .filter(|name| zone(name).is_ok())
+        .or_else(system_zone_name)
+        .unwrap_or_else(|| "UTC".to_string())
+}
+
+/// The zone guide times are shown in: the Settings choice, else the system zone.
+#[tauri::command]
+pub fn get_display_timezone(settings: State<'_, SettingsStore>) -> DisplayTimezone {
+    let name = display_zone_name(&settings);
+    let now = crate::now_secs() as i64;
+    match zone(&name) {
+        Ok(zone) => DisplayTimezone {
+            offset_minutes: zone.offset_at(now) / 60,
+            next_change: zone.next_change(now),
+            name,
+        },
+        Err(_) => DisplayTimezone {
+            name,
+            offset_minutes: 0,
+            next_change: None,
+        },
+    }
+}
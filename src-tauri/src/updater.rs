@@ -0,0 +1,184 @@
+//! Update checks through the Tauri updater, which verifies every download against the
+//! minisign public key before installing it. The release feed and key are baked in at build
+//! time (`TVX_UPDATE_ENDPOINT`, `TVX_UPDATE_PUBKEY`); builds without them never check. The
+//! endpoint may contain `{channel}`, replaced with `stable` or `beta` from the settings.
+
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, Manager, State};
+use tauri_plugin_updater::{Update, UpdaterExt};
+
+use crate::settings::SettingsStore;
+
+const ENDPOINT: Option<&str> = option_env!("TVX_UPDATE_ENDPOINT");
+const PUBKEY: Option<&str> = option_env!("TVX_UPDATE_PUBKEY");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum UpdateChannel {
+    #[default]
+    Stable,
+    Beta,
+}
+
+impl UpdateChannel {
+    fn as_str(self) -> &'static str {
+        match self {
+            UpdateChannel::Stable => "stable",
+            UpdateChannel::Beta => "beta",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateSettings {
+    #[serde(default)]
+    pub channel: UpdateChannel,
+    /// Check once at startup.
+    #[serde(default = "check_on_start_default")]
+    pub check_on_start: bool,
+}
+
+fn check_on_start_default() -> bool {
+    true
+}
+
+impl Default for UpdateSettings {
+    fn default() -> Self {
+        Self {
+            channel: UpdateChannel::default(),
+            check_on_start: check_on_start_default(),
+        }
+    }
+}
+
+/// The update found by the last check, kept for `install_update`.
+#[derive(Default)]
+pub struct UpdaterState {
+    pending: Mutex<Option<Update>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateInfo {
+    pub version: String,
+    pub current_version: String,
+    pub date: Option<String>,
+    /// Release notes.
+    pub notes: Option<String>,
+    pub channel: UpdateChannel,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DownloadProgress {
+    downloaded: u64,
+    total: Option<u64>,
+}
+
+async fn check(app: &tauri::AppHandle) -> Result<Option<UpdateInfo>, String> {
+    let (Some(endpoint), Some(pubkey)) = (ENDPOINT, PUBKEY) else {
+        return Err("This build of TvX was not configured for updates".to_string());
+    };
+    let channel = app.state::<SettingsStore>().read(|s| s.updates.channel);
+    let url = reqwest::Url::parse(&endpoint.replace("{channel}", channel.as_str()))
+        .map_err(|e| format!("Invalid update endpoint: {}", e))?;
+    let updater = app
+        .updater_builder()
+        .pubkey(pubkey)
+        .endpoints(vec![url])
+        .and_then(|builder| builder.build())
+        .map_err(|e| format!("Updater unavailable: {}", e))?;
+    let update = updater
+        .check()
+        .await
+        .map_err(|e| format!("Update check failed: {}", e))?;
+    let info = update.as_ref().map(|u| UpdateInfo {
+        version: u.version.clone(),
+        current_version: u.current_version.clone(),
+        date: u.date.as_ref().map(|d| d.to_string()),
+        notes: u.body.clone(),
+        channel,
+    });
+    *app.state::<UpdaterState>()
+        .pending
+        .lock()
+        .unwrap_or_else(|e| e.into_inner()) = update;
+    Ok(info)
+}
+
+/// Background check at startup; announces a newer version with `update-available`.
+pub fn start(app: &tauri::AppHandle) {
+    let enabled = app
+        .state::<SettingsStore>()
+        .read(|s| s.updates.check_on_start);
+    if !enabled || ENDPOINT.is_none() || PUBKEY.is_none() {
+        return;
+    }
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        match check(&app).await {
+            Ok(Some(info)) => {
+                tracing::info!("Update {} available", info.version);
+                let _ = app.emit("update-available", info);
+            }
+            Ok(None) => tracing::debug!("No update available"),
+            Err(e) => tracing::warn!("{}", e),
+        }
+    });
+}
+
+/// Checks the configured channel. Returns the newer version, if there is one.
+#[tauri::command]
+pub async fn check_for_updates(app: tauri::AppHandle) -> Result<Option<UpdateInfo>, String> {
+    check(&app).await
+}
+
+/// Downloads and installs the update found by the last check, emitting
+/// `update-download-progress` while downloading, then restarts the app.
+#[tauri::command]
+pub async fn install_update(
+    app: tauri::AppHandle,
+    state: State<'_, UpdaterState>,
+) -> Result<(), String> {
+    let update = state
+        .pending
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .ok_or_else(|| "No update to install; check for updates first".to_string())?;
+    let mut downloaded = 0u64;
+    let progress = app.clone();
+    let finished = app.clone();
+    update
+        .download_and_install(
+            move |chunk, total| {
+                downloaded += chunk as u64;
+                let _ = progress.emit(
+                    "update-download-progress",
+                    DownloadProgress { downloaded, total },
+                );
+            },
+            move || {
+                let _ = finished.emit("update-downloaded", ());
+            },
+        )
+        .await
+        .map_err(|e| format!("Update failed: {}", e))?;
+    tracing::info!("Installed update {}, restarting", update.version);
+    app.restart()
+}
+
+#[tauri::command]
+pub fn set_update_channel(
+    settings: State<'_, SettingsStore>,
+    state: State<'_, UpdaterState>,
+    channel: UpdateChannel,
+) -> Result<(), String> {
+    settings.update(|s| s.updates.channel = channel)?;
+    // An update found on the other channel no longer applies
+    *state.pending.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    Ok(())
+}
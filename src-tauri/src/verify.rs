@@ -0,0 +1,399 @@
+//! Checks a server configuration before it is saved, so a mistyped URL or password shows up in
+//! the add-server form instead of as empty screens later. Three steps run in order: signing in,
+//! fetching a sample of the channel list, and asking one stream for its headers. A step that
+//! fails skips the ones after it, and steps a kind of server has no use for are skipped with
+//! the reason.
+
+use std::future::Future;
+use std::time::Instant;
+
+use serde::Serialize;
+
+use crate::servers::{ServerConfig, ServerKind};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TestStepKind {
+    Auth,
+    Categories,
+    Stream,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TestStepStatus {
+    Passed,
+    Failed,
+    Skipped,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TestStep {
+    pub step: TestStepKind,
+    pub status: TestStepStatus,
+    /// What was found, why the step failed, or why it was skipped.
+    pub detail: String,
+    pub elapsed_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerTest {
+    /// No step failed.
+    pub ok: bool,
+    pub steps: Vec<TestStep>,
+}
+
+#[derive(Default)]
+struct Steps(Vec<TestStep>);
+
+impl Steps {
+    fn failed(&self) -> bool {
+        self.0.iter().any(|s| s.status == TestStepStatus::Failed)
+    }
+
+    fn skip(&mut self, step: TestStepKind, reason: &str) {
+        self.0.push(TestStep {
+            step,
+            status: TestStepStatus::Skipped,
+            detail: reason.to_string(),
+            elapsed_ms: 0,
+        });
+    }
+
+    /// Runs `check` unless an earlier step failed, recording how it went. `check` returns its
+    /// detail and whatever the next step needs.
+    async fn run<T>(
+        &mut self,
+        step: TestStepKind,
+        check: impl Future<Output = Result<(String, T), String>>,
+    ) -> Option<T> {
+        if self.failed() {
+            self.skip(step, "Skipped after the previous step failed");
+            return None;
+        }
+        let started = Instant::now();
+        let result = check.await;
+        let elapsed_ms = started.elapsed().as_millis() as u64;
+        let (status, detail, value) = match result {
+            Ok((detail, value)) => (TestStepStatus::Passed, detail, Some(value)),
+            Err(e) => (TestStepStatus::Failed, e, None),
+        };
+        self.0.push(TestStep {
+            step,
+            status,
+            detail,
+            elapsed_ms,
+        });
+        value
+    }
+}
+
+fn plural(n: usize, one: &str, many: &str) -> String {
+    format!("{} {}", n, if n == 1 { one } else { many })
+}
+
+fn date(secs: u64) -> String {
+    let (year, month, day) = crate::civil_date(secs);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Asks `url` for its headers: HEAD, or a one-byte GET for servers that refuse HEAD.
+async fn probe_stream(server: &ServerConfig, url: &str) -> Result<(String, ()), String> {
+    let client = crate::http::stream_client(Some(server))?;
+    let mut resp = crate::http::send(client.head(url))
+        .await
+        .map_err(|e| format!("The stream didn't answer: {}", e))?;
+    if matches!(resp.status().as_u16(), 405 | 501) {
+        resp = crate::http::send(client.get(url).header("Range", "bytes=0-0"))
+            .await
+            .map_err(|e| format!("The stream didn't answer: {}", e))?;
+    }
+    let status = resp.status().as_u16();
+    let content_type = resp
+        .headers()
+        .get("content-type")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    if !resp.status().is_success() {
+        return Err(crate::failure::classify(status, &content_type, &[])
+            .map(|code| crate::failure::StreamFailure::new(code, Some(status)).message)
+            .unwrap_or_else(|| format!("The stream returned HTTP {}", status)));
+    }
+    Ok((
+        if content_type.is_empty() {
+            format!("A sample stream answered HTTP {}", status)
+        } else {
+            format!(
+                "A sample stream answered HTTP {} ({})",
+                status, content_type
+            )
+        },
+        (),
+    ))
+}
+
+async fn test_xtream(app: &tauri::AppHandle, server: &ServerConfig, steps: &mut Steps) {
+    let auth = async {
+        let info = crate::account::fetch(server).await?;
+        if !info.authorized {
+            return Err("The provider rejected the username or password".to_string());
+        }
+        if let Some(expires) = info.expires_at.filter(|_| info.is_expired()) {
+            return Err(format!("The subscription expired on {}", date(expires)));
+        }
+        if let Some(status) = info
+            .status
+            .as_deref()
+            .filter(|s| !s.eq_ignore_ascii_case("active"))
+        {
+            return Err(format!("The subscription is {}", status.to_lowercase()));
+        }
+        let mut detail = match info.expires_at {
+            Some(expires) => format!("Signed in; the subscription runs until {}", date(expires)),
+            None => "Signed in; the subscription doesn't expire".to_string(),
+        };
+        if let Some(max) = info.max_connections {
+            detail.push_str(&format!(
+                ", {} of {} in use",
+                info.active_connections.unwrap_or(0),
+                plural(max as usize, "connection", "connections")
+            ));
+        }
+        Ok((detail, ()))
+    };
+    steps.run(TestStepKind::Auth, auth).await;
+
+    let categories = async {
+        let categories = crate::catalog::xtream_action(server, "get_live_categories").await?;
+        let first = categories
+            .iter()
+            .find_map(|c| crate::catalog::text(&c["category_id"]));
+        Ok((
+            plural(categories.len(), "live category", "live categories"),
+            first,
+        ))
+    };
+    let first_category = steps.run(TestStepKind::Categories, categories).await;
+
+    let Some(Some(category)) = first_category else {
+        if first_category.is_some() {
+            steps.skip(
+                TestStepKind::Stream,
+                "The account has no live channels to try",
+            );
+        } else {
+            steps.skip(
+                TestStepKind::Stream,
+                "Skipped after the previous step failed",
+            );
+        }
+        return;
+    };
+    let stream = async {
+        let streams: Vec<serde_json::Value> = crate::catalog::xtream_call(
+            server,
+            "get_live_streams",
+            &[("category_id", category.as_str())],
+        )
+        .await?;
+        let id = streams
+            .iter()
+            .find_map(|s| crate::catalog::text(&s["stream_id"]))
+            .ok_or_else(|| "The first live category has no channels".to_string())?;
+        let url = crate::catalog::stream_url(app, server, &id).await?;
+        probe_stream(server, &url).await
+    };
+    steps.run(TestStepKind::Stream, stream).await;
+}
+
+async fn test_m3u(app: &tauri::AppHandle, server: &ServerConfig, steps: &mut Steps) {
+    steps.skip(TestStepKind::Auth, "Playlists have no sign-in");
+    let list = async {
+        let import_id = uuid::Uuid::new_v4().to_string();
+        let entries = crate::playlist::import(app, server, &import_id).await?;
+        if entries.is_empty() {
+            return Err("The playlist has no channels".to_string());
+        }
+        let groups: std::collections::BTreeSet<&str> =
+            entries.iter().filter_map(|e| e.group.as_deref()).collect();
+        Ok((
+            format!(
+                "{} in {}",
+                plural(entries.len(), "channel", "channels"),
+                plural(groups.len(), "group", "groups")
+            ),
+            entries.into_iter().next().map(|e| e.url),
+        ))
+    };
+    match steps.run(TestStepKind::Categories, list).await {
+        Some(Some(url)) if url.starts_with("http://") || url.starts_with("https://") => {
+            steps
+                .run(TestStepKind::Stream, probe_stream(server, &url))
+                .await;
+        }
+        Some(_) => steps.skip(
+            TestStepKind::Stream,
+            "The first channel isn't an HTTP stream; it is checked when played",
+        ),
+        None => steps.skip(
+            TestStepKind::Stream,
+            "Skipped after the previous step failed",
+        ),
+    }
+}
+
+async fn test_emby(server: &ServerConfig, steps: &mut Steps) {
+    let auth = async {
+        let signed_in = crate::emby::sign_in(server.clone()).await?;
+        Ok(("Signed in".to_string(), signed_in))
+    };
+    let Some(signed_in) = steps.run(TestStepKind::Auth, auth).await else {
+        steps.skip(
+            TestStepKind::Categories,
+            "Skipped after the previous step failed",
+        );
+        steps.skip(
+            TestStepKind::Stream,
+            "Skipped after the previous step failed",
+        );
+        return;
+    };
+    let channels = async {
+        let channels = crate::emby::live_channels(&signed_in).await?;
+        Ok((
+            plural(channels.len(), "live TV channel", "live TV channels"),
+            (),
+        ))
+    };
+    steps.run(TestStepKind::Categories, channels).await;
+    steps.skip(
+        TestStepKind::Stream,
+        "Emby opens a playback session per stream; it is checked when played",
+    );
+}
+
+async fn test_tvheadend(server: &ServerConfig, steps: &mut Steps) {
+    let started = Instant::now();
+    let fetched = crate::tvheadend::fetch_channels(server).await;
+    let elapsed_ms = started.elapsed().as_millis() as u64;
+    let channels = match fetched {
+        Ok(channels) => channels,
+        Err(e) => {
+            steps.0.push(TestStep {
+                step: TestStepKind::Auth,
+                status: TestStepStatus::Failed,
+                detail: e,
+                elapsed_ms,
+            });
+            steps.skip(
+                TestStepKind::Categories,
+                "Skipped after the previous step failed",
+            );
+            steps.skip(
+                TestStepKind::Stream,
+                "Skipped after the previous step failed",
+            );
+            return;
+        }
+    };
+    // The channel grid needs the same credentials, so one request answers both steps
+    for (step, detail) in [
+        (TestStepKind::Auth, "Signed in".to_string()),
+        (
+            TestStepKind::Categories,
+            plural(channels.len(), "enabled channel", "enabled channels"),
+        ),
+    ] {
+        steps.0.push(TestStep {
+            step,
+            status: TestStepStatus::Passed,
+            detail,
+            elapsed_ms,
+        });
+    }
+    let Some(first) = channels.first() else {
+        steps.skip(
+            TestStepKind::Stream,
+            "The server has no enabled channels to try",
+        );
+        return;
+    };
+    let stream = async {
+        let url = crate::tvheadend::stream_url(server, &first.uuid, None)?;
+        probe_stream(server, &url).await
+    };
+    steps.run(TestStepKind::Stream, stream).await;
+}
+
+async fn test_stalker(server: &ServerConfig, steps: &mut Steps) {
+    let auth = async {
+        crate::stalker::handshake(server).await?;
+        Ok(("The portal accepted this device".to_string(), ()))
+    };
+    if steps.run(TestStepKind::Auth, auth).await.is_none() {
+        steps.skip(
+            TestStepKind::Categories,
+            "Skipped after the previous step failed",
+        );
+        steps.skip(
+            TestStepKind::Stream,
+            "Skipped after the previous step failed",
+        );
+        return;
+    }
+    let channels = async {
+        let channels = crate::stalker::fetch_channels(server).await?;
+        Ok((plural(channels.len(), "channel", "channels"), ()))
+    };
+    steps.run(TestStepKind::Categories, channels).await;
+    steps.skip(
+        TestStepKind::Stream,
+        "Portal links are handed out per play; one is checked when played",
+    );
+}
+
+async fn test_tuner(server: &ServerConfig, steps: &mut Steps) {
+    steps.skip(TestStepKind::Auth, "Tuners have no sign-in");
+    let lineup = async {
+        let count = if server.kind == ServerKind::Hdhomerun {
+            crate::hdhomerun::lineup(server).await?.len()
+        } else {
+            crate::satip::channel_list(server).await?.len()
+        };
+        Ok((plural(count, "channel", "channels"), ()))
+    };
+    steps.run(TestStepKind::Categories, lineup).await;
+    steps.skip(
+        TestStepKind::Stream,
+        "Trying a stream would tie up a tuner; it is checked when played",
+    );
+}
+
+/// Validates an unsaved server: sign-in, channel list and one stream, each with its result.
+#[tauri::command]
+pub async fn test_server_connection(
+    app: tauri::AppHandle,
+    config: ServerConfig,
+) -> Result<ServerTest, String> {
+    if config.url.trim().is_empty() {
+        return Err("Enter the server's address".to_string());
+    }
+    config.validate_network()?;
+    let mut steps = Steps::default();
+    match config.kind {
+        ServerKind::Xtream => test_xtream(&app, &config, &mut steps).await,
+        ServerKind::M3u => test_m3u(&app, &config, &mut steps).await,
+        ServerKind::Emby => test_emby(&config, &mut steps).await,
+        ServerKind::Tvheadend => test_tvheadend(&config, &mut steps).await,
+        ServerKind::Stalker => test_stalker(&config, &mut steps).await,
+        ServerKind::Hdhomerun | ServerKind::Satip => test_tuner(&config, &mut steps).await,
+    }
+    tracing::info!(server = %config.name, ok = !steps.failed(), "Tested server connection");
+    Ok(ServerTest {
+        ok: !steps.failed(),
+        steps: steps.0,
+    })
+}
@@ -0,0 +1,256 @@
+//! Launching VLC as an external player. When the caller passes a content key, VLC is started
+//! with its HTTP interface on a random loopback port and polled so resume keeps working.
+
+use std::net::{Ipv4Addr, TcpListener};
+use std::path::PathBuf;
+use std::process::{Child, Command};
+use std::time::Duration;
+
+use serde::Deserialize;
+use tauri::{Manager, State};
+
+use crate::progress::{self, ProgressStore};
+use crate::settings::SettingsStore;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+const STATUS_TIMEOUT: Duration = Duration::from_secs(2);
+/// Positions closer than this to the start aren't worth resuming.
+const MIN_RESUME_SECS: f64 = 5.0;
+
+#[derive(Deserialize)]
+struct VlcStatus {
+    #[serde(default)]
+    state: String,
+    /// Seconds.
+    #[serde(default)]
+    time: f64,
+    /// Seconds; 0 for live streams.
+    #[serde(default)]
+    length: f64,
+}
+
+/// Tries PATH, then common install paths on Windows and macOS.
+pub fn find_vlc() -> Result<String, String> {
+    let vlc_path = if cfg!(target_os = "windows") {
+        // Try PATH first, then common install locations
+        let path_vlc = which::which("vlc").ok();
+        let path_vlc_exe = which::which("vlc.exe").ok();
+        path_vlc
+            .or(path_vlc_exe)
+            .map(|p| p.to_string_lossy().into_owned())
+            .or_else(|| {
+                let pf = std::env::var("ProgramFiles").ok()?;
+                let path = PathBuf::from(pf)
+                    .join("VideoLAN")
+                    .join("VLC")
+                    .join("vlc.exe");
+                path.exists().then(|| path.to_string_lossy().into_owned())
+            })
+            .or_else(|| {
+                let pf = std::env::var("ProgramFiles(x86)").ok()?;
+                let path = PathBuf::from(pf)
+                    .join("VideoLAN")
+                    .join("VLC")
+                    .join("vlc.exe");
+                path.exists().then(|| path.to_string_lossy().into_owned())
+            })
+    } else if cfg!(target_os = "macos") {
+        which::which("vlc")
+            .ok()
+            .map(|p| p.to_string_lossy().into_owned())
+            .or_else(|| {
+                let p = PathBuf::from("/Applications/VLC.app/Contents/MacOS/VLC");
+                p.exists().then(|| p.to_string_lossy().into_owned())
+            })
+    } else {
+        which::which("vlc")
+            .ok()
+            .map(|p| p.to_string_lossy().into_owned())
+    };
+
+    vlc_path.ok_or_else(|| {
+        "VLC not found. Install VLC and ensure it is in your PATH or in Program Files (Windows).".to_string()
+    })
+}
+
+fn free_port() -> Result<u16, String> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).map_err(|e| e.to_string())?;
+    listener
+        .local_addr()
+        .map(|a| a.port())
+        .map_err(|e| e.to_string())
+}
+
+/// Frees what VLC played through as `holder` once it exits: its relay and connection.
+fn release(app: &tauri::AppHandle, holder: &str) {
+    crate::proxy::release(app, holder);
+    crate::connections::release(holder);
+}
+
+/// Polls VLC's status until the process exits, recording progress for VOD content.
+async fn track(
+    app: tauri::AppHandle,
+    client: reqwest::Client,
+    mut child: Child,
+    port: u16,
+    password: String,
+    key: String,
+    holder: Option<String>,
+) {
+    let url = format!("http://127.0.0.1:{}/requests/status.json", port);
+    loop {
+        tokio::time::sleep(POLL_INTERVAL).await;
+        if !matches!(child.try_wait(), Ok(None)) {
+            break;
+        }
+        let Ok(resp) = client
+            .get(&url)
+            .basic_auth("", Some(&password))
+            .send()
+            .await
+        else {
+            // The interface isn't up yet while VLC starts
+            continue;
+        };
+        let Ok(status) = resp.json::<VlcStatus>().await else {
+            continue;
+        };
+        if status.state == "stopped" || status.length <= 0.0 {
+            continue;
+        }
+        let store = app.state::<ProgressStore>();
+        let _ = progress::record(&app, &store, &key, status.time, Some(status.length), None);
+    }
+    if let Some(holder) = holder {
+        release(&app, &holder);
+    }
+}
+
+/// Opens the given URL in VLC. With `content_key`, resumes from the saved position and tracks progress while VLC plays.
+/// `subtitle_path` is passed as an external subtitle file. Audio goes to the profile's output
+/// device when VLC can address it.
+#[tauri::command]
+pub fn open_in_vlc(
+    app: tauri::AppHandle,
+    progress: State<'_, ProgressStore>,
+    settings: State<'_, SettingsStore>,
+    url: String,
+    content_key: Option<String>,
+    subtitle_path: Option<String>,
+    profile_id: Option<String>,
+) -> Result<(), String> {
+    launch(
+        &app,
+        &progress,
+        &settings,
+        &url,
+        content_key,
+        subtitle_path,
+        profile_id,
+        None,
+    )
+}
+
+/// Like `open_in_vlc`. With `holder`, the relay and connection it owns (see
+/// `transcode::Source`) are freed when VLC exits or fails to start.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn launch(
+    app: &tauri::AppHandle,
+    progress: &ProgressStore,
+    settings: &SettingsStore,
+    url: &str,
+    content_key: Option<String>,
+    subtitle_path: Option<String>,
+    profile_id: Option<String>,
+    holder: Option<String>,
+) -> Result<(), String> {
+    let spawned = spawn(
+        progress,
+        settings,
+        url,
+        content_key,
+        subtitle_path,
+        profile_id,
+    );
+    let (child, tracking) = match spawned {
+        Ok(spawned) => spawned,
+        Err(e) => {
+            if let Some(holder) = &holder {
+                release(app, holder);
+            }
+            return Err(e);
+        }
+    };
+    match (tracking, holder) {
+        (Some((client, port, password, key)), holder) => {
+            tauri::async_runtime::spawn(track(
+                app.clone(),
+                client,
+                child,
+                port,
+                password,
+                key,
+                holder,
+            ));
+        }
+        (None, Some(holder)) => {
+            let app = app.clone();
+            let mut child = child;
+            std::thread::spawn(move || {
+                let _ = child.wait();
+                release(&app, &holder);
+            });
+        }
+        (None, None) => {}
+    }
+    Ok(())
+}
+
+/// VLC's HTTP interface client, port, password and the content key it tracks.
+type Tracking = (reqwest::Client, u16, String, String);
+
+fn spawn(
+    progress: &ProgressStore,
+    settings: &SettingsStore,
+    url: &str,
+    content_key: Option<String>,
+    subtitle_path: Option<String>,
+    profile_id: Option<String>,
+) -> Result<(Child, Option<Tracking>), String> {
+    let vlc_path = find_vlc()?;
+    let mut command = Command::new(&vlc_path);
+    let profile = crate::settings::profile_id(profile_id);
+    if let Some(device) = crate::audio::device_for(settings, &profile) {
+        if !crate::audio::select_for_vlc(&mut command, &device) {
+            tracing::debug!("VLC cannot select audio device {}", device);
+        }
+    }
+    if let Some(subtitle) = subtitle_path {
+        command.arg(format!("--sub-file={}", subtitle));
+    }
+    let tracking = match content_key {
+        Some(key) => {
+            // The interface is on loopback, so no proxy or custom resolver applies
+            let client = crate::http::direct_client(Some(STATUS_TIMEOUT))?;
+            let port = free_port()?;
+            let password = uuid::Uuid::new_v4().simple().to_string();
+            command.args(["--extraintf", "http", "--http-host", "127.0.0.1"]);
+            command.arg(format!("--http-port={}", port));
+            command.arg(format!("--http-password={}", password));
+            let resume = progress
+                .read(|entries| entries.get(&key).map(|e| e.position_secs))
+                .filter(|p| *p > MIN_RESUME_SECS);
+            if let Some(start) = resume {
+                command.arg(format!("--start-time={}", start));
+            }
+            Some((client, port, password, key))
+        }
+        None => None,
+    };
+
+    let child = command
+        .arg(url)
+        .spawn()
+        .map_err(|e| format!("Failed to start VLC: {}", e))?;
+    Ok((child, tracking))
+}
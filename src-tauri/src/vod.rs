@@ -0,0 +1,156 @@
+//! Server-side VOD index: movies and series from Xtream and Emby with the metadata providers
+//! carry (genres, cast and director, mostly sourced from TMDB by the panels), loaded once per
+//! server into memory through the offline cache.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use crate::cache::CacheKind;
+use crate::catalog::text;
+use crate::offline;
+use crate::servers::{ServerConfig, ServerKind};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VodKind {
+    Movie,
+    Series,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VodItem {
+    pub server_id: String,
+    /// Xtream stream/series id or Emby item id.
+    pub id: String,
+    pub kind: VodKind,
+    pub name: String,
+    /// Provider category, e.g. "Action Movies".
+    pub category: Option<String>,
+    pub genres: Vec<String>,
+    pub cast: Vec<String>,
+    pub director: Option<String>,
+    pub year: Option<u32>,
+    /// Out of 10.
+    pub rating: Option<f32>,
+    pub image: Option<String>,
+}
+
+impl VodItem {
+    /// The progress key the frontend uses for this item (`{serverId}:{kind}:{id}`).
+    pub fn content_key(&self) -> String {
+        let kind = match self.kind {
+            VodKind::Movie => "movie",
+            VodKind::Series => "series",
+        };
+        format!("{}:{}:{}", self.server_id, kind, self.id)
+    }
+}
+
+static INDEX: Mutex<BTreeMap<String, Arc<Vec<VodItem>>>> = Mutex::new(BTreeMap::new());
+
+fn index() -> std::sync::MutexGuard<'static, BTreeMap<String, Arc<Vec<VodItem>>>> {
+    INDEX.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Splits provider lists like `"Action, Drama / Thriller"`.
+pub fn split_list(text: Option<String>) -> Vec<String> {
+    text.unwrap_or_default()
+        .split([',', '/', '|', ';'])
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn rating(value: &serde_json::Value) -> Option<f32> {
+    text(value)?.parse::<f32>().ok().filter(|r| *r > 0.0)
+}
+
+async fn fetch_xtream(server: &ServerConfig) -> Result<Vec<VodItem>, String> {
+    let categories = |list: Vec<serde_json::Value>| -> BTreeMap<String, String> {
+        list.iter()
+            .filter_map(|c| Some((text(&c["category_id"])?, text(&c["category_name"])?)))
+            .collect()
+    };
+    let vod_categories =
+        categories(crate::catalog::xtream_action(server, "get_vod_categories").await?);
+    let series_categories =
+        categories(crate::catalog::xtream_action(server, "get_series_categories").await?);
+    let item = |v: &serde_json::Value, kind: VodKind, id_field: &str| -> Option<VodItem> {
+        let cats = match kind {
+            VodKind::Movie => &vod_categories,
+            VodKind::Series => &series_categories,
+        };
+        Some(VodItem {
+            server_id: server.id.clone(),
+            id: text(&v[id_field])?,
+            kind,
+            name: text(&v["name"]).unwrap_or_default(),
+            category: text(&v["category_id"]).and_then(|id| cats.get(&id).cloned()),
+            // Movie lists carry these on some panels only; series lists always do
+            genres: split_list(text(&v["genre"])),
+            cast: split_list(text(&v["cast"])),
+            director: text(&v["director"]),
+            year: text(&v["year"])
+                .or_else(|| text(&v["releaseDate"]))
+                .and_then(|y| y.get(..4)?.parse().ok()),
+            rating: rating(&v["rating"]),
+            image: text(&v["stream_icon"]).or_else(|| text(&v["cover"])),
+        })
+    };
+    let mut items: Vec<VodItem> = crate::catalog::xtream_action(server, "get_vod_streams")
+        .await?
+        .iter()
+        .filter_map(|v| item(v, VodKind::Movie, "stream_id"))
+        .collect();
+    items.extend(
+        crate::catalog::xtream_action(server, "get_series")
+            .await?
+            .iter()
+            .filter_map(|v| item(v, VodKind::Series, "series_id")),
+    );
+    Ok(items)
+}
+
+/// The server's VOD items, loaded on first use (from the offline copy when unreachable).
+/// Backends without VOD have none.
+pub async fn items(
+    app: &tauri::AppHandle,
+    server: &ServerConfig,
+) -> Result<Arc<Vec<VodItem>>, String> {
+    if let Some(items) = index().get(&server.id) {
+        return Ok(items.clone());
+    }
+    let cached = match server.kind {
+        ServerKind::Xtream => {
+            offline::cached(
+                app,
+                CacheKind::Catalogs,
+                &server.id,
+                "vod",
+                fetch_xtream(server),
+            )
+            .await?
+        }
+        ServerKind::Emby => {
+            offline::cached(
+                app,
+                CacheKind::Catalogs,
+                &server.id,
+                "vod",
+                crate::emby::library(app, &server.id),
+            )
+            .await?
+        }
+        _ => return Ok(Arc::new(Vec::new())),
+    };
+    let items = Arc::new(cached.data);
+    // Offline copies are used for now but fetched again on the next call
+    if !cached.stale {
+        index().insert(server.id.clone(), items.clone());
+    }
+    Ok(items)
+}
@@ -0,0 +1,69 @@
+//! Per-channel volume, persisted in `channel-volume.json`. IPTV channels vary wildly in
+//! loudness, so the player reports the volume the user settles on for a channel and it is
+//! reapplied whenever that channel is tuned again.
+
+use std::collections::BTreeMap;
+
+use tauri::{Manager, State};
+
+use crate::remote::PlayerCommand;
+use crate::store::JsonStore;
+
+/// Volume (0-100) keyed by `{serverId}:{channelId}`.
+pub type VolumeStore = JsonStore<BTreeMap<String, f64>>;
+
+pub fn open(app: &tauri::AppHandle) -> VolumeStore {
+    JsonStore::open(app, "channel-volume.json")
+}
+
+fn key(server_id: &str, channel_id: &str) -> String {
+    format!("{}:{}", server_id, channel_id)
+}
+
+/// Sets window `label` to the volume saved for a channel, if any.
+pub fn reapply(app: &tauri::AppHandle, label: &str, server_id: &str, channel_id: &str) {
+    let level = app
+        .state::<VolumeStore>()
+        .read(|levels| levels.get(&key(server_id, channel_id)).copied());
+    if let Some(level) = level {
+        if let Err(e) =
+            crate::remote::send_player_command(app, label, &PlayerCommand::Volume { level })
+        {
+            tracing::debug!("Could not restore the channel volume: {}", e);
+        }
+    }
+}
+
+/// Remembers `level` for the live channel window `label` shows; other media are ignored.
+pub fn report(store: &VolumeStore, label: &str, level: f64) -> Result<(), String> {
+    let Some((server_id, channel_id, _)) = crate::zap::current(label) else {
+        return Ok(());
+    };
+    let key = key(&server_id, &channel_id);
+    let level = level.clamp(0.0, 100.0);
+    // Restoring a level reports it back, which needs no write
+    if store.read(|levels| levels.get(&key) == Some(&level)) {
+        return Ok(());
+    }
+    store.update(|levels| levels.insert(key, level))?;
+    Ok(())
+}
+
+/// Remembers the volume the user chose on the channel window `window_label` is showing.
+#[tauri::command]
+pub fn report_channel_volume(
+    store: State<'_, VolumeStore>,
+    window_label: String,
+    level: f64,
+) -> Result<(), String> {
+    report(&store, &window_label, level)
+}
+
+#[tauri::command]
+pub fn get_channel_volume(
+    store: State<'_, VolumeStore>,
+    server_id: String,
+    channel_id: String,
+) -> Option<f64> {
+    store.read(|levels| levels.get(&key(&server_id, &channel_id)).copied())
+}
@@ -0,0 +1,92 @@
+//! Watch-later list, persisted in `watch-later.json`. Unlike favorites it is a to-do list:
+//! items drop off once they count as watched (see `progress`).
+
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, Manager, State};
+
+use crate::progress::ProgressStore;
+use crate::store::JsonStore;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchLaterItem {
+    /// Progress key of the item, e.g. `{serverId}:movie:{id}`.
+    pub content_key: String,
+    pub title: String,
+    #[serde(default)]
+    pub server_id: Option<String>,
+    #[serde(default)]
+    pub stream_url: Option<String>,
+    #[serde(default)]
+    pub image: Option<String>,
+    /// Unix seconds; set by `add_watch_later`.
+    #[serde(default)]
+    pub added_at: u64,
+}
+
+pub type WatchLaterStore = JsonStore<Vec<WatchLaterItem>>;
+
+pub fn open(app: &tauri::AppHandle) -> WatchLaterStore {
+    JsonStore::open(app, "watch-later.json")
+}
+
+/// Drops an item that has just been watched; emits `watch-later-changed` when it was listed.
+pub fn on_watched(app: &tauri::AppHandle, content_key: &str) {
+    let store = app.state::<WatchLaterStore>();
+    let removed = store.update(|items| {
+        let before = items.len();
+        items.retain(|i| i.content_key != content_key);
+        items.len() != before
+    });
+    match removed {
+        Ok(true) => {
+            let _ = app.emit("watch-later-changed", content_key);
+        }
+        Ok(false) => {}
+        Err(e) => tracing::warn!("Could not update the watch-later list: {}", e),
+    }
+}
+
+/// The list, oldest first, without items watched since they were added.
+pub fn items(store: &WatchLaterStore, progress: &ProgressStore) -> Vec<WatchLaterItem> {
+    let items = store.read(|items| items.clone());
+    progress.read(|entries| {
+        items
+            .into_iter()
+            .filter(|i| !entries.get(&i.content_key).is_some_and(|e| e.watched))
+            .collect()
+    })
+}
+
+/// Adds an item, or refreshes its details when already listed.
+#[tauri::command]
+pub fn add_watch_later(
+    store: State<'_, WatchLaterStore>,
+    mut item: WatchLaterItem,
+) -> Result<(), String> {
+    item.added_at = crate::now_secs();
+    store.update(|items| {
+        if let Some(existing) = items.iter_mut().find(|i| i.content_key == item.content_key) {
+            item.added_at = existing.added_at;
+            *existing = item;
+        } else {
+            items.push(item);
+        }
+    })
+}
+
+#[tauri::command]
+pub fn remove_watch_later(
+    store: State<'_, WatchLaterStore>,
+    content_key: String,
+) -> Result<(), String> {
+    store.update(|items| items.retain(|i| i.content_key != content_key))
+}
+
+#[tauri::command]
+pub fn list_watch_later(
+    store: State<'_, WatchLaterStore>,
+    progress: State<'_, ProgressStore>,
+) -> Vec<WatchLaterItem> {
+    items(&store, &progress)
+}
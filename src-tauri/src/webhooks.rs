@@ -0,0 +1,267 @@
+//! Outgoing webhooks: user-registered URLs receive a JSON POST when playback starts or stops
+//! when recordings start, finish or fail, and when a guide reminder fires. Failed deliveries are retried with backoff and
+//! every attempt is kept in an in-memory delivery log.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::{Manager, State};
+
+use crate::feed::EventFeed;
+use crate::store::JsonStore;
+
+/// Delay before each retry; the first attempt is immediate.
+const RETRY_DELAYS: [Duration; 2] = [Duration::from_secs(5), Duration::from_secs(30)];
+const LOG_CAPACITY: usize = 200;
+
+pub const EVENTS: [&str; 6] = [
+    "playback.started",
+    "playback.stopped",
+    "recording.started",
+    "recording.completed",
+    "recording.failed",
+    "reminder.fired",
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Webhook {
+    #[serde(default)]
+    pub id: String,
+    pub url: String,
+    /// Events to send; empty means all.
+    #[serde(default)]
+    pub events: Vec<String>,
+    /// When set, bodies are signed with HMAC-SHA256 in `X-TvX-Signature`.
+    #[serde(default)]
+    pub secret: Option<String>,
+    #[serde(default = "enabled_default")]
+    pub enabled: bool,
+}
+
+fn enabled_default() -> bool {
+    true
+}
+
+pub type WebhookStore = JsonStore<Vec<Webhook>>;
+
+pub fn open(app: &tauri::AppHandle) -> WebhookStore {
+    JsonStore::open(app, "webhooks.json")
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Delivery {
+    pub webhook_id: String,
+    pub event: String,
+    pub attempt: u32,
+    pub at: u64,
+    /// HTTP status when the receiver answered.
+    pub status: Option<u16>,
+    pub error: Option<String>,
+}
+
+#[derive(Default)]
+pub struct DeliveryLog {
+    entries: Mutex<VecDeque<Delivery>>,
+}
+
+impl DeliveryLog {
+    fn push(&self, delivery: Delivery) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if entries.len() == LOG_CAPACITY {
+            entries.pop_front();
+        }
+        entries.push_back(delivery);
+    }
+}
+
+/// Maps an event-feed message to a webhook event name.
+fn webhook_event(feed_event: &str, payload: &serde_json::Value) -> Option<&'static str> {
+    match (feed_event, payload["status"].as_str()) {
+        ("video-window-opened", _) => Some("playback.started"),
+        ("video-window-closed", _) => Some("playback.stopped"),
+        ("hdhomerun-recording" | "recording", Some("started")) => Some("recording.started"),
+        ("hdhomerun-recording" | "recording", Some("finished")) => Some("recording.completed"),
+        ("hdhomerun-recording" | "recording", Some("failed")) => Some("recording.failed"),
+        ("epg-reminder", _) => Some("reminder.fired"),
+        _ => None,
+    }
+}
+
+fn hmac_sha256(key: &[u8], message: &[u8]) -> String {
+    const BLOCK: usize = 64;
+    let mut key_block = [0u8; BLOCK];
+    if key.len() > BLOCK {
+        key_block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        key_block[..key.len()].copy_from_slice(key);
+    }
+    let pad = |byte: u8| key_block.iter().map(|k| k ^ byte).collect::<Vec<u8>>();
+    let inner = Sha256::new()
+        .chain_update(pad(0x36))
+        .chain_update(message)
+        .finalize();
+    let outer = Sha256::new()
+        .chain_update(pad(0x5c))
+        .chain_update(inner)
+        .finalize();
+    outer.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+async fn post(hook: &Webhook, event: &str, body: &[u8]) -> Result<u16, String> {
+    let mut req = crate::http::direct_client(Some(Duration::from_secs(15)))?
+        .post(&hook.url)
+        .header("Content-Type", "application/json")
+        .header("X-TvX-Event", event)
+        .body(body.to_vec());
+    if let Some(secret) = hook.secret.as_deref().filter(|s| !s.is_empty()) {
+        req = req.header(
+            "X-TvX-Signature",
+            format!("sha256={}", hmac_sha256(secret.as_bytes(), body)),
+        );
+    }
+    let resp = crate::http::send(req).await.map_err(|e| e.to_string())?;
+    Ok(resp.status().as_u16())
+}
+
+/// Delivers `data` as `event` to one webhook, retrying failures and logging every attempt.
+async fn deliver(app: tauri::AppHandle, hook: Webhook, event: String, data: serde_json::Value) {
+    let body = serde_json::json!({
+        "event": event,
+        "timestamp": crate::now_secs(),
+        "data": data,
+    })
+    .to_string();
+    for attempt in 0..=RETRY_DELAYS.len() {
+        if let Some(delay) = attempt.checked_sub(1).map(|i| RETRY_DELAYS[i]) {
+            tokio::time::sleep(delay).await;
+        }
+        let result = post(&hook, &event, body.as_bytes()).await;
+        let ok = matches!(result, Ok(status) if (200..300).contains(&status));
+        app.state::<DeliveryLog>().push(Delivery {
+            webhook_id: hook.id.clone(),
+            event: event.clone(),
+            attempt: attempt as u32 + 1,
+            at: crate::now_secs(),
+            status: result.as_ref().ok().copied(),
+            error: result.err(),
+        });
+        if ok {
+            return;
+        }
+        tracing::warn!(
+            webhook = %hook.id,
+            attempt = attempt + 1,
+            "Webhook delivery of {} to {} failed",
+            event,
+            reqwest::Url::parse(&hook.url)
+                .ok()
+                .and_then(|u| u.host_str().map(str::to_string))
+                .unwrap_or_default()
+        );
+    }
+}
+
+fn dispatch(app: &tauri::AppHandle, event: &str, data: serde_json::Value) {
+    let hooks: Vec<Webhook> = app.state::<WebhookStore>().read(|hooks| {
+        hooks
+            .iter()
+            .filter(|h| h.enabled && (h.events.is_empty() || h.events.iter().any(|e| e == event)))
+            .cloned()
+            .collect()
+    });
+    for hook in hooks {
+        tauri::async_runtime::spawn(deliver(app.clone(), hook, event.to_string(), data.clone()));
+    }
+}
+
+/// Subscribes to the event feed and fires matching webhooks.
+pub fn start(app: &tauri::AppHandle) {
+    let events = app.state::<EventFeed>().subscribe();
+    let app = app.clone();
+    std::thread::spawn(move || {
+        for message in events {
+            let Ok(message) = serde_json::from_str::<serde_json::Value>(&message) else {
+                continue;
+            };
+            let feed_event = message["event"].as_str().unwrap_or_default();
+            if let Some(event) = webhook_event(feed_event, &message["payload"]) {
+                dispatch(&app, event, message["payload"].clone());
+            }
+        }
+    });
+}
+
+#[tauri::command]
+pub fn list_webhooks(store: State<'_, WebhookStore>) -> Vec<Webhook> {
+    store.read(|hooks| hooks.clone())
+}
+
+#[tauri::command]
+pub fn save_webhook(
+    store: State<'_, WebhookStore>,
+    mut webhook: Webhook,
+) -> Result<Webhook, String> {
+    let url = reqwest::Url::parse(&webhook.url).map_err(|e| format!("Invalid URL: {}", e))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err("Webhook URLs must be http or https".to_string());
+    }
+    if let Some(unknown) = webhook
+        .events
+        .iter()
+        .find(|e| !EVENTS.contains(&e.as_str()))
+    {
+        return Err(format!("Unknown webhook event: {}", unknown));
+    }
+    if webhook.id.is_empty() {
+        webhook.id = uuid::Uuid::new_v4().to_string();
+    }
+    let saved = webhook.clone();
+    store.update(
+        |hooks| match hooks.iter_mut().find(|h| h.id == webhook.id) {
+            Some(existing) => *existing = webhook,
+            None => hooks.push(webhook),
+        },
+    )?;
+    Ok(saved)
+}
+
+#[tauri::command]
+pub fn remove_webhook(store: State<'_, WebhookStore>, webhook_id: String) -> Result<(), String> {
+    store.update(|hooks| hooks.retain(|h| h.id != webhook_id))
+}
+
+/// Sends a `test` event to one webhook (with retries, like real events).
+#[tauri::command]
+pub fn test_webhook(
+    app: tauri::AppHandle,
+    store: State<'_, WebhookStore>,
+    webhook_id: String,
+) -> Result<(), String> {
+    let hook = store
+        .read(|hooks| hooks.iter().find(|h| h.id == webhook_id).cloned())
+        .ok_or_else(|| format!("Unknown webhook: {}", webhook_id))?;
+    tauri::async_runtime::spawn(deliver(
+        app,
+        hook,
+        "test".to_string(),
+        serde_json::json!({ "message": "Test delivery from TvX" }),
+    ));
+    Ok(())
+}
+
+/// Recent delivery attempts, newest first.
+#[tauri::command]
+pub fn list_webhook_deliveries(log: State<'_, DeliveryLog>) -> Vec<Delivery> {
+    log.entries
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .rev()
+        .cloned()
+        .collect()
+}
@@ -0,0 +1,129 @@
+//! Server side of RFC 6455 WebSockets, just enough for pushing JSON text messages to remote
+//! clients: handshake, unfragmented text frames out, ping/pong and close in. The reader
+//! answers pings on the same connection the messages go out on, so every frame is written
+//! under the connection's lock to keep frames from interleaving.
+
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::sync::{Arc, Mutex};
+
+use base64::Engine;
+use sha1::{Digest, Sha1};
+
+use crate::httpd::{self, Request};
+
+const HANDSHAKE_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+const OP_TEXT: u8 = 0x1;
+const OP_CLOSE: u8 = 0x8;
+const OP_PING: u8 = 0x9;
+const OP_PONG: u8 = 0xA;
+
+/// Client frames we accept at most; clients only send control frames and small messages.
+const MAX_INCOMING: u64 = 64 * 1024;
+
+pub fn is_upgrade(req: &Request) -> bool {
+    req.header("upgrade")
+        .is_some_and(|u| u.eq_ignore_ascii_case("websocket"))
+}
+
+/// Completes the opening handshake for an upgrade request.
+pub fn accept(stream: &mut TcpStream, req: &Request) -> Result<(), String> {
+    let Some(key) = req.header("sec-websocket-key") else {
+        httpd::respond_error(stream, 400, "Missing Sec-WebSocket-Key");
+        return Err("Missing Sec-WebSocket-Key".to_string());
+    };
+    let digest = Sha1::digest(format!("{}{}", key, HANDSHAKE_GUID).as_bytes());
+    let accept = base64::engine::general_purpose::STANDARD.encode(digest);
+    let response = format!(
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        accept
+    );
+    stream
+        .write_all(response.as_bytes())
+        .map_err(|e| e.to_string())
+}
+
+fn write_frame(stream: &Mutex<TcpStream>, opcode: u8, payload: &[u8]) -> std::io::Result<()> {
+    let mut frame = vec![0x80 | opcode];
+    match payload.len() {
+        len @ 0..=125 => frame.push(len as u8),
+        len @ 126..=0xFFFF => {
+            frame.push(126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    stream
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .write_all(&frame)
+}
+
+pub fn send_text(stream: &Mutex<TcpStream>, text: &str) -> std::io::Result<()> {
+    write_frame(stream, OP_TEXT, text.as_bytes())
+}
+
+pub fn send_ping(stream: &Mutex<TcpStream>) -> std::io::Result<()> {
+    write_frame(stream, OP_PING, b"")
+}
+
+/// Reads client frames from `stream` until the client closes or the connection drops,
+/// answering pings through `writer`. Text messages are passed to `on_text`.
+pub fn read_loop(
+    mut stream: TcpStream,
+    writer: Arc<Mutex<TcpStream>>,
+    mut on_text: impl FnMut(&str),
+) {
+    loop {
+        let mut head = [0u8; 2];
+        if stream.read_exact(&mut head).is_err() {
+            return;
+        }
+        let opcode = head[0] & 0x0F;
+        let masked = head[1] & 0x80 != 0;
+        let mut len = u64::from(head[1] & 0x7F);
+        if len == 126 {
+            let mut ext = [0u8; 2];
+            if stream.read_exact(&mut ext).is_err() {
+                return;
+            }
+            len = u64::from(u16::from_be_bytes(ext));
+        } else if len == 127 {
+            let mut ext = [0u8; 8];
+            if stream.read_exact(&mut ext).is_err() {
+                return;
+            }
+            len = u64::from_be_bytes(ext);
+        }
+        if len > MAX_INCOMING {
+            return;
+        }
+        let mut mask = [0u8; 4];
+        if masked && stream.read_exact(&mut mask).is_err() {
+            return;
+        }
+        let mut payload = vec![0u8; len as usize];
+        if stream.read_exact(&mut payload).is_err() {
+            return;
+        }
+        if masked {
+            for (i, b) in payload.iter_mut().enumerate() {
+                *b ^= mask[i % 4];
+            }
+        }
+        match opcode {
+            OP_CLOSE => {
+                let _ = write_frame(&writer, OP_CLOSE, &payload);
+                return;
+            }
+            OP_PING if write_frame(&writer, OP_PONG, &payload).is_err() => return,
+            OP_TEXT => on_text(&String::from_utf8_lossy(&payload)),
+            _ => {}
+        }
+    }
+}
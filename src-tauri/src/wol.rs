@@ -0,0 +1,92 @@
+//! Wake-on-LAN for home servers that sleep, with automatic wake-and-retry on connect failures.
+
+use std::net::{Ipv4Addr, UdpSocket};
+use std::time::{Duration, Instant};
+
+use tauri::State;
+
+use crate::http::SendError;
+use crate::servers::{self, ServerConfig, ServerStore};
+
+/// How long to keep retrying after sending a magic packet. Most NAS/HTPCs resume within this.
+const WAKE_TIMEOUT: Duration = Duration::from_secs(45);
+const RETRY_INTERVAL: Duration = Duration::from_secs(3);
+
+/// Parses `AA:BB:CC:DD:EE:FF`, `AA-BB-...` or `AABBCCDDEEFF`.
+pub fn parse_mac(mac: &str) -> Result<[u8; 6], String> {
+    let hex: String = mac
+        .chars()
+        .filter(|c| !matches!(c, ':' | '-' | '.' | ' '))
+        .collect();
+    let invalid = || format!("Invalid MAC address: {}", mac);
+    // Checked first: byte slicing would panic on a multi-byte character
+    if hex.len() != 12 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(invalid());
+    }
+    let mut bytes = [0u8; 6];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).map_err(|_| invalid())?;
+    }
+    Ok(bytes)
+}
+
+/// Broadcasts a magic packet (6 x 0xFF followed by the MAC 16 times) on UDP ports 9 and 7.
+pub fn send_magic_packet(mac: &str) -> Result<(), String> {
+    let mac = parse_mac(mac)?;
+    let mut packet = vec![0xFF; 6];
+    for _ in 0..16 {
+        packet.extend_from_slice(&mac);
+    }
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).map_err(|e| e.to_string())?;
+    socket.set_broadcast(true).map_err(|e| e.to_string())?;
+    for port in [9, 7] {
+        socket
+            .send_to(&packet, (Ipv4Addr::BROADCAST, port))
+            .map_err(|e| format!("Failed to send wake packet: {}", e))?;
+    }
+    Ok(())
+}
+
+/// Sends `req`. If the server can't be reached and has a MAC address, wakes it and keeps
+/// retrying until it answers or `WAKE_TIMEOUT` passes. Requests with streaming bodies
+/// can't be replayed and are sent once. The retries bypass the circuit breaker, which would
+/// otherwise open while the server is still booting.
+pub async fn send_waking(
+    server: &ServerConfig,
+    req: reqwest::RequestBuilder,
+) -> Result<reqwest::Response, SendError> {
+    let Some(mac) = server.mac_address.as_deref().filter(|m| !m.is_empty()) else {
+        return crate::http::send(req).await;
+    };
+    let Some(retry) = req.try_clone() else {
+        return crate::http::send(req).await;
+    };
+    let err = match crate::http::send(req).await {
+        Err(e) if e.is_unreachable() => e,
+        other => return other,
+    };
+    if send_magic_packet(mac).is_err() {
+        return Err(err);
+    }
+    let deadline = Instant::now() + WAKE_TIMEOUT;
+    loop {
+        tokio::time::sleep(RETRY_INTERVAL).await;
+        let Some(attempt) = retry.try_clone() else {
+            return Err(err);
+        };
+        match crate::http::send_ignoring_circuit(attempt).await {
+            Err(e) if e.is_unreachable() && Instant::now() < deadline => continue,
+            other => return other,
+        }
+    }
+}
+
+#[tauri::command]
+pub fn wake_server(store: State<'_, ServerStore>, server_id: String) -> Result<(), String> {
+    let server = servers::get(&store, &server_id)?;
+    let mac = server
+        .mac_address
+        .filter(|m| !m.is_empty())
+        .ok_or_else(|| format!("No MAC address is set for {}", server.name))?;
+    send_magic_packet(&mac)
+}
@@ -0,0 +1,359 @@
+//! Channel surfing in a video window. `zap` steps to the next or previous channel within the
+//! tuned channel's group or the server's favorites and pushes `change-stream` (URL and channel
+//! details) to the window, which swaps the stream without reopening; mpv windows are loaded
+//! directly. The new channel is prepared and served like a first open (see
+//! `transcode::prepare`), replacing everything the window played before. Each window
+//! remembers the channel it came from, so `zap_back` flips between two.
+//! `retune` reloads the current channel, for resume after sleep.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, Manager, State};
+
+use crate::catalog::CatalogChannel;
+use crate::favorites::FavoriteStore;
+use crate::history::{WatchKind, WatchStart};
+use crate::mpv::MpvState;
+use crate::proxy::ProxyState;
+use crate::remote::PlayerCommand;
+use crate::servers::ServerStore;
+use crate::settings::SettingsStore;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ZapDirection {
+    Next,
+    Previous,
+}
+
+/// Which channels zapping cycles through.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ZapScope {
+    /// The tuned channel's group, or every channel when it has none.
+    #[default]
+    Group,
+    Favorites,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeStream {
+    pub server_id: String,
+    pub channel_id: String,
+    pub name: String,
+    pub number: Option<String>,
+    pub group: Option<String>,
+    pub logo: Option<String>,
+    pub stream_url: String,
+}
+
+struct Tuned {
+    server_id: String,
+    channel_id: String,
+    scope: ZapScope,
+    /// Server and channel tuned before this one.
+    previous: Option<(String, String)>,
+}
+
+static TUNED: Mutex<BTreeMap<String, Tuned>> = Mutex::new(BTreeMap::new());
+
+fn tuned() -> std::sync::MutexGuard<'static, BTreeMap<String, Tuned>> {
+    TUNED.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+pub fn release(label: &str) {
+    tuned().remove(label);
+}
+
+/// The window's channel: where it last zapped to, else the live session it reported.
+pub(crate) fn current(label: &str) -> Option<(String, String, Option<ZapScope>)> {
+    if let Some(t) = tuned().get(label) {
+        return Some((t.server_id.clone(), t.channel_id.clone(), Some(t.scope)));
+    }
+    let (server_id, channel_id) = crate::history::live_channel(label)?;
+    Some((server_id, channel_id, None))
+}
+
+/// The channels to cycle through, in list order.
+fn lineup<'a>(
+    channels: &'a [CatalogChannel],
+    favorites: &[String],
+    scope: ZapScope,
+    channel_id: &str,
+) -> Vec<&'a CatalogChannel> {
+    match scope {
+        ZapScope::Favorites => favorites
+            .iter()
+            .filter_map(|id| channels.iter().find(|c| &c.id == id && !c.hidden))
+            .collect(),
+        ZapScope::Group => {
+            let group = channels
+                .iter()
+                .find(|c| c.id == channel_id)
+                .and_then(|c| c.group.as_deref());
+            channels
+                .iter()
+                .filter(|c| !c.hidden && (group.is_none() || c.group.as_deref() == group))
+                .collect()
+        }
+    }
+}
+
+/// Switches a window to `channel` of `server_id`, remembering it as tuned.
+async fn tune(
+    app: &tauri::AppHandle,
+    label: &str,
+    server_id: &str,
+    channel: &CatalogChannel,
+    scope: ZapScope,
+) -> Result<ChangeStream, String> {
+    let server = crate::servers::get(&app.state::<ServerStore>(), server_id)?;
+    let previous = current(label)
+        .map(|(server_id, channel_id, _)| (server_id, channel_id))
+        .filter(|(s, c)| s != server_id || c != &channel.id);
+    let url = crate::catalog::stream_url(app, &server, &channel.id).await?;
+    let proxy = app.state::<ProxyState>();
+    let settings = app.state::<SettingsStore>();
+    // Prepared like a first open, taking over the connection of the window's current stream
+    let source = crate::transcode::prepare(
+        app,
+        &proxy,
+        &settings,
+        Some(&server),
+        &channel.name,
+        url,
+        None,
+        false,
+        Some(label),
+    )
+    .await?;
+    let mpv = app.state::<MpvState>();
+    let served = if mpv.has(label) {
+        Ok((source.url.clone(), None))
+    } else {
+        crate::transcode::webview_stream(app, &proxy, &settings, &source, label).await
+    };
+    let (url, session) = match served {
+        Ok(served) => served,
+        Err(e) => {
+            let _ = source.finish(&proxy, Err(e.clone()));
+            return Err(e);
+        }
+    };
+    // What the window played before stops, ffmpeg sessions included
+    proxy.stop_owned_by(label);
+    crate::release_playback(app, label);
+    if let Some(session) = &session {
+        proxy.set_owner(&session.id, label);
+    }
+    source.finish(&proxy, Ok(label.to_string()))?;
+    let change = ChangeStream {
+        server_id: server.id.clone(),
+        channel_id: channel.id.clone(),
+        name: channel.name.clone(),
+        number: channel.number.clone(),
+        group: channel.group.clone(),
+        logo: channel.logo.clone(),
+        stream_url: url,
+    };
+    if mpv.has(label) {
+        mpv.control(
+            label,
+            &PlayerCommand::Load {
+                url: change.stream_url.clone(),
+                content_key: None,
+            },
+        )?;
+    }
+    app.emit_to(label, "change-stream", change.clone())
+        .map_err(|e| e.to_string())?;
+    if let Some(window) = app.get_webview_window(label) {
+        let _ = window.set_title(&channel.name);
+    }
+    tuned().insert(
+        label.to_string(),
+        Tuned {
+            server_id: server.id.clone(),
+            channel_id: channel.id.clone(),
+            scope,
+            previous,
+        },
+    );
+    crate::history::start_watching(
+        app.clone(),
+        label.to_string(),
+        WatchStart {
+            server_id: server.id,
+            kind: WatchKind::Live,
+            title: channel.name.clone(),
+            content_key: None,
+            channel_id: Some(channel.id.clone()),
+            channel_name: Some(channel.name.clone()),
+            genre: None,
+        },
+    );
+    Ok(change)
+}
+
+/// Switches a window to channel `channel_id` of `server_id`, e.g. for a remote client.
+pub(crate) async fn tune_channel(
+    app: &tauri::AppHandle,
+    label: &str,
+    server_id: &str,
+    channel_id: &str,
+) -> Result<ChangeStream, String> {
+    let server = crate::servers::get(&app.state::<ServerStore>(), server_id)?;
+    let channel = crate::catalog::channels(app, &server)
+        .await?
+        .1
+        .iter()
+        .find(|c| c.id == channel_id)
+        .cloned()
+        .ok_or_else(|| "The channel is not in the channel list".to_string())?;
+    let scope = current(label).and_then(|(_, _, scope)| scope);
+    tune(app, label, server_id, &channel, scope.unwrap_or_default()).await
+}
+
+/// Tunes the window to its current channel again with a freshly resolved URL, e.g. when the
+/// old one's token or connection died while the machine slept.
+pub(crate) async fn retune(app: &tauri::AppHandle, label: &str) -> Result<ChangeStream, String> {
+    let (server_id, channel_id, scope) =
+        current(label).ok_or_else(|| "Window is not playing a live channel".to_string())?;
+    let channel = crate::catalog::lookup(&server_id, &channel_id)
+        .ok_or_else(|| "The channel is no longer in the channel list".to_string())?;
+    let previous = tuned().get(label).and_then(|t| t.previous.clone());
+    let change = tune(app, label, &server_id, &channel, scope.unwrap_or_default()).await?;
+    // Re-tuning the same channel must not lose the one to flip back to
+    if let Some(t) = tuned().get_mut(label) {
+        t.previous = previous;
+    }
+    Ok(change)
+}
+
+/// Steps the window to the next or previous channel, wrapping around the list. `scope`
+/// defaults to the one last zapped in, else the channel's group.
+#[tauri::command]
+pub async fn zap(
+    app: tauri::AppHandle,
+    favorites: State<'_, FavoriteStore>,
+    window_label: String,
+    direction: ZapDirection,
+    scope: Option<ZapScope>,
+) -> Result<ChangeStream, String> {
+    let (server_id, channel_id, last_scope) =
+        current(&window_label).ok_or_else(|| "Window is not playing a live channel".to_string())?;
+    let scope = scope.or(last_scope).unwrap_or_default();
+    let server = crate::servers::get(&app.state::<ServerStore>(), &server_id)?;
+    let channels = crate::catalog::channels(&app, &server).await?.1;
+    let favorites = favorites.read(|f| f.get(&server_id).cloned().unwrap_or_default());
+    let lineup = lineup(&channels, &favorites, scope, &channel_id);
+    if lineup.is_empty() {
+        return Err(match scope {
+            ZapScope::Favorites => "No favorite channels on this server".to_string(),
+            ZapScope::Group => "No channels to zap to".to_string(),
+        });
+    }
+    // A channel outside the list (e.g. zapping favorites from another channel) starts at its
+    // first entry
+    let len = lineup.len();
+    let index = match lineup.iter().position(|c| c.id == channel_id) {
+        Some(i) if direction == ZapDirection::Next => (i + 1) % len,
+        Some(i) => (i + len - 1) % len,
+        None if direction == ZapDirection::Next => 0,
+        None => len - 1,
+    };
+    let channel = lineup[index].clone();
+    tune(&app, &window_label, &server_id, &channel, scope).await
+}
+
+/// Returns the window to the channel it was on before the current one.
+#[tauri::command]
+pub async fn zap_back(app: tauri::AppHandle, window_label: String) -> Result<ChangeStream, String> {
+    let (previous, scope) = tuned()
+        .get(&window_label)
+        .and_then(|t| Some((t.previous.clone()?, t.scope)))
+        .ok_or_else(|| "No previous channel".to_string())?;
+    let (server_id, channel_id) = previous;
+    let server = crate::servers::get(&app.state::<ServerStore>(), &server_id)?;
+    let channel = crate::catalog::channels(&app, &server)
+        .await?
+        .1
+        .iter()
+        .find(|c| c.id == channel_id)
+        .cloned()
+        .ok_or_else(|| "The previous channel is no longer in the channel list".to_string())?;
+    tune(&app, &window_label, &server_id, &channel, scope).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn channel(id: &str, group: &str) -> CatalogChannel {
+        CatalogChannel {
+            id: id.to_string(),
+            name: format!("Channel {}", id),
+            number: None,
+            group: Some(group.to_string()),
+            logo: None,
+            epg_id: None,
+            radio: false,
+            clearkey: None,
+            hidden: false,
+        }
+    }
+
+    fn ids(lineup: Vec<&CatalogChannel>) -> Vec<&str> {
+        lineup.into_iter().map(|c| c.id.as_str()).collect()
+    }
+
+    #[test]
+    fn servers_saved_from_the_ui_can_be_zapped() {
+        let dir = std::env::temp_dir().join(format!("tvx-zap-{}", uuid::Uuid::new_v4()));
+        let store = ServerStore::at(dir.join("servers.json"));
+        // A `ServerConnection` as the settings store sends it to `save_server`
+        let saved: crate::servers::ServerConfig = serde_json::from_value(serde_json::json!({
+            "id": "1760000000000",
+            "name": "Provider",
+            "url": "http://provider.example:8080",
+            "username": "user",
+            "password": "pass",
+            "lastConnected": 1_760_000_000_000u64,
+            "proxy": null,
+            "maxConnections": null,
+        }))
+        .unwrap();
+        crate::servers::upsert(&store, saved).unwrap();
+        let server = crate::servers::get(&store, "1760000000000").unwrap();
+        assert_eq!(server.kind, crate::servers::ServerKind::Xtream);
+        let channels = [
+            channel("1", "News"),
+            channel("2", "Sport"),
+            channel("3", "News"),
+        ];
+        let favorites = ["3".to_string(), "2".to_string()];
+        assert_eq!(
+            ids(lineup(&channels, &favorites, ZapScope::Group, "3")),
+            ["1", "3"]
+        );
+        assert_eq!(
+            ids(lineup(&channels, &favorites, ZapScope::Favorites, "1")),
+            ["3", "2"]
+        );
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn hidden_channels_are_skipped() {
+        let mut hidden = channel("2", "News");
+        hidden.hidden = true;
+        let channels = [channel("1", "News"), hidden, channel("3", "News")];
+        assert_eq!(
+            ids(lineup(&channels, &[], ZapScope::Group, "1")),
+            ["1", "3"]
+        );
+    }
+}
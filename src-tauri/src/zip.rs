@@ -0,0 +1,119 @@
+//! Minimal ZIP archive writer: deflated entries, no encryption, no ZIP64 (entries and the
+//! archive must stay under 4 GB).
+
+use std::io::Write;
+
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+
+const METHOD_DEFLATE: u16 = 8;
+/// Names are UTF-8 (general purpose bit 11).
+const FLAG_UTF8: u16 = 1 << 11;
+const VERSION: u16 = 20;
+
+struct Entry {
+    name: String,
+    crc: u32,
+    compressed: u32,
+    size: u32,
+    offset: u32,
+}
+
+pub struct ZipWriter {
+    data: Vec<u8>,
+    entries: Vec<Entry>,
+    /// MS-DOS time and date stamped on every entry.
+    dos_time: u16,
+    dos_date: u16,
+}
+
+impl Default for ZipWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ZipWriter {
+    pub fn new() -> Self {
+        let secs = crate::now_secs();
+        let (year, month, day) = crate::civil_date(secs);
+        let dos_date =
+            ((year.clamp(1980, 2107) - 1980) as u16) << 9 | (month as u16) << 5 | day as u16;
+        let dos_time = ((secs / 3600 % 24) as u16) << 11
+            | ((secs / 60 % 60) as u16) << 5
+            | (secs % 60 / 2) as u16;
+        Self {
+            data: Vec::new(),
+            entries: Vec::new(),
+            dos_time,
+            dos_date,
+        }
+    }
+
+    pub fn add(&mut self, name: &str, contents: &[u8]) -> Result<(), String> {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder
+            .write_all(contents)
+            .map_err(|e| format!("Failed to compress {}: {}", name, e))?;
+        let compressed = encoder
+            .finish()
+            .map_err(|e| format!("Failed to compress {}: {}", name, e))?;
+        let too_big = |_| format!("{} is too large for a ZIP archive", name);
+        let entry = Entry {
+            name: name.to_string(),
+            crc: crc32fast::hash(contents),
+            compressed: u32::try_from(compressed.len()).map_err(too_big)?,
+            size: u32::try_from(contents.len()).map_err(too_big)?,
+            offset: u32::try_from(self.data.len()).map_err(too_big)?,
+        };
+        let d = &mut self.data;
+        d.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+        d.extend_from_slice(&VERSION.to_le_bytes());
+        d.extend_from_slice(&FLAG_UTF8.to_le_bytes());
+        d.extend_from_slice(&METHOD_DEFLATE.to_le_bytes());
+        d.extend_from_slice(&self.dos_time.to_le_bytes());
+        d.extend_from_slice(&self.dos_date.to_le_bytes());
+        d.extend_from_slice(&entry.crc.to_le_bytes());
+        d.extend_from_slice(&entry.compressed.to_le_bytes());
+        d.extend_from_slice(&entry.size.to_le_bytes());
+        d.extend_from_slice(&(entry.name.len() as u16).to_le_bytes());
+        d.extend_from_slice(&0u16.to_le_bytes());
+        d.extend_from_slice(entry.name.as_bytes());
+        d.extend_from_slice(&compressed);
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    /// Appends the central directory and returns the archive bytes.
+    pub fn finish(mut self) -> Vec<u8> {
+        let directory_offset = self.data.len() as u32;
+        let d = &mut self.data;
+        for entry in &self.entries {
+            d.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+            d.extend_from_slice(&VERSION.to_le_bytes()); // made by
+            d.extend_from_slice(&VERSION.to_le_bytes()); // needed to extract
+            d.extend_from_slice(&FLAG_UTF8.to_le_bytes());
+            d.extend_from_slice(&METHOD_DEFLATE.to_le_bytes());
+            d.extend_from_slice(&self.dos_time.to_le_bytes());
+            d.extend_from_slice(&self.dos_date.to_le_bytes());
+            d.extend_from_slice(&entry.crc.to_le_bytes());
+            d.extend_from_slice(&entry.compressed.to_le_bytes());
+            d.extend_from_slice(&entry.size.to_le_bytes());
+            d.extend_from_slice(&(entry.name.len() as u16).to_le_bytes());
+            // Extra field, comment, disk number, internal and external attributes
+            d.extend_from_slice(&[0u8; 12]);
+            d.extend_from_slice(&entry.offset.to_le_bytes());
+            d.extend_from_slice(entry.name.as_bytes());
+        }
+        let directory_size = d.len() as u32 - directory_offset;
+        let count = self.entries.len() as u16;
+        d.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+        d.extend_from_slice(&[0u8; 4]); // disk numbers
+        d.extend_from_slice(&count.to_le_bytes());
+        d.extend_from_slice(&count.to_le_bytes());
+        d.extend_from_slice(&directory_size.to_le_bytes());
+        d.extend_from_slice(&directory_offset.to_le_bytes());
+        d.extend_from_slice(&0u16.to_le_bytes()); // comment length
+        self.data
+    }
+}
@@ -1,6 +1,7 @@
 import { lazy, Suspense } from 'react';
 import { BrowserRouter, Routes, Route, Navigate } from 'react-router-dom';
 import { Layout } from './components/Layout';
+import { LockScreen } from './components/LockScreen';
 import { useAppStore } from './store';
 import './App.css';
 
@@ -36,6 +37,7 @@ function PageFallback() {
 function App() {
   return (
     <BrowserRouter>
+      <LockScreen />
       <Suspense fallback={<PageFallback />}>
         <Routes>
           {/* Public routes */}
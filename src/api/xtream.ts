@@ -1,5 +1,6 @@
 // Xtream Codes API Service
 
+import { invoke } from '@tauri-apps/api/core';
 import type {
   ServerConnection,
   AuthResponse,
@@ -28,6 +29,33 @@ export class XtreamApiError extends Error {
   }
 }
 
+interface FetchResponse {
+  status: number;
+  body: string;
+  // Set when the server was unreachable and this is the last cached copy
+  stale: boolean;
+  fetchedAt?: number;
+}
+
+// Requests go through the backend so the server's (or the global) proxy and resolver apply
+function backendFetch(url: string, server: ServerConnection, signal: AbortSignal): Promise<Response> {
+  return new Promise((resolve, reject) => {
+    const onAbort = () => reject(new DOMException('Aborted', 'AbortError'));
+    signal.addEventListener('abort', onAbort, { once: true });
+    invoke<FetchResponse>('http_fetch', { url, server })
+      .then(({ status, body, stale, fetchedAt }) => {
+        // Responses with these statuses must not carry a body
+        const empty = status === 204 || status === 304;
+        const headers: Record<string, string> = {};
+        if (stale) headers['X-TvX-Stale'] = '1';
+        if (fetchedAt) headers['X-TvX-Fetched-At'] = String(fetchedAt);
+        resolve(new Response(empty ? null : body, { status, headers }));
+      })
+      .catch((err) => reject(new Error(String(err))))
+      .finally(() => signal.removeEventListener('abort', onAbort));
+  });
+}
+
 // Default timeout in milliseconds
 const DEFAULT_TIMEOUT = 30000;
 
@@ -84,19 +112,12 @@ export class XtreamApi {
   }
 
   // Fetch with timeout and retry logic
-  private async fetchWithTimeout(
-    url: string,
-    options: RequestInit = {},
-    retryCount: number = 0
-  ): Promise<Response> {
+  private async fetchWithTimeout(url: string, retryCount: number = 0): Promise<Response> {
     this.abortController = new AbortController();
     const timeoutId = setTimeout(() => this.abortController?.abort(), this.config.timeout);
 
     try {
-      const response = await fetch(url, {
-        ...options,
-        signal: this.abortController.signal,
-      });
+      const response = await backendFetch(url, this.server, this.abortController.signal);
 
       clearTimeout(timeoutId);
 
@@ -121,16 +142,16 @@ export class XtreamApi {
       if (err.name === 'AbortError') {
         if (retryCount < this.config.retries) {
           await this.delay(this.config.retryDelay);
-          return this.fetchWithTimeout(url, options, retryCount + 1);
+          return this.fetchWithTimeout(url, retryCount + 1);
         }
         throw new XtreamApiError('Request timeout', undefined, true);
       }
 
       // Handle network errors
-      if (err.message.includes('fetch') || err.message.includes('network')) {
+      if (/fetch|network/i.test(err.message)) {
         if (retryCount < this.config.retries) {
           await this.delay(this.config.retryDelay);
-          return this.fetchWithTimeout(url, options, retryCount + 1);
+          return this.fetchWithTimeout(url, retryCount + 1);
         }
         throw new XtreamApiError('Network error: Unable to connect to server', undefined, false, true);
       }
@@ -26,8 +26,9 @@ export function ContentGrid({ type, items, loading, onItemClick }: ContentGridPr
   const serverFavorites = favorites[serverId]?.[type] || [];
   const { addToWatchHistory } = useSettingsStore();
 
+  // reuse: false opens a new video window even when the setting reuses the newest one
   const handleItemClick = useCallback(
-    async (item: Channel | Movie | Series) => {
+    async (item: Channel | Movie | Series, reuse?: boolean) => {
       if (onItemClick) {
         onItemClick(item);
         return;
@@ -38,10 +39,25 @@ export function ContentGrid({ type, items, loading, onItemClick }: ContentGridPr
       if (type === 'live') {
         const streamUrl = api.buildLiveStreamUrl(item.id, 'm3u8');
         try {
-          await invoke('open_video_window', {
+          const label = await invoke<string | null>('open_video_window', {
             title: item.name,
             streamUrl,
+            server: api.getServer(),
+            reuse,
           });
+          if (serverId && label) {
+            // Tells the backend which channel the window shows, for history and zapping
+            invoke('start_watching', {
+              label,
+              watch: {
+                serverId,
+                kind: 'live',
+                title: item.name,
+                channelId: String(item.id),
+                channelName: item.name,
+              },
+            }).catch(() => {});
+          }
           if (serverId) {
             addToWatchHistory(serverId, {
               contentType: 'live',
@@ -59,6 +75,9 @@ export function ContentGrid({ type, items, loading, onItemClick }: ContentGridPr
           await invoke('open_video_window', {
             title: item.name,
             streamUrl,
+            contentKey: serverId ? `${serverId}:movie:${item.id}` : undefined,
+            server: api.getServer(),
+            reuse,
           });
           if (serverId) {
             addToWatchHistory(serverId, {
@@ -174,6 +193,18 @@ export function ContentGrid({ type, items, loading, onItemClick }: ContentGridPr
               style={{ left: contextMenu.x, top: contextMenu.y }}
               onClick={(e) => e.stopPropagation()}
             >
+              <button
+                onClick={() => {
+                  handleItemClick(contextMenu!.item, false);
+                  setContextMenu(null);
+                }}
+                className="w-full px-4 py-2.5 text-left text-sm text-gray-300 hover:bg-gray-700/50 flex items-center gap-3 transition-colors"
+              >
+                <svg className="w-4 h-4 text-emerald-400" fill="none" stroke="currentColor" viewBox="0 0 24 24">
+                  <path strokeLinecap="round" strokeLinejoin="round" strokeWidth={2} d="M12 4v16m8-8H4" />
+                </svg>
+                Play in New Window
+              </button>
               <button
                 onClick={() => {
                   navigate(`/player/${type}/${contextMenu!.item.id}`);
@@ -264,18 +295,32 @@ export function ContentGrid({ type, items, loading, onItemClick }: ContentGridPr
             onClick={(e) => e.stopPropagation()}
           >
             {type === 'movie' && (
-              <button
-                onClick={() => {
-                  navigate(`/player/${type}/${contextMenu!.item.id}`);
-                  setContextMenu(null);
-                }}
-                className="w-full px-4 py-2.5 text-left text-sm text-gray-300 hover:bg-gray-700/50 flex items-center gap-3 transition-colors"
-              >
-                <svg className="w-4 h-4 text-emerald-400" fill="none" stroke="currentColor" viewBox="0 0 24 24">
-                  <path strokeLinecap="round" strokeLinejoin="round" strokeWidth={2} d="M10 6H6a2 2 0 00-2 2v10a2 2 0 002 2h10a2 2 0 002-2v-4M14 4h6m0 0v6m0-6L10 14" />
-                </svg>
-                Additional Players
-              </button>
+              <>
+                <button
+                  onClick={() => {
+                    handleItemClick(contextMenu!.item, false);
+                    setContextMenu(null);
+                  }}
+                  className="w-full px-4 py-2.5 text-left text-sm text-gray-300 hover:bg-gray-700/50 flex items-center gap-3 transition-colors"
+                >
+                  <svg className="w-4 h-4 text-emerald-400" fill="none" stroke="currentColor" viewBox="0 0 24 24">
+                    <path strokeLinecap="round" strokeLinejoin="round" strokeWidth={2} d="M12 4v16m8-8H4" />
+                  </svg>
+                  Play in New Window
+                </button>
+                <button
+                  onClick={() => {
+                    navigate(`/player/${type}/${contextMenu!.item.id}`);
+                    setContextMenu(null);
+                  }}
+                  className="w-full px-4 py-2.5 text-left text-sm text-gray-300 hover:bg-gray-700/50 flex items-center gap-3 transition-colors"
+                >
+                  <svg className="w-4 h-4 text-emerald-400" fill="none" stroke="currentColor" viewBox="0 0 24 24">
+                    <path strokeLinecap="round" strokeLinejoin="round" strokeWidth={2} d="M10 6H6a2 2 0 00-2 2v10a2 2 0 002 2h10a2 2 0 002-2v-4M14 4h6m0 0v6m0-6L10 14" />
+                  </svg>
+                  Additional Players
+                </button>
+              </>
             )}
             <button
               onClick={() => handleCopyUrl(contextMenu.item)}
@@ -1,7 +1,7 @@
 import { useEffect } from 'react';
 import { Outlet, useNavigate, useLocation } from 'react-router-dom';
 import { useAppStore } from '../store';
-import { useLoadInitialCategories } from '../hooks';
+import { useGamepad, useLoadInitialCategories } from '../hooks';
 import type { ContentType } from '../types';
 import type { ReactNode } from 'react';
 
@@ -10,6 +10,7 @@ export function Layout() {
   const location = useLocation();
   const { currentServer, activeTab, setActiveTab, isConnected } = useAppStore();
   const { loading: loadingCategories, progressMessage, progressPercent, runInitialLoad } = useLoadInitialCategories();
+  useGamepad();
 
   useEffect(() => {
     if (currentServer?.id) runInitialLoad();
@@ -0,0 +1,92 @@
+import { useEffect, useState } from 'react';
+import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
+
+interface LockStatus {
+  enabled: boolean;
+  locked: boolean;
+  idleMinutes: number;
+  lockOnLaunch: boolean;
+  retryIn?: number;
+}
+
+/** How often input is reported to the backend's idle timer. */
+const ACTIVITY_THROTTLE_MS = 30_000;
+
+/** Covers the whole window while the app is locked and asks for the PIN. */
+export function LockScreen() {
+  const [locked, setLocked] = useState(false);
+  const [pin, setPin] = useState('');
+  const [error, setError] = useState<string | null>(null);
+
+  useEffect(() => {
+    invoke<LockStatus>('get_lock_status')
+      .then((status) => setLocked(status.locked))
+      .catch(() => {});
+    const unlistenLocked = listen('app-locked', () => setLocked(true));
+    const unlistenUnlocked = listen('app-unlocked', () => {
+      setLocked(false);
+      setPin('');
+      setError(null);
+    });
+    return () => {
+      unlistenLocked.then((f) => f());
+      unlistenUnlocked.then((f) => f());
+    };
+  }, []);
+
+  useEffect(() => {
+    let last = 0;
+    const onActivity = () => {
+      const now = Date.now();
+      if (now - last >= ACTIVITY_THROTTLE_MS) {
+        last = now;
+        invoke('report_activity').catch(() => {});
+      }
+    };
+    const events = ['keydown', 'pointerdown', 'pointermove', 'wheel'];
+    events.forEach((e) => window.addEventListener(e, onActivity, { passive: true }));
+    return () => events.forEach((e) => window.removeEventListener(e, onActivity));
+  }, []);
+
+  if (!locked) {
+    return null;
+  }
+
+  const unlock = async (e: React.FormEvent) => {
+    e.preventDefault();
+    try {
+      await invoke('unlock_app', { pin });
+    } catch (err) {
+      setError(String(err));
+      setPin('');
+    }
+  };
+
+  return (
+    <div className="fixed inset-0 z-[1000] flex items-center justify-center bg-gray-950">
+      <form onSubmit={unlock} className="flex flex-col items-center gap-4 w-64">
+        <svg className="w-12 h-12 text-gray-400" fill="none" stroke="currentColor" viewBox="0 0 24 24">
+          <path strokeLinecap="round" strokeLinejoin="round" strokeWidth={2} d="M12 15v2m-6 4h12a2 2 0 002-2v-6a2 2 0 00-2-2H6a2 2 0 00-2 2v6a2 2 0 002 2zm10-10V7a4 4 0 00-8 0v4h8z" />
+        </svg>
+        <input
+          type="password"
+          inputMode="numeric"
+          autoFocus
+          value={pin}
+          onChange={(e) => setPin(e.target.value)}
+          placeholder="PIN"
+          className="w-full px-4 py-2 rounded-lg bg-gray-800 text-white text-center tracking-widest focus:outline-none focus:ring-2 focus:ring-blue-500"
+        />
+        {error && <p className="text-sm text-red-400 text-center">{error}</p>}
+        <button
+          type="submit"
+          disabled={!pin}
+          className="w-full px-4 py-2 rounded-lg bg-blue-600 text-white hover:bg-blue-500 disabled:opacity-50"
+        >
+          Unlock
+        </button>
+      </form>
+    </div>
+  );
+}
@@ -2,3 +2,4 @@ export * from './Layout';
 export * from './Toast';
 export * from './CategoryBrowser';
 export * from './ContentGrid';
+export * from './LockScreen';
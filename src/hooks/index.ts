@@ -1,3 +1,5 @@
 export * from './useKeyboard';
 export * from './useToast';
 export * from './useApi';
+export * from './useGamepad';
+export * from './useShortcuts';
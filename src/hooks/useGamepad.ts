@@ -0,0 +1,83 @@
+import { useEffect } from 'react';
+import { getCurrentWebviewWindow } from '@tauri-apps/api/webviewWindow';
+
+export type GamepadAction =
+  | 'up'
+  | 'down'
+  | 'left'
+  | 'right'
+  | 'select'
+  | 'back'
+  | 'menu'
+  | 'playPause'
+  | 'info'
+  | 'channelUp'
+  | 'channelDown'
+  | 'seekBack'
+  | 'seekForward';
+
+const FOCUSABLE =
+  'a[href], button:not([disabled]), input:not([disabled]), select:not([disabled]), textarea:not([disabled]), [tabindex]:not([tabindex="-1"])';
+
+/** Moves focus to the nearest visible focusable element in `direction`. */
+function moveFocus(direction: 'up' | 'down' | 'left' | 'right') {
+  const current = document.activeElement as HTMLElement | null;
+  const candidates = Array.from(document.querySelectorAll<HTMLElement>(FOCUSABLE)).filter(
+    (el) => el !== current && el.offsetParent !== null
+  );
+  if (!current || current === document.body) {
+    candidates[0]?.focus();
+    return;
+  }
+  const from = current.getBoundingClientRect();
+  const fromX = from.left + from.width / 2;
+  const fromY = from.top + from.height / 2;
+  let best: HTMLElement | null = null;
+  let bestScore = Infinity;
+  for (const el of candidates) {
+    const rect = el.getBoundingClientRect();
+    const dx = rect.left + rect.width / 2 - fromX;
+    const dy = rect.top + rect.height / 2 - fromY;
+    const [along, across] =
+      direction === 'up' ? [-dy, dx] : direction === 'down' ? [dy, dx] : direction === 'left' ? [-dx, dy] : [dx, dy];
+    if (along <= 0) continue;
+    // Prefer elements in line with the current one over closer ones off to the side
+    const score = along + Math.abs(across) * 2;
+    if (score < bestScore) {
+      bestScore = score;
+      best = el;
+    }
+  }
+  best?.focus();
+  best?.scrollIntoView({ block: 'nearest', inline: 'nearest' });
+}
+
+/**
+ * Drives the page from a game controller: directions move focus, select clicks the focused
+ * element and back goes back. Other actions go to `onAction`.
+ */
+export function useGamepad(onAction?: (action: GamepadAction) => void) {
+  useEffect(() => {
+    const unlisten = getCurrentWebviewWindow().listen<GamepadAction>('gamepad-input', ({ payload }) => {
+      switch (payload) {
+        case 'up':
+        case 'down':
+        case 'left':
+        case 'right':
+          moveFocus(payload);
+          break;
+        case 'select':
+          (document.activeElement as HTMLElement | null)?.click();
+          break;
+        case 'back':
+          window.history.back();
+          break;
+        default:
+          onAction?.(payload);
+      }
+    });
+    return () => {
+      unlisten.then((f) => f());
+    };
+  }, [onAction]);
+}
@@ -21,7 +21,8 @@ export function useKeyboard(bindings: KeyBindings, enabled: boolean = true) {
       if (event.ctrlKey || event.metaKey) parts.push('ctrl');
       if (event.altKey) parts.push('alt');
       if (event.shiftKey) parts.push('shift');
-      parts.push(event.key.toLowerCase());
+      const key = event.key === ' ' ? 'space' : event.key.toLowerCase();
+      parts.push(key);
 
       const keyString = parts.join('+');
 
@@ -33,14 +34,14 @@ export function useKeyboard(bindings: KeyBindings, enabled: boolean = true) {
       }
 
       // Check for key without modifiers
-      if (!event.ctrlKey && !event.altKey && !event.shiftKey && bindings[event.key.toLowerCase()]) {
+      if (!event.ctrlKey && !event.altKey && !event.shiftKey && bindings[key]) {
         // Don't trigger if typing in an input
         const target = event.target as HTMLElement;
         if (target.tagName === 'INPUT' || target.tagName === 'TEXTAREA' || target.isContentEditable) {
           return;
         }
         event.preventDefault();
-        bindings[event.key.toLowerCase()](event);
+        bindings[key](event);
       }
     },
     [bindings, enabled]
@@ -0,0 +1,59 @@
+import { useEffect, useMemo, useState } from 'react';
+import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
+import { useKeyboard } from './useKeyboard';
+
+export type ShortcutAction =
+  | 'playPause'
+  | 'mute'
+  | 'fullscreen'
+  | 'seekBack'
+  | 'seekForward'
+  | 'zapNext'
+  | 'zapPrevious'
+  | 'zapBack'
+  | 'record'
+  | 'screenshot'
+  | 'pictureInPicture'
+  | 'toggleSubtitles';
+
+export interface Shortcut {
+  action: ShortcutAction;
+  keys: string[];
+  defaultKeys: string[];
+}
+
+/** The shortcut map from the backend, kept current when it is remapped in any window. */
+export function useShortcutMap() {
+  const [shortcuts, setShortcuts] = useState<Shortcut[]>([]);
+
+  useEffect(() => {
+    invoke<Shortcut[]>('get_shortcuts').then(setShortcuts).catch(() => {});
+    const unlisten = listen<Shortcut[]>('shortcuts-changed', ({ payload }) => setShortcuts(payload));
+    return () => {
+      unlisten.then((f) => f());
+    };
+  }, []);
+
+  return shortcuts;
+}
+
+/**
+ * Runs `handlers` for the keys the shortcut map binds to their actions. Actions without a
+ * handler are ignored.
+ */
+export function useShortcuts(
+  handlers: Partial<Record<ShortcutAction, () => void>>,
+  enabled: boolean = true
+) {
+  const shortcuts = useShortcutMap();
+  const bindings = useMemo(() => {
+    const map: Record<string, () => void> = {};
+    for (const { action, keys } of shortcuts) {
+      const handler = handlers[action];
+      if (handler) keys.forEach((key) => (map[key] = handler));
+    }
+    return map;
+  }, [shortcuts, handlers]);
+  useKeyboard(bindings, enabled);
+}
@@ -77,7 +77,22 @@ export function DetailPage() {
       return;
     }
     try {
-      await invoke('open_video_window', { title, streamUrl });
+      const contentId = contentType === 'movie' ? itemId : episodeId;
+      const label = await invoke<string | null>('open_video_window', {
+        title,
+        streamUrl,
+        contentKey: serverId ? `${serverId}:${contentType}:${contentId}` : undefined,
+        server: api.getServer(),
+      });
+      if (serverId && episodeId && label) {
+        // Lets the backend prefetch and offer the next episode in the same window
+        invoke('track_series_playback', {
+          windowLabel: label,
+          serverId,
+          seriesId: String(itemId),
+          episodeId: String(episodeId),
+        }).catch(() => {});
+      }
       if (serverId) {
         addToWatchHistory(serverId, {
           contentType,
@@ -71,6 +71,8 @@ export function PlayerPage() {
       await invoke('open_video_window', {
         title: contentInfo?.name || 'Stream',
         streamUrl,
+        server: currentServer ?? null,
+        reuse: false,
       });
     } catch (err) {
       console.error('Failed to open video window:', err);
@@ -1,16 +1,692 @@
-import { useEffect, useRef, useState } from 'react';
+import { useCallback, useEffect, useMemo, useRef, useState } from 'react';
 import { useSearchParams } from 'react-router-dom';
 import Hls from 'hls.js';
+import { invoke } from '@tauri-apps/api/core';
+import { getCurrentWebviewWindow } from '@tauri-apps/api/webviewWindow';
+import { useGamepad, useShortcuts, type GamepadAction } from '../hooks';
+
+interface SubtitleTrack {
+  url: string;
+  path: string;
+  label: string;
+  language: string | null;
+}
+
+type PlayerCommand =
+  | { action: 'play' | 'pause' | 'togglePause' }
+  | { action: 'seek'; position: number }
+  | { action: 'seekBy'; seconds: number }
+  | { action: 'volume'; level: number }
+  | { action: 'mute'; muted: boolean }
+  | { action: 'load'; url: string; contentKey?: string | null };
+
+/** Sent by the backend when zapping to another channel. */
+interface ChangeStream {
+  serverId: string;
+  channelId: string;
+  name: string;
+  streamUrl: string;
+}
+
+/** Sent every second while the stream is relayed by the backend proxy. */
+interface StreamStats {
+  bytes: number;
+  throughputBps: number;
+  bitrateBps: number;
+  stalls: number;
+  stalledSecs: number;
+  continuityErrors: number;
+}
+
+/** Song a radio station reports in its ICY metadata. */
+interface NowPlaying {
+  label: string;
+  station: string | null;
+  artist: string | null;
+  title: string;
+}
+
+/** Sent by the backend near the end of an episode registered with `track_series_playback`. */
+interface NextEpisode {
+  episodeId: string;
+  title: string;
+  season: number | null;
+  episode: number | null;
+  streamUrl: string;
+  contentKey: string;
+  countdownSecs: number;
+}
+
+/** The live buffer of a timeshift window, from `get_timeshift_status`. */
+interface TimeshiftStatus {
+  capacitySecs: number;
+  bufferedSecs: number;
+  bytes: number;
+}
+
+function clock(secs: number) {
+  const s = Math.max(0, Math.floor(secs));
+  return `${Math.floor(s / 60)}:${String(s % 60).padStart(2, '0')}`;
+}
+
+/** An instant recording, from `record_current`. */
+interface ActiveRecording {
+  label: string;
+  windowLabel: string;
+  title: string;
+}
+
+/** Why a stream failed, from `diagnose_stream`. */
+interface StreamFailure {
+  code:
+    | 'auth_expired'
+    | 'max_connections'
+    | 'geo_blocked'
+    | 'dead_link'
+    | 'server_error'
+    | 'unreachable'
+    | 'unknown';
+  status: number | null;
+  message: string;
+  maxConnections: number | null;
+  activeConnections: number | null;
+}
+
+const mbps = (bps: number) => (bps / 1_000_000).toFixed(2) + ' Mb/s';
+
+/** Diagnostics panel; stays empty for streams that don't go through the proxy. */
+function StreamStatsOverlay() {
+  const [stats, setStats] = useState<StreamStats | null>(null);
+
+  useEffect(() => {
+    const unlisten = getCurrentWebviewWindow().listen<StreamStats>('stream-stats', ({ payload }) =>
+      setStats(payload)
+    );
+    return () => {
+      unlisten.then((f) => f());
+    };
+  }, []);
+
+  return (
+    <div className="absolute top-10 right-2 px-3 py-2 bg-black/70 text-white text-xs rounded tabular-nums pointer-events-none">
+      {stats ? (
+        <dl className="grid grid-cols-2 gap-x-3">
+          <dt>Throughput</dt>
+          <dd>{mbps(stats.throughputBps)}</dd>
+          <dt>Bitrate</dt>
+          <dd>{mbps(stats.bitrateBps)}</dd>
+          <dt>Received</dt>
+          <dd>{(stats.bytes / 1_048_576).toFixed(1)} MB</dd>
+          <dt>Stalls</dt>
+          <dd>
+            {stats.stalls} ({stats.stalledSecs.toFixed(1)}s)
+          </dd>
+          <dt>TS errors</dt>
+          <dd>{stats.continuityErrors}</dd>
+        </dl>
+      ) : (
+        'No statistics for this stream'
+      )}
+    </div>
+  );
+}
+
+/** Counts down to the next episode and plays it in this window, unless cancelled. */
+function NextEpisodeCountdown() {
+  const [label] = useState(() => getCurrentWebviewWindow().label);
+  const [next, setNext] = useState<NextEpisode | null>(null);
+  const [remaining, setRemaining] = useState(0);
+
+  useEffect(() => {
+    const unlisten = getCurrentWebviewWindow().listen<NextEpisode>(
+      'next-episode-ready',
+      ({ payload }) => {
+        setNext(payload);
+        setRemaining(Math.ceil(payload.countdownSecs));
+      }
+    );
+    return () => {
+      unlisten.then((f) => f());
+    };
+  }, []);
+
+  const playNext = useCallback(() => {
+    setNext(null);
+    invoke('play_next_episode', { windowLabel: label }).catch(() => {});
+  }, [label]);
+
+  useEffect(() => {
+    if (!next) return;
+    if (remaining <= 0) {
+      playNext();
+      return;
+    }
+    const timer = window.setTimeout(() => setRemaining((r) => r - 1), 1000);
+    return () => window.clearTimeout(timer);
+  }, [next, remaining, playNext]);
+
+  if (!next) return null;
+  const number =
+    next.season != null && next.episode != null ? `S${next.season} E${next.episode} · ` : '';
+  return (
+    <div className="absolute bottom-16 right-4 flex items-center gap-3 px-3 py-2 bg-black/80 text-white text-sm rounded">
+      <div className="min-w-0">
+        <p className="text-gray-400 text-xs tabular-nums">Next episode in {remaining}s</p>
+        <p className="truncate max-w-xs">
+          {number}
+          {next.title}
+        </p>
+      </div>
+      <button type="button" className="px-2 py-1 bg-white text-black rounded" onClick={playNext}>
+        Play now
+      </button>
+      <button
+        type="button"
+        className="px-2 py-1 bg-white/10 rounded"
+        onClick={() => setNext(null)}
+      >
+        Cancel
+      </button>
+    </div>
+  );
+}
+
+interface MpvPropertyChange {
+  name: string;
+  value: unknown;
+}
+
+/** Feeds a watch-together session; ignored by the backend unless this window takes part. */
+function reportSyncState(label: string, positionSecs: number, paused: boolean, seeked = false) {
+  invoke('sync_report_state', { label, positionSecs, paused, seeked }).catch(() => {});
+}
+
+/** Controls drawn over the native mpv surface; the page itself stays transparent. */
+function MpvControls() {
+  const [label] = useState(() => getCurrentWebviewWindow().label);
+  const [paused, setPaused] = useState(false);
+  const [position, setPosition] = useState(0);
+  const [duration, setDuration] = useState(0);
+  const [buffering, setBuffering] = useState(true);
+
+  useEffect(() => {
+    // Position and pause state as last reported, for watch-together sync
+    const sync = { position: 0, paused: false, at: Date.now() };
+    const unlisten = getCurrentWebviewWindow().listen<MpvPropertyChange>(
+      'mpv-property-change',
+      ({ payload }) => {
+        switch (payload.name) {
+          case 'time-pos': {
+            const next = Number(payload.value) || 0;
+            const expected = sync.paused
+              ? sync.position
+              : sync.position + (Date.now() - sync.at) / 1000;
+            const seeked = Math.abs(next - expected) > 2;
+            sync.position = next;
+            sync.at = Date.now();
+            if (seeked) reportSyncState(label, next, sync.paused, true);
+            setPosition(next);
+            setBuffering(false);
+            break;
+          }
+          case 'duration':
+            setDuration(Number(payload.value) || 0);
+            break;
+          case 'pause':
+            sync.paused = payload.value === true;
+            reportSyncState(label, sync.position, sync.paused);
+            setPaused(payload.value === true);
+            break;
+          case 'paused-for-cache':
+            setBuffering(payload.value === true);
+            break;
+        }
+      }
+    );
+    const timer = window.setInterval(() => {
+      if (!sync.paused) reportSyncState(label, sync.position, false);
+    }, 2000);
+    return () => {
+      window.clearInterval(timer);
+      unlisten.then((f) => f());
+    };
+  }, [label]);
+
+  const format = (secs: number) => {
+    const s = Math.floor(secs);
+    const h = Math.floor(s / 3600);
+    const m = Math.floor((s % 3600) / 60);
+    const pad = (n: number) => String(n).padStart(2, '0');
+    return h > 0 ? `${h}:${pad(m)}:${pad(s % 60)}` : `${m}:${pad(s % 60)}`;
+  };
+
+  return (
+    <div className="min-h-screen flex flex-col justify-end" style={{ background: 'transparent' }}>
+      {buffering && (
+        <div className="absolute inset-0 flex items-center justify-center pointer-events-none">
+          <span className="w-10 h-10 border-2 border-white/30 border-t-white rounded-full animate-spin" />
+        </div>
+      )}
+      <div className="flex items-center gap-3 px-4 py-2 bg-black/60 text-white text-sm">
+        <button
+          type="button"
+          className="px-2 py-1 rounded hover:bg-white/10"
+          onClick={() => invoke('mpv_pause', { label, paused: !paused })}
+        >
+          {paused ? 'Play' : 'Pause'}
+        </button>
+        <span className="tabular-nums">{format(position)}</span>
+        {duration > 0 ? (
+          <input
+            type="range"
+            className="flex-1"
+            min={0}
+            max={duration}
+            step={1}
+            value={position}
+            onChange={(e) => invoke('mpv_seek', { label, seconds: Number(e.target.value) })}
+          />
+        ) : (
+          <span className="flex-1 text-red-400 text-xs uppercase">Live</span>
+        )}
+        {duration > 0 && <span className="tabular-nums">{format(duration)}</span>}
+      </div>
+    </div>
+  );
+}
 
 export function VideoWindowPage() {
-  const [searchParams] = useSearchParams();
+  const [searchParams, setSearchParams] = useSearchParams();
   const url = searchParams.get('url');
+  const engine = searchParams.get('engine');
+  const audioLang = searchParams.get('audioLang');
+  const subLang = searchParams.get('subLang');
+  const contentKey = searchParams.get('contentKey');
+  // Set by the backend when saving data on a metered connection
+  const maxHeight = Number(searchParams.get('maxHeight') ?? 0) || 0;
+  const initialSubDelay = Number(searchParams.get('subDelay') ?? 0) || 0;
+  // Radio stations and other streams without video open in a compact window
+  const audioOnly = searchParams.get('audio') === '1';
+  // Set when the backend keeps a live buffer to pause and rewind in
+  const timeshift = searchParams.has('timeshift');
   const videoRef = useRef<HTMLVideoElement>(null);
   const hlsRef = useRef<Hls | null>(null);
 
   const [status, setStatus] = useState<'loading' | 'playing' | 'error'>('loading');
   const [errorMessage, setErrorMessage] = useState<string>('');
   const [showUnmuteHint, setShowUnmuteHint] = useState(true);
+  const [showStats, setShowStats] = useState(false);
+  const [subtitles, setSubtitles] = useState<{ src: string; label: string; lang: string }[]>([]);
+  const [nowPlaying, setNowPlaying] = useState<Omit<NowPlaying, 'label'> | null>(null);
+  const [recording, setRecording] = useState<ActiveRecording | null>(null);
+  const [timeshiftStatus, setTimeshiftStatus] = useState<TimeshiftStatus | null>(null);
+  const [behindLive, setBehindLive] = useState(0);
+
+  useEffect(() => {
+    if (!timeshift) return;
+    const label = getCurrentWebviewWindow().label;
+    const poll = () => {
+      invoke<TimeshiftStatus | null>('get_timeshift_status', { windowLabel: label })
+        .then(setTimeshiftStatus)
+        .catch(() => {});
+      const live = hlsRef.current?.liveSyncPosition;
+      const video = videoRef.current;
+      setBehindLive(live != null && video ? Math.max(0, live - video.currentTime) : 0);
+    };
+    poll();
+    const timer = window.setInterval(poll, 1000);
+    return () => window.clearInterval(timer);
+  }, [timeshift]);
+
+  const jumpToLive = () => {
+    const live = hlsRef.current?.liveSyncPosition;
+    const video = videoRef.current;
+    if (live == null || !video) return;
+    video.currentTime = live;
+    video.play().catch(() => {});
+    setBehindLive(0);
+  };
+
+  useEffect(() => {
+    const label = getCurrentWebviewWindow().label;
+    invoke<ActiveRecording[]>('list_active_recordings')
+      .then((active) => setRecording(active.find((r) => r.windowLabel === label) ?? null))
+      .catch(() => {});
+    const unlisten = getCurrentWebviewWindow().listen<{ status: string; recording: ActiveRecording }>(
+      'recording',
+      ({ payload }) => {
+        if (payload.recording.windowLabel !== label) return;
+        setRecording(payload.status === 'started' ? payload.recording : null);
+      }
+    );
+    return () => {
+      unlisten.then((f) => f());
+    };
+  }, []);
+
+  // Playback buttons are handled by the backend; info toggles the stats overlay
+  useGamepad(
+    useCallback((action: GamepadAction) => {
+      if (action === 'info') setShowStats((s) => !s);
+    }, [])
+  );
+
+  useEffect(() => {
+    if (!audioOnly) return;
+    const label = getCurrentWebviewWindow().label;
+    invoke<Omit<NowPlaying, 'label'> | null>('get_now_playing', { label })
+      .then(setNowPlaying)
+      .catch(() => {});
+    const unlisten = getCurrentWebviewWindow().listen<NowPlaying>('now-playing', ({ payload }) => {
+      if (payload.label === label) setNowPlaying(payload);
+    });
+    return () => {
+      unlisten.then((f) => f());
+    };
+  }, [audioOnly]);
+
+  useEffect(() => {
+    // Fetched into blob URLs: a cross-origin <track> would require CORS on the video itself
+    const unlisten = getCurrentWebviewWindow().listen<SubtitleTrack>(
+      'subtitle-added',
+      async ({ payload }) => {
+        const blob = await fetch(payload.url).then((r) => r.blob());
+        const src = URL.createObjectURL(blob);
+        setSubtitles((prev) => [
+          ...prev,
+          { src, label: payload.label, lang: payload.language ?? '' },
+        ]);
+      }
+    );
+    return () => {
+      unlisten.then((f) => f());
+    };
+  }, []);
+
+  useEffect(() => {
+    // Remote control and shortcuts for the HTML5 player (mpv windows are driven natively)
+    const unlisten = getCurrentWebviewWindow().listen<PlayerCommand>(
+      'player-command',
+      ({ payload }) => {
+        const video = videoRef.current;
+        if (!video) return;
+        switch (payload.action) {
+          case 'play':
+            video.play().catch(() => {});
+            break;
+          case 'pause':
+            video.pause();
+            break;
+          case 'togglePause':
+            if (video.paused) video.play().catch(() => {});
+            else video.pause();
+            break;
+          case 'seek':
+            video.currentTime = payload.position;
+            break;
+          case 'seekBy':
+            video.currentTime = Math.max(0, video.currentTime + payload.seconds);
+            break;
+          case 'volume':
+            video.volume = Math.min(1, Math.max(0, payload.level / 100));
+            video.muted = false;
+            break;
+          case 'mute':
+            video.muted = payload.muted;
+            break;
+          case 'load':
+            // Swapping the URL re-runs the player setup below, e.g. for the next episode
+            setSearchParams((params) => {
+              params.set('url', payload.url);
+              if (payload.contentKey) params.set('contentKey', payload.contentKey);
+              else params.delete('contentKey');
+              return params;
+            });
+            break;
+        }
+      }
+    );
+    return () => {
+      unlisten.then((f) => f());
+    };
+  }, [setSearchParams]);
+
+  useShortcuts(
+    useMemo(() => {
+      const label = getCurrentWebviewWindow().label;
+      const video = () => videoRef.current;
+      const zap = (direction: 'next' | 'previous') =>
+        invoke('zap', { windowLabel: label, direction }).catch(() => {});
+      return {
+        playPause: () => {
+          const v = video();
+          if (!v) return;
+          if (v.paused) v.play().catch(() => {});
+          else v.pause();
+        },
+        mute: () => {
+          const v = video();
+          if (v) v.muted = !v.muted;
+        },
+        seekBack: () => {
+          const v = video();
+          if (v) v.currentTime = Math.max(0, v.currentTime - 10);
+        },
+        seekForward: () => {
+          const v = video();
+          if (v) v.currentTime += 10;
+        },
+        fullscreen: () => {
+          const win = getCurrentWebviewWindow();
+          win.isFullscreen().then((full) => win.setFullscreen(!full)).catch(() => {});
+        },
+        zapNext: () => zap('next'),
+        zapPrevious: () => zap('previous'),
+        zapBack: () => invoke('zap_back', { windowLabel: label }).catch(() => {}),
+        record: () => invoke('record_current', { windowLabel: label }).catch(() => {}),
+        screenshot: () => {
+          // Live streams have no position to seek to; ffmpeg takes the frame at the live edge
+          const v = video();
+          const positionSecs = v && Number.isFinite(v.duration) ? v.currentTime : undefined;
+          invoke('capture_screenshot', { windowLabel: label, positionSecs }).catch(() => {});
+        },
+        pictureInPicture: () => {
+          if (document.pictureInPictureElement) document.exitPictureInPicture().catch(() => {});
+          else video()?.requestPictureInPicture().catch(() => {});
+        },
+        toggleSubtitles: () => {
+          const tracks = Array.from(video()?.textTracks ?? []);
+          const showing = tracks.some((t) => t.mode === 'showing');
+          tracks.forEach((t, i) => (t.mode = !showing && i === 0 ? 'showing' : 'disabled'));
+        },
+      };
+    }, [])
+  );
+
+  useEffect(() => {
+    const video = videoRef.current;
+    if (!video) return;
+    const label = getCurrentWebviewWindow().label;
+    const report = (seeked: boolean) => () =>
+      reportSyncState(label, video.currentTime, video.paused, seeked);
+    const onPlayPause = report(false);
+    const onSeeked = report(true);
+    video.addEventListener('play', onPlayPause);
+    video.addEventListener('pause', onPlayPause);
+    video.addEventListener('seeked', onSeeked);
+    const timer = window.setInterval(() => {
+      if (!video.paused) reportSyncState(label, video.currentTime, false);
+    }, 2000);
+    return () => {
+      video.removeEventListener('play', onPlayPause);
+      video.removeEventListener('pause', onPlayPause);
+      video.removeEventListener('seeked', onSeeked);
+      window.clearInterval(timer);
+    };
+  }, []);
+
+  useEffect(() => {
+    // Remembered per live channel by the backend, which ignores other media
+    const video = videoRef.current;
+    if (!video) return;
+    const windowLabel = getCurrentWebviewWindow().label;
+    let timer: number | undefined;
+    const onVolumeChange = () => {
+      window.clearTimeout(timer);
+      if (video.muted) return;
+      // Settled on after dragging the slider, not every step of it
+      timer = window.setTimeout(() => {
+        invoke('report_channel_volume', {
+          windowLabel,
+          level: Math.round(video.volume * 100),
+        }).catch(() => {});
+      }, 1000);
+    };
+    video.addEventListener('volumechange', onVolumeChange);
+    return () => {
+      window.clearTimeout(timer);
+      video.removeEventListener('volumechange', onVolumeChange);
+    };
+  }, []);
+
+  useEffect(() => {
+    const unlisten = getCurrentWebviewWindow().listen<ChangeStream>(
+      'change-stream',
+      ({ payload }) => {
+        setSearchParams((params) => {
+          params.set('url', payload.streamUrl);
+          params.delete('contentKey');
+          return params;
+        });
+      }
+    );
+    return () => {
+      unlisten.then((f) => f());
+    };
+  }, [setSearchParams]);
+
+  useEffect(() => {
+    // After the machine slept the connection is dead; reopen it where playback was
+    const unlisten = getCurrentWebviewWindow().listen('stream-reconnect', () => {
+      const video = videoRef.current;
+      if (!video) return;
+      const position = video.currentTime;
+      const vod = Number.isFinite(video.duration);
+      const hls = hlsRef.current;
+      if (hls) {
+        hls.stopLoad();
+        hls.startLoad(vod ? position : -1);
+      } else {
+        video.load();
+        if (vod) {
+          video.addEventListener('loadedmetadata', () => (video.currentTime = position), {
+            once: true,
+          });
+        }
+      }
+      video.play().catch(() => {});
+    });
+    return () => {
+      unlisten.then((f) => f());
+    };
+  }, []);
+
+  useEffect(() => {
+    // Waits after playback first started count as rebuffers in the provider quality report
+    const video = videoRef.current;
+    if (!video) return;
+    const label = getCurrentWebviewWindow().label;
+    let started = false;
+    let waitingSince: number | null = null;
+    const onWaiting = () => {
+      if (started && waitingSince === null) waitingSince = Date.now();
+    };
+    const onPlaying = () => {
+      if (waitingSince !== null) {
+        const durationSecs = (Date.now() - waitingSince) / 1000;
+        invoke('report_rebuffer', { windowLabel: label, durationSecs }).catch(() => {});
+      }
+      started = true;
+      waitingSince = null;
+    };
+    video.addEventListener('waiting', onWaiting);
+    video.addEventListener('playing', onPlaying);
+    return () => {
+      video.removeEventListener('waiting', onWaiting);
+      video.removeEventListener('playing', onPlaying);
+    };
+  }, []);
+
+  useEffect(() => {
+    // Ignored by the backend unless this window is playing the queue
+    const video = videoRef.current;
+    if (!video) return;
+    const label = getCurrentWebviewWindow().label;
+    const onEnded = () => {
+      invoke('queue_next', { windowLabel: label }).catch(() => {});
+    };
+    video.addEventListener('ended', onEnded);
+    return () => video.removeEventListener('ended', onEnded);
+  }, []);
+
+  useEffect(() => {
+    // Show the most recently added track
+    const tracks = videoRef.current?.textTracks;
+    if (!tracks || tracks.length === 0) return;
+    for (let i = 0; i < tracks.length; i++) {
+      tracks[i].mode = i === tracks.length - 1 ? 'showing' : 'disabled';
+    }
+  }, [subtitles]);
+
+  // Cue times are shifted in place, so track how far they have been moved already
+  const [subDelay, setSubDelay] = useState(initialSubDelay);
+  const appliedDelay = useRef(new Map<TextTrack, number>());
+
+  useEffect(() => {
+    const tracks = videoRef.current?.textTracks;
+    if (!tracks) return;
+    const shift = () => {
+      for (let i = 0; i < tracks.length; i++) {
+        const track = tracks[i];
+        const cues = track.cues;
+        if (!cues || cues.length === 0) continue;
+        const delta = (subDelay - (appliedDelay.current.get(track) ?? 0)) / 1000;
+        if (delta === 0) continue;
+        for (let j = 0; j < cues.length; j++) {
+          cues[j].startTime = Math.max(0, cues[j].startTime + delta);
+          cues[j].endTime = Math.max(0, cues[j].endTime + delta);
+        }
+        appliedDelay.current.set(track, subDelay);
+      }
+    };
+    shift();
+    // Cues of newly added tracks only exist once they have loaded
+    const video = videoRef.current;
+    video?.addEventListener('loadeddata', shift, true);
+    const timer = window.setInterval(shift, 1000);
+    return () => {
+      video?.removeEventListener('loadeddata', shift, true);
+      window.clearInterval(timer);
+    };
+  }, [subDelay, subtitles]);
+
+  const adjustSubDelay = (deltaMs: number) => {
+    const next = subDelay + deltaMs;
+    setSubDelay(next);
+    if (contentKey) {
+      invoke('set_item_subtitle_delay', { contentKey, delayMs: next }).catch(() => {});
+    }
+  };
+
+  const loadSubtitles = async () => {
+    const path = await invoke<string | null>('pick_subtitle_file');
+    if (!path) return;
+    try {
+      await invoke('load_subtitle_file', { path, label: getCurrentWebviewWindow().label });
+    } catch (err) {
+      setErrorMessage(String(err));
+    }
+  };
 
   const isHls = url != null && (url.includes('m3u8') || url.endsWith('.m3u8'));
 
@@ -20,10 +696,22 @@ export function VideoWindowPage() {
     const video = videoRef.current;
 
     const onPlaying = () => setStatus('playing');
+    // Replaces the player's generic message with what the backend finds out about the failure
+    const diagnose = () => {
+      invoke<StreamFailure | null>('diagnose_stream', {
+        url,
+        windowLabel: getCurrentWebviewWindow().label,
+      })
+        .then((failure) => {
+          if (failure) setErrorMessage(failure.message);
+        })
+        .catch(() => {});
+    };
     const onError = (e: Event) => {
       const msg = video.error?.message ?? (e instanceof ErrorEvent ? e.message : 'Playback failed');
       setStatus('error');
       setErrorMessage(String(msg));
+      diagnose();
     };
 
     if (isHls && Hls.isSupported()) {
@@ -33,12 +721,39 @@ export function VideoWindowPage() {
       });
       hlsRef.current = hls;
 
-      hls.on(Hls.Events.MANIFEST_PARSED, () => setStatus('loading'));
+      // Track languages come from ffprobe (e.g. "eng") while manifests often use "en"
+      const sameLang = (a: string | undefined, b: string) =>
+        !!a && a.toLowerCase().slice(0, 2) === b.toLowerCase().slice(0, 2);
+      hls.on(Hls.Events.MANIFEST_PARSED, () => {
+        setStatus('loading');
+        if (maxHeight > 0) {
+          const allowed = hls.levels.map((l, i) => (l.height <= maxHeight ? i : -1));
+          const cap = Math.max(...allowed);
+          if (cap >= 0) hls.autoLevelCapping = cap;
+        }
+        if (audioLang) {
+          const idx = hls.audioTracks.findIndex((t) => sameLang(t.lang, audioLang));
+          if (idx >= 0) hls.audioTrack = idx;
+        }
+        if (subLang) {
+          const idx = hls.subtitleTracks.findIndex((t) => sameLang(t.lang, subLang));
+          if (idx >= 0) hls.subtitleTrack = idx;
+        }
+      });
+      hls.on(Hls.Events.AUDIO_TRACK_SWITCHED, (_, data) => {
+        const lang = hls.audioTracks[data.id]?.lang;
+        if (contentKey && lang && !sameLang(lang, audioLang ?? '')) {
+          invoke('set_item_audio_track', { contentKey, index: null, language: lang }).catch(
+            () => {}
+          );
+        }
+      });
       hls.on(Hls.Events.FRAG_BUFFERED, onPlaying);
       hls.on(Hls.Events.ERROR, (_, data) => {
         if (data.fatal) {
           setStatus('error');
           setErrorMessage(data.type + ': ' + (data.details ?? 'Unknown'));
+          if (data.type === Hls.ErrorTypes.NETWORK_ERROR) diagnose();
           hls.destroy();
           hlsRef.current = null;
         }
@@ -81,7 +796,16 @@ export function VideoWindowPage() {
     setStatus('error');
     setErrorMessage('HLS is not supported in this browser.');
     return undefined;
-  }, [url, isHls]);
+  }, [url, isHls, audioLang, subLang, contentKey, maxHeight]);
+
+  if (engine === 'mpv') {
+    return (
+      <>
+        <MpvControls />
+        <NextEpisodeCountdown />
+      </>
+    );
+  }
 
   if (!url) {
     return (
@@ -91,6 +815,31 @@ export function VideoWindowPage() {
     );
   }
 
+  if (audioOnly) {
+    return (
+      <div className="min-h-screen bg-gray-900 flex flex-col justify-center gap-2 px-4">
+        <div className="flex items-center gap-2 text-sm min-w-0">
+          {status === 'loading' && (
+            <span className="w-4 h-4 border-2 border-white/30 border-t-white rounded-full animate-spin" />
+          )}
+          {status === 'error' ? (
+            <span className="text-red-400">{errorMessage || 'Could not play stream'}</span>
+          ) : (
+            <div className="min-w-0">
+              <p className="text-white truncate">{nowPlaying?.title ?? 'Radio'}</p>
+              {(nowPlaying?.artist || nowPlaying?.station) && (
+                <p className="text-gray-400 text-xs truncate">
+                  {[nowPlaying.artist, nowPlaying.station].filter(Boolean).join(' · ')}
+                </p>
+              )}
+            </div>
+          )}
+        </div>
+        <video ref={videoRef} className="w-full h-10" controls autoPlay playsInline />
+      </div>
+    );
+  }
+
   return (
     <div className="min-h-screen bg-black flex flex-col relative">
       <video
@@ -105,7 +854,68 @@ export function VideoWindowPage() {
           if (videoRef.current) videoRef.current.muted = false;
         }}
         onPlay={() => setShowUnmuteHint(false)}
-      />
+      >
+        {subtitles.map((t) => (
+          <track key={t.src} kind="subtitles" src={t.src} label={t.label} srcLang={t.lang} />
+        ))}
+      </video>
+      <div className="absolute top-2 right-2 flex items-center gap-1 text-white text-xs opacity-60 hover:opacity-100">
+        {subtitles.length > 0 && (
+          <>
+            <button
+              type="button"
+              className="px-2 py-1 bg-black/60 rounded"
+              onClick={() => adjustSubDelay(-250)}
+            >
+              −
+            </button>
+            <span className="px-1 py-1 bg-black/60 rounded tabular-nums">
+              {(subDelay / 1000).toFixed(2)}s
+            </span>
+            <button
+              type="button"
+              className="px-2 py-1 bg-black/60 rounded"
+              onClick={() => adjustSubDelay(250)}
+            >
+              +
+            </button>
+          </>
+        )}
+        <button type="button" className="px-2 py-1 bg-black/60 rounded" onClick={loadSubtitles}>
+          Subtitles…
+        </button>
+        <button
+          type="button"
+          className="px-2 py-1 bg-black/60 rounded"
+          onClick={() => setShowStats((s) => !s)}
+        >
+          Stats
+        </button>
+      </div>
+      {recording && (
+        <div
+          className="absolute top-2 left-2 flex items-center gap-1.5 px-2 py-1 bg-black/60 text-white text-xs rounded"
+          title={recording.title}
+        >
+          <span className="w-2 h-2 rounded-full bg-red-500 animate-pulse" />
+          REC
+        </div>
+      )}
+      {timeshift && (behindLive > 5 || videoRef.current?.paused) && (
+        <div className="absolute bottom-14 right-2 flex items-center gap-2 px-2 py-1 bg-black/70 text-white text-xs rounded tabular-nums">
+          <span>−{clock(behindLive)}</span>
+          {timeshiftStatus && (
+            <span className="text-gray-400">
+              buffer {clock(timeshiftStatus.bufferedSecs)} / {clock(timeshiftStatus.capacitySecs)}
+            </span>
+          )}
+          <button type="button" className="px-2 py-0.5 bg-red-600 rounded" onClick={jumpToLive}>
+            Live
+          </button>
+        </div>
+      )}
+      {showStats && <StreamStatsOverlay />}
+      <NextEpisodeCountdown />
       {showUnmuteHint && status === 'playing' && (
         <div
           className="absolute bottom-14 left-1/2 -translate-x-1/2 px-3 py-1.5 bg-black/70 text-white text-xs rounded pointer-events-none"
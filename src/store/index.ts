@@ -1,5 +1,7 @@
 import { create } from 'zustand';
 import { persist } from 'zustand/middleware';
+import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
 import type {
   ServerConnection,
   Category,
@@ -185,6 +187,11 @@ interface SettingsState {
   clearCache: (serverId: string) => void;
 }
 
+/** Saves a server in the backend too, where zapping, recording and remotes look servers up. */
+function syncServer(server: ServerConnection) {
+  invoke('save_server', { server }).catch((err) => console.error('Failed to save server:', err));
+}
+
 const emptyCache: ContentCache = {
   categories: { live: [], movie: [], series: [] },
   channels: {},
@@ -210,18 +217,26 @@ export const useSettingsStore = create<SettingsState>()(
       customGroups: {},
       contentCache: {},
 
-      addServer: (server) =>
-        set((state) => ({ servers: [...state.servers, server] })),
+      addServer: (server) => {
+        set((state) => ({ servers: [...state.servers, server] }));
+        syncServer(server);
+      },
 
-      updateServer: (server) =>
+      updateServer: (server) => {
         set((state) => ({
           servers: state.servers.map((s) => (s.id === server.id ? server : s)),
-        })),
+        }));
+        syncServer(server);
+      },
 
-      removeServer: (id) =>
+      removeServer: (id) => {
         set((state) => ({
           servers: state.servers.filter((s) => s.id !== id),
-        })),
+        }));
+        invoke('remove_server', { serverId: id }).catch((err) =>
+          console.error('Failed to remove server:', err)
+        );
+      },
 
       setPreferences: (prefs) =>
         set((state) => ({
@@ -469,10 +484,21 @@ export const useSettingsStore = create<SettingsState>()(
     }),
     {
       name: 'tvx-settings',
+      // Servers saved before the backend kept its own copy reach it on the next start
+      onRehydrateStorage: () => (state) => {
+        state?.servers.forEach(syncServer);
+      },
     }
   )
 );
 
+// A restored backup merges its servers into the backend's list, which becomes the saved one
+listen('backup-imported', () => {
+  invoke<ServerConnection[]>('list_servers')
+    .then((servers) => useSettingsStore.setState({ servers }))
+    .catch((err) => console.error('Failed to reload servers:', err));
+});
+
 // Non-persisted refresh state store (survives navigation within the app)
 export interface RefreshStats {
   liveCategories: number;
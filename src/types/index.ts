@@ -6,6 +6,58 @@ export interface ServerConnection {
   username: string;
   password: string;
   lastConnected: number | null;
+  /** Outbound proxy for this server; unset uses the global one from Settings. */
+  proxy?: ProxyConfig | null;
+  /** Resolver for this server's host; unset uses the global one from Settings. */
+  dns?: DnsConfig | null;
+  /** Certificate verification override for self-signed servers. */
+  tls?: TlsConfig | null;
+  /** Concurrent catalog/EPG fetches to this server; unset uses the default of 4. */
+  maxConnections?: number | null;
+  /** Shift applied to this server's guide times, for providers whose EPG is consistently off. */
+  epgOffsetMinutes?: number | null;
+  /** IANA zone the provider's catch-up times are in, overriding what the server declares. */
+  timezone?: string | null;
+  /** Recordings this server can run at once; falls back to maxConnections. */
+  maxRecordings?: number | null;
+  /** udpxy gateway multicast channels are played through, e.g. `http://192.168.1.1:4022`. */
+  udpxyUrl?: string | null;
+  /** Request rate this server's panel tolerates; unset uses the global per-host limit. */
+  rateLimit?: RateLimit | null;
+}
+
+export interface ProxyConfig {
+  kind: 'direct' | 'http' | 'socks5';
+  host: string;
+  port: number;
+  username?: string | null;
+  password?: string | null;
+}
+
+export interface DnsConfig {
+  kind: 'system' | 'udp' | 'doh';
+  /** `ip` or `ip:port`, tried in order. */
+  servers?: string[];
+  dohUrl?: string | null;
+}
+
+export interface RateLimit {
+  /** Sustained requests per second; below 1 spaces requests more than a second apart. */
+  requestsPerSecond: number;
+  /** Requests that may go out back to back after a quiet spell. */
+  burst?: number;
+}
+
+/**
+ * `pinned` trusts only the certificate fetched with `fetch_server_certificate`; `insecure`
+ * skips verification entirely, so anyone on the network path can impersonate the server.
+ */
+export interface TlsConfig {
+  kind: 'verify' | 'pinned' | 'insecure';
+  /** Base64 DER of the pinned certificate. */
+  certificate?: string | null;
+  /** SHA-256 fingerprint, e.g. `AB:CD:...`, to show before pinning. */
+  fingerprint?: string | null;
 }
 
 // Content Types